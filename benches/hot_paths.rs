@@ -0,0 +1,195 @@
+//! Criterion benchmarks for the engine's hot paths
+//!
+//! Run with: cargo bench --features localhost --bench hot_paths
+//!
+//! Covers agent trades, cranks over N live accounts, the validation layer
+//! (dry-run trades and market params) and localhost snapshot encode, decode
+//! and restore. Every benchmark id is prefixed with the slab capacity of the
+//! build (`accounts=4096/...`), so baselines saved with
+//! `-- --save-baseline <name>` are kept apart per capacity configuration and
+//! `-- --baseline <name>` compares like with like, e.g.:
+//!
+//!   PERCOLATOR_MAX_ACCOUNTS=1024 cargo bench --features localhost --bench hot_paths -- --save-baseline main
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use percolator::clawcolator::*;
+use percolator::layout::LAYOUT;
+use percolator::localhost::{Command, MemoryStore, ServerState, Snapshot, SnapshotStore};
+use percolator::{Result, RiskParams, U128};
+
+const ORACLE: u64 = 1_000_000;
+
+/// Fills every trade at the oracle price
+#[derive(Clone, Copy)]
+struct PassthroughAgent;
+
+impl OpenClawAgent for PassthroughAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(TradeDecision::Accept {
+            price: context.oracle_price,
+            size: request.size,
+        })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        Ok(LiquidityAllocation {
+            target_active_capital: context.total_capital,
+            reserve_capital: 0,
+            defensive_mode: false,
+        })
+    }
+
+    fn assess_risk(&self, _context: &AgentContext) -> Result<RiskAssessment> {
+        Ok(RiskAssessment {
+            risk_level_bps: 0,
+            actions: RiskActions::default(),
+        })
+    }
+
+    fn detect_anomalies(&self, _context: &AgentContext) -> Result<AnomalyResponse> {
+        Ok(AnomalyResponse {
+            anomaly_type: AnomalyType::Other,
+            severity_bps: 0,
+            actions: AnomalyActions::default(),
+        })
+    }
+
+    fn should_shutdown(&self, _context: &AgentContext) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+fn params() -> RiskParams {
+    RiskParams {
+        warmup_period_slots: 100,
+        maintenance_margin_bps: 500,
+        initial_margin_bps: 1000,
+        trading_fee_bps: 10,
+        max_accounts: LAYOUT.max_accounts as u64,
+        new_account_fee: U128::new(0),
+        risk_reduction_threshold: U128::new(0),
+        maintenance_fee_per_slot: U128::new(0),
+        max_crank_staleness_slots: u64::MAX,
+        liquidation_fee_bps: 50,
+        liquidation_fee_cap: U128::new(100_000),
+        liquidation_buffer_bps: 100,
+        min_liquidation_abs: U128::new(100_000),
+    }
+}
+
+fn oracle(slot: u64) -> OraclePrice {
+    OraclePrice {
+        price: ORACLE,
+        confidence: 0,
+        publish_slot: slot,
+    }
+}
+
+/// LP at index 0 and `users` funded users, each holding a position
+fn engine_with_users(users: usize) -> Box<ClawcolatorEngine> {
+    let mut engine = Box::new(ClawcolatorEngine::new(params()));
+    let risk = engine.risk_engine_mut();
+    let lp = risk.add_lp([0; 32], [0; 32], 0).unwrap();
+    risk.deposit(lp, u64::MAX as u128, 0).unwrap();
+    for i in 0..users {
+        let risk = engine.risk_engine_mut();
+        let user = risk.add_user(0).unwrap();
+        risk.deposit(user, 1_000_000, 0).unwrap();
+        let size = if i % 2 == 0 { 1_000_000 } else { -1_000_000 };
+        engine.execute_trade(&PassthroughAgent, user, oracle(1), size, 1).unwrap();
+    }
+    engine
+}
+
+fn id(name: &str) -> String {
+    format!("accounts={}/{}", LAYOUT.max_accounts, name)
+}
+
+fn bench_execute_trade(c: &mut Criterion) {
+    let mut engine = engine_with_users(1);
+    let mut slot = 1;
+    let mut size = 500_000;
+    c.bench_function(&id("execute_trade"), |b| {
+        b.iter(|| {
+            slot += 1;
+            size = -size;
+            engine.execute_trade(&PassthroughAgent, 1, oracle(slot), black_box(size), slot).unwrap();
+        })
+    });
+}
+
+fn bench_crank(c: &mut Criterion) {
+    let mut group = c.benchmark_group(id("crank"));
+    for users in [16, 256, LAYOUT.max_accounts / 2] {
+        let mut engine = engine_with_users(users.min(LAYOUT.max_accounts - 1));
+        let mut slot = 1;
+        group.bench_function(format!("users={}", users), |b| {
+            b.iter(|| {
+                slot += 1;
+                black_box(engine.crank(0, slot, ORACLE, None).unwrap());
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_validate(c: &mut Criterion) {
+    let engine = engine_with_users(16);
+    let mut group = c.benchmark_group(id("validate"));
+    group.bench_function("trade_dry_run_accept", |b| {
+        b.iter(|| engine.execute_trade_dry_run(&PassthroughAgent, 1, oracle(2), black_box(100_000), 2))
+    });
+    group.bench_function("trade_dry_run_reject", |b| {
+        b.iter(|| engine.execute_trade_dry_run(&PassthroughAgent, 1, oracle(2), black_box(100_000_000), 2))
+    });
+    group.bench_function("market_params", |b| b.iter(|| engine.dry_run_agent(black_box(&PassthroughAgent))));
+    group.finish();
+}
+
+/// Journal of a short session: accounts, trades and cranks
+fn journal() -> Snapshot {
+    let mut commands = vec![Command::AddLp { deposit: 1_000_000_000_000 }];
+    for _ in 0..32 {
+        commands.push(Command::AddUser { deposit: 1_000_000 });
+    }
+    for slot in 1..=64u64 {
+        commands.push(Command::Trade {
+            user_idx: 1 + (slot % 32) as u16,
+            oracle_price: ORACLE,
+            size: if slot % 2 == 0 { 100_000 } else { -100_000 },
+            slot,
+            client_order_id: None,
+            sequence: None,
+            valid_until_slot: None,
+        });
+        commands.push(Command::Crank { caller_idx: 0, oracle_price: ORACLE, slot });
+    }
+    Snapshot { commands }
+}
+
+fn bench_snapshot(c: &mut Criterion) {
+    let snapshot = journal();
+    let text = snapshot.encode();
+    let mut group = c.benchmark_group(id("snapshot"));
+    group.bench_function("encode", |b| b.iter(|| black_box(&snapshot).encode()));
+    group.bench_function("decode", |b| b.iter(|| Snapshot::decode(black_box(&text)).unwrap()));
+    group.bench_function("restore", |b| {
+        b.iter_batched(
+            || {
+                let mut store = MemoryStore::default();
+                store.save(&snapshot).unwrap();
+                (Box::new(ClawcolatorEngine::new(params())), store)
+            },
+            |(engine, store)| ServerState::restore(engine, PassthroughAgent, Box::new(store)).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_execute_trade, bench_crank, bench_validate, bench_snapshot);
+criterion_main!(benches);
@@ -68,6 +68,14 @@ impl OpenClawAgent for SimpleClawAgent {
             funding_rate_bps_per_slot: 0,
             min_margin_bps: 500,
             active_capital_ratio_bps: 8000,
+            tier_limits: DEFAULT_TIER_LIMITS,
+            dust_policy: DEFAULT_DUST_POLICY,
+            withdrawal_policy: DEFAULT_WITHDRAWAL_POLICY,
+            fee_split: DEFAULT_FEE_SPLIT,
+            vamm_depth: 0,
+            max_oi_share_bps: 10_000,
+            warmup_period_slots: None,
+            lending_policy: DEFAULT_LENDING_POLICY,
         })
     }
     
@@ -178,23 +186,24 @@ fn main() {
     
     // Демонстрация принятия решения о сделке
     println!("\n3️⃣ Демонстрация принятия решения о сделке...");
-    let context = AgentContext {
-        current_slot: 1000,
-        oracle_price: 1_000_000,
-        vault: 10_000_000,
-        insurance_balance: 1_000_000,
-        total_capital: 9_000_000,
-        total_positive_pnl: 0,
-        total_open_interest: 0,
-        risk_params: base_params,
-        risk_reduction_mode: false,
-        last_crank_slot: 999,
-    };
+    let context = AgentContext::builder()
+        .current_slot(1000)
+        .oracle_price(1_000_000)
+        .oracle_publish_slot(1000)
+        .vault(10_000_000)
+        .insurance_balance(1_000_000)
+        .total_capital(9_000_000)
+        .risk_params(base_params)
+        .last_crank_slot(999)
+        .build();
     
     let request = TradeRequest {
         user_idx: 0,
         size: 1000,
         requested_price: None,
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: None,
     };
     
     match agent.decide_trade(&context, &request) {
@@ -223,6 +232,9 @@ fn main() {
         user_idx: 0,
         size: 2_000_000, // Превышает max_position_size
         requested_price: None,
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: None,
     };
     
     match agent.decide_trade(&context, &large_request) {
@@ -261,7 +273,9 @@ fn main() {
             println!("      - Уровень риска: {} bps ({}%)", assessment.risk_level_bps, assessment.risk_level_bps / 100);
             println!("      - Действия:");
             println!("        • Снизить экспозицию: {}", assessment.actions.reduce_exposure);
-            println!("        • Хеджировать: {}", assessment.actions.hedge);
+            if let Some(hedge) = assessment.actions.hedge {
+                println!("        • Хеджировать: {} контрактов", hedge);
+            }
             if let Some(margin) = assessment.actions.increase_margin {
                 println!("        • Увеличить маржу до: {} bps ({}%)", margin, margin / 100);
             }
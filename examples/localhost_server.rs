@@ -1,15 +1,39 @@
 //! Clawcolator Localhost HTTP Server
 //!
-//! Запуск: cargo run --features localhost --example localhost_server
+//! Запуск: cargo run --features localhost --example localhost_server [-- --config params.toml] [--state файл] [--keys файл] [--leaderboard слотов]
+//!
+//! Состояние сохраняется в журнал (по умолчанию clawcolator-state.journal)
+//! после каждого изменяющего запроса и восстанавливается при запуске.
+//!
+//! С --keys каждый запрос требует `Authorization: Bearer <ключ>`. Файл ключей
+//! содержит строки `<ключ> read|trade:<user_idx>|admin`.
+//!
+//! POST /instances создаёт независимый рынок со своим агентом и
+//! параметрами; его API доступно под /i/<name>/..., например
+//! /i/team-a/execute.
+//!
+//! Слот новых команд задают часы (GET/POST /clock): вручную (кранками и
+//! advance), в реальном времени (400 мс на слот) или ускоренно, например
+//! 100 слотов в секунду, чтобы быстро проверить фандинг и прогрев.
+//!
+//! С --leaderboard <слотов> сервер ведёт таблицу соревнования (GET
+//! /leaderboard): PnL, объём и максимальная просадка каждого счёта за
+//! последние <слотов> слотов.
 //!
 //! API будет доступен на http://localhost:8080
 
 #![cfg(all(feature = "localhost", feature = "clawcolator"))]
 
-use std::net::SocketAddr;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
 
+use percolator::clawcolator::config::{EngineConfig, MarketConfig};
 use percolator::clawcolator::*;
-use percolator::{RiskParams, U128, Result, MAX_ORACLE_PRICE};
+use percolator::localhost::{
+    decode_hex32, serve_instances, AgentRegistry, ApiKeys, ClockMode, Command, Fault, Fields, FileStore, Instances,
+    MemoryStore, Request, Response, RouteDoc, Router, Scope, ServerError, ServerState,
+};
+use percolator::{Result, RiskError, MAX_ORACLE_PRICE};
 
 // Простой агент для демонстрации
 struct SimpleClawAgent {
@@ -64,6 +88,33 @@ impl OpenClawAgent for SimpleClawAgent {
         Ok(TradeDecision::Accept { price: execution_price, size: request.size })
     }
     
+    // Стратегия 1 (фиксированный спред), версия 1; уверенность падает с размером
+    fn decide_trade_with_meta(
+        &self,
+        context: &AgentContext,
+        request: &TradeRequest,
+    ) -> Result<(TradeDecision, Option<DecisionMeta>)> {
+        let decision = self.decide_trade(context, request)?;
+        let usage_bps = request.size.unsigned_abs().saturating_mul(10_000) / self.max_position_size.max(1);
+        let meta = DecisionMeta {
+            confidence_bps: 10_000u128.saturating_sub(usage_bps) as u16,
+            strategy: 1,
+            model_version: 1,
+            reason_code: matches!(decision, TradeDecision::Accept { .. }) as u16,
+        };
+        Ok((decision, Some(meta)))
+    }
+    
+    // Те же лимиты, что и в decide_trade: размер и плечо к общему капиталу
+    fn quote_capacity(&self, context: &AgentContext, _user_idx: u16, _side: TradeSide) -> Result<u128> {
+        if context.risk_reduction_mode || context.oracle_price == 0 {
+            return Ok(0);
+        }
+        let max_notional = context.total_capital * self.max_leverage_bps as u128 / 10_000;
+        let by_leverage = max_notional * 1_000_000 / context.oracle_price as u128;
+        Ok(self.max_position_size.min(by_leverage))
+    }
+    
     fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
         Ok(MarketParams {
             max_leverage_bps: self.max_leverage_bps,
@@ -72,6 +123,14 @@ impl OpenClawAgent for SimpleClawAgent {
             funding_rate_bps_per_slot: 0,
             min_margin_bps: 500,
             active_capital_ratio_bps: 8000,
+            tier_limits: DEFAULT_TIER_LIMITS,
+            dust_policy: DEFAULT_DUST_POLICY,
+            withdrawal_policy: DEFAULT_WITHDRAWAL_POLICY,
+            fee_split: DEFAULT_FEE_SPLIT,
+            vamm_depth: 0,
+            max_oi_share_bps: 10_000,
+            warmup_period_slots: None,
+            lending_policy: DEFAULT_LENDING_POLICY,
         })
     }
     
@@ -149,217 +208,1304 @@ fn main() {
     println!("{}", "=".repeat(50));
     println!("\n🚀 Запуск сервера на http://localhost:8080\n");
     
+    // Конфигурация: --config <файл.toml|файл.json>, иначе значения по умолчанию
+    let args: Vec<String> = std::env::args().collect();
+    let config = match args.iter().position(|a| a == "--config") {
+        Some(i) => match args.get(i + 1).map(|path| EngineConfig::load(path)) {
+            Some(Ok(config)) => config,
+            Some(Err(e)) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+            None => {
+                eprintln!("❌ --config требует путь к файлу");
+                std::process::exit(1);
+            }
+        },
+        None => EngineConfig {
+            market: MarketConfig {
+                max_position_size: 1_000_000,
+                ..MarketConfig::default()
+            },
+            ..EngineConfig::default()
+        },
+    };
+    
     // Создаем агента
-    let agent = SimpleClawAgent::new(1_000_000, 1000, 10);
+    let agent = SimpleClawAgent::new(
+        config.market.max_position_size,
+        config.market.max_leverage_bps,
+        config.market.spread_bps,
+    );
     
     // Создаем движок
-    let base_params = RiskParams {
-        warmup_period_slots: 100,
-        maintenance_margin_bps: 500,
-        initial_margin_bps: 1000,
-        trading_fee_bps: 10,
-        max_accounts: 1000,
-        new_account_fee: U128::new(0),
-        risk_reduction_threshold: U128::new(0),
-        maintenance_fee_per_slot: U128::new(0),
-        max_crank_staleness_slots: u64::MAX,
-        liquidation_fee_bps: 50,
-        liquidation_fee_cap: U128::new(100_000),
-        liquidation_buffer_bps: 100,
-        min_liquidation_abs: U128::new(100_000),
+    let engine = match config.build_engine() {
+        Ok(engine) => engine,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
     };
     
-    let mut engine = ClawcolatorEngine::new(base_params);
+    // Журнал состояния: --state <файл>, восстанавливается при запуске
+    let state_path = args
+        .iter()
+        .position(|a| a == "--state")
+        .and_then(|i| args.get(i + 1).cloned())
+        .unwrap_or_else(|| "clawcolator-state.journal".to_string());
+    let store = Box::new(FileStore::new(&*state_path));
+    let mut state = match ServerState::restore_with_agents(engine, agent, agents(&config), store) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("❌ Не удалось восстановить {}: {:?}", state_path, e);
+            std::process::exit(1);
+        }
+    };
+    
+    // Соревнование: --leaderboard <окно в слотах>
+    let leaderboard = args.iter().position(|a| a == "--leaderboard").map(|i| {
+        match args.get(i + 1).and_then(|v| v.parse::<u64>().ok()) {
+            Some(window) => window,
+            None => {
+                eprintln!("❌ --leaderboard требует окно в слотах");
+                std::process::exit(1);
+            }
+        }
+    });
+    if let Some(window) = leaderboard {
+        state = state.with_leaderboard(window);
+    }
+    
+    // Именованные экземпляры (POST /instances): свой движок, агент и
+    // параметры, журнал только в памяти
+    let instances = Instances::new(state).with_factory(move |spec| {
+        let mut config = config;
+        let risk = &mut config.risk;
+        risk.initial_margin_bps = spec.initial_margin_bps.unwrap_or(risk.initial_margin_bps);
+        risk.maintenance_margin_bps = spec.maintenance_margin_bps.unwrap_or(risk.maintenance_margin_bps);
+        risk.trading_fee_bps = spec.trading_fee_bps.unwrap_or(risk.trading_fee_bps);
+        risk.warmup_period_slots = spec.warmup_period_slots.unwrap_or(risk.warmup_period_slots);
+        let engine = config.build_engine().map_err(|e| e.to_string())?;
+        let agents = agents(&config);
+        let name = spec.agent.as_deref().unwrap_or("default");
+        let agent = agents.build(name).ok_or_else(|| format!("unknown agent {}", name))?;
+        let store = Box::new(MemoryStore::default());
+        let state = ServerState::restore_with_agents(engine, agent, agents, store)
+            .map_err(|e| format!("{:?}", e))?;
+        Ok(match leaderboard {
+            Some(window) => state.with_leaderboard(window),
+            None => state,
+        })
+    });
+    
+    // Ключи API: --keys <файл>, без него доступ открыт
+    let keys = args.iter().position(|a| a == "--keys").map(|i| {
+        let path = args.get(i + 1).cloned().unwrap_or_default();
+        match std::fs::read_to_string(&path).and_then(|text| ApiKeys::decode(&text)) {
+            Ok(keys) => keys,
+            Err(e) => {
+                eprintln!("❌ Не удалось загрузить ключи {}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    });
     
     println!("✅ Clawcolator Engine инициализирован");
+    println!(
+        "✅ Состояние: {} ({} команд восстановлено)",
+        state_path,
+        instances.default_state().read().unwrap().journal().commands.len()
+    );
+    match keys {
+        Some(_) => println!("✅ Авторизация по ключам API включена"),
+        None => println!("⚠️  Ключи API не заданы (--keys), доступ открыт"),
+    }
     println!("✅ OpenClaw Agent готов\n");
     
     println!("📡 API Endpoints:");
     println!("   GET  /health          - Проверка здоровья сервера");
     println!("   GET  /status          - Статус движка");
-    println!("   POST /trade           - Выполнить сделку");
+    println!("   POST /trade           - Решение агента по сделке (без исполнения)");
+    println!("   POST /accounts/user   - Открыть счёт пользователя {{\"deposit\"}}");
+    println!("   POST /accounts/lp     - Открыть счёт LP {{\"deposit\"}}");
+    println!("   POST /deposit         - Пополнить счёт {{\"idx\", \"amount\"}}");
+    println!("   POST /trade/simulate  - Прогон сделки без исполнения: цена, комиссия, маржа после");
+    println!("   POST /execute         - Исполнить сделку {{\"user_idx\", \"size\", \"oracle_price\", \"client_order_id\"?, \"sequence\"?, \"valid_until_slot\"?}}");
+    println!("   POST /trade/commit    - Заявить sha256(user_idx, size, max_slippage_bps, salt) {{\"user_idx\", \"commitment\"}}");
+    println!("   POST /trade/reveal    - Раскрыть сделку в следующем слоте {{\"user_idx\", \"size\", \"max_slippage_bps\", \"salt\", \"oracle_price\"}}");
+    println!("   POST /batch/order     - Заявка в текущий аукцион (режим пакетных аукционов) {{\"user_idx\", \"size\"}}");
+    println!("   POST /batch/clear     - Провести аукцион по единой цене агента {{\"oracle_price\"}}");
+    println!("   GET  /batch           - Текущий пакет заявок и итог последнего аукциона");
+    println!("   GET  /wind-down       - Ход делистинга: маржа, открытые позиции, цена расчёта");
+    println!("   GET  /alerts          - События для оператора: флаппинг агента, блокировка параметров");
+    println!("   POST /heartbeat       - Heartbeat агента между кранками");
+    println!("   POST /crank           - Кранк в следующем слоте {{\"caller_idx\", \"oracle_price\"}}");
+    println!("   POST /simulate        - Симуляция {{\"model\": \"random_walk\"|\"trend\"|\"crash\", \"traders\", \"steps\", \"seed\", ...}}");
+    println!("   GET  /clock           - Часы слотов");
+    println!("   POST /clock           - {{\"mode\": \"manual\"|\"wall_clock\"|\"accelerated\", \"slots_per_sec\"?, \"advance\"?}} (admin)");
     println!("   GET  /market-params   - Получить параметры рынка");
     println!("   GET  /risk            - Оценка риска");
     println!("   GET  /anomalies       - Проверка аномалий");
+    println!("   GET  /keeper-rewards/<idx> - Награды кипера");
+    println!("   GET  /accounts/<idx>  - Состояние счёта");
+    println!("   GET  /accounts/<idx>/margin - Маржа и цена ликвидации");
+    println!("   GET  /accounts/<idx>/max-size?side=buy - Максимальный размер сделки");
+    println!("   GET  /withdrawals     - Очередь выводов");
+    println!("   GET  /trades?from=&to= - История сделок по слотам");
+    println!("   GET  /candles?interval=10 - OHLC-свечи");
+    println!("   GET  /events?since=0  - Журнал событий");
+    println!("   GET  /epochs          - Отчёты по эпохам");
+    println!("   GET  /governance/proposals - Ожидающие предложения");
+    println!("   GET  /stress?shock_bps=-2000 - Стресс-прогноз страхового фонда");
+    println!("   GET  /openapi.json    - Описание API (OpenAPI 3)");
+    println!("   POST /inject          - Инъекция сбоя {{\"kind\": \"oracle_jump\", \"bps\": -1500}} (admin)");
+    println!("   GET  /faults          - Активные сбои");
+    println!("   GET  /agent           - Активный агент");
+    println!("   GET  /leaderboard?limit=10 - Таблица соревнования (с --leaderboard)");
+    println!("   POST /instances       - Новый экземпляр {{\"name\", \"agent\"?, \"initial_margin_bps\"?, ...}} (admin)");
+    println!("   GET  /instances       - Именованные экземпляры; их API под /i/<name>/...");
+    println!("   POST /agent/swap      - Замена агента {{\"name\": \"conservative\"}} (admin)");
+    println!("   POST /admin/freeze    - Заморозить рынок (admin)");
+    println!("   POST /admin/resume    - Снять заморозку (admin)");
+    println!("   POST /admin/market-params - Применить параметры агента (admin)");
     println!("\n{}", "=".repeat(50));
     println!("\n💡 Используйте curl или браузер для тестирования API");
     println!("   Пример: curl http://localhost:8080/health\n");
     
-    // Простой HTTP сервер на std::net::TcpListener
+    // Пул потоков: GET-запросы читают общее состояние параллельно,
+    // изменяющие запросы сериализуются через блокировку на запись
     let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
-    let listener = std::net::TcpListener::bind(addr).expect("Failed to bind");
+    let listener = TcpListener::bind(addr).expect("Failed to bind");
+    let workers = std::thread::available_parallelism().map_or(4, |n| n.get());
     
-    println!("✅ Сервер запущен на {}", addr);
+    println!("✅ Сервер запущен на {} ({} потоков)", addr, workers);
     println!("   Нажмите Ctrl+C для остановки\n");
     
-    for stream in listener.incoming() {
-        match stream {
-            Ok(mut stream) => {
-                // Простая обработка HTTP запросов
-                let mut buffer = [0; 1024];
-                if let Ok(size) = stream.read(&mut buffer) {
-                    let request = String::from_utf8_lossy(&buffer[..size]);
-                    let response = handle_request(&request, &mut engine, &agent);
-                    
-                    let http_response = format!(
-                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
-                        response.len(),
-                        response
-                    );
-                    
-                    let _ = stream.write_all(http_response.as_bytes());
+    let router = match keys {
+        Some(keys) => router().with_keys(keys),
+        None => router(),
+    }
+    .with_openapi("Clawcolator Localhost API", env!("CARGO_PKG_VERSION"));
+    if let Err(e) = serve_instances(listener, Arc::new(instances), Arc::new(router), workers) {
+        eprintln!("Ошибка сервера: {}", e);
+    }
+}
+
+// Агенты для горячей замены (POST /agent/swap) и новых экземпляров
+fn agents(config: &EngineConfig) -> AgentRegistry<SimpleClawAgent> {
+    let (size, leverage, spread) = (
+        config.market.max_position_size,
+        config.market.max_leverage_bps,
+        config.market.spread_bps,
+    );
+    AgentRegistry::new()
+        .with("default", move || SimpleClawAgent::new(size, leverage, spread))
+        .with("conservative", move || SimpleClawAgent::new(size / 4, leverage / 2, spread * 2))
+        // Плечо выше потолка: отклоняется пробным запуском
+        .with("reckless", move || SimpleClawAgent::new(size * 10, 100_000, 0))
+}
+
+// Таблица маршрутов
+fn router() -> Router<SimpleClawAgent> {
+    Router::new()
+        .read("GET", "/health", query(health))
+        .doc(doc("Проверка здоровья сервера", HEALTH))
+        .read("GET", "/status", query(status))
+        .doc(doc("Статус движка", STATUS))
+        .read("GET", "/market-params", query(market_params))
+        .doc(doc("Параметры рынка от агента", MARKET_PARAMS))
+        .read("GET", "/risk", query(risk))
+        .doc(doc("Оценка риска", RISK))
+        .read("GET", "/anomalies", query(anomalies))
+        .doc(doc("Проверка аномалий", ANOMALIES))
+        .read("GET", "/epochs", query(epochs))
+        .doc(doc("Отчёты по эпохам", EPOCHS))
+        .read("GET", "/governance/proposals", query(governance_proposals))
+        .doc(doc("Ожидающие предложения", PROPOSALS))
+        .read("GET", "/withdrawals", query(withdrawals))
+        .doc(doc("Очередь выводов", WITHDRAWALS))
+        .read("GET", "/stress", query(stress))
+        .doc(RouteDoc {
+            query: &[("shock_bps", "integer")],
+            ..doc("Стресс-прогноз страхового фонда", STRESS)
+        })
+        .read("GET", "/trades", trades)
+        .doc(RouteDoc {
+            query: SLOT_RANGE,
+            ..doc("Исполненные сделки за диапазон слотов", TRADES)
+        })
+        .read("GET", "/candles", candles)
+        .doc(RouteDoc {
+            query: CANDLE_QUERY,
+            ..doc("OHLC-свечи по interval слотов", CANDLES)
+        })
+        .read("GET", "/events", events)
+        .doc(RouteDoc {
+            query: &[("since", "integer")],
+            ..doc("Применённые и отклонённые команды", EVENTS)
+        })
+        .read("GET", "/agent", agent)
+        .doc(doc("Активный и доступные агенты", AGENT))
+        .read("GET", "/faults", faults)
+        .doc(doc("Активные инъекции сбоев", FAULTS))
+        .read("GET", "/leaderboard", leaderboard)
+        .doc(RouteDoc {
+            query: &[("limit", "integer")],
+            ..doc("Таблица соревнования: PnL, объём и просадка за окно", LEADERBOARD)
+        })
+        .read("GET", "/accounts/{idx}/margin", query(account_margin))
+        .doc(RouteDoc { path: IDX, ..doc("Маржа и цена ликвидации", MARGIN) })
+        .read("GET", "/accounts/{idx}/max-size", query(max_size))
+        .doc(RouteDoc {
+            path: IDX,
+            query: &[("side", "string"), ("oracle_price", "integer")],
+            ..doc("Максимальный размер сделки, который сейчас пройдёт", MAX_SIZE)
+        })
+        .read("GET", "/accounts/{idx}", query(account))
+        .doc(RouteDoc { path: IDX, ..doc("Состояние счёта", ACCOUNT) })
+        .read("GET", "/keeper-rewards/{idx}", query(keeper_rewards))
+        .doc(RouteDoc { path: IDX, ..doc("Награды кипера", KEEPER_REWARDS) })
+        .read("POST", "/trade", query(trade_preview))
+        .scope(Scope::Trade)
+        .doc(RouteDoc {
+            body: TRADE,
+            ..doc("Решение агента по сделке (без исполнения)", TRADE_DECISION)
+        })
+        .read("POST", "/trade/simulate", query(simulate_trade))
+        .scope(Scope::Trade)
+        .doc(RouteDoc {
+            body: TRADE,
+            ..doc("Прогон сделки через все проверки без исполнения", SIMULATION)
+        })
+        .write("POST", "/accounts/user", |state, req| {
+            let deposit = req.json_int("deposit").unwrap_or(0) as u128;
+            apply(state, Command::AddUser { deposit })
+        })
+        .doc(RouteDoc { body: OPEN_ACCOUNT, ..doc("Открыть счёт пользователя", APPLIED) })
+        .write("POST", "/accounts/lp", |state, req| {
+            let deposit = req.json_int("deposit").unwrap_or(0) as u128;
+            apply(state, Command::AddLp { deposit })
+        })
+        .doc(RouteDoc { body: OPEN_ACCOUNT, ..doc("Открыть счёт LP", APPLIED) })
+        .write("POST", "/deposit", |state, req| {
+            let idx = req.json_int("idx").unwrap_or(0) as u16;
+            let amount = req.json_int("amount").unwrap_or(0) as u128;
+            apply(state, Command::Deposit { idx, amount })
+        })
+        .doc(RouteDoc { body: DEPOSIT, ..doc("Пополнить счёт", APPLIED) })
+        .write("POST", "/execute", execute)
+        .scope(Scope::Trade)
+        .doc(RouteDoc { body: EXECUTE, ..doc("Исполнить сделку", EXECUTED) })
+        .write("POST", "/trade/commit", |state, req| {
+            let Some(commitment) = req.json_str("commitment").and_then(decode_hex32) else {
+                return Response::bad_request("commitment: 64 hex digits required");
+            };
+            let user_idx = req.json_int("user_idx").unwrap_or(0) as u16;
+            let slot = state.slot();
+            apply(state, Command::CommitTrade { user_idx, commitment, slot })
+        })
+        .scope(Scope::Trade)
+        .doc(RouteDoc { body: COMMIT, ..doc("Заявить хэш сделки (commit-reveal)", APPLIED) })
+        .write("POST", "/trade/reveal", reveal)
+        .scope(Scope::Trade)
+        .doc(RouteDoc { body: REVEAL, ..doc("Раскрыть и исполнить заявленную сделку", EXECUTED) })
+        .read("GET", "/batch", query(batch))
+        .doc(doc("Текущий пакет заявок и итог последнего аукциона", BATCH))
+        .read("GET", "/wind-down", query(wind_down))
+        .doc(doc("Ход делистинга рынка", WIND_DOWN))
+        .read("GET", "/alerts", query(alerts))
+        .doc(doc("События для оператора (флаппинг агента)", ALERTS))
+        .write("POST", "/batch/order", |state, req| {
+            let command = Command::SubmitBatchOrder {
+                user_idx: req.json_int("user_idx").unwrap_or(0) as u16,
+                size: req.json_int("size").unwrap_or(0),
+                slot: state.slot(),
+            };
+            apply(state, command)
+        })
+        .scope(Scope::Trade)
+        .doc(RouteDoc { body: BATCH_ORDER, ..doc("Заявка в текущий пакетный аукцион", APPLIED) })
+        .write("POST", "/batch/clear", |state, req| {
+            let command = Command::ClearBatch {
+                oracle_price: req.json_int("oracle_price").unwrap_or(1_000_000) as u64,
+                slot: state.slot(),
+            };
+            apply(state, command)
+        })
+        .doc(RouteDoc {
+            body: &[("oracle_price", "integer")],
+            ..doc("Провести пакетный аукцион по единой цене", APPLIED)
+        })
+        .write("POST", "/heartbeat", |state, _req| {
+            let slot = state.slot();
+            apply(state, Command::Heartbeat { slot })
+        })
+        .doc(doc("Heartbeat агента: подтверждение живости между кранками", APPLIED))
+        .write("POST", "/crank", |state, req| {
+            let command = Command::Crank {
+                caller_idx: req.json_int("caller_idx").unwrap_or(0) as u16,
+                oracle_price: req.json_int("oracle_price").unwrap_or(1_000_000) as u64,
+                slot: state.slot().max(state.engine.risk_engine().current_slot + 1),
+            };
+            apply(state, command)
+        })
+        .doc(RouteDoc { body: CRANK, ..doc("Кранк в следующем слоте", APPLIED) })
+        .read("POST", "/simulate", simulate_market)
+        .doc(RouteDoc {
+            body: SIMULATE,
+            ..doc("Детерминированная симуляция рынка против агента", SIMULATION_REPORT)
+        })
+        .read("GET", "/clock", clock)
+        .doc(doc("Часы слотов для новых команд", CLOCK))
+        .write("POST", "/clock", |state, req| {
+            let rate = req.json_int("slots_per_sec").and_then(|r| u64::try_from(r).ok());
+            if let Some(mode) = req.json_str("mode") {
+                match ClockMode::decode(mode, rate) {
+                    Some(mode) => state.set_clock(mode),
+                    None => {
+                        return Response::bad_request(
+                            "mode: manual | wall_clock | accelerated {slots_per_sec}",
+                        )
+                    }
                 }
             }
-            Err(e) => {
-                eprintln!("Ошибка соединения: {}", e);
+            if let Some(slots) = req.json_int("advance").and_then(|n| u64::try_from(n).ok()) {
+                state.advance_clock(slots);
             }
+            clock(state, req)
+        })
+        .doc(RouteDoc {
+            body: CLOCK_UPDATE,
+            ..doc("Режим часов (ручной, реальное время, ускоренный) и сдвиг вперёд", CLOCK)
+        })
+        .write("POST", "/admin/freeze", |state, _| {
+            apply(state, Command::SetFrozen { frozen: true })
+        })
+        .doc(doc("Заморозить рынок", APPLIED))
+        .write("POST", "/admin/resume", |state, _| {
+            apply(state, Command::SetFrozen { frozen: false })
+        })
+        .doc(doc("Снять заморозку", APPLIED))
+        .write("POST", "/admin/market-params", |state, _| {
+            apply(state, Command::UpdateMarketParams)
+        })
+        .doc(doc("Применить параметры агента", APPLIED))
+        .write("POST", "/agent/swap", |state, req| match req.json_str("name") {
+            Some(name) => apply(state, Command::SwapAgent { name: name.to_string() }),
+            None => Response::bad_request("name required"),
+        })
+        .doc(RouteDoc { body: &[("name", "string")], ..doc("Горячая замена агента", APPLIED) })
+        .write("POST", "/inject", |state, req| match fault(req) {
+            Some(fault) => apply(state, Command::Inject(fault)),
+            None => Response::bad_request(
+                "kind: oracle_jump {bps} | oracle_outage {slots} | agent_outage {slots} | mass_liquidation {shock_bps} | clear",
+            ),
+        })
+        .doc(RouteDoc { body: INJECT, ..doc("Инъекция сбоя (хаос-тестирование)", APPLIED) })
+}
+
+// Параметры симуляции из тела POST /simulate (незаданные — по умолчанию)
+fn simulation_params(req: &Request) -> Option<SimulationParams> {
+    let d = SimulationParams::default();
+    let int = |key, default: i128| req.json_int(key).unwrap_or(default);
+    let step_bps = u32::try_from(int("step_bps", 100)).ok()?;
+    let price_model = match req.json_str("model").unwrap_or("random_walk") {
+        "random_walk" => PriceModel::RandomWalk { step_bps },
+        "trend" => PriceModel::Trend { drift_bps: i32::try_from(int("drift_bps", 0)).ok()?, step_bps },
+        "crash" => PriceModel::Crash {
+            at_step: u32::try_from(int("at_step", 0)).ok()?,
+            shock_bps: i32::try_from(int("shock_bps", -3000)).ok()?,
+            step_bps,
+        },
+        _ => return None,
+    };
+    Some(SimulationParams {
+        price_model,
+        start_price: u64::try_from(int("start_price", d.start_price as i128)).ok()?,
+        steps: u32::try_from(int("steps", d.steps as i128)).ok()?,
+        traders: u16::try_from(int("traders", d.traders as i128)).ok()?,
+        trader_deposit: u128::try_from(int("trader_deposit", d.trader_deposit as i128)).ok()?,
+        lp_deposit: u128::try_from(int("lp_deposit", d.lp_deposit as i128)).ok()?,
+        max_order_size: u128::try_from(int("max_order_size", d.max_order_size as i128)).ok()?,
+        order_probability_bps: u16::try_from(int("order_probability_bps", d.order_probability_bps as i128)).ok()?,
+        seed: u64::try_from(int("seed", 0)).ok()?,
+    })
+}
+
+// Симуляция на свежем движке с параметрами этого экземпляра
+fn simulate_market(state: &ServerState<SimpleClawAgent>, req: &Request) -> Response {
+    let Some(params) = simulation_params(req) else {
+        return Response::bad_request("model: random_walk | trend {drift_bps} | crash {at_step, shock_bps}; numeric fields must be non-negative integers");
+    };
+    match state.simulate(&params) {
+        Ok(r) => Response::ok(format!(
+            r#"{{"seed": {}, "steps": {}, "final_price": {}, "min_price": {}, "max_price": {}, "orders": {}, "fills": {}, "rejections": {}, "volume": {}, "liquidations": {}, "failed_cranks": {}, "fees": {}, "lp_pnl": {}, "insurance_start": {}, "insurance_min": {}, "insurance_end": {}, "socialized": {}, "solvent": {}}}"#,
+            r.seed, r.steps, r.final_price, r.min_price, r.max_price, r.orders, r.fills, r.rejections, r.volume,
+            r.liquidations, r.failed_cranks, r.fees, r.lp_pnl, r.insurance_start, r.insurance_min, r.insurance_end,
+            r.socialized, r.solvent
+        )),
+        Err(e) => Response::bad_request(&format!("{:?}", e)),
+    }
+}
+
+// Сбой из тела POST /inject
+fn fault(req: &Request) -> Option<Fault> {
+    let int = |key| req.json_int(key);
+    let fault = match req.json_str("kind")? {
+        "oracle_jump" => Fault::OracleJump { bps: int("bps")? as i64 },
+        "oracle_outage" => Fault::OracleOutage { slots: int("slots")? as u64 },
+        "agent_outage" => Fault::AgentOutage { slots: int("slots")? as u64 },
+        "mass_liquidation" => Fault::MassLiquidation {
+            shock_bps: int("shock_bps").unwrap_or(-5000) as i64,
+        },
+        "clear" => Fault::Clear,
+        _ => return None,
+    };
+    Some(fault)
+}
+
+// Описание маршрута для /openapi.json
+fn doc(summary: &'static str, response: Fields) -> RouteDoc {
+    RouteDoc {
+        summary,
+        response,
+        ..RouteDoc::default()
+    }
+}
+
+// Схемы тел запросов и ответов (должны совпадать с обработчиками ниже)
+const IDX: Fields = &[("idx", "integer")];
+const AGENT: Fields = &[("active", "string"), ("available", "array")];
+const INJECT: Fields = &[
+    ("kind", "string"),
+    ("bps", "integer"),
+    ("slots", "integer"),
+    ("shock_bps", "integer"),
+];
+const FAULTS: Fields = &[
+    ("oracle_offset_bps", "integer"),
+    ("oracle_outage_until", "integer"),
+    ("agent_outage_until", "integer"),
+    ("last_oracle_price", "integer"),
+];
+const SLOT_RANGE: Fields = &[("from", "integer"), ("to", "integer")];
+const CANDLE_QUERY: Fields = &[("interval", "integer"), ("from", "integer"), ("to", "integer")];
+const TRADES: Fields = &[("trades", "array")];
+const SIMULATE: Fields = &[
+    ("model", "string"),
+    ("step_bps", "integer"),
+    ("drift_bps", "integer"),
+    ("at_step", "integer"),
+    ("shock_bps", "integer"),
+    ("start_price", "integer"),
+    ("steps", "integer"),
+    ("traders", "integer"),
+    ("trader_deposit", "integer"),
+    ("lp_deposit", "integer"),
+    ("max_order_size", "integer"),
+    ("order_probability_bps", "integer"),
+    ("seed", "integer"),
+];
+const SIMULATION_REPORT: Fields = &[
+    ("seed", "integer"),
+    ("steps", "integer"),
+    ("final_price", "integer"),
+    ("min_price", "integer"),
+    ("max_price", "integer"),
+    ("orders", "integer"),
+    ("fills", "integer"),
+    ("rejections", "integer"),
+    ("volume", "integer"),
+    ("liquidations", "integer"),
+    ("failed_cranks", "integer"),
+    ("fees", "integer"),
+    ("lp_pnl", "integer"),
+    ("insurance_start", "integer"),
+    ("insurance_min", "integer"),
+    ("insurance_end", "integer"),
+    ("socialized", "integer"),
+    ("solvent", "boolean"),
+];
+const CLOCK: Fields = &[("mode", "string"), ("slots_per_sec", "number"), ("slot", "integer"), ("engine_slot", "integer")];
+const CLOCK_UPDATE: Fields = &[("mode", "string"), ("slots_per_sec", "integer"), ("advance", "integer")];
+const LEADERBOARD: Fields = &[("slot", "integer"), ("window_slots", "integer"), ("standings", "array")];
+const CANDLES: Fields = &[("interval", "integer"), ("candles", "array")];
+const EVENTS: Fields = &[("next_seq", "integer"), ("events", "array")];
+const OPEN_ACCOUNT: Fields = &[("deposit", "integer")];
+const DEPOSIT: Fields = &[("idx", "integer"), ("amount", "integer")];
+const TRADE: Fields = &[("user_idx", "integer"), ("size", "integer"), ("oracle_price", "integer")];
+const EXECUTE: Fields = &[
+    ("user_idx", "integer"),
+    ("size", "integer"),
+    ("oracle_price", "integer"),
+    ("client_order_id", "integer"),
+    ("sequence", "integer"),
+    ("valid_until_slot", "integer"),
+];
+const COMMIT: Fields = &[("user_idx", "integer"), ("commitment", "string")];
+const REVEAL: Fields = &[
+    ("user_idx", "integer"),
+    ("size", "integer"),
+    ("max_slippage_bps", "integer"),
+    ("salt", "string"),
+    ("oracle_price", "integer"),
+];
+const BATCH_ORDER: Fields = &[("user_idx", "integer"), ("size", "integer")];
+const BATCH: Fields = &[
+    ("enabled", "boolean"),
+    ("batch_slots", "integer"),
+    ("max_deviation_bps", "integer"),
+    ("opened_slot", "integer"),
+    ("orders", "array"),
+    ("last", "object"),
+];
+const ALERTS: Fields = &[("params_locked", "boolean"), ("alerts", "array")];
+const WIND_DOWN: Fields = &[
+    ("active", "boolean"),
+    ("phase", "string"),
+    ("ramp_bps", "integer"),
+    ("maintenance_margin_bps", "integer"),
+    ("initial_margin_bps", "integer"),
+    ("settle_slot", "integer"),
+    ("open_positions", "integer"),
+    ("open_interest", "integer"),
+    ("settlement_price", "integer"),
+];
+const CRANK: Fields = &[("caller_idx", "integer"), ("oracle_price", "integer")];
+const APPLIED: Fields = &[("ok", "boolean"), ("idx", "integer"), ("error", "string"), ("warning", "string")];
+const EXECUTED: Fields = &[("ok", "boolean"), ("error", "string"), ("warning", "string"), ("rejection", "object")];
+const HEALTH: Fields = &[("status", "string"), ("service", "string")];
+const STATUS: Fields = &[
+    ("vault", "integer"),
+    ("insurance", "integer"),
+    ("total_capital", "integer"),
+    ("total_open_interest", "integer"),
+    ("current_slot", "integer"),
+    ("last_agent_activity_slot", "integer"),
+    ("heartbeat_age_slots", "integer"),
+    ("dead_man_stage", "string"),
+];
+const MARKET_PARAMS: Fields = &[
+    ("max_leverage_bps", "integer"),
+    ("max_position_size", "integer"),
+    ("spread_bps", "integer"),
+    ("funding_rate_bps_per_slot", "integer"),
+    ("min_margin_bps", "integer"),
+    ("active_capital_ratio_bps", "integer"),
+];
+const RISK: Fields = &[
+    ("risk_level_bps", "integer"),
+    ("reduce_exposure", "boolean"),
+    ("hedge", "boolean"),
+    ("increase_margin", "boolean"),
+];
+const ANOMALIES: Fields = &[
+    ("anomaly_type", "string"),
+    ("severity_bps", "integer"),
+    ("freeze_market", "boolean"),
+    ("stop_trading", "boolean"),
+    ("initiate_shutdown", "boolean"),
+];
+const EPOCHS: Fields = &[("current_epoch", "integer"), ("epochs", "array")];
+const PROPOSALS: Fields = &[("governed", "boolean"), ("proposals", "array")];
+const WITHDRAWALS: Fields = &[
+    ("pending_count", "integer"),
+    ("pending_amount", "integer"),
+    ("next_release_slot", "integer"),
+    ("paused", "boolean"),
+];
+const STRESS: Fields = &[
+    ("shock_bps", "integer"),
+    ("shocked_price", "integer"),
+    ("liquidatable_accounts", "integer"),
+    ("bankrupt_accounts", "integer"),
+    ("total_deficit", "integer"),
+    ("insurance_drawdown", "integer"),
+    ("insurance_remaining", "integer"),
+    ("socialized_loss", "integer"),
+    ("socialization_triggered", "boolean"),
+];
+const MARGIN: Fields = &[
+    ("equity", "integer"),
+    ("notional", "integer"),
+    ("initial_margin", "integer"),
+    ("maintenance_margin", "integer"),
+    ("margin_ratio_bps", "integer"),
+    ("free_collateral", "integer"),
+    ("liquidation_price", "integer"),
+];
+const ACCOUNT: Fields = &[
+    ("idx", "integer"),
+    ("kind", "string"),
+    ("capital", "integer"),
+    ("pnl", "integer"),
+    ("unrealized_pnl", "integer"),
+    ("withdrawable_pnl", "integer"),
+    ("unvested_pnl", "integer"),
+    ("slots_to_vest", "integer"),
+    ("position_size", "integer"),
+    ("equity", "integer"),
+    ("margin_ratio_bps", "integer"),
+    ("undercollateralized", "boolean"),
+    ("liquidation_price", "integer"),
+    ("funding_paid", "integer"),
+    ("sequence", "integer"),
+];
+const MAX_SIZE: Fields = &[
+    ("idx", "integer"),
+    ("side", "string"),
+    ("protocol_max_size", "integer"),
+    ("max_size", "integer"),
+];
+const SIMULATION: Fields = &[
+    ("price", "integer"),
+    ("size", "integer"),
+    ("vamm", "boolean"),
+    ("fee", "integer"),
+    ("position_size", "integer"),
+    ("equity", "integer"),
+    ("margin_ratio_bps", "integer"),
+    ("liquidation_price", "integer"),
+    ("meta", "object"),
+    ("error", "string"),
+    ("rejection", "object"),
+];
+const KEEPER_REWARDS: Fields = &[
+    ("keeper_idx", "integer"),
+    ("pending", "integer"),
+    ("pending_total", "integer"),
+    ("paid_total", "integer"),
+];
+const TRADE_DECISION: Fields = &[
+    ("decision", "string"),
+    ("price", "integer"),
+    ("size", "integer"),
+    ("reason", "string"),
+    ("quote_price", "integer"),
+    ("max_size", "integer"),
+];
+
+// Изменяющие запросы идут через ServerState, чтобы попасть в журнал
+fn apply(state: &mut ServerState<SimpleClawAgent>, command: Command) -> Response {
+    applied(state.apply(command))
+}
+
+fn applied(result: std::result::Result<Option<u16>, ServerError>) -> Response {
+    match result {
+        Ok(Some(idx)) => Response::ok(format!(r#"{{"ok": true, "idx": {}}}"#, idx)),
+        Ok(None) => Response::ok(r#"{"ok": true}"#),
+        Err(ServerError::Engine(e)) => Response::ok(format!(r#"{{"error": "{:?}"}}"#, e)),
+        Err(e) => Response::ok(format!(r#"{{"ok": true, "warning": "не сохранено: {:?}"}}"#, e)),
+    }
+}
+
+// Сделка; при отказе добавляем причину, лимиты и встречную котировку агента.
+// Повтор с тем же client_order_id не исполняется, а возвращает исходный итог;
+// sequence должен быть больше последнего номера счёта, после valid_until_slot
+// сделка отклоняется
+fn execute(state: &mut ServerState<SimpleClawAgent>, req: &Request) -> Response {
+    let command = Command::Trade {
+        user_idx: req.json_int("user_idx").unwrap_or(0) as u16,
+        oracle_price: req.json_int("oracle_price").unwrap_or(1_000_000) as u64,
+        size: req.json_int("size").unwrap_or(0),
+        slot: state.slot(),
+        client_order_id: req.json_int("client_order_id").map(|id| id as u64),
+        sequence: req.json_int("sequence").map(|seq| seq as u64),
+        valid_until_slot: req.json_int("valid_until_slot").map(|slot| slot as u64),
+    };
+    let result = state.apply(command);
+    traded(state, result)
+}
+
+// Раскрытие сделки, о которой пользователь заранее заявил через /trade/commit
+fn reveal(state: &mut ServerState<SimpleClawAgent>, req: &Request) -> Response {
+    let Some(salt) = req.json_str("salt").and_then(decode_hex32) else {
+        return Response::bad_request("salt: 64 hex digits required");
+    };
+    let command = Command::RevealTrade {
+        reveal: TradeReveal {
+            user_idx: req.json_int("user_idx").unwrap_or(0) as u16,
+            size: req.json_int("size").unwrap_or(0),
+            max_slippage_bps: req.json_int("max_slippage_bps").unwrap_or(0) as u64,
+            salt,
+        },
+        oracle_price: req.json_int("oracle_price").unwrap_or(1_000_000) as u64,
+        slot: state.slot(),
+    };
+    let result = state.apply(command);
+    traded(state, result)
+}
+
+fn traded(
+    state: &ServerState<SimpleClawAgent>,
+    result: std::result::Result<Option<u16>, ServerError>,
+) -> Response {
+    match result {
+        Err(ServerError::Engine(e)) => {
+            let rejection = state.engine.last_trade_outcome().and_then(|o| o.rejection());
+            Response::ok(format!(
+                r#"{{"error": "{:?}", "rejection": {}}}"#,
+                e,
+                rejection_json(rejection)
+            ))
         }
+        result => applied(result),
     }
 }
 
-use std::io::{Read, Write};
+// Причина отказа в сделке или null
+fn rejection_json(rejection: Option<&TradeRejection>) -> String {
+    rejection.map_or("null".to_string(), |r| {
+        let quote = r.counter_quote.map_or("null".to_string(), |q| {
+            format!(r#"{{"price": {}, "max_size": {}}}"#, q.price, q.max_size)
+        });
+        format!(
+            r#"{{"cause": "{:?}", "max_size": {}, "max_leverage_bps": {}, "counter_quote": {}}}"#,
+            r.cause, r.limits.max_size, r.limits.max_leverage_bps, quote
+        )
+    })
+}
+
+type Query = fn(&ClawcolatorEngine, &SimpleClawAgent, &Request) -> String;
 
-fn handle_request(request: &str, engine: &mut ClawcolatorEngine, agent: &SimpleClawAgent) -> String {
-    let lines: Vec<&str> = request.lines().collect();
-    if lines.is_empty() {
-        return r#"{"error": "Empty request"}"#.to_string();
+// Обработчик только для чтения
+fn query(f: Query) -> impl Fn(&ServerState<SimpleClawAgent>, &Request) -> Response {
+    move |state, req| Response::ok(f(&state.engine, &state.agent, req))
+}
+
+fn agent(state: &ServerState<SimpleClawAgent>, _req: &Request) -> Response {
+    let available: Vec<String> = state.agents().names().map(|n| format!(r#""{}""#, n)).collect();
+    Response::ok(format!(
+        r#"{{"active": "{}", "available": [{}]}}"#,
+        state.agent_name().unwrap_or("default"),
+        available.join(", ")
+    ))
+}
+
+fn faults(state: &ServerState<SimpleClawAgent>, _req: &Request) -> Response {
+    let f = state.faults();
+    let slot = |s: Option<u64>| s.map_or("null".to_string(), |s| s.to_string());
+    Response::ok(format!(
+        r#"{{"oracle_offset_bps": {}, "oracle_outage_until": {}, "agent_outage_until": {}, "last_oracle_price": {}}}"#,
+        f.oracle_offset_bps,
+        slot(f.oracle_outage.map(|(_, until)| until)),
+        slot(f.agent_outage_until),
+        f.last_oracle_price
+    ))
+}
+
+fn clock(state: &ServerState<SimpleClawAgent>, _req: &Request) -> Response {
+    let mode = state.clock().mode();
+    Response::ok(format!(
+        r#"{{"mode": "{}", "slots_per_sec": {}, "slot": {}, "engine_slot": {}}}"#,
+        mode.name(),
+        mode.slots_per_sec(),
+        state.slot(),
+        state.engine.risk_engine().current_slot
+    ))
+}
+
+fn leaderboard(state: &ServerState<SimpleClawAgent>, req: &Request) -> Response {
+    let Some(board) = state.leaderboard() else {
+        return Response::bad_request("Leaderboard disabled (start with --leaderboard <slots>)");
+    };
+    let limit = req.query_param("limit").and_then(|v| v.parse().ok()).unwrap_or(usize::MAX);
+    let slot = state.slot();
+    let standings: Vec<String> = board
+        .standings(slot)
+        .iter()
+        .take(limit)
+        .enumerate()
+        .map(|(rank, s)| {
+            format!(
+                r#"{{"rank": {}, "idx": {}, "account_id": {}, "lp": {}, "pnl": {}, "volume": {}, "trades": {}, "max_drawdown": {}}}"#,
+                rank + 1,
+                s.idx,
+                s.account_id,
+                s.lp,
+                s.pnl,
+                s.volume,
+                s.trades,
+                s.max_drawdown
+            )
+        })
+        .collect();
+    Response::ok(format!(
+        r#"{{"slot": {}, "window_slots": {}, "standings": [{}]}}"#,
+        slot,
+        board.window_slots(),
+        standings.join(", ")
+    ))
+}
+
+// Обоснование решения агента или null
+fn meta_json(meta: Option<&DecisionMeta>) -> String {
+    meta.map_or("null".to_string(), |m| {
+        format!(
+            r#"{{"confidence_bps": {}, "strategy": {}, "model_version": {}, "reason_code": {}}}"#,
+            m.confidence_bps, m.strategy, m.model_version, m.reason_code
+        )
+    })
+}
+
+// Диапазон слотов из ?from=&to= (по умолчанию вся история)
+fn slot_range(req: &Request) -> (u64, u64) {
+    let slot = |name, default| req.query_param(name).and_then(|v| v.parse().ok()).unwrap_or(default);
+    (slot("from", 0), slot("to", u64::MAX))
+}
+
+fn trades(state: &ServerState<SimpleClawAgent>, req: &Request) -> Response {
+    let (from, to) = slot_range(req);
+    let trades: Vec<String> = state
+        .history()
+        .trades(from, to)
+        .map(|t| {
+            format!(
+                r#"{{"slot": {}, "user_idx": {}, "lp_idx": {}, "price": {}, "size": {}, "vamm": {}, "meta": {}, "client_order_id": {}}}"#,
+                t.slot,
+                t.user_idx,
+                t.lp_idx,
+                t.price,
+                t.size,
+                t.vamm,
+                meta_json(t.meta.as_ref()),
+                t.client_order_id.map_or("null".to_string(), |id| id.to_string())
+            )
+        })
+        .collect();
+    Response::ok(format!(r#"{{"trades": [{}]}}"#, trades.join(", ")))
+}
+
+fn candles(state: &ServerState<SimpleClawAgent>, req: &Request) -> Response {
+    let interval = match req.query_param("interval").and_then(|v| v.parse::<u64>().ok()) {
+        Some(interval) if interval > 0 => interval,
+        _ => return Response::bad_request("interval (slots > 0) required"),
+    };
+    let (from, to) = slot_range(req);
+    let candles: Vec<String> = state
+        .history()
+        .candles(interval, from, to)
+        .iter()
+        .map(|c| {
+            format!(
+                r#"{{"start_slot": {}, "open": {}, "high": {}, "low": {}, "close": {}, "volume": {}, "trades": {}}}"#,
+                c.start_slot, c.open, c.high, c.low, c.close, c.volume, c.trades
+            )
+        })
+        .collect();
+    Response::ok(format!(
+        r#"{{"interval": {}, "candles": [{}]}}"#,
+        interval,
+        candles.join(", ")
+    ))
+}
+
+fn events(state: &ServerState<SimpleClawAgent>, req: &Request) -> Response {
+    let since = req.query_param("since").and_then(|v| v.parse().ok()).unwrap_or(0);
+    let events: Vec<String> = state
+        .history()
+        .events(since)
+        .map(|e| {
+            format!(
+                r#"{{"seq": {}, "slot": {}, "command": "{}", "error": {}, "meta": {}}}"#,
+                e.seq,
+                e.slot,
+                e.command.encode(),
+                e.error.map_or("null".to_string(), |err| format!(r#""{:?}""#, err)),
+                meta_json(e.meta.as_ref())
+            )
+        })
+        .collect();
+    Response::ok(format!(
+        r#"{{"next_seq": {}, "events": [{}]}}"#,
+        state.history().next_seq(),
+        events.join(", ")
+    ))
+}
+
+fn health(_engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, _req: &Request) -> String {
+    r#"{"status": "ok", "service": "clawcolator"}"#.to_string()
+}
+
+fn status(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, _req: &Request) -> String {
+    let context = engine.build_context(fresh_oracle(engine, 1_000_000));
+    format!(
+        r#"{{"vault": {}, "insurance": {}, "total_capital": {}, "total_open_interest": {}, "current_slot": {}, "last_agent_activity_slot": {}, "heartbeat_age_slots": {}, "dead_man_stage": {}}}"#,
+        context.vault,
+        context.insurance_balance,
+        context.total_capital,
+        context.total_open_interest,
+        context.current_slot,
+        engine.last_agent_activity_slot(),
+        context.heartbeat_age_slots.map_or("null".to_string(), |age| age.to_string()),
+        engine
+            .dead_man_status(context.current_slot)
+            .map_or("null".to_string(), |s| format!(r#""{:?}""#, s.stage))
+    )
+}
+
+fn market_params(engine: &ClawcolatorEngine, agent: &SimpleClawAgent, _req: &Request) -> String {
+    let context = engine.build_context(fresh_oracle(engine, 1_000_000));
+    match agent.get_market_params(&context) {
+        Ok(params) => {
+            format!(
+                r#"{{"max_leverage_bps": {}, "max_position_size": {}, "spread_bps": {}, "funding_rate_bps_per_slot": {}, "min_margin_bps": {}, "active_capital_ratio_bps": {}}}"#,
+                params.max_leverage_bps,
+                params.max_position_size,
+                params.spread_bps,
+                params.funding_rate_bps_per_slot,
+                params.min_margin_bps,
+                params.active_capital_ratio_bps
+            )
+        }
+        Err(e) => format!(r#"{{"error": "{:?}"}}"#, e),
     }
-    
-    let request_line = lines[0];
-    let parts: Vec<&str> = request_line.split_whitespace().collect();
-    
-    if parts.len() < 2 {
-        return r#"{"error": "Invalid request"}"#.to_string();
+}
+
+fn risk(engine: &ClawcolatorEngine, agent: &SimpleClawAgent, _req: &Request) -> String {
+    let context = engine.build_context(fresh_oracle(engine, 1_000_000));
+    match agent.assess_risk(&context) {
+        Ok(assessment) => {
+            format!(
+                r#"{{"risk_level_bps": {}, "reduce_exposure": {}, "hedge": {}, "increase_margin": {}}}"#,
+                assessment.risk_level_bps,
+                assessment.actions.reduce_exposure,
+                assessment.actions.hedge.map(|h| h.to_string()).unwrap_or_else(|| "null".to_string()),
+                assessment.actions.increase_margin.map(|m| m.to_string()).unwrap_or_else(|| "null".to_string())
+            )
+        }
+        Err(e) => format!(r#"{{"error": "{:?}"}}"#, e),
     }
-    
-    let method = parts[0];
-    let path = parts[1];
-    
-    match (method, path) {
-        ("GET", "/health") => {
-            r#"{"status": "ok", "service": "clawcolator"}"#.to_string()
+}
+
+fn anomalies(engine: &ClawcolatorEngine, agent: &SimpleClawAgent, _req: &Request) -> String {
+    let context = engine.build_context(fresh_oracle(engine, 1_000_000));
+    match agent.detect_anomalies(&context) {
+        Ok(response) => {
+            format!(
+                r#"{{"anomaly_type": "{:?}", "severity_bps": {}, "freeze_market": {}, "stop_trading": {}, "initiate_shutdown": {}}}"#,
+                response.anomaly_type,
+                response.severity_bps,
+                response.actions.freeze_market,
+                response.actions.stop_trading,
+                response.actions.initiate_shutdown
+            )
         }
-        ("GET", "/status") => {
-            let context = engine.build_context(1_000_000);
+        Err(e) => format!(r#"{{"error": "{:?}"}}"#, e),
+    }
+}
+
+fn epochs(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, _req: &Request) -> String {
+    let reports: Vec<String> = engine
+        .epoch_history()
+        .iter()
+        .map(|r| {
+            format!(
+                r#"{{"epoch": {}, "start_slot": {}, "end_slot": {}, "fees": {{"lp": {}, "insurance": {}, "treasury": {}, "referral": {}, "maker_rebate": {}}}, "funding_index_delta": {}, "lp_funding_received": {}, "funding_paid": {}, "funding_received": {}, "liquidations": {}, "forced_reductions": {}, "dust_closed": {}, "trades_accepted": {}, "trades_rejected": {}, "vamm_fills": {}, "volume": {}, "lp_equity_change": {}, "max_escalation": "{:?}"}}"#,
+                r.epoch,
+                r.start_slot,
+                r.end_slot,
+                r.fees.lp,
+                r.fees.insurance,
+                r.fees.treasury,
+                r.fees.referral,
+                r.fees.maker_rebate,
+                r.funding_index_delta,
+                r.lp_funding_received,
+                r.funding_paid,
+                r.funding_received,
+                r.liquidations.liquidations,
+                r.liquidations.forced_reductions,
+                r.liquidations.dust_closed,
+                r.scorecard.trades_accepted,
+                r.scorecard.trades_rejected,
+                r.scorecard.vamm_fills,
+                r.scorecard.volume,
+                r.scorecard.lp_equity_change(),
+                r.scorecard.max_escalation
+            )
+        })
+        .collect();
+    format!(
+        r#"{{"current_epoch": {}, "epochs": [{}]}}"#,
+        engine.current_epoch().epoch,
+        reports.join(", ")
+    )
+}
+
+fn governance_proposals(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, _req: &Request) -> String {
+    let proposals: Vec<String> = engine
+        .pending_proposals()
+        .map(|p| {
             format!(
-                r#"{{"vault": {}, "insurance": {}, "total_capital": {}, "total_open_interest": {}, "current_slot": {}}}"#,
-                context.vault,
-                context.insurance_balance,
-                context.total_capital,
-                context.total_open_interest,
-                context.current_slot
+                r#"{{"id": {}, "action": "{:?}", "proposed_slot": {}, "approvals": {}, "eta_slot": {}}}"#,
+                p.id,
+                p.action,
+                p.proposed_slot,
+                p.approval_count(),
+                p.eta_slot.map(|s| s.to_string()).unwrap_or_else(|| "null".to_string())
+            )
+        })
+        .collect();
+    format!(
+        r#"{{"governed": {}, "proposals": [{}]}}"#,
+        engine.governance().is_some(),
+        proposals.join(", ")
+    )
+}
+
+fn withdrawals(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, _req: &Request) -> String {
+    let status = engine.build_context(fresh_oracle(engine, 1_000_000)).withdrawal_queue;
+    format!(
+        r#"{{"pending_count": {}, "pending_amount": {}, "next_release_slot": {}, "paused": {}}}"#,
+        status.pending_count,
+        status.pending_amount,
+        status.next_release_slot.map(|s| s.to_string()).unwrap_or_else(|| "null".to_string()),
+        status.paused
+    )
+}
+
+fn stress(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, req: &Request) -> String {
+    let shock_bps = req.query_param("shock_bps").and_then(|v| v.parse::<i64>().ok());
+    match shock_bps {
+        Some(shock_bps) => {
+            let s = engine.stress_project(shock_bps, 1_000_000);
+            format!(
+                r#"{{"shock_bps": {}, "shocked_price": {}, "liquidatable_accounts": {}, "bankrupt_accounts": {}, "total_deficit": {}, "insurance_drawdown": {}, "insurance_remaining": {}, "socialized_loss": {}, "socialization_triggered": {}}}"#,
+                s.shock_bps,
+                s.shocked_price,
+                s.liquidatable_accounts,
+                s.bankrupt_accounts,
+                s.total_deficit,
+                s.insurance_drawdown,
+                s.insurance_remaining,
+                s.socialized_loss,
+                s.socialization_triggered()
             )
         }
-        ("GET", "/market-params") => {
-            let context = engine.build_context(1_000_000);
-            match agent.get_market_params(&context) {
-                Ok(params) => {
+        None => r#"{"error": "shock_bps required"}"#.to_string(),
+    }
+}
+
+fn account_margin(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, req: &Request) -> String {
+    let idx = req.param("idx").unwrap_or("").parse::<u16>();
+    match idx.ok().and_then(|idx| engine.margin_summary(idx, 1_000_000)) {
+        Some(m) => format!(
+            r#"{{"equity": {}, "notional": {}, "initial_margin": {}, "maintenance_margin": {}, "margin_ratio_bps": {}, "free_collateral": {}, "liquidation_price": {}}}"#,
+            m.equity,
+            m.notional,
+            m.initial_margin,
+            m.maintenance_margin,
+            m.margin_ratio_bps,
+            m.free_collateral,
+            m.liquidation_price.map(|p| p.to_string()).unwrap_or_else(|| "null".to_string())
+        ),
+        None => r#"{"error": "Account not found"}"#.to_string(),
+    }
+}
+
+// Для ползунка размера: лимит протокола и лимит с учётом агента
+fn max_size(engine: &ClawcolatorEngine, agent: &SimpleClawAgent, req: &Request) -> String {
+    let Ok(idx) = req.param("idx").unwrap_or("").parse::<u16>() else {
+        return r#"{"error": "Invalid account index"}"#.to_string();
+    };
+    let side = match req.query_param("side").unwrap_or("buy") {
+        "buy" => TradeSide::Buy,
+        "sell" => TradeSide::Sell,
+        _ => return r#"{"error": "side: buy | sell"}"#.to_string(),
+    };
+    let oracle_price = req.query_param("oracle_price").and_then(|p| p.parse().ok()).unwrap_or(1_000_000);
+    let oracle = fresh_oracle(engine, oracle_price);
+    match engine.max_tradeable_size_with_agent(agent, idx, side, oracle) {
+        Ok(size) => format!(
+            r#"{{"idx": {}, "side": "{:?}", "protocol_max_size": {}, "max_size": {}}}"#,
+            idx,
+            side,
+            engine.max_tradeable_size(idx, side, oracle_price),
+            size
+        ),
+        Err(e) => format!(r#"{{"error": "{:?}"}}"#, e),
+    }
+}
+
+// Пакетный аукцион: параметры, заявки и итог последнего
+fn batch(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, _req: &Request) -> String {
+    let params = engine.batch_auction().copied().unwrap_or_default();
+    let book = engine.batch_book();
+    let orders: Vec<String> = book
+        .iter()
+        .map(|o| format!(r#"{{"user_idx": {}, "size": {}}}"#, o.user_idx, o.size))
+        .collect();
+    let last = engine.last_batch().map_or("null".to_string(), |r| {
+        format!(
+            r#"{{"slot": {}, "price": {}, "filled": {}, "rejected": {}, "volume": {}}}"#,
+            r.slot, r.price, r.filled, r.rejected, r.volume
+        )
+    });
+    format!(
+        r#"{{"enabled": {}, "batch_slots": {}, "max_deviation_bps": {}, "opened_slot": {}, "orders": [{}], "last": {}}}"#,
+        engine.batch_auction().is_some(),
+        params.batch_slots,
+        params.max_deviation_bps,
+        book.opened_slot().map_or("null".to_string(), |s| s.to_string()),
+        orders.join(", "),
+        last
+    )
+}
+
+// События для оператора и блокировка параметров после флаппинга
+fn alerts(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, _req: &Request) -> String {
+    let alerts: Vec<String> = engine
+        .operator_alerts()
+        .iter()
+        .map(|a| match a.kind {
+            AlertKind::DecisionFlapping { flips } => {
+                format!(r#"{{"slot": {}, "kind": "DecisionFlapping", "flips": {}}}"#, a.slot, flips)
+            }
+        })
+        .collect();
+    format!(
+        r#"{{"params_locked": {}, "alerts": [{}]}}"#,
+        engine.params_locked(),
+        alerts.join(", ")
+    )
+}
+
+// Делистинг: этап, текущая маржа и оставшиеся позиции
+fn wind_down(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, _req: &Request) -> String {
+    match engine.wind_down_progress(engine.risk_engine().current_slot) {
+        Some(p) => format!(
+            r#"{{"active": true, "phase": "{:?}", "ramp_bps": {}, "maintenance_margin_bps": {}, "initial_margin_bps": {}, "settle_slot": {}, "open_positions": {}, "open_interest": {}, "settlement_price": {}}}"#,
+            p.phase,
+            p.ramp_bps,
+            p.maintenance_margin_bps,
+            p.initial_margin_bps,
+            p.settle_slot,
+            p.open_positions,
+            p.open_interest,
+            p.settlement_price.map_or("null".to_string(), |p| p.to_string())
+        ),
+        None => r#"{"active": false}"#.to_string(),
+    }
+}
+
+fn account(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, req: &Request) -> String {
+    match req.param("idx").unwrap_or("")
+        .parse::<u16>()
+        .ok()
+        .and_then(|idx| engine.account_view(idx, 1_000_000))
+    {
+        Some(v) => format!(
+            r#"{{"idx": {}, "kind": "{:?}", "capital": {}, "pnl": {}, "unrealized_pnl": {}, "withdrawable_pnl": {}, "unvested_pnl": {}, "slots_to_vest": {}, "position_size": {}, "equity": {}, "margin_ratio_bps": {}, "undercollateralized": {}, "liquidation_price": {}, "funding_paid": {}, "sequence": {}}}"#,
+            v.idx,
+            v.kind,
+            v.capital,
+            v.pnl,
+            v.unrealized_pnl,
+            v.withdrawable_pnl,
+            v.unvested_pnl,
+            v.slots_to_vest,
+            v.position_size,
+            v.equity,
+            v.margin_ratio_bps,
+            v.undercollateralized,
+            v.liquidation_price.map(|p| p.to_string()).unwrap_or_else(|| "null".to_string()),
+            v.funding_paid,
+            engine.account_sequence(v.idx)
+        ),
+        None => r#"{"error": "Account not found"}"#.to_string(),
+    }
+}
+
+fn keeper_rewards(engine: &ClawcolatorEngine, _agent: &SimpleClawAgent, req: &Request) -> String {
+    match req.param("idx").unwrap_or("").parse::<u16>() {
+        Ok(keeper_idx) => format!(
+            r#"{{"keeper_idx": {}, "pending": {}, "pending_total": {}, "paid_total": {}}}"#,
+            keeper_idx,
+            engine.pending_keeper_rewards(keeper_idx),
+            engine.keeper_ledger().pending_total(),
+            engine.keeper_ledger().paid_total()
+        ),
+        Err(_) => r#"{"error": "Invalid keeper index"}"#.to_string(),
+    }
+}
+
+fn trade_preview(engine: &ClawcolatorEngine, agent: &SimpleClawAgent, req: &Request) -> String {
+    // Простой парсинг: ищем "size" и "oracle_price"
+    let size = req.json_int("size").unwrap_or(0);
+    let oracle_price = req.json_int("oracle_price").unwrap_or(1_000_000) as u64;
+    let user_idx = req.json_int("user_idx").unwrap_or(0) as u16;
+    // В режиме commit-reveal агент не должен видеть незаявленные сделки
+    if engine.commit_reveal().required {
+        return format!(r#"{{"error": "{:?}"}}"#, RiskError::Unauthorized);
+    }
+    
+    let context = engine.build_context(fresh_oracle(engine, oracle_price));
+    let trade = TradeRequest {
+        user_idx,
+        size,
+        requested_price: None,
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: None,
+    };
+    
+    match agent.decide_trade(&context, &trade) {
+        Ok(decision) => {
+            match decision {
+                TradeDecision::Accept { price, size } => {
                     format!(
-                        r#"{{"max_leverage_bps": {}, "max_position_size": {}, "spread_bps": {}, "funding_rate_bps_per_slot": {}, "min_margin_bps": {}, "active_capital_ratio_bps": {}}}"#,
-                        params.max_leverage_bps,
-                        params.max_position_size,
-                        params.spread_bps,
-                        params.funding_rate_bps_per_slot,
-                        params.min_margin_bps,
-                        params.active_capital_ratio_bps
+                        r#"{{"decision": "accept", "price": {}, "size": {}}}"#,
+                        price, size
                     )
                 }
-                Err(e) => format!(r#"{{"error": "{:?}"}}"#, e),
-            }
-        }
-        ("GET", "/risk") => {
-            let context = engine.build_context(1_000_000);
-            match agent.assess_risk(&context) {
-                Ok(assessment) => {
+                TradeDecision::Reject { reason } => {
                     format!(
-                        r#"{{"risk_level_bps": {}, "reduce_exposure": {}, "hedge": {}, "increase_margin": {}}}"#,
-                        assessment.risk_level_bps,
-                        assessment.actions.reduce_exposure,
-                        assessment.actions.hedge,
-                        assessment.actions.increase_margin.map(|m| m.to_string()).unwrap_or_else(|| "null".to_string())
+                        r#"{{"decision": "reject", "reason": "{:?}"}}"#,
+                        reason
                     )
                 }
-                Err(e) => format!(r#"{{"error": "{:?}"}}"#, e),
-            }
-        }
-        ("GET", "/anomalies") => {
-            let context = engine.build_context(1_000_000);
-            match agent.detect_anomalies(&context) {
-                Ok(response) => {
+                TradeDecision::RequestQuote { quote_price, max_size } => {
                     format!(
-                        r#"{{"anomaly_type": "{:?}", "severity_bps": {}, "freeze_market": {}, "stop_trading": {}, "initiate_shutdown": {}}}"#,
-                        response.anomaly_type,
-                        response.severity_bps,
-                        response.actions.freeze_market,
-                        response.actions.stop_trading,
-                        response.actions.initiate_shutdown
+                        r#"{{"decision": "quote", "quote_price": {}, "max_size": {}}}"#,
+                        quote_price, max_size
                     )
                 }
-                Err(e) => format!(r#"{{"error": "{:?}"}}"#, e),
             }
         }
-        ("POST", "/trade") => {
-            // Простой парсинг JSON из тела запроса
-            let body_start = request.find("\r\n\r\n").unwrap_or(0) + 4;
-            let body = &request[body_start..];
-            
-            // Простой парсинг: ищем "size" и "oracle_price"
-            let size = extract_json_value(body, "size").unwrap_or(0);
-            let oracle_price = extract_json_value(body, "oracle_price").unwrap_or(1_000_000) as u64;
-            let user_idx = extract_json_value(body, "user_idx").unwrap_or(0) as u16;
-            
-            let context = engine.build_context(oracle_price);
-            let request = TradeRequest {
-                user_idx,
-                size,
-                requested_price: None,
-            };
-            
-            match agent.decide_trade(&context, &request) {
-                Ok(decision) => {
-                    match decision {
-                        TradeDecision::Accept { price, size } => {
-                            format!(
-                                r#"{{"decision": "accept", "price": {}, "size": {}}}"#,
-                                price, size
-                            )
-                        }
-                        TradeDecision::Reject { reason } => {
-                            format!(
-                                r#"{{"decision": "reject", "reason": "{:?}"}}"#,
-                                reason
-                            )
-                        }
-                        TradeDecision::RequestQuote { quote_price, max_size } => {
-                            format!(
-                                r#"{{"decision": "quote", "quote_price": {}, "max_size": {}}}"#,
-                                quote_price, max_size
-                            )
-                        }
-                    }
-                }
-                Err(e) => format!(r#"{{"error": "{:?}"}}"#, e),
-            }
-        }
-        _ => {
-            format!(
-                r#"{{"error": "Not found", "path": "{}", "method": "{}"}}"#,
-                path, method
-            )
-        }
+        Err(e) => format!(r#"{{"error": "{:?}"}}"#, e),
     }
 }
 
-fn extract_json_value(json: &str, key: &str) -> Option<i128> {
-    let pattern = format!("\"{}\":", key);
-    if let Some(start) = json.find(&pattern) {
-        let value_start = start + pattern.len();
-        let value_str = json[value_start..]
-            .trim_start()
-            .split(|c: char| c == ',' || c == '}' || c.is_whitespace())
-            .next()?;
-        value_str.parse().ok()
-    } else {
-        None
+// Полный прогон сделки (агент, проверки протокола, маржа) без изменения состояния
+fn simulate_trade(engine: &ClawcolatorEngine, agent: &SimpleClawAgent, req: &Request) -> String {
+    let user_idx = req.json_int("user_idx").unwrap_or(0) as u16;
+    let size = req.json_int("size").unwrap_or(0);
+    let oracle_price = req.json_int("oracle_price").unwrap_or(1_000_000) as u64;
+    let oracle = fresh_oracle(engine, oracle_price);
+    let slot = engine.risk_engine().current_slot;
+    match engine.execute_trade_dry_run(agent, user_idx, oracle, size, slot) {
+        Ok(s) => format!(
+            r#"{{"price": {}, "size": {}, "vamm": {}, "fee": {}, "position_size": {}, "equity": {}, "margin_ratio_bps": {}, "liquidation_price": {}, "meta": {}}}"#,
+            s.price,
+            s.size,
+            s.vamm,
+            s.fee,
+            s.position_size,
+            s.equity,
+            s.margin_ratio_bps,
+            s.liquidation_price.map(|p| p.to_string()).unwrap_or_else(|| "null".to_string()),
+            meta_json(s.meta.as_ref())
+        ),
+        Err(r) => format!(
+            r#"{{"error": "{:?}", "rejection": {}}}"#,
+            r.error,
+            rejection_json(Some(&r))
+        ),
     }
 }
+
+// Локальный сервер не имеет оракула: цена считается опубликованной в текущем слоте
+fn fresh_oracle(engine: &ClawcolatorEngine, price: u64) -> OraclePrice {
+    OraclePrice::new(price, 0, engine.risk_engine().current_slot)
+}
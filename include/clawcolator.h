@@ -0,0 +1,102 @@
+/*
+ * C API of the Clawcolator engine (percolator crate, `ffi` feature).
+ *
+ * Build: cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Functions return CLAW_OK or a negative CLAW_ERR_* code. The engine
+ * handle is not thread-safe; callers serialise access.
+ */
+
+#ifndef CLAWCOLATOR_H
+#define CLAWCOLATOR_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CLAW_OK 0
+#define CLAW_ERR_NULL (-1)
+#define CLAW_ERR_INSUFFICIENT_BALANCE (-2)
+#define CLAW_ERR_UNDERCOLLATERALIZED (-3)
+#define CLAW_ERR_UNAUTHORIZED (-4)
+#define CLAW_ERR_INVALID_MATCHING_ENGINE (-5)
+#define CLAW_ERR_PNL_NOT_WARMED_UP (-6)
+#define CLAW_ERR_OVERFLOW (-7)
+#define CLAW_ERR_ACCOUNT_NOT_FOUND (-8)
+#define CLAW_ERR_NOT_AN_LP_ACCOUNT (-9)
+#define CLAW_ERR_POSITION_SIZE_MISMATCH (-10)
+#define CLAW_ERR_ACCOUNT_KIND_MISMATCH (-11)
+
+#define CLAW_DECISION_REJECT 0
+#define CLAW_DECISION_ACCEPT 1
+
+typedef struct ClawEngine ClawEngine;
+
+typedef struct {
+    uint64_t current_slot;
+    uint64_t oracle_price;
+    uint64_t vault;
+    uint64_t insurance_balance;
+    uint64_t total_capital;
+    uint64_t total_open_interest;
+    uint64_t lp_liquidity;
+    uint64_t oracle_divergence_bps;
+    bool risk_reduction_mode;
+} ClawContext;
+
+typedef struct {
+    uint16_t user_idx;
+    int64_t size;
+} ClawTradeRequest;
+
+typedef struct {
+    int32_t kind; /* CLAW_DECISION_* */
+    uint64_t price;
+    int64_t size;
+} ClawDecision;
+
+typedef struct {
+    uint64_t current_slot;
+    uint64_t vault;
+    uint64_t insurance_balance;
+    uint64_t total_capital;
+    uint64_t total_open_interest;
+} ClawStatus;
+
+typedef struct {
+    uint16_t idx;
+    bool is_lp;
+    uint64_t capital;
+    int64_t pnl;
+    int64_t position_size;
+    uint64_t equity;
+    uint64_t margin_ratio_bps;
+    bool undercollateralized;
+} ClawAccount;
+
+/* Fill `out` and return 0; non-zero counts as an agent error */
+typedef int32_t (*ClawDecisionFn)(void *user_data, const ClawContext *context,
+                                  const ClawTradeRequest *request, ClawDecision *out);
+
+ClawEngine *claw_engine_new(uint64_t maintenance_margin_bps, uint64_t initial_margin_bps,
+                            uint64_t trading_fee_bps, uint64_t warmup_period_slots);
+void claw_engine_free(ClawEngine *engine);
+
+int32_t claw_set_decision_callback(ClawEngine *engine, ClawDecisionFn decide, void *user_data);
+int32_t claw_add_account(ClawEngine *engine, bool is_lp, uint64_t deposit, uint16_t *out_idx);
+int32_t claw_execute_trade(ClawEngine *engine, uint16_t user_idx, uint64_t oracle_price,
+                           int64_t size, uint64_t now_slot);
+int32_t claw_crank(ClawEngine *engine, uint16_t caller_idx, uint64_t now_slot,
+                   uint64_t oracle_price);
+int32_t claw_status(const ClawEngine *engine, ClawStatus *out);
+int32_t claw_account(const ClawEngine *engine, uint16_t idx, uint64_t oracle_price,
+                     ClawAccount *out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CLAWCOLATOR_H */
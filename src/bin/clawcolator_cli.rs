@@ -0,0 +1,394 @@
+//! clawcolator-cli: run a TOML scenario against a built-in agent
+//!
+//! Usage: cargo run --features cli --bin clawcolator-cli -- <scenario.toml>
+//!        [--agent passthrough|spread|conservative] [--json [FILE]]
+//!
+//! A scenario lists accounts, a price path and a trade sequence, plus
+//! optional `[risk]`, `[market]` and `[bounds.*]` sections in the config
+//! file format (see `clawcolator::config` and `scenarios/basic.toml`). Step `i` runs at slot `i + 1`
+//! and oracle price `prices[i]`: the step's trades execute in order, then
+//! the first account cranks. The final state and the event log are
+//! printed as text, or as JSON to stdout or FILE with `--json`.
+
+use std::fmt::Write as _;
+use std::process::ExitCode;
+
+use percolator::clawcolator::config::{BoundsConfig, EngineConfig, MarketConfig, RiskConfig};
+use percolator::clawcolator::*;
+use percolator::{Result, MAX_ORACLE_PRICE};
+use serde::Deserialize;
+
+/// Scenario file contents
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+    #[serde(default)]
+    name: String,
+    /// Built-in agent (overridden by `--agent`)
+    #[serde(default = "default_agent")]
+    agent: String,
+    #[serde(default)]
+    risk: RiskConfig,
+    /// Market params the built-in agent publishes and quotes with
+    #[serde(default)]
+    market: MarketConfig,
+    #[serde(default)]
+    bounds: BoundsConfig,
+    accounts: Vec<ScenarioAccount>,
+    prices: Vec<u64>,
+    #[serde(default)]
+    trades: Vec<ScenarioTrade>,
+}
+
+fn default_agent() -> String {
+    "passthrough".into()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioAccount {
+    /// "lp" or "user"
+    kind: String,
+    deposit: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioTrade {
+    /// Index into the price path
+    step: usize,
+    /// Index into `accounts`
+    account: usize,
+    size: i64,
+}
+
+/// Built-in agent strategies
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Strategy {
+    /// Fill everything at the oracle price
+    Passthrough,
+    /// Quote the oracle plus/minus `spread_bps`, capped at `max_position_size`
+    Spread,
+    /// `Spread`, plus a leverage cap and no trading in risk-reduction mode
+    Conservative,
+}
+
+impl Strategy {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "passthrough" => Some(Self::Passthrough),
+            "spread" => Some(Self::Spread),
+            "conservative" => Some(Self::Conservative),
+            _ => None,
+        }
+    }
+}
+
+struct BuiltinAgent {
+    strategy: Strategy,
+    market: MarketConfig,
+}
+
+impl OpenClawAgent for BuiltinAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        let reject = |reason| Ok(TradeDecision::Reject { reason });
+        if self.strategy == Strategy::Passthrough {
+            return Ok(TradeDecision::Accept {
+                price: context.oracle_price,
+                size: request.size,
+            });
+        }
+        if self.strategy == Strategy::Conservative && context.risk_reduction_mode {
+            return reject(TradeRejectionReason::RiskLimit);
+        }
+        if request.size.unsigned_abs() > self.market.max_position_size {
+            return reject(TradeRejectionReason::RiskLimit);
+        }
+        if self.strategy == Strategy::Conservative {
+            let notional = request.size.unsigned_abs() * context.oracle_price as u128 / 1_000_000;
+            if context.total_capital == 0
+                || notional * 10_000 / context.total_capital > self.market.max_leverage_bps as u128
+            {
+                return reject(TradeRejectionReason::RiskLimit);
+            }
+        }
+        let spread = (context.oracle_price as u128 * self.market.spread_bps as u128 / 10_000) as u64;
+        let price = if request.size > 0 {
+            context.oracle_price.saturating_add(spread)
+        } else {
+            context.oracle_price.saturating_sub(spread)
+        };
+        if price == 0 || price > MAX_ORACLE_PRICE {
+            return reject(TradeRejectionReason::MarketConditions);
+        }
+        Ok(TradeDecision::Accept {
+            price,
+            size: request.size,
+        })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(self.market.market_params())
+    }
+
+    fn agent_capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities::CORE
+    }
+}
+
+/// Something that happened while running the scenario
+enum Event {
+    Trade {
+        slot: u64,
+        account: u16,
+        size: i64,
+        result: core::result::Result<(), String>,
+    },
+    Crank {
+        slot: u64,
+        price: u64,
+        liquidations: u32,
+        force_closed: u16,
+    },
+    CrankFailed {
+        slot: u64,
+        error: String,
+    },
+}
+
+impl Event {
+    fn text(&self) -> String {
+        match self {
+            Event::Trade { slot, account, size, result } => match result {
+                Ok(()) => format!("slot {slot}: trade #{account} {size} ok"),
+                Err(e) => format!("slot {slot}: trade #{account} {size} rejected ({e})"),
+            },
+            Event::Crank { slot, price, liquidations, force_closed } => format!(
+                "slot {slot}: crank @ {price} ({liquidations} liquidated, {force_closed} force-closed)"
+            ),
+            Event::CrankFailed { slot, error } => format!("slot {slot}: crank failed ({error})"),
+        }
+    }
+
+    fn json(&self) -> String {
+        match self {
+            Event::Trade { slot, account, size, result } => format!(
+                r#"{{"type": "trade", "slot": {}, "account": {}, "size": {}, "ok": {}, "error": {}}}"#,
+                slot,
+                account,
+                size,
+                result.is_ok(),
+                result.as_ref().err().map_or("null".into(), |e| format!("\"{e}\""))
+            ),
+            Event::Crank { slot, price, liquidations, force_closed } => format!(
+                r#"{{"type": "crank", "slot": {}, "price": {}, "liquidations": {}, "force_closed": {}}}"#,
+                slot, price, liquidations, force_closed
+            ),
+            Event::CrankFailed { slot, error } => format!(
+                r#"{{"type": "crank_failed", "slot": {}, "error": "{}"}}"#,
+                slot, error
+            ),
+        }
+    }
+}
+
+/// Outcome of a scenario run
+struct Run {
+    engine: Box<ClawcolatorEngine>,
+    accounts: Vec<u16>,
+    events: Vec<Event>,
+    final_price: u64,
+}
+
+fn run(scenario: &Scenario, strategy: Strategy) -> core::result::Result<Run, String> {
+    if scenario.accounts.is_empty() || scenario.prices.is_empty() {
+        return Err("scenario needs at least one account and one price".into());
+    }
+    let agent = BuiltinAgent {
+        strategy,
+        market: scenario.market,
+    };
+    let config = EngineConfig {
+        risk: scenario.risk,
+        market: scenario.market,
+        bounds: scenario.bounds,
+    };
+    let mut engine = config.build_engine().map_err(|e| e.to_string())?;
+    let mut accounts = Vec::new();
+    for (i, account) in scenario.accounts.iter().enumerate() {
+        let risk = engine.risk_engine_mut();
+        let idx = match account.kind.as_str() {
+            "lp" => risk.add_lp([0; 32], [0; 32], 0),
+            "user" => risk.add_user(0),
+            other => return Err(format!("account {i}: unknown kind \"{other}\"")),
+        }
+        .and_then(|idx| risk.deposit(idx, account.deposit as u128, 0).map(|_| idx))
+        .map_err(|e| format!("account {i}: {e:?}"))?;
+        accounts.push(idx);
+    }
+    if let Some(t) = scenario
+        .trades
+        .iter()
+        .find(|t| t.step >= scenario.prices.len() || t.account >= accounts.len())
+    {
+        return Err(format!("trade at step {} on account {} is out of range", t.step, t.account));
+    }
+    engine
+        .update_market_params(&agent)
+        .map_err(|e| format!("market params: {e:?}"))?;
+
+    let mut events = Vec::new();
+    for (step, &price) in scenario.prices.iter().enumerate() {
+        let slot = step as u64 + 1;
+        let oracle = OraclePrice::new(price, 0, slot);
+        for trade in scenario.trades.iter().filter(|t| t.step == step) {
+            let account = accounts[trade.account];
+            let result = engine
+                .execute_trade(&agent, account, oracle, trade.size as i128, slot)
+                .map_err(|e| format!("{e:?}"));
+            events.push(Event::Trade { slot, account, size: trade.size, result });
+        }
+        events.push(match engine.crank(accounts[0], slot, price, None) {
+            Ok(outcome) => Event::Crank {
+                slot,
+                price,
+                liquidations: outcome.num_liquidations,
+                force_closed: outcome.force_realize_closed,
+            },
+            Err(e) => Event::CrankFailed { slot, error: format!("{e:?}") },
+        });
+    }
+    Ok(Run {
+        engine,
+        accounts,
+        events,
+        final_price: *scenario.prices.last().unwrap_or(&0),
+    })
+}
+
+fn report_text(name: &str, strategy: Strategy, run: &Run) -> String {
+    let risk = run.engine.risk_engine();
+    let mut out = String::new();
+    let _ = writeln!(out, "scenario: {name} (agent: {strategy:?})");
+    for event in &run.events {
+        let _ = writeln!(out, "  {}", event.text());
+    }
+    let _ = writeln!(
+        out,
+        "vault {} | insurance {} | capital {} | open interest {}",
+        risk.vault.get(),
+        risk.insurance_fund.balance.get(),
+        risk.c_tot.get(),
+        risk.total_open_interest.get()
+    );
+    for &idx in &run.accounts {
+        if let Some(v) = run.engine.account_view(idx, run.final_price) {
+            let _ = writeln!(
+                out,
+                "  #{} {:?}: capital {} pnl {} position {} equity {}{}",
+                v.idx,
+                v.kind,
+                v.capital,
+                v.pnl,
+                v.position_size,
+                v.equity,
+                if v.undercollateralized { " UNDERCOLLATERALIZED" } else { "" }
+            );
+        }
+    }
+    out
+}
+
+fn report_json(name: &str, strategy: Strategy, run: &Run) -> String {
+    let risk = run.engine.risk_engine();
+    let accounts: Vec<String> = run
+        .accounts
+        .iter()
+        .filter_map(|&idx| run.engine.account_view(idx, run.final_price))
+        .map(|v| {
+            format!(
+                r#"{{"idx": {}, "kind": "{:?}", "capital": {}, "pnl": {}, "position_size": {}, "equity": {}, "undercollateralized": {}}}"#,
+                v.idx, v.kind, v.capital, v.pnl, v.position_size, v.equity, v.undercollateralized
+            )
+        })
+        .collect();
+    let events: Vec<String> = run.events.iter().map(Event::json).collect();
+    format!(
+        r#"{{"scenario": "{}", "agent": "{:?}", "final_price": {}, "vault": {}, "insurance": {}, "total_capital": {}, "total_open_interest": {}, "accounts": [{}], "events": [{}]}}"#,
+        name,
+        strategy,
+        run.final_price,
+        risk.vault.get(),
+        risk.insurance_fund.balance.get(),
+        risk.c_tot.get(),
+        risk.total_open_interest.get(),
+        accounts.join(", "),
+        events.join(", ")
+    )
+}
+
+const USAGE: &str =
+    "usage: clawcolator-cli <scenario.toml> [--agent passthrough|spread|conservative] [--json [FILE]]";
+
+fn main() -> ExitCode {
+    let mut path = None;
+    let mut agent = None;
+    let mut json: Option<Option<String>> = None;
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--agent" => agent = args.next(),
+            "--json" => json = Some(args.next_if(|a| !a.starts_with("--"))),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let scenario: Scenario = match std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|s| toml::from_str(&s).map_err(|e| e.to_string()))
+    {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let agent_name = agent.unwrap_or_else(|| scenario.agent.clone());
+    let Some(strategy) = Strategy::parse(&agent_name) else {
+        eprintln!("unknown agent \"{agent_name}\"\n{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let name = if scenario.name.is_empty() { path.as_str() } else { scenario.name.as_str() };
+
+    let run = match run(&scenario, strategy) {
+        Ok(run) => run,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match json {
+        None => print!("{}", report_text(name, strategy, &run)),
+        Some(None) => println!("{}", report_json(name, strategy, &run)),
+        Some(Some(file)) => {
+            if let Err(e) = std::fs::write(&file, report_json(name, strategy, &run) + "\n") {
+                eprintln!("{file}: {e}");
+                return ExitCode::FAILURE;
+            }
+            print!("{}", report_text(name, strategy, &run));
+        }
+    }
+    ExitCode::SUCCESS
+}
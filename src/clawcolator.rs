@@ -6,12 +6,169 @@
 //! while the protocol serves as a strict enforcement and protection layer.
 
 #![no_std]
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "ffi"), forbid(unsafe_code))]
 
 // Re-export types we need from parent module
 use crate::{
-    RiskEngine, RiskParams, RiskError, Result, MatchingEngine, TradeExecution,
-    MAX_ORACLE_PRICE, MAX_POSITION_ABS, U128, I128,
+    RiskEngine, RiskParams, RiskError, Result, MatchingEngine, TradeExecution, CrankOutcome,
+    Account, AccountKind, MAX_ACCOUNTS, MAX_ORACLE_PRICE, MAX_POSITION_ABS, U128, I128,
+};
+use crate::metering::{self, MeteredOp};
+
+pub mod hedging;
+pub use hedging::{HedgeExecutor, HedgeFill, HedgePosition};
+
+pub mod oracle;
+
+pub mod keeper;
+pub use keeper::{KeeperLedger, KeeperRewardParams};
+
+pub mod epochs;
+pub use epochs::{
+    AgentScorecard, EpochHistory, EpochReport, LiquidationStats, DEFAULT_EPOCH_SLOTS,
+    EPOCH_HISTORY_LEN,
+};
+
+pub mod fees;
+pub use fees::{
+    price_improvement_bps, FeeSplit, FeeSplitBounds, FeeTotals, MakerRebateParams, DEFAULT_FEE_SPLIT,
+};
+
+pub mod lp_pool;
+pub use lp_pool::{LpPool, PoolPosition, PoolProvider};
+
+pub mod operator;
+pub use operator::{OperatorAuthority, OperatorCommand, SignatureVerifier, OPERATOR_MESSAGE_LEN};
+
+pub mod governance;
+pub use governance::{
+    Governance, GovernanceConfig, GovernanceVote, Proposal, ProposalAction,
+    GOVERNANCE_MESSAGE_LEN, MAX_GOVERNORS, MAX_PROPOSALS,
+};
+
+pub mod math;
+pub use math::Rounding;
+use math::{apply_bps, mul_div, offset_bps, ratio_bps};
+
+pub mod mathx;
+pub use mathx::EwmaVolatility;
+
+#[cfg(feature = "analysis")]
+pub mod analysis;
+#[cfg(feature = "simd")]
+pub mod batch;
+#[cfg(feature = "analysis")]
+pub mod monte_carlo;
+#[cfg(feature = "analysis")]
+pub mod tuner;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "python")]
+pub mod python;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "anchor")]
+pub mod anchor;
+
+#[cfg(feature = "indexer")]
+pub mod indexer;
+
+#[cfg(feature = "rl")]
+pub mod rl;
+
+#[cfg(feature = "config")]
+pub mod config;
+
+pub mod extensions;
+pub use extensions::{ContextExtensions, ExtensionKey, ExtensionValue, MAX_CONTEXT_EXTENSIONS};
+
+pub mod margin;
+pub use margin::MarginSummary;
+use margin::margin_required;
+
+pub mod stress;
+pub use stress::StressProjection;
+
+pub mod impact;
+pub use impact::{ImpactCurve, ImpactModel, DEFAULT_IMPACT_MODEL};
+use impact::offset_price;
+
+pub mod vamm;
+pub use vamm::{vamm_quote, VammBounds};
+
+pub mod funding;
+pub use funding::{FundingAccrual, FundingFlows, FundingLedger};
+
+pub mod var;
+pub use var::{PriceHistory, PriceSample, ValueAtRisk, DEFAULT_VAR_CONFIDENCE_BPS, PRICE_HISTORY_LEN};
+
+pub mod outcome;
+pub use outcome::{
+    CounterQuote, RejectionCause, TradeLimits, TradeOutcome, TradeRejection, TradeSide, TradeSimulation,
+};
+
+pub mod auction;
+pub use auction::{BatchAuctionParams, BatchBook, BatchOrder, BatchReport, BatchSummary, BATCH_BOOK_LEN};
+
+pub mod commit;
+pub use commit::{
+    CommitBook, CommitRevealParams, TradeCommitment, TradeReveal, COMMIT_BOOK_LEN,
+    MAX_COMMITS_PER_ACCOUNT,
+};
+
+pub mod orders;
+pub use orders::{ClientOrder, ClientOrderLog, CLIENT_ORDER_LOG_LEN, DEFAULT_CLIENT_ORDER_WINDOW_SLOTS};
+
+pub mod lending;
+pub use lending::{LendingBook, LendingBounds, LendingPolicy, LendingTotals, Loan, DEFAULT_LENDING_POLICY};
+
+pub mod wind_down;
+pub use wind_down::{WindDown, WindDownParams, WindDownPhase, WindDownProgress};
+pub mod dead_man;
+pub use dead_man::{DeadManParams, DeadManStage, DeadManStatus};
+use dead_man::WidenedMargins;
+pub mod flapping;
+pub use flapping::{AlertKind, AlertLog, Flip, FlipKind, FlipLog, FlappingGuard, OperatorAlert};
+mod trace;
+pub mod account_ref;
+pub use account_ref::{AccountRef, AccountRefs};
+pub mod liquidation_index;
+pub use liquidation_index::{LiquidationIndex, LIQ_BUCKET_COUNT, LIQ_CANDIDATES_PER_CRANK};
+pub mod liquidation_auction;
+pub use liquidation_auction::{
+    AuctionFill, AuctionTotals, LiquidationAuctionParams, LiquidationAuctions, LiquidationLot,
+    MAX_LIQUIDATION_AUCTIONS,
+};
+pub mod backstop;
+pub use backstop::{BackstopLp, BackstopParams, BackstopRegistry, MAX_BACKSTOP_LPS};
+pub mod bankruptcy;
+pub use bankruptcy::{
+    Bankruptcy, BankruptcyCause, BankruptcyLedger, BankruptcyTotals, BANKRUPTCY_LEDGER_LEN,
+};
+pub mod referral;
+pub use referral::{ReferralBounds, ReferralLedger, ReferralLink, ReferralPolicy};
+pub mod cpi;
+pub use cpi::{CpiInvoker, CpiMatcher, MatchRequest, MATCH_DISCRIMINATOR, MATCH_REQUEST_LEN, MATCH_RETURN_LEN};
+pub mod events;
+pub use events::{EventLog, EventSink, LiquidationEvent, MarketEvent, MarketParamsEvent, ProgramLogSink, EVENT_LOG_LEN};
+pub mod act;
+pub use act::{
+    certify, CheckResult, ConformanceCheck, ConformanceReport, Violation, ViolationKind,
+    CONFORMANCE_CHECK_COUNT,
+};
+pub mod simulation;
+pub use simulation::{
+    replay, simulate, PriceModel, SimulationParams, SimulationReport, MAX_SIM_STEPS, MAX_SIM_TRADERS,
+};
+
+pub mod withdrawals;
+pub use withdrawals::{
+    QueuedWithdrawal, WithdrawalBounds, WithdrawalPolicy, WithdrawalQueue, WithdrawalQueueStatus,
+    WithdrawalStatus, DEFAULT_WITHDRAWAL_POLICY,
 };
 
 // Helper function (mirrored from percolator.rs)
@@ -29,7 +186,12 @@ fn saturating_abs_i128(val: i128) -> i128 {
 // ============================================================================
 
 /// Read-only context provided to the agent for decision-making
+///
+/// Non-exhaustive so fields can be added without breaking agents; build
+/// one outside the engine with `AgentContext::builder()`. Telemetry that
+/// only some agents care about goes into `extensions`.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct AgentContext {
     /// Current slot
     pub current_slot: u64,
@@ -37,6 +199,15 @@ pub struct AgentContext {
     /// Oracle price
     pub oracle_price: u64,
     
+    /// Oracle confidence interval (same units as price)
+    pub oracle_confidence: u64,
+    
+    /// Slot at which the oracle price was published
+    pub oracle_publish_slot: u64,
+    
+    /// Whether the oracle price is older than the protocol staleness bound
+    pub oracle_stale: bool,
+    
     /// Vault balance
     pub vault: u128,
     
@@ -58,8 +229,190 @@ pub struct AgentContext {
     /// Whether system is in risk-reduction-only mode
     pub risk_reduction_mode: bool,
     
+    /// Conditions holding the system in risk-reduction-only mode
+    pub risk_reduction_triggers: RiskReductionTriggers,
+    
+    /// Whether the operator has vetoed agent parameter updates
+    pub params_vetoed: bool,
+    
+    /// Whether agent parameter updates are locked after flapping
+    pub params_locked: bool,
+    
+    /// Whether the agent or a matching engine fills trades
+    pub market_mode: MarketMode,
+    
     /// Last crank slot
     pub last_crank_slot: u64,
+    
+    /// Recent anomaly checks (most recent last)
+    pub anomaly_history: AnomalyHistory,
+    
+    /// Divergence between primary and secondary oracle at the last crank (bps)
+    pub oracle_divergence_bps: u64,
+    
+    /// Anomaly detected by the protocol itself (independent of the agent)
+    pub protocol_anomaly: Option<AnomalyType>,
+    
+    /// Withdrawal queue summary
+    pub withdrawal_queue: WithdrawalQueueStatus,
+    
+    /// Price-impact model the engine enforces on fills
+    pub impact_model: ImpactModel,
+    
+    /// Liquidity behind the agent's quotes (LP equity at the oracle)
+    pub lp_liquidity: u128,
+    
+    /// Largest user share of open interest, as of the last crank
+    pub concentration: ConcentrationStatus,
+    
+    /// Oracle price at which the agent's LP account would be liquidated
+    pub lp_liquidation_price: Option<u64>,
+    
+    /// VaR/ES of the LP inventory and open interest, as of the last
+    /// advancing crank
+    pub value_at_risk: ValueAtRisk,
+    
+    /// Slots since the agent's last heartbeat (None = never)
+    pub heartbeat_age_slots: Option<u64>,
+    
+    /// Lifetime bankruptcy totals (holes and how they were covered)
+    pub bankruptcies: BankruptcyTotals,
+    
+    /// Typed extension slots (see `ClawcolatorEngine::set_context_extension`)
+    pub extensions: ContextExtensions,
+}
+
+impl Default for AgentContext {
+    fn default() -> Self {
+        Self {
+            current_slot: 0,
+            oracle_price: 0,
+            oracle_confidence: 0,
+            oracle_publish_slot: 0,
+            oracle_stale: false,
+            vault: 0,
+            insurance_balance: 0,
+            total_capital: 0,
+            total_positive_pnl: 0,
+            total_open_interest: 0,
+            risk_params: RiskParams {
+                warmup_period_slots: 0,
+                maintenance_margin_bps: 0,
+                initial_margin_bps: 0,
+                trading_fee_bps: 0,
+                max_accounts: 0,
+                new_account_fee: U128::ZERO,
+                risk_reduction_threshold: U128::ZERO,
+                maintenance_fee_per_slot: U128::ZERO,
+                max_crank_staleness_slots: 0,
+                liquidation_fee_bps: 0,
+                liquidation_fee_cap: U128::ZERO,
+                liquidation_buffer_bps: 0,
+                min_liquidation_abs: U128::ZERO,
+            },
+            risk_reduction_mode: false,
+            risk_reduction_triggers: RiskReductionTriggers::default(),
+            params_vetoed: false,
+            params_locked: false,
+            market_mode: MarketMode::default(),
+            last_crank_slot: 0,
+            anomaly_history: AnomalyHistory::default(),
+            oracle_divergence_bps: 0,
+            protocol_anomaly: None,
+            withdrawal_queue: WithdrawalQueueStatus::default(),
+            impact_model: DEFAULT_IMPACT_MODEL,
+            lp_liquidity: 0,
+            concentration: ConcentrationStatus::default(),
+            lp_liquidation_price: None,
+            value_at_risk: ValueAtRisk::default(),
+            heartbeat_age_slots: None,
+            bankruptcies: BankruptcyTotals::default(),
+            extensions: ContextExtensions::default(),
+        }
+    }
+}
+
+/// Builder for `AgentContext` (tests, simulators, agent harnesses)
+///
+/// Unset fields keep their `Default` values, including zeroed risk params.
+#[derive(Clone, Debug, Default)]
+pub struct AgentContextBuilder {
+    context: AgentContext,
+}
+
+macro_rules! context_setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            #[doc = concat!("Set `", stringify!($field), "`")]
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.context.$field = $field;
+                self
+            }
+        )*
+    };
+}
+
+impl AgentContextBuilder {
+    context_setters! {
+        current_slot: u64,
+        oracle_price: u64,
+        oracle_confidence: u64,
+        oracle_publish_slot: u64,
+        oracle_stale: bool,
+        vault: u128,
+        insurance_balance: u128,
+        total_capital: u128,
+        total_positive_pnl: u128,
+        total_open_interest: u128,
+        risk_params: RiskParams,
+        risk_reduction_mode: bool,
+        risk_reduction_triggers: RiskReductionTriggers,
+        params_vetoed: bool,
+        params_locked: bool,
+        market_mode: MarketMode,
+        last_crank_slot: u64,
+        anomaly_history: AnomalyHistory,
+        oracle_divergence_bps: u64,
+        protocol_anomaly: Option<AnomalyType>,
+        withdrawal_queue: WithdrawalQueueStatus,
+        impact_model: ImpactModel,
+        lp_liquidity: u128,
+        concentration: ConcentrationStatus,
+        lp_liquidation_price: Option<u64>,
+        value_at_risk: ValueAtRisk,
+        heartbeat_age_slots: Option<u64>,
+        bankruptcies: BankruptcyTotals,
+    }
+    
+    /// Set extension `key` to `value`
+    ///
+    /// Fails with `Overflow` when every extension slot is taken.
+    pub fn extension<T: ExtensionValue>(mut self, key: ExtensionKey<T>, value: T) -> Result<Self> {
+        self.context.extensions.insert(key, value)?;
+        Ok(self)
+    }
+    
+    pub fn build(self) -> AgentContext {
+        self.context
+    }
+}
+
+impl AgentContext {
+    /// Builder starting from `AgentContext::default()`
+    pub fn builder() -> AgentContextBuilder {
+        AgentContextBuilder::default()
+    }
+    
+    /// Impact (bps) of a trade of `size` at the current oracle price
+    pub fn impact_bps(&self, size: i128) -> u64 {
+        let notional = math::notional(size.unsigned_abs(), self.oracle_price, Rounding::Down);
+        self.impact_model.impact_bps(notional, self.lp_liquidity)
+    }
+    
+    /// Oracle price moved by the impact of `size`
+    pub fn impact_price(&self, size: i128) -> u64 {
+        self.impact_model.impact_price(self.oracle_price, size, self.lp_liquidity)
+    }
 }
 
 // ============================================================================
@@ -77,6 +430,44 @@ pub struct TradeRequest {
     
     /// Requested price (optional, agent may override)
     pub requested_price: Option<u64>,
+    
+    /// Client-chosen ID for duplicate suppression (see `execute_trade_with_id`)
+    pub client_order_id: Option<u64>,
+    
+    /// Account sequence number; must exceed the last one the account used
+    /// (see `account_sequence`)
+    pub sequence: Option<u64>,
+    
+    /// Last slot the request may execute in; later it is rejected as stale
+    pub valid_until_slot: Option<u64>,
+}
+
+/// Executed trade, as filled by the engine
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TradeFill {
+    /// Slot of the fill
+    pub slot: u64,
+    
+    /// Taker (user) account index
+    pub user_idx: u16,
+    
+    /// Maker (LP) account index
+    pub lp_idx: u16,
+    
+    /// Execution price
+    pub price: u64,
+    
+    /// Filled size from the user's side (positive = long, negative = short)
+    pub size: i128,
+    
+    /// Filled by the vAMM fallback rather than the agent
+    pub vamm: bool,
+    
+    /// Rationale the agent gave for the decision behind this fill
+    pub meta: Option<DecisionMeta>,
+    
+    /// Client order ID the trade was submitted with
+    pub client_order_id: Option<u64>,
 }
 
 /// Agent's decision about a trade
@@ -105,6 +496,24 @@ pub enum TradeDecision {
     },
 }
 
+/// Agent's rationale for a trade decision, for attributing outcomes
+///
+/// Tags are opaque to the protocol; the agent operator owns their meaning.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecisionMeta {
+    /// Agent's confidence in the decision (0-10_000 bps)
+    pub confidence_bps: u16,
+    
+    /// Strategy that made the decision
+    pub strategy: u32,
+    
+    /// Version of the model or strategy parameters
+    pub model_version: u32,
+    
+    /// Short reason code
+    pub reason_code: u16,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TradeRejectionReason {
     /// Market conditions not favorable
@@ -121,6 +530,171 @@ pub enum TradeRejectionReason {
     Other,
 }
 
+// ============================================================================
+// Account Tiers
+// ============================================================================
+
+/// Number of account tiers
+pub const ACCOUNT_TIER_COUNT: usize = 3;
+
+/// Risk tier assigned to an account
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountTier {
+    /// Default tier for user accounts
+    Retail = 0,
+    /// Professional users with relaxed limits
+    Pro = 1,
+    /// Liquidity providers (LP accounts only)
+    LP = 2,
+}
+
+impl AccountTier {
+    /// Index into `MarketParams::tier_limits`
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Tier assigned to an account slot
+///
+/// The stored account_id guards against slot reuse: a new account in a
+/// recycled slot starts as Retail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct TierStamp {
+    /// Account id the tier was assigned to (u64::MAX = never assigned)
+    account_id: u64,
+    
+    /// Assigned tier
+    tier: AccountTier,
+}
+
+impl TierStamp {
+    const NONE: Self = Self {
+        account_id: u64::MAX,
+        tier: AccountTier::Retail,
+    };
+    
+    /// Tier of `account_id` (Retail unless assigned to it)
+    fn tier_of(&self, account_id: u64) -> AccountTier {
+        if self.account_id == account_id {
+            self.tier
+        } else {
+            AccountTier::Retail
+        }
+    }
+}
+
+/// Per-tier limits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TierLimits {
+    /// Maximum leverage (in basis points, e.g., 1000 = 10x)
+    pub max_leverage_bps: u64,
+    
+    /// Maximum absolute position per account in this tier
+    pub max_position_size: u128,
+}
+
+/// Default tier limits (no tier is tighter than the global market limits)
+pub const DEFAULT_TIER_LIMITS: [TierLimits; ACCOUNT_TIER_COUNT] = [
+    TierLimits { max_leverage_bps: 1000, max_position_size: MAX_POSITION_ABS }, // Retail
+    TierLimits { max_leverage_bps: 1000, max_position_size: MAX_POSITION_ABS }, // Pro
+    TierLimits { max_leverage_bps: 1000, max_position_size: MAX_POSITION_ABS }, // LP
+];
+
+// ============================================================================
+// Sub-Accounts (isolated margin)
+// ============================================================================
+
+/// Maximum number of sub-accounts per user
+pub const MAX_SUB_ACCOUNTS: usize = 8;
+
+/// Link from a sub-account slot to its parent user account
+///
+/// Sub-accounts are ordinary engine accounts, so each one has its own
+/// capital, position and liquidation. The stored account_id guards against
+/// slot reuse after the sub-account is closed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SubAccountLink {
+    parent: u16,
+    parent_account_id: u64,
+    account_id: u64,
+    sub_id: u8,
+}
+
+impl SubAccountLink {
+    const NONE: Self = Self {
+        parent: u16::MAX,
+        parent_account_id: 0,
+        account_id: 0,
+        sub_id: 0,
+    };
+}
+
+/// How a user's capital backs the positions of its sub-accounts
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarginMode {
+    /// Each sub-account is margined only by its own capital
+    Isolated = 0,
+    /// Parent capital is drawn into sub-accounts to meet margin and avert liquidation
+    Cross = 1,
+}
+
+/// Margin mode chosen for a top-level user account
+///
+/// The stored account_id guards against slot reuse: a new account in a
+/// recycled slot starts isolated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct MarginModeStamp {
+    /// Account id the mode was chosen by (u64::MAX = never chosen)
+    account_id: u64,
+    
+    /// Chosen mode
+    mode: MarginMode,
+}
+
+impl MarginModeStamp {
+    const NONE: Self = Self {
+        account_id: u64::MAX,
+        mode: MarginMode::Isolated,
+    };
+    
+    /// Mode of `account_id` (Isolated unless it chose one)
+    fn mode_of(&self, account_id: u64) -> MarginMode {
+        if self.account_id == account_id {
+            self.mode
+        } else {
+            MarginMode::Isolated
+        }
+    }
+}
+
+/// Aggregate over a user account and all of its sub-accounts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UserAggregate {
+    /// Parent account index
+    pub parent_idx: u16,
+    
+    /// Number of live sub-accounts
+    pub sub_accounts: u8,
+    
+    /// Capital across parent and sub-accounts
+    pub total_capital: u128,
+    
+    /// Mark-to-market equity across parent and sub-accounts
+    pub total_equity: u128,
+    
+    /// Sum of position sizes (longs and shorts net out)
+    pub net_position: i128,
+    
+    /// Sum of absolute position sizes
+    pub gross_position: u128,
+    
+    /// Number of undercollateralized accounts (parent or sub)
+    pub undercollateralized: u8,
+}
+
 // ============================================================================
 // Market Parameters (dynamic, set by agent)
 // ============================================================================
@@ -146,6 +720,31 @@ pub struct MarketParams {
     /// Maximum active capital ratio (0-10000 bps = 0-100%)
     /// Agent can limit how much capital is actively trading
     pub active_capital_ratio_bps: u64,
+    
+    /// Leverage and position limits per account tier (indexed by AccountTier)
+    pub tier_limits: [TierLimits; ACCOUNT_TIER_COUNT],
+    
+    /// Maintenance fee and dust cleanup thresholds
+    pub dust_policy: DustPolicy,
+    
+    /// Withdrawal queue threshold, delay and expedite condition
+    pub withdrawal_policy: WithdrawalPolicy,
+    
+    /// Virtual base reserve of the fallback vAMM curve (0 = no fallback)
+    pub vamm_depth: u128,
+    
+    /// Largest share of open interest one user may hold (bps, 10000 = off)
+    pub max_oi_share_bps: u64,
+    
+    /// PnL warmup period for warmups starting after the change
+    /// (None = keep the engine's period)
+    pub warmup_period_slots: Option<u64>,
+    
+    /// Split of trading fees between LP pool, insurance and treasury
+    pub fee_split: FeeSplit,
+    
+    /// Interest rate and limits of margin loans from the LP reserve
+    pub lending_policy: LendingPolicy,
 }
 
 impl Default for MarketParams {
@@ -157,10 +756,144 @@ impl Default for MarketParams {
             funding_rate_bps_per_slot: 0,
             min_margin_bps: 500, // 5% default
             active_capital_ratio_bps: 10000, // 100% default
+            tier_limits: DEFAULT_TIER_LIMITS,
+            dust_policy: DEFAULT_DUST_POLICY,
+            withdrawal_policy: DEFAULT_WITHDRAWAL_POLICY,
+            fee_split: DEFAULT_FEE_SPLIT,
+            vamm_depth: 0,
+            max_oi_share_bps: 10_000,
+            warmup_period_slots: None,
+            lending_policy: DEFAULT_LENDING_POLICY,
+        }
+    }
+}
+
+// ============================================================================
+// Maintenance Fees & Dust Cleanup
+// ============================================================================
+
+/// Agent-tunable maintenance fee and dust thresholds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DustPolicy {
+    /// Maintenance fee charged per slot (None = keep the engine's fee)
+    pub maintenance_fee_per_slot: Option<u128>,
+    
+    /// Flat accounts with capital at or below this are dust (0 = engine GC only)
+    pub dust_capital_threshold: u128,
+    
+    /// Slots without activity before a dust account may be closed
+    pub idle_slots: u64,
+}
+
+/// Default dust policy: engine fee, engine GC only
+pub const DEFAULT_DUST_POLICY: DustPolicy = DustPolicy {
+    maintenance_fee_per_slot: None,
+    dust_capital_threshold: 0,
+    idle_slots: 0,
+};
+
+/// Protocol bounds on the agent's dust policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DustBounds {
+    /// Maximum maintenance fee per slot
+    pub max_maintenance_fee_per_slot: u128,
+    
+    /// Maximum dust capital threshold
+    pub max_dust_capital_threshold: u128,
+    
+    /// Minimum idle period before dust may be closed (slots)
+    pub min_idle_slots: u64,
+}
+
+impl Default for DustBounds {
+    fn default() -> Self {
+        Self {
+            max_maintenance_fee_per_slot: 1_000,
+            max_dust_capital_threshold: 10_000,
+            min_idle_slots: 1_000,
         }
     }
 }
 
+/// Maximum dust accounts closed by one sweep
+pub const MAX_DUST_EVENTS: usize = 16;
+
+/// Dust account closed by a sweep
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DustEvent {
+    /// Freed account slot
+    pub account_idx: u16,
+    
+    /// Id of the closed account (slot ids are recycled, account ids are not)
+    pub account_id: u64,
+    
+    /// Remaining capital swept into insurance
+    pub swept_capital: u128,
+    
+    /// Negative PnL written off
+    pub written_off_pnl: u128,
+}
+
+/// Result of the last dust sweep
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DustSweep {
+    /// Slot of the sweep
+    pub slot: u64,
+    
+    /// Closed accounts (max 16 per sweep)
+    pub closed: [DustEvent; MAX_DUST_EVENTS],
+    
+    /// Number of valid entries in `closed`
+    pub closed_len: usize,
+    
+    /// Capital swept into insurance
+    pub swept_total: u128,
+}
+
+impl DustSweep {
+    /// Closed accounts of this sweep
+    pub fn events(&self) -> &[DustEvent] {
+        &self.closed[..self.closed_len]
+    }
+}
+
+/// Last observed activity of an account slot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ActivityStamp {
+    /// Account id the stamp belongs to (u64::MAX = never seen)
+    account_id: u64,
+    
+    /// Slot of the last activity
+    slot: u64,
+}
+
+impl ActivityStamp {
+    const NONE: Self = Self {
+        account_id: u64::MAX,
+        slot: 0,
+    };
+}
+
+/// Last sequence number an account slot has used
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SequenceStamp {
+    /// Account id the stamp belongs to (u64::MAX = never seen)
+    account_id: u64,
+    
+    /// Highest sequence accepted (0 = none yet)
+    last: u64,
+}
+
+impl SequenceStamp {
+    const NONE: Self = Self {
+        account_id: u64::MAX,
+        last: 0,
+    };
+}
+
+/// Slots scanned per dust sweep
+const DUST_SWEEP_SCAN: usize = 256;
+
 // ============================================================================
 // Liquidity Allocation
 // ============================================================================
@@ -192,16 +925,37 @@ pub struct RiskAssessment {
     pub actions: RiskActions,
 }
 
+impl RiskAssessment {
+    /// No risk, no actions
+    pub fn none() -> Self {
+        Self {
+            risk_level_bps: 0,
+            actions: RiskActions::default(),
+        }
+    }
+}
+
+/// Forced reduction of a single account's position
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PositionReduction {
+    /// Account to reduce
+    pub account_idx: u16,
+    
+    /// Absolute size to close (>= position size closes it fully)
+    pub reduce_by: u128,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct RiskActions {
     /// Reduce exposure
     pub reduce_exposure: bool,
     
-    /// Hedge positions
-    pub hedge: bool,
+    /// Hedge LP inventory by this many contracts on an external venue
+    /// (positive = buy, negative = sell, None = no hedge)
+    pub hedge: Option<i128>,
     
-    /// Close specific positions (max 16 positions per assessment)
-    pub close_positions: [u16; 16],
+    /// Reduce specific positions at mark price (max 16 per assessment)
+    pub close_positions: [PositionReduction; 16],
     pub close_positions_len: usize,
     
     /// Increase margin requirements (None = no change)
@@ -240,6 +994,17 @@ pub struct AnomalyResponse {
     pub actions: AnomalyActions,
 }
 
+impl AnomalyResponse {
+    /// Nothing detected
+    pub fn none() -> Self {
+        Self {
+            anomaly_type: AnomalyType::Other,
+            severity_bps: 0,
+            actions: AnomalyActions::default(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct AnomalyActions {
     /// Freeze market
@@ -255,16 +1020,245 @@ pub struct AnomalyActions {
     pub initiate_shutdown: bool,
 }
 
-// ============================================================================
-// OpenClaw Agent Trait
-// ============================================================================
+/// Protocol-side thresholds gating the agent's anomaly actions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EscalationPolicy {
+    /// Minimum severity for any action; below this anomalies are only logged
+    pub action_threshold_bps: u64,
+    
+    /// Minimum severity to freeze the market or stop trading
+    pub freeze_threshold_bps: u64,
+    
+    /// Minimum severity for a shutdown request to count
+    pub shutdown_threshold_bps: u64,
+    
+    /// Consecutive anomaly checks at shutdown severity required to shut down
+    pub shutdown_consecutive_checks: u32,
+    
+    /// Severity below which a check counts as calm
+    pub deescalation_threshold_bps: u64,
+    
+    /// Consecutive calm checks after which anomaly restrictions are lifted
+    pub deescalation_consecutive_checks: u32,
+}
 
-/// Trait for OpenClaw autonomous agent
-///
-/// The agent is the sole decision-maker for all market operations.
-/// All decisions are validated by the protocol before execution.
-pub trait OpenClawAgent {
-    /// Decide whether to accept, reject, or quote a trade
+impl Default for EscalationPolicy {
+    fn default() -> Self {
+        Self {
+            action_threshold_bps: 2000, // 20%
+            freeze_threshold_bps: 5000, // 50%
+            shutdown_threshold_bps: 8000, // 80%
+            shutdown_consecutive_checks: 3,
+            deescalation_threshold_bps: 1000, // 10%
+            deescalation_consecutive_checks: 5,
+        }
+    }
+}
+
+/// Highest escalation level reached by an anomaly check
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EscalationLevel {
+    /// Below action threshold: recorded only
+    #[default]
+    Logged,
+    /// Position limits reduced
+    Restricted,
+    /// Market frozen
+    Frozen,
+    /// Shutdown requested but not yet sustained long enough
+    ShutdownPending,
+    /// System shut down
+    Shutdown,
+}
+
+/// Result of applying an anomaly response through the escalation policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EscalationOutcome {
+    /// Anomaly reported by the agent
+    pub anomaly_type: AnomalyType,
+    
+    /// Severity reported by the agent
+    pub severity_bps: u64,
+    
+    /// Highest level applied
+    pub level: EscalationLevel,
+    
+    /// Consecutive checks at shutdown severity so far
+    pub shutdown_streak: u32,
+    
+    /// Whether this check lifted earlier anomaly restrictions
+    pub deescalated: bool,
+}
+
+/// Number of anomaly checks retained in AnomalyHistory
+pub const ANOMALY_HISTORY_LEN: usize =
+    crate::layout::capacity(option_env!("PERCOLATOR_ANOMALY_HISTORY_LEN"), 16);
+
+/// Single entry in the anomaly history
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnomalyRecord {
+    /// Slot at which the check ran
+    pub slot: u64,
+    
+    /// Anomaly reported by the agent
+    pub anomaly_type: AnomalyType,
+    
+    /// Severity reported by the agent (clamped to 10000)
+    pub severity_bps: u64,
+    
+    /// Escalation level applied
+    pub level: EscalationLevel,
+}
+
+impl Default for AnomalyRecord {
+    fn default() -> Self {
+        Self {
+            slot: 0,
+            anomaly_type: AnomalyType::Other,
+            severity_bps: 0,
+            level: EscalationLevel::Logged,
+        }
+    }
+}
+
+/// Ring buffer of recent anomaly checks
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnomalyHistory {
+    records: [AnomalyRecord; ANOMALY_HISTORY_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl AnomalyHistory {
+    /// Append a record, evicting the oldest when full
+    pub fn push(&mut self, record: AnomalyRecord) {
+        self.records[self.head] = record;
+        self.head = (self.head + 1) % ANOMALY_HISTORY_LEN;
+        self.len = (self.len + 1).min(ANOMALY_HISTORY_LEN);
+    }
+    
+    /// Number of retained records
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    
+    /// Whether no checks have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    
+    /// Record `i` in chronological order (0 = oldest retained)
+    pub fn get(&self, i: usize) -> Option<&AnomalyRecord> {
+        if i >= self.len {
+            return None;
+        }
+        let start = (self.head + ANOMALY_HISTORY_LEN - self.len) % ANOMALY_HISTORY_LEN;
+        Some(&self.records[(start + i) % ANOMALY_HISTORY_LEN])
+    }
+    
+    /// Most recent record
+    pub fn latest(&self) -> Option<&AnomalyRecord> {
+        self.len.checked_sub(1).and_then(|i| self.get(i))
+    }
+    
+    /// Records in chronological order
+    pub fn iter(&self) -> impl Iterator<Item = &AnomalyRecord> + '_ {
+        (0..self.len).filter_map(move |i| self.get(i))
+    }
+}
+
+/// Who froze the market
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FreezeOrigin {
+    /// Agent anomaly response (lifted automatically on de-escalation)
+    Anomaly,
+    /// Administrative freeze (only lifted by an administrative call)
+    Admin,
+    /// Operator kill-switch (lifted by the operator or an administrative call)
+    Operator,
+    /// Dead-man switch (lifted automatically once the agent is heard from)
+    AgentTimeout,
+}
+
+// ============================================================================
+// OpenClaw Agent Trait
+// ============================================================================
+
+/// Revision of `OpenClawAgent`
+///
+/// Bumped whenever a hook is added. New hooks always come with a default
+/// implementation, so agents written against an older revision keep
+/// compiling and behave as before.
+pub const AGENT_TRAIT_VERSION: u16 = 6;
+
+/// Optional hooks an agent implements
+///
+/// The engine skips hooks the agent leaves out and uses the neutral value
+/// the default implementation would have returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AgentCapabilities {
+    /// `AGENT_TRAIT_VERSION` the agent was written against
+    pub version: u16,
+    
+    /// `decide_liquidity_allocation`
+    pub liquidity_allocation: bool,
+    
+    /// `assess_risk`
+    pub risk_assessment: bool,
+    
+    /// `detect_anomalies`
+    pub anomaly_detection: bool,
+    
+    /// `should_shutdown`
+    pub shutdown: bool,
+    
+    /// `max_pool_utilization_bps`
+    pub pool_utilization: bool,
+    
+    /// `quote_capacity`
+    pub quote_capacity: bool,
+    
+    /// `batch_clearing_price`
+    pub batch_clearing: bool,
+    
+    /// `heartbeat`
+    pub heartbeat: bool,
+}
+
+impl AgentCapabilities {
+    /// Every hook (the default, so existing agents are called as before)
+    pub const ALL: Self = Self {
+        version: AGENT_TRAIT_VERSION,
+        liquidity_allocation: true,
+        risk_assessment: true,
+        anomaly_detection: true,
+        shutdown: true,
+        pool_utilization: true,
+        quote_capacity: true,
+        batch_clearing: true,
+        heartbeat: true,
+    };
+    
+    /// Only the required `decide_trade` and `get_market_params`
+    pub const CORE: Self = Self {
+        version: AGENT_TRAIT_VERSION,
+        liquidity_allocation: false,
+        risk_assessment: false,
+        anomaly_detection: false,
+        shutdown: false,
+        pool_utilization: false,
+        quote_capacity: false,
+        batch_clearing: false,
+        heartbeat: false,
+    };
+}
+
+/// Trait for OpenClaw autonomous agent
+///
+/// The agent is the sole decision-maker for all market operations.
+/// All decisions are validated by the protocol before execution.
+pub trait OpenClawAgent {
+    /// Decide whether to accept, reject, or quote a trade
     ///
     /// # Arguments
     /// * `context` - Read-only view of engine state
@@ -279,6 +1273,19 @@ pub trait OpenClawAgent {
         request: &TradeRequest,
     ) -> Result<TradeDecision>;
     
+    /// Decide a trade and explain the decision
+    ///
+    /// The engine calls this instead of `decide_trade`; the meta is recorded
+    /// with the fill and exposed via `last_decision_meta`. Defaults to
+    /// `decide_trade` without meta.
+    fn decide_trade_with_meta(
+        &self,
+        context: &AgentContext,
+        request: &TradeRequest,
+    ) -> Result<(TradeDecision, Option<DecisionMeta>)> {
+        Ok((self.decide_trade(context, request)?, None))
+    }
+    
     /// Get current market parameters
     ///
     /// Agent dynamically sets market parameters.
@@ -291,19 +1298,28 @@ pub trait OpenClawAgent {
     /// Decide liquidity allocation
     ///
     /// Agent determines how much capital should be actively trading
-    /// vs. kept in reserve.
+    /// vs. kept in reserve. Defaults to keeping all capital active.
     fn decide_liquidity_allocation(
         &self,
         context: &AgentContext,
-    ) -> Result<LiquidityAllocation>;
+    ) -> Result<LiquidityAllocation> {
+        Ok(LiquidityAllocation {
+            target_active_capital: context.total_capital,
+            reserve_capital: 0,
+            defensive_mode: false,
+        })
+    }
     
     /// Assess current risk level
     ///
-    /// Agent evaluates system risk and recommends actions.
+    /// Agent evaluates system risk and recommends actions. Defaults to no
+    /// risk and no actions.
     fn assess_risk(
         &self,
-        context: &AgentContext,
-    ) -> Result<RiskAssessment>;
+        _context: &AgentContext,
+    ) -> Result<RiskAssessment> {
+        Ok(RiskAssessment::none())
+    }
     
     /// Detect anomalies in market conditions
     ///
@@ -312,283 +1328,5585 @@ pub trait OpenClawAgent {
     /// - High volatility
     /// - Unusual patterns
     /// - Liquidity issues
+    ///
+    /// Defaults to nothing detected.
     fn detect_anomalies(
         &self,
-        context: &AgentContext,
-    ) -> Result<AnomalyResponse>;
+        _context: &AgentContext,
+    ) -> Result<AnomalyResponse> {
+        Ok(AnomalyResponse::none())
+    }
     
     /// Decide if system should shutdown
     ///
     /// Agent can initiate controlled shutdown if market conditions
-    /// are deemed unsafe.
+    /// are deemed unsafe. Defaults to never.
     fn should_shutdown(
+        &self,
+        _context: &AgentContext,
+    ) -> Result<bool> {
+        Ok(false)
+    }
+    
+    /// Cap on LP pool utilization (initial margin of LP inventory over LP
+    /// equity, in basis points)
+    ///
+    /// Trades that would push utilization above the cap are rejected, and
+    /// pool withdrawals may not leave the pool above it.
+    fn max_pool_utilization_bps(
+        &self,
+        _context: &AgentContext,
+    ) -> Result<u64> {
+        Ok(10_000)
+    }
+    
+    /// Largest size the agent would fill for `user_idx` on `side`
+    ///
+    /// Only used to tell users what they can trade; the trade itself is
+    /// still decided by `decide_trade`. Defaults to no limit beyond the
+    /// protocol's.
+    fn quote_capacity(
+        &self,
+        _context: &AgentContext,
+        _user_idx: u16,
+        _side: TradeSide,
+    ) -> Result<u128> {
+        Ok(u128::MAX)
+    }
+    
+    /// Uniform price to clear a batch auction at (see `auction`)
+    ///
+    /// Must lie within the batch band around the oracle or the batch is not
+    /// cleared. Defaults to the oracle price.
+    fn batch_clearing_price(
         &self,
         context: &AgentContext,
-    ) -> Result<bool>;
+        _batch: &BatchSummary,
+    ) -> Result<u64> {
+        Ok(context.oracle_price)
+    }
+    
+    /// Prove liveness between cranks (see `ClawcolatorEngine::record_heartbeat`)
+    ///
+    /// An error means the agent is not in a state to serve; the heartbeat
+    /// is then not recorded. Defaults to alive.
+    fn heartbeat(&self, _context: &AgentContext) -> Result<()> {
+        Ok(())
+    }
+    
+    /// Optional hooks this agent implements
+    ///
+    /// Agents relying on default hooks can return a narrower set (e.g.
+    /// `AgentCapabilities::CORE`) so the engine does not call them at all.
+    fn agent_capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities::ALL
+    }
+}
+
+/// How a trade reached the engine
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TradeEntry {
+    /// Submitted as is (refused while commit-reveal is required)
+    Direct,
+    /// Revealed from a commitment, filled within the committed slippage
+    Revealed { max_slippage_bps: u64 },
+    /// Cleared in a batch auction at its uniform price
+    Batch { price: u64 },
+}
+
+/// Agent's decision on a trade, after the vAMM fallback
+struct PlannedTrade {
+    decision: TradeDecision,
+    meta: Option<DecisionMeta>,
+    /// Accepted by the vAMM fallback rather than the agent
+    vamm: bool,
+    context: AgentContext,
+}
+
+/// Parent capital a cross-margin sub-account needs moved in
+#[derive(Clone, Copy, Debug)]
+struct CrossMarginTopUp {
+    parent: u16,
+    amount: u128,
+}
+
+/// Price and size of an accepted decision, or the rejection for any other
+fn accepted_fill(decision: TradeDecision) -> core::result::Result<(u64, i128), TradeRejection> {
+    match decision {
+        TradeDecision::Accept { price, size } => Ok((price, size)),
+        TradeDecision::Reject { reason } => Err(TradeRejection::new(
+            RejectionCause::Agent(reason),
+            RiskError::Unauthorized,
+        )),
+        // RFQ - the user has to come back at the agent's quote
+        TradeDecision::RequestQuote { quote_price, max_size } => {
+            let mut rejection =
+                TradeRejection::new(RejectionCause::CounterQuote, RiskError::Unauthorized);
+            rejection.counter_quote = Some(CounterQuote { price: quote_price, max_size });
+            Err(rejection)
+        }
+    }
+}
+
+/// Reject fills worse than `max_slippage_bps` from the oracle for the taker
+fn validate_slippage(price: u64, exec_size: i128, oracle_price: u64, max_slippage_bps: u64) -> Result<()> {
+    let band = apply_bps(oracle_price as u128, max_slippage_bps, Rounding::Down);
+    let worse_by = if exec_size > 0 {
+        (price as u128).saturating_sub(oracle_price as u128)
+    } else {
+        (oracle_price as u128).saturating_sub(price as u128)
+    };
+    if worse_by > band {
+        return Err(RiskError::Unauthorized);
+    }
+    Ok(())
+}
+
+/// Agent's pool utilization cap (100% if the agent does not set one)
+fn pool_utilization_cap<A: OpenClawAgent + ?Sized>(agent: &A, context: &AgentContext) -> Result<u64> {
+    if !agent.agent_capabilities().pool_utilization {
+        return Ok(10_000);
+    }
+    Ok(agent.max_pool_utilization_bps(context)?.min(10_000))
 }
 
 // ============================================================================
-// Clawcolator Engine
+// State Delta (light-client export)
 // ============================================================================
 
-/// Clawcolator engine wrapper around RiskEngine
+/// Maximum number of changed accounts carried in a single StateDelta
+pub const MAX_DELTA_ACCOUNTS: usize = 16;
+
+/// Compact snapshot of an account touched since the last delta
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccountDelta {
+    /// Account index
+    pub idx: u16,
+    
+    /// Whether the slot is still occupied (false = account was closed)
+    pub in_use: bool,
+    
+    /// Current capital
+    pub capital: u128,
+    
+    /// Current realized PnL
+    pub pnl: i128,
+    
+    /// Current position size
+    pub position_size: i128,
+    
+    /// Last settlement price
+    pub entry_price: u64,
+}
+
+/// Compact state diff emitted after mutating calls
 ///
-/// Delegates all market decisions to OpenClaw agent while enforcing
-/// protocol invariants and safety checks.
-pub struct ClawcolatorEngine {
-    /// Underlying risk engine
-    engine: RiskEngine,
+/// Indexers and UIs apply deltas to maintain a mirror of engine state.
+/// If `resync_required` is set, the delta is incomplete and the mirror
+/// must be rebuilt from a full read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StateDelta {
+    /// Slot at which the delta was taken
+    pub slot: u64,
     
-    /// Current market parameters (set by agent)
-    market_params: MarketParams,
+    /// Change in vault balance since the previous delta
+    pub vault_delta: i128,
     
-    /// Whether system is shutdown
-    shutdown: bool,
+    /// Change in insurance fund balance since the previous delta
+    pub insurance_delta: i128,
     
-    /// Whether market is frozen
-    market_frozen: bool,
+    /// Accounts changed since the previous delta (max 16 per delta)
+    pub accounts: [AccountDelta; MAX_DELTA_ACCOUNTS],
+    
+    /// Number of valid entries in `accounts`
+    pub accounts_len: usize,
+    
+    /// New market parameters (None = unchanged)
+    pub market_params: Option<MarketParams>,
+    
+    /// New shutdown flag (None = unchanged)
+    pub shutdown: Option<bool>,
+    
+    /// New market-frozen flag (None = unchanged)
+    pub market_frozen: Option<bool>,
+    
+    /// Delta is incomplete (account overflow or raw engine access)
+    pub resync_required: bool,
 }
 
-impl ClawcolatorEngine {
-    /// Create new Clawcolator engine
-    pub fn new(base_params: RiskParams) -> Self {
+/// Baseline and dirty set accumulated between `take_state_delta` calls
+#[derive(Clone, Copy, Debug)]
+struct DeltaTracker {
+    base_vault: u128,
+    base_insurance: u128,
+    base_market_params: MarketParams,
+    base_shutdown: bool,
+    base_frozen: bool,
+    changed: [u16; MAX_DELTA_ACCOUNTS],
+    changed_len: usize,
+    resync_required: bool,
+}
+
+impl DeltaTracker {
+    fn new() -> Self {
         Self {
-            engine: RiskEngine::new(base_params),
-            market_params: MarketParams::default(),
-            shutdown: false,
-            market_frozen: false,
+            base_vault: 0,
+            base_insurance: 0,
+            base_market_params: MarketParams::default(),
+            base_shutdown: false,
+            base_frozen: false,
+            changed: [0; MAX_DELTA_ACCOUNTS],
+            changed_len: 0,
+            resync_required: false,
         }
     }
     
-    /// Initialize in place (for Solana BPF)
-    pub fn init_in_place(&mut self, base_params: RiskParams) {
-        self.engine.init_in_place(base_params);
-        self.market_params = MarketParams::default();
-        self.shutdown = false;
-        self.market_frozen = false;
-    }
-    
-    /// Build agent context from current engine state
-    pub fn build_context(&self, oracle_price: u64) -> AgentContext {
-        AgentContext {
-            current_slot: self.engine.current_slot,
-            oracle_price,
-            vault: self.engine.vault.get(),
-            insurance_balance: self.engine.insurance_fund.balance.get(),
-            total_capital: self.engine.c_tot.get(),
-            total_positive_pnl: self.engine.pnl_pos_tot.get(),
-            total_open_interest: self.engine.total_open_interest.get(),
-            risk_params: self.engine.params,
-            risk_reduction_mode: false, // TODO: implement risk reduction mode check
-            last_crank_slot: self.engine.last_crank_slot,
+    fn record(&mut self, idx: u16) {
+        if self.changed[..self.changed_len].contains(&idx) {
+            return;
         }
+        if self.changed_len == MAX_DELTA_ACCOUNTS {
+            self.resync_required = true;
+            return;
+        }
+        self.changed[self.changed_len] = idx;
+        self.changed_len += 1;
+    }
+}
+
+#[inline]
+fn signed_diff_u128(new: u128, old: u128) -> i128 {
+    if new >= old {
+        (new - old).min(i128::MAX as u128) as i128
+    } else {
+        -((old - new).min(i128::MAX as u128) as i128)
     }
+}
+
+// ============================================================================
+// Account Views (safe iteration)
+// ============================================================================
+
+/// Filter applied when iterating accounts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountFilter {
+    /// Every occupied account
+    All,
+    /// Only accounts with an open position
+    NonzeroPosition,
+    /// Only accounts below maintenance margin at the given oracle price
+    Undercollateralized,
+}
+
+/// Read-only summary of a single account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountView {
+    /// Account index
+    pub idx: u16,
     
-    /// Execute trade with agent decision
-    ///
-    /// Flow:
-    /// 1. Check if system is shutdown/frozen
-    /// 2. Get agent's trade decision
-    /// 3. Validate decision
-    /// 4. Execute via underlying risk engine
-    pub fn execute_trade<A: OpenClawAgent>(
-        &mut self,
-        agent: &A,
-        user_idx: u16,
+    /// Account kind (User or LP)
+    pub kind: AccountKind,
+    
+    /// Deposited capital
+    pub capital: u128,
+    
+    /// Realized PnL (settled marks, fees and funding)
+    pub pnl: i128,
+    
+    /// Unrealized PnL: mark-to-market of the position since the last
+    /// settlement price
+    pub unrealized_pnl: i128,
+    
+    /// Positive realized PnL past warmup (vested), before the haircut
+    pub withdrawable_pnl: u128,
+    
+    /// Positive realized PnL still warming up
+    pub unvested_pnl: u128,
+    
+    /// Slots until `unvested_pnl` has fully vested at the current slope
+    /// (0 when nothing is warming up)
+    pub slots_to_vest: u64,
+    
+    /// Position size (+ long, - short)
+    pub position_size: i128,
+    
+    /// Last settlement price
+    pub entry_price: u64,
+    
+    /// Mark-to-market equity at the oracle price
+    pub equity: u128,
+    
+    /// Equity / position notional in basis points (u64::MAX when flat)
+    pub margin_ratio_bps: u64,
+    
+    /// Whether the account is below maintenance margin
+    pub undercollateralized: bool,
+    
+    /// Oracle price at which the account falls below maintenance margin
+    /// (None when flat or unliquidatable; ignores cross-margin top-ups)
+    pub liquidation_price: Option<u64>,
+    
+    /// Risk tier
+    pub tier: AccountTier,
+    
+    /// Parent user account if this is a sub-account
+    pub parent_idx: Option<u16>,
+    
+    /// Sub-account id under the parent (0 for top-level accounts)
+    pub sub_id: u8,
+    
+    /// Margin mode of the account's user group
+    pub margin_mode: MarginMode,
+    
+    /// Cumulative funding paid (negative = received)
+    pub funding_paid: i128,
+}
+
+impl AccountView {
+    fn from_engine(
+        engine: &RiskEngine,
+        idx: u16,
         oracle_price: u64,
-        size: i128,
-        now_slot: u64,
-    ) -> Result<()> {
-        // Check system state
-        if self.shutdown {
-            return Err(RiskError::Unauthorized);
-        }
-        if self.market_frozen {
-            return Err(RiskError::Unauthorized);
-        }
-        
-        // Build context
-        let context = self.build_context(oracle_price);
-        
-        // Create trade request
-        let request = TradeRequest {
-            user_idx,
-            size,
-            requested_price: None,
+        tier: AccountTier,
+    ) -> Self {
+        let account = &engine.accounts[idx as usize];
+        let position_size = account.position_size.get();
+        let equity = engine.account_equity_mtm_at_oracle(account, oracle_price);
+        let margin_ratio_bps = margin::margin_ratio_bps(equity, position_size, oracle_price);
+        let withdrawable_pnl = engine.withdrawable_pnl(account);
+        let unvested_pnl = (account.pnl.get().max(0) as u128)
+            .saturating_sub(account.reserved_pnl as u128)
+            .saturating_sub(withdrawable_pnl);
+        let slots_to_vest = match account.warmup_slope_per_step.get() {
+            _ if unvested_pnl == 0 => 0,
+            0 => u64::MAX,
+            slope => unvested_pnl.div_ceil(slope).min(u64::MAX as u128) as u64,
         };
         
-        // Get agent decision
-        let decision = agent.decide_trade(&context, &request)?;
-        
-        // Process decision
-        match decision {
-            TradeDecision::Accept { price, size: exec_size } => {
-                // Validate agent's decision
-                self.validate_trade_execution(price, exec_size, size)?;
-                
-                // Execute via underlying engine
-                // Note: We need to adapt this to work with agent's decision
-                // For now, we'll use a simple matcher that respects agent's decision
-                let matcher = AgentMatcher {
-                    price,
-                    size: exec_size,
-                };
-                
-                // Find LP account (in Clawcolator, agent IS the LP)
-                // For now, assume LP is account 0 (this needs proper design)
-                let lp_idx = 0;
-                
-                self.engine.execute_trade(
-                    &matcher,
-                    lp_idx,
-                    user_idx,
-                    now_slot,
-                    oracle_price,
-                    size,
-                )
-            }
-            
-            TradeDecision::Reject { reason: _ } => {
-                Err(RiskError::Unauthorized)
-            }
-            
-            TradeDecision::RequestQuote { quote_price: _, max_size: _ } => {
-                // RFQ - return error to indicate quote needed
-                Err(RiskError::Unauthorized)
-            }
+        Self {
+            idx,
+            kind: account.kind,
+            capital: account.capital.get(),
+            pnl: account.pnl.get(),
+            unrealized_pnl: RiskEngine::mark_pnl_for_position(
+                position_size,
+                account.entry_price,
+                oracle_price,
+            )
+            .unwrap_or(0),
+            withdrawable_pnl,
+            unvested_pnl,
+            slots_to_vest,
+            position_size,
+            entry_price: account.entry_price,
+            equity,
+            margin_ratio_bps,
+            undercollateralized: position_size != 0
+                && !engine.is_above_maintenance_margin_mtm(account, oracle_price),
+            liquidation_price: margin::liquidation_price(
+                equity,
+                position_size,
+                oracle_price,
+                engine.params.maintenance_margin_bps,
+            ),
+            tier,
+            parent_idx: None,
+            sub_id: 0,
+            margin_mode: MarginMode::Isolated,
+            funding_paid: 0,
         }
     }
     
-    /// Validate trade execution from agent
-    fn validate_trade_execution(
+    fn with_link(
+        mut self,
+        engine: &RiskEngine,
+        links: &[SubAccountLink; MAX_ACCOUNTS],
+        modes: &[MarginModeStamp; MAX_ACCOUNTS],
+    ) -> Self {
+        if let Some(link) = live_link(engine, links, self.idx) {
+            self.parent_idx = Some(link.parent);
+            self.sub_id = link.sub_id;
+        }
+        self.margin_mode = group_margin_mode(engine, links, modes, self.idx);
+        self
+    }
+    
+    fn with_funding(mut self, ledger: FundingLedger) -> Self {
+        self.funding_paid = ledger.paid(self.idx);
+        self
+    }
+    
+    fn matches(&self, filter: AccountFilter) -> bool {
+        match filter {
+            AccountFilter::All => true,
+            AccountFilter::NonzeroPosition => self.position_size != 0,
+            AccountFilter::Undercollateralized => self.undercollateralized,
+        }
+    }
+}
+
+/// Result of `ClawcolatorEngine::settle_pnl`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PnlSettlement {
+    /// Realized loss paid from capital
+    pub loss_settled: u128,
+    
+    /// Realized loss capital could not cover, written off
+    pub loss_written_off: u128,
+    
+    /// Warmed-up profit taken off PnL
+    pub profit_released: u128,
+    
+    /// Capital credited for the released profit (after the haircut)
+    pub capital_credited: u128,
+}
+
+impl PnlSettlement {
+    /// Profit forfeited to the haircut because positive PnL is not fully
+    /// backed by the vault residual
+    pub fn haircut(&self) -> u128 {
+        self.profit_released - self.capital_credited
+    }
+}
+
+/// Iterator over occupied accounts in index order
+pub struct AccountIter<'a> {
+    engine: &'a RiskEngine,
+    tiers: &'a [TierStamp; MAX_ACCOUNTS],
+    links: &'a [SubAccountLink; MAX_ACCOUNTS],
+    modes: &'a [MarginModeStamp; MAX_ACCOUNTS],
+    funding: FundingLedger<'a>,
+    oracle_price: u64,
+    filter: AccountFilter,
+    next_idx: usize,
+}
+
+impl Iterator for AccountIter<'_> {
+    type Item = AccountView;
+    
+    fn next(&mut self) -> Option<AccountView> {
+        while self.next_idx < MAX_ACCOUNTS {
+            let idx = self.next_idx;
+            self.next_idx += 1;
+            if !self.engine.is_used(idx) {
+                continue;
+            }
+            let tier = effective_tier(self.engine, self.tiers, idx as u16);
+            let view = AccountView::from_engine(self.engine, idx as u16, self.oracle_price, tier)
+                .with_link(self.engine, self.links, self.modes)
+                .with_funding(self.funding);
+            if view.matches(self.filter) {
+                return Some(view);
+            }
+        }
+        None
+    }
+}
+
+/// LP accounts are always in the LP tier; users use the tier assigned to
+/// them, Retail if it was assigned to a previous holder of the slot
+fn effective_tier(engine: &RiskEngine, tiers: &[TierStamp; MAX_ACCOUNTS], idx: u16) -> AccountTier {
+    let account = &engine.accounts[idx as usize];
+    if account.is_lp() {
+        AccountTier::LP
+    } else {
+        tiers[idx as usize].tier_of(account.account_id)
+    }
+}
+
+/// Sub-account link for `idx`, if both it and its parent are still live
+fn live_link(
+    engine: &RiskEngine,
+    links: &[SubAccountLink; MAX_ACCOUNTS],
+    idx: u16,
+) -> Option<SubAccountLink> {
+    let link = links[idx as usize];
+    if link.parent == u16::MAX
+        || !engine.is_used(idx as usize)
+        || !engine.is_used(link.parent as usize)
+        || engine.accounts[idx as usize].account_id != link.account_id
+        || engine.accounts[link.parent as usize].account_id != link.parent_account_id
+    {
+        return None;
+    }
+    Some(link)
+}
+
+/// Margin mode of `idx`'s user group: the mode its top-level account chose,
+/// Isolated if a previous holder of that slot chose it
+fn group_margin_mode(
+    engine: &RiskEngine,
+    links: &[SubAccountLink; MAX_ACCOUNTS],
+    modes: &[MarginModeStamp; MAX_ACCOUNTS],
+    idx: u16,
+) -> MarginMode {
+    let root = live_link(engine, links, idx).map_or(idx, |link| link.parent);
+    modes[root as usize].mode_of(engine.accounts[root as usize].account_id)
+}
+
+// ============================================================================
+// Oracle Input
+// ============================================================================
+
+/// Oracle price with publication metadata
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OraclePrice {
+    /// Price (1e6 scale)
+    pub price: u64,
+    
+    /// Confidence interval (same units as price)
+    pub confidence: u64,
+    
+    /// Slot at which the price was published
+    pub publish_slot: u64,
+}
+
+impl OraclePrice {
+    pub fn new(price: u64, confidence: u64, publish_slot: u64) -> Self {
+        Self {
+            price,
+            confidence,
+            publish_slot,
+        }
+    }
+    
+    /// Confidence interval as basis points of price (u64::MAX for zero price)
+    pub fn confidence_bps(&self) -> u64 {
+        if self.price == 0 {
+            return u64::MAX;
+        }
+        ratio_bps(self.confidence as u128, self.price as u128, Rounding::Down)
+    }
+    
+    /// Whether the price is older than `max_staleness_slots` at `now_slot`
+    pub fn is_stale(&self, now_slot: u64, max_staleness_slots: u64) -> bool {
+        now_slot.saturating_sub(self.publish_slot) > max_staleness_slots
+    }
+}
+
+/// Protocol bounds on oracle inputs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OracleBounds {
+    /// Maximum age of a price before trades are rejected (slots)
+    pub max_staleness_slots: u64,
+    
+    /// Maximum confidence interval before trades are rejected (bps of price)
+    pub max_confidence_bps: u64,
+    
+    /// Maximum primary/secondary divergence tolerated at crank (bps)
+    pub max_divergence_bps: u64,
+}
+
+impl Default for OracleBounds {
+    fn default() -> Self {
+        Self {
+            max_staleness_slots: 25,
+            max_confidence_bps: 200, // 2%
+            max_divergence_bps: 200, // 2%
+        }
+    }
+}
+
+/// Divergence of `secondary` from `primary` in basis points of `primary`
+pub fn oracle_divergence_bps(primary: u64, secondary: u64) -> u64 {
+    ratio_bps(primary.abs_diff(secondary) as u128, primary as u128, Rounding::Down)
+}
+
+// ============================================================================
+// Concentration Limits
+// ============================================================================
+
+/// Protocol bounds on the agent's concentration limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConcentrationBounds {
+    /// Loosest open-interest share the agent may allow (bps)
+    pub max_oi_share_bps: u64,
+    
+    /// Open interest below which concentration is not enforced
+    /// (the first trades in a market are always 100% of it)
+    pub min_open_interest: u128,
+}
+
+impl Default for ConcentrationBounds {
+    fn default() -> Self {
+        Self {
+            max_oi_share_bps: 10_000,
+            min_open_interest: 1_000_000,
+        }
+    }
+}
+
+/// Protocol bounds on the agent's PnL warmup period
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WarmupBounds {
+    /// Shortest warmup the agent may set (slots)
+    pub min_slots: u64,
+    
+    /// Longest warmup the agent may set (slots)
+    pub max_slots: u64,
+}
+
+impl Default for WarmupBounds {
+    fn default() -> Self {
+        Self {
+            min_slots: 1,
+            max_slots: 1_000_000,
+        }
+    }
+}
+
+/// Largest user's (parent plus sub-accounts) share of open interest
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConcentrationStatus {
+    /// Top-level account of the user with the largest gross position
+    /// (None = no user positions)
+    pub largest_idx: Option<u16>,
+    
+    /// Its gross share of total open interest (bps), the quantity
+    /// `max_oi_share_bps` caps
+    pub largest_share_bps: u64,
+}
+
+// ============================================================================
+// Market Mode
+// ============================================================================
+
+/// How trades in a market are priced and filled
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MarketMode {
+    /// The agent decides every trade (Clawcolator flow)
+    #[default]
+    AgentDriven = 0,
+    /// A caller-supplied matching engine fills trades (base percolator flow);
+    /// the agent still observes, assesses risk and flags anomalies
+    Classic = 1,
+}
+
+// ============================================================================
+// Risk Reduction Mode
+// ============================================================================
+
+/// Conditions currently holding the protocol in risk-reduction-only mode
+///
+/// While any trigger is set, only position-reducing trades execute,
+/// withdrawals are rate-limited and market params may only tighten.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RiskReductionTriggers {
+    /// Primary/secondary oracle divergence above bound at the last crank
+    pub oracle_divergence: bool,
+    
+    /// Agent's last risk assessment asked to reduce exposure
+    pub agent_assessment: bool,
+    
+    /// Engine under stress at the last crank: liquidation errors, or
+    /// force-realize with a non-zero insurance threshold configured
+    pub engine_stress: bool,
+    
+    /// Administrative trigger
+    pub admin: bool,
+    
+    /// Operator kill-switch trigger
+    pub operator: bool,
+    
+    /// Dead-man switch: agent silent past its threshold at the last crank
+    pub agent_timeout: bool,
+}
+
+impl RiskReductionTriggers {
+    /// Whether risk-reduction-only mode is in effect
+    pub fn active(&self) -> bool {
+        self.oracle_divergence
+            || self.agent_assessment
+            || self.engine_stress
+            || self.admin
+            || self.operator
+            || self.agent_timeout
+    }
+}
+
+/// Withdrawal rate limit applied in risk-reduction-only mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RiskReductionLimits {
+    /// Maximum withdrawn per window (bps of vault at window start)
+    pub max_withdrawal_bps_per_window: u64,
+    
+    /// Rate-limit window length (slots)
+    pub window_slots: u64,
+}
+
+impl Default for RiskReductionLimits {
+    fn default() -> Self {
+        Self {
+            max_withdrawal_bps_per_window: 100, // 1% of vault
+            window_slots: 150,                  // ~1 minute
+        }
+    }
+}
+
+/// Withdrawals counted against the current rate-limit window
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct WithdrawalWindow {
+    start_slot: u64,
+    budget: u128,
+    withdrawn: u128,
+}
+
+// ============================================================================
+// Clawcolator Engine
+// ============================================================================
+
+/// Account index of the agent's LP (counterparty to every trade)
+pub const AGENT_LP_IDX: u16 = 0;
+
+/// Clawcolator engine wrapper around RiskEngine
+///
+/// Delegates all market decisions to OpenClaw agent while enforcing
+/// protocol invariants and safety checks.
+pub struct ClawcolatorEngine {
+    /// Underlying risk engine
+    engine: RiskEngine,
+    
+    /// Current market parameters (set by agent)
+    market_params: MarketParams,
+    
+    /// Whether system is shutdown
+    shutdown: bool,
+    
+    /// Whether market is frozen
+    market_frozen: bool,
+    
+    /// Pending state diff for light clients
+    delta: DeltaTracker,
+    
+    /// Assigned tier per user account (LP accounts are always AccountTier::LP)
+    account_tiers: [TierStamp; MAX_ACCOUNTS],
+    
+    /// Sub-account -> parent links (indexed by sub-account slot)
+    sub_links: [SubAccountLink; MAX_ACCOUNTS],
+    
+    /// Margin mode per top-level user account
+    margin_modes: [MarginModeStamp; MAX_ACCOUNTS],
+    
+    /// Hedge position held on external venues
+    hedge: HedgePosition,
+    
+    /// Thresholds gating the agent's anomaly actions
+    escalation: EscalationPolicy,
+    
+    /// Consecutive anomaly checks requesting shutdown at shutdown severity
+    shutdown_streak: u32,
+    
+    /// Recent anomaly checks
+    anomaly_history: AnomalyHistory,
+    
+    /// Consecutive anomaly checks below the de-escalation threshold
+    calm_streak: u32,
+    
+    /// Origin of the current freeze (None when not frozen)
+    freeze_origin: Option<FreezeOrigin>,
+    
+    /// max_position_size before anomaly-driven reductions (None = not reduced)
+    pre_anomaly_max_position: Option<u128>,
+    
+    /// Protocol bounds on oracle staleness, confidence and divergence
+    oracle_bounds: OracleBounds,
+    
+    /// Divergence measured at the last crank (bps)
+    oracle_divergence_bps: u64,
+    
+    /// Conditions holding the protocol in risk-reduction-only mode
+    risk_reduction: RiskReductionTriggers,
+    
+    /// Withdrawal rate limit while in risk-reduction-only mode
+    risk_reduction_limits: RiskReductionLimits,
+    
+    /// Operator kill-switch key (None = no operator)
+    operator: Option<OperatorAuthority>,
+    
+    /// Multisig governance over protocol params (None = admin setters)
+    governance: Option<Governance>,
+    
+    /// Highest leverage the agent may set (bps)
+    max_leverage_ceiling_bps: u64,
+    
+    /// Protocol bounds on the fallback vAMM
+    vamm_bounds: VammBounds,
+    
+    /// Price-impact model capping agent fill prices
+    impact_model: ImpactModel,
+    
+    /// Protocol bounds on the agent's concentration limit
+    concentration_bounds: ConcentrationBounds,
+    
+    /// Protocol bounds on the agent's warmup period
+    warmup_bounds: WarmupBounds,
+    
+    /// Agent-driven or classic trade flow
+    market_mode: MarketMode,
+    
+    /// Mode switch waiting for the next advancing crank
+    pending_market_mode: Option<MarketMode>,
+    
+    /// Oracle prices recorded by advancing cranks
+    price_history: PriceHistory,
+    
+    /// Risk estimate refreshed on each advancing crank
+    value_at_risk: ValueAtRisk,
+    
+    /// Largest user OI share, refreshed on each crank
+    concentration: ConcentrationStatus,
+    
+    /// Agent param updates blocked by operator veto
+    params_vetoed: bool,
+    
+    /// Params in effect before the agent's latest update (restored on veto)
+    previous_market_params: MarketParams,
+    
+    /// Current withdrawal rate-limit window
+    withdrawal_window: WithdrawalWindow,
+    
+    /// Keeper reward schedule
+    keeper_params: KeeperRewardParams,
+    
+    /// Unclaimed keeper rewards
+    keeper_ledger: KeeperLedger,
+    
+    /// Maker rebate schedule (None = no rebates)
+    maker_rebate: Option<MakerRebateParams>,
+    
+    /// Referral program (None = off)
+    referral_policy: Option<ReferralPolicy>,
+    
+    /// Protocol caps on the referral program
+    referral_bounds: ReferralBounds,
+    
+    /// Referrer links and unclaimed referral rebates
+    referrals: ReferralLedger,
+    
+    /// Protocol bounds on the agent's dust policy
+    dust_bounds: DustBounds,
+    
+    /// Last activity per account slot (for idle detection)
+    activity: [ActivityStamp; MAX_ACCOUNTS],
+    
+    /// Last sequence number per account slot (replay protection)
+    sequences: [SequenceStamp; MAX_ACCOUNTS],
+    
+    /// Next slot the dust sweep scans
+    dust_cursor: u16,
+    
+    /// Result of the last dust sweep
+    last_dust_sweep: DustSweep,
+    
+    /// Last executed trade
+    last_fill: Option<TradeFill>,
+    
+    /// Meta of the agent's last trade decision
+    last_decision_meta: Option<DecisionMeta>,
+    
+    /// Outcome of the last agent-driven trade
+    last_trade_outcome: Option<TradeOutcome>,
+    
+    /// Recent client order IDs and their outcomes
+    client_orders: ClientOrderLog,
+    
+    /// Commit-reveal timing and whether it is required
+    commit_reveal: CommitRevealParams,
+    
+    /// Batch auction mode (None = trades are priced one by one)
+    batch_auction: Option<BatchAuctionParams>,
+    
+    /// Orders of the batch being collected
+    batch_book: BatchBook,
+    
+    /// Result of the last cleared batch
+    last_batch: Option<BatchReport>,
+    
+    /// Fills of the last cleared batch
+    batch_fills: [Option<TradeFill>; BATCH_BOOK_LEN],
+    
+    /// Live trade commitments
+    commit_book: CommitBook,
+    
+    /// Extensions copied into every agent context
+    context_extensions: ContextExtensions,
+    
+    /// Protocol bounds on the agent's withdrawal policy
+    withdrawal_bounds: WithdrawalBounds,
+    
+    /// Large withdrawals awaiting release
+    withdrawal_queue: WithdrawalQueue,
+    
+    /// Passive LP shares of the agent's LP account
+    lp_pool: LpPool,
+    
+    /// Protocol bounds on the agent's lending policy
+    lending_bounds: LendingBounds,
+    
+    /// Margin loans from the LP reserve
+    lending: LendingBook,
+    
+    /// Delisting in progress (None = market listed)
+    wind_down: Option<WindDown>,
+    
+    /// Dead-man switch thresholds (None = disarmed)
+    dead_man: Option<DeadManParams>,
+    
+    /// Dead-man stage applied at the last crank
+    dead_man_stage: DeadManStage,
+    
+    /// Engine margins before the dead-man switch widened them
+    widened_margins: Option<WidenedMargins>,
+    
+    /// Slot of the agent's last valid output
+    last_agent_activity_slot: u64,
+    
+    /// Slot of the agent's last heartbeat
+    last_heartbeat_slot: Option<u64>,
+    
+    /// Flapping thresholds (None = disarmed)
+    flapping_guard: Option<FlappingGuard>,
+    
+    /// Recent changes of agent decisions
+    flips: FlipLog,
+    
+    /// Agent param updates locked after flapping (released by admin)
+    params_locked: bool,
+    
+    /// Events raised for the operator
+    alerts: AlertLog,
+    
+    /// Fills, liquidations and param changes not yet drained to indexers
+    events: EventLog,
+    
+    /// Accounts with positions, bucketed by liquidation price
+    liquidation_index: LiquidationIndex,
+    
+    /// Dutch-auction schedule for bankrupt positions (None = close at oracle)
+    liquidation_auction_params: Option<LiquidationAuctionParams>,
+    
+    /// Bankrupt positions on offer
+    liquidation_auctions: LiquidationAuctions,
+    
+    /// Terms for backstop LPs (None = no backstop role)
+    backstop_params: Option<BackstopParams>,
+    
+    /// Accounts registered to absorb liquidated positions
+    backstops: BackstopRegistry,
+    
+    /// Holes left by bankrupt accounts and how they were covered
+    bankruptcies: BankruptcyLedger,
+    
+    /// Agent's last published pool utilization cap (bps)
+    pool_utilization_cap_bps: u64,
+    
+    /// Protocol ranges for the agent's fee split
+    fee_split_bounds: FeeSplitBounds,
+    
+    /// Treasury account (index, account id)
+    treasury: Option<(u16, u64)>,
+    
+    /// Fees distributed since creation
+    fee_totals: FeeTotals,
+    
+    /// Fees distributed in the current accounting period
+    epoch_fee_totals: FeeTotals,
+    
+    /// Epoch length in slots
+    epoch_slots: u64,
+    
+    /// Epoch in progress (fees are tracked in `epoch_fee_totals`)
+    epoch: EpochReport,
+    
+    /// Finalized epochs
+    epoch_history: EpochHistory,
+}
+
+impl ClawcolatorEngine {
+    /// Create new Clawcolator engine
+    pub fn new(base_params: RiskParams) -> Self {
+        let mut market = Self {
+            engine: RiskEngine::new(base_params),
+            market_params: MarketParams::default(),
+            shutdown: false,
+            market_frozen: false,
+            delta: DeltaTracker::new(),
+            account_tiers: [TierStamp::NONE; MAX_ACCOUNTS],
+            sub_links: [SubAccountLink::NONE; MAX_ACCOUNTS],
+            margin_modes: [MarginModeStamp::NONE; MAX_ACCOUNTS],
+            hedge: HedgePosition::default(),
+            escalation: EscalationPolicy::default(),
+            shutdown_streak: 0,
+            anomaly_history: AnomalyHistory::default(),
+            calm_streak: 0,
+            freeze_origin: None,
+            pre_anomaly_max_position: None,
+            oracle_bounds: OracleBounds::default(),
+            oracle_divergence_bps: 0,
+            risk_reduction: RiskReductionTriggers::default(),
+            risk_reduction_limits: RiskReductionLimits::default(),
+            operator: None,
+            governance: None,
+            max_leverage_ceiling_bps: 10_000,
+            vamm_bounds: VammBounds::default(),
+            impact_model: DEFAULT_IMPACT_MODEL,
+            concentration_bounds: ConcentrationBounds::default(),
+            warmup_bounds: WarmupBounds::default(),
+            market_mode: MarketMode::AgentDriven,
+            pending_market_mode: None,
+            price_history: PriceHistory::default(),
+            value_at_risk: ValueAtRisk::default(),
+            concentration: ConcentrationStatus::default(),
+            params_vetoed: false,
+            previous_market_params: MarketParams::default(),
+            withdrawal_window: WithdrawalWindow::default(),
+            keeper_params: KeeperRewardParams::default(),
+            keeper_ledger: KeeperLedger::new(),
+            maker_rebate: None,
+            referral_policy: None,
+            referral_bounds: ReferralBounds::default(),
+            referrals: ReferralLedger::new(),
+            dust_bounds: DustBounds::default(),
+            activity: [ActivityStamp::NONE; MAX_ACCOUNTS],
+            sequences: [SequenceStamp::NONE; MAX_ACCOUNTS],
+            dust_cursor: 0,
+            last_dust_sweep: DustSweep::default(),
+            last_fill: None,
+            last_decision_meta: None,
+            last_trade_outcome: None,
+            client_orders: ClientOrderLog::default(),
+            commit_reveal: CommitRevealParams::default(),
+            commit_book: CommitBook::new(),
+            batch_auction: None,
+            batch_book: BatchBook::new(),
+            last_batch: None,
+            batch_fills: [None; BATCH_BOOK_LEN],
+            context_extensions: ContextExtensions::default(),
+            withdrawal_bounds: WithdrawalBounds::default(),
+            withdrawal_queue: WithdrawalQueue::new(),
+            lp_pool: LpPool::new(),
+            lending_bounds: LendingBounds::default(),
+            lending: LendingBook::new(),
+            wind_down: None,
+            dead_man: None,
+            dead_man_stage: DeadManStage::Live,
+            widened_margins: None,
+            last_agent_activity_slot: 0,
+            last_heartbeat_slot: None,
+            flapping_guard: None,
+            flips: FlipLog::new(),
+            params_locked: false,
+            alerts: AlertLog::new(),
+            events: EventLog::new(),
+            liquidation_index: LiquidationIndex::new(),
+            liquidation_auction_params: None,
+            liquidation_auctions: LiquidationAuctions::new(),
+            backstop_params: None,
+            backstops: BackstopRegistry::new(),
+            bankruptcies: BankruptcyLedger::new(),
+            pool_utilization_cap_bps: 10_000,
+            fee_split_bounds: FeeSplitBounds::default(),
+            treasury: None,
+            fee_totals: FeeTotals::default(),
+            epoch_fee_totals: FeeTotals::default(),
+            epoch_slots: DEFAULT_EPOCH_SLOTS,
+            epoch: EpochReport::default(),
+            epoch_history: EpochHistory::default(),
+        };
+        // Liquidations run off the liquidation index, not the engine sweep
+        market.engine.set_deferred_liquidations(true);
+        market
+    }
+    
+    /// Initialize in place (for Solana BPF)
+    pub fn init_in_place(&mut self, base_params: RiskParams) {
+        self.engine.init_in_place(base_params);
+        self.engine.set_deferred_liquidations(true);
+        self.market_params = MarketParams::default();
+        self.shutdown = false;
+        self.market_frozen = false;
+        self.delta = DeltaTracker::new();
+        self.account_tiers = [TierStamp::NONE; MAX_ACCOUNTS];
+        self.sub_links = [SubAccountLink::NONE; MAX_ACCOUNTS];
+        self.margin_modes = [MarginModeStamp::NONE; MAX_ACCOUNTS];
+        self.hedge = HedgePosition::default();
+        self.escalation = EscalationPolicy::default();
+        self.shutdown_streak = 0;
+        self.anomaly_history = AnomalyHistory::default();
+        self.calm_streak = 0;
+        self.freeze_origin = None;
+        self.pre_anomaly_max_position = None;
+        self.oracle_bounds = OracleBounds::default();
+        self.oracle_divergence_bps = 0;
+        self.risk_reduction = RiskReductionTriggers::default();
+        self.risk_reduction_limits = RiskReductionLimits::default();
+        self.operator = None;
+        self.governance = None;
+        self.max_leverage_ceiling_bps = 10_000;
+        self.vamm_bounds = VammBounds::default();
+        self.impact_model = DEFAULT_IMPACT_MODEL;
+        self.concentration_bounds = ConcentrationBounds::default();
+        self.warmup_bounds = WarmupBounds::default();
+        self.market_mode = MarketMode::AgentDriven;
+        self.pending_market_mode = None;
+        self.price_history = PriceHistory::default();
+        self.value_at_risk = ValueAtRisk::default();
+        self.concentration = ConcentrationStatus::default();
+        self.params_vetoed = false;
+        self.previous_market_params = MarketParams::default();
+        self.withdrawal_window = WithdrawalWindow::default();
+        self.keeper_params = KeeperRewardParams::default();
+        self.keeper_ledger = KeeperLedger::new();
+        self.maker_rebate = None;
+        self.referral_policy = None;
+        self.referral_bounds = ReferralBounds::default();
+        self.referrals = ReferralLedger::new();
+        self.dust_bounds = DustBounds::default();
+        self.activity = [ActivityStamp::NONE; MAX_ACCOUNTS];
+        self.sequences = [SequenceStamp::NONE; MAX_ACCOUNTS];
+        self.dust_cursor = 0;
+        self.last_dust_sweep = DustSweep::default();
+        self.last_fill = None;
+        self.last_decision_meta = None;
+        self.last_trade_outcome = None;
+        self.client_orders = ClientOrderLog::default();
+        self.commit_reveal = CommitRevealParams::default();
+        self.commit_book = CommitBook::new();
+        self.batch_auction = None;
+        self.batch_book = BatchBook::new();
+        self.last_batch = None;
+        self.batch_fills = [None; BATCH_BOOK_LEN];
+        self.context_extensions = ContextExtensions::default();
+        self.withdrawal_bounds = WithdrawalBounds::default();
+        self.withdrawal_queue = WithdrawalQueue::new();
+        self.lp_pool = LpPool::new();
+        self.lending_bounds = LendingBounds::default();
+        self.lending = LendingBook::new();
+        self.wind_down = None;
+        self.dead_man = None;
+        self.dead_man_stage = DeadManStage::Live;
+        self.widened_margins = None;
+        self.last_agent_activity_slot = 0;
+        self.last_heartbeat_slot = None;
+        self.flapping_guard = None;
+        self.flips = FlipLog::new();
+        self.params_locked = false;
+        self.alerts = AlertLog::new();
+        self.events = EventLog::new();
+        self.liquidation_index = LiquidationIndex::new();
+        self.liquidation_auction_params = None;
+        self.liquidation_auctions = LiquidationAuctions::new();
+        self.backstop_params = None;
+        self.backstops = BackstopRegistry::new();
+        self.bankruptcies = BankruptcyLedger::new();
+        self.pool_utilization_cap_bps = 10_000;
+        self.fee_split_bounds = FeeSplitBounds::default();
+        self.treasury = None;
+        self.fee_totals = FeeTotals::default();
+        self.epoch_fee_totals = FeeTotals::default();
+        self.epoch_slots = DEFAULT_EPOCH_SLOTS;
+        self.epoch = EpochReport::default();
+        self.epoch_history = EpochHistory::default();
+    }
+    
+    /// Build agent context from current engine state
+    pub fn build_context(&self, oracle: OraclePrice) -> AgentContext {
+        AgentContext {
+            current_slot: self.engine.current_slot,
+            oracle_price: oracle.price,
+            oracle_confidence: oracle.confidence,
+            oracle_publish_slot: oracle.publish_slot,
+            oracle_stale: oracle.is_stale(
+                self.engine.current_slot,
+                self.oracle_bounds.max_staleness_slots,
+            ),
+            vault: self.engine.vault.get(),
+            insurance_balance: self.engine.insurance_fund.balance.get(),
+            total_capital: self.engine.c_tot.get(),
+            total_positive_pnl: self.engine.pnl_pos_tot.get(),
+            total_open_interest: self.engine.total_open_interest.get(),
+            risk_params: self.engine.params,
+            risk_reduction_mode: self.reduce_only(),
+            risk_reduction_triggers: self.risk_reduction,
+            params_vetoed: self.params_vetoed,
+            params_locked: self.params_locked,
+            market_mode: self.market_mode,
+            last_crank_slot: self.engine.last_crank_slot,
+            anomaly_history: self.anomaly_history,
+            oracle_divergence_bps: self.oracle_divergence_bps,
+            protocol_anomaly: (self.oracle_divergence_bps > self.oracle_bounds.max_divergence_bps)
+                .then_some(AnomalyType::OracleManipulation),
+            withdrawal_queue: self.withdrawal_queue.status(self.withdrawals_paused()),
+            impact_model: self.impact_model,
+            lp_liquidity: self.lp_equity(oracle.price),
+            concentration: self.concentration,
+            lp_liquidation_price: self.liquidation_price(AGENT_LP_IDX, oracle.price),
+            value_at_risk: self.value_at_risk,
+            heartbeat_age_slots: self.heartbeat_age(self.engine.current_slot),
+            bankruptcies: *self.bankruptcies.totals(),
+            extensions: self.context_extensions,
+        }
+    }
+    
+    /// Publish `value` under `key` in every agent context from now on
+    ///
+    /// Fails with `Overflow` when every extension slot is taken.
+    pub fn set_context_extension<T: ExtensionValue>(&mut self, key: ExtensionKey<T>, value: T) -> Result<()> {
+        self.context_extensions.insert(key, value)
+    }
+    
+    /// Stop publishing `key`; returns whether it was set
+    pub fn remove_context_extension<T>(&mut self, key: ExtensionKey<T>) -> bool {
+        self.context_extensions.remove(key)
+    }
+    
+    /// Execute trade with agent decision
+    ///
+    /// Flow:
+    /// 1. Check if system is shutdown/frozen and the oracle is usable
+    /// 2. Get agent's trade decision
+    /// 3. Validate decision
+    /// 4. Execute via underlying risk engine
+    ///
+    /// The fill, or why the trade was rejected, is kept in
+    /// `last_trade_outcome`.
+    pub fn execute_trade<A: OpenClawAgent>(
+        &mut self,
+        agent: &A,
+        user_idx: u16,
+        oracle: OraclePrice,
+        size: i128,
+        now_slot: u64,
+    ) -> Result<()> {
+        let request = TradeRequest {
+            user_idx,
+            size,
+            requested_price: None,
+            client_order_id: None,
+            sequence: None,
+            valid_until_slot: None,
+        };
+        self.execute_agent_trade(agent, None, TradeEntry::Direct, request, oracle, now_slot)
+    }
+    
+    /// Execute a full trade request: `execute_trade`, plus duplicate
+    /// suppression when it carries a client order ID, replay protection
+    /// when it carries a sequence number and a deadline when it carries
+    /// `valid_until_slot`
+    ///
+    /// A sequence not above the account's last one is rejected with
+    /// `RejectionCause::Sequence`; any other outcome consumes it, so a
+    /// rejected request cannot be replayed later either.
+    pub fn submit_trade<A: OpenClawAgent>(
+        &mut self,
+        agent: &A,
+        request: TradeRequest,
+        oracle: OraclePrice,
+        now_slot: u64,
+    ) -> Result<()> {
+        let Some(client_order_id) = request.client_order_id else {
+            return self.execute_agent_trade(agent, None, TradeEntry::Direct, request, oracle, now_slot);
+        };
+        let user_idx = request.user_idx;
+        if let Some(order) = self.client_orders.find(user_idx, client_order_id, now_slot) {
+            let outcome = order.outcome;
+            self.last_trade_outcome = Some(outcome);
+            return match outcome {
+                TradeOutcome::Filled(_) => Ok(()),
+                TradeOutcome::Rejected(rejection) => Err(rejection.error),
+            };
+        }
+        let result = self.execute_agent_trade(agent, None, TradeEntry::Direct, request, oracle, now_slot);
+        if let Some(outcome) = self.last_trade_outcome {
+            self.client_orders.record(ClientOrder {
+                user_idx,
+                client_order_id,
+                slot: now_slot,
+                outcome,
+            });
+        }
+        result
+    }
+    
+    /// Execute trade with agent decision, at most once per client order ID
+    ///
+    /// Resubmitting `client_order_id` for the same account within the dedup
+    /// window (`DEFAULT_CLIENT_ORDER_WINDOW_SLOTS`) does not trade again: the
+    /// original outcome is restored into `last_trade_outcome` and its result
+    /// returned. Rejections are remembered too, so a retry needs a new ID.
+    pub fn execute_trade_with_id<A: OpenClawAgent>(
+        &mut self,
+        agent: &A,
+        user_idx: u16,
+        oracle: OraclePrice,
+        size: i128,
+        client_order_id: u64,
+        now_slot: u64,
+    ) -> Result<()> {
+        let request = TradeRequest {
+            user_idx,
+            size,
+            requested_price: None,
+            client_order_id: Some(client_order_id),
+            sequence: None,
+            valid_until_slot: None,
+        };
+        self.submit_trade(agent, request, oracle, now_slot)
+    }
+    
+    /// Commit to a trade without showing it to the agent
+    ///
+    /// `commitment` is `TradeReveal::commitment` of the trade to reveal in
+    /// a later slot with `reveal_trade`. Expired commitments are purged
+    /// first; an account holds at most `MAX_COMMITS_PER_ACCOUNT`.
+    pub fn commit_trade(&mut self, user_idx: u16, commitment: [u8; 32], now_slot: u64) -> Result<()> {
+        if user_idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(user_idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let account = &self.engine.accounts[user_idx as usize];
+        if !account.is_user() {
+            return Err(RiskError::AccountKindMismatch);
+        }
+        if self.shutdown || self.market_frozen {
+            return Err(RiskError::Unauthorized);
+        }
+        self.commit_book.purge_expired(now_slot, self.commit_reveal.max_delay_slots);
+        if self.commit_book.count_for(user_idx) >= MAX_COMMITS_PER_ACCOUNT {
+            return Err(RiskError::Unauthorized);
+        }
+        let commit = TradeCommitment {
+            user_idx,
+            account_id: account.account_id,
+            hash: commitment,
+            slot: now_slot,
+        };
+        if !self.commit_book.insert(commit) {
+            return Err(RiskError::Overflow); // Book full
+        }
+        self.mark_active(user_idx);
+        Ok(())
+    }
+    
+    /// Reveal a committed trade and execute it through the agent
+    ///
+    /// The commitment is consumed whatever the outcome. A reveal before
+    /// `min_delay_slots` or after `max_delay_slots`, or one matching no
+    /// live commitment of the account, is rejected with
+    /// `RejectionCause::Commitment`; a fill worse than the committed
+    /// slippage with `RejectionCause::Slippage`.
+    pub fn reveal_trade<A: OpenClawAgent>(
+        &mut self,
+        agent: &A,
+        reveal: TradeReveal,
+        oracle: OraclePrice,
+        now_slot: u64,
+    ) -> Result<()> {
+        let request = TradeRequest {
+            user_idx: reveal.user_idx,
+            size: reveal.size,
+            requested_price: None,
+            client_order_id: None,
+            sequence: None,
+            valid_until_slot: None,
+        };
+        let commit = self.commit_book.take(reveal.user_idx, &reveal.commitment());
+        let account_id = self.engine.accounts.get(reveal.user_idx as usize).map(|a| a.account_id);
+        let params = self.commit_reveal;
+        let opened = commit.filter(|c| {
+            let age = now_slot.saturating_sub(c.slot);
+            Some(c.account_id) == account_id
+                && now_slot >= c.slot
+                && age >= params.min_delay_slots
+                && age <= params.max_delay_slots
+        });
+        if opened.is_none() {
+            let mut rejection = TradeRejection::new(RejectionCause::Commitment, RiskError::Unauthorized);
+            rejection.limits = self.trade_limits(reveal.user_idx, reveal.size, oracle.price);
+            self.last_decision_meta = None;
+            self.last_trade_outcome = Some(TradeOutcome::Rejected(rejection));
+            return Err(rejection.error);
+        }
+        let entry = TradeEntry::Revealed { max_slippage_bps: reveal.max_slippage_bps };
+        self.execute_agent_trade(agent, None, entry, request, oracle, now_slot)
+    }
+    
+    /// Require (or stop requiring) commit-reveal and set its timing
+    pub fn set_commit_reveal(&mut self, params: CommitRevealParams) -> Result<()> {
+        if !params.is_valid() {
+            return Err(RiskError::Overflow);
+        }
+        self.commit_reveal = params;
+        Ok(())
+    }
+    
+    /// Commit-reveal timing and whether it is required
+    pub fn commit_reveal(&self) -> &CommitRevealParams {
+        &self.commit_reveal
+    }
+    
+    /// Live trade commitments
+    pub fn commit_book(&self) -> &CommitBook {
+        &self.commit_book
+    }
+    
+    /// Switch batch auction mode on (`Some`) or off
+    ///
+    /// While on, trades are only accepted through `submit_batch_order`.
+    /// Turning it off with orders still pending is refused.
+    pub fn set_batch_auction(&mut self, params: Option<BatchAuctionParams>) -> Result<()> {
+        if params.is_some_and(|p| !p.is_valid()) {
+            return Err(RiskError::Overflow);
+        }
+        if params.is_none() && self.batch_book.opened_slot().is_some() {
+            return Err(RiskError::Unauthorized);
+        }
+        self.batch_auction = params;
+        Ok(())
+    }
+    
+    /// Batch auction parameters, if batch mode is on
+    pub fn batch_auction(&self) -> Option<&BatchAuctionParams> {
+        self.batch_auction.as_ref()
+    }
+    
+    /// Orders of the batch being collected
+    pub fn batch_book(&self) -> &BatchBook {
+        &self.batch_book
+    }
+    
+    /// Result of the last cleared batch
+    pub fn last_batch(&self) -> Option<&BatchReport> {
+        self.last_batch.as_ref()
+    }
+    
+    /// Fills of the last cleared batch
+    pub fn batch_fills(&self) -> impl Iterator<Item = &TradeFill> {
+        self.batch_fills.iter().flatten()
+    }
+    
+    /// Add a trade to the current batch; orders of one account are netted
+    pub fn submit_batch_order(&mut self, user_idx: u16, size: i128, now_slot: u64) -> Result<()> {
+        if self.batch_auction.is_none() || self.shutdown || self.market_frozen {
+            return Err(RiskError::Unauthorized);
+        }
+        if user_idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(user_idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let account = &self.engine.accounts[user_idx as usize];
+        if !account.is_user() {
+            return Err(RiskError::AccountKindMismatch);
+        }
+        if size == 0 || size == i128::MIN {
+            return Err(RiskError::Overflow);
+        }
+        if !self.batch_book.add(user_idx, account.account_id, size, now_slot) {
+            return Err(RiskError::Overflow); // Book full
+        }
+        self.mark_active(user_idx);
+        Ok(())
+    }
+    
+    /// Withdraw the account's order from the current batch
+    pub fn cancel_batch_order(&mut self, user_idx: u16) -> Option<i128> {
+        self.batch_book.cancel(user_idx)
+    }
+    
+    /// Clear the current batch once `batch_slots` have passed since it opened
+    ///
+    /// The agent is asked once for a uniform price, which must lie within
+    /// `max_deviation_bps` of the oracle; otherwise the batch stays open and
+    /// `InvalidMatchingEngine` is returned. Each order then runs through the
+    /// usual protocol checks at that price; the outcome of the last one is
+    /// in `last_trade_outcome`, the totals in the returned report.
+    pub fn clear_batch<A: OpenClawAgent>(
+        &mut self,
+        agent: &A,
+        oracle: OraclePrice,
+        now_slot: u64,
+    ) -> Result<BatchReport> {
+        let _op = metering::enter(MeteredOp::ClearBatch);
+        let Some(params) = self.batch_auction else {
+            return Err(RiskError::Unauthorized);
+        };
+        let Some(opened) = self.batch_book.opened_slot() else {
+            return Err(RiskError::Unauthorized); // Nothing to clear
+        };
+        if now_slot < opened.saturating_add(params.batch_slots) || self.shutdown || self.market_frozen {
+            return Err(RiskError::Unauthorized);
+        }
+        self.validate_oracle(&oracle, now_slot)?;
+        
+        let context = self.build_context(oracle);
+        let price = if agent.agent_capabilities().batch_clearing {
+            agent.batch_clearing_price(&context, &self.batch_book.summary())?
+        } else {
+            oracle.price
+        };
+        let band = apply_bps(oracle.price as u128, params.max_deviation_bps, Rounding::Down);
+        if price == 0 || (price as u128).abs_diff(oracle.price as u128) > band {
+            return Err(RiskError::InvalidMatchingEngine);
+        }
+        self.pool_utilization_cap_bps = pool_utilization_cap(agent, &context)?;
+        self.record_agent_activity(now_slot);
+        
+        let mut report = BatchReport { slot: now_slot, price, ..BatchReport::default() };
+        self.batch_fills = [None; BATCH_BOOK_LEN];
+        for (order, fill_slot) in self.batch_book.drain().into_iter().flatten().zip(0..) {
+            metering::iterate(1);
+            let current = self.engine.accounts.get(order.user_idx as usize).map(|a| a.account_id);
+            let request = TradeRequest {
+                user_idx: order.user_idx,
+                size: order.size,
+                requested_price: None,
+                client_order_id: None,
+                sequence: None,
+                valid_until_slot: None,
+            };
+            let entry = TradeEntry::Batch { price };
+            let filled = current == Some(order.account_id)
+                && self.execute_agent_trade(agent, None, entry, request, oracle, now_slot).is_ok();
+            match (filled, self.last_fill) {
+                (true, Some(fill)) => {
+                    self.batch_fills[fill_slot] = Some(fill);
+                    report.filled = report.filled.saturating_add(1);
+                    let notional = math::notional(fill.size.unsigned_abs(), price, Rounding::Down);
+                    report.volume = report.volume.saturating_add(notional);
+                }
+                _ => report.rejected = report.rejected.saturating_add(1),
+            }
+        }
+        self.last_batch = Some(report);
+        Ok(report)
+    }
+    
+    /// Execute trade with agent decision, filling accepted trades through
+    /// `matcher` (hybrid execution)
+    ///
+    /// The agent's accepted price acts as a limit: the matcher may improve
+    /// on it and fill less than the agent accepted, but never cross it,
+    /// overfill or flip direction.
+    pub fn execute_trade_routed<A: OpenClawAgent, M: MatchingEngine>(
+        &mut self,
+        agent: &A,
+        matcher: &M,
+        user_idx: u16,
+        oracle: OraclePrice,
+        size: i128,
+        now_slot: u64,
+    ) -> Result<()> {
+        let request = TradeRequest {
+            user_idx,
+            size,
+            requested_price: None,
+            client_order_id: None,
+            sequence: None,
+            valid_until_slot: None,
+        };
+        self.execute_agent_trade(agent, Some(matcher), TradeEntry::Direct, request, oracle, now_slot)
+    }
+    
+    fn execute_agent_trade<A: OpenClawAgent>(
+        &mut self,
+        agent: &A,
+        route: Option<&dyn MatchingEngine>,
+        entry: TradeEntry,
+        request: TradeRequest,
+        oracle: OraclePrice,
+        now_slot: u64,
+    ) -> Result<()> {
+        let _op = metering::enter(MeteredOp::Trade);
+        let span = trace::OpSpan::trade(request.user_idx, request.size);
+        self.last_decision_meta = None;
+        let outcome = match self.run_agent_trade(agent, route, entry, request, oracle, now_slot) {
+            Ok(fill) => TradeOutcome::Filled(fill),
+            Err(mut rejection) => {
+                rejection.limits = self.trade_limits(request.user_idx, request.size, oracle.price);
+                TradeOutcome::Rejected(rejection)
+            }
+        };
+        self.last_trade_outcome = Some(outcome);
+        span.finish_trade(Some(&outcome));
+        match outcome {
+            TradeOutcome::Filled(_) => Ok(()),
+            TradeOutcome::Rejected(rejection) => Err(rejection.error),
+        }
+    }
+    
+    fn run_agent_trade<A: OpenClawAgent>(
+        &mut self,
+        agent: &A,
+        route: Option<&dyn MatchingEngine>,
+        entry: TradeEntry,
+        request: TradeRequest,
+        oracle: OraclePrice,
+        now_slot: u64,
+    ) -> core::result::Result<TradeFill, TradeRejection> {
+        let reject = |cause| move |error| TradeRejection::new(cause, error);
+        let TradeRequest { user_idx, size, .. } = request;
+        if entry == TradeEntry::Direct && self.commit_reveal.required {
+            return Err(TradeRejection::new(RejectionCause::CommitRequired, RiskError::Unauthorized));
+        }
+        let batch = matches!(entry, TradeEntry::Batch { .. });
+        if self.batch_auction.is_some() && !batch {
+            return Err(TradeRejection::new(RejectionCause::BatchMode, RiskError::Unauthorized));
+        }
+        if let Some(sequence) = request.sequence {
+            self.consume_sequence(user_idx, sequence)
+                .map_err(reject(RejectionCause::Sequence))?;
+        }
+        if request.valid_until_slot.is_some_and(|deadline| now_slot > deadline) {
+            return Err(TradeRejection::new(RejectionCause::Expired, RiskError::Unauthorized));
+        }
+        let oracle_price = oracle.price;
+        let plan = self.plan_agent_trade(agent, entry, request, oracle, now_slot)?;
+        self.last_decision_meta = plan.meta;
+        if !batch {
+            self.record_agent_activity(now_slot);
+        }
+        
+        // The vAMM fills on its own curve, never through the router
+        let mut route = route;
+        if plan.vamm {
+            route = None;
+            self.epoch.scorecard.vamm_fills = self.epoch.scorecard.vamm_fills.saturating_add(1);
+        }
+        
+        // Process decision
+        let (price, exec_size) = match accepted_fill(plan.decision) {
+            Ok(fill) => fill,
+            Err(rejection) => {
+                let scorecard = &mut self.epoch.scorecard;
+                scorecard.trades_rejected = scorecard.trades_rejected.saturating_add(1);
+                return Err(rejection);
+            }
+        };
+        if let TradeEntry::Revealed { max_slippage_bps } = entry {
+            validate_slippage(price, exec_size, oracle_price, max_slippage_bps)
+                .map_err(reject(RejectionCause::Slippage))?;
+        }
+        
+        // Validate agent's decision
+        self.validate_fill(user_idx, price, exec_size, size, plan.vamm || batch, oracle_price)?;
+        let top_up = self
+            .cross_margin_top_up_for_trade(user_idx, exec_size, oracle_price)
+            .map_err(reject(RejectionCause::CrossMargin))?;
+        // A batch asks the agent for its cap once, when it is cleared
+        if !batch {
+            self.pool_utilization_cap_bps = pool_utilization_cap(agent, &plan.context)
+                .map_err(reject(RejectionCause::AgentError))?;
+        }
+        self.validate_fill_limits(
+            user_idx,
+            exec_size,
+            oracle_price,
+            self.pool_utilization_cap_bps,
+            top_up.map_or(0, |top_up| top_up.amount),
+        )?;
+        
+        // Hybrid execution: the routed fill replaces the agent's
+        let (price, exec_size) = match route {
+            Some(matcher) => self
+                .route_fill(matcher, price, exec_size, oracle_price)
+                .map_err(reject(RejectionCause::InvalidFill))?,
+            None => (price, exec_size),
+        };
+        
+        // Execute via underlying engine
+        // Note: We need to adapt this to work with agent's decision
+        // For now, we'll use a simple matcher that respects agent's decision
+        let matcher = AgentMatcher {
+            price,
+            size: exec_size,
+        };
+        
+        // Find LP account (in Clawcolator, agent IS the LP)
+        let lp_idx = AGENT_LP_IDX;
+        
+        self.record_change(lp_idx);
+        self.record_change(user_idx);
+        self.mark_active(user_idx);
+        let fee_revenue = self.engine.insurance_fund.fee_revenue.get();
+        // Parent capital moves only once the fill is otherwise cleared
+        if let Some(top_up) = top_up {
+            self.transfer_capital(top_up.parent, user_idx, top_up.amount, oracle_price)
+                .map_err(reject(RejectionCause::CrossMargin))?;
+        }
+        if let Err(error) =
+            self.engine.execute_trade(&matcher, lp_idx, user_idx, now_slot, oracle_price, size)
+        {
+            if let Some(top_up) = top_up {
+                self.return_cross_margin(top_up, user_idx);
+            }
+            return Err(TradeRejection::new(RejectionCause::Engine, error));
+        }
+        self.collect_bankruptcies();
+        self.reindex_liquidation(user_idx, oracle_price);
+        self.reindex_liquidation(lp_idx, oracle_price);
+        
+        // Trading fee as charged by the engine, never more than was booked
+        let notional =
+            math::notional(saturating_abs_i128(exec_size) as u128, price, Rounding::Down);
+        let fee = apply_bps(notional, self.engine.params.trading_fee_bps, Rounding::Up);
+        let booked = self.engine.insurance_fund.fee_revenue.get().saturating_sub(fee_revenue);
+        let maker_like = self
+            .maker_rebate
+            .is_some_and(|params| params.is_maker_like(exec_size, price, oracle_price));
+        self.distribute_fee(fee.min(booked), user_idx, maker_like);
+        self.rebate_backstop_fee(user_idx, fee.min(booked));
+        
+        let scorecard = &mut self.epoch.scorecard;
+        scorecard.trades_accepted = scorecard.trades_accepted.saturating_add(1);
+        scorecard.volume = scorecard.volume.saturating_add(notional);
+        let fill = TradeFill {
+            slot: now_slot,
+            user_idx,
+            lp_idx,
+            price,
+            size: exec_size,
+            vamm: plan.vamm,
+            meta: plan.meta,
+            client_order_id: request.client_order_id,
+        };
+        self.last_fill = Some(fill);
+        self.events.push(MarketEvent::Trade(fill));
+        Ok(fill)
+    }
+    
+    /// Protocol gates and the agent's decision, with the vAMM fallback
+    /// applied when the agent is out of liquidity
+    fn plan_agent_trade<A: OpenClawAgent>(
+        &self,
+        agent: &A,
+        entry: TradeEntry,
+        request: TradeRequest,
+        oracle: OraclePrice,
+        now_slot: u64,
+    ) -> core::result::Result<PlannedTrade, TradeRejection> {
+        // Check system state
+        if self.shutdown {
+            return Err(TradeRejection::new(RejectionCause::Shutdown, RiskError::Unauthorized));
+        }
+        if self.market_frozen {
+            return Err(TradeRejection::new(RejectionCause::MarketFrozen, RiskError::Unauthorized));
+        }
+        if self.market_mode != MarketMode::AgentDriven {
+            return Err(TradeRejection::new(
+                RejectionCause::MarketMode,
+                RiskError::InvalidMatchingEngine,
+            ));
+        }
+        self.validate_oracle(&oracle, now_slot)
+            .map_err(|error| TradeRejection::new(RejectionCause::Oracle, error))?;
+        if self.engine.is_liquidation_held(request.user_idx as usize) {
+            return Err(TradeRejection::new(
+                RejectionCause::LiquidationAuction,
+                RiskError::Unauthorized,
+            ));
+        }
+        
+        // Build context
+        let context = self.build_context(oracle);
+        
+        // Batch orders are priced for the whole batch, not one by one
+        if let TradeEntry::Batch { price } = entry {
+            let decision = TradeDecision::Accept { price, size: request.size };
+            return Ok(PlannedTrade { decision, meta: None, vamm: false, context });
+        }
+        
+        // Get agent decision
+        let (decision, meta) = agent
+            .decide_trade_with_meta(&context, &request)
+            .map_err(|error| TradeRejection::new(RejectionCause::AgentError, error))?;
+        
+        // Out of agent liquidity: fall back to the vAMM curve if enabled
+        let mut vamm = false;
+        let decision = match decision {
+            TradeDecision::Reject {
+                reason: TradeRejectionReason::InsufficientLiquidity,
+            } => match vamm_quote(
+                self.market_params.vamm_depth,
+                oracle.price,
+                request.size,
+                self.vamm_bounds.max_fill_bps,
+            ) {
+                Some(price) => {
+                    vamm = true;
+                    TradeDecision::Accept { price, size: request.size }
+                }
+                None => decision,
+            },
+            other => other,
+        };
+        Ok(PlannedTrade { decision, meta, vamm, context })
+    }
+    
+    /// Checks on the fill itself: bounds, price impact, risk reduction
+    ///
+    /// vAMM and batch fills skip the impact check; their prices are bounded
+    /// by the curve and the batch band instead.
+    fn validate_fill(
+        &self,
+        user_idx: u16,
+        price: u64,
+        exec_size: i128,
+        size: i128,
+        skip_impact: bool,
+        oracle_price: u64,
+    ) -> core::result::Result<(), TradeRejection> {
+        let reject = |cause| move |error| TradeRejection::new(cause, error);
+        self.validate_trade_execution(price, exec_size, size)
+            .map_err(|error| match error {
+                RiskError::Undercollateralized => TradeRejection::new(RejectionCause::SizeLimit, error),
+                _ => TradeRejection::new(RejectionCause::InvalidFill, error),
+            })?;
+        if !skip_impact {
+            self.validate_impact_price(price, exec_size, oracle_price)
+                .map_err(reject(RejectionCause::InvalidFill))?;
+        }
+        self.validate_risk_reduction(user_idx, exec_size)
+            .map_err(reject(RejectionCause::RiskReduction))
+    }
+    
+    /// Checks on the resulting positions: tier, pool utilization, concentration
+    ///
+    /// `top_up` is the parent capital a cross-margin sub-account receives
+    /// before the fill, counted towards its leverage.
+    fn validate_fill_limits(
+        &self,
+        user_idx: u16,
+        exec_size: i128,
+        oracle_price: u64,
+        utilization_cap_bps: u64,
+        top_up: u128,
+    ) -> core::result::Result<(), TradeRejection> {
+        let reject = |cause| move |error| TradeRejection::new(cause, error);
+        self.validate_tier_limits(user_idx, exec_size, oracle_price, top_up)
+            .map_err(reject(RejectionCause::SizeLimit))?;
+        self.validate_pool_utilization(exec_size, oracle_price, utilization_cap_bps)
+            .map_err(reject(RejectionCause::PoolUtilization))?;
+        self.validate_concentration(user_idx, exec_size)
+            .map_err(reject(RejectionCause::Concentration))
+    }
+    
+    /// Run a trade through the agent and every protocol and margin check
+    /// without changing any state
+    ///
+    /// Returns the projected fill, fee and the user's margin afterwards, or
+    /// the rejection `execute_trade` would record. A cross-margin
+    /// sub-account is projected with the parent top-up the fill would draw;
+    /// pending maintenance fees are not simulated, and the agent's decision
+    /// is only as repeatable as the agent.
+    pub fn execute_trade_dry_run<A: OpenClawAgent>(
+        &self,
+        agent: &A,
+        user_idx: u16,
+        oracle: OraclePrice,
+        size: i128,
+        now_slot: u64,
+    ) -> core::result::Result<TradeSimulation, TradeRejection> {
+        self.simulate_agent_trade(agent, user_idx, oracle, size, now_slot)
+            .map_err(|mut rejection| {
+                rejection.limits = self.trade_limits(user_idx, size, oracle.price);
+                rejection
+            })
+    }
+    
+    fn simulate_agent_trade<A: OpenClawAgent>(
+        &self,
+        agent: &A,
+        user_idx: u16,
+        oracle: OraclePrice,
+        size: i128,
+        now_slot: u64,
+    ) -> core::result::Result<TradeSimulation, TradeRejection> {
+        // Previews would show the agent uncommitted flow
+        if self.commit_reveal.required {
+            return Err(TradeRejection::new(RejectionCause::CommitRequired, RiskError::Unauthorized));
+        }
+        let oracle_price = oracle.price;
+        let request = TradeRequest {
+            user_idx,
+            size,
+            requested_price: None,
+            client_order_id: None,
+            sequence: None,
+            valid_until_slot: None,
+        };
+        let plan = self.plan_agent_trade(agent, TradeEntry::Direct, request, oracle, now_slot)?;
+        let (price, exec_size) = accepted_fill(plan.decision)?;
+        self.validate_fill(user_idx, price, exec_size, size, plan.vamm, oracle_price)?;
+        let top_up = self
+            .cross_margin_top_up_for_trade(user_idx, exec_size, oracle_price)
+            .map_err(|error| TradeRejection::new(RejectionCause::CrossMargin, error))?
+            .map_or(0, |top_up| top_up.amount);
+        let utilization_cap_bps = pool_utilization_cap(agent, &plan.context)
+            .map_err(|error| TradeRejection::new(RejectionCause::AgentError, error))?;
+        self.validate_fill_limits(user_idx, exec_size, oracle_price, utilization_cap_bps, top_up)?;
+        
+        let (user, fee) = self
+            .project_fill(user_idx, price, exec_size, oracle_price, now_slot, top_up)
+            .map_err(|error| TradeRejection::new(RejectionCause::Engine, error))?;
+        let position_size = user.position_size.get();
+        let equity = self.engine.account_equity_mtm_at_oracle(&user, oracle_price);
+        Ok(TradeSimulation {
+            price,
+            size: exec_size,
+            vamm: plan.vamm,
+            fee,
+            position_size,
+            equity,
+            margin_ratio_bps: margin::margin_ratio_bps(equity, position_size, oracle_price),
+            liquidation_price: margin::liquidation_price(
+                equity,
+                position_size,
+                oracle_price,
+                self.engine.params.maintenance_margin_bps,
+            ),
+            meta: plan.meta,
+        })
+    }
+    
+    /// User account after the engine books a fill, and the fee charged
+    ///
+    /// Mirrors `RiskEngine::execute_trade`: crank freshness, mark to the
+    /// oracle, trade PnL, fee from capital, and initial margin for risk
+    /// increasing sides (maintenance otherwise) on both user and LP.
+    /// `top_up` is credited to the user's capital first.
+    fn project_fill(
+        &self,
+        user_idx: u16,
+        price: u64,
+        exec_size: i128,
+        oracle_price: u64,
+        now_slot: u64,
+        top_up: u128,
+    ) -> Result<(Account, u128)> {
+        self.engine.require_fresh_crank(now_slot)?;
+        let lp_idx = AGENT_LP_IDX;
+        if !self.engine.is_used(lp_idx as usize) || !self.engine.is_used(user_idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let mut user = self.engine.accounts[user_idx as usize];
+        let lp = self.engine.accounts[lp_idx as usize];
+        if !lp.is_lp() || !user.is_user() {
+            return Err(RiskError::AccountKindMismatch);
+        }
+        user.capital = U128::new(user.capital.get().saturating_add(top_up));
+        if exec_size == 0 {
+            return Ok((user, 0));
+        }
+        
+        let notional = math::notional(saturating_abs_i128(exec_size) as u128, price, Rounding::Down);
+        let fee = apply_bps(notional, self.engine.params.trading_fee_bps, Rounding::Up);
+        let trade_pnl = (oracle_price as i128 - price as i128)
+            .checked_mul(exec_size)
+            .ok_or(RiskError::Overflow)?
+            / 1_000_000;
+        let project = |account: Account, fill: i128, pnl: i128, fee: u128| -> Result<Account> {
+            let mark = RiskEngine::mark_pnl_for_position(
+                account.position_size.get(),
+                account.entry_price,
+                oracle_price,
+            )?;
+            let mut projected = account;
+            projected.capital =
+                U128::new(account.capital.get().checked_sub(fee).ok_or(RiskError::InsufficientBalance)?);
+            projected.pnl = I128::new(account.pnl.get().saturating_add(mark).saturating_add(pnl));
+            projected.position_size = I128::new(account.position_size.get().saturating_add(fill));
+            projected.entry_price = oracle_price;
+            Ok(projected)
+        };
+        let new_user = project(user, exec_size, trade_pnl, fee)?;
+        let new_lp = project(lp, -exec_size, -trade_pnl, 0)?;
+        
+        let mut risk_increasing = false;
+        for (old, new) in [(&user, &new_user), (&lp, &new_lp)] {
+            let old_pos = old.position_size.get();
+            let new_pos = new.position_size.get();
+            if saturating_abs_i128(new_pos) as u128 > MAX_POSITION_ABS {
+                return Err(RiskError::Overflow);
+            }
+            let increasing = saturating_abs_i128(new_pos) > saturating_abs_i128(old_pos)
+                || (old_pos != 0 && new_pos != 0 && (old_pos > 0) != (new_pos > 0));
+            risk_increasing |= increasing;
+            let bps = if increasing {
+                self.engine.params.initial_margin_bps
+            } else {
+                self.engine.params.maintenance_margin_bps
+            };
+            if new_pos != 0 && !self.engine.is_above_margin_bps_mtm(new, oracle_price, bps) {
+                return Err(RiskError::Undercollateralized);
+            }
+        }
+        if risk_increasing {
+            self.engine.require_recent_full_sweep(now_slot)?;
+        }
+        Ok((new_user, fee))
+    }
+    
+    /// Execute a trade through a matching engine (classic market mode)
+    ///
+    /// The base percolator flow: `matcher` prices and fills against `lp_idx`
+    /// and the whole trading fee goes to insurance. Protocol-level checks
+    /// (shutdown, freeze, oracle, risk-reduction-only mode) still apply.
+    pub fn execute_classic_trade<M: MatchingEngine>(
+        &mut self,
+        matcher: &M,
+        lp_idx: u16,
+        user_idx: u16,
+        oracle: OraclePrice,
+        size: i128,
+        now_slot: u64,
+    ) -> Result<()> {
+        if self.shutdown || self.market_frozen {
+            return Err(RiskError::Unauthorized);
+        }
+        if self.market_mode != MarketMode::Classic {
+            return Err(RiskError::InvalidMatchingEngine);
+        }
+        self.validate_oracle(&oracle, now_slot)?;
+        if user_idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(user_idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        if lp_idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(lp_idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        
+        // Match first so reduce-only applies to the actual fill, then replay
+        // that fill through the engine
+        let lp = &self.engine.accounts[lp_idx as usize];
+        let execution = matcher.execute_match(
+            &lp.matcher_program,
+            &lp.matcher_context,
+            lp.account_id,
+            oracle.price,
+            size,
+        )?;
+        let filled = execution.size;
+        self.validate_risk_reduction(user_idx, filled)?;
+        
+        self.record_change(lp_idx);
+        self.record_change(user_idx);
+        self.mark_active(user_idx);
+        let replay = AgentMatcher {
+            price: execution.price,
+            size: filled,
+        };
+        self.engine.execute_trade(&replay, lp_idx, user_idx, now_slot, oracle.price, size)?;
+        self.collect_bankruptcies();
+        self.reindex_liquidation(user_idx, oracle.price);
+        self.reindex_liquidation(lp_idx, oracle.price);
+        
+        let notional = math::notional(
+            saturating_abs_i128(filled) as u128,
+            execution.price,
+            Rounding::Down,
+        );
+        let scorecard = &mut self.epoch.scorecard;
+        scorecard.trades_accepted = scorecard.trades_accepted.saturating_add(1);
+        scorecard.volume = scorecard.volume.saturating_add(notional);
+        let fill = TradeFill {
+            slot: now_slot,
+            user_idx,
+            lp_idx,
+            price: execution.price,
+            size: filled,
+            vamm: false,
+            meta: None,
+            client_order_id: None,
+        };
+        self.last_fill = Some(fill);
+        self.events.push(MarketEvent::Trade(fill));
+        Ok(())
+    }
+    
+    /// Fill an accepted trade through `matcher` with the agent's price as limit
+    fn route_fill(
+        &self,
+        matcher: &dyn MatchingEngine,
+        limit_price: u64,
+        exec_size: i128,
+        oracle_price: u64,
+    ) -> Result<(u64, i128)> {
+        if exec_size == 0 {
+            return Ok((limit_price, 0));
+        }
+        let lp = &self.engine.accounts[AGENT_LP_IDX as usize];
+        let execution = matcher.execute_match(
+            &lp.matcher_program,
+            &lp.matcher_context,
+            lp.account_id,
+            oracle_price,
+            exec_size,
+        )?;
+        let within_limit = if exec_size > 0 {
+            execution.price <= limit_price
+        } else {
+            execution.price >= limit_price
+        };
+        let overfill = saturating_abs_i128(execution.size) > saturating_abs_i128(exec_size);
+        let flipped = execution.size != 0 && (execution.size > 0) != (exec_size > 0);
+        if execution.price == 0 || !within_limit || overfill || flipped {
+            return Err(RiskError::InvalidMatchingEngine);
+        }
+        Ok((execution.price, execution.size))
+    }
+    
+    /// Validate trade execution from agent
+    fn validate_trade_execution(
+        &self,
+        price: u64,
+        exec_size: i128,
+        requested_size: i128,
+    ) -> Result<()> {
+        cpi::validate_execution(&TradeExecution { price, size: exec_size }, requested_size)?;
+        
+        // Check against market params
+        if saturating_abs_i128(exec_size) as u128 > self.market_params.max_position_size {
+            return Err(RiskError::Undercollateralized);
+        }
+        
+        Ok(())
+    }
+    
+    /// Cap how far in the LP's favour the agent may price a fill:
+    /// at most spread plus impact away from the oracle
+    fn validate_impact_price(&self, price: u64, exec_size: i128, oracle_price: u64) -> Result<()> {
+        if exec_size == 0 {
+            return Ok(());
+        }
+        let notional = math::notional(exec_size.unsigned_abs(), oracle_price, Rounding::Down);
+        let impact = self
+            .impact_model
+            .impact_bps(notional, self.lp_equity(oracle_price));
+        let limit = offset_price(
+            oracle_price,
+            exec_size,
+            self.market_params.spread_bps.saturating_add(impact),
+        );
+        let within = if exec_size > 0 { price <= limit } else { price >= limit };
+        if !within {
+            return Err(RiskError::InvalidMatchingEngine);
+        }
+        Ok(())
+    }
+    
+    /// Reject growth of a user's (parent plus sub-accounts) gross position
+    /// beyond `max_oi_share_bps` of resulting open interest
+    fn validate_concentration(&self, user_idx: u16, exec_size: i128) -> Result<()> {
+        let cap = self.market_params.max_oi_share_bps;
+        if cap >= 10_000 || exec_size == 0 {
+            return Ok(());
+        }
+        let pos = self.engine.accounts[user_idx as usize].position_size.get();
+        let old_abs = saturating_abs_i128(pos) as u128;
+        let new_abs = saturating_abs_i128(pos.saturating_add(exec_size)) as u128;
+        if new_abs <= old_abs {
+            return Ok(());
+        }
+        
+        // Open interest after the trade (the LP takes the other side)
+        let lp_pos = self.engine.accounts[AGENT_LP_IDX as usize].position_size.get();
+        let lp_old = saturating_abs_i128(lp_pos) as u128;
+        let lp_new = saturating_abs_i128(lp_pos.saturating_sub(exec_size)) as u128;
+        let open_interest = self
+            .engine
+            .total_open_interest
+            .get()
+            .saturating_sub(old_abs + lp_old)
+            .saturating_add(new_abs + lp_new);
+        if open_interest < self.concentration_bounds.min_open_interest {
+            return Ok(());
+        }
+        
+        let owner = live_link(&self.engine, &self.sub_links, user_idx)
+            .map(|link| link.parent)
+            .unwrap_or(user_idx);
+        let gross = self
+            .group_gross_position(owner)
+            .saturating_sub(old_abs)
+            .saturating_add(new_abs);
+        if gross.saturating_mul(10_000) > open_interest.saturating_mul(cap as u128) {
+            return Err(RiskError::Undercollateralized);
+        }
+        Ok(())
+    }
+    
+    /// Gross position of a user (parent plus live sub-accounts)
+    fn group_gross_position(&self, parent_idx: u16) -> u128 {
+        let mut gross =
+            saturating_abs_i128(self.engine.accounts[parent_idx as usize].position_size.get())
+                as u128;
+        metering::scan(MAX_ACCOUNTS as u64);
+        for idx in 0..MAX_ACCOUNTS as u16 {
+            if let Some(link) = live_link(&self.engine, &self.sub_links, idx) {
+                if link.parent == parent_idx {
+                    let pos = self.engine.accounts[idx as usize].position_size.get();
+                    gross = gross.saturating_add(saturating_abs_i128(pos) as u128);
+                }
+            }
+        }
+        gross
+    }
+    
+    /// Largest user's (parent plus sub-accounts) share of open interest
+    ///
+    /// Scans the slab; `build_context` reports the value the last crank
+    /// computed.
+    pub fn concentration(&self) -> ConcentrationStatus {
+        let open_interest = self.engine.total_open_interest.get();
+        let mut status = ConcentrationStatus::default();
+        if open_interest == 0 {
+            return status;
+        }
+        let mut gross = [0u128; MAX_ACCOUNTS];
+        metering::scan(MAX_ACCOUNTS as u64);
+        for idx in 0..MAX_ACCOUNTS as u16 {
+            let account = &self.engine.accounts[idx as usize];
+            if !self.engine.is_used(idx as usize) || account.is_lp() {
+                continue;
+            }
+            let owner = live_link(&self.engine, &self.sub_links, idx)
+                .map(|link| link.parent)
+                .unwrap_or(idx);
+            let abs = saturating_abs_i128(account.position_size.get()) as u128;
+            gross[owner as usize] = gross[owner as usize].saturating_add(abs);
+        }
+        let mut largest = 0u128;
+        for (idx, &abs) in gross.iter().enumerate() {
+            if abs > largest {
+                largest = abs;
+                status.largest_idx = Some(idx as u16);
+            }
+        }
+        status.largest_share_bps = ratio_bps(largest, open_interest, Rounding::Down);
+        status
+    }
+    
+    /// In risk-reduction-only mode, only trades that shrink the user's
+    /// position are allowed
+    fn validate_risk_reduction(&self, user_idx: u16, exec_size: i128) -> Result<()> {
+        if !self.reduce_only() || exec_size == 0 {
+            return Ok(());
+        }
+        let pos = self.engine.accounts[user_idx as usize].position_size.get();
+        let new_pos = pos.saturating_add(exec_size);
+        let flips = new_pos != 0 && (new_pos > 0) != (pos > 0);
+        if flips || saturating_abs_i128(new_pos) > saturating_abs_i128(pos) {
+            return Err(RiskError::Unauthorized);
+        }
+        Ok(())
+    }
+    
+    /// Validate that the user's resulting position stays within its tier limits
+    fn validate_tier_limits(
+        &self,
+        user_idx: u16,
+        exec_size: i128,
+        oracle_price: u64,
+        top_up: u128,
+    ) -> Result<()> {
+        if !self.engine.is_used(user_idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let tier = effective_tier(&self.engine, &self.account_tiers, user_idx);
+        let mut account = self.engine.accounts[user_idx as usize];
+        account.capital = U128::new(account.capital.get().saturating_add(top_up));
+        let old_pos = account.position_size.get();
+        let new_pos = old_pos.saturating_add(exec_size);
+
+        // Reducing exposure is never held to the caps (the engine's
+        // maintenance check still applies), so an over-levered account
+        // can always trade its way back down
+        if saturating_abs_i128(new_pos) <= saturating_abs_i128(old_pos)
+            && (new_pos == 0 || (new_pos > 0) == (old_pos > 0))
+        {
+            return Ok(());
+        }
+        self.check_tier_position(&account, tier, new_pos, oracle_price)
+    }
+    
+    /// Check a prospective position against tier position and leverage limits
+    fn check_tier_position(
+        &self,
+        account: &Account,
+        tier: AccountTier,
+        new_pos: i128,
+        oracle_price: u64,
+    ) -> Result<()> {
+        let limits = self.market_params.tier_limits[tier.index()];
+        let abs_pos = saturating_abs_i128(new_pos) as u128;
+        if abs_pos > limits.max_position_size {
+            return Err(RiskError::Undercollateralized);
+        }
+        if abs_pos == 0 {
+            return Ok(());
+        }
+        
+        // Leverage in bps where 100 = 1x (matches MarketParams::max_leverage_bps)
+        let notional = math::notional(abs_pos, oracle_price, Rounding::Down);
+        let equity = self.engine.account_equity_mtm_at_oracle(account, oracle_price);
+        let max_notional = equity.saturating_mul(limits.max_leverage_bps as u128) / 100;
+        if notional > max_notional {
+            return Err(RiskError::Undercollateralized);
+        }
+        
+        Ok(())
+    }
+    
+    /// Limits binding a trade of `size`: the protocol's max tradeable size
+    /// on its side and the user's leverage cap
+    fn trade_limits(&self, user_idx: u16, size: i128, oracle_price: u64) -> TradeLimits {
+        if !self.engine.is_used(user_idx as usize) {
+            return TradeLimits::default();
+        }
+        let tier = effective_tier(&self.engine, &self.account_tiers, user_idx);
+        TradeLimits {
+            max_size: TradeSide::of(size)
+                .map_or(0, |side| self.max_tradeable_size(user_idx, side, oracle_price)),
+            max_leverage_bps: self.market_params.tier_limits[tier.index()].max_leverage_bps,
+        }
+    }
+    
+    /// Largest trade on `side` the protocol would currently accept for
+    /// `user_idx` at `oracle_price`
+    ///
+    /// Accounts for the per-fill size limit, the tier's position and
+    /// leverage limits at current equity, risk-reduction-only mode, the
+    /// concentration cap and the LP pool utilization cap. A cross-margin
+    /// sub-account's equity includes the parent's free capital, up to the
+    /// margin its fills are topped up to. 0 when the market is closed or
+    /// the account is unknown.
+    pub fn max_tradeable_size(&self, user_idx: u16, side: TradeSide, oracle_price: u64) -> u128 {
+        let closed = self.shutdown || self.market_frozen || self.market_mode != MarketMode::AgentDriven;
+        if closed || oracle_price == 0 || !self.engine.is_used(user_idx as usize) {
+            return 0;
+        }
+        let tier = effective_tier(&self.engine, &self.account_tiers, user_idx);
+        let limits = self.market_params.tier_limits[tier.index()];
+        
+        // Largest position the tier allows at this price
+        let account = &self.engine.accounts[user_idx as usize];
+        let equity = self.engine.account_equity_mtm_at_oracle(account, oracle_price);
+        let max_notional = match self.cross_margin_free(user_idx, oracle_price) {
+            Some((_, free)) => {
+                let available = equity.saturating_add(free);
+                let target_bps = self.cross_margin_trade_bps(user_idx).max(1) as u128;
+                (available.saturating_mul(limits.max_leverage_bps as u128) / 100)
+                    .min(available.saturating_sub(1).saturating_mul(10_000) / target_bps)
+            }
+            None => equity.saturating_mul(limits.max_leverage_bps as u128) / 100,
+        };
+        let by_leverage =
+            math::saturating_mul_div(max_notional, math::PRICE_SCALE, oracle_price as u128, Rounding::Down)
+                .unwrap_or(u128::MAX);
+        let max_abs = limits.max_position_size.min(by_leverage);
+        
+        // Trading against the position may close it and open up to max_abs
+        // on the other side
+        let pos = account.position_size.get();
+        let pos_abs = saturating_abs_i128(pos) as u128;
+        let reducing = pos != 0 && (pos > 0) != (side == TradeSide::Buy);
+        let room = match (reducing, self.reduce_only()) {
+            (false, false) => max_abs.saturating_sub(pos_abs),
+            (false, true) => 0,
+            (true, false) => pos_abs.saturating_add(max_abs),
+            (true, true) => pos_abs,
+        };
+        let room = room.min(self.market_params.max_position_size).min(MAX_POSITION_ABS);
+        
+        // Concentration and utilization caps tighten as the size grows:
+        // search for the largest size that still passes both
+        let passes = |size: u128| {
+            let exec_size = side.signed(size);
+            self.validate_pool_utilization(exec_size, oracle_price, self.pool_utilization_cap_bps).is_ok()
+                && self.validate_concentration(user_idx, exec_size).is_ok()
+        };
+        if passes(room) {
+            return room;
+        }
+        let (mut lo, mut hi) = (0u128, room);
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if passes(mid) {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+    
+    /// `max_tradeable_size`, further capped by the agent's `quote_capacity`
+    pub fn max_tradeable_size_with_agent<A: OpenClawAgent>(
+        &self,
+        agent: &A,
+        user_idx: u16,
+        side: TradeSide,
+        oracle: OraclePrice,
+    ) -> Result<u128> {
+        let size = self.max_tradeable_size(user_idx, side, oracle.price);
+        if size == 0 || !agent.agent_capabilities().quote_capacity {
+            return Ok(size);
+        }
+        let context = self.build_context(oracle);
+        Ok(size.min(agent.quote_capacity(&context, user_idx, side)?))
+    }
+    
+    /// Assign a risk tier to an account (agent-initiated, protocol-validated)
+    ///
+    /// The LP tier is reserved for LP accounts, which cannot leave it.
+    /// A downgrade is rejected if the current position would breach the
+    /// new tier's limits.
+    pub fn set_account_tier(
+        &mut self,
+        idx: u16,
+        tier: AccountTier,
+        oracle_price: u64,
+    ) -> Result<()> {
+        if !self.engine.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let account = &self.engine.accounts[idx as usize];
+        if account.is_lp() != (tier == AccountTier::LP) {
+            return Err(RiskError::AccountKindMismatch);
+        }
+        self.check_tier_position(account, tier, account.position_size.get(), oracle_price)?;
+        
+        self.account_tiers[idx as usize] = TierStamp {
+            account_id: account.account_id,
+            tier,
+        };
+        self.record_change(idx);
+        Ok(())
+    }
+    
+    /// Current tier of an account
+    pub fn account_tier(&self, idx: u16) -> Result<AccountTier> {
+        if !self.engine.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        Ok(effective_tier(&self.engine, &self.account_tiers, idx))
+    }
+    
+    /// Update market parameters from agent
+    pub fn update_market_params<A: OpenClawAgent>(
+        &mut self,
+        agent: &A,
+    ) -> Result<()> {
+        let _op = metering::enter(MeteredOp::UpdateMarketParams);
+        // Oracle price not needed for params
+        if self.params_vetoed || self.params_locked {
+            return Err(RiskError::Unauthorized); // ParamsVetoed
+        }
+        
+        let context = self.build_context(OraclePrice::default());
+        let params = agent.get_market_params(&context)?;
+        
+        // Validate parameters
+        self.validate_market_params(&params)?;
+        
+        // Apply parameters (fresh agent params supersede anomaly reductions)
+        let before = self.market_params;
+        self.previous_market_params = self.market_params;
+        self.apply_market_params(params);
+        self.pool_utilization_cap_bps = pool_utilization_cap(agent, &context)?;
+        self.pre_anomaly_max_position = None;
+        self.record_agent_activity(self.engine.current_slot);
+        if params != before {
+            self.record_flip(FlipKind::Params, before);
+        }
+        
+        Ok(())
+    }
+    
+    /// Dry-run `agent` against the current state without applying anything
+    ///
+    /// Returns the market params the agent would publish, validated like
+    /// `update_market_params` would. Used to stage an agent swap so a
+    /// misbehaving replacement is rejected before it goes live.
+    pub fn dry_run_agent<A: OpenClawAgent>(&self, agent: &A) -> Result<MarketParams> {
+        let context = self.build_context(OraclePrice::default());
+        let params = agent.get_market_params(&context)?;
+        self.validate_market_params(&params)?;
+        pool_utilization_cap(agent, &context)?;
+        Ok(params)
+    }
+    
+    /// Install market params and mirror the engine-level ones
+    fn apply_market_params(&mut self, params: MarketParams) {
+        if params != self.market_params {
+            self.events.push(MarketEvent::ParamsChanged(MarketParamsEvent {
+                slot: self.engine.current_slot,
+                max_leverage_bps: params.max_leverage_bps,
+                max_position_size: params.max_position_size,
+                spread_bps: params.spread_bps,
+                funding_rate_bps_per_slot: params.funding_rate_bps_per_slot,
+                min_margin_bps: params.min_margin_bps,
+                max_oi_share_bps: params.max_oi_share_bps,
+            }));
+        }
+        self.market_params = params;
+        
+        // Update underlying engine params if needed
+        // (some params map to RiskParams, others are Clawcolator-specific)
+        if let Some(fee) = params.dust_policy.maintenance_fee_per_slot {
+            self.engine.params.maintenance_fee_per_slot = U128::new(fee);
+        }
+        if let Some(slots) = params.warmup_period_slots {
+            self.engine.params.warmup_period_slots = slots;
+        }
+    }
+    
+    /// Validate market parameters
+    fn validate_market_params(&self, params: &MarketParams) -> Result<()> {
+        // Max leverage must stay under the governed ceiling (default 100x)
+        if params.max_leverage_bps > self.max_leverage_ceiling_bps {
+            return Err(RiskError::Overflow);
+        }
+        
+        // Max position size must be within bounds
+        if params.max_position_size > MAX_POSITION_ABS {
+            return Err(RiskError::Overflow);
+        }
+        
+        // Active capital ratio must be <= 100%
+        if params.active_capital_ratio_bps > 10000 {
+            return Err(RiskError::Overflow);
+        }
+        
+        // Tier limits follow the same bounds as the global limits
+        for limits in params.tier_limits.iter() {
+            if limits.max_leverage_bps > self.max_leverage_ceiling_bps
+                || limits.max_position_size > MAX_POSITION_ABS
+            {
+                return Err(RiskError::Overflow);
+            }
+        }
+        
+        // Min margin must be >= maintenance margin (as set, not as widened
+        // by the dead-man switch)
+        let maintenance_margin_bps = self
+            .widened_margins
+            .map_or(self.engine.params.maintenance_margin_bps, |m| m.base_maintenance_bps);
+        if params.min_margin_bps < maintenance_margin_bps {
+            return Err(RiskError::Undercollateralized);
+        }
+        
+        // Dust policy must stay within protocol bounds
+        let dust = &params.dust_policy;
+        if dust.maintenance_fee_per_slot.unwrap_or(0) > self.dust_bounds.max_maintenance_fee_per_slot
+            || dust.dust_capital_threshold > self.dust_bounds.max_dust_capital_threshold
+            || dust.idle_slots < self.dust_bounds.min_idle_slots
+                && dust.dust_capital_threshold > 0
+        {
+            return Err(RiskError::Overflow);
+        }
+        
+        // Withdrawal queue policy must stay within protocol bounds
+        params.withdrawal_policy.validate(&self.withdrawal_bounds)?;
+        
+        // Fee split must sum to 100% inside protocol ranges
+        params.fee_split.validate(&self.fee_split_bounds)?;
+        
+        // Lending rate and limits within protocol bounds
+        params.lending_policy.validate(&self.lending_bounds)?;
+        
+        // Fallback curve depth within protocol bounds
+        self.vamm_bounds.validate_depth(params.vamm_depth)?;
+        
+        // Warmup period within protocol bounds
+        if let Some(slots) = params.warmup_period_slots {
+            if slots < self.warmup_bounds.min_slots || slots > self.warmup_bounds.max_slots {
+                return Err(RiskError::Overflow);
+            }
+        }
+        
+        // Concentration limit no looser than the protocol allows
+        if params.max_oi_share_bps > self.concentration_bounds.max_oi_share_bps
+            || params.max_oi_share_bps == 0
+        {
+            return Err(RiskError::Overflow);
+        }
+        
+        // In risk-reduction-only mode params may only tighten
+        if self.risk_reduction.active() && !self.params_tighter_or_equal(params) {
+            return Err(RiskError::Unauthorized);
+        }
+        
+        Ok(())
+    }
+    
+    /// Whether `params` are at least as conservative as the current ones
+    fn params_tighter_or_equal(&self, params: &MarketParams) -> bool {
+        let cur = &self.market_params;
+        let tiers_ok = params
+            .tier_limits
+            .iter()
+            .zip(cur.tier_limits.iter())
+            .all(|(new, old)| {
+                new.max_leverage_bps <= old.max_leverage_bps
+                    && new.max_position_size <= old.max_position_size
+            });
+        tiers_ok
+            && params.max_leverage_bps <= cur.max_leverage_bps
+            && params.max_position_size <= cur.max_position_size
+            && params.min_margin_bps >= cur.min_margin_bps
+            && params.spread_bps >= cur.spread_bps
+            && params.active_capital_ratio_bps <= cur.active_capital_ratio_bps
+            && params.withdrawal_policy.queue_threshold <= cur.withdrawal_policy.queue_threshold
+            && params.withdrawal_policy.delay_slots >= cur.withdrawal_policy.delay_slots
+            && params.vamm_depth <= cur.vamm_depth
+            && params.max_oi_share_bps <= cur.max_oi_share_bps
+            && params.lending_policy.max_utilization_bps <= cur.lending_policy.max_utilization_bps
+            && params.lending_policy.max_borrow_bps <= cur.lending_policy.max_borrow_bps
+            && params
+                .warmup_period_slots
+                .is_none_or(|slots| slots >= self.engine.params.warmup_period_slots)
+    }
+    
+    /// Check for anomalies and apply agent's response
+    ///
+    /// Actions are gated by the escalation policy: low-severity anomalies are
+    /// only logged, freezes need `freeze_threshold_bps`, and shutdown needs
+    /// `shutdown_threshold_bps` sustained over consecutive checks.
+    pub fn check_anomalies<A: OpenClawAgent>(
+        &mut self,
+        agent: &A,
+        oracle: OraclePrice,
+    ) -> Result<EscalationOutcome> {
+        let _op = metering::enter(MeteredOp::CheckAnomalies);
+        let context = self.build_context(oracle);
+        let response = if agent.agent_capabilities().anomaly_detection {
+            agent.detect_anomalies(&context)?
+        } else {
+            AnomalyResponse::none()
+        };
+        let policy = self.escalation;
+        let severity = response.severity_bps.min(10_000);
+        let (was_frozen, params_before) = (self.market_frozen, self.market_params);
+        let mut level = EscalationLevel::Logged;
+        
+        let wants_shutdown =
+            response.actions.initiate_shutdown && severity >= policy.shutdown_threshold_bps;
+        self.shutdown_streak = if wants_shutdown {
+            self.shutdown_streak.saturating_add(1)
+        } else {
+            0
+        };
+        
+        if severity >= policy.action_threshold_bps {
+            if let Some(new_max_size) = response.actions.reduce_limits {
+                if new_max_size <= MAX_POSITION_ABS {
+                    if self.pre_anomaly_max_position.is_none() {
+                        self.pre_anomaly_max_position = Some(self.market_params.max_position_size);
+                    }
+                    self.market_params.max_position_size = new_max_size;
+                    level = EscalationLevel::Restricted;
+                }
+            }
+            
+            if (response.actions.freeze_market || response.actions.stop_trading)
+                && severity >= policy.freeze_threshold_bps
+            {
+                self.market_frozen = true;
+                if self.freeze_origin.is_none() {
+                    self.freeze_origin = Some(FreezeOrigin::Anomaly);
+                }
+                level = EscalationLevel::Frozen;
+            }
+            
+            if wants_shutdown {
+                if self.shutdown_streak >= policy.shutdown_consecutive_checks {
+                    self.shutdown = true;
+                    level = EscalationLevel::Shutdown;
+                } else {
+                    level = level.max(EscalationLevel::ShutdownPending);
+                }
+            }
+        }
+        
+        // Automatic de-escalation after a sustained calm period
+        self.calm_streak = if severity < policy.deescalation_threshold_bps {
+            self.calm_streak.saturating_add(1)
+        } else {
+            0
+        };
+        let mut deescalated = false;
+        if self.calm_streak >= policy.deescalation_consecutive_checks && !self.params_locked {
+            if let Some(max_size) = self.pre_anomaly_max_position.take() {
+                self.market_params.max_position_size = max_size;
+                deescalated = true;
+            }
+            if self.freeze_origin == Some(FreezeOrigin::Anomaly) {
+                self.market_frozen = false;
+                self.freeze_origin = None;
+                deescalated = true;
+            }
+        }
+        
+        self.anomaly_history.push(AnomalyRecord {
+            slot: self.engine.current_slot,
+            anomaly_type: response.anomaly_type,
+            severity_bps: severity,
+            level,
+        });
+        let scorecard = &mut self.epoch.scorecard;
+        if severity > 0 {
+            scorecard.anomalies_flagged = scorecard.anomalies_flagged.saturating_add(1);
+        }
+        scorecard.max_escalation = scorecard.max_escalation.max(level);
+        if self.market_frozen != was_frozen {
+            self.record_flip(FlipKind::Freeze, params_before);
+        }
+        
+        Ok(EscalationOutcome {
+            anomaly_type: response.anomaly_type,
+            severity_bps: severity,
+            level,
+            shutdown_streak: self.shutdown_streak,
+            deescalated,
+        })
+    }
+    
+    /// Run the keeper crank with a protocol-level oracle cross-check
+    ///
+    /// If a secondary oracle is supplied and diverges from the primary by
+    /// more than the configured bound, the protocol enters risk-reduction
+    /// mode and flags `OracleManipulation` in the agent context. Agreement
+    /// on a later crank lifts the divergence-triggered mode.
+    pub fn crank(
+        &mut self,
+        caller_idx: u16,
+        now_slot: u64,
+        oracle_price: u64,
+        secondary_oracle_price: Option<u64>,
+    ) -> Result<CrankOutcome> {
+        let _op = metering::enter(MeteredOp::Crank);
+        let span = trace::OpSpan::crank(caller_idx, now_slot);
+        let result = self.run_crank(caller_idx, now_slot, oracle_price, secondary_oracle_price);
+        span.finish_crank(&result);
+        result
+    }
+    
+    fn run_crank(
+        &mut self,
+        caller_idx: u16,
+        now_slot: u64,
+        oracle_price: u64,
+        secondary_oracle_price: Option<u64>,
+    ) -> Result<CrankOutcome> {
+        self.oracle_divergence_bps = match secondary_oracle_price {
+            Some(secondary) => oracle_divergence_bps(oracle_price, secondary),
+            None => 0,
+        };
+        self.risk_reduction.oracle_divergence =
+            self.oracle_divergence_bps > self.oracle_bounds.max_divergence_bps;
+        
+        self.ramp_wind_down_margins(now_slot);
+        self.check_dead_man(now_slot);
+        let funding_index = self.engine.funding_index_qpb_e6.get();
+        let lp_position = self.engine.accounts[AGENT_LP_IDX as usize].position_size.get();
+        let (long_oi, short_oi) = (
+            self.engine.long_open_interest.get(),
+            self.engine.short_open_interest.get(),
+        );
+        let mut changed = [0u64; MAX_ACCOUNTS.div_ceil(64)];
+        let mut outcome = self.engine.keeper_crank_with(
+            caller_idx,
+            now_slot,
+            oracle_price,
+            self.market_params.funding_rate_bps_per_slot,
+            false,
+            |idx| changed[idx as usize >> 6] |= 1u64 << (idx & 63),
+        )?;
+        // Only the accounts the sweep settled or closed moved
+        self.settle_crank_changes(&changed, oracle_price);
+        
+        // Funding accrued this crank; the LP receives what its position pays
+        let funding_delta = self.engine.funding_index_qpb_e6.get().saturating_sub(funding_index);
+        self.epoch.funding_index_delta = self.epoch.funding_index_delta.saturating_add(funding_delta);
+        self.epoch.lp_funding_received = self
+            .epoch
+            .lp_funding_received
+            .saturating_sub(lp_position.saturating_mul(funding_delta) / 1_000_000);
+        let flows = FundingFlows::accrued(long_oi, short_oi, funding_delta);
+        self.epoch.funding_paid = self.epoch.funding_paid.saturating_add(flows.paid);
+        self.epoch.funding_received = self.epoch.funding_received.saturating_add(flows.received);
+        self.risk_reduction.engine_stress = outcome.num_liq_errors > 0
+            || (outcome.force_realize_needed
+                && !self.engine.params.risk_reduction_threshold.is_zero());
+        let stats = &mut self.epoch.liquidations;
+        stats.liquidations = stats.liquidations.saturating_add(outcome.num_liquidations);
+        stats.liquidation_errors =
+            stats.liquidation_errors.saturating_add(outcome.num_liq_errors as u32);
+        
+        // GC and force-realize touch accounts we cannot enumerate cheaply
+        if outcome.num_gc_closed > 0 || outcome.force_realize_closed > 0 {
+            self.delta.resync_required = true;
+        }
+        self.refresh_liquidation_index(oracle_price);
+        let indexed = self.liquidate_candidates(now_slot, oracle_price);
+        outcome.num_liquidations = outcome.num_liquidations.saturating_add(indexed);
+        self.run_liquidation_auctions(now_slot, oracle_price);
+        self.sweep_dust(now_slot, oracle_price);
+        self.epoch.liquidations.dust_closed = self
+            .epoch
+            .liquidations
+            .dust_closed
+            .saturating_add(self.last_dust_sweep.closed_len as u32);
+        if self.settle_wind_down(now_slot, oracle_price) {
+            self.delta.resync_required = true;
+        }
+        self.settle_loans(now_slot);
+        self.process_withdrawals(now_slot, oracle_price);
+        self.collect_bankruptcies();
+        self.concentration = self.concentration();
+        if outcome.num_gc_closed > 0 || self.last_dust_sweep.closed_len > 0 {
+            self.keeper_ledger.forfeit_closed(&self.engine);
+            self.referrals.forfeit_closed(&self.engine);
+        }
+        self.keeper_ledger.accrue_crank(
+            &self.engine,
+            &self.keeper_params,
+            caller_idx,
+            outcome.advanced,
+            outcome.num_liquidations,
+        );
+        self.record_change(caller_idx);
+        self.mark_active(caller_idx);
+        self.roll_epoch_if_due(now_slot, oracle_price);
+        
+        // Mode switches land only on a crank that settled every account
+        if outcome.advanced {
+            if let Some(mode) = self.pending_market_mode.take() {
+                self.market_mode = mode;
+            }
+            self.record_price(now_slot, oracle_price);
+        }
+        
+        Ok(outcome)
+    }
+    
+    /// Record and refile the occupied accounts a crank reported changed
+    fn settle_crank_changes(&mut self, changed: &[u64], oracle_price: u64) {
+        for (word, &mask) in changed.iter().enumerate() {
+            let mut bits = mask;
+            while bits != 0 {
+                let idx = (word * 64 + bits.trailing_zeros() as usize) as u16;
+                bits &= bits - 1;
+                if self.engine.is_used(idx as usize) {
+                    self.delta.record(idx);
+                    self.reindex_liquidation(idx, oracle_price);
+                }
+            }
+        }
+    }
+    
+    /// Append a crank price and refresh the VaR estimate
+    fn record_price(&mut self, now_slot: u64, oracle_price: u64) {
+        self.price_history.push(PriceSample {
+            slot: now_slot,
+            price: oracle_price,
+        });
+        self.value_at_risk = var::estimate(
+            &self.price_history,
+            DEFAULT_VAR_CONFIDENCE_BPS,
+            oracle_price,
+            self.engine.accounts[AGENT_LP_IDX as usize].position_size.get(),
+            self.engine.total_open_interest.get(),
+        );
+    }
+    
+    /// Oracle prices recorded by advancing cranks
+    pub fn price_history(&self) -> &PriceHistory {
+        &self.price_history
+    }
+    
+    /// VaR/ES as of the last advancing crank
+    pub fn value_at_risk(&self) -> ValueAtRisk {
+        self.value_at_risk
+    }
+    
+    /// Finalize the epoch in progress once `epoch_slots` have elapsed
+    fn roll_epoch_if_due(&mut self, now_slot: u64, oracle_price: u64) {
+        if self.epoch_slots == 0 || now_slot < self.epoch.start_slot.saturating_add(self.epoch_slots) {
+            return;
+        }
+        let mut report = self.epoch;
+        report.end_slot = now_slot;
+        report.fees = self.roll_fee_epoch();
+        report.scorecard.lp_equity_end = self.lp_equity(oracle_price);
+        self.epoch_history.push(report);
+        
+        self.epoch = EpochReport {
+            epoch: report.epoch.saturating_add(1),
+            start_slot: now_slot,
+            ..EpochReport::default()
+        };
+        self.epoch.scorecard.lp_equity_start = report.scorecard.lp_equity_end;
+    }
+    
+    /// Set the epoch length (0 disables rollover)
+    pub fn set_epoch_slots(&mut self, epoch_slots: u64) {
+        self.epoch_slots = epoch_slots;
+    }
+    
+    /// Epoch in progress (fees so far are in `epoch_fee_totals`)
+    pub fn current_epoch(&self) -> &EpochReport {
+        &self.epoch
+    }
+    
+    /// Finalized epochs
+    pub fn epoch_history(&self) -> &EpochHistory {
+        &self.epoch_history
+    }
+    
+    /// Replace the protocol bounds on the agent's dust policy
+    ///
+    /// Refused once governance is enabled; use a proposal instead.
+    pub fn set_dust_bounds(&mut self, bounds: DustBounds) -> Result<()> {
+        self.require_ungoverned()?;
+        self.dust_bounds = bounds;
+        Ok(())
+    }
+    
+    /// Current protocol bounds on the agent's dust policy
+    pub fn dust_bounds(&self) -> &DustBounds {
+        &self.dust_bounds
+    }
+    
+    /// Result of the last dust sweep
+    pub fn last_dust_sweep(&self) -> &DustSweep {
+        &self.last_dust_sweep
+    }
+    
+    /// Last executed trade (None before the first fill)
+    pub fn last_fill(&self) -> Option<&TradeFill> {
+        self.last_fill.as_ref()
+    }
+    
+    /// Meta the agent attached to its last trade decision
+    ///
+    /// Cleared at the start of every agent-driven trade, so it is None when
+    /// the trade failed before reaching the agent or the agent gave none.
+    pub fn last_decision_meta(&self) -> Option<&DecisionMeta> {
+        self.last_decision_meta.as_ref()
+    }
+    
+    /// Outcome of the last agent-driven trade: the fill, or why it was
+    /// rejected with the limits a retry must respect
+    pub fn last_trade_outcome(&self) -> Option<&TradeOutcome> {
+        self.last_trade_outcome.as_ref()
+    }
+    
+    /// Last sequence number account `idx` used (0 if none); the next
+    /// sequenced trade or withdrawal must carry a higher one
+    pub fn account_sequence(&self, idx: u16) -> u64 {
+        match (self.sequences.get(idx as usize), self.engine.accounts.get(idx as usize)) {
+            (Some(stamp), Some(account)) if stamp.account_id == account.account_id => stamp.last,
+            _ => 0,
+        }
+    }
+    
+    /// Accept `sequence` from account `idx` if it is above the last one
+    fn consume_sequence(&mut self, idx: u16, sequence: u64) -> Result<()> {
+        if idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        if sequence <= self.account_sequence(idx) {
+            return Err(RiskError::Unauthorized); // Replayed or out of order
+        }
+        self.sequences[idx as usize] = SequenceStamp {
+            account_id: self.engine.accounts[idx as usize].account_id,
+            last: sequence,
+        };
+        Ok(())
+    }
+    
+    /// Recent client order IDs and their outcomes
+    pub fn client_orders(&self) -> &ClientOrderLog {
+        &self.client_orders
+    }
+    
+    /// Remember client order IDs for `slots` slots
+    pub fn set_client_order_window(&mut self, slots: u64) {
+        self.client_orders.set_window_slots(slots);
+    }
+    
+    /// Withdraw `amount` of capital, queueing it if above the agent's threshold
+    pub fn request_withdrawal(
+        &mut self,
+        idx: u16,
+        amount: u128,
+        now_slot: u64,
+        oracle_price: u64,
+    ) -> Result<WithdrawalStatus> {
+        let _op = metering::enter(MeteredOp::Withdrawal);
+        if idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let policy = self.market_params.withdrawal_policy;
+        let account = &self.engine.accounts[idx as usize];
+        let debt = self.lending.debt(idx, account.account_id);
+        // Borrowed capital and queued amounts stay in until repaid or released
+        let reserved = self
+            .withdrawal_queue
+            .pending_for(account.account_id)
+            .saturating_add(debt);
+        let overdrawn = reserved.saturating_add(amount) > account.capital.get();
+        if reserved > 0 && overdrawn {
+            return Err(RiskError::InsufficientBalance);
+        }
+        if amount <= policy.queue_threshold {
+            if amount > self.withdrawal_allowance(now_slot) {
+                return Err(RiskError::Unauthorized); // WithdrawalRateLimited
+            }
+            self.engine.withdraw(idx, amount, now_slot, oracle_price)?;
+            self.record_withdrawal(amount);
+            self.record_change(idx);
+            self.mark_active(idx);
+            return Ok(WithdrawalStatus::Executed);
+        }
+        
+        if overdrawn {
+            return Err(RiskError::InsufficientBalance);
+        }
+        let account = &self.engine.accounts[idx as usize];
+        let release_slot = now_slot.saturating_add(policy.delay_slots);
+        self.withdrawal_queue.push(QueuedWithdrawal {
+            account_idx: idx,
+            account_id: account.account_id,
+            amount,
+            requested_slot: now_slot,
+            release_slot,
+        })?;
+        self.mark_active(idx);
+        Ok(WithdrawalStatus::Queued { release_slot })
+    }
+    
+    /// `request_withdrawal` guarded by the account's sequence number
+    ///
+    /// Trades and withdrawals share one sequence per account; a rejected
+    /// sequence leaves nothing queued or withdrawn.
+    pub fn request_withdrawal_sequenced(
+        &mut self,
+        idx: u16,
+        amount: u128,
+        sequence: u64,
+        now_slot: u64,
+        oracle_price: u64,
+    ) -> Result<WithdrawalStatus> {
+        self.consume_sequence(idx, sequence)?;
+        self.request_withdrawal(idx, amount, now_slot, oracle_price)
+    }
+    
+    /// Cancel all queued withdrawals of `idx`; returns the amount cancelled
+    pub fn cancel_withdrawals(&mut self, idx: u16) -> Result<u128> {
+        if idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        Ok(self.withdrawal_queue.cancel(self.engine.accounts[idx as usize].account_id))
+    }
+    
+    /// Queued withdrawals
+    pub fn withdrawal_queue(&self) -> &WithdrawalQueue {
+        &self.withdrawal_queue
+    }
+    
+    /// Replace the protocol bounds on the agent's withdrawal policy
+    ///
+    /// Refused once governance is enabled; use a proposal instead.
+    pub fn set_withdrawal_bounds(&mut self, bounds: WithdrawalBounds) -> Result<()> {
+        self.require_ungoverned()?;
+        self.withdrawal_bounds = bounds;
+        Ok(())
+    }
+    
+    /// Replace the protocol bounds on the agent's lending policy
+    ///
+    /// Refused once governance is enabled; use a proposal instead.
+    pub fn set_lending_bounds(&mut self, bounds: LendingBounds) -> Result<()> {
+        self.require_ungoverned()?;
+        self.lending_bounds = bounds;
+        Ok(())
+    }
+    
+    /// Protocol bounds on the agent's lending policy
+    pub fn lending_bounds(&self) -> &LendingBounds {
+        &self.lending_bounds
+    }
+    
+    /// Margin loan ledger
+    pub fn lending(&self) -> &LendingBook {
+        &self.lending
+    }
+    
+    /// Debt of `idx` (principal plus interest accrued up to the last accrual)
+    pub fn loan_debt(&self, idx: u16) -> u128 {
+        match self.engine.accounts.get(idx as usize) {
+            Some(account) if self.engine.is_used(idx as usize) => {
+                self.lending.debt(idx, account.account_id)
+            }
+            _ => 0,
+        }
+    }
+    
+    /// Share of the LP reserve lent out (bps)
+    pub fn lending_utilization_bps(&self) -> u64 {
+        let lp_capital = match self.engine.is_used(AGENT_LP_IDX as usize) {
+            true => self.engine.accounts[AGENT_LP_IDX as usize].capital.get(),
+            false => 0,
+        };
+        self.lending.utilization_bps(lp_capital)
+    }
+    
+    /// Borrow `amount` of margin from the LP reserve into `user_idx`'s capital
+    ///
+    /// Bounded by the agent's lending policy (reserve utilization and debt
+    /// against the borrower's own capital) and by the LP account's own
+    /// margin, as for `pool_withdraw`.
+    pub fn borrow(
+        &mut self,
+        user_idx: u16,
+        amount: u128,
+        now_slot: u64,
+        oracle_price: u64,
+    ) -> Result<()> {
+        let policy = self.market_params.lending_policy;
+        if !policy.enabled() || self.shutdown || self.market_frozen {
+            return Err(RiskError::Unauthorized);
+        }
+        if amount == 0 {
+            return Err(RiskError::Overflow);
+        }
+        let idx = user_idx as usize;
+        if idx >= MAX_ACCOUNTS
+            || !self.engine.is_used(idx)
+            || !self.engine.is_used(AGENT_LP_IDX as usize)
+        {
+            return Err(RiskError::AccountNotFound);
+        }
+        if !self.engine.accounts[idx].is_user() {
+            return Err(RiskError::AccountKindMismatch);
+        }
+        self.lending.accrue(now_slot, policy.interest_bps_per_slot);
+        
+        // Debt against the borrower's own capital
+        let account = &self.engine.accounts[idx];
+        let (account_id, capital) = (account.account_id, account.capital.get());
+        let debt = self.lending.debt(user_idx, account_id);
+        let own = capital.saturating_sub(debt);
+        if debt.saturating_add(amount) > apply_bps(own, policy.max_borrow_bps, Rounding::Down) {
+            return Err(RiskError::Undercollateralized);
+        }
+        
+        // Reserve utilization and the LP's own margin
+        let lp_capital = self.engine.accounts[AGENT_LP_IDX as usize].capital.get();
+        let lent = self.lending.totals().principal.saturating_add(amount);
+        let reserve = lp_capital.saturating_add(self.lending.totals().principal);
+        if amount > lp_capital || lent > apply_bps(reserve, policy.max_utilization_bps, Rounding::Down) {
+            return Err(RiskError::InsufficientBalance);
+        }
+        let lp_position = self.engine.accounts[AGENT_LP_IDX as usize].position_size.get();
+        let remaining = self.lp_equity(oracle_price).saturating_sub(amount);
+        if lp_position != 0
+            && self.pool_utilization_bps(lp_position, remaining, oracle_price)
+                > self.pool_utilization_cap_bps
+        {
+            return Err(RiskError::Undercollateralized);
+        }
+        
+        self.lending.lend(user_idx, account_id, amount)?;
+        self.engine.set_capital(AGENT_LP_IDX as usize, lp_capital - amount);
+        self.engine.set_capital(idx, capital.checked_add(amount).ok_or(RiskError::Overflow)?);
+        self.record_change(AGENT_LP_IDX);
+        self.record_change(user_idx);
+        self.mark_active(user_idx);
+        Ok(())
+    }
+    
+    /// Repay up to `amount` of `user_idx`'s debt from its capital, interest
+    /// first; returns the amount repaid
+    ///
+    /// Refused if the account would drop below initial margin.
+    pub fn repay_loan(
+        &mut self,
+        user_idx: u16,
+        amount: u128,
+        now_slot: u64,
+        oracle_price: u64,
+    ) -> Result<u128> {
+        let idx = user_idx as usize;
+        if idx >= MAX_ACCOUNTS
+            || !self.engine.is_used(idx)
+            || !self.engine.is_used(AGENT_LP_IDX as usize)
+        {
+            return Err(RiskError::AccountNotFound);
+        }
+        self.lending.accrue(now_slot, self.market_params.lending_policy.interest_bps_per_slot);
+        let account = &self.engine.accounts[idx];
+        let capital = account.capital.get();
+        let pay = amount.min(self.lending.debt(user_idx, account.account_id)).min(capital);
+        if pay == 0 {
+            return Ok(0);
+        }
+        let position = account.position_size.get();
+        if position != 0 {
+            let equity = self.engine.account_equity_mtm_at_oracle(account, oracle_price);
+            let required = margin_required(position, oracle_price, self.engine.params.initial_margin_bps);
+            if equity.saturating_sub(pay) < required {
+                return Err(RiskError::Undercollateralized);
+            }
+        }
+        
+        self.lending.repay(user_idx, pay);
+        self.engine.set_capital(idx, capital - pay);
+        let lp_capital = self.engine.accounts[AGENT_LP_IDX as usize].capital.get();
+        self.engine.set_capital(AGENT_LP_IDX as usize, lp_capital.saturating_add(pay));
+        self.record_change(AGENT_LP_IDX);
+        self.record_change(user_idx);
+        self.mark_active(user_idx);
+        Ok(pay)
+    }
+    
+    /// Accrue interest and settle defaulted loans (crank step)
+    ///
+    /// A loan defaults when its account is gone, or is flat with capital
+    /// below the debt. The borrower's capital repays what it can, insurance
+    /// above the floor (net of pending keeper claims) covers the rest, and
+    /// the remainder is written off.
+    fn settle_loans(&mut self, now_slot: u64) {
+        self.lending.accrue(now_slot, self.market_params.lending_policy.interest_bps_per_slot);
+        if self.lending.totals().principal == 0 && self.lending.totals().interest == 0 {
+            return;
+        }
+        if !self.engine.is_used(AGENT_LP_IDX as usize) {
+            return;
+        }
+        metering::scan(MAX_ACCOUNTS as u64);
+        for idx in 0..MAX_ACCOUNTS {
+            let Some(loan) = self.lending.loan(idx as u16).copied() else {
+                continue;
+            };
+            let debt = loan.debt();
+            let account = &self.engine.accounts[idx];
+            let live = self.engine.is_used(idx) && account.account_id == loan.account_id;
+            if live && (account.position_size.get() != 0 || account.capital.get() >= debt) {
+                continue;
+            }
+            
+            let repaid = if live { account.capital.get().min(debt) } else { 0 };
+            let insurance = self.engine.insurance_fund.balance.get();
+            let available = insurance
+                .saturating_sub(self.lending_bounds.insurance_floor)
+                .saturating_sub(self.insurance_claims());
+            let covered = (debt - repaid).min(available);
+            if repaid > 0 {
+                let capital = account.capital.get();
+                self.engine.set_capital(idx, capital - repaid);
+                self.record_change(idx as u16);
+            }
+            self.engine.insurance_fund.balance = U128::new(insurance - covered);
+            let lp_capital = self.engine.accounts[AGENT_LP_IDX as usize].capital.get();
+            self.engine
+                .set_capital(AGENT_LP_IDX as usize, lp_capital.saturating_add(repaid + covered));
+            self.record_change(AGENT_LP_IDX);
+            self.lending.default_loan(idx as u16, repaid, covered);
+            if debt == repaid {
+                continue;
+            }
+            self.record_bankruptcy(Bankruptcy {
+                slot: now_slot,
+                idx: idx as u16,
+                account_id: loan.account_id,
+                cause: BankruptcyCause::LoanDefault,
+                hole: debt - repaid,
+                insurance: covered,
+                socialized: debt - repaid - covered,
+            });
+        }
+    }
+    
+    /// Whether queued withdrawals are held (frozen market or settlement)
+    pub fn withdrawals_paused(&self) -> bool {
+        self.market_frozen || self.shutdown
+    }
+    
+    /// Pool utilization: open interest notional over total capital (bps)
+    fn utilization_bps(&self, oracle_price: u64) -> u64 {
+        let notional =
+            math::notional(self.engine.total_open_interest.get(), oracle_price, Rounding::Down);
+        ratio_bps(notional, self.engine.c_tot.get(), Rounding::Down)
+    }
+    
+    /// Release matured (or expedited) queued withdrawals
+    ///
+    /// Payouts are scaled by vault coverage of capital when the vault no
+    /// longer covers capital plus insurance; the remainder stays queued.
+    /// Entries that fail (account gone, margin) are dropped.
+    fn process_withdrawals(&mut self, now_slot: u64, oracle_price: u64) {
+        if self.withdrawal_queue.is_empty() || self.withdrawals_paused() {
+            return;
+        }
+        let expedite_bps = self.market_params.withdrawal_policy.expedite_utilization_bps;
+        let expedite = expedite_bps > 0 && self.utilization_bps(oracle_price) <= expedite_bps;
+        
+        let mut pos = 0;
+        while let Some(entry) = self.withdrawal_queue.get_mut(pos).copied() {
+            metering::iterate(1);
+            if entry.release_slot > now_slot && !expedite {
+                pos += 1;
+                continue;
+            }
+            let idx = entry.account_idx;
+            if !self.engine.is_used(idx as usize)
+                || self.engine.accounts[idx as usize].account_id != entry.account_id
+            {
+                self.withdrawal_queue.remove(pos);
+                continue;
+            }
+            
+            let covered = self
+                .engine
+                .vault
+                .get()
+                .saturating_sub(self.engine.insurance_fund.balance.get());
+            let c_tot = self.engine.c_tot.get();
+            let payout = if covered >= c_tot {
+                entry.amount
+            } else {
+                entry.amount.saturating_mul(covered) / c_tot
+            };
+            let payout = payout.min(self.withdrawal_allowance(now_slot));
+            let account = &self.engine.accounts[idx as usize];
+            let debt = self.lending.debt(idx, entry.account_id);
+            let payout = payout.min(account.capital.get().saturating_sub(debt));
+            
+            if payout == 0 {
+                pos += 1;
+                continue;
+            }
+            if self.engine.withdraw(idx, payout, now_slot, oracle_price).is_err() {
+                self.withdrawal_queue.remove(pos);
+                continue;
+            }
+            self.record_withdrawal(payout);
+            self.record_change(idx);
+            if payout < entry.amount {
+                if let Some(e) = self.withdrawal_queue.get_mut(pos) {
+                    e.amount -= payout;
+                }
+                pos += 1;
+            } else {
+                self.withdrawal_queue.remove(pos);
+            }
+        }
+    }
+    
+    /// Move the LP and treasury shares of a trading fee paid by `payer_idx`
+    /// out of insurance, accrue its referrer's share and, for a maker-like
+    /// fill, the LP's maker rebate
+    ///
+    /// Without a live treasury account its share stays in insurance.
+    fn distribute_fee(&mut self, fee: u128, payer_idx: u16, maker_like: bool) {
+        let mut split = self.market_params.fee_split.apply(fee);
+        if let Some(referrer_idx) = self.live_referrer(payer_idx) {
+            let share = self.referral_policy.map_or(0, |policy| policy.share_bps);
+            split.referral = apply_bps(fee, share, Rounding::Down).min(split.insurance);
+            split.insurance -= split.referral;
+            self.referrals.accrue(referrer_idx, split.referral);
+        }
+        if let (true, Some(params)) = (maker_like, self.maker_rebate) {
+            split.maker_rebate = apply_bps(fee, params.rebate_bps, Rounding::Down).min(split.insurance);
+            split.insurance -= split.maker_rebate;
+        }
+        let treasury_idx = match self.treasury {
+            Some((idx, account_id))
+                if self.engine.is_used(idx as usize)
+                    && self.engine.accounts[idx as usize].account_id == account_id =>
+            {
+                Some(idx)
+            }
+            _ => None,
+        };
+        if treasury_idx.is_none() {
+            split.insurance = split.insurance.saturating_add(split.treasury);
+            split.treasury = 0;
+        }
+        
+        let lp_amount = split.lp.saturating_add(split.maker_rebate);
+        for (idx, amount) in [(Some(AGENT_LP_IDX), lp_amount), (treasury_idx, split.treasury)] {
+            let Some(idx) = idx else { continue };
+            if amount == 0 || !self.engine.is_used(idx as usize) {
+                continue;
+            }
+            let insurance = self.engine.insurance_fund.balance.get();
+            let amount = amount.min(insurance);
+            self.engine.insurance_fund.balance = U128::new(insurance - amount);
+            let capital = self.engine.accounts[idx as usize].capital.get();
+            self.engine.set_capital(idx as usize, capital.saturating_add(amount));
+            self.record_change(idx);
+        }
+        
+        self.fee_totals.add(&split);
+        self.epoch_fee_totals.add(&split);
+    }
+    
+    /// Rebate a slice of the taker fee to the LP on fills inside the
+    /// oracle (None turns rebates off)
+    pub fn set_maker_rebate(&mut self, params: Option<MakerRebateParams>) -> Result<()> {
+        self.require_ungoverned()?;
+        if let Some(params) = &params {
+            params.validate()?;
+        }
+        self.maker_rebate = params;
+        Ok(())
+    }
+    
+    /// Maker rebate schedule, if rebates are on
+    pub fn maker_rebate(&self) -> Option<&MakerRebateParams> {
+        self.maker_rebate.as_ref()
+    }
+    
+    /// Designate the treasury account (admin)
+    pub fn set_treasury_account(&mut self, idx: u16) -> Result<()> {
+        if idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        self.treasury = Some((idx, self.engine.accounts[idx as usize].account_id));
+        Ok(())
+    }
+    
+    /// Replace the protocol ranges for the agent's fee split
+    ///
+    /// Refused once governance is enabled; use a proposal instead.
+    pub fn set_fee_split_bounds(&mut self, bounds: FeeSplitBounds) -> Result<()> {
+        self.require_ungoverned()?;
+        self.fee_split_bounds = bounds;
+        Ok(())
+    }
+    
+    /// Replace the protocol bounds on the fallback vAMM
+    ///
+    /// Refused once governance is enabled; use a proposal instead.
+    pub fn set_vamm_bounds(&mut self, bounds: VammBounds) -> Result<()> {
+        self.require_ungoverned()?;
+        self.vamm_bounds = bounds;
+        Ok(())
+    }
+    
+    /// Current protocol bounds on the fallback vAMM
+    pub fn vamm_bounds(&self) -> &VammBounds {
+        &self.vamm_bounds
+    }
+    
+    /// Replace the price-impact model
+    ///
+    /// Refused once governance is enabled; use a proposal instead.
+    pub fn set_impact_model(&mut self, model: ImpactModel) -> Result<()> {
+        self.require_ungoverned()?;
+        model.validate()?;
+        self.impact_model = model;
+        Ok(())
+    }
+    
+    /// Current price-impact model
+    pub fn impact_model(&self) -> &ImpactModel {
+        &self.impact_model
+    }
+    
+    /// Replace the protocol bounds on the agent's concentration limit
+    ///
+    /// Refused once governance is enabled; use a proposal instead.
+    pub fn set_concentration_bounds(&mut self, bounds: ConcentrationBounds) -> Result<()> {
+        self.require_ungoverned()?;
+        self.concentration_bounds = bounds;
+        Ok(())
+    }
+    
+    /// Replace the protocol bounds on the agent's warmup period
+    ///
+    /// Refused once governance is enabled; use a proposal instead.
+    pub fn set_warmup_bounds(&mut self, bounds: WarmupBounds) -> Result<()> {
+        self.require_ungoverned()?;
+        self.install_warmup_bounds(bounds)
+    }
+    
+    fn install_warmup_bounds(&mut self, bounds: WarmupBounds) -> Result<()> {
+        if bounds.min_slots > bounds.max_slots {
+            return Err(RiskError::Overflow);
+        }
+        self.warmup_bounds = bounds;
+        Ok(())
+    }
+    
+    /// Protocol bounds on the agent's warmup period
+    pub fn warmup_bounds(&self) -> &WarmupBounds {
+        &self.warmup_bounds
+    }
+    
+    /// Fees distributed since creation
+    pub fn fee_totals(&self) -> &FeeTotals {
+        &self.fee_totals
+    }
+    
+    /// Fees distributed in the current accounting period
+    pub fn epoch_fee_totals(&self) -> &FeeTotals {
+        &self.epoch_fee_totals
+    }
+    
+    /// Close the current accounting period; returns its fee totals
+    pub fn roll_fee_epoch(&mut self) -> FeeTotals {
+        core::mem::take(&mut self.epoch_fee_totals)
+    }
+    
+    /// Mark-to-market equity of the agent's LP account
+    fn lp_equity(&self, oracle_price: u64) -> u128 {
+        if !self.engine.is_used(AGENT_LP_IDX as usize) {
+            return 0;
+        }
+        self.engine
+            .account_equity_mtm_at_oracle(&self.engine.accounts[AGENT_LP_IDX as usize], oracle_price)
+    }
+    
+    /// LP utilization: initial margin of `lp_position` over `lp_equity` (bps)
+    fn pool_utilization_bps(&self, lp_position: i128, lp_equity: u128, oracle_price: u64) -> u64 {
+        let required =
+            margin_required(lp_position, oracle_price, self.engine.params.initial_margin_bps);
+        ratio_bps(required, lp_equity, Rounding::Down)
+    }
+    
+    /// Reject trades that grow LP inventory beyond the agent's utilization cap
+    fn validate_pool_utilization(&self, exec_size: i128, oracle_price: u64, cap_bps: u64) -> Result<()> {
+        if !self.engine.is_used(AGENT_LP_IDX as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let lp_position = self.engine.accounts[AGENT_LP_IDX as usize].position_size.get();
+        let new_position = lp_position.checked_sub(exec_size).ok_or(RiskError::Overflow)?;
+        if saturating_abs_i128(new_position) <= saturating_abs_i128(lp_position) {
+            return Ok(());
+        }
+        let utilization =
+            self.pool_utilization_bps(new_position, self.lp_equity(oracle_price), oracle_price);
+        if utilization > cap_bps {
+            return Err(RiskError::Undercollateralized);
+        }
+        Ok(())
+    }
+    
+    /// Deposit `amount` into the LP pool on behalf of `owner`; returns shares minted
+    ///
+    /// The first pool deposit mints the LP account's existing equity to the
+    /// LP account owner 1:1, so the operator's stake is not diluted.
+    pub fn pool_deposit(
+        &mut self,
+        owner: [u8; 32],
+        amount: u128,
+        now_slot: u64,
+        oracle_price: u64,
+    ) -> Result<u128> {
+        if amount == 0 {
+            return Err(RiskError::Overflow);
+        }
+        if !self.engine.is_used(AGENT_LP_IDX as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let equity = self.lp_equity(oracle_price);
+        if self.lp_pool.total_shares() == 0 && equity > 0 {
+            let operator = self.engine.accounts[AGENT_LP_IDX as usize].owner;
+            self.lp_pool.mint(operator, equity, equity)?;
+        }
+        
+        let shares = self.lp_pool.shares_for_deposit(amount, equity)?;
+        if shares == 0 {
+            return Err(RiskError::Overflow);
+        }
+        self.engine.deposit(AGENT_LP_IDX, amount, now_slot)?;
+        self.lp_pool.mint(owner, shares, amount)?;
+        self.record_change(AGENT_LP_IDX);
+        Ok(shares)
+    }
+    
+    /// Burn `shares` of `owner` and pay out their value; returns the amount paid
+    pub fn pool_withdraw(
+        &mut self,
+        owner: [u8; 32],
+        shares: u128,
+        now_slot: u64,
+        oracle_price: u64,
+    ) -> Result<u128> {
+        let held = self.lp_pool.provider(&owner).map_or(0, |p| p.shares);
+        if shares == 0 || shares > held {
+            return Err(RiskError::InsufficientBalance);
+        }
+        let equity = self.lp_equity(oracle_price);
+        let amount = self.lp_pool.value_of_shares(shares, equity);
+        
+        let lp_position = self.engine.accounts[AGENT_LP_IDX as usize].position_size.get();
+        let remaining = equity.saturating_sub(amount);
+        if lp_position != 0
+            && self.pool_utilization_bps(lp_position, remaining, oracle_price)
+                > self.pool_utilization_cap_bps
+        {
+            return Err(RiskError::Undercollateralized);
+        }
+        
+        if amount > self.withdrawal_allowance(now_slot) {
+            return Err(RiskError::Unauthorized); // WithdrawalRateLimited
+        }
+        if amount > 0 {
+            self.engine.withdraw(AGENT_LP_IDX, amount, now_slot, oracle_price)?;
+            self.record_withdrawal(amount);
+        }
+        self.lp_pool.burn(&owner, shares, amount)?;
+        self.record_change(AGENT_LP_IDX);
+        Ok(amount)
+    }
+    
+    /// Pool share ledger
+    pub fn lp_pool(&self) -> &LpPool {
+        &self.lp_pool
+    }
+    
+    /// Stake of `owner` valued at `oracle_price`
+    pub fn pool_position(&self, owner: &[u8; 32], oracle_price: u64) -> Option<PoolPosition> {
+        self.lp_pool.position(owner, self.lp_equity(oracle_price))
+    }
+    
+    /// Stamp activity on an account at the current slot
+    fn mark_active(&mut self, idx: u16) {
+        if let Some(account) = self.engine.accounts.get(idx as usize) {
+            self.activity[idx as usize] = ActivityStamp {
+                account_id: account.account_id,
+                slot: self.engine.current_slot,
+            };
+        }
+    }
+    
+    /// Close idle flat user accounts whose capital is at or below the dust
+    /// threshold: remaining capital goes to insurance, negative PnL is
+    /// written off and the slot is freed. Engine GC handles zero-capital dust.
+    fn sweep_dust(&mut self, now_slot: u64, oracle_price: u64) {
+        let policy = self.market_params.dust_policy;
+        let mut sweep = DustSweep {
+            slot: now_slot,
+            ..DustSweep::default()
+        };
+        if policy.dust_capital_threshold == 0 {
+            self.last_dust_sweep = sweep;
+            return;
+        }
+        
+        let start = self.dust_cursor as usize;
+        let scan = DUST_SWEEP_SCAN.min(MAX_ACCOUNTS);
+        metering::scan(scan as u64);
+        for offset in 0..scan {
+            if sweep.closed_len >= MAX_DUST_EVENTS {
+                break;
+            }
+            let idx = ((start + offset) % MAX_ACCOUNTS) as u16;
+            if !self.engine.is_used(idx as usize) || self.engine.accounts[idx as usize].is_lp() {
+                continue;
+            }
+            
+            // First sighting of an account starts its idle clock
+            let account_id = self.engine.accounts[idx as usize].account_id;
+            let stamp = self.activity[idx as usize];
+            if stamp.account_id != account_id {
+                self.activity[idx as usize] = ActivityStamp { account_id, slot: now_slot };
+                continue;
+            }
+            if now_slot.saturating_sub(stamp.slot) < policy.idle_slots {
+                continue;
+            }
+            
+            if self.engine.touch_account_full(idx, now_slot, oracle_price).is_err() {
+                continue;
+            }
+            let account = &self.engine.accounts[idx as usize];
+            let capital = account.capital.get();
+            let pnl = account.pnl.get();
+            if !account.position_size.is_zero()
+                || account.reserved_pnl != 0
+                || pnl > 0
+                || capital > policy.dust_capital_threshold
+            {
+                continue;
+            }
+            
+            // Parents keep their sub-accounts' collateral chain alive
+            let is_parent = (0..MAX_ACCOUNTS as u16).any(|sub| {
+                live_link(&self.engine, &self.sub_links, sub).is_some_and(|l| l.parent == idx)
+            });
+            if is_parent {
+                continue;
+            }
+            
+            self.engine.set_capital(idx as usize, 0);
+            self.engine.insurance_fund.balance =
+                self.engine.insurance_fund.balance.saturating_add_u128(U128::new(capital));
+            self.engine.insurance_fund.fee_revenue =
+                self.engine.insurance_fund.fee_revenue.saturating_add_u128(U128::new(capital));
+            if pnl < 0 {
+                self.engine.set_pnl(idx as usize, 0);
+            }
+            if self.engine.close_account(idx, now_slot, oracle_price).is_err() {
+                continue;
+            }
+            
+            self.sub_links[idx as usize] = SubAccountLink::NONE;
+            self.activity[idx as usize] = ActivityStamp::NONE;
+            self.record_change(idx);
+            sweep.closed[sweep.closed_len] = DustEvent {
+                account_idx: idx,
+                account_id,
+                swept_capital: capital,
+                written_off_pnl: saturating_abs_i128(pnl.min(0)) as u128,
+            };
+            sweep.closed_len += 1;
+            sweep.swept_total = sweep.swept_total.saturating_add(capital);
+        }
+        
+        self.dust_cursor = ((start + scan) % MAX_ACCOUNTS) as u16;
+        self.last_dust_sweep = sweep;
+    }
+    
+    /// Replace the keeper reward schedule
+    pub fn set_keeper_reward_params(&mut self, params: KeeperRewardParams) {
+        self.keeper_params = params;
+    }
+    
+    /// Current keeper reward schedule
+    pub fn keeper_reward_params(&self) -> &KeeperRewardParams {
+        &self.keeper_params
+    }
+    
+    /// Keeper reward claims
+    pub fn keeper_ledger(&self) -> &KeeperLedger {
+        &self.keeper_ledger
+    }
+    
+    /// Cumulative funding per account
+    pub fn funding_ledger(&self) -> FundingLedger<'_> {
+        FundingLedger::new(&self.engine)
+    }
+    
+    /// Unclaimed rewards of `keeper_idx`
+    pub fn pending_keeper_rewards(&self, keeper_idx: u16) -> u128 {
+        self.keeper_ledger.pending(keeper_idx)
+    }
+    
+    /// Pay `keeper_idx`'s accrued rewards from insurance into its capital
+    pub fn claim_keeper_rewards(&mut self, keeper_idx: u16) -> Result<u128> {
+        let paid = self
+            .keeper_ledger
+            .claim(&mut self.engine, &self.keeper_params, keeper_idx)?;
+        if paid > 0 {
+            self.record_change(keeper_idx);
+        }
+        Ok(paid)
+    }
+    
+    /// Insurance owed to keepers and referrers but not yet claimed
+    fn insurance_claims(&self) -> u128 {
+        self.keeper_ledger
+            .pending_total()
+            .saturating_add(self.referrals.pending_total())
+    }
+    
+    /// Pay `share_bps` of referred accounts' trading fees to their
+    /// referrers (None ends accrual; pending rebates stay claimable)
+    pub fn set_referral_policy(&mut self, policy: Option<ReferralPolicy>) -> Result<()> {
+        self.require_ungoverned()?;
+        if let Some(policy) = &policy {
+            policy.validate(&self.referral_bounds)?;
+        }
+        self.referral_policy = policy;
+        Ok(())
+    }
+    
+    /// Replace the protocol caps on the referral program
+    ///
+    /// Refused once governance is enabled, or if the current policy would
+    /// exceed the new caps.
+    pub fn set_referral_bounds(&mut self, bounds: ReferralBounds) -> Result<()> {
+        self.require_ungoverned()?;
+        if let Some(policy) = &self.referral_policy {
+            policy.validate(&bounds)?;
+        }
+        self.referral_bounds = bounds;
+        Ok(())
+    }
+    
+    /// Referral program in force, if any
+    pub fn referral_policy(&self) -> Option<&ReferralPolicy> {
+        self.referral_policy.as_ref()
+    }
+    
+    /// Referrer links and referral rebates
+    pub fn referrals(&self) -> &ReferralLedger {
+        &self.referrals
+    }
+    
+    /// Record `referrer_idx` as the referrer of `idx`
+    ///
+    /// An account's referrer is set once; a reused slot starts afresh.
+    pub fn register_referrer(&mut self, idx: u16, referrer_idx: u16) -> Result<()> {
+        if self.referral_policy.is_none() {
+            return Err(RiskError::Unauthorized);
+        }
+        for i in [idx, referrer_idx] {
+            if i as usize >= MAX_ACCOUNTS || !self.engine.is_used(i as usize) {
+                return Err(RiskError::AccountNotFound);
+            }
+        }
+        let account_id = self.engine.accounts[idx as usize].account_id;
+        if idx == referrer_idx || self.referrals.link(idx, account_id).is_some() {
+            return Err(RiskError::Unauthorized);
+        }
+        self.referrals.set_link(
+            idx,
+            ReferralLink {
+                account_id,
+                referrer_idx,
+                referrer_account_id: self.engine.accounts[referrer_idx as usize].account_id,
+            },
+        );
+        Ok(())
+    }
+    
+    /// Referrer of `idx` while the program runs and both accounts are live
+    fn live_referrer(&self, idx: u16) -> Option<u16> {
+        self.referral_policy?;
+        let link = self
+            .referrals
+            .link(idx, self.engine.accounts[idx as usize].account_id)?;
+        let referrer = link.referrer_idx as usize;
+        (self.engine.is_used(referrer)
+            && self.engine.accounts[referrer].account_id == link.referrer_account_id)
+            .then_some(link.referrer_idx)
+    }
+    
+    /// Unclaimed referral rebates of `idx`
+    pub fn pending_referral_rebates(&self, idx: u16) -> u128 {
+        self.referrals.pending(idx)
+    }
+    
+    /// Pay `idx`'s referral rebates from insurance into its capital
+    pub fn claim_referral_rebates(&mut self, idx: u16) -> Result<u128> {
+        let paid = self.referrals.claim(&mut self.engine, idx)?;
+        if paid > 0 {
+            self.record_change(idx);
+        }
+        Ok(paid)
+    }
+    
+    /// Replace the protocol bounds on oracle inputs
+    ///
+    /// Refused once governance is enabled; use a proposal instead.
+    pub fn set_oracle_bounds(&mut self, bounds: OracleBounds) -> Result<()> {
+        self.require_ungoverned()?;
+        self.install_oracle_bounds(bounds)
+    }
+    
+    fn install_oracle_bounds(&mut self, bounds: OracleBounds) -> Result<()> {
+        if bounds.max_confidence_bps == 0
+            || bounds.max_confidence_bps > 10_000
+            || bounds.max_divergence_bps == 0
+            || bounds.max_divergence_bps > 10_000
+        {
+            return Err(RiskError::Overflow);
+        }
+        self.oracle_bounds = bounds;
+        Ok(())
+    }
+    
+    /// Current protocol bounds on oracle inputs
+    pub fn oracle_bounds(&self) -> &OracleBounds {
+        &self.oracle_bounds
+    }
+    
+    /// Reject oracle prices that are out of range, stale or too uncertain
+    fn validate_oracle(&self, oracle: &OraclePrice, now_slot: u64) -> Result<()> {
+        if oracle.price == 0 || oracle.price > MAX_ORACLE_PRICE {
+            return Err(RiskError::Overflow);
+        }
+        if oracle.is_stale(now_slot, self.oracle_bounds.max_staleness_slots) {
+            return Err(RiskError::Unauthorized); // StaleOracle
+        }
+        if oracle.confidence_bps() > self.oracle_bounds.max_confidence_bps {
+            return Err(RiskError::Unauthorized); // OracleConfidence
+        }
+        Ok(())
+    }
+    
+    /// Whether the protocol is in risk-reduction-only mode
+    pub fn risk_reduction_mode(&self) -> bool {
+        self.risk_reduction.active()
+    }
+    
+    /// Whether only position-reducing trades execute (risk-reduction mode
+    /// or a wind-down)
+    fn reduce_only(&self) -> bool {
+        self.risk_reduction.active() || self.wind_down.is_some()
+    }
+    
+    /// Conditions holding the protocol in risk-reduction-only mode
+    pub fn risk_reduction_triggers(&self) -> &RiskReductionTriggers {
+        &self.risk_reduction
+    }
+    
+    /// Administrative risk-reduction trigger
+    pub fn set_risk_reduction_admin(&mut self, active: bool) {
+        self.risk_reduction.admin = active;
+    }
+    
+    /// Replace the withdrawal rate limit applied in risk-reduction-only mode
+    pub fn set_risk_reduction_limits(&mut self, limits: RiskReductionLimits) -> Result<()> {
+        if limits.max_withdrawal_bps_per_window > 10_000 || limits.window_slots == 0 {
+            return Err(RiskError::Overflow);
+        }
+        self.risk_reduction_limits = limits;
+        Ok(())
+    }
+    
+    /// Withdrawal amount still allowed at `now_slot` (unlimited outside
+    /// risk-reduction-only mode)
+    pub fn withdrawal_allowance(&mut self, now_slot: u64) -> u128 {
+        if !self.risk_reduction.active() {
+            return u128::MAX;
+        }
+        let limits = self.risk_reduction_limits;
+        let window = &mut self.withdrawal_window;
+        if window.budget == 0 || now_slot >= window.start_slot.saturating_add(limits.window_slots) {
+            *window = WithdrawalWindow {
+                start_slot: now_slot,
+                budget: apply_bps(
+                    self.engine.vault.get(),
+                    limits.max_withdrawal_bps_per_window,
+                    Rounding::Down,
+                ),
+                withdrawn: 0,
+            };
+        }
+        window.budget.saturating_sub(window.withdrawn)
+    }
+    
+    /// Count a completed withdrawal against the rate-limit window
+    fn record_withdrawal(&mut self, amount: u128) {
+        if self.risk_reduction.active() {
+            self.withdrawal_window.withdrawn = self.withdrawal_window.withdrawn.saturating_add(amount);
+        }
+    }
+    
+    /// Administrative freeze/unfreeze
+    ///
+    /// An admin freeze is never lifted by anomaly de-escalation; unfreezing
+    /// here clears any freeze regardless of origin.
+    pub fn set_market_frozen(&mut self, frozen: bool) {
+        self.market_frozen = frozen;
+        self.freeze_origin = frozen.then_some(FreezeOrigin::Admin);
+    }
+    
+    /// Install (or remove) the operator kill-switch key (admin)
+    pub fn set_operator(&mut self, pubkey: Option<[u8; 32]>) {
+        self.operator = pubkey.map(OperatorAuthority::new);
+    }
+    
+    /// Operator kill-switch key and nonce state
+    pub fn operator(&self) -> Option<&OperatorAuthority> {
+        self.operator.as_ref()
+    }
+    
+    /// Market params currently in effect
+    pub fn market_params(&self) -> &MarketParams {
+        &self.market_params
+    }
+    
+    /// Whether agent param updates are vetoed by the operator
+    pub fn params_vetoed(&self) -> bool {
+        self.params_vetoed
+    }
+    
+    /// Execute a signed operator kill-switch command
+    ///
+    /// `nonce` must exceed every nonce previously accepted and `signature`
+    /// must cover `command.encode(nonce)` under the operator key.
+    pub fn execute_operator_command<V: SignatureVerifier>(
+        &mut self,
+        verifier: &V,
+        command: OperatorCommand,
+        nonce: u64,
+        signature: &[u8; 64],
+    ) -> Result<()> {
+        let operator = self.operator.as_mut().ok_or(RiskError::Unauthorized)?;
+        operator.authorize(verifier, command, nonce, signature)?;
+        
+        match command {
+            OperatorCommand::Freeze(frozen) => {
+                self.market_frozen = frozen;
+                self.freeze_origin = frozen.then_some(FreezeOrigin::Operator);
+            }
+            OperatorCommand::ForceRiskReduction(active) => {
+                self.risk_reduction.operator = active;
+            }
+            OperatorCommand::VetoParams(veto) => {
+                if veto && !self.params_vetoed {
+                    self.apply_market_params(self.previous_market_params);
+                    self.pre_anomaly_max_position = None;
+                }
+                self.params_vetoed = veto;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Hand protocol parameter control to multisig governance (one-way)
+    pub fn enable_governance(&mut self, config: GovernanceConfig) -> Result<()> {
+        self.require_ungoverned()?;
+        self.governance = Some(Governance::new(config)?);
+        Ok(())
+    }
+    
+    /// Governance state, if enabled
+    pub fn governance(&self) -> Option<&Governance> {
+        self.governance.as_ref()
+    }
+    
+    /// Proposals awaiting approval or timelock
+    pub fn pending_proposals(&self) -> impl Iterator<Item = &Proposal> {
+        self.governance.iter().flat_map(|g| g.pending())
+    }
+    
+    /// Submit a governance proposal signed by `signer`; returns its id
+    pub fn propose_governance_change<V: SignatureVerifier>(
+        &mut self,
+        verifier: &V,
+        action: ProposalAction,
+        signer: u8,
+        signature: &[u8; 64],
+        now_slot: u64,
+    ) -> Result<u64> {
+        let governance = self.governance.as_mut().ok_or(RiskError::Unauthorized)?;
+        governance.propose(verifier, action, signer, signature, now_slot)
+    }
+    
+    /// Add a signer's approval to a pending proposal
+    pub fn approve_governance_proposal<V: SignatureVerifier>(
+        &mut self,
+        verifier: &V,
+        id: u64,
+        signer: u8,
+        signature: &[u8; 64],
+        now_slot: u64,
+    ) -> Result<()> {
+        let governance = self.governance.as_mut().ok_or(RiskError::Unauthorized)?;
+        governance.approve(verifier, id, signer, signature, now_slot)
+    }
+    
+    /// Cancel a pending proposal (any signer)
+    pub fn cancel_governance_proposal<V: SignatureVerifier>(
+        &mut self,
+        verifier: &V,
+        id: u64,
+        signer: u8,
+        signature: &[u8; 64],
+    ) -> Result<()> {
+        let governance = self.governance.as_mut().ok_or(RiskError::Unauthorized)?;
+        governance.cancel(verifier, id, signer, signature)
+    }
+    
+    /// Apply an approved proposal once its timelock has elapsed
+    ///
+    /// Permissionless: approvals and the timelock are the authorization.
+    /// A proposal whose action fails validation is consumed without effect.
+    pub fn execute_governance_proposal(&mut self, id: u64, now_slot: u64) -> Result<()> {
+        let governance = self.governance.as_mut().ok_or(RiskError::Unauthorized)?;
+        let action = governance.take_executable(id, now_slot)?;
+        
+        match action {
+            ProposalAction::RiskParams(params) => {
+                if params.maintenance_margin_bps > params.initial_margin_bps
+                    || params.initial_margin_bps > 10_000
+                    || params.max_accounts > MAX_ACCOUNTS as u64
+                {
+                    return Err(RiskError::Overflow);
+                }
+                self.engine.params = params;
+            }
+            ProposalAction::LeverageCeiling(bps) => {
+                if bps > 10_000 {
+                    return Err(RiskError::Overflow);
+                }
+                self.max_leverage_ceiling_bps = bps;
+            }
+            ProposalAction::OracleBounds(bounds) => self.install_oracle_bounds(bounds)?,
+            ProposalAction::EscalationPolicy(policy) => self.install_escalation_policy(policy)?,
+            ProposalAction::DustBounds(bounds) => self.dust_bounds = bounds,
+            ProposalAction::WithdrawalBounds(bounds) => self.withdrawal_bounds = bounds,
+            ProposalAction::FeeSplitBounds(bounds) => self.fee_split_bounds = bounds,
+            ProposalAction::LendingBounds(bounds) => self.lending_bounds = bounds,
+            ProposalAction::VammBounds(bounds) => self.vamm_bounds = bounds,
+            ProposalAction::ConcentrationBounds(bounds) => self.concentration_bounds = bounds,
+            ProposalAction::WarmupBounds(bounds) => self.install_warmup_bounds(bounds)?,
+            ProposalAction::ImpactModel(model) => {
+                model.validate()?;
+                self.impact_model = model;
+            }
+            ProposalAction::MarketMode(mode) => self.stage_market_mode(mode),
+            ProposalAction::WindDown(params) => self.install_wind_down(params, now_slot)?,
+            ProposalAction::Config(config) => governance.replace_config(config)?,
+        }
+        Ok(())
+    }
+    
+    /// Start delisting the market (admin, ungoverned markets only)
+    ///
+    /// Trading turns reduce-only at once; the crank ramps margins and
+    /// settles residual positions on the schedule in `params`.
+    pub fn start_wind_down(&mut self, params: WindDownParams, now_slot: u64) -> Result<()> {
+        self.require_ungoverned()?;
+        self.install_wind_down(params, now_slot)
+    }
+    
+    fn install_wind_down(&mut self, params: WindDownParams, now_slot: u64) -> Result<()> {
+        if self.wind_down.is_some() {
+            return Err(RiskError::Unauthorized);
+        }
+        let risk = &self.engine.params;
+        params.validate(risk.maintenance_margin_bps, risk.initial_margin_bps)?;
+        self.wind_down = Some(WindDown {
+            params,
+            started_slot: now_slot,
+            start_maintenance_margin_bps: risk.maintenance_margin_bps,
+            start_initial_margin_bps: risk.initial_margin_bps,
+            settlement_price: None,
+            settled_positions: 0,
+        });
+        Ok(())
+    }
+    
+    /// Delisting in progress, if any
+    pub fn wind_down(&self) -> Option<&WindDown> {
+        self.wind_down.as_ref()
+    }
+    
+    /// Where the wind-down stands at `now_slot`
+    pub fn wind_down_progress(&self, now_slot: u64) -> Option<WindDownProgress> {
+        let wind_down = self.wind_down.as_ref()?;
+        let (maintenance_margin_bps, initial_margin_bps) = wind_down.margins_at(now_slot);
+        let open_positions = (0..MAX_ACCOUNTS)
+            .filter(|&idx| self.engine.is_used(idx) && !self.engine.accounts[idx].position_size.is_zero())
+            .count() as u32;
+        Some(WindDownProgress {
+            phase: wind_down.phase(now_slot),
+            ramp_bps: wind_down.ramp_bps(now_slot),
+            maintenance_margin_bps,
+            initial_margin_bps,
+            settle_slot: wind_down.settle_slot(),
+            open_positions,
+            open_interest: self.engine.total_open_interest.get(),
+            settlement_price: wind_down.settlement_price,
+        })
+    }
+    
+    /// Raise engine margins along the wind-down ramp (crank step)
+    fn ramp_wind_down_margins(&mut self, now_slot: u64) {
+        if let Some(wind_down) = &self.wind_down {
+            let (maintenance, initial) = wind_down.margins_at(now_slot);
+            self.engine.params.maintenance_margin_bps = maintenance;
+            self.engine.params.initial_margin_bps = initial;
+        }
+    }
+    
+    /// Settle every residual position at the TWAP once due (crank step);
+    /// returns whether positions were settled
+    fn settle_wind_down(&mut self, now_slot: u64, oracle_price: u64) -> bool {
+        let Some(wind_down) = self.wind_down else {
+            return false;
+        };
+        if wind_down.settlement_price.is_some() || now_slot < wind_down.settle_slot() {
+            return false;
+        }
+        let since = now_slot.saturating_sub(wind_down.params.twap_slots);
+        let price = self.price_history.twap(since, now_slot).unwrap_or(oracle_price);
+        let mut settled = 0u32;
+        metering::scan(MAX_ACCOUNTS as u64);
+        for idx in 0..MAX_ACCOUNTS {
+            if !self.engine.is_used(idx) || self.engine.accounts[idx].position_size.is_zero() {
+                continue;
+            }
+            if self.engine.settle_position_at_price(idx as u16, now_slot, price).is_ok() {
+                settled = settled.saturating_add(1);
+            }
+        }
+        if let Some(wind_down) = self.wind_down.as_mut() {
+            wind_down.settlement_price = Some(price);
+            wind_down.settled_positions = settled;
+        }
+        true
+    }
+    
+    /// Arm (or disarm, with None) the flapping guard (admin, ungoverned
+    /// markets only)
+    pub fn set_flapping_guard(&mut self, guard: Option<FlappingGuard>) -> Result<()> {
+        self.require_ungoverned()?;
+        if let Some(guard) = &guard {
+            guard.validate()?;
+        }
+        self.flapping_guard = guard;
+        Ok(())
+    }
+    
+    /// Flapping thresholds, if armed
+    pub fn flapping_guard(&self) -> Option<&FlappingGuard> {
+        self.flapping_guard.as_ref()
+    }
+    
+    /// Whether agent param updates are locked after flapping
+    pub fn params_locked(&self) -> bool {
+        self.params_locked
+    }
+    
+    /// Release a flapping lock (admin); the flip count starts over
+    pub fn unlock_params(&mut self) {
+        self.params_locked = false;
+        self.flips.clear();
+    }
+    
+    /// Events raised for the operator (most recent last)
+    pub fn operator_alerts(&self) -> &AlertLog {
+        &self.alerts
+    }
+    
+    /// Market events not yet drained
+    pub fn pending_events(&self) -> &EventLog {
+        &self.events
+    }
+    
+    /// Emit pending market events into `sink` in order and clear them;
+    /// returns how many were emitted
+    ///
+    /// On-chain wrappers call this at the end of every instruction with a
+    /// `ProgramLogSink`.
+    pub fn drain_events<S: EventSink>(&mut self, sink: &mut S) -> usize {
+        self.events.drain(sink)
+    }
+    
+    /// Log a change of agent decision; lock params and alert the operator
+    /// once the agent flaps past the guard
+    fn record_flip(&mut self, kind: FlipKind, params: MarketParams) {
+        let Some(guard) = self.flapping_guard else {
+            return;
+        };
+        let slot = self.engine.current_slot;
+        self.flips.push(Flip { slot, kind, params });
+        let since = slot.saturating_sub(guard.window_slots);
+        let flips = self.flips.count_since(since);
+        if flips <= guard.max_flips || self.params_locked {
+            return;
+        }
+        let locked = self.flips.tightest_since(since, &self.market_params);
+        self.apply_market_params(locked);
+        self.pre_anomaly_max_position = None;
+        self.params_locked = true;
+        self.alerts.push(OperatorAlert {
+            slot,
+            kind: AlertKind::DecisionFlapping { flips },
+        });
+    }
+    
+    /// Arm (or disarm, with None) the dead-man switch (admin, ungoverned
+    /// markets only)
+    ///
+    /// Idle time counts from the agent's last valid output. Disarming
+    /// lifts whatever the switch has applied at the next crank.
+    pub fn set_dead_man_switch(&mut self, params: Option<DeadManParams>) -> Result<()> {
+        self.require_ungoverned()?;
+        if let Some(params) = &params {
+            params.validate()?;
+        }
+        self.dead_man = params;
+        Ok(())
+    }
+    
+    /// Dead-man switch thresholds, if armed
+    pub fn dead_man_switch(&self) -> Option<&DeadManParams> {
+        self.dead_man.as_ref()
+    }
+    
+    /// Slot of the agent's last valid output (trade decision, batch price,
+    /// market params update or heartbeat)
+    pub fn last_agent_activity_slot(&self) -> u64 {
+        self.last_agent_activity_slot
+    }
+    
+    /// Record a heartbeat from `agent` at `now_slot`
+    ///
+    /// A cheap liveness proof between cranks: nothing but the agent's
+    /// `heartbeat` hook runs, and success counts as agent activity for the
+    /// dead-man switch.
+    pub fn record_heartbeat<A: OpenClawAgent>(&mut self, agent: &A, now_slot: u64) -> Result<()> {
+        if agent.agent_capabilities().heartbeat {
+            agent.heartbeat(&self.build_context(OraclePrice::default()))?;
+        }
+        self.last_heartbeat_slot = Some(self.last_heartbeat_slot.map_or(now_slot, |s| s.max(now_slot)));
+        self.record_agent_activity(now_slot);
+        Ok(())
+    }
+    
+    /// Slot of the agent's last heartbeat
+    pub fn last_heartbeat_slot(&self) -> Option<u64> {
+        self.last_heartbeat_slot
+    }
+    
+    /// Slots since the agent's last heartbeat, at `now_slot`
+    pub fn heartbeat_age(&self, now_slot: u64) -> Option<u64> {
+        self.last_heartbeat_slot.map(|s| now_slot.saturating_sub(s))
+    }
+    
+    /// Dead-man switch state at `now_slot` (None while disarmed)
+    pub fn dead_man_status(&self, now_slot: u64) -> Option<DeadManStatus> {
+        self.dead_man.as_ref()?;
+        Some(DeadManStatus {
+            last_agent_activity_slot: self.last_agent_activity_slot,
+            idle_slots: now_slot.saturating_sub(self.last_agent_activity_slot),
+            stage: self.dead_man_stage,
+        })
+    }
+    
+    fn record_agent_activity(&mut self, slot: u64) {
+        self.last_agent_activity_slot = self.last_agent_activity_slot.max(slot);
+    }
+    
+    /// Apply the dead-man stage for the agent's idle time (crank step)
+    ///
+    /// Margins widened at an earlier crank are restored first, unless
+    /// something else (governance, the wind-down ramp) has replaced them
+    /// since.
+    fn check_dead_man(&mut self, now_slot: u64) {
+        if let Some(widened) = self.widened_margins.take() {
+            let risk = &mut self.engine.params;
+            if risk.maintenance_margin_bps == widened.maintenance_bps
+                && risk.initial_margin_bps == widened.initial_bps
+            {
+                risk.maintenance_margin_bps = widened.base_maintenance_bps;
+                risk.initial_margin_bps = widened.base_initial_bps;
+            }
+        }
+        
+        let idle_slots = now_slot.saturating_sub(self.last_agent_activity_slot);
+        let stage = self.dead_man.map_or(DeadManStage::Live, |p| p.stage(idle_slots));
+        self.dead_man_stage = stage;
+        self.risk_reduction.agent_timeout = stage >= DeadManStage::RiskReduction;
+        
+        if let (true, Some(params)) = (stage >= DeadManStage::RiskReduction, self.dead_man) {
+            let risk = &mut self.engine.params;
+            let widened = WidenedMargins {
+                base_maintenance_bps: risk.maintenance_margin_bps,
+                base_initial_bps: risk.initial_margin_bps,
+                maintenance_bps: risk.maintenance_margin_bps.saturating_add(params.margin_widen_bps).min(10_000),
+                initial_bps: risk.initial_margin_bps.saturating_add(params.margin_widen_bps).min(10_000),
+            };
+            risk.maintenance_margin_bps = widened.maintenance_bps;
+            risk.initial_margin_bps = widened.initial_bps;
+            self.widened_margins = Some(widened);
+        }
+        
+        if stage == DeadManStage::Frozen {
+            if !self.market_frozen {
+                self.market_frozen = true;
+                self.freeze_origin = Some(FreezeOrigin::AgentTimeout);
+            }
+        } else if self.freeze_origin == Some(FreezeOrigin::AgentTimeout) {
+            self.market_frozen = false;
+            self.freeze_origin = None;
+        }
+    }
+    
+    /// Stage a market mode switch (admin, ungoverned markets only)
+    ///
+    /// The switch takes effect at the next crank that advances, after
+    /// funding, liquidations and withdrawals have settled at that slot.
+    pub fn set_market_mode(&mut self, mode: MarketMode) -> Result<()> {
+        self.require_ungoverned()?;
+        self.stage_market_mode(mode);
+        Ok(())
+    }
+    
+    fn stage_market_mode(&mut self, mode: MarketMode) {
+        self.pending_market_mode = (mode != self.market_mode).then_some(mode);
+    }
+    
+    /// Current market mode
+    pub fn market_mode(&self) -> MarketMode {
+        self.market_mode
+    }
+    
+    /// Mode switch waiting for the next advancing crank
+    pub fn pending_market_mode(&self) -> Option<MarketMode> {
+        self.pending_market_mode
+    }
+    
+    /// Highest leverage the agent may set (bps)
+    pub fn max_leverage_ceiling_bps(&self) -> u64 {
+        self.max_leverage_ceiling_bps
+    }
+    
+    /// Direct admin changes to governed params are refused under governance
+    fn require_ungoverned(&self) -> Result<()> {
+        if self.governance.is_some() {
+            return Err(RiskError::Unauthorized); // Governed
+        }
+        Ok(())
+    }
+    
+    /// Whether the market is frozen, and by whom
+    pub fn freeze_origin(&self) -> Option<FreezeOrigin> {
+        self.freeze_origin
+    }
+    
+    /// Recent anomaly checks
+    pub fn anomaly_history(&self) -> &AnomalyHistory {
+        &self.anomaly_history
+    }
+    
+    /// Replace the anomaly escalation policy
+    ///
+    /// Refused once governance is enabled; use a proposal instead.
+    pub fn set_escalation_policy(&mut self, policy: EscalationPolicy) -> Result<()> {
+        self.require_ungoverned()?;
+        self.install_escalation_policy(policy)
+    }
+    
+    fn install_escalation_policy(&mut self, policy: EscalationPolicy) -> Result<()> {
+        if policy.shutdown_threshold_bps > 10_000
+            || policy.action_threshold_bps > policy.freeze_threshold_bps
+            || policy.freeze_threshold_bps > policy.shutdown_threshold_bps
+            || policy.shutdown_consecutive_checks == 0
+            || policy.deescalation_threshold_bps > policy.action_threshold_bps
+            || policy.deescalation_consecutive_checks == 0
+        {
+            return Err(RiskError::Overflow);
+        }
+        self.escalation = policy;
+        Ok(())
+    }
+    
+    /// Current anomaly escalation policy
+    pub fn escalation_policy(&self) -> &EscalationPolicy {
+        &self.escalation
+    }
+    
+    /// Run the agent's risk assessment and execute its actions
+    ///
+    /// Position reductions are validated as a batch (user accounts only,
+    /// and everything the engine could reject) before any is executed at
+    /// oracle price, so either all of them apply or none do. Hedge requests
+    /// are routed to `executor` after validation against LP inventory.
+    pub fn apply_risk_assessment<A: OpenClawAgent, H: HedgeExecutor>(
+        &mut self,
+        agent: &A,
+        executor: &mut H,
+        now_slot: u64,
+        oracle: OraclePrice,
+    ) -> Result<RiskAssessment> {
+        self.validate_oracle(&oracle, now_slot)?;
+        let oracle_price = oracle.price;
+        let context = self.build_context(oracle);
+        let assessment = if agent.agent_capabilities().risk_assessment {
+            agent.assess_risk(&context)?
+        } else {
+            RiskAssessment::none()
+        };
+        self.risk_reduction.agent_assessment = assessment.actions.reduce_exposure;
+        
+        let actions = &assessment.actions;
+        if actions.close_positions_len > actions.close_positions.len() {
+            return Err(RiskError::Overflow);
+        }
+        let reductions = &actions.close_positions[..actions.close_positions_len];
+        for r in reductions {
+            if !self.engine.is_used(r.account_idx as usize) {
+                return Err(RiskError::AccountNotFound);
+            }
+            // The agent is the LP; it cannot force-close its own book here
+            if !self.engine.accounts[r.account_idx as usize].is_user() {
+                return Err(RiskError::AccountKindMismatch);
+            }
+            self.engine.check_force_reduce(r.account_idx, r.reduce_by, oracle_price)?;
+        }
+        for r in reductions {
+            self.record_change(r.account_idx);
+            self.engine
+                .force_reduce_at_oracle(r.account_idx, r.reduce_by, now_slot, oracle_price)?;
+            let stats = &mut self.epoch.liquidations;
+            stats.forced_reductions = stats.forced_reductions.saturating_add(1);
+        }
+        
+        if let Some(size) = actions.hedge {
+            self.execute_hedge(executor, size, oracle_price)?;
+        }
+        
+        Ok(assessment)
+    }
+    
+    /// Hedge `size` contracts of LP inventory on an external venue
+    ///
+    /// The resulting hedge must offset LP inventory (opposite side, no larger
+    /// than the LP's net position). The fill is booked as a hedge position.
+    pub fn execute_hedge<H: HedgeExecutor>(
+        &mut self,
+        executor: &mut H,
+        size: i128,
+        oracle_price: u64,
+    ) -> Result<HedgeFill> {
+        if self.shutdown {
+            return Err(RiskError::Unauthorized);
+        }
+        if oracle_price == 0 || oracle_price > MAX_ORACLE_PRICE {
+            return Err(RiskError::Overflow);
+        }
+        hedging::validate_hedge_request(self.engine.net_lp_pos.get(), self.hedge.size, size)?;
+        
+        let fill = executor.execute_hedge(size, oracle_price)?;
+        hedging::validate_hedge_fill(size, &fill)?;
+        self.hedge.apply_fill(fill)?;
+        
+        Ok(fill)
+    }
+    
+    /// Current hedge position on external venues
+    pub fn hedge_position(&self) -> &HedgePosition {
+        &self.hedge
+    }
+    
+    /// Check if agent wants to shutdown
+    pub fn check_shutdown<A: OpenClawAgent>(
+        &mut self,
+        agent: &A,
+        oracle: OraclePrice,
+    ) -> Result<()> {
+        let context = self.build_context(oracle);
+        let should_shutdown =
+            agent.agent_capabilities().shutdown && agent.should_shutdown(&context)?;
+        
+        if should_shutdown {
+            self.shutdown = true;
+        }
+        
+        Ok(())
+    }
+    
+    /// Read-only view of a single account (None if the slot is free)
+    pub fn account_view(&self, idx: u16, oracle_price: u64) -> Option<AccountView> {
+        if !self.engine.is_used(idx as usize) {
+            return None;
+        }
+        let tier = effective_tier(&self.engine, &self.account_tiers, idx);
+        Some(
+            AccountView::from_engine(&self.engine, idx, oracle_price, tier)
+                .with_link(&self.engine, &self.sub_links, &self.margin_modes)
+                .with_funding(self.funding_ledger()),
+        )
+    }
+    
+    /// Borrowed accessor for a live account: fields are read in place and
+    /// derived values computed on demand (cheaper than `account_view` on BPF)
+    pub fn account_ref(&self, idx: u16) -> Option<AccountRef<'_>> {
+        AccountRef::new(self, idx)
+    }
+    
+    /// Live accounts as borrowed accessors, in index order
+    pub fn account_refs(&self) -> AccountRefs<'_> {
+        AccountRefs::new(self)
+    }
+    
+    /// Project liquidations, bankruptcies and the insurance waterfall if the
+    /// oracle moved `shock_bps` (e.g. -2000 = 20% drop) from `oracle_price`
+    pub fn stress_project(&self, shock_bps: i64, oracle_price: u64) -> StressProjection {
+        let price = stress::shocked_price(oracle_price, shock_bps);
+        let mut projection = StressProjection {
+            shock_bps,
+            shocked_price: price,
+            ..StressProjection::default()
+        };
+        metering::scan(MAX_ACCOUNTS as u64);
+        for idx in 0..MAX_ACCOUNTS {
+            if !self.engine.is_used(idx) {
+                continue;
+            }
+            let account = &self.engine.accounts[idx];
+            let position = account.position_size.get();
+            if position == 0 {
+                continue;
+            }
+            let mark = RiskEngine::mark_pnl_for_position(position, account.entry_price, price)
+                .unwrap_or(i128::MIN);
+            let equity = (account.capital.get().min(i128::MAX as u128) as i128)
+                .saturating_add(account.pnl.get())
+                .saturating_add(mark);
+            let maintenance =
+                margin_required(position, price, self.engine.params.maintenance_margin_bps);
+            if equity < 0 || (equity as u128) < maintenance {
+                projection.liquidatable_accounts += 1;
+            }
+            if equity < 0 {
+                projection.bankrupt_accounts += 1;
+                projection.total_deficit =
+                    projection.total_deficit.saturating_add(equity.unsigned_abs());
+            }
+        }
+        let insurance = self.engine.insurance_fund.balance.get();
+        projection.insurance_drawdown = projection.total_deficit.min(insurance);
+        projection.insurance_remaining = insurance - projection.insurance_drawdown;
+        projection.socialized_loss = projection.total_deficit - projection.insurance_drawdown;
+        projection
+    }
+    
+    /// Move an account's realized PnL into capital
+    ///
+    /// Funding is settled first. Losses are paid from capital; warmed-up
+    /// profit converts at the haircut ratio, so only gains backed by the
+    /// vault residual become withdrawable. Unrealized PnL is untouched.
+    pub fn settle_pnl(&mut self, idx: u16) -> Result<PnlSettlement> {
+        if idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        self.engine.touch_account(idx)?;
+        let account = &self.engine.accounts[idx as usize];
+        let (pnl, capital) = (account.pnl.get(), account.capital.get());
+        self.engine.settle_warmup_to_capital(idx)?;
+        self.collect_bankruptcies();
+        
+        let account = &self.engine.accounts[idx as usize];
+        let (new_pnl, new_capital) = (account.pnl.get(), account.capital.get());
+        let mut settlement = PnlSettlement::default();
+        if pnl < 0 {
+            settlement.loss_settled = capital - new_capital;
+            settlement.loss_written_off = pnl.unsigned_abs() - settlement.loss_settled;
+        } else {
+            settlement.profit_released = (pnl - new_pnl) as u128;
+            settlement.capital_credited = new_capital - capital;
+        }
+        self.record_change(idx);
+        self.mark_active(idx);
+        Ok(settlement)
+    }
+    
+    /// Oracle price at which an account falls below maintenance margin under
+    /// current params (None when free, flat or unliquidatable)
+    pub fn liquidation_price(&self, idx: u16, oracle_price: u64) -> Option<u64> {
+        self.margin_summary(idx, oracle_price)?.liquidation_price
+    }
+    
+    /// Accounts whose liquidation price `oracle_price` may have crossed
+    ///
+    /// Drawn from the liquidation index: a superset of the accounts below
+    /// maintenance as of their last refresh, found without scanning the slab.
+    pub fn liquidation_candidates(&self, oracle_price: u64) -> impl Iterator<Item = u16> + '_ {
+        self.liquidation_index.candidates(oracle_price)
+    }
+    
+    /// Liquidation-price index of accounts with positions
+    pub fn liquidation_index(&self) -> &LiquidationIndex {
+        &self.liquidation_index
+    }
+    
+    /// Refile an account in the liquidation index
+    fn reindex_liquidation(&mut self, idx: u16, oracle_price: u64) {
+        let position = match self.engine.is_used(idx as usize) {
+            true => self.engine.accounts[idx as usize].position_size.get(),
+            false => 0,
+        };
+        let price = (position != 0).then(|| self.liquidation_price(idx, oracle_price)).flatten();
+        self.liquidation_index.update(idx, position > 0, price);
+    }
+    
+    /// Refile the accounts changed since the last crank, or every account
+    /// after raw engine access
+    fn refresh_liquidation_index(&mut self, oracle_price: u64) {
+        if self.liquidation_index.needs_rebuild() {
+            self.liquidation_index.clear();
+            metering::scan(MAX_ACCOUNTS as u64);
+            for idx in 0..MAX_ACCOUNTS as u16 {
+                self.reindex_liquidation(idx, oracle_price);
+            }
+            return;
+        }
+        for word in 0..MAX_ACCOUNTS.div_ceil(64) {
+            let mut bits = self.liquidation_index.take_stale(word);
+            while bits != 0 {
+                let idx = (word * 64 + bits.trailing_zeros() as usize) as u16;
+                bits &= bits - 1;
+                self.reindex_liquidation(idx, oracle_price);
+            }
+        }
+    }
+    
+    /// Liquidate index candidates below maintenance (the crank's
+    /// liquidation pass); returns how many were liquidated
+    ///
+    /// Candidates still above maintenance stay filed and do not count
+    /// towards the `LIQ_CANDIDATES_PER_CRANK` budget.
+    fn liquidate_candidates(&mut self, now_slot: u64, oracle_price: u64) -> u32 {
+        let mut batch = [0u16; LIQ_CANDIDATES_PER_CRANK];
+        let mut len = 0;
+        for idx in self.liquidation_index.candidates(oracle_price) {
+            metering::iterate(1);
+            if len == batch.len() {
+                break;
+            }
+            let account = &self.engine.accounts[idx as usize];
+            let underwater = self.engine.is_used(idx as usize)
+                && !account.position_size.is_zero()
+                && !self.engine.is_liquidation_held(idx as usize)
+                && !self.engine.is_above_maintenance_margin_mtm(account, oracle_price);
+            if underwater {
+                batch[len] = idx;
+                len += 1;
+            }
+        }
+        let mut liquidated = 0u32;
+        for &idx in &batch[..len] {
+            if self.liquidate(idx, now_slot, oracle_price).unwrap_or(false) {
+                liquidated += 1;
+            }
+        }
+        liquidated
+    }
+    
+    /// Margin requirements, free collateral and liquidation price of an account
+    pub fn margin_summary(&self, idx: u16, oracle_price: u64) -> Option<MarginSummary> {
+        if idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(idx as usize) {
+            return None;
+        }
+        let account = &self.engine.accounts[idx as usize];
+        Some(MarginSummary::new(
+            self.engine.account_equity_mtm_at_oracle(account, oracle_price),
+            account.position_size.get(),
+            oracle_price,
+            &self.engine.params,
+        ))
+    }
+    
+    /// Iterate occupied accounts matching `filter`, starting at `start_idx`
+    pub fn accounts(
         &self,
-        price: u64,
-        exec_size: i128,
-        requested_size: i128,
-    ) -> Result<()> {
-        // Price bounds
-        if price == 0 || price > MAX_ORACLE_PRICE {
-            return Err(RiskError::InvalidMatchingEngine);
+        start_idx: u16,
+        oracle_price: u64,
+        filter: AccountFilter,
+    ) -> AccountIter<'_> {
+        AccountIter {
+            engine: &self.engine,
+            tiers: &self.account_tiers,
+            links: &self.sub_links,
+            modes: &self.margin_modes,
+            funding: self.funding_ledger(),
+            oracle_price,
+            filter,
+            next_idx: start_idx as usize,
         }
-        
-        // Size bounds
-        if exec_size == 0 {
-            return Ok(()); // No fill is valid
+    }
+    
+    /// Fill `out` with one page of matching accounts
+    ///
+    /// Returns the number of views written and the cursor to pass as
+    /// `start_idx` for the next page (None when iteration is complete).
+    pub fn accounts_page(
+        &self,
+        start_idx: u16,
+        oracle_price: u64,
+        filter: AccountFilter,
+        out: &mut [AccountView],
+    ) -> (usize, Option<u16>) {
+        let mut iter = self.accounts(start_idx, oracle_price, filter);
+        let mut len = 0;
+        while len < out.len() {
+            match iter.next() {
+                Some(view) => {
+                    out[len] = view;
+                    len += 1;
+                }
+                None => return (len, None),
+            }
         }
-        if exec_size == i128::MIN {
-            return Err(RiskError::InvalidMatchingEngine);
+        let next = (iter.next_idx < MAX_ACCOUNTS).then_some(iter.next_idx as u16);
+        (len, next)
+    }
+    
+    /// Move up to `max_moves` user accounts from the highest occupied slots
+    /// into the lowest free ones (see `RiskEngine::compact_accounts`)
+    ///
+    /// Everything kept per slot follows the account: tier, margin mode,
+    /// sub-account links, activity and sequence stamps, loan, queued
+    /// withdrawals, batch order, client order log, referral and keeper
+    /// claims, backstop registration and the treasury. Commitments of a
+    /// moved account are dropped, since they bind its old index. Returns
+    /// the number of moves.
+    pub fn compact_accounts(&mut self, max_moves: u16) -> u16 {
+        let mut moved_from = [u16::MAX; MAX_ACCOUNTS];
+        let moves = self
+            .engine
+            .compact_accounts(max_moves, |from, to| moved_from[to as usize] = from);
+        for (to, &from) in moved_from.iter().enumerate() {
+            if from != u16::MAX {
+                self.follow_moved_account(from, to as u16);
+            }
         }
-        if saturating_abs_i128(exec_size) as u128 > MAX_POSITION_ABS {
-            return Err(RiskError::InvalidMatchingEngine);
+        moves
+    }
+    
+    /// Remap per-slot state of the account compaction moved from `from` to
+    /// the free slot `to`
+    ///
+    /// Slot arrays are swapped, so whatever a closed account left in `to`
+    /// lands in the freed slot, where account id checks and forfeits
+    /// discard it as before.
+    fn follow_moved_account(&mut self, from: u16, to: u16) {
+        let account_id = self.engine.accounts[to as usize].account_id;
+        let (f, t) = (from as usize, to as usize);
+        self.account_tiers.swap(f, t);
+        self.margin_modes.swap(f, t);
+        self.sub_links.swap(f, t);
+        self.activity.swap(f, t);
+        self.sequences.swap(f, t);
+        for link in self.sub_links.iter_mut() {
+            if link.parent == from && link.parent_account_id == account_id {
+                link.parent = to;
+            }
         }
-        
-        // Must be same direction as requested
-        if (exec_size > 0) != (requested_size > 0) {
-            return Err(RiskError::InvalidMatchingEngine);
+        if self.treasury == Some((from, account_id)) {
+            self.treasury = Some((to, account_id));
         }
-        
-        // Must be partial fill at most
-        if saturating_abs_i128(exec_size) > saturating_abs_i128(requested_size) {
-            return Err(RiskError::InvalidMatchingEngine);
+        self.lending.move_account(from, to);
+        self.withdrawal_queue.move_account(from, to, account_id);
+        self.batch_book.move_account(from, to, account_id);
+        self.client_orders.move_account(from, to);
+        self.commit_book.clear_account(from);
+        self.referrals.move_account(from, to, account_id);
+        self.keeper_ledger.move_account(from, to);
+        self.backstops.move_account(from, to, account_id);
+        self.liquidation_index.remove(from);
+        self.record_change(from);
+        self.record_change(to);
+    }
+    
+    // ========================================
+    // Sub-Accounts
+    // ========================================
+    
+    /// Open an isolated sub-account under a user account
+    ///
+    /// The sub-account inherits the parent's owner and tier and starts with
+    /// zero capital; fund it with `transfer_to_sub_account`.
+    pub fn open_sub_account(&mut self, parent_idx: u16, sub_id: u8) -> Result<u16> {
+        if !self.engine.is_used(parent_idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let parent = self.engine.accounts[parent_idx as usize];
+        if !parent.is_user() || live_link(&self.engine, &self.sub_links, parent_idx).is_some() {
+            return Err(RiskError::AccountKindMismatch);
         }
         
-        // Check against market params
-        if saturating_abs_i128(exec_size) as u128 > self.market_params.max_position_size {
-            return Err(RiskError::Undercollateralized);
+        let mut count = 0;
+        metering::scan(MAX_ACCOUNTS as u64);
+        for idx in 0..MAX_ACCOUNTS as u16 {
+            if let Some(link) = live_link(&self.engine, &self.sub_links, idx) {
+                if link.parent == parent_idx {
+                    if link.sub_id == sub_id {
+                        return Err(RiskError::Unauthorized);
+                    }
+                    count += 1;
+                }
+            }
+        }
+        if count >= MAX_SUB_ACCOUNTS {
+            return Err(RiskError::Overflow);
         }
         
-        Ok(())
+        // Sub-account creation is fee-free: the parent already paid
+        let fee = self.engine.params.new_account_fee;
+        self.engine.params.new_account_fee = U128::ZERO;
+        let created = self.engine.add_user(0);
+        self.engine.params.new_account_fee = fee;
+        let idx = created?;
+        
+        self.engine.accounts[idx as usize].owner = parent.owner;
+        self.account_tiers[idx as usize] = TierStamp {
+            account_id: self.engine.accounts[idx as usize].account_id,
+            tier: effective_tier(&self.engine, &self.account_tiers, parent_idx),
+        };
+        self.sub_links[idx as usize] = SubAccountLink {
+            parent: parent_idx,
+            parent_account_id: parent.account_id,
+            account_id: self.engine.accounts[idx as usize].account_id,
+            sub_id,
+        };
+        self.record_change(idx);
+        Ok(idx)
     }
     
-    /// Update market parameters from agent
-    pub fn update_market_params<A: OpenClawAgent>(
+    /// Move capital from the parent account into one of its sub-accounts
+    pub fn transfer_to_sub_account(
         &mut self,
-        agent: &A,
+        sub_idx: u16,
+        amount: u128,
+        oracle_price: u64,
     ) -> Result<()> {
-        let context = self.build_context(0); // Oracle price not needed for params
-        let params = agent.get_market_params(&context)?;
+        let link = live_link(&self.engine, &self.sub_links, sub_idx)
+            .ok_or(RiskError::AccountNotFound)?;
+        self.transfer_capital(link.parent, sub_idx, amount, oracle_price)
+    }
+    
+    /// Move capital from a sub-account back to its parent
+    pub fn transfer_from_sub_account(
+        &mut self,
+        sub_idx: u16,
+        amount: u128,
+        oracle_price: u64,
+    ) -> Result<()> {
+        let link = live_link(&self.engine, &self.sub_links, sub_idx)
+            .ok_or(RiskError::AccountNotFound)?;
+        self.transfer_capital(sub_idx, link.parent, amount, oracle_price)
+    }
+    
+    /// Close a flat sub-account, returning its capital to the parent
+    pub fn close_sub_account(
+        &mut self,
+        sub_idx: u16,
+        now_slot: u64,
+        oracle_price: u64,
+    ) -> Result<()> {
+        let link = live_link(&self.engine, &self.sub_links, sub_idx)
+            .ok_or(RiskError::AccountNotFound)?;
+        if !self.engine.accounts[sub_idx as usize].position_size.is_zero() {
+            return Err(RiskError::Undercollateralized);
+        }
+        self.engine.touch_account_full(sub_idx, now_slot, oracle_price)?;
         
-        // Validate parameters
-        self.validate_market_params(&params)?;
+        let capital = self.engine.accounts[sub_idx as usize].capital.get();
+        self.transfer_capital(sub_idx, link.parent, capital, oracle_price)?;
+        self.engine.close_account(sub_idx, now_slot, oracle_price)?;
+        self.keeper_ledger.forfeit_closed(&self.engine);
         
-        // Apply parameters
-        self.market_params = params;
+        self.sub_links[sub_idx as usize] = SubAccountLink::NONE;
+        self.record_change(sub_idx);
+        Ok(())
+    }
+    
+    /// Aggregate a user account with all of its live sub-accounts
+    pub fn user_aggregate(&self, parent_idx: u16, oracle_price: u64) -> Result<UserAggregate> {
+        let parent = self
+            .account_view(parent_idx, oracle_price)
+            .ok_or(RiskError::AccountNotFound)?;
+        if parent.parent_idx.is_some() {
+            return Err(RiskError::AccountKindMismatch);
+        }
         
-        // Update underlying engine params if needed
-        // (some params map to RiskParams, others are Clawcolator-specific)
+        let mut agg = UserAggregate {
+            parent_idx,
+            ..UserAggregate::default()
+        };
+        for view in self
+            .accounts(0, oracle_price, AccountFilter::All)
+            .filter(|v| v.idx == parent_idx || v.parent_idx == Some(parent_idx))
+        {
+            if view.idx != parent_idx {
+                agg.sub_accounts += 1;
+            }
+            agg.total_capital = agg.total_capital.saturating_add(view.capital);
+            agg.total_equity = agg.total_equity.saturating_add(view.equity);
+            agg.net_position = agg.net_position.saturating_add(view.position_size);
+            agg.gross_position = agg
+                .gross_position
+                .saturating_add(saturating_abs_i128(view.position_size) as u128);
+            if view.undercollateralized {
+                agg.undercollateralized += 1;
+            }
+        }
+        Ok(agg)
+    }
+    
+    // ========================================
+    // Margin Modes
+    // ========================================
+    
+    /// Switch a user group between cross and isolated margin
+    ///
+    /// Only top-level user accounts carry a mode, and the switch is only
+    /// allowed while the user and all of its sub-accounts are flat.
+    pub fn set_margin_mode(&mut self, idx: u16, mode: MarginMode, oracle_price: u64) -> Result<()> {
+        let agg = self.user_aggregate(idx, oracle_price)?;
+        if !self.engine.accounts[idx as usize].is_user() {
+            return Err(RiskError::AccountKindMismatch);
+        }
+        if agg.gross_position != 0 {
+            return Err(RiskError::Undercollateralized);
+        }
         
+        self.margin_modes[idx as usize] = MarginModeStamp {
+            account_id: self.engine.accounts[idx as usize].account_id,
+            mode,
+        };
+        self.record_change(idx);
         Ok(())
     }
     
-    /// Validate market parameters
-    fn validate_market_params(&self, params: &MarketParams) -> Result<()> {
-        // Max leverage must be reasonable (e.g., <= 100x = 10000 bps)
-        if params.max_leverage_bps > 10000 {
-            return Err(RiskError::Overflow);
+    /// Margin mode governing an account (sub-accounts follow their parent)
+    pub fn margin_mode(&self, idx: u16) -> MarginMode {
+        group_margin_mode(&self.engine, &self.sub_links, &self.margin_modes, idx)
+    }
+    
+    /// Liquidate an account at oracle price, drawing cross-margin support first
+    ///
+    /// In cross mode a sub-account below maintenance is topped up from its
+    /// parent's free capital before the engine liquidates it.
+    pub fn liquidate(&mut self, idx: u16, now_slot: u64, oracle_price: u64) -> Result<bool> {
+        if !self.engine.is_used(idx as usize) || self.engine.is_liquidation_held(idx as usize) {
+            return Ok(false);
+        }
+        let account = &self.engine.accounts[idx as usize];
+        let mut liquidation = LiquidationEvent {
+            slot: now_slot,
+            idx,
+            account_id: account.account_id,
+            size: account.position_size.get(),
+            remaining: 0,
+            oracle_price,
+        };
+        if !self.engine.is_above_maintenance_margin_mtm(account, oracle_price) {
+            let target_bps = self
+                .engine
+                .params
+                .maintenance_margin_bps
+                .saturating_add(self.engine.params.liquidation_buffer_bps);
+            self.cover_cross_margin(idx, account.position_size.get(), target_bps, oracle_price)?;
         }
         
-        // Max position size must be within bounds
-        if params.max_position_size > MAX_POSITION_ABS {
-            return Err(RiskError::Overflow);
+        // Backstops take what they can before auction, insurance or haircut
+        let account = &self.engine.accounts[idx as usize];
+        let absorbed = !account.position_size.is_zero()
+            && !self.engine.is_above_maintenance_margin_mtm(account, oracle_price)
+            && self.absorb_by_backstops(idx, now_slot, oracle_price) > 0;
+        let account = &self.engine.accounts[idx as usize];
+        if absorbed && account.position_size.is_zero() {
+            self.engine.write_off_loss(idx)?;
+            self.collect_bankruptcies();
+            self.reindex_liquidation(idx, oracle_price);
+            self.record_liquidation(liquidation);
+            return Ok(true);
+        }
+        if self.liquidation_auction_params.is_some()
+            && !account.position_size.is_zero()
+            && self.engine.account_equity_mtm_at_oracle(account, oracle_price) == 0
+        {
+            self.engine.set_liquidation_held(idx as usize, true);
+            return self.open_liquidation_auction(idx, now_slot, oracle_price).map(|()| true);
         }
         
-        // Active capital ratio must be <= 100%
-        if params.active_capital_ratio_bps > 10000 {
+        self.record_change(idx);
+        let liquidated = self.engine.liquidate_at_oracle(idx, now_slot, oracle_price)? || absorbed;
+        self.collect_bankruptcies();
+        self.reindex_liquidation(idx, oracle_price);
+        if liquidated {
+            let account = &self.engine.accounts[idx as usize];
+            if self.engine.is_used(idx as usize) && account.account_id == liquidation.account_id {
+                liquidation.remaining = account.position_size.get();
+            }
+            self.record_liquidation(liquidation);
+        }
+        Ok(liquidated)
+    }
+    
+    fn record_liquidation(&mut self, event: LiquidationEvent) {
+        let stats = &mut self.epoch.liquidations;
+        stats.liquidations = stats.liquidations.saturating_add(1);
+        self.events.push(MarketEvent::Liquidation(event));
+    }
+    
+    /// Bankruptcies recorded so far
+    pub fn bankruptcies(&self) -> &BankruptcyLedger {
+        &self.bankruptcies
+    }
+    
+    fn record_bankruptcy(&mut self, entry: Bankruptcy) {
+        self.bankruptcies.record(entry);
+        self.epoch.bankruptcies.add(&entry);
+    }
+    
+    /// Move the engine's queued write-offs into the bankruptcy ledger
+    ///
+    /// Write-offs that overflowed the engine's queue land as one entry with
+    /// `idx == u16::MAX`.
+    fn collect_bankruptcies(&mut self) {
+        let (ledger, epoch) = (&mut self.bankruptcies, &mut self.epoch.bankruptcies);
+        let overflow = self.engine.take_write_offs(|write_off| {
+            let entry = Bankruptcy::from_write_off(write_off);
+            ledger.record(entry);
+            epoch.add(&entry);
+        });
+        if overflow > 0 {
+            self.record_bankruptcy(Bankruptcy {
+                slot: self.engine.current_slot,
+                idx: u16::MAX,
+                hole: overflow,
+                socialized: overflow,
+                ..Bankruptcy::default()
+            });
+        }
+    }
+    
+    /// Offer backstop LPs liquidated positions at a discount in exchange
+    /// for fee rebates (None ends the role; registrations are kept)
+    pub fn set_backstop_params(&mut self, params: Option<BackstopParams>) -> Result<()> {
+        self.require_ungoverned()?;
+        if let Some(params) = &params {
+            params.validate()?;
+        }
+        self.backstop_params = params;
+        Ok(())
+    }
+    
+    /// Terms for backstop LPs, if the role is offered
+    pub fn backstop_params(&self) -> Option<&BackstopParams> {
+        self.backstop_params.as_ref()
+    }
+    
+    /// Registered backstop LPs
+    pub fn backstops(&self) -> &BackstopRegistry {
+        &self.backstops
+    }
+    
+    /// Register account `idx` to absorb liquidated positions
+    pub fn register_backstop(&mut self, idx: u16) -> Result<()> {
+        if self.backstop_params.is_none() {
+            return Err(RiskError::Unauthorized);
+        }
+        if idx as usize >= MAX_ACCOUNTS || !self.engine.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let account_id = self.engine.accounts[idx as usize].account_id;
+        if self.backstops.get(idx).is_some_and(|lp| lp.account_id == account_id) {
+            return Ok(());
+        }
+        self.backstops.deregister(idx);
+        if !self.backstops.register(idx, account_id) {
             return Err(RiskError::Overflow);
         }
-        
-        // Min margin must be >= maintenance margin
-        if params.min_margin_bps < self.engine.params.maintenance_margin_bps {
-            return Err(RiskError::Undercollateralized);
+        Ok(())
+    }
+    
+    /// Stop absorbing liquidations on account `idx`
+    pub fn deregister_backstop(&mut self, idx: u16) -> Result<()> {
+        match self.backstops.deregister(idx) {
+            true => Ok(()),
+            false => Err(RiskError::AccountNotFound),
+        }
+    }
+    
+    /// Hand `idx`'s position to the live backstops, pro rata to their
+    /// capital, at the backstop discount; returns the size absorbed
+    ///
+    /// A backstop that would fall below initial margin takes nothing.
+    fn absorb_by_backstops(&mut self, idx: u16, now_slot: u64, oracle_price: u64) -> u128 {
+        let Some(params) = self.backstop_params else {
+            return 0;
+        };
+        let mut takers = [(0u16, 0u128); MAX_BACKSTOP_LPS];
+        let mut len = 0;
+        let mut total_capital = 0u128;
+        for lp in self.backstops.iter() {
+            let i = lp.idx as usize;
+            if lp.idx == idx
+                || !self.engine.is_used(i)
+                || self.engine.accounts[i].account_id != lp.account_id
+                || self.engine.is_liquidation_held(i)
+            {
+                continue;
+            }
+            let capital = self.engine.accounts[i].capital.get();
+            if capital > 0 {
+                takers[len] = (lp.idx, capital);
+                len += 1;
+                total_capital = total_capital.saturating_add(capital);
+            }
         }
         
+        let position = self.engine.accounts[idx as usize].position_size.get();
+        let price = offset_bps(oracle_price, params.discount_bps, position < 0, Rounding::Down).max(1);
+        let mut absorbed = 0u128;
+        for (n, &(lp_idx, capital)) in takers[..len].iter().enumerate() {
+            // The last backstop also takes the rounding remainder
+            let share = match n + 1 == len {
+                true => position.unsigned_abs() - absorbed,
+                false => mul_div(position.unsigned_abs(), capital, total_capital, Rounding::Down).unwrap_or(0),
+            };
+            if share == 0 {
+                continue;
+            }
+            let signed = if position > 0 { share as i128 } else { -(share as i128) };
+            let Ok(gain) = self
+                .engine
+                .transfer_position(idx, lp_idx, signed, price, now_slot, oracle_price, true)
+            else {
+                continue;
+            };
+            absorbed += share;
+            self.record_change(lp_idx);
+            self.reindex_liquidation(lp_idx, oracle_price);
+            if let Some(lp) = self.backstops.get_mut(lp_idx) {
+                lp.absorbed = lp.absorbed.saturating_add(share);
+                lp.discount_earned = lp.discount_earned.saturating_add(gain);
+            }
+        }
+        if absorbed > 0 {
+            self.record_change(idx);
+        }
+        absorbed
+    }
+    
+    /// Pay a backstop its rebate on a trading fee out of insurance
+    fn rebate_backstop_fee(&mut self, idx: u16, fee: u128) {
+        let Some(params) = self.backstop_params else {
+            return;
+        };
+        let account_id = self.engine.accounts[idx as usize].account_id;
+        if self.backstops.get(idx).is_none_or(|lp| lp.account_id != account_id) {
+            return;
+        }
+        let insurance = self.engine.insurance_fund.balance.get();
+        let rebate = apply_bps(fee, params.fee_rebate_bps, Rounding::Down).min(insurance);
+        if rebate == 0 {
+            return;
+        }
+        self.engine.insurance_fund.balance = U128::new(insurance - rebate);
+        let capital = self.engine.accounts[idx as usize].capital.get();
+        self.engine.set_capital(idx as usize, capital.saturating_add(rebate));
+        if let Some(lp) = self.backstops.get_mut(idx) {
+            lp.rebates = lp.rebates.saturating_add(rebate);
+        }
+    }
+    
+    /// Auction bankrupt positions instead of closing them at the oracle
+    ///
+    /// None restores instant liquidation; lots already open run to the end
+    /// of their own schedule.
+    pub fn set_liquidation_auctions(&mut self, params: Option<LiquidationAuctionParams>) -> Result<()> {
+        self.require_ungoverned()?;
+        if let Some(params) = &params {
+            params.validate()?;
+        }
+        self.liquidation_auction_params = params;
+        self.engine.set_bankruptcy_auctions(params.is_some());
         Ok(())
     }
     
-    /// Check for anomalies and apply agent's response
-    pub fn check_anomalies<A: OpenClawAgent>(
+    /// Dutch-auction schedule for bankrupt positions, if enabled
+    pub fn liquidation_auction_params(&self) -> Option<&LiquidationAuctionParams> {
+        self.liquidation_auction_params.as_ref()
+    }
+    
+    /// Open liquidation lots and lifetime totals
+    pub fn liquidation_auctions(&self) -> &LiquidationAuctions {
+        &self.liquidation_auctions
+    }
+    
+    /// Take up to `size` of the lot on account `idx` at the current
+    /// auction price
+    ///
+    /// The taker must not be under auction itself and must stay above
+    /// initial margin without counting the discount. Taking the last of the
+    /// lot settles the bankrupt account.
+    pub fn bid_liquidation_auction(
         &mut self,
-        agent: &A,
+        bidder_idx: u16,
+        idx: u16,
+        size: u128,
+        now_slot: u64,
         oracle_price: u64,
-    ) -> Result<()> {
-        let context = self.build_context(oracle_price);
-        let response = agent.detect_anomalies(&context)?;
-        
-        // Apply anomaly actions
-        if response.actions.freeze_market {
-            self.market_frozen = true;
+    ) -> Result<AuctionFill> {
+        if self.shutdown || self.market_frozen {
+            return Err(RiskError::Unauthorized);
         }
-        
-        if response.actions.stop_trading {
-            self.market_frozen = true;
+        let lot = *self.liquidation_auctions.lot(idx).ok_or(RiskError::AccountNotFound)?;
+        if bidder_idx == idx || self.engine.is_liquidation_held(bidder_idx as usize) || lot.ended(now_slot) {
+            return Err(RiskError::Unauthorized);
         }
-        
-        if response.actions.initiate_shutdown {
-            self.shutdown = true;
+        let position = self.engine.accounts[idx as usize].position_size.get();
+        let take = size.min(lot.size.unsigned_abs()).min(position.unsigned_abs());
+        if take == 0 {
+            return Err(RiskError::PositionSizeMismatch);
         }
+        let signed = if lot.size > 0 { take as i128 } else { -(take as i128) };
+        let price = lot.price_at(now_slot, oracle_price);
+        let discount = self
+            .engine
+            .transfer_position(idx, bidder_idx, signed, price, now_slot, oracle_price, true)?;
+        self.record_change(idx);
+        self.record_change(bidder_idx);
+        self.mark_active(bidder_idx);
+        self.reindex_liquidation(bidder_idx, oracle_price);
+        self.liquidation_auctions.totals_mut().taken_by_bidders += take;
         
-        if let Some(new_max_size) = response.actions.reduce_limits {
-            if new_max_size <= MAX_POSITION_ABS {
-                self.market_params.max_position_size = new_max_size;
+        let remaining = lot.size - signed;
+        match self.liquidation_auctions.lot_mut(idx) {
+            Some(lot) if remaining != 0 && take < position.unsigned_abs() => lot.size = remaining,
+            _ => self.settle_liquidation_lot(idx),
+        }
+        Ok(AuctionFill { price, size: signed, discount })
+    }
+    
+    /// Put a held account's position up for auction, or close it at the
+    /// oracle when every lot is taken
+    fn open_liquidation_auction(&mut self, idx: u16, now_slot: u64, oracle_price: u64) -> Result<()> {
+        let params = self.liquidation_auction_params.unwrap_or_default();
+        self.engine.touch_account(idx)?;
+        self.engine.settle_mark_to_oracle(idx, oracle_price)?;
+        self.engine.pay_loss_from_capital(idx);
+        let account = &self.engine.accounts[idx as usize];
+        let lot = LiquidationLot {
+            idx,
+            account_id: account.account_id,
+            size: account.position_size.get(),
+            start_slot: now_slot,
+            params,
+        };
+        self.record_change(idx);
+        self.liquidation_index.remove(idx);
+        let stats = &mut self.epoch.liquidations;
+        stats.liquidations = stats.liquidations.saturating_add(1);
+        if !self.liquidation_auctions.open(lot) {
+            self.engine.set_liquidation_held(idx as usize, false);
+            self.engine.liquidate_at_oracle(idx, now_slot, oracle_price)?;
+        }
+        Ok(())
+    }
+    
+    /// Crank step: open lots for accounts the engine held as bankrupt and
+    /// hand the rest of ended lots to the LP at their final price
+    fn run_liquidation_auctions(&mut self, now_slot: u64, oracle_price: u64) {
+        metering::scan(self.engine.liquidation_held.len() as u64);
+        for word in 0..self.engine.liquidation_held.len() {
+            let mut bits = self.engine.liquidation_held[word];
+            while bits != 0 {
+                let idx = (word * 64 + bits.trailing_zeros() as usize) as u16;
+                bits &= bits - 1;
+                if self.liquidation_auctions.lot(idx).is_none()
+                    && self.open_liquidation_auction(idx, now_slot, oracle_price).is_err()
+                {
+                    self.engine.set_liquidation_held(idx as usize, false);
+                }
             }
         }
         
-        Ok(())
+        let mut ended = [0u16; MAX_LIQUIDATION_AUCTIONS];
+        let mut len = 0;
+        for lot in self.liquidation_auctions.iter() {
+            let live = self.engine.is_used(lot.idx as usize)
+                && self.engine.accounts[lot.idx as usize].account_id == lot.account_id;
+            if lot.ended(now_slot) || !live {
+                ended[len] = lot.idx;
+                len += 1;
+            }
+        }
+        for &idx in &ended[..len] {
+            self.end_liquidation_auction(idx, now_slot, oracle_price);
+        }
     }
     
-    /// Check if agent wants to shutdown
-    pub fn check_shutdown<A: OpenClawAgent>(
+    /// Leave what no bidder took to the LP, then settle the lot
+    fn end_liquidation_auction(&mut self, idx: u16, now_slot: u64, oracle_price: u64) {
+        let Some(lot) = self.liquidation_auctions.lot(idx).copied() else {
+            return;
+        };
+        let live = self.engine.is_used(idx as usize)
+            && self.engine.accounts[idx as usize].account_id == lot.account_id;
+        let position = match live {
+            true => self.engine.accounts[idx as usize].position_size.get(),
+            false => 0,
+        };
+        if position != 0 {
+            let lp = AGENT_LP_IDX;
+            let price = lot.price_at(now_slot, oracle_price);
+            let to_lp = idx != lp
+                && self.engine.is_used(lp as usize)
+                && self.engine.accounts[lp as usize].is_lp()
+                && self
+                    .engine
+                    .transfer_position(idx, lp, position, price, now_slot, oracle_price, false)
+                    .is_ok();
+            if to_lp {
+                self.liquidation_auctions.totals_mut().taken_by_lp += position.unsigned_abs();
+                self.record_change(lp);
+                self.reindex_liquidation(lp, oracle_price);
+            } else {
+                let _ = self.engine.settle_position_at_price(idx, now_slot, oracle_price);
+            }
+        }
+        self.settle_liquidation_lot(idx);
+    }
+    
+    /// Release a lot's account, write off the loss its capital cannot pay
+    /// and cover that shortfall from insurance (net of keeper claims)
+    fn settle_liquidation_lot(&mut self, idx: u16) {
+        self.liquidation_auctions.close(idx);
+        self.engine.set_liquidation_held(idx as usize, false);
+        self.collect_bankruptcies();
+        let shortfall = self.engine.write_off_loss(idx).unwrap_or(0);
+        // This lot's write-off, recorded below with its cover
+        self.engine.take_write_offs(|_| {});
+        let insurance = self.engine.insurance_fund.balance.get();
+        let covered = shortfall.min(insurance.saturating_sub(self.insurance_claims()));
+        self.engine.insurance_fund.balance = U128::new(insurance - covered);
+        let totals = self.liquidation_auctions.totals_mut();
+        totals.insurance_covered += covered;
+        totals.uncovered += shortfall - covered;
+        if shortfall > 0 {
+            self.record_bankruptcy(Bankruptcy {
+                slot: self.engine.current_slot,
+                idx,
+                account_id: self.engine.accounts[idx as usize].account_id,
+                cause: BankruptcyCause::Auction,
+                hole: shortfall,
+                insurance: covered,
+                socialized: shortfall - covered,
+            });
+        }
+        self.record_change(idx);
+        self.liquidation_index.remove(idx);
+    }
+    
+    /// Top-up a cross-margin sub-account needs so the post-trade position
+    /// meets both the engine's initial margin and the tier leverage limit
+    fn cross_margin_top_up_for_trade(
+        &self,
+        idx: u16,
+        exec_size: i128,
+        oracle_price: u64,
+    ) -> Result<Option<CrossMarginTopUp>> {
+        if !self.engine.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let target_bps = self.cross_margin_trade_bps(idx);
+        let new_pos = self.engine.accounts[idx as usize]
+            .position_size
+            .get()
+            .saturating_add(exec_size);
+        Ok(self.cross_margin_top_up(idx, new_pos, target_bps, oracle_price))
+    }
+    
+    /// Margin a cross-margin sub-account's trades are topped up to: the
+    /// engine's initial margin or the tier leverage limit, whichever is
+    /// stricter, plus the trading fee (charged from capital)
+    fn cross_margin_trade_bps(&self, idx: u16) -> u64 {
+        let tier = effective_tier(&self.engine, &self.account_tiers, idx);
+        let leverage_bps = self.market_params.tier_limits[tier.index()].max_leverage_bps;
+        let tier_margin_bps = 1_000_000u64.checked_div(leverage_bps).unwrap_or(10_000);
+        self.engine
+            .params
+            .initial_margin_bps
+            .max(tier_margin_bps)
+            .saturating_add(self.engine.params.trading_fee_bps)
+    }
+    
+    /// Move parent free capital into a cross-margin sub-account until
+    /// `position` is covered at `target_bps` (no-op for isolated accounts)
+    fn cover_cross_margin(
         &mut self,
-        agent: &A,
+        idx: u16,
+        position: i128,
+        target_bps: u64,
         oracle_price: u64,
     ) -> Result<()> {
-        let context = self.build_context(oracle_price);
-        let should_shutdown = agent.should_shutdown(&context)?;
+        match self.cross_margin_top_up(idx, position, target_bps, oracle_price) {
+            Some(top_up) => self.transfer_capital(top_up.parent, idx, top_up.amount, oracle_price),
+            None => Ok(()),
+        }
+    }
+    
+    /// Parent free capital `cover_cross_margin` would move (None = isolated
+    /// account, already covered or nothing free)
+    fn cross_margin_top_up(
+        &self,
+        idx: u16,
+        position: i128,
+        target_bps: u64,
+        oracle_price: u64,
+    ) -> Option<CrossMarginTopUp> {
+        let (parent, free) = self.cross_margin_free(idx, oracle_price)?;
+        let required = margin_required(position, oracle_price, target_bps).saturating_add(1);
+        let equity = self
+            .engine
+            .account_equity_mtm_at_oracle(&self.engine.accounts[idx as usize], oracle_price);
+        let amount = required.saturating_sub(equity).min(free);
+        if amount == 0 {
+            return None;
+        }
+        Some(CrossMarginTopUp { parent, amount })
+    }
+    
+    /// Parent of a cross-margin sub-account and the capital it can spare
+    /// above its own initial margin (None = isolated account)
+    fn cross_margin_free(&self, idx: u16, oracle_price: u64) -> Option<(u16, u128)> {
+        let link = match live_link(&self.engine, &self.sub_links, idx) {
+            Some(link) if self.margin_mode(idx) == MarginMode::Cross => link,
+            _ => return None,
+        };
+        let parent = &self.engine.accounts[link.parent as usize];
+        let parent_required = margin_required(
+            parent.position_size.get(),
+            oracle_price,
+            self.engine.params.initial_margin_bps,
+        );
+        let free = self
+            .engine
+            .account_equity_mtm_at_oracle(parent, oracle_price)
+            .saturating_sub(parent_required)
+            .min(parent.capital.get());
+        Some((link.parent, free))
+    }
+    
+    /// Give a trade's top-up back to the parent after the fill failed
+    fn return_cross_margin(&mut self, top_up: CrossMarginTopUp, idx: u16) {
+        let capital = self.engine.accounts[idx as usize].capital.get();
+        let amount = top_up.amount.min(capital);
+        self.engine.set_capital(idx as usize, capital - amount);
+        let parent_capital = self.engine.accounts[top_up.parent as usize].capital.get();
+        self.engine.set_capital(top_up.parent as usize, parent_capital.saturating_add(amount));
+    }
+    
+    /// Move capital between two accounts, keeping the source above initial margin
+    fn transfer_capital(&mut self, from: u16, to: u16, amount: u128, oracle_price: u64) -> Result<()> {
+        if oracle_price == 0 || oracle_price > MAX_ORACLE_PRICE {
+            return Err(RiskError::Overflow);
+        }
+        let from_capital = self.engine.accounts[from as usize].capital.get();
+        if from_capital < amount {
+            return Err(RiskError::InsufficientBalance);
+        }
         
-        if should_shutdown {
-            self.shutdown = true;
+        self.engine.set_capital(from as usize, from_capital - amount);
+        let source = &self.engine.accounts[from as usize];
+        if !source.position_size.is_zero()
+            && !self.engine.is_above_margin_bps_mtm(
+                source,
+                oracle_price,
+                self.engine.params.initial_margin_bps,
+            )
+        {
+            self.engine.set_capital(from as usize, from_capital);
+            return Err(RiskError::Undercollateralized);
         }
         
+        let to_capital = self.engine.accounts[to as usize].capital.get();
+        self.engine.set_capital(to as usize, to_capital.saturating_add(amount));
+        self.record_change(from);
+        self.record_change(to);
+        self.mark_active(from);
+        self.mark_active(to);
         Ok(())
     }
     
@@ -598,16 +6916,78 @@ impl ClawcolatorEngine {
     }
     
     /// Get mutable underlying risk engine (use with caution)
+    ///
+    /// Raw mutations are not tracked, so the next StateDelta is marked
+    /// `resync_required` and the next crank rebuilds the liquidation index.
     pub fn risk_engine_mut(&mut self) -> &mut RiskEngine {
+        self.delta.resync_required = true;
+        self.liquidation_index.invalidate();
         &mut self.engine
     }
+    
+    /// Note a change to an account for the next StateDelta and the
+    /// liquidation index (refiled on the next crank)
+    fn record_change(&mut self, idx: u16) {
+        self.delta.record(idx);
+        self.liquidation_index.mark_stale(idx);
+    }
+    
+    /// Take the state diff accumulated since the previous call
+    ///
+    /// Resets the baseline to the current state.
+    pub fn take_state_delta(&mut self) -> StateDelta {
+        let vault = self.engine.vault.get();
+        let insurance = self.engine.insurance_fund.balance.get();
+        
+        let mut accounts = [AccountDelta::default(); MAX_DELTA_ACCOUNTS];
+        for (slot, &idx) in accounts
+            .iter_mut()
+            .zip(self.delta.changed[..self.delta.changed_len].iter())
+        {
+            let account = &self.engine.accounts[idx as usize];
+            *slot = AccountDelta {
+                idx,
+                in_use: self.engine.is_used(idx as usize),
+                capital: account.capital.get(),
+                pnl: account.pnl.get(),
+                position_size: account.position_size.get(),
+                entry_price: account.entry_price,
+            };
+        }
+        
+        let delta = StateDelta {
+            slot: self.engine.current_slot,
+            vault_delta: signed_diff_u128(vault, self.delta.base_vault),
+            insurance_delta: signed_diff_u128(insurance, self.delta.base_insurance),
+            accounts,
+            accounts_len: self.delta.changed_len,
+            market_params: (self.market_params != self.delta.base_market_params)
+                .then_some(self.market_params),
+            shutdown: (self.shutdown != self.delta.base_shutdown).then_some(self.shutdown),
+            market_frozen: (self.market_frozen != self.delta.base_frozen)
+                .then_some(self.market_frozen),
+            resync_required: self.delta.resync_required,
+        };
+        
+        self.delta = DeltaTracker {
+            base_vault: vault,
+            base_insurance: insurance,
+            base_market_params: self.market_params,
+            base_shutdown: self.shutdown,
+            base_frozen: self.market_frozen,
+            ..DeltaTracker::new()
+        };
+        
+        delta
+    }
 }
 
 // ============================================================================
 // Agent Matcher (adapter for existing MatchingEngine trait)
 // ============================================================================
 
-/// Adapter that makes agent decisions compatible with MatchingEngine trait
+/// Adapter that makes agent decisions (and pre-matched classic fills)
+/// compatible with MatchingEngine trait
 struct AgentMatcher {
     price: u64,
     size: i128,
@@ -0,0 +1,153 @@
+//! Borrowed account accessors
+//!
+//! `AccountView` is a ~200-byte snapshot that computes every derived field
+//! (equity, margin ratio, liquidation price, vesting) up front. On BPF,
+//! with 4KB stack frames and a compute budget, a handler that needs two of
+//! those fields pays for all of them and for the copy. `AccountRef` is a
+//! pointer and an index into the engine's state: raw fields are read in
+//! place and each derived value is computed only when asked for.
+
+use super::{effective_tier, live_link, margin, AccountTier, AccountView, ClawcolatorEngine, MarginMode};
+use crate::{Account, AccountKind, RiskEngine, MAX_ACCOUNTS};
+
+/// Borrowed view of one live account
+#[derive(Clone, Copy)]
+pub struct AccountRef<'a> {
+    engine: &'a ClawcolatorEngine,
+    idx: u16,
+}
+
+impl<'a> AccountRef<'a> {
+    pub(super) fn new(engine: &'a ClawcolatorEngine, idx: u16) -> Option<Self> {
+        engine.engine.is_used(idx as usize).then_some(Self { engine, idx })
+    }
+
+    /// Account index
+    pub fn idx(&self) -> u16 {
+        self.idx
+    }
+
+    /// Underlying engine account
+    pub fn account(&self) -> &'a Account {
+        &self.engine.engine.accounts[self.idx as usize]
+    }
+
+    pub fn kind(&self) -> AccountKind {
+        self.account().kind
+    }
+
+    pub fn capital(&self) -> u128 {
+        self.account().capital.get()
+    }
+
+    /// Realized PnL
+    pub fn pnl(&self) -> i128 {
+        self.account().pnl.get()
+    }
+
+    /// Position size (+ long, - short)
+    pub fn position_size(&self) -> i128 {
+        self.account().position_size.get()
+    }
+
+    /// Last settlement price
+    pub fn entry_price(&self) -> u64 {
+        self.account().entry_price
+    }
+
+    /// Mark-to-market of the position since the last settlement price
+    pub fn unrealized_pnl(&self, oracle_price: u64) -> i128 {
+        RiskEngine::mark_pnl_for_position(self.position_size(), self.entry_price(), oracle_price)
+            .unwrap_or(0)
+    }
+
+    /// Mark-to-market equity
+    pub fn equity(&self, oracle_price: u64) -> u128 {
+        self.engine.engine.account_equity_mtm_at_oracle(self.account(), oracle_price)
+    }
+
+    /// Equity / position notional in basis points (u64::MAX when flat)
+    pub fn margin_ratio_bps(&self, oracle_price: u64) -> u64 {
+        margin::margin_ratio_bps(self.equity(oracle_price), self.position_size(), oracle_price)
+    }
+
+    /// Whether the account is below maintenance margin
+    pub fn undercollateralized(&self, oracle_price: u64) -> bool {
+        self.position_size() != 0
+            && !self.engine.engine.is_above_maintenance_margin_mtm(self.account(), oracle_price)
+    }
+
+    /// Oracle price at which the account falls below maintenance margin
+    pub fn liquidation_price(&self, oracle_price: u64) -> Option<u64> {
+        margin::liquidation_price(
+            self.equity(oracle_price),
+            self.position_size(),
+            oracle_price,
+            self.engine.engine.params.maintenance_margin_bps,
+        )
+    }
+
+    /// Positive realized PnL past warmup, before the haircut
+    pub fn withdrawable_pnl(&self) -> u128 {
+        self.engine.engine.withdrawable_pnl(self.account())
+    }
+
+    pub fn tier(&self) -> AccountTier {
+        effective_tier(&self.engine.engine, &self.engine.account_tiers, self.idx)
+    }
+
+    /// Parent account and sub-account id, if this is a live sub-account
+    pub fn parent(&self) -> Option<(u16, u8)> {
+        live_link(&self.engine.engine, &self.engine.sub_links, self.idx).map(|link| (link.parent, link.sub_id))
+    }
+
+    /// Margin mode of the account's user group
+    pub fn margin_mode(&self) -> MarginMode {
+        self.engine.margin_mode(self.idx)
+    }
+
+    /// Cumulative funding paid (negative = received)
+    pub fn funding_paid(&self) -> i128 {
+        self.engine.funding_ledger().paid(self.idx)
+    }
+
+    /// Full snapshot with every derived field
+    pub fn to_view(&self, oracle_price: u64) -> AccountView {
+        AccountView::from_engine(&self.engine.engine, self.idx, oracle_price, self.tier())
+            .with_link(&self.engine.engine, &self.engine.sub_links, &self.engine.margin_modes)
+            .with_funding(self.engine.funding_ledger())
+    }
+}
+
+impl core::fmt::Debug for AccountRef<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AccountRef").field("idx", &self.idx).finish()
+    }
+}
+
+/// Iterator over live accounts as `AccountRef`s, in index order
+pub struct AccountRefs<'a> {
+    engine: &'a ClawcolatorEngine,
+    next_idx: usize,
+}
+
+impl<'a> AccountRefs<'a> {
+    pub(super) fn new(engine: &'a ClawcolatorEngine) -> Self {
+        Self { engine, next_idx: 0 }
+    }
+}
+
+impl<'a> Iterator for AccountRefs<'a> {
+    type Item = AccountRef<'a>;
+
+    fn next(&mut self) -> Option<AccountRef<'a>> {
+        while self.next_idx < MAX_ACCOUNTS {
+            let idx = self.next_idx as u16;
+            self.next_idx += 1;
+            if let Some(account) = AccountRef::new(self.engine, idx) {
+                return Some(account);
+            }
+        }
+        None
+    }
+}
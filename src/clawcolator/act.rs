@@ -0,0 +1,382 @@
+//! Agent conformance kit
+//!
+//! `certify` runs an `OpenClawAgent` through a fixed battery of synthetic
+//! contexts derived from an engine's own state and reports, per property,
+//! how many probes it ran and which one failed first:
+//!
+//! - decision bounds: accepted fills and quotes have a valid price, never
+//!   flip or overfill the request, and stay within the agent's own spread
+//!   plus the engine's impact model of the oracle
+//! - params consistency: published params pass the engine's validation,
+//!   and no leverage cap (global or per tier) allows positions below the
+//!   agent's own minimum margin
+//! - anomaly monotonicity: along ladders of worsening oracle divergence,
+//!   confidence, age and liquidity, severity never drops and actions are
+//!   never withdrawn or loosened
+//! - oversized trades: no fill or quote exceeds the agent's published
+//!   `max_position_size`, however large the request
+//!
+//! Errors follow the engine's semantics: a failed `decide_trade` is a
+//! rejection, a failed `get_market_params` leaves the engine's current
+//! params in force, and a failed `detect_anomalies` aborts the check (a
+//! violation, since nothing can escalate). Third-party agent authors run
+//! it against a freshly configured engine in their own test suite.
+
+use super::impact::offset_price;
+use super::{
+    saturating_abs_i128, AgentContext, AnomalyResponse, ClawcolatorEngine, MarketParams,
+    OpenClawAgent, OraclePrice, TradeDecision, TradeRequest, AGENT_LP_IDX,
+};
+use crate::{RiskError, TradeExecution, MAX_ORACLE_PRICE, MAX_POSITION_ABS};
+
+/// Properties checked by `certify`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConformanceCheck {
+    DecisionBounds = 0,
+    ParamsConsistency = 1,
+    AnomalyMonotonicity = 2,
+    OversizedTrades = 3,
+}
+
+/// Number of `ConformanceCheck` variants
+pub const CONFORMANCE_CHECK_COUNT: usize = 4;
+
+impl ConformanceCheck {
+    pub const ALL: [ConformanceCheck; CONFORMANCE_CHECK_COUNT] = [
+        ConformanceCheck::DecisionBounds,
+        ConformanceCheck::ParamsConsistency,
+        ConformanceCheck::AnomalyMonotonicity,
+        ConformanceCheck::OversizedTrades,
+    ];
+}
+
+/// Why a probe failed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// Zero or out-of-range price, flipped side or fill larger than requested
+    InvalidFill,
+    /// Fill priced further from the oracle than spread plus impact
+    PriceBeyondSpread,
+    /// Fill or quote above the published `max_position_size`
+    Oversized,
+    /// Params the engine would refuse to apply
+    ParamsRejected(RiskError),
+    /// Leverage cap above what the minimum margin supports
+    LeverageExceedsMargin,
+    /// Spread of 100% or more
+    SpreadOutOfRange,
+    /// Severity above 10000 bps
+    SeverityOutOfRange,
+    /// Severity lower than on a milder rung
+    SeverityDecreased,
+    /// Freeze, stop or shutdown dropped on a worse rung
+    ActionWithdrawn,
+    /// Position limit reduction dropped or raised on a worse rung
+    LimitsLoosened,
+    /// `detect_anomalies` failed
+    AgentError(RiskError),
+}
+
+/// A failed probe
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// Name of the scenario or ladder
+    pub scenario: &'static str,
+
+    /// Request size for trade probes, rung value for anomaly ladders,
+    /// 0 for params
+    pub input: i128,
+
+    pub kind: ViolationKind,
+}
+
+/// Outcome of one check
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CheckResult {
+    /// Probes run
+    pub cases: u32,
+
+    /// Probes that failed
+    pub failures: u32,
+
+    pub first_violation: Option<Violation>,
+}
+
+impl CheckResult {
+    pub fn passed(&self) -> bool {
+        self.failures == 0
+    }
+
+    fn record(&mut self, violation: Option<Violation>) {
+        self.cases = self.cases.saturating_add(1);
+        if let Some(violation) = violation {
+            self.failures = self.failures.saturating_add(1);
+            self.first_violation.get_or_insert(violation);
+        }
+    }
+}
+
+/// Result of `certify`, indexed by `ConformanceCheck`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConformanceReport {
+    pub results: [CheckResult; CONFORMANCE_CHECK_COUNT],
+}
+
+impl ConformanceReport {
+    pub fn result(&self, check: ConformanceCheck) -> &CheckResult {
+        &self.results[check as usize]
+    }
+
+    /// Every check passed
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(CheckResult::passed)
+    }
+
+    /// First violation, in `ConformanceCheck` order
+    pub fn first_violation(&self) -> Option<(ConformanceCheck, Violation)> {
+        ConformanceCheck::ALL
+            .iter()
+            .find_map(|&check| self.result(check).first_violation.map(|v| (check, v)))
+    }
+
+    fn result_mut(&mut self, check: ConformanceCheck) -> &mut CheckResult {
+        &mut self.results[check as usize]
+    }
+}
+
+/// Market conditions a trade probe runs under
+struct Scenario {
+    name: &'static str,
+    price: u64,
+    confidence_bps: u64,
+    liquidity: u128,
+    stale: bool,
+    risk_reduction: bool,
+}
+
+const DEEP_LIQUIDITY: u128 = 1_000_000_000_000;
+
+const SCENARIOS: [Scenario; 7] = [
+    Scenario { name: "calm", price: 1_000_000, confidence_bps: 10, liquidity: DEEP_LIQUIDITY, stale: false, risk_reduction: false },
+    Scenario { name: "thin_book", price: 1_000_000, confidence_bps: 10, liquidity: 1_000_000, stale: false, risk_reduction: false },
+    Scenario { name: "wide_confidence", price: 1_000_000, confidence_bps: 500, liquidity: DEEP_LIQUIDITY, stale: false, risk_reduction: false },
+    Scenario { name: "stale_oracle", price: 1_000_000, confidence_bps: 10, liquidity: DEEP_LIQUIDITY, stale: true, risk_reduction: false },
+    Scenario { name: "risk_reduction", price: 1_000_000, confidence_bps: 10, liquidity: DEEP_LIQUIDITY, stale: false, risk_reduction: true },
+    Scenario { name: "max_price", price: MAX_ORACLE_PRICE, confidence_bps: 10, liquidity: DEEP_LIQUIDITY, stale: false, risk_reduction: false },
+    Scenario { name: "min_price", price: 1, confidence_bps: 0, liquidity: DEEP_LIQUIDITY, stale: false, risk_reduction: false },
+];
+
+/// Request sizes probed in every scenario (both sides)
+const REQUEST_SIZES: [i128; 5] = [1, 1_000, 1_000_000, 1_000_000_000, 1_000_000_000_000];
+
+/// One input worsening rung by rung from a calm context
+struct Ladder {
+    name: &'static str,
+    rungs: [u64; 8],
+    apply: fn(&mut AgentContext, u64, &ClawcolatorEngine),
+}
+
+const LADDERS: [Ladder; 4] = [
+    Ladder {
+        name: "oracle_divergence",
+        rungs: [0, 100, 250, 500, 1_000, 2_500, 5_000, 10_000],
+        apply: |context, bps, _| context.oracle_divergence_bps = bps,
+    },
+    Ladder {
+        name: "oracle_confidence",
+        rungs: [0, 100, 250, 500, 1_000, 2_500, 5_000, 10_000],
+        apply: |context, bps, _| {
+            context.oracle_confidence = (context.oracle_price as u128 * bps as u128 / 10_000) as u64
+        },
+    },
+    Ladder {
+        name: "oracle_age",
+        rungs: [0, 1, 5, 25, 100, 500, 2_500, 10_000],
+        apply: |context, slots, engine| {
+            context.current_slot = context.oracle_publish_slot.saturating_add(slots);
+            context.oracle_stale = slots > engine.oracle_bounds().max_staleness_slots;
+        },
+    },
+    Ladder {
+        name: "liquidity_drain",
+        rungs: [0, 100, 250, 500, 1_000, 2_500, 5_000, 10_000],
+        apply: |context, bps, _| {
+            context.lp_liquidity = DEEP_LIQUIDITY * (10_000 - bps as u128) / 10_000;
+        },
+    },
+];
+
+/// Run every conformance check of `agent` against `engine`'s bounds
+///
+/// Contexts start from `engine.build_context` and override the scenario's
+/// market conditions; nothing on the engine changes. An agent that panics
+/// on a probe fails the run outright.
+pub fn certify<A: OpenClawAgent>(engine: &ClawcolatorEngine, agent: &A) -> ConformanceReport {
+    let mut report = ConformanceReport::default();
+    for scenario in SCENARIOS.iter() {
+        let context = scenario_context(engine, scenario);
+        let params = match agent.get_market_params(&context) {
+            Ok(params) => {
+                let violation = params_violation(engine, &params).map(|kind| Violation {
+                    scenario: scenario.name,
+                    input: 0,
+                    kind,
+                });
+                report.result_mut(ConformanceCheck::ParamsConsistency).record(violation);
+                params
+            }
+            Err(_) => *engine.market_params(),
+        };
+        for &size in REQUEST_SIZES.iter().chain(oversized_requests(&params).iter()) {
+            for size in [size, -size] {
+                probe_trade(&mut report, agent, &context, &params, scenario.name, size);
+            }
+        }
+    }
+    if agent.agent_capabilities().anomaly_detection {
+        for ladder in LADDERS.iter() {
+            climb(&mut report, engine, agent, ladder);
+        }
+    }
+    report
+}
+
+fn scenario_context(engine: &ClawcolatorEngine, scenario: &Scenario) -> AgentContext {
+    let slot = engine.risk_engine().current_slot;
+    let confidence = (scenario.price as u128 * scenario.confidence_bps as u128 / 10_000) as u64;
+    let mut context = engine.build_context(OraclePrice::new(scenario.price, confidence, slot));
+    context.oracle_stale = scenario.stale;
+    if scenario.stale {
+        context.oracle_publish_slot = slot.saturating_sub(engine.oracle_bounds().max_staleness_slots + 1);
+    }
+    context.risk_reduction_mode |= scenario.risk_reduction;
+    context.lp_liquidity = scenario.liquidity;
+    context.total_capital = context.total_capital.max(scenario.liquidity);
+    context.vault = context.vault.max(context.total_capital);
+    context
+}
+
+/// Requests just above and far above the published position cap
+fn oversized_requests(params: &MarketParams) -> [i128; 4] {
+    let cap = params.max_position_size.min(MAX_POSITION_ABS) as i128;
+    [cap + 1, cap.saturating_mul(2), MAX_POSITION_ABS as i128 + 1, i128::MAX]
+}
+
+fn params_violation(engine: &ClawcolatorEngine, params: &MarketParams) -> Option<ViolationKind> {
+    if let Err(e) = engine.validate_market_params(params) {
+        return Some(ViolationKind::ParamsRejected(e));
+    }
+    if params.spread_bps >= 10_000 {
+        return Some(ViolationKind::SpreadOutOfRange);
+    }
+    // max_leverage_bps is in hundredths (1000 = 10x), so a position at the
+    // cap holds 1_000_000 / max_leverage_bps bps of margin
+    let supported = |max_leverage_bps: u64| {
+        (max_leverage_bps as u128) * (params.min_margin_bps as u128) <= 1_000_000
+    };
+    let tiers = params.tier_limits.iter().map(|limits| limits.max_leverage_bps);
+    if !core::iter::once(params.max_leverage_bps).chain(tiers).all(supported) {
+        return Some(ViolationKind::LeverageExceedsMargin);
+    }
+    None
+}
+
+fn probe_trade<A: OpenClawAgent>(
+    report: &mut ConformanceReport,
+    agent: &A,
+    context: &AgentContext,
+    params: &MarketParams,
+    scenario: &'static str,
+    size: i128,
+) {
+    let request = TradeRequest {
+        user_idx: AGENT_LP_IDX + 1,
+        size,
+        requested_price: None,
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: None,
+    };
+    // An error is a rejection
+    let (price, fill) = match agent.decide_trade(context, &request) {
+        Ok(TradeDecision::Accept { price, size }) => (price, size),
+        Ok(TradeDecision::RequestQuote { quote_price, max_size }) => (quote_price, max_size),
+        Ok(TradeDecision::Reject { .. }) | Err(_) => (1, 0),
+    };
+    let violation = |kind| Some(Violation { scenario, input: size, kind });
+
+    let bounds = if super::cpi::validate_execution(&TradeExecution { price, size: fill }, size).is_err() {
+        violation(ViolationKind::InvalidFill)
+    } else if !within_spread(context, params, price, fill) {
+        violation(ViolationKind::PriceBeyondSpread)
+    } else {
+        None
+    };
+    report.result_mut(ConformanceCheck::DecisionBounds).record(bounds);
+
+    let oversized = if saturating_abs_i128(fill) as u128 > params.max_position_size {
+        violation(ViolationKind::Oversized)
+    } else {
+        None
+    };
+    report.result_mut(ConformanceCheck::OversizedTrades).record(oversized);
+}
+
+/// Same band as the engine's impact check: spread plus impact from the oracle
+fn within_spread(context: &AgentContext, params: &MarketParams, price: u64, fill: i128) -> bool {
+    if fill == 0 {
+        return true;
+    }
+    let bps = params.spread_bps.saturating_add(context.impact_bps(fill));
+    let limit = offset_price(context.oracle_price, fill, bps);
+    if fill > 0 {
+        price <= limit
+    } else {
+        price >= limit
+    }
+}
+
+fn climb<A: OpenClawAgent>(
+    report: &mut ConformanceReport,
+    engine: &ClawcolatorEngine,
+    agent: &A,
+    ladder: &Ladder,
+) {
+    let base = scenario_context(engine, &SCENARIOS[0]);
+    let mut previous: Option<AnomalyResponse> = None;
+    for &rung in ladder.rungs.iter() {
+        let mut context = base.clone();
+        (ladder.apply)(&mut context, rung, engine);
+        let violation = |kind| Some(Violation { scenario: ladder.name, input: rung as i128, kind });
+        let result = match agent.detect_anomalies(&context) {
+            Err(e) => violation(ViolationKind::AgentError(e)),
+            Ok(response) => {
+                let kind = escalation_violation(previous.as_ref(), &response);
+                previous = Some(response);
+                kind.and_then(violation)
+            }
+        };
+        report.result_mut(ConformanceCheck::AnomalyMonotonicity).record(result);
+    }
+}
+
+/// How `current` de-escalates from the response on the milder rung
+fn escalation_violation(previous: Option<&AnomalyResponse>, current: &AnomalyResponse) -> Option<ViolationKind> {
+    if current.severity_bps > 10_000 {
+        return Some(ViolationKind::SeverityOutOfRange);
+    }
+    let previous = previous?;
+    let (was, now) = (&previous.actions, &current.actions);
+    if current.severity_bps < previous.severity_bps {
+        Some(ViolationKind::SeverityDecreased)
+    } else if was.freeze_market && !now.freeze_market
+        || was.stop_trading && !now.stop_trading
+        || was.initiate_shutdown && !now.initiate_shutdown
+    {
+        Some(ViolationKind::ActionWithdrawn)
+    } else if was.reduce_limits.is_some_and(|limit| now.reduce_limits.is_none_or(|now| now > limit)) {
+        Some(ViolationKind::LimitsLoosened)
+    } else {
+        None
+    }
+}
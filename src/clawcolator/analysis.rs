@@ -0,0 +1,175 @@
+//! Floating-point analytics for off-chain simulation and backtests
+//!
+//! Gated behind the `analysis` feature, which links `std`. Nothing in the
+//! engine depends on this module: it only reads integer state (price
+//! history, epoch reports) and converts it to f64 series and statistics,
+//! so BPF builds without the feature stay integer-only.
+
+use std::vec::Vec;
+
+use super::{ClawcolatorEngine, EpochHistory, PriceHistory};
+
+/// Price scale of engine prices as f64
+const PRICE_SCALE: f64 = 1_000_000.0;
+
+/// Crank prices as f64 (1.0 = one quote unit)
+pub fn prices(history: &PriceHistory) -> Vec<f64> {
+    history.iter().map(|s| s.price as f64 / PRICE_SCALE).collect()
+}
+
+/// LP equity at the end of each finalized epoch
+pub fn lp_equity_curve(history: &EpochHistory) -> Vec<f64> {
+    history.iter().map(|r| r.scorecard.lp_equity_end as f64).collect()
+}
+
+/// Simple returns between consecutive points (non-positive points skipped)
+pub fn returns(series: &[f64]) -> Vec<f64> {
+    series
+        .windows(2)
+        .filter(|w| w[0] > 0.0)
+        .map(|w| w[1] / w[0] - 1.0)
+        .collect()
+}
+
+/// Log returns between consecutive points (non-positive points skipped)
+pub fn log_returns(series: &[f64]) -> Vec<f64> {
+    series
+        .windows(2)
+        .filter(|w| w[0] > 0.0 && w[1] > 0.0)
+        .map(|w| (w[1] / w[0]).ln())
+        .collect()
+}
+
+/// Arithmetic mean (None when empty)
+pub fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// Sample standard deviation (None with fewer than two values)
+pub fn std_dev(values: &[f64]) -> Option<f64> {
+    if values.len() < 2 {
+        return None;
+    }
+    let m = mean(values)?;
+    let var = values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    Some(var.sqrt())
+}
+
+/// Pearson correlation over the common prefix (None if either side is flat)
+pub fn correlation(a: &[f64], b: &[f64]) -> Option<f64> {
+    let n = a.len().min(b.len());
+    if n < 2 {
+        return None;
+    }
+    let (a, b) = (&a[..n], &b[..n]);
+    let (ma, mb) = (mean(a)?, mean(b)?);
+    let mut cov = 0.0;
+    let mut va = 0.0;
+    let mut vb = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        cov += (x - ma) * (y - mb);
+        va += (x - ma).powi(2);
+        vb += (y - mb).powi(2);
+    }
+    if va == 0.0 || vb == 0.0 {
+        return None;
+    }
+    Some(cov / (va * vb).sqrt())
+}
+
+/// Largest peak-to-trough decline of a series
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Drawdown {
+    /// Decline as a fraction of the peak (0.25 = 25%)
+    pub depth: f64,
+
+    /// Index of the peak
+    pub peak_idx: usize,
+
+    /// Index of the trough
+    pub trough_idx: usize,
+}
+
+/// Maximum drawdown of `series` (zero depth when it never declines)
+pub fn max_drawdown(series: &[f64]) -> Drawdown {
+    let mut worst = Drawdown::default();
+    let mut peak_idx = 0;
+    for (i, v) in series.iter().enumerate() {
+        if *v > series[peak_idx] {
+            peak_idx = i;
+        }
+        let peak = series[peak_idx];
+        if peak > 0.0 {
+            let depth = (peak - v) / peak;
+            if depth > worst.depth {
+                worst = Drawdown { depth, peak_idx, trough_idx: i };
+            }
+        }
+    }
+    worst
+}
+
+/// Summary statistics of a level series
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SeriesStats {
+    /// Number of returns
+    pub samples: usize,
+
+    /// Mean simple return
+    pub mean_return: f64,
+
+    /// Standard deviation of simple returns
+    pub volatility: f64,
+
+    /// Maximum drawdown of the levels
+    pub drawdown: Drawdown,
+}
+
+impl SeriesStats {
+    /// Statistics of `series` (levels, not returns)
+    pub fn of(series: &[f64]) -> Self {
+        let r = returns(series);
+        Self {
+            samples: r.len(),
+            mean_return: mean(&r).unwrap_or(0.0),
+            volatility: std_dev(&r).unwrap_or(0.0),
+            drawdown: max_drawdown(series),
+        }
+    }
+}
+
+/// Market and LP analytics from engine history
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EngineAnalytics {
+    /// Crank price statistics
+    pub price: SeriesStats,
+
+    /// LP equity statistics across finalized epochs
+    pub lp_equity: SeriesStats,
+
+    /// Correlation of LP epoch returns with price returns over the same
+    /// number of most recent points (None without enough data)
+    pub lp_price_correlation: Option<f64>,
+}
+
+impl EngineAnalytics {
+    /// Analyse the engine's retained price and epoch history
+    pub fn capture(engine: &ClawcolatorEngine) -> Self {
+        let price_series = prices(engine.price_history());
+        let equity_series = lp_equity_curve(engine.epoch_history());
+        let price_returns = returns(&price_series);
+        let equity_returns = returns(&equity_series);
+        let n = price_returns.len().min(equity_returns.len());
+        Self {
+            price: SeriesStats::of(&price_series),
+            lp_equity: SeriesStats::of(&equity_series),
+            lp_price_correlation: correlation(
+                &price_returns[price_returns.len() - n..],
+                &equity_returns[equity_returns.len() - n..],
+            ),
+        }
+    }
+}
@@ -0,0 +1,332 @@
+//! Anchor-compatible layout for on-chain programs embedding the engine
+//!
+//! Mirrors what `#[account]`, `#[derive(Accounts)]` and `#[instruction]`
+//! would generate, without pulling in `anchor-lang`: the market account's
+//! discriminator and space, the instruction discriminators and their borsh
+//! argument layouts, and one context struct per instruction that checks
+//! the account list the way Anchor's constraints would. A program built
+//! with Anchor can use these constants in `#[account(zero_copy)]` and
+//! `space = ...` attributes and in its IDL; one built without it gets the
+//! same wire format for free.
+//!
+//! Market account: discriminator (8) | `ClawcolatorEngine` (zero-copy)
+
+use crate::{RiskError, Result};
+
+use super::oracle::AccountReader;
+use super::ClawcolatorEngine;
+
+/// Largest account the runtime allows (10 MiB)
+pub const MAX_PERMITTED_DATA_LENGTH: usize = 10 * 1024 * 1024;
+
+/// Longest instruction data of any market instruction
+pub const MAX_INSTRUCTION_LEN: usize = 26;
+
+/// An account type with an Anchor discriminator
+pub trait AnchorAccount {
+    /// `sha256("account:<Name>")[..8]`
+    const DISCRIMINATOR: [u8; 8];
+
+    /// Bytes to allocate, discriminator included
+    const SPACE: usize;
+
+    /// The account's body after checking its discriminator and size
+    fn body(data: &[u8]) -> Result<&[u8]> {
+        if data.len() < Self::SPACE || data[..8] != Self::DISCRIMINATOR {
+            return Err(RiskError::AccountKindMismatch);
+        }
+        Ok(&data[8..Self::SPACE])
+    }
+}
+
+/// The market: one `ClawcolatorEngine`, zero-copy after the discriminator
+pub struct MarketAccount;
+
+impl AnchorAccount for MarketAccount {
+    const DISCRIMINATOR: [u8; 8] = [0xdb, 0xbe, 0xd5, 0x37, 0x00, 0xe3, 0xc6, 0x9a];
+    const SPACE: usize = 8 + core::mem::size_of::<ClawcolatorEngine>();
+}
+
+impl MarketAccount {
+    /// Whether the market fits in one account in this build (see `layout`
+    /// for shrinking the account slab)
+    pub const FITS: bool = Self::SPACE <= MAX_PERMITTED_DATA_LENGTH;
+}
+
+/// Instruction discriminators (`sha256("global:<name>")[..8]`)
+pub const INITIALIZE_MARKET_DISCRIMINATOR: [u8; 8] = [0x23, 0x23, 0xbd, 0xc1, 0x9b, 0x30, 0xaa, 0xcb];
+pub const ADD_USER_DISCRIMINATOR: [u8; 8] = [0x0f, 0xc8, 0x03, 0xa8, 0xb8, 0x29, 0xbd, 0xb0];
+pub const DEPOSIT_DISCRIMINATOR: [u8; 8] = [0xf2, 0x23, 0xc6, 0x89, 0x52, 0xe1, 0xf2, 0xb6];
+pub const WITHDRAW_DISCRIMINATOR: [u8; 8] = [0xb7, 0x12, 0x46, 0x9c, 0x94, 0x6d, 0xa1, 0x22];
+pub const EXECUTE_TRADE_DISCRIMINATOR: [u8; 8] = [0x4d, 0x10, 0xc0, 0x87, 0x0d, 0x00, 0x6a, 0x61];
+pub const CRANK_DISCRIMINATOR: [u8; 8] = [0x00, 0xe8, 0x03, 0xc3, 0x7c, 0x75, 0x69, 0x35];
+
+/// Market instructions and their (borsh) arguments
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarketInstruction {
+    /// Context: `InitializeMarketAccounts`
+    InitializeMarket,
+
+    /// Context: `AddUserAccounts`
+    AddUser { fee_payment: u128 },
+
+    /// Context: `TransferAccounts`
+    Deposit { account_idx: u16, amount: u128 },
+
+    /// Context: `TransferAccounts`
+    Withdraw { account_idx: u16, amount: u128 },
+
+    /// Context: `TradeAccounts`
+    ExecuteTrade { user_idx: u16, size: i128 },
+
+    /// Context: `CrankAccounts`
+    Crank { caller_idx: u16 },
+}
+
+/// Encoded instruction data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstructionData {
+    bytes: [u8; MAX_INSTRUCTION_LEN],
+    len: usize,
+}
+
+impl InstructionData {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl MarketInstruction {
+    pub fn discriminator(&self) -> [u8; 8] {
+        match self {
+            MarketInstruction::InitializeMarket => INITIALIZE_MARKET_DISCRIMINATOR,
+            MarketInstruction::AddUser { .. } => ADD_USER_DISCRIMINATOR,
+            MarketInstruction::Deposit { .. } => DEPOSIT_DISCRIMINATOR,
+            MarketInstruction::Withdraw { .. } => WITHDRAW_DISCRIMINATOR,
+            MarketInstruction::ExecuteTrade { .. } => EXECUTE_TRADE_DISCRIMINATOR,
+            MarketInstruction::Crank { .. } => CRANK_DISCRIMINATOR,
+        }
+    }
+
+    pub fn encode(&self) -> InstructionData {
+        let mut bytes = [0u8; MAX_INSTRUCTION_LEN];
+        bytes[..8].copy_from_slice(&self.discriminator());
+        let len = match *self {
+            MarketInstruction::InitializeMarket => 8,
+            MarketInstruction::AddUser { fee_payment } => {
+                bytes[8..24].copy_from_slice(&fee_payment.to_le_bytes());
+                24
+            }
+            MarketInstruction::Deposit { account_idx, amount }
+            | MarketInstruction::Withdraw { account_idx, amount } => {
+                bytes[8..10].copy_from_slice(&account_idx.to_le_bytes());
+                bytes[10..26].copy_from_slice(&amount.to_le_bytes());
+                26
+            }
+            MarketInstruction::ExecuteTrade { user_idx, size } => {
+                bytes[8..10].copy_from_slice(&user_idx.to_le_bytes());
+                bytes[10..26].copy_from_slice(&size.to_le_bytes());
+                26
+            }
+            MarketInstruction::Crank { caller_idx } => {
+                bytes[8..10].copy_from_slice(&caller_idx.to_le_bytes());
+                10
+            }
+        };
+        InstructionData { bytes, len }
+    }
+
+    /// Parse instruction data; trailing bytes are rejected like borsh does
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let reader = AccountReader::new(data);
+        let (instruction, len) = match reader.bytes::<8>(0)? {
+            INITIALIZE_MARKET_DISCRIMINATOR => (MarketInstruction::InitializeMarket, 8),
+            ADD_USER_DISCRIMINATOR => (
+                MarketInstruction::AddUser { fee_payment: reader.u128(8)? },
+                24,
+            ),
+            DEPOSIT_DISCRIMINATOR => (
+                MarketInstruction::Deposit {
+                    account_idx: reader.u16(8)?,
+                    amount: reader.u128(10)?,
+                },
+                26,
+            ),
+            WITHDRAW_DISCRIMINATOR => (
+                MarketInstruction::Withdraw {
+                    account_idx: reader.u16(8)?,
+                    amount: reader.u128(10)?,
+                },
+                26,
+            ),
+            EXECUTE_TRADE_DISCRIMINATOR => (
+                MarketInstruction::ExecuteTrade {
+                    user_idx: reader.u16(8)?,
+                    size: reader.i128(10)?,
+                },
+                26,
+            ),
+            CRANK_DISCRIMINATOR => (MarketInstruction::Crank { caller_idx: reader.u16(8)? }, 10),
+            _ => return Err(RiskError::Unauthorized),
+        };
+        if data.len() != len {
+            return Err(RiskError::Overflow);
+        }
+        Ok(instruction)
+    }
+}
+
+/// An account passed to an instruction
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccountKey {
+    pub key: [u8; 32],
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+/// What an instruction requires of one of its accounts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountSpec {
+    pub name: &'static str,
+    pub signer: bool,
+    pub writable: bool,
+}
+
+const fn spec(name: &'static str, signer: bool, writable: bool) -> AccountSpec {
+    AccountSpec { name, signer, writable }
+}
+
+/// Keys of `accounts` in order, if they meet `specs`
+///
+/// Extra accounts are allowed (Anchor's `remaining_accounts`); missing ones,
+/// missing signatures and read-only accounts that must be written are not.
+fn keys<const N: usize>(accounts: &[AccountKey], specs: &[AccountSpec; N]) -> Result<[[u8; 32]; N]> {
+    if accounts.len() < N {
+        return Err(RiskError::AccountNotFound);
+    }
+    let mut keys = [[0u8; 32]; N];
+    for ((key, account), spec) in keys.iter_mut().zip(accounts).zip(specs) {
+        if (spec.signer && !account.is_signer) || (spec.writable && !account.is_writable) {
+            return Err(RiskError::Unauthorized);
+        }
+        *key = account.key;
+    }
+    Ok(keys)
+}
+
+/// Accounts of `InitializeMarket`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InitializeMarketAccounts {
+    /// Market account, allocated with `MarketAccount::SPACE`
+    pub market: [u8; 32],
+    /// Pays for the market and becomes its admin
+    pub authority: [u8; 32],
+    pub system_program: [u8; 32],
+}
+
+impl InitializeMarketAccounts {
+    pub const ACCOUNTS: [AccountSpec; 3] = [
+        spec("market", false, true),
+        spec("authority", true, true),
+        spec("system_program", false, false),
+    ];
+
+    pub fn from_accounts(accounts: &[AccountKey]) -> Result<Self> {
+        let [market, authority, system_program] = keys(accounts, &Self::ACCOUNTS)?;
+        // The system program's id is all zeros
+        if system_program != [0; 32] {
+            return Err(RiskError::Unauthorized);
+        }
+        Ok(Self { market, authority, system_program })
+    }
+}
+
+/// Accounts of `AddUser`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddUserAccounts {
+    pub market: [u8; 32],
+    /// Becomes the account's owner
+    pub owner: [u8; 32],
+}
+
+impl AddUserAccounts {
+    pub const ACCOUNTS: [AccountSpec; 2] = [spec("market", false, true), spec("owner", true, false)];
+
+    pub fn from_accounts(accounts: &[AccountKey]) -> Result<Self> {
+        let [market, owner] = keys(accounts, &Self::ACCOUNTS)?;
+        Ok(Self { market, owner })
+    }
+}
+
+/// Accounts of `Deposit` and `Withdraw`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransferAccounts {
+    pub market: [u8; 32],
+    /// Owner of the engine account
+    pub owner: [u8; 32],
+    /// Market's collateral vault
+    pub vault: [u8; 32],
+    /// Owner's collateral token account
+    pub owner_token: [u8; 32],
+    pub token_program: [u8; 32],
+}
+
+impl TransferAccounts {
+    pub const ACCOUNTS: [AccountSpec; 5] = [
+        spec("market", false, true),
+        spec("owner", true, false),
+        spec("vault", false, true),
+        spec("owner_token", false, true),
+        spec("token_program", false, false),
+    ];
+
+    pub fn from_accounts(accounts: &[AccountKey]) -> Result<Self> {
+        let [market, owner, vault, owner_token, token_program] = keys(accounts, &Self::ACCOUNTS)?;
+        Ok(Self { market, owner, vault, owner_token, token_program })
+    }
+}
+
+/// Accounts of `ExecuteTrade`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TradeAccounts {
+    pub market: [u8; 32],
+    /// Owner of the trading account
+    pub owner: [u8; 32],
+    /// Price account (see `oracle`)
+    pub oracle: [u8; 32],
+}
+
+impl TradeAccounts {
+    pub const ACCOUNTS: [AccountSpec; 3] = [
+        spec("market", false, true),
+        spec("owner", true, false),
+        spec("oracle", false, false),
+    ];
+
+    pub fn from_accounts(accounts: &[AccountKey]) -> Result<Self> {
+        let [market, owner, oracle] = keys(accounts, &Self::ACCOUNTS)?;
+        Ok(Self { market, owner, oracle })
+    }
+}
+
+/// Accounts of `Crank`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrankAccounts {
+    pub market: [u8; 32],
+    /// Keeper collecting the crank reward
+    pub keeper: [u8; 32],
+    pub oracle: [u8; 32],
+}
+
+impl CrankAccounts {
+    pub const ACCOUNTS: [AccountSpec; 3] = [
+        spec("market", false, true),
+        spec("keeper", true, false),
+        spec("oracle", false, false),
+    ];
+
+    pub fn from_accounts(accounts: &[AccountKey]) -> Result<Self> {
+        let [market, keeper, oracle] = keys(accounts, &Self::ACCOUNTS)?;
+        Ok(Self { market, keeper, oracle })
+    }
+}
@@ -0,0 +1,183 @@
+//! Periodic batch auctions
+//!
+//! In batch mode trade requests are not priced one by one. They collect
+//! in a book for `batch_slots` slots, then a single call asks the agent for
+//! one uniform clearing price for the whole batch. The price must sit
+//! within `max_deviation_bps` of the oracle; every order then fills at it
+//! (or fails its own margin and limit checks). Nobody in a batch gets a
+//! better or worse price for arriving first, and the agent is consulted
+//! once per batch instead of once per trade.
+
+/// Orders a batch holds (orders of one account are netted into one)
+pub const BATCH_BOOK_LEN: usize = crate::layout::capacity(option_env!("PERCOLATOR_BATCH_BOOK_LEN"), 64);
+
+/// Batch auction timing and price band
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchAuctionParams {
+    /// Slots a batch collects orders before it can be cleared
+    pub batch_slots: u64,
+
+    /// Largest distance of the clearing price from the oracle (bps)
+    pub max_deviation_bps: u64,
+}
+
+impl Default for BatchAuctionParams {
+    fn default() -> Self {
+        Self {
+            batch_slots: 10,
+            max_deviation_bps: 100,
+        }
+    }
+}
+
+impl BatchAuctionParams {
+    /// At least one slot per batch, band below 100%
+    pub fn is_valid(&self) -> bool {
+        self.batch_slots >= 1 && self.max_deviation_bps < 10_000
+    }
+}
+
+/// Net order of one account in the current batch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchOrder {
+    /// User account index
+    pub user_idx: u16,
+
+    /// Id of the account when the order was placed (slots are reused)
+    pub account_id: u64,
+
+    /// Net size (positive = long, negative = short)
+    pub size: i128,
+}
+
+/// What the agent sees when pricing a batch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    /// Slot the batch opened
+    pub opened_slot: u64,
+
+    /// Orders in the batch
+    pub orders: u16,
+
+    /// Total size of buy orders
+    pub buy_size: u128,
+
+    /// Total size of sell orders
+    pub sell_size: u128,
+}
+
+/// Result of clearing a batch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchReport {
+    /// Slot the batch was cleared
+    pub slot: u64,
+
+    /// Uniform clearing price
+    pub price: u64,
+
+    /// Orders filled
+    pub filled: u16,
+
+    /// Orders rejected by their own checks (margin, limits)
+    pub rejected: u16,
+
+    /// Notional filled
+    pub volume: u128,
+}
+
+/// Orders of the batch being collected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchBook {
+    orders: [Option<BatchOrder>; BATCH_BOOK_LEN],
+    opened_slot: Option<u64>,
+}
+
+impl Default for BatchBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchBook {
+    /// Empty book
+    pub const fn new() -> Self {
+        Self {
+            orders: [None; BATCH_BOOK_LEN],
+            opened_slot: None,
+        }
+    }
+
+    /// Orders in the batch
+    pub fn iter(&self) -> impl Iterator<Item = &BatchOrder> {
+        self.orders.iter().flatten()
+    }
+
+    /// Slot of the first order in the batch (None while empty)
+    pub fn opened_slot(&self) -> Option<u64> {
+        self.opened_slot
+    }
+
+    /// Add `size` to the account's net order; false if the book is full
+    pub fn add(&mut self, user_idx: u16, account_id: u64, size: i128, now_slot: u64) -> bool {
+        let existing = self
+            .orders
+            .iter_mut()
+            .flatten()
+            .find(|o| o.user_idx == user_idx && o.account_id == account_id);
+        if let Some(order) = existing {
+            order.size = order.size.saturating_add(size);
+            return true;
+        }
+        let Some(free) = self.orders.iter_mut().find(|o| o.is_none()) else {
+            return false;
+        };
+        *free = Some(BatchOrder { user_idx, account_id, size });
+        self.opened_slot.get_or_insert(now_slot);
+        true
+    }
+
+    /// Remove the account's order; returns its net size
+    pub fn cancel(&mut self, user_idx: u16) -> Option<i128> {
+        let order = self
+            .orders
+            .iter_mut()
+            .find(|o| o.is_some_and(|o| o.user_idx == user_idx))?
+            .take()?;
+        if self.iter().next().is_none() {
+            self.opened_slot = None;
+        }
+        Some(order.size)
+    }
+
+    /// Follow account `account_id` moved by compaction from `from` to `to`
+    pub(crate) fn move_account(&mut self, from: u16, to: u16, account_id: u64) {
+        for order in self.orders.iter_mut().flatten() {
+            if order.user_idx == from && order.account_id == account_id {
+                order.user_idx = to;
+            }
+        }
+    }
+
+    /// Totals the agent prices the batch from
+    pub fn summary(&self) -> BatchSummary {
+        let mut summary = BatchSummary {
+            opened_slot: self.opened_slot.unwrap_or(0),
+            ..BatchSummary::default()
+        };
+        for order in self.iter() {
+            summary.orders = summary.orders.saturating_add(1);
+            if order.size > 0 {
+                summary.buy_size = summary.buy_size.saturating_add(order.size.unsigned_abs());
+            } else {
+                summary.sell_size = summary.sell_size.saturating_add(order.size.unsigned_abs());
+            }
+        }
+        summary
+    }
+
+    /// Empty the book, returning its orders
+    pub fn drain(&mut self) -> [Option<BatchOrder>; BATCH_BOOK_LEN] {
+        self.opened_slot = None;
+        core::mem::replace(&mut self.orders, [None; BATCH_BOOK_LEN])
+    }
+}
@@ -0,0 +1,138 @@
+//! Backstop liquidity providers
+//!
+//! Accounts can register to absorb liquidated positions. When an account is
+//! liquidated, its whole position is first split across the registered
+//! backstops pro rata to their capital and handed over at `discount_bps`
+//! off the oracle; a backstop that would end up below initial margin is
+//! skipped. Only what they cannot take goes on through the usual pipeline
+//! (auction or close at the oracle, then insurance and the haircut). In
+//! exchange, backstops get `fee_rebate_bps` of their trading fees back from
+//! the insurance fund.
+
+use crate::{Result, RiskError};
+
+/// Backstops that can be registered at once
+pub const MAX_BACKSTOP_LPS: usize = 16;
+
+/// Terms offered to backstop LPs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackstopParams {
+    /// Discount to the oracle at which liquidated positions are handed over (bps)
+    pub discount_bps: u64,
+
+    /// Share of a backstop's trading fees rebated to it (bps)
+    pub fee_rebate_bps: u64,
+}
+
+impl Default for BackstopParams {
+    fn default() -> Self {
+        Self {
+            discount_bps: 200,
+            fee_rebate_bps: 5_000,
+        }
+    }
+}
+
+impl BackstopParams {
+    pub fn validate(&self) -> Result<()> {
+        if self.discount_bps >= 10_000 || self.fee_rebate_bps > 10_000 {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+}
+
+/// One registered backstop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BackstopLp {
+    /// Backstop account
+    pub idx: u16,
+
+    /// Its account id (guards against slot reuse)
+    pub account_id: u64,
+
+    /// Absolute size absorbed from liquidations
+    pub absorbed: u128,
+
+    /// PnL gained from the discount on absorbed positions
+    pub discount_earned: i128,
+
+    /// Trading fees rebated
+    pub rebates: u128,
+}
+
+/// Registered backstops
+pub struct BackstopRegistry {
+    lps: [Option<BackstopLp>; MAX_BACKSTOP_LPS],
+}
+
+impl Default for BackstopRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackstopRegistry {
+    pub const fn new() -> Self {
+        Self {
+            lps: [None; MAX_BACKSTOP_LPS],
+        }
+    }
+
+    /// Backstop registered on account `idx`
+    pub fn get(&self, idx: u16) -> Option<&BackstopLp> {
+        self.lps.iter().flatten().find(|lp| lp.idx == idx)
+    }
+
+    /// Registered backstops
+    pub fn iter(&self) -> impl Iterator<Item = &BackstopLp> {
+        self.lps.iter().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lps.iter().all(Option::is_none)
+    }
+
+    pub(crate) fn get_mut(&mut self, idx: u16) -> Option<&mut BackstopLp> {
+        self.lps.iter_mut().flatten().find(|lp| lp.idx == idx)
+    }
+
+    /// Register `idx`; false when every slot is taken
+    pub(crate) fn register(&mut self, idx: u16, account_id: u64) -> bool {
+        let Some(free) = self.lps.iter_mut().find(|lp| lp.is_none()) else {
+            return false;
+        };
+        *free = Some(BackstopLp {
+            idx,
+            account_id,
+            absorbed: 0,
+            discount_earned: 0,
+            rebates: 0,
+        });
+        true
+    }
+
+    /// Follow backstop `account_id` moved by compaction from `from` to `to`
+    pub(crate) fn move_account(&mut self, from: u16, to: u16, account_id: u64) {
+        for lp in self.lps.iter_mut().flatten() {
+            if lp.idx == from && lp.account_id == account_id {
+                lp.idx = to;
+            }
+        }
+    }
+
+    /// Remove `idx`; false if it was not registered
+    pub(crate) fn deregister(&mut self, idx: u16) -> bool {
+        for slot in self.lps.iter_mut() {
+            if slot.is_some_and(|lp| lp.idx == idx) {
+                *slot = None;
+                return true;
+            }
+        }
+        false
+    }
+}
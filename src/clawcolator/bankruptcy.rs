@@ -0,0 +1,169 @@
+//! Bankruptcy accounting: every hole an account leaves once its capital is
+//! gone, and who covered it
+//!
+//! The engine writes off negative PnL it cannot collect (spec §6.1) and
+//! queues each write-off (`RiskEngine::take_write_offs`); the Clawcolator
+//! layer collects them after cranks, trades and liquidations and adds the
+//! holes it settles itself (auctioned positions, defaulted loans). Each
+//! entry splits the hole into what the insurance fund paid and what was
+//! socialized. In this engine socialization and ADL are one mechanism: an
+//! uncovered hole lowers the vault residual, and the haircut ratio scales
+//! every positive PnL down until the books balance again (defaulted loans
+//! are borne by the lending LP instead).
+
+use crate::WriteOff;
+
+/// Entries retained by the ledger
+pub const BANKRUPTCY_LEDGER_LEN: usize = 64;
+
+/// What left the hole
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BankruptcyCause {
+    /// Engine write-off on liquidation, force-close, settlement or GC
+    #[default]
+    WriteOff,
+
+    /// Settlement of a liquidation auction lot
+    Auction,
+
+    /// Loan the borrower's capital could not repay
+    LoanDefault,
+}
+
+/// One bankruptcy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bankruptcy {
+    /// Slot the hole was recorded
+    pub slot: u64,
+
+    /// Account index (u16::MAX for write-offs that overflowed the engine's
+    /// queue, summed into one entry)
+    pub idx: u16,
+
+    /// Account id (the slot may since have been reused)
+    pub account_id: u64,
+
+    pub cause: BankruptcyCause,
+
+    /// Loss beyond the account's capital
+    pub hole: u128,
+
+    /// Part paid by the insurance fund
+    pub insurance: u128,
+
+    /// Part socialized (haircut on positive PnL, or the LP for loans)
+    pub socialized: u128,
+}
+
+impl Bankruptcy {
+    /// Entry for an engine write-off (socialized in full)
+    pub fn from_write_off(write_off: WriteOff) -> Self {
+        Self {
+            slot: write_off.slot,
+            idx: write_off.idx,
+            account_id: write_off.account_id,
+            cause: BankruptcyCause::WriteOff,
+            hole: write_off.amount,
+            insurance: 0,
+            socialized: write_off.amount,
+        }
+    }
+}
+
+/// Sums over bankruptcies
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BankruptcyTotals {
+    pub count: u64,
+    pub hole: u128,
+    pub insurance: u128,
+    pub socialized: u128,
+}
+
+impl BankruptcyTotals {
+    pub fn add(&mut self, entry: &Bankruptcy) {
+        self.count = self.count.saturating_add(1);
+        self.hole = self.hole.saturating_add(entry.hole);
+        self.insurance = self.insurance.saturating_add(entry.insurance);
+        self.socialized = self.socialized.saturating_add(entry.socialized);
+    }
+}
+
+/// Ring of recent bankruptcies plus lifetime totals
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BankruptcyLedger {
+    entries: [Bankruptcy; BANKRUPTCY_LEDGER_LEN],
+    head: usize,
+    len: usize,
+    totals: BankruptcyTotals,
+}
+
+impl Default for BankruptcyLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BankruptcyLedger {
+    pub const fn new() -> Self {
+        Self {
+            entries: [Bankruptcy {
+                slot: 0,
+                idx: 0,
+                account_id: 0,
+                cause: BankruptcyCause::WriteOff,
+                hole: 0,
+                insurance: 0,
+                socialized: 0,
+            }; BANKRUPTCY_LEDGER_LEN],
+            head: 0,
+            len: 0,
+            totals: BankruptcyTotals {
+                count: 0,
+                hole: 0,
+                insurance: 0,
+                socialized: 0,
+            },
+        }
+    }
+
+    /// Append an entry, evicting the oldest when full (totals keep it)
+    pub fn record(&mut self, entry: Bankruptcy) {
+        self.totals.add(&entry);
+        self.entries[self.head] = entry;
+        self.head = (self.head + 1) % BANKRUPTCY_LEDGER_LEN;
+        self.len = (self.len + 1).min(BANKRUPTCY_LEDGER_LEN);
+    }
+
+    /// Retained entries
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Entry `i` in chronological order (0 = oldest retained)
+    pub fn get(&self, i: usize) -> Option<&Bankruptcy> {
+        if i >= self.len {
+            return None;
+        }
+        let start = (self.head + BANKRUPTCY_LEDGER_LEN - self.len) % BANKRUPTCY_LEDGER_LEN;
+        Some(&self.entries[(start + i) % BANKRUPTCY_LEDGER_LEN])
+    }
+
+    /// Retained entries, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Bankruptcy> {
+        (0..self.len).filter_map(move |i| self.get(i))
+    }
+
+    /// Retained entries of account `account_id`
+    pub fn for_account(&self, account_id: u64) -> impl Iterator<Item = &Bankruptcy> {
+        self.iter().filter(move |entry| entry.account_id == account_id)
+    }
+
+    /// Lifetime totals, including evicted entries
+    pub fn totals(&self) -> &BankruptcyTotals {
+        &self.totals
+    }
+}
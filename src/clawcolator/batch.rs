@@ -0,0 +1,144 @@
+//! Fixed-width batch margin checks for backtests and Monte Carlo runs
+//!
+//! Gated behind the `simd` feature (std, implies `analysis`). Scenario
+//! sweeps ask one question many times: which accounts are below maintenance
+//! at this price? `MarginBatch` snapshots every account with a position into
+//! f64 columns once, then answers per price in fixed chunks of `LANES`
+//! accounts with branch-free lane arithmetic that the compiler lowers to
+//! vector instructions on stable Rust.
+//!
+//! Results match the engine's `is_above_maintenance_margin_mtm` except for
+//! accounts within f64 rounding of the boundary; the engine remains the
+//! authority for anything that moves funds.
+
+use std::vec::Vec;
+
+use super::ClawcolatorEngine;
+use crate::{RiskEngine, MAX_ACCOUNTS};
+
+/// Accounts processed per step
+pub const LANES: usize = 8;
+
+/// Column snapshot of the accounts with open positions
+#[derive(Clone, Debug, Default)]
+pub struct MarginBatch {
+    /// Account index per row
+    idx: Vec<u16>,
+
+    /// Capital + realized PnL (positive PnL after the haircut), before mark
+    base_equity: Vec<f64>,
+
+    /// Unpaid maintenance fees
+    fee_debt: Vec<f64>,
+
+    /// Position size (+ long, - short)
+    position: Vec<f64>,
+
+    /// Last settlement price
+    entry: Vec<f64>,
+}
+
+impl MarginBatch {
+    /// Snapshot every live account with a position
+    pub fn capture(engine: &ClawcolatorEngine) -> Self {
+        let risk = engine.risk_engine();
+        let mut batch = Self::default();
+        for idx in 0..MAX_ACCOUNTS {
+            if risk.is_used(idx) && !risk.accounts[idx].position_size.is_zero() {
+                batch.push_account(risk, idx as u16);
+            }
+        }
+        batch
+    }
+
+    fn push_account(&mut self, risk: &RiskEngine, idx: u16) {
+        let account = &risk.accounts[idx as usize];
+        let pnl = account.pnl.get();
+        let base_equity = account.capital.get() as f64 + pnl.min(0) as f64 + risk.effective_pos_pnl(pnl) as f64;
+        let fee_debt = (-account.fee_credits.get()).max(0) as f64;
+        self.push(idx, base_equity, fee_debt, account.position_size.get() as f64, account.entry_price as f64);
+    }
+
+    /// Add a synthetic row (scenario generators, large account sets)
+    pub fn push(&mut self, idx: u16, base_equity: f64, fee_debt: f64, position: f64, entry_price: f64) {
+        self.idx.push(idx);
+        self.base_equity.push(base_equity);
+        self.fee_debt.push(fee_debt);
+        self.position.push(position);
+        self.entry.push(entry_price);
+    }
+
+    /// Rows in the batch
+    pub fn len(&self) -> usize {
+        self.idx.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.idx.is_empty()
+    }
+
+    /// Lane mask of accounts below `maintenance_bps` at `price`, per chunk
+    /// of `LANES` rows (bit i = row chunk * LANES + i)
+    fn masks(&self, price: u64, maintenance_bps: u64) -> impl Iterator<Item = u8> + '_ {
+        let price = price as f64;
+        let rate = price / 1_000_000.0 * maintenance_bps as f64 / 10_000.0;
+        let chunks = self.len().div_ceil(LANES);
+        (0..chunks).map(move |chunk| {
+            let start = chunk * LANES;
+            let end = (start + LANES).min(self.len());
+            let mut base = [0.0; LANES];
+            let mut debt = [0.0; LANES];
+            let mut position = [0.0; LANES];
+            let mut entry = [0.0; LANES];
+            base[..end - start].copy_from_slice(&self.base_equity[start..end]);
+            debt[..end - start].copy_from_slice(&self.fee_debt[start..end]);
+            position[..end - start].copy_from_slice(&self.position[start..end]);
+            entry[..end - start].copy_from_slice(&self.entry[start..end]);
+            below_maintenance(&base, &debt, &position, &entry, price, rate)
+        })
+    }
+
+    /// Accounts below maintenance margin at `price`, appended to `out`
+    pub fn undercollateralized(&self, price: u64, maintenance_bps: u64, out: &mut Vec<u16>) {
+        for (chunk, mask) in self.masks(price, maintenance_bps).enumerate() {
+            let mut mask = mask;
+            while mask != 0 {
+                let lane = mask.trailing_zeros() as usize;
+                out.push(self.idx[chunk * LANES + lane]);
+                mask &= mask - 1;
+            }
+        }
+    }
+
+    /// Number of accounts below maintenance margin at `price`
+    pub fn count_undercollateralized(&self, price: u64, maintenance_bps: u64) -> usize {
+        self.masks(price, maintenance_bps).map(|mask| mask.count_ones() as usize).sum()
+    }
+
+    /// `count_undercollateralized` for every scenario price
+    pub fn count_per_price(&self, prices: &[u64], maintenance_bps: u64) -> Vec<usize> {
+        prices.iter().map(|&price| self.count_undercollateralized(price, maintenance_bps)).collect()
+    }
+}
+
+/// One chunk: bit set where equity at `price` is at or below maintenance.
+/// Padding lanes have zero position and never set a bit.
+#[inline]
+fn below_maintenance(
+    base: &[f64; LANES],
+    debt: &[f64; LANES],
+    position: &[f64; LANES],
+    entry: &[f64; LANES],
+    price: f64,
+    rate: f64,
+) -> u8 {
+    let mut mask = 0u8;
+    for lane in 0..LANES {
+        let mark = position[lane] * (price - entry[lane]) / 1_000_000.0;
+        let equity = ((base[lane] + mark).max(0.0) - debt[lane]).max(0.0);
+        let maintenance = position[lane].abs() * rate;
+        let below = position[lane] != 0.0 && equity <= maintenance;
+        mask |= (below as u8) << lane;
+    }
+    mask
+}
@@ -0,0 +1,227 @@
+//! Commit-reveal trading
+//!
+//! The agent quotes every trade it sees, so it could selectively lean on
+//! flow before pricing it. In commit-reveal mode a user first commits to
+//! `sha256(user_idx, size, max_slippage_bps, salt)`; only a later reveal
+//! shows the trade to the agent, which then has to fill it within the
+//! committed slippage of the oracle or not at all. The commit discloses
+//! nothing but the account, and a reveal that does not match a live
+//! commitment of that account is refused.
+
+/// Commitments held at once across all accounts
+pub const COMMIT_BOOK_LEN: usize = crate::layout::capacity(option_env!("PERCOLATOR_COMMIT_BOOK_LEN"), 64);
+
+/// Live commitments a single account may hold
+pub const MAX_COMMITS_PER_ACCOUNT: usize = 4;
+
+/// Timing of commit-reveal trading
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommitRevealParams {
+    /// Direct trades are refused; every trade must be committed first
+    pub required: bool,
+
+    /// Slots between commit and the earliest reveal (at least 1)
+    pub min_delay_slots: u64,
+
+    /// Slots after commit the reveal stays valid
+    pub max_delay_slots: u64,
+}
+
+impl Default for CommitRevealParams {
+    fn default() -> Self {
+        Self {
+            required: false,
+            min_delay_slots: 1,
+            max_delay_slots: 150,
+        }
+    }
+}
+
+impl CommitRevealParams {
+    /// Reveal window must start after the commit slot and be non-empty
+    pub fn is_valid(&self) -> bool {
+        self.min_delay_slots >= 1 && self.max_delay_slots >= self.min_delay_slots
+    }
+}
+
+/// Trade a user reveals against an earlier commitment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TradeReveal {
+    /// User account index
+    pub user_idx: u16,
+
+    /// Position size (positive = long, negative = short)
+    pub size: i128,
+
+    /// Worst acceptable fill, in bps from the oracle price
+    pub max_slippage_bps: u64,
+
+    /// User's secret blinding the commitment
+    pub salt: [u8; 32],
+}
+
+impl TradeReveal {
+    /// The commitment this reveal opens
+    pub fn commitment(&self) -> [u8; 32] {
+        let mut preimage = [0u8; 2 + 16 + 8 + 32];
+        preimage[..2].copy_from_slice(&self.user_idx.to_le_bytes());
+        preimage[2..18].copy_from_slice(&self.size.to_le_bytes());
+        preimage[18..26].copy_from_slice(&self.max_slippage_bps.to_le_bytes());
+        preimage[26..].copy_from_slice(&self.salt);
+        sha256(&preimage)
+    }
+}
+
+/// Live commitment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TradeCommitment {
+    /// Committing account
+    pub user_idx: u16,
+
+    /// Id of the account at commit time (slots are reused)
+    pub account_id: u64,
+
+    /// Hash committed to (see `TradeReveal::commitment`)
+    pub hash: [u8; 32],
+
+    /// Slot of the commit
+    pub slot: u64,
+}
+
+/// Fixed table of live commitments
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommitBook {
+    commits: [Option<TradeCommitment>; COMMIT_BOOK_LEN],
+}
+
+impl Default for CommitBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommitBook {
+    /// Empty book
+    pub const fn new() -> Self {
+        Self { commits: [None; COMMIT_BOOK_LEN] }
+    }
+
+    /// Live commitments
+    pub fn iter(&self) -> impl Iterator<Item = &TradeCommitment> {
+        self.commits.iter().flatten()
+    }
+
+    /// Live commitments of `user_idx`
+    pub fn count_for(&self, user_idx: u16) -> usize {
+        self.iter().filter(|c| c.user_idx == user_idx).count()
+    }
+
+    /// Drop commitments whose reveal window closed before `now_slot`
+    pub fn purge_expired(&mut self, now_slot: u64, max_delay_slots: u64) {
+        for slot in self.commits.iter_mut() {
+            if slot.is_some_and(|c| now_slot.saturating_sub(c.slot) > max_delay_slots) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Store a commitment; false if the table is full
+    pub fn insert(&mut self, commit: TradeCommitment) -> bool {
+        match self.commits.iter_mut().find(|c| c.is_none()) {
+            Some(free) => {
+                *free = Some(commit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove and return the commitment of `user_idx` to `hash`
+    pub fn take(&mut self, user_idx: u16, hash: &[u8; 32]) -> Option<TradeCommitment> {
+        self.commits
+            .iter_mut()
+            .find(|c| c.is_some_and(|c| c.user_idx == user_idx && c.hash == *hash))?
+            .take()
+    }
+
+    /// Remove every commitment of `user_idx`
+    pub fn clear_account(&mut self, user_idx: u16) {
+        for slot in self.commits.iter_mut() {
+            if slot.is_some_and(|c| c.user_idx == user_idx) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 of a short message (no dependencies, matches `sol_sha256`)
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+    let bit_len = (data.len() as u64).wrapping_mul(8);
+    // Message, 0x80, zero padding, then the 64-bit length, in 64-byte blocks
+    let blocks = (data.len() + 9).div_ceil(64);
+    for block_idx in 0..blocks {
+        let mut block = [0u8; 64];
+        for (i, byte) in block.iter_mut().enumerate() {
+            let pos = block_idx * 64 + i;
+            *byte = match pos {
+                p if p < data.len() => data[p],
+                p if p == data.len() => 0x80,
+                _ => 0,
+            };
+        }
+        if block_idx == blocks - 1 {
+            block[56..].copy_from_slice(&bit_len.to_be_bytes());
+        }
+
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, v) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(v);
+        }
+    }
+
+    let mut out = [0u8; 32];
+    for (chunk, word) in out.chunks_exact_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
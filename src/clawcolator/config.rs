@@ -0,0 +1,529 @@
+//! TOML/JSON configuration for risk params, market params and protocol bounds
+//!
+//! Gated behind the `config` feature, which links `std` and serde. Every
+//! section and field is optional and falls back to the same defaults the
+//! examples use, so a config file only lists what it changes:
+//!
+//! ```toml
+//! [risk]
+//! maintenance_margin_bps = 500
+//! initial_margin_bps = 1000
+//!
+//! [market]
+//! spread_bps = 15
+//!
+//! [bounds.oracle]
+//! max_staleness_slots = 50
+//! ```
+//!
+//! Amounts that do not fit in 64 bits are written as decimal strings.
+//! `EngineConfig::validate` checks the values up front and names the
+//! offending field, instead of surfacing a bare `RiskError` from the engine
+//! on the first trade.
+
+use core::fmt;
+use serde::Deserialize;
+use std::{boxed::Box, format, string::String, string::ToString};
+
+use super::{
+    ClawcolatorEngine, ConcentrationBounds, DustBounds, MarketParams, OracleBounds, VammBounds,
+    WarmupBounds,
+};
+use crate::{RiskParams, MAX_ACCOUNTS, MAX_POSITION_ABS, U128};
+
+/// Leverage ceiling of a fresh engine (bps)
+const DEFAULT_LEVERAGE_CEILING_BPS: u64 = 10_000;
+
+/// Why a config could not be loaded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The file could not be read
+    Io(String),
+
+    /// The document is malformed or has unknown fields
+    Parse(String),
+
+    /// A value is out of range
+    Invalid {
+        field: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "cannot read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "invalid config: {}", e),
+            ConfigError::Invalid { field, reason } => write!(f, "{}: {}", field, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn invalid(field: &'static str, reason: impl Into<String>) -> ConfigError {
+    ConfigError::Invalid {
+        field,
+        reason: reason.into(),
+    }
+}
+
+/// u128 amount: an integer, or a decimal string above `u64::MAX` (TOML
+/// integers are 64-bit)
+fn amount<'de, D: serde::Deserializer<'de>>(d: D) -> Result<u128, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Amount {
+        Int(u64),
+        Str(String),
+    }
+    match Amount::deserialize(d)? {
+        Amount::Int(v) => Ok(v as u128),
+        Amount::Str(s) => s
+            .replace('_', "")
+            .parse()
+            .map_err(|_| serde::de::Error::custom(format!("invalid amount \"{}\"", s))),
+    }
+}
+
+fn check_bps(field: &'static str, value: u64) -> Result<(), ConfigError> {
+    if value > 10_000 {
+        return Err(invalid(field, format!("{} bps exceeds 100%", value)));
+    }
+    Ok(())
+}
+
+/// `RiskParams` section (`[risk]`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskConfig {
+    pub warmup_period_slots: u64,
+    pub maintenance_margin_bps: u64,
+    pub initial_margin_bps: u64,
+    pub trading_fee_bps: u64,
+    pub max_accounts: u64,
+    #[serde(deserialize_with = "amount")]
+    pub new_account_fee: u128,
+    #[serde(deserialize_with = "amount")]
+    pub risk_reduction_threshold: u128,
+    #[serde(deserialize_with = "amount")]
+    pub maintenance_fee_per_slot: u128,
+    pub max_crank_staleness_slots: u64,
+    pub liquidation_fee_bps: u64,
+    #[serde(deserialize_with = "amount")]
+    pub liquidation_fee_cap: u128,
+    pub liquidation_buffer_bps: u64,
+    #[serde(deserialize_with = "amount")]
+    pub min_liquidation_abs: u128,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self {
+            warmup_period_slots: 100,
+            maintenance_margin_bps: 500,
+            initial_margin_bps: 1000,
+            trading_fee_bps: 10,
+            max_accounts: MAX_ACCOUNTS as u64,
+            new_account_fee: 0,
+            risk_reduction_threshold: 0,
+            maintenance_fee_per_slot: 0,
+            max_crank_staleness_slots: u64::MAX,
+            liquidation_fee_bps: 50,
+            liquidation_fee_cap: 100_000,
+            liquidation_buffer_bps: 100,
+            min_liquidation_abs: 100_000,
+        }
+    }
+}
+
+impl RiskConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.maintenance_margin_bps == 0 {
+            return Err(invalid("risk.maintenance_margin_bps", "must be positive"));
+        }
+        check_bps("risk.maintenance_margin_bps", self.maintenance_margin_bps)?;
+        check_bps("risk.initial_margin_bps", self.initial_margin_bps)?;
+        if self.initial_margin_bps < self.maintenance_margin_bps {
+            return Err(invalid(
+                "risk.initial_margin_bps",
+                format!(
+                    "{} is below maintenance_margin_bps {}",
+                    self.initial_margin_bps, self.maintenance_margin_bps
+                ),
+            ));
+        }
+        check_bps("risk.trading_fee_bps", self.trading_fee_bps)?;
+        check_bps("risk.liquidation_fee_bps", self.liquidation_fee_bps)?;
+        check_bps("risk.liquidation_buffer_bps", self.liquidation_buffer_bps)?;
+        if self.max_accounts == 0 || self.max_accounts > MAX_ACCOUNTS as u64 {
+            return Err(invalid(
+                "risk.max_accounts",
+                format!("must be in 1..={}", MAX_ACCOUNTS),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn risk_params(&self) -> RiskParams {
+        RiskParams {
+            warmup_period_slots: self.warmup_period_slots,
+            maintenance_margin_bps: self.maintenance_margin_bps,
+            initial_margin_bps: self.initial_margin_bps,
+            trading_fee_bps: self.trading_fee_bps,
+            max_accounts: self.max_accounts,
+            new_account_fee: U128::new(self.new_account_fee),
+            risk_reduction_threshold: U128::new(self.risk_reduction_threshold),
+            maintenance_fee_per_slot: U128::new(self.maintenance_fee_per_slot),
+            max_crank_staleness_slots: self.max_crank_staleness_slots,
+            liquidation_fee_bps: self.liquidation_fee_bps,
+            liquidation_fee_cap: U128::new(self.liquidation_fee_cap),
+            liquidation_buffer_bps: self.liquidation_buffer_bps,
+            min_liquidation_abs: U128::new(self.min_liquidation_abs),
+        }
+    }
+}
+
+/// Scalar `MarketParams` an agent publishes (`[market]`); tier, dust,
+/// withdrawal and fee-split policies keep their defaults
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarketConfig {
+    pub max_leverage_bps: u64,
+    #[serde(deserialize_with = "amount")]
+    pub max_position_size: u128,
+    pub spread_bps: u64,
+    pub funding_rate_bps_per_slot: i64,
+    pub min_margin_bps: u64,
+    pub active_capital_ratio_bps: u64,
+    #[serde(deserialize_with = "amount")]
+    pub vamm_depth: u128,
+    pub max_oi_share_bps: u64,
+    pub warmup_period_slots: Option<u64>,
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        let p = MarketParams::default();
+        Self {
+            max_leverage_bps: p.max_leverage_bps,
+            max_position_size: p.max_position_size,
+            spread_bps: p.spread_bps,
+            funding_rate_bps_per_slot: p.funding_rate_bps_per_slot,
+            min_margin_bps: p.min_margin_bps,
+            active_capital_ratio_bps: p.active_capital_ratio_bps,
+            vamm_depth: p.vamm_depth,
+            max_oi_share_bps: p.max_oi_share_bps,
+            warmup_period_slots: p.warmup_period_slots,
+        }
+    }
+}
+
+impl MarketConfig {
+    /// Check against the risk section and the engine's default bounds
+    pub fn validate(&self, risk: &RiskConfig, bounds: &BoundsConfig) -> Result<(), ConfigError> {
+        if self.max_leverage_bps > DEFAULT_LEVERAGE_CEILING_BPS {
+            return Err(invalid(
+                "market.max_leverage_bps",
+                format!("exceeds the leverage ceiling of {} bps", DEFAULT_LEVERAGE_CEILING_BPS),
+            ));
+        }
+        if self.max_position_size > MAX_POSITION_ABS {
+            return Err(invalid(
+                "market.max_position_size",
+                format!("exceeds MAX_POSITION_ABS ({})", MAX_POSITION_ABS),
+            ));
+        }
+        check_bps("market.spread_bps", self.spread_bps)?;
+        check_bps("market.active_capital_ratio_bps", self.active_capital_ratio_bps)?;
+        check_bps("market.max_oi_share_bps", self.max_oi_share_bps)?;
+        if self.min_margin_bps < risk.maintenance_margin_bps {
+            return Err(invalid(
+                "market.min_margin_bps",
+                format!(
+                    "{} is below risk.maintenance_margin_bps {}",
+                    self.min_margin_bps, risk.maintenance_margin_bps
+                ),
+            ));
+        }
+        if self.vamm_depth > bounds.vamm.max_depth {
+            return Err(invalid("market.vamm_depth", "exceeds bounds.vamm.max_depth"));
+        }
+        if self.max_oi_share_bps > bounds.concentration.max_oi_share_bps {
+            return Err(invalid(
+                "market.max_oi_share_bps",
+                "looser than bounds.concentration.max_oi_share_bps",
+            ));
+        }
+        if let Some(slots) = self.warmup_period_slots {
+            let w = &bounds.warmup;
+            if slots < w.min_slots || slots > w.max_slots {
+                return Err(invalid(
+                    "market.warmup_period_slots",
+                    format!("must be in {}..={}", w.min_slots, w.max_slots),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn market_params(&self) -> MarketParams {
+        MarketParams {
+            max_leverage_bps: self.max_leverage_bps,
+            max_position_size: self.max_position_size,
+            spread_bps: self.spread_bps,
+            funding_rate_bps_per_slot: self.funding_rate_bps_per_slot,
+            min_margin_bps: self.min_margin_bps,
+            active_capital_ratio_bps: self.active_capital_ratio_bps,
+            vamm_depth: self.vamm_depth,
+            max_oi_share_bps: self.max_oi_share_bps,
+            warmup_period_slots: self.warmup_period_slots,
+            ..MarketParams::default()
+        }
+    }
+}
+
+/// `[bounds.oracle]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OracleBoundsConfig {
+    pub max_staleness_slots: u64,
+    pub max_confidence_bps: u64,
+    pub max_divergence_bps: u64,
+}
+
+impl Default for OracleBoundsConfig {
+    fn default() -> Self {
+        let b = OracleBounds::default();
+        Self {
+            max_staleness_slots: b.max_staleness_slots,
+            max_confidence_bps: b.max_confidence_bps,
+            max_divergence_bps: b.max_divergence_bps,
+        }
+    }
+}
+
+/// `[bounds.warmup]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WarmupBoundsConfig {
+    pub min_slots: u64,
+    pub max_slots: u64,
+}
+
+impl Default for WarmupBoundsConfig {
+    fn default() -> Self {
+        let b = WarmupBounds::default();
+        Self {
+            min_slots: b.min_slots,
+            max_slots: b.max_slots,
+        }
+    }
+}
+
+/// `[bounds.concentration]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConcentrationBoundsConfig {
+    pub max_oi_share_bps: u64,
+    #[serde(deserialize_with = "amount")]
+    pub min_open_interest: u128,
+}
+
+impl Default for ConcentrationBoundsConfig {
+    fn default() -> Self {
+        let b = ConcentrationBounds::default();
+        Self {
+            max_oi_share_bps: b.max_oi_share_bps,
+            min_open_interest: b.min_open_interest,
+        }
+    }
+}
+
+/// `[bounds.vamm]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VammBoundsConfig {
+    #[serde(deserialize_with = "amount")]
+    pub max_depth: u128,
+    pub max_fill_bps: u64,
+}
+
+impl Default for VammBoundsConfig {
+    fn default() -> Self {
+        let b = VammBounds::default();
+        Self {
+            max_depth: b.max_depth,
+            max_fill_bps: b.max_fill_bps,
+        }
+    }
+}
+
+/// `[bounds.dust]`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DustBoundsConfig {
+    #[serde(deserialize_with = "amount")]
+    pub max_maintenance_fee_per_slot: u128,
+    #[serde(deserialize_with = "amount")]
+    pub max_dust_capital_threshold: u128,
+    pub min_idle_slots: u64,
+}
+
+impl Default for DustBoundsConfig {
+    fn default() -> Self {
+        let b = DustBounds::default();
+        Self {
+            max_maintenance_fee_per_slot: b.max_maintenance_fee_per_slot,
+            max_dust_capital_threshold: b.max_dust_capital_threshold,
+            min_idle_slots: b.min_idle_slots,
+        }
+    }
+}
+
+/// Protocol bounds (`[bounds.*]`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoundsConfig {
+    pub oracle: OracleBoundsConfig,
+    pub warmup: WarmupBoundsConfig,
+    pub concentration: ConcentrationBoundsConfig,
+    pub vamm: VammBoundsConfig,
+    pub dust: DustBoundsConfig,
+}
+
+impl BoundsConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let o = &self.oracle;
+        if o.max_confidence_bps == 0 {
+            return Err(invalid("bounds.oracle.max_confidence_bps", "must be positive"));
+        }
+        check_bps("bounds.oracle.max_confidence_bps", o.max_confidence_bps)?;
+        if o.max_divergence_bps == 0 {
+            return Err(invalid("bounds.oracle.max_divergence_bps", "must be positive"));
+        }
+        check_bps("bounds.oracle.max_divergence_bps", o.max_divergence_bps)?;
+        if self.warmup.min_slots == 0 {
+            return Err(invalid("bounds.warmup.min_slots", "must be positive"));
+        }
+        if self.warmup.max_slots < self.warmup.min_slots {
+            return Err(invalid("bounds.warmup.max_slots", "is below min_slots"));
+        }
+        if self.concentration.max_oi_share_bps == 0 {
+            return Err(invalid("bounds.concentration.max_oi_share_bps", "must be positive"));
+        }
+        check_bps("bounds.concentration.max_oi_share_bps", self.concentration.max_oi_share_bps)?;
+        if self.vamm.max_fill_bps == 0 {
+            return Err(invalid("bounds.vamm.max_fill_bps", "must be positive"));
+        }
+        check_bps("bounds.vamm.max_fill_bps", self.vamm.max_fill_bps)?;
+        Ok(())
+    }
+
+    pub fn oracle_bounds(&self) -> OracleBounds {
+        OracleBounds {
+            max_staleness_slots: self.oracle.max_staleness_slots,
+            max_confidence_bps: self.oracle.max_confidence_bps,
+            max_divergence_bps: self.oracle.max_divergence_bps,
+        }
+    }
+
+    pub fn warmup_bounds(&self) -> WarmupBounds {
+        WarmupBounds {
+            min_slots: self.warmup.min_slots,
+            max_slots: self.warmup.max_slots,
+        }
+    }
+
+    pub fn concentration_bounds(&self) -> ConcentrationBounds {
+        ConcentrationBounds {
+            max_oi_share_bps: self.concentration.max_oi_share_bps,
+            min_open_interest: self.concentration.min_open_interest,
+        }
+    }
+
+    pub fn vamm_bounds(&self) -> VammBounds {
+        VammBounds {
+            max_depth: self.vamm.max_depth,
+            max_fill_bps: self.vamm.max_fill_bps,
+        }
+    }
+
+    pub fn dust_bounds(&self) -> DustBounds {
+        DustBounds {
+            max_maintenance_fee_per_slot: self.dust.max_maintenance_fee_per_slot,
+            max_dust_capital_threshold: self.dust.max_dust_capital_threshold,
+            min_idle_slots: self.dust.min_idle_slots,
+        }
+    }
+}
+
+/// Full engine configuration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    pub risk: RiskConfig,
+    pub market: MarketConfig,
+    pub bounds: BoundsConfig,
+}
+
+impl EngineConfig {
+    /// Parse and validate a TOML document
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(s).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse and validate a JSON document
+    pub fn from_json(s: &str) -> Result<Self, ConfigError> {
+        let config: Self =
+            serde_json::from_str(s).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load a file, as JSON if it ends in `.json` and TOML otherwise
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::Io(format!("{}: {}", path, e)))?;
+        if path.ends_with(".json") {
+            Self::from_json(&s)
+        } else {
+            Self::from_toml(&s)
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.risk.validate()?;
+        self.bounds.validate()?;
+        self.market.validate(&self.risk, &self.bounds)
+    }
+
+    /// Engine with the configured risk params and protocol bounds installed
+    ///
+    /// Market params are the agent's to publish; agents built from a config
+    /// return `self.market.market_params()`.
+    pub fn build_engine(&self) -> Result<Box<ClawcolatorEngine>, ConfigError> {
+        self.validate()?;
+        let mut engine = Box::new(ClawcolatorEngine::new(self.risk.risk_params()));
+        let rejected = |field| move |e| invalid(field, format!("rejected by the engine: {:?}", e));
+        engine
+            .set_oracle_bounds(self.bounds.oracle_bounds())
+            .map_err(rejected("bounds.oracle"))?;
+        engine
+            .set_warmup_bounds(self.bounds.warmup_bounds())
+            .map_err(rejected("bounds.warmup"))?;
+        engine
+            .set_concentration_bounds(self.bounds.concentration_bounds())
+            .map_err(rejected("bounds.concentration"))?;
+        engine
+            .set_vamm_bounds(self.bounds.vamm_bounds())
+            .map_err(rejected("bounds.vamm"))?;
+        engine
+            .set_dust_bounds(self.bounds.dust_bounds())
+            .map_err(rejected("bounds.dust"))?;
+        Ok(engine)
+    }
+}
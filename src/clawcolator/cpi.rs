@@ -0,0 +1,173 @@
+//! CPI calling convention for external matching engines
+//!
+//! An LP registered with a non-zero `matcher_program` is matched by that
+//! program: the wrapper invokes it with the LP's `matcher_context` as its
+//! only account and reads the fill back from the invocation's return data.
+//!
+//! Instruction data (little-endian, Anchor-compatible):
+//! discriminator (8) | lp_account_id u64 | oracle_price u64 | size i128
+//!
+//! Return data, set by the matcher program itself:
+//! price u64 | size i128
+//!
+//! `CpiMatcher` does the encoding through a `CpiInvoker` (the wrapper's
+//! `invoke` + `get_return_data`) and holds the answer to the same bounds as
+//! agent fills before the engine sees it.
+
+use crate::{MatchingEngine, RiskError, Result, TradeExecution, MAX_ORACLE_PRICE, MAX_POSITION_ABS};
+
+use super::saturating_abs_i128;
+use super::oracle::AccountReader;
+
+/// Anchor discriminator of `execute_match` (`sha256("global:execute_match")[..8]`)
+pub const MATCH_DISCRIMINATOR: [u8; 8] = [0x4c, 0x2f, 0x5b, 0xdf, 0x14, 0x0a, 0x93, 0xe8];
+
+/// Length of the instruction data
+pub const MATCH_REQUEST_LEN: usize = 40;
+
+/// Length of the return data
+pub const MATCH_RETURN_LEN: usize = 24;
+
+/// Request sent to a matcher program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchRequest {
+    /// Unique ID of the LP account (never recycled)
+    pub lp_account_id: u64,
+
+    pub oracle_price: u64,
+
+    /// Requested size (positive = user buys)
+    pub size: i128,
+}
+
+impl MatchRequest {
+    pub fn encode(&self) -> [u8; MATCH_REQUEST_LEN] {
+        let mut data = [0u8; MATCH_REQUEST_LEN];
+        data[0..8].copy_from_slice(&MATCH_DISCRIMINATOR);
+        data[8..16].copy_from_slice(&self.lp_account_id.to_le_bytes());
+        data[16..24].copy_from_slice(&self.oracle_price.to_le_bytes());
+        data[24..40].copy_from_slice(&self.size.to_le_bytes());
+        data
+    }
+
+    /// Parse instruction data (for matcher programs written against this crate)
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let reader = AccountReader::new(data);
+        if data.len() != MATCH_REQUEST_LEN || reader.bytes::<8>(0)? != MATCH_DISCRIMINATOR {
+            return Err(RiskError::InvalidMatchingEngine);
+        }
+        Ok(Self {
+            lp_account_id: reader.u64(8)?,
+            oracle_price: reader.u64(16)?,
+            size: reader.i128(24)?,
+        })
+    }
+}
+
+/// Return data a matcher program sets for `execution`
+pub fn encode_execution(execution: &TradeExecution) -> [u8; MATCH_RETURN_LEN] {
+    let mut data = [0u8; MATCH_RETURN_LEN];
+    data[0..8].copy_from_slice(&execution.price.to_le_bytes());
+    data[8..24].copy_from_slice(&execution.size.to_le_bytes());
+    data
+}
+
+/// Parse a matcher program's return data
+pub fn decode_execution(data: &[u8]) -> Result<TradeExecution> {
+    if data.len() != MATCH_RETURN_LEN {
+        return Err(RiskError::InvalidMatchingEngine);
+    }
+    let reader = AccountReader::new(data);
+    Ok(TradeExecution {
+        price: reader.u64(0)?,
+        size: reader.i128(8)?,
+    })
+}
+
+/// Bounds every fill must meet, whoever priced it: a sane price, and at
+/// most the requested size in the requested direction
+pub fn validate_execution(execution: &TradeExecution, requested_size: i128) -> Result<()> {
+    let (price, exec_size) = (execution.price, execution.size);
+    if price == 0 || price > MAX_ORACLE_PRICE {
+        return Err(RiskError::InvalidMatchingEngine);
+    }
+    if exec_size == 0 {
+        return Ok(()); // No fill is valid
+    }
+    if exec_size == i128::MIN || saturating_abs_i128(exec_size) as u128 > MAX_POSITION_ABS {
+        return Err(RiskError::InvalidMatchingEngine);
+    }
+    if (exec_size > 0) != (requested_size > 0) {
+        return Err(RiskError::InvalidMatchingEngine);
+    }
+    if saturating_abs_i128(exec_size) > saturating_abs_i128(requested_size) {
+        return Err(RiskError::InvalidMatchingEngine);
+    }
+    Ok(())
+}
+
+/// Cross-program invocation, as provided by the on-chain wrapper
+pub trait CpiInvoker {
+    /// Invoke `program` with `context` as its only account and `data` as
+    /// instruction data, then copy the return data into `return_data`
+    ///
+    /// # Returns
+    /// * `Ok(Some((program_id, len)))` - Program that set the return data and its full length
+    /// * `Ok(None)` - No return data was set
+    /// * `Err(RiskError)` - The invocation failed
+    fn invoke(
+        &self,
+        program: &[u8; 32],
+        context: &[u8; 32],
+        data: &[u8],
+        return_data: &mut [u8],
+    ) -> Result<Option<([u8; 32], usize)>>;
+}
+
+/// `MatchingEngine` that matches through the LP's own program via CPI
+pub struct CpiMatcher<I: CpiInvoker> {
+    invoker: I,
+}
+
+impl<I: CpiInvoker> CpiMatcher<I> {
+    pub fn new(invoker: I) -> Self {
+        Self { invoker }
+    }
+}
+
+impl<I: CpiInvoker> MatchingEngine for CpiMatcher<I> {
+    fn execute_match(
+        &self,
+        lp_program: &[u8; 32],
+        lp_context: &[u8; 32],
+        lp_account_id: u64,
+        oracle_price: u64,
+        size: i128,
+    ) -> Result<TradeExecution> {
+        // An LP without a matcher program cannot be matched externally
+        if *lp_program == [0; 32] {
+            return Err(RiskError::InvalidMatchingEngine);
+        }
+        let request = MatchRequest {
+            lp_account_id,
+            oracle_price,
+            size,
+        };
+        let mut buf = [0u8; MATCH_RETURN_LEN];
+        let returned = self
+            .invoker
+            .invoke(lp_program, lp_context, &request.encode(), &mut buf)?;
+
+        // Return data set by any other program (e.g. one the matcher called)
+        // is not the matcher's answer
+        let Some((program_id, len)) = returned else {
+            return Err(RiskError::InvalidMatchingEngine);
+        };
+        if program_id != *lp_program || len != MATCH_RETURN_LEN {
+            return Err(RiskError::InvalidMatchingEngine);
+        }
+        let execution = decode_execution(&buf)?;
+        validate_execution(&execution, size)?;
+        Ok(execution)
+    }
+}
@@ -0,0 +1,88 @@
+//! Dead-man switch: protocol response to a silent agent
+//!
+//! Every valid agent output (a trade decision, a batch price, a market
+//! params update) stamps `last_agent_activity_slot`. With the switch armed,
+//! a crank that finds the agent idle for `reduce_after_slots` puts the
+//! protocol in risk-reduction-only mode and widens margins by
+//! `margin_widen_bps`; idle for `freeze_after_slots` more, it freezes the
+//! market. Both lift at the first crank after the agent is heard from
+//! again.
+
+use crate::{RiskError, Result};
+
+/// Idle thresholds and margin widening of the dead-man switch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeadManParams {
+    /// Idle slots before risk-reduction-only mode
+    pub reduce_after_slots: u64,
+
+    /// Further idle slots before the market freezes
+    pub freeze_after_slots: u64,
+
+    /// Added to maintenance and initial margin while the agent is idle (bps)
+    pub margin_widen_bps: u64,
+}
+
+impl Default for DeadManParams {
+    fn default() -> Self {
+        Self {
+            reduce_after_slots: 1_500, // ~10 minutes at 400ms slots
+            freeze_after_slots: 7_500,
+            margin_widen_bps: 500,
+        }
+    }
+}
+
+impl DeadManParams {
+    /// Non-zero thresholds, widening below 100%
+    pub fn validate(&self) -> Result<()> {
+        if self.reduce_after_slots == 0 || self.freeze_after_slots == 0 || self.margin_widen_bps > 10_000 {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+
+    /// Stage for an agent idle for `idle_slots`
+    pub fn stage(&self, idle_slots: u64) -> DeadManStage {
+        if idle_slots < self.reduce_after_slots {
+            DeadManStage::Live
+        } else if idle_slots < self.reduce_after_slots.saturating_add(self.freeze_after_slots) {
+            DeadManStage::RiskReduction
+        } else {
+            DeadManStage::Frozen
+        }
+    }
+}
+
+/// Protocol response to agent silence
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeadManStage {
+    /// Agent active recently
+    Live,
+    /// Risk-reduction-only with widened margins
+    RiskReduction,
+    /// Market frozen
+    Frozen,
+}
+
+/// Dead-man switch state (see `ClawcolatorEngine::dead_man_status`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeadManStatus {
+    /// Slot of the agent's last valid output
+    pub last_agent_activity_slot: u64,
+
+    /// Slots since then
+    pub idle_slots: u64,
+
+    /// Stage applied at the last crank
+    pub stage: DeadManStage,
+}
+
+/// Margins in effect before the switch widened them, and what it set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct WidenedMargins {
+    pub base_maintenance_bps: u64,
+    pub base_initial_bps: u64,
+    pub maintenance_bps: u64,
+    pub initial_bps: u64,
+}
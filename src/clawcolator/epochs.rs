@@ -0,0 +1,147 @@
+//! Epoch accounting: periodic reports over fixed slot windows
+//!
+//! Counters accumulate during an epoch; the crank that reaches the epoch
+//! end finalizes them into an `EpochReport` and keeps the last
+//! `EPOCH_HISTORY_LEN` reports.
+
+use super::{BankruptcyTotals, EscalationLevel, FeeTotals};
+
+/// Number of finalized epoch reports retained
+pub const EPOCH_HISTORY_LEN: usize = 8;
+
+/// Default epoch length (~1 hour at 400ms slots)
+pub const DEFAULT_EPOCH_SLOTS: u64 = 9_000;
+
+/// Liquidation activity in an epoch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LiquidationStats {
+    /// Accounts liquidated (crank and direct)
+    pub liquidations: u32,
+
+    /// Liquidation attempts that errored during cranks
+    pub liquidation_errors: u32,
+
+    /// Agent-directed forced reductions
+    pub forced_reductions: u32,
+
+    /// Dust accounts closed
+    pub dust_closed: u32,
+}
+
+/// Agent performance over an epoch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AgentScorecard {
+    /// Trades the agent accepted and the engine executed
+    pub trades_accepted: u32,
+
+    /// Trades the agent rejected or answered with a quote
+    pub trades_rejected: u32,
+
+    /// Trades the agent lacked liquidity for, filled on the vAMM curve
+    pub vamm_fills: u32,
+
+    /// Executed notional
+    pub volume: u128,
+
+    /// LP equity at epoch start
+    pub lp_equity_start: u128,
+
+    /// LP equity at epoch end
+    pub lp_equity_end: u128,
+
+    /// Anomaly checks reporting non-zero severity
+    pub anomalies_flagged: u32,
+
+    /// Highest escalation level applied
+    pub max_escalation: EscalationLevel,
+}
+
+impl AgentScorecard {
+    /// LP equity change over the epoch (includes deposits and withdrawals)
+    pub fn lp_equity_change(&self) -> i128 {
+        (self.lp_equity_end as i128).saturating_sub(self.lp_equity_start as i128)
+    }
+}
+
+/// Finalized epoch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EpochReport {
+    /// Epoch number (0-based)
+    pub epoch: u64,
+
+    /// First slot of the epoch
+    pub start_slot: u64,
+
+    /// Slot at which the epoch was finalized
+    pub end_slot: u64,
+
+    /// Trading fees distributed
+    pub fees: FeeTotals,
+
+    /// Funding index movement (quote per base, 1e6 scale)
+    pub funding_index_delta: i128,
+
+    /// Funding received by the LP (negative = paid)
+    pub lp_funding_received: i128,
+
+    /// Funding paid by all paying accounts
+    pub funding_paid: u128,
+
+    /// Funding received by all receiving accounts
+    pub funding_received: u128,
+
+    /// Liquidation activity
+    pub liquidations: LiquidationStats,
+
+    /// Bankruptcies recorded in the epoch and how they were covered
+    pub bankruptcies: BankruptcyTotals,
+
+    /// Agent scorecard
+    pub scorecard: AgentScorecard,
+}
+
+/// Ring buffer of finalized epoch reports
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EpochHistory {
+    reports: [EpochReport; EPOCH_HISTORY_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl EpochHistory {
+    /// Append a report, evicting the oldest when full
+    pub fn push(&mut self, report: EpochReport) {
+        self.reports[self.head] = report;
+        self.head = (self.head + 1) % EPOCH_HISTORY_LEN;
+        self.len = (self.len + 1).min(EPOCH_HISTORY_LEN);
+    }
+
+    /// Number of retained reports
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no epoch has been finalized
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Report `i` in chronological order (0 = oldest retained)
+    pub fn get(&self, i: usize) -> Option<&EpochReport> {
+        if i >= self.len {
+            return None;
+        }
+        let start = (self.head + EPOCH_HISTORY_LEN - self.len) % EPOCH_HISTORY_LEN;
+        Some(&self.reports[(start + i) % EPOCH_HISTORY_LEN])
+    }
+
+    /// Most recent report
+    pub fn latest(&self) -> Option<&EpochReport> {
+        self.len.checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    /// Reports in chronological order
+    pub fn iter(&self) -> impl Iterator<Item = &EpochReport> + '_ {
+        (0..self.len).filter_map(move |i| self.get(i))
+    }
+}
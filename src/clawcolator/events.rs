@@ -0,0 +1,284 @@
+//! Market events for indexers
+//!
+//! The engine appends an event for every fill, every liquidation and every
+//! change of the published market params to a fixed ring, and an
+//! `EventSink` drains it after each instruction. On Solana the wrapper
+//! drains into a `ProgramLogSink` over `sol_log_data`, which writes each
+//! event as an Anchor event (`Program data: <base64>` with the event's
+//! discriminator and borsh fields), so Anchor clients, Helius webhooks and
+//! Geyser consumers can follow a market from its logs without diffing
+//! account state. Events evicted before a drain are counted as dropped.
+
+use crate::{RiskError, Result};
+
+use super::oracle::AccountReader;
+use super::TradeFill;
+
+/// Events retained between drains
+pub const EVENT_LOG_LEN: usize = 32;
+
+/// Longest encoded event
+pub const MAX_EVENT_LEN: usize = 72;
+
+const TRADE_EVENT_LEN: usize = 46;
+const LIQUIDATION_EVENT_LEN: usize = 66;
+const MARKET_PARAMS_EVENT_LEN: usize = 72;
+
+/// Anchor event discriminators (`sha256("event:<Name>")[..8]`)
+pub const TRADE_EVENT_DISCRIMINATOR: [u8; 8] = [0xbd, 0xdb, 0x7f, 0xd3, 0x4e, 0xe6, 0x61, 0xee];
+pub const LIQUIDATION_EVENT_DISCRIMINATOR: [u8; 8] = [0x03, 0x0d, 0x15, 0x5d, 0xad, 0x88, 0x48, 0x90];
+pub const MARKET_PARAMS_EVENT_DISCRIMINATOR: [u8; 8] = [0xfb, 0x3e, 0x2b, 0x03, 0x44, 0x70, 0xf4, 0x86];
+
+/// An account liquidated at the oracle
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LiquidationEvent {
+    pub slot: u64,
+    pub idx: u16,
+    pub account_id: u64,
+
+    /// Position the account held before liquidation
+    pub size: i128,
+
+    /// Position left after it (partial liquidations keep some)
+    pub remaining: i128,
+
+    pub oracle_price: u64,
+}
+
+/// Published market params after a change (the fields indexers chart)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MarketParamsEvent {
+    pub slot: u64,
+    pub max_leverage_bps: u64,
+    pub max_position_size: u128,
+    pub spread_bps: u64,
+    pub funding_rate_bps_per_slot: i64,
+    pub min_margin_bps: u64,
+    pub max_oi_share_bps: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarketEvent {
+    Trade(TradeFill),
+    Liquidation(LiquidationEvent),
+    ParamsChanged(MarketParamsEvent),
+}
+
+/// Encoded event: discriminator and borsh fields
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EventData {
+    bytes: [u8; MAX_EVENT_LEN],
+    len: usize,
+}
+
+impl EventData {
+    fn new(discriminator: [u8; 8]) -> Self {
+        let mut data = Self { bytes: [0; MAX_EVENT_LEN], len: 0 };
+        data.put(&discriminator);
+        data
+    }
+
+    fn put(&mut self, bytes: &[u8]) {
+        self.bytes[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl MarketEvent {
+    /// Anchor event encoding
+    ///
+    /// `TradeEvent { slot: u64, user_idx: u16, lp_idx: u16, price: u64,
+    /// size: i128, vamm: bool, client_order_id: Option<u64> }`,
+    /// `LiquidationEvent { slot: u64, idx: u16, account_id: u64, size: i128,
+    /// remaining: i128, oracle_price: u64 }` and `MarketParamsEvent` in
+    /// field order.
+    pub fn encode(&self) -> EventData {
+        match self {
+            MarketEvent::Trade(fill) => {
+                let mut data = EventData::new(TRADE_EVENT_DISCRIMINATOR);
+                data.put(&fill.slot.to_le_bytes());
+                data.put(&fill.user_idx.to_le_bytes());
+                data.put(&fill.lp_idx.to_le_bytes());
+                data.put(&fill.price.to_le_bytes());
+                data.put(&fill.size.to_le_bytes());
+                data.put(&[fill.vamm as u8]);
+                match fill.client_order_id {
+                    Some(id) => {
+                        data.put(&[1]);
+                        data.put(&id.to_le_bytes());
+                    }
+                    None => data.put(&[0]),
+                }
+                data
+            }
+            MarketEvent::Liquidation(event) => {
+                let mut data = EventData::new(LIQUIDATION_EVENT_DISCRIMINATOR);
+                data.put(&event.slot.to_le_bytes());
+                data.put(&event.idx.to_le_bytes());
+                data.put(&event.account_id.to_le_bytes());
+                data.put(&event.size.to_le_bytes());
+                data.put(&event.remaining.to_le_bytes());
+                data.put(&event.oracle_price.to_le_bytes());
+                data
+            }
+            MarketEvent::ParamsChanged(event) => {
+                let mut data = EventData::new(MARKET_PARAMS_EVENT_DISCRIMINATOR);
+                data.put(&event.slot.to_le_bytes());
+                data.put(&event.max_leverage_bps.to_le_bytes());
+                data.put(&event.max_position_size.to_le_bytes());
+                data.put(&event.spread_bps.to_le_bytes());
+                data.put(&event.funding_rate_bps_per_slot.to_le_bytes());
+                data.put(&event.min_margin_bps.to_le_bytes());
+                data.put(&event.max_oi_share_bps.to_le_bytes());
+                data
+            }
+        }
+    }
+
+    /// Parse an event logged by `ProgramLogSink` (the decoded `Program data`
+    /// payload); decision metadata is not logged and comes back empty
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let r = AccountReader::new(data);
+        let (event, len) = match r.bytes::<8>(0)? {
+            TRADE_EVENT_DISCRIMINATOR => {
+                let (client_order_id, len) = match r.u8(45)? {
+                    0 => (None, TRADE_EVENT_LEN),
+                    1 => (Some(r.u64(46)?), TRADE_EVENT_LEN + 8),
+                    _ => return Err(RiskError::Overflow),
+                };
+                let fill = TradeFill {
+                    slot: r.u64(8)?,
+                    user_idx: r.u16(16)?,
+                    lp_idx: r.u16(18)?,
+                    price: r.u64(20)?,
+                    size: r.i128(28)?,
+                    vamm: r.u8(44)? != 0,
+                    meta: None,
+                    client_order_id,
+                };
+                (MarketEvent::Trade(fill), len)
+            }
+            LIQUIDATION_EVENT_DISCRIMINATOR => {
+                let event = LiquidationEvent {
+                    slot: r.u64(8)?,
+                    idx: r.u16(16)?,
+                    account_id: r.u64(18)?,
+                    size: r.i128(26)?,
+                    remaining: r.i128(42)?,
+                    oracle_price: r.u64(58)?,
+                };
+                (MarketEvent::Liquidation(event), LIQUIDATION_EVENT_LEN)
+            }
+            MARKET_PARAMS_EVENT_DISCRIMINATOR => {
+                let event = MarketParamsEvent {
+                    slot: r.u64(8)?,
+                    max_leverage_bps: r.u64(16)?,
+                    max_position_size: r.u128(24)?,
+                    spread_bps: r.u64(40)?,
+                    funding_rate_bps_per_slot: r.i64(48)?,
+                    min_margin_bps: r.u64(56)?,
+                    max_oi_share_bps: r.u64(64)?,
+                };
+                (MarketEvent::ParamsChanged(event), MARKET_PARAMS_EVENT_LEN)
+            }
+            _ => return Err(RiskError::Unauthorized),
+        };
+        if data.len() != len {
+            return Err(RiskError::Overflow);
+        }
+        Ok(event)
+    }
+}
+
+/// Where drained events go
+pub trait EventSink {
+    fn emit(&mut self, event: &MarketEvent);
+}
+
+/// Sink writing each event as an Anchor event through a `sol_log_data`-like
+/// function (e.g. `solana_program::log::sol_log_data`)
+pub struct ProgramLogSink<F: FnMut(&[&[u8]])> {
+    log: F,
+}
+
+impl<F: FnMut(&[&[u8]])> ProgramLogSink<F> {
+    pub fn new(log: F) -> Self {
+        Self { log }
+    }
+}
+
+impl<F: FnMut(&[&[u8]])> EventSink for ProgramLogSink<F> {
+    fn emit(&mut self, event: &MarketEvent) {
+        (self.log)(&[event.encode().as_bytes()]);
+    }
+}
+
+/// Ring buffer of events not yet drained
+#[derive(Clone, Copy, Debug)]
+pub struct EventLog {
+    events: [Option<MarketEvent>; EVENT_LOG_LEN],
+    head: usize,
+    len: usize,
+    dropped: u64,
+}
+
+impl EventLog {
+    pub const fn new() -> Self {
+        Self {
+            events: [None; EVENT_LOG_LEN],
+            head: 0,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Append an event, evicting the oldest when full
+    pub(crate) fn push(&mut self, event: MarketEvent) {
+        if self.len == EVENT_LOG_LEN {
+            self.dropped = self.dropped.saturating_add(1);
+        }
+        self.events[self.head] = Some(event);
+        self.head = (self.head + 1) % EVENT_LOG_LEN;
+        self.len = (self.len + 1).min(EVENT_LOG_LEN);
+    }
+
+    /// Number of pending events
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Events evicted before they were drained, to date
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Pending events in order
+    pub fn iter(&self) -> impl Iterator<Item = &MarketEvent> + '_ {
+        let start = (self.head + EVENT_LOG_LEN - self.len) % EVENT_LOG_LEN;
+        (0..self.len).filter_map(move |i| self.events[(start + i) % EVENT_LOG_LEN].as_ref())
+    }
+
+    /// Emit pending events into `sink` in order and clear them
+    pub(crate) fn drain<S: EventSink>(&mut self, sink: &mut S) -> usize {
+        let drained = self.len;
+        for event in self.iter() {
+            sink.emit(event);
+        }
+        self.len = 0;
+        drained
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
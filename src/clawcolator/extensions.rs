@@ -0,0 +1,147 @@
+//! Typed extension slots on `AgentContext`
+//!
+//! New telemetry for agents goes into a fixed set of slots keyed by
+//! `ExtensionKey<T>` instead of new struct fields, so adding it never breaks
+//! agent code. Keys are plain ids with a value type attached; publisher and
+//! agent agree on a key by sharing the `ExtensionKey` constant. Agents that
+//! do not know a key never see its value.
+
+use core::marker::PhantomData;
+
+use crate::{Result, RiskError};
+
+/// Slots per context
+pub const MAX_CONTEXT_EXTENSIONS: usize = 16;
+
+/// Typed key of an extension slot
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExtensionKey<T> {
+    pub id: u32,
+    _value: PhantomData<T>,
+}
+
+impl<T> ExtensionKey<T> {
+    pub const fn new(id: u32) -> Self {
+        Self {
+            id,
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for ExtensionKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ExtensionKey<T> {}
+
+/// Value types an extension slot can hold (stored as 128 raw bits)
+pub trait ExtensionValue: Copy {
+    fn into_raw(self) -> i128;
+    fn from_raw(raw: i128) -> Self;
+}
+
+impl ExtensionValue for i128 {
+    fn into_raw(self) -> i128 {
+        self
+    }
+
+    fn from_raw(raw: i128) -> Self {
+        raw
+    }
+}
+
+impl ExtensionValue for u128 {
+    fn into_raw(self) -> i128 {
+        self as i128
+    }
+
+    fn from_raw(raw: i128) -> Self {
+        raw as u128
+    }
+}
+
+impl ExtensionValue for u64 {
+    fn into_raw(self) -> i128 {
+        self as i128
+    }
+
+    fn from_raw(raw: i128) -> Self {
+        raw as u64
+    }
+}
+
+impl ExtensionValue for i64 {
+    fn into_raw(self) -> i128 {
+        self as i128
+    }
+
+    fn from_raw(raw: i128) -> Self {
+        raw as i64
+    }
+}
+
+impl ExtensionValue for bool {
+    fn into_raw(self) -> i128 {
+        self as i128
+    }
+
+    fn from_raw(raw: i128) -> Self {
+        raw != 0
+    }
+}
+
+/// Extension slots of one context
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContextExtensions {
+    slots: [(u32, i128); MAX_CONTEXT_EXTENSIONS],
+    len: usize,
+}
+
+impl ContextExtensions {
+    /// Value under `key`, if set
+    pub fn get<T: ExtensionValue>(&self, key: ExtensionKey<T>) -> Option<T> {
+        self.slots[..self.len]
+            .iter()
+            .find(|(id, _)| *id == key.id)
+            .map(|(_, raw)| T::from_raw(*raw))
+    }
+
+    /// Set `key` to `value`, replacing any previous value
+    ///
+    /// Fails with `Overflow` when every slot is taken by another key.
+    pub fn insert<T: ExtensionValue>(&mut self, key: ExtensionKey<T>, value: T) -> Result<()> {
+        if let Some(slot) = self.slots[..self.len].iter_mut().find(|(id, _)| *id == key.id) {
+            slot.1 = value.into_raw();
+            return Ok(());
+        }
+        if self.len == MAX_CONTEXT_EXTENSIONS {
+            return Err(RiskError::Overflow);
+        }
+        self.slots[self.len] = (key.id, value.into_raw());
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Clear `key`; returns whether it was set
+    pub fn remove<T>(&mut self, key: ExtensionKey<T>) -> bool {
+        let Some(pos) = self.slots[..self.len].iter().position(|(id, _)| *id == key.id) else {
+            return false;
+        };
+        self.slots.copy_within(pos + 1..self.len, pos);
+        self.len -= 1;
+        self.slots[self.len] = (0, 0);
+        true
+    }
+
+    /// Number of keys set
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
@@ -0,0 +1,170 @@
+//! Fee distribution: split of trading fees between LP pool, insurance and treasury
+//!
+//! The engine books every trading fee into the insurance fund. After each
+//! fill the Clawcolator layer moves the LP and treasury shares out of
+//! insurance into the LP and treasury accounts' capital, so vault, capital
+//! and insurance stay balanced.
+//!
+//! Fills the agent makes inside the oracle (price improvement for the user)
+//! count as maker-like flow: a slice of the taker's fee is rebated to the
+//! LP. Rebates come out of the insurance share of that same fee, so they can
+//! never exceed what was collected.
+
+use crate::{RiskError, Result};
+
+/// Agent-adjustable fee split (basis points, must sum to 10_000)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeSplit {
+    /// Share credited to the LP pool
+    pub lp_bps: u64,
+
+    /// Share kept by the insurance fund
+    pub insurance_bps: u64,
+
+    /// Share credited to the treasury account
+    pub treasury_bps: u64,
+}
+
+/// Default split: everything stays in insurance
+pub const DEFAULT_FEE_SPLIT: FeeSplit = FeeSplit {
+    lp_bps: 0,
+    insurance_bps: 10_000,
+    treasury_bps: 0,
+};
+
+/// Protocol-enforced ranges for the fee split
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeSplitBounds {
+    /// Minimum share kept by insurance
+    pub min_insurance_bps: u64,
+
+    /// Maximum share paid to the treasury
+    pub max_treasury_bps: u64,
+}
+
+impl Default for FeeSplitBounds {
+    fn default() -> Self {
+        Self {
+            min_insurance_bps: 2_000,
+            max_treasury_bps: 3_000,
+        }
+    }
+}
+
+impl FeeSplit {
+    /// Check the split sums to 100% and respects protocol ranges
+    pub fn validate(&self, bounds: &FeeSplitBounds) -> Result<()> {
+        let total = self
+            .lp_bps
+            .checked_add(self.insurance_bps)
+            .and_then(|v| v.checked_add(self.treasury_bps))
+            .ok_or(RiskError::Overflow)?;
+        if total != 10_000
+            || self.insurance_bps < bounds.min_insurance_bps
+            || self.treasury_bps > bounds.max_treasury_bps
+        {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+
+    /// Split `fee`; rounding dust stays with insurance
+    pub fn apply(&self, fee: u128) -> FeeTotals {
+        let lp = fee.saturating_mul(self.lp_bps as u128) / 10_000;
+        let treasury = fee.saturating_mul(self.treasury_bps as u128) / 10_000;
+        FeeTotals {
+            lp,
+            insurance: fee.saturating_sub(lp).saturating_sub(treasury),
+            treasury,
+            referral: 0,
+            maker_rebate: 0,
+        }
+    }
+}
+
+/// Fees distributed per destination
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FeeTotals {
+    /// Credited to the LP pool
+    pub lp: u128,
+
+    /// Kept by the insurance fund
+    pub insurance: u128,
+
+    /// Credited to the treasury
+    pub treasury: u128,
+
+    /// Accrued to referrers, carved out of the insurance share (held in
+    /// insurance until claimed)
+    pub referral: u128,
+
+    /// Rebated to the LP for maker-like fills, carved out of the insurance
+    /// share
+    pub maker_rebate: u128,
+}
+
+impl FeeTotals {
+    pub fn total(&self) -> u128 {
+        self.lp
+            .saturating_add(self.insurance)
+            .saturating_add(self.treasury)
+            .saturating_add(self.referral)
+            .saturating_add(self.maker_rebate)
+    }
+
+    pub(crate) fn add(&mut self, other: &FeeTotals) {
+        self.lp = self.lp.saturating_add(other.lp);
+        self.insurance = self.insurance.saturating_add(other.insurance);
+        self.treasury = self.treasury.saturating_add(other.treasury);
+        self.referral = self.referral.saturating_add(other.referral);
+        self.maker_rebate = self.maker_rebate.saturating_add(other.maker_rebate);
+    }
+}
+
+/// Maker rebate schedule
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MakerRebateParams {
+    /// Share of the taker fee rebated on maker-like fills (bps)
+    pub rebate_bps: u64,
+
+    /// Price improvement over the oracle that makes a fill maker-like (bps, > 0)
+    pub min_improvement_bps: u64,
+}
+
+impl Default for MakerRebateParams {
+    fn default() -> Self {
+        Self {
+            rebate_bps: 2_500,
+            min_improvement_bps: 1,
+        }
+    }
+}
+
+impl MakerRebateParams {
+    pub fn validate(&self) -> Result<()> {
+        if self.rebate_bps > 10_000 || self.min_improvement_bps == 0 {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+
+    /// Whether a user fill of `size` at `price` improves on `oracle_price`
+    /// by at least `min_improvement_bps`
+    pub fn is_maker_like(&self, size: i128, price: u64, oracle_price: u64) -> bool {
+        price_improvement_bps(size, price, oracle_price) >= self.min_improvement_bps
+    }
+}
+
+/// Improvement of a fill over the oracle for the user (bps; 0 when the
+/// fill is at or through the oracle)
+pub fn price_improvement_bps(size: i128, price: u64, oracle_price: u64) -> u64 {
+    let improvement = if size > 0 {
+        oracle_price.saturating_sub(price)
+    } else {
+        price.saturating_sub(oracle_price)
+    };
+    if oracle_price == 0 {
+        return 0;
+    }
+    (improvement as u128 * 10_000 / oracle_price as u128) as u64
+}
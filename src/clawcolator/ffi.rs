@@ -0,0 +1,380 @@
+//! C ABI for embedding the engine in non-Rust trading systems
+//!
+//! Gated behind the `ffi` feature; the matching header is
+//! `include/clawcolator.h`. The engine is an opaque handle created by
+//! `claw_engine_new` and released by `claw_engine_free`. Trade decisions
+//! come from a registered C callback; everything else the agent decides
+//! uses passive defaults. Functions return `CLAW_OK` (0) or a negative
+//! `CLAW_ERR_*` code. Amounts cross the boundary as 64-bit integers and
+//! saturate on the way out. Build the shared library as a cdylib:
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+
+#![allow(unsafe_code)]
+
+use core::ffi::c_void;
+use std::boxed::Box;
+
+use super::{
+    AgentCapabilities, AgentContext, ClawcolatorEngine, MarketParams, OpenClawAgent, OraclePrice,
+    TradeDecision, TradeRejectionReason, TradeRequest,
+};
+use crate::{AccountKind, RiskError, RiskParams, Result, MAX_ACCOUNTS, U128};
+
+pub const CLAW_OK: i32 = 0;
+pub const CLAW_ERR_NULL: i32 = -1;
+pub const CLAW_ERR_INSUFFICIENT_BALANCE: i32 = -2;
+pub const CLAW_ERR_UNDERCOLLATERALIZED: i32 = -3;
+pub const CLAW_ERR_UNAUTHORIZED: i32 = -4;
+pub const CLAW_ERR_INVALID_MATCHING_ENGINE: i32 = -5;
+pub const CLAW_ERR_PNL_NOT_WARMED_UP: i32 = -6;
+pub const CLAW_ERR_OVERFLOW: i32 = -7;
+pub const CLAW_ERR_ACCOUNT_NOT_FOUND: i32 = -8;
+pub const CLAW_ERR_NOT_AN_LP_ACCOUNT: i32 = -9;
+pub const CLAW_ERR_POSITION_SIZE_MISMATCH: i32 = -10;
+pub const CLAW_ERR_ACCOUNT_KIND_MISMATCH: i32 = -11;
+
+/// Callback decision: reject the trade
+pub const CLAW_DECISION_REJECT: i32 = 0;
+
+/// Callback decision: accept at `price` for `size`
+pub const CLAW_DECISION_ACCEPT: i32 = 1;
+
+/// Error code of a `RiskError`
+pub fn error_code(e: RiskError) -> i32 {
+    match e {
+        RiskError::InsufficientBalance => CLAW_ERR_INSUFFICIENT_BALANCE,
+        RiskError::Undercollateralized => CLAW_ERR_UNDERCOLLATERALIZED,
+        RiskError::Unauthorized => CLAW_ERR_UNAUTHORIZED,
+        RiskError::InvalidMatchingEngine => CLAW_ERR_INVALID_MATCHING_ENGINE,
+        RiskError::PnlNotWarmedUp => CLAW_ERR_PNL_NOT_WARMED_UP,
+        RiskError::Overflow => CLAW_ERR_OVERFLOW,
+        RiskError::AccountNotFound => CLAW_ERR_ACCOUNT_NOT_FOUND,
+        RiskError::NotAnLPAccount => CLAW_ERR_NOT_AN_LP_ACCOUNT,
+        RiskError::PositionSizeMismatch => CLAW_ERR_POSITION_SIZE_MISMATCH,
+        RiskError::AccountKindMismatch => CLAW_ERR_ACCOUNT_KIND_MISMATCH,
+    }
+}
+
+fn code(result: Result<()>) -> i32 {
+    result.map_or_else(error_code, |_| CLAW_OK)
+}
+
+fn sat(value: u128) -> u64 {
+    value.min(u64::MAX as u128) as u64
+}
+
+fn sat_i(value: i128) -> i64 {
+    value.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Agent context passed to the decision callback
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClawContext {
+    pub current_slot: u64,
+    pub oracle_price: u64,
+    pub vault: u64,
+    pub insurance_balance: u64,
+    pub total_capital: u64,
+    pub total_open_interest: u64,
+    pub lp_liquidity: u64,
+    pub oracle_divergence_bps: u64,
+    pub risk_reduction_mode: bool,
+}
+
+impl From<&AgentContext> for ClawContext {
+    fn from(c: &AgentContext) -> Self {
+        Self {
+            current_slot: c.current_slot,
+            oracle_price: c.oracle_price,
+            vault: sat(c.vault),
+            insurance_balance: sat(c.insurance_balance),
+            total_capital: sat(c.total_capital),
+            total_open_interest: sat(c.total_open_interest),
+            lp_liquidity: sat(c.lp_liquidity),
+            oracle_divergence_bps: c.oracle_divergence_bps,
+            risk_reduction_mode: c.risk_reduction_mode,
+        }
+    }
+}
+
+/// Trade request passed to the decision callback
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClawTradeRequest {
+    pub user_idx: u16,
+    pub size: i64,
+}
+
+/// Decision written by the callback
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClawDecision {
+    /// `CLAW_DECISION_ACCEPT` or `CLAW_DECISION_REJECT`
+    pub kind: i32,
+    pub price: u64,
+    pub size: i64,
+}
+
+/// Engine totals
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClawStatus {
+    pub current_slot: u64,
+    pub vault: u64,
+    pub insurance_balance: u64,
+    pub total_capital: u64,
+    pub total_open_interest: u64,
+}
+
+/// Account state
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClawAccount {
+    pub idx: u16,
+    pub is_lp: bool,
+    pub capital: u64,
+    pub pnl: i64,
+    pub position_size: i64,
+    pub equity: u64,
+    pub margin_ratio_bps: u64,
+    pub undercollateralized: bool,
+}
+
+/// Trade decision callback: fill `out` and return 0, or non-zero on error
+/// (treated as an agent error)
+pub type ClawDecisionFn = extern "C" fn(
+    user_data: *mut c_void,
+    context: *const ClawContext,
+    request: *const ClawTradeRequest,
+    out: *mut ClawDecision,
+) -> i32;
+
+/// Opaque engine handle
+pub struct ClawEngine {
+    inner: Box<ClawcolatorEngine>,
+    decide: Option<ClawDecisionFn>,
+    user_data: *mut c_void,
+}
+
+/// `OpenClawAgent` forwarding trade decisions to the registered callback
+struct CallbackAgent {
+    decide: Option<ClawDecisionFn>,
+    user_data: *mut c_void,
+}
+
+impl OpenClawAgent for CallbackAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        let decide = self.decide.ok_or(RiskError::Unauthorized)?;
+        let ctx = ClawContext::from(context);
+        let req = ClawTradeRequest {
+            user_idx: request.user_idx,
+            size: sat_i(request.size),
+        };
+        let mut out = ClawDecision::default();
+        if decide(self.user_data, &ctx, &req, &mut out) != 0 {
+            return Err(RiskError::Unauthorized);
+        }
+        match out.kind {
+            CLAW_DECISION_ACCEPT => Ok(TradeDecision::Accept {
+                price: out.price,
+                size: out.size as i128,
+            }),
+            CLAW_DECISION_REJECT => Ok(TradeDecision::Reject {
+                reason: TradeRejectionReason::Other,
+            }),
+            _ => Err(RiskError::InvalidMatchingEngine),
+        }
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+
+    fn agent_capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities::CORE
+    }
+}
+
+/// Create an engine; free it with `claw_engine_free`
+#[no_mangle]
+pub extern "C" fn claw_engine_new(
+    maintenance_margin_bps: u64,
+    initial_margin_bps: u64,
+    trading_fee_bps: u64,
+    warmup_period_slots: u64,
+) -> *mut ClawEngine {
+    let params = RiskParams {
+        warmup_period_slots,
+        maintenance_margin_bps,
+        initial_margin_bps,
+        trading_fee_bps,
+        max_accounts: MAX_ACCOUNTS as u64,
+        new_account_fee: U128::new(0),
+        risk_reduction_threshold: U128::new(0),
+        maintenance_fee_per_slot: U128::new(0),
+        max_crank_staleness_slots: u64::MAX,
+        liquidation_fee_bps: 50,
+        liquidation_fee_cap: U128::new(100_000),
+        liquidation_buffer_bps: 100,
+        min_liquidation_abs: U128::new(100_000),
+    };
+    Box::into_raw(Box::new(ClawEngine {
+        inner: Box::new(ClawcolatorEngine::new(params)),
+        decide: None,
+        user_data: core::ptr::null_mut(),
+    }))
+}
+
+/// Release an engine (null is ignored)
+///
+/// # Safety
+/// `engine` must come from `claw_engine_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn claw_engine_free(engine: *mut ClawEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// Register the trade decision callback; `user_data` is passed back as is
+///
+/// # Safety
+/// `engine` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn claw_set_decision_callback(
+    engine: *mut ClawEngine,
+    decide: Option<ClawDecisionFn>,
+    user_data: *mut c_void,
+) -> i32 {
+    let Some(engine) = engine.as_mut() else {
+        return CLAW_ERR_NULL;
+    };
+    engine.decide = decide;
+    engine.user_data = user_data;
+    CLAW_OK
+}
+
+/// Open an account funded with `deposit`, writing its index to `out_idx`
+///
+/// # Safety
+/// `engine` must be a live handle and `out_idx` writable.
+#[no_mangle]
+pub unsafe extern "C" fn claw_add_account(
+    engine: *mut ClawEngine,
+    is_lp: bool,
+    deposit: u64,
+    out_idx: *mut u16,
+) -> i32 {
+    let (Some(engine), Some(out_idx)) = (engine.as_mut(), out_idx.as_mut()) else {
+        return CLAW_ERR_NULL;
+    };
+    let risk = engine.inner.risk_engine_mut();
+    let idx = match if is_lp {
+        risk.add_lp([0; 32], [0; 32], 0)
+    } else {
+        risk.add_user(0)
+    } {
+        Ok(idx) => idx,
+        Err(e) => return error_code(e),
+    };
+    *out_idx = idx;
+    code(risk.deposit(idx, deposit as u128, 0))
+}
+
+/// Run a trade through the decision callback at a fresh oracle price
+///
+/// # Safety
+/// `engine` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn claw_execute_trade(
+    engine: *mut ClawEngine,
+    user_idx: u16,
+    oracle_price: u64,
+    size: i64,
+    now_slot: u64,
+) -> i32 {
+    let Some(engine) = engine.as_mut() else {
+        return CLAW_ERR_NULL;
+    };
+    let agent = CallbackAgent {
+        decide: engine.decide,
+        user_data: engine.user_data,
+    };
+    let oracle = OraclePrice::new(oracle_price, 0, now_slot);
+    code(
+        engine
+            .inner
+            .execute_trade(&agent, user_idx, oracle, size as i128, now_slot),
+    )
+}
+
+/// Crank the engine as `caller_idx`
+///
+/// # Safety
+/// `engine` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn claw_crank(
+    engine: *mut ClawEngine,
+    caller_idx: u16,
+    now_slot: u64,
+    oracle_price: u64,
+) -> i32 {
+    let Some(engine) = engine.as_mut() else {
+        return CLAW_ERR_NULL;
+    };
+    code(engine.inner.crank(caller_idx, now_slot, oracle_price, None).map(|_| ()))
+}
+
+/// Write engine totals to `out`
+///
+/// # Safety
+/// `engine` must be a live handle and `out` writable.
+#[no_mangle]
+pub unsafe extern "C" fn claw_status(engine: *const ClawEngine, out: *mut ClawStatus) -> i32 {
+    let (Some(engine), Some(out)) = (engine.as_ref(), out.as_mut()) else {
+        return CLAW_ERR_NULL;
+    };
+    let risk = engine.inner.risk_engine();
+    *out = ClawStatus {
+        current_slot: risk.current_slot,
+        vault: sat(risk.vault.get()),
+        insurance_balance: sat(risk.insurance_fund.balance.get()),
+        total_capital: sat(risk.c_tot.get()),
+        total_open_interest: sat(risk.total_open_interest.get()),
+    };
+    CLAW_OK
+}
+
+/// Write account `idx` at `oracle_price` to `out`
+///
+/// # Safety
+/// `engine` must be a live handle and `out` writable.
+#[no_mangle]
+pub unsafe extern "C" fn claw_account(
+    engine: *const ClawEngine,
+    idx: u16,
+    oracle_price: u64,
+    out: *mut ClawAccount,
+) -> i32 {
+    let (Some(engine), Some(out)) = (engine.as_ref(), out.as_mut()) else {
+        return CLAW_ERR_NULL;
+    };
+    let Some(v) = engine.inner.account_view(idx, oracle_price) else {
+        return CLAW_ERR_ACCOUNT_NOT_FOUND;
+    };
+    *out = ClawAccount {
+        idx: v.idx,
+        is_lp: v.kind == AccountKind::LP,
+        capital: sat(v.capital),
+        pnl: sat_i(v.pnl),
+        position_size: sat_i(v.position_size),
+        equity: sat(v.equity),
+        margin_ratio_bps: v.margin_ratio_bps,
+        undercollateralized: v.undercollateralized,
+    };
+    CLAW_OK
+}
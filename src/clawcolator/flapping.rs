@@ -0,0 +1,208 @@
+//! Rate-of-change guard on agent decisions
+//!
+//! An agent that keeps freezing and unfreezing the market, or keeps
+//! publishing different params, is as dangerous as a silent one: users
+//! cannot rely on the limits they trade against. With the guard armed,
+//! every such change is logged as a flip; more than `max_flips` flips within
+//! `window_slots` lock the market params at the most conservative values in
+//! force during the window and raise an operator alert. Agent updates are
+//! refused until the lock is released administratively.
+
+use super::MarketParams;
+use crate::{RiskError, Result};
+
+/// Flips retained (bounds `FlappingGuard::max_flips`)
+pub const FLIP_LOG_LEN: usize = 16;
+
+/// Operator alerts retained
+pub const ALERT_LOG_LEN: usize = 16;
+
+/// Flapping thresholds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlappingGuard {
+    /// Flips tolerated within the window
+    pub max_flips: u32,
+
+    /// Window the flips are counted over (slots)
+    pub window_slots: u64,
+}
+
+impl Default for FlappingGuard {
+    fn default() -> Self {
+        Self {
+            max_flips: 4,
+            window_slots: 150, // ~1 minute at 400ms slots
+        }
+    }
+}
+
+impl FlappingGuard {
+    /// Non-zero window, at least one flip tolerated, fewer than the log holds
+    pub fn validate(&self) -> Result<()> {
+        if self.max_flips == 0 || self.max_flips as usize >= FLIP_LOG_LEN || self.window_slots == 0 {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+}
+
+/// What the agent changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlipKind {
+    /// Froze or unfroze the market (anomaly response or de-escalation)
+    Freeze,
+    /// Published params different from those in force
+    Params,
+}
+
+/// One change of agent decision
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Flip {
+    /// Slot of the change
+    pub slot: u64,
+
+    /// What changed
+    pub kind: FlipKind,
+
+    /// Market params in force before the change
+    pub params: MarketParams,
+}
+
+/// Recent flips, oldest evicted first
+#[derive(Clone, Copy, Debug)]
+pub struct FlipLog {
+    flips: [Option<Flip>; FLIP_LOG_LEN],
+    head: usize,
+}
+
+impl Default for FlipLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlipLog {
+    pub const fn new() -> Self {
+        Self {
+            flips: [None; FLIP_LOG_LEN],
+            head: 0,
+        }
+    }
+
+    /// Append a flip, evicting the oldest when full
+    pub fn push(&mut self, flip: Flip) {
+        self.flips[self.head] = Some(flip);
+        self.head = (self.head + 1) % FLIP_LOG_LEN;
+    }
+
+    /// Retained flips (unordered)
+    pub fn iter(&self) -> impl Iterator<Item = &Flip> {
+        self.flips.iter().flatten()
+    }
+
+    /// Flips at or after `since_slot`
+    pub fn count_since(&self, since_slot: u64) -> u32 {
+        self.iter().filter(|f| f.slot >= since_slot).count() as u32
+    }
+
+    /// `current` tightened by every param set in force since `since_slot`
+    pub fn tightest_since(&self, since_slot: u64, current: &MarketParams) -> MarketParams {
+        self.iter()
+            .filter(|f| f.slot >= since_slot)
+            .fold(*current, |acc, f| tightest(&acc, &f.params))
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// `a` with every risk limit replaced by the more conservative of `a` and
+/// `b` (the fields `params_tighter_or_equal` compares); the rest from `a`
+fn tightest(a: &MarketParams, b: &MarketParams) -> MarketParams {
+    let mut out = *a;
+    for (tier, other) in out.tier_limits.iter_mut().zip(b.tier_limits.iter()) {
+        tier.max_leverage_bps = tier.max_leverage_bps.min(other.max_leverage_bps);
+        tier.max_position_size = tier.max_position_size.min(other.max_position_size);
+    }
+    out.max_leverage_bps = a.max_leverage_bps.min(b.max_leverage_bps);
+    out.max_position_size = a.max_position_size.min(b.max_position_size);
+    out.min_margin_bps = a.min_margin_bps.max(b.min_margin_bps);
+    out.spread_bps = a.spread_bps.max(b.spread_bps);
+    out.active_capital_ratio_bps = a.active_capital_ratio_bps.min(b.active_capital_ratio_bps);
+    out.withdrawal_policy.queue_threshold =
+        a.withdrawal_policy.queue_threshold.min(b.withdrawal_policy.queue_threshold);
+    out.withdrawal_policy.delay_slots = a.withdrawal_policy.delay_slots.max(b.withdrawal_policy.delay_slots);
+    out.vamm_depth = a.vamm_depth.min(b.vamm_depth);
+    out.max_oi_share_bps = a.max_oi_share_bps.min(b.max_oi_share_bps);
+    out.lending_policy.max_utilization_bps =
+        a.lending_policy.max_utilization_bps.min(b.lending_policy.max_utilization_bps);
+    out.lending_policy.max_borrow_bps = a.lending_policy.max_borrow_bps.min(b.lending_policy.max_borrow_bps);
+    out
+}
+
+/// Why the operator is alerted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertKind {
+    /// Agent decisions flapped; params locked
+    DecisionFlapping {
+        /// Flips counted in the window
+        flips: u32,
+    },
+}
+
+/// Event raised for the operator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OperatorAlert {
+    /// Slot the alert was raised
+    pub slot: u64,
+
+    /// What happened
+    pub kind: AlertKind,
+}
+
+/// Ring buffer of recent operator alerts
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AlertLog {
+    alerts: [Option<OperatorAlert>; ALERT_LOG_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl AlertLog {
+    pub const fn new() -> Self {
+        Self {
+            alerts: [None; ALERT_LOG_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Append an alert, evicting the oldest when full
+    pub fn push(&mut self, alert: OperatorAlert) {
+        self.alerts[self.head] = Some(alert);
+        self.head = (self.head + 1) % ALERT_LOG_LEN;
+        self.len = (self.len + 1).min(ALERT_LOG_LEN);
+    }
+
+    /// Number of retained alerts
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no alert has been raised
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Alerts in chronological order
+    pub fn iter(&self) -> impl Iterator<Item = &OperatorAlert> + '_ {
+        let start = (self.head + ALERT_LOG_LEN - self.len) % ALERT_LOG_LEN;
+        (0..self.len).filter_map(move |i| self.alerts[(start + i) % ALERT_LOG_LEN].as_ref())
+    }
+
+    /// Most recent alert
+    pub fn latest(&self) -> Option<&OperatorAlert> {
+        self.iter().last()
+    }
+}
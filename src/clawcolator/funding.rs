@@ -0,0 +1,101 @@
+//! Cumulative funding paid and received per account
+//!
+//! Funding accrues into one global index, moved in O(1) per crank; an
+//! account is charged the index movement against its position only when
+//! it is next touched, and the engine records every such charge in
+//! `RiskEngine::funding_settled`. The ledger is a read-only view over that
+//! state: an account's cumulative funding is what was settled plus what has
+//! accrued since its index snapshot, computed on demand, so nothing here
+//! walks the account slab. Payments round the way the engine does: up when
+//! paying, towards zero when receiving.
+
+use crate::RiskEngine;
+
+/// Funding accrual state of one account
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FundingAccrual {
+    /// Global funding index at the last settlement (quote per base, 1e6 scale)
+    pub index: i128,
+
+    /// Position the pending accrual is charged against
+    pub position: i128,
+
+    /// Funding settled into PnL so far (negative = received)
+    pub settled: i128,
+
+    /// Accrued since `index`, settled on the account's next touch
+    pub pending: i128,
+}
+
+impl FundingAccrual {
+    /// Cumulative funding paid (negative = received)
+    pub fn paid(&self) -> i128 {
+        self.settled.saturating_add(self.pending)
+    }
+}
+
+/// Funding moved by one crank, summed over accounts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FundingFlows {
+    /// Total paid by accounts on the paying side
+    pub paid: u128,
+
+    /// Total received by accounts on the receiving side
+    pub received: u128,
+}
+
+impl FundingFlows {
+    /// Flows of an index move of `index_delta` over the long and short open
+    /// interest it accrued against. O(1): the paying side is whichever the
+    /// rate charges (longs when the index rises).
+    pub fn accrued(long_open_interest: u128, short_open_interest: u128, index_delta: i128) -> Self {
+        let (payers, receivers) = if index_delta >= 0 {
+            (long_open_interest, short_open_interest)
+        } else {
+            (short_open_interest, long_open_interest)
+        };
+        let delta = index_delta.unsigned_abs();
+        Self {
+            paid: payers.saturating_mul(delta).div_ceil(1_000_000),
+            received: receivers.saturating_mul(delta) / 1_000_000,
+        }
+    }
+}
+
+/// Per-account funding ledger, read lazily from the engine
+#[derive(Clone, Copy)]
+pub struct FundingLedger<'a> {
+    engine: &'a RiskEngine,
+}
+
+impl<'a> FundingLedger<'a> {
+    pub fn new(engine: &'a RiskEngine) -> Self {
+        Self { engine }
+    }
+
+    /// Accrual state of `idx` (None if the slot is free)
+    pub fn accrual(&self, idx: u16) -> Option<FundingAccrual> {
+        let i = idx as usize;
+        if !self.engine.is_used(i) {
+            return None;
+        }
+        let account = &self.engine.accounts[i];
+        Some(FundingAccrual {
+            index: account.funding_index.get(),
+            position: account.position_size.get(),
+            settled: self.engine.funding_settled[i].get(),
+            pending: self.engine.pending_funding(i),
+        })
+    }
+
+    /// Cumulative funding paid by `idx` (negative = received)
+    pub fn paid(&self, idx: u16) -> i128 {
+        self.accrual(idx).map_or(0, |a| a.paid())
+    }
+}
+
+impl core::fmt::Debug for FundingLedger<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FundingLedger").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,344 @@
+//! Governance: M-of-N signed, timelocked changes to protocol parameters
+//!
+//! Once governance is enabled, base `RiskParams` and the protocol bounds the
+//! agent operates within can only change through proposals. A signer
+//! proposes (counting as the first approval), further signers approve by
+//! signing the proposal id, and once `threshold` approvals are collected the
+//! proposal becomes executable after `timelock_slots`. Any signer may cancel
+//! a pending proposal. Signatures are checked with the same
+//! `SignatureVerifier` used for the operator kill-switch.
+
+use super::{
+    ConcentrationBounds, DustBounds, EscalationPolicy, FeeSplitBounds, ImpactModel, MarketMode,
+    LendingBounds, OracleBounds, SignatureVerifier, VammBounds, WarmupBounds, WindDownParams,
+    WithdrawalBounds,
+};
+use crate::{RiskError, RiskParams, Result};
+
+/// Maximum governance signers
+pub const MAX_GOVERNORS: usize = 8;
+
+/// Maximum proposals pending at once
+pub const MAX_PROPOSALS: usize = 8;
+
+/// Domain separator prefixed to every signed governance message
+pub const GOVERNANCE_DOMAIN: &[u8; 16] = b"clawcolator:gov\0";
+
+/// Length of an encoded governance message (domain, vote, proposal id)
+pub const GOVERNANCE_MESSAGE_LEN: usize = 16 + 1 + 8;
+
+/// Signer set, approval threshold and timelock
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GovernanceConfig {
+    /// Signer public keys
+    pub signers: [[u8; 32]; MAX_GOVERNORS],
+
+    /// Number of valid entries in `signers`
+    pub signers_len: u8,
+
+    /// Approvals required (M of N)
+    pub threshold: u8,
+
+    /// Slots between reaching the threshold and execution
+    pub timelock_slots: u64,
+}
+
+impl GovernanceConfig {
+    /// Build a config from a signer list
+    pub fn new(signers: &[[u8; 32]], threshold: u8, timelock_slots: u64) -> Result<Self> {
+        if signers.len() > MAX_GOVERNORS {
+            return Err(RiskError::Overflow);
+        }
+        let mut config = Self {
+            signers: [[0; 32]; MAX_GOVERNORS],
+            signers_len: signers.len() as u8,
+            threshold,
+            timelock_slots,
+        };
+        config.signers[..signers.len()].copy_from_slice(signers);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check threshold and signer list
+    pub fn validate(&self) -> Result<()> {
+        if self.signers_len as usize > MAX_GOVERNORS
+            || self.threshold == 0
+            || self.threshold > self.signers_len
+        {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+
+    /// Active signer keys
+    pub fn signers(&self) -> &[[u8; 32]] {
+        &self.signers[..self.signers_len as usize]
+    }
+}
+
+/// Parameter change carried by a proposal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProposalAction {
+    /// Replace the base engine parameters
+    RiskParams(RiskParams),
+
+    /// Replace the ceiling on agent leverage (bps)
+    LeverageCeiling(u64),
+
+    /// Replace the oracle bounds
+    OracleBounds(OracleBounds),
+
+    /// Replace the anomaly escalation thresholds
+    EscalationPolicy(EscalationPolicy),
+
+    /// Replace the dust policy bounds
+    DustBounds(DustBounds),
+
+    /// Replace the withdrawal policy bounds
+    WithdrawalBounds(WithdrawalBounds),
+
+    /// Replace the fee split bounds
+    FeeSplitBounds(FeeSplitBounds),
+
+    /// Replace the lending policy bounds
+    LendingBounds(LendingBounds),
+
+    /// Replace the fallback vAMM bounds
+    VammBounds(VammBounds),
+
+    /// Replace the concentration limit bounds
+    ConcentrationBounds(ConcentrationBounds),
+
+    /// Replace the warmup period bounds
+    WarmupBounds(WarmupBounds),
+
+    /// Replace the price-impact model
+    ImpactModel(ImpactModel),
+
+    /// Switch between agent-driven and classic trading (staged to the next crank)
+    MarketMode(MarketMode),
+
+    /// Delist the market on the given wind-down schedule
+    WindDown(WindDownParams),
+
+    /// Replace the signer set, threshold or timelock
+    Config(GovernanceConfig),
+}
+
+/// What a governance signature authorizes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GovernanceVote {
+    /// Propose (for the next id) or approve a proposal
+    Approve,
+
+    /// Cancel a pending proposal
+    Cancel,
+}
+
+impl GovernanceVote {
+    /// Canonical message a signer signs for this vote on `proposal_id`
+    pub fn encode(&self, proposal_id: u64) -> [u8; GOVERNANCE_MESSAGE_LEN] {
+        let mut message = [0u8; GOVERNANCE_MESSAGE_LEN];
+        message[..16].copy_from_slice(GOVERNANCE_DOMAIN);
+        message[16] = *self as u8;
+        message[17..].copy_from_slice(&proposal_id.to_le_bytes());
+        message
+    }
+}
+
+/// Pending proposal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Proposal {
+    /// Proposal id (never reused)
+    pub id: u64,
+
+    /// Change to apply
+    pub action: ProposalAction,
+
+    /// Slot the proposal was created
+    pub proposed_slot: u64,
+
+    /// Bitmask of approving signer indices
+    pub approvals: u16,
+
+    /// Slot from which the proposal may execute (set once approved)
+    pub eta_slot: Option<u64>,
+}
+
+impl Proposal {
+    /// Number of approvals collected
+    pub fn approval_count(&self) -> u32 {
+        self.approvals.count_ones()
+    }
+}
+
+/// Governance state: config plus pending proposals
+#[derive(Clone, Copy, Debug)]
+pub struct Governance {
+    config: GovernanceConfig,
+    proposals: [Option<Proposal>; MAX_PROPOSALS],
+    next_id: u64,
+}
+
+impl Governance {
+    /// Governance under `config`
+    pub fn new(config: GovernanceConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            proposals: [None; MAX_PROPOSALS],
+            next_id: 0,
+        })
+    }
+
+    /// Current config
+    pub fn config(&self) -> &GovernanceConfig {
+        &self.config
+    }
+
+    /// Id the next proposal will receive
+    pub fn next_proposal_id(&self) -> u64 {
+        self.next_id
+    }
+
+    /// Pending proposals
+    pub fn pending(&self) -> impl Iterator<Item = &Proposal> {
+        self.proposals.iter().flatten()
+    }
+
+    /// Pending proposal by id
+    pub fn proposal(&self, id: u64) -> Option<&Proposal> {
+        self.pending().find(|p| p.id == id)
+    }
+
+    /// Create a proposal; the proposer's signature over the next id counts
+    /// as the first approval
+    pub fn propose<V: SignatureVerifier>(
+        &mut self,
+        verifier: &V,
+        action: ProposalAction,
+        signer: u8,
+        signature: &[u8; 64],
+        now_slot: u64,
+    ) -> Result<u64> {
+        if let ProposalAction::Config(config) = action {
+            config.validate()?;
+        }
+        let id = self.next_id;
+        self.check_signature(verifier, GovernanceVote::Approve, id, signer, signature)?;
+        let slot = self
+            .proposals
+            .iter_mut()
+            .find(|p| p.is_none())
+            .ok_or(RiskError::Overflow)?;
+        let mut proposal = Proposal {
+            id,
+            action,
+            proposed_slot: now_slot,
+            approvals: 0,
+            eta_slot: None,
+        };
+        Self::record_approval(&self.config, &mut proposal, signer, now_slot);
+        *slot = Some(proposal);
+        self.next_id += 1;
+        Ok(id)
+    }
+
+    /// Add a signer's approval
+    pub fn approve<V: SignatureVerifier>(
+        &mut self,
+        verifier: &V,
+        id: u64,
+        signer: u8,
+        signature: &[u8; 64],
+        now_slot: u64,
+    ) -> Result<()> {
+        self.check_signature(verifier, GovernanceVote::Approve, id, signer, signature)?;
+        let config = self.config;
+        let proposal = self.find_mut(id)?;
+        Self::record_approval(&config, proposal, signer, now_slot);
+        Ok(())
+    }
+
+    /// Drop a pending proposal on any signer's signed cancel
+    pub fn cancel<V: SignatureVerifier>(
+        &mut self,
+        verifier: &V,
+        id: u64,
+        signer: u8,
+        signature: &[u8; 64],
+    ) -> Result<()> {
+        self.check_signature(verifier, GovernanceVote::Cancel, id, signer, signature)?;
+        let slot = self
+            .proposals
+            .iter_mut()
+            .find(|p| p.is_some_and(|p| p.id == id))
+            .ok_or(RiskError::AccountNotFound)?;
+        *slot = None;
+        Ok(())
+    }
+
+    /// Remove and return a proposal whose timelock has elapsed
+    pub fn take_executable(&mut self, id: u64, now_slot: u64) -> Result<ProposalAction> {
+        let slot = self
+            .proposals
+            .iter_mut()
+            .find(|p| p.is_some_and(|p| p.id == id))
+            .ok_or(RiskError::AccountNotFound)?;
+        let proposal = slot.ok_or(RiskError::AccountNotFound)?;
+        match proposal.eta_slot {
+            Some(eta) if now_slot >= eta => {
+                *slot = None;
+                Ok(proposal.action)
+            }
+            _ => Err(RiskError::Unauthorized), // TimelockPending
+        }
+    }
+
+    /// Install a new config; approvals gathered under the old signer set
+    /// no longer count
+    pub fn replace_config(&mut self, config: GovernanceConfig) -> Result<()> {
+        config.validate()?;
+        self.config = config;
+        for proposal in self.proposals.iter_mut().flatten() {
+            proposal.approvals = 0;
+            proposal.eta_slot = None;
+        }
+        Ok(())
+    }
+
+    fn find_mut(&mut self, id: u64) -> Result<&mut Proposal> {
+        self.proposals
+            .iter_mut()
+            .flatten()
+            .find(|p| p.id == id)
+            .ok_or(RiskError::AccountNotFound)
+    }
+
+    fn check_signature<V: SignatureVerifier>(
+        &self,
+        verifier: &V,
+        vote: GovernanceVote,
+        id: u64,
+        signer: u8,
+        signature: &[u8; 64],
+    ) -> Result<()> {
+        let pubkey = self
+            .config
+            .signers()
+            .get(signer as usize)
+            .ok_or(RiskError::Unauthorized)?;
+        if !verifier.verify(pubkey, &vote.encode(id), signature) {
+            return Err(RiskError::Unauthorized); // BadSignature
+        }
+        Ok(())
+    }
+
+    fn record_approval(config: &GovernanceConfig, proposal: &mut Proposal, signer: u8, now_slot: u64) {
+        proposal.approvals |= 1 << signer;
+        if proposal.eta_slot.is_none() && proposal.approval_count() >= config.threshold as u32 {
+            proposal.eta_slot = Some(now_slot.saturating_add(config.timelock_slots));
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Hedging: agent-requested offsets of LP inventory on external venues
+//!
+//! The agent asks for a hedge of N contracts through `RiskActions::hedge`.
+//! The protocol checks the request against LP inventory, routes it to a
+//! `HedgeExecutor` (venue adapter) and books the fill as a hedge position.
+
+use crate::{RiskEngine, RiskError, Result, MAX_ORACLE_PRICE, MAX_POSITION_ABS};
+
+use super::saturating_abs_i128;
+
+/// Fill reported by an external venue
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HedgeFill {
+    /// Filled size (positive = bought, negative = sold)
+    pub size: i128,
+
+    /// Average fill price
+    pub price: u64,
+}
+
+/// Adapter for an external hedging venue
+pub trait HedgeExecutor {
+    /// Execute a hedge order of `size` contracts on the venue
+    ///
+    /// # Returns
+    /// * `Ok(HedgeFill)` - Actual fill (may be partial, never larger than `size`)
+    /// * `Err(RiskError)` - Venue rejected or failed the order
+    fn execute_hedge(&mut self, size: i128, oracle_price: u64) -> Result<HedgeFill>;
+}
+
+/// Hedge position held on external venues
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HedgePosition {
+    /// Net hedge size (+ long, - short)
+    pub size: i128,
+
+    /// Volume-weighted entry price of the open hedge
+    pub entry_price: u64,
+
+    /// PnL realized by reducing or flipping the hedge
+    pub realized_pnl: i128,
+}
+
+impl HedgePosition {
+    /// Book a venue fill into the position
+    pub fn apply_fill(&mut self, fill: HedgeFill) -> Result<()> {
+        if fill.size == 0 {
+            return Ok(());
+        }
+        let old = self.size;
+        let new = old.checked_add(fill.size).ok_or(RiskError::Overflow)?;
+
+        if old == 0 || (old > 0) == (fill.size > 0) {
+            // Increase: weighted average entry
+            let old_abs = saturating_abs_i128(old) as u128;
+            let fill_abs = saturating_abs_i128(fill.size) as u128;
+            let weighted = old_abs
+                .checked_mul(self.entry_price as u128)
+                .and_then(|v| v.checked_add(fill_abs.checked_mul(fill.price as u128)?))
+                .ok_or(RiskError::Overflow)?;
+            self.entry_price = (weighted / (old_abs + fill_abs)) as u64;
+        } else {
+            // Reduce (and possibly flip): realize PnL on the closed part
+            let closed_abs = saturating_abs_i128(old).min(saturating_abs_i128(fill.size));
+            let closed = if old > 0 { closed_abs } else { -closed_abs };
+            let pnl = RiskEngine::mark_pnl_for_position(closed, self.entry_price, fill.price)?;
+            self.realized_pnl = self.realized_pnl.saturating_add(pnl);
+            if new == 0 {
+                self.entry_price = 0;
+            } else if (new > 0) != (old > 0) {
+                self.entry_price = fill.price;
+            }
+        }
+
+        self.size = new;
+        Ok(())
+    }
+
+    /// Unrealized PnL of the open hedge at `oracle_price`
+    pub fn unrealized_pnl(&self, oracle_price: u64) -> Result<i128> {
+        RiskEngine::mark_pnl_for_position(self.size, self.entry_price, oracle_price)
+    }
+}
+
+/// Validate a hedge request against LP inventory
+///
+/// A hedge may only offset inventory: the resulting hedge must sit on the
+/// opposite side of `lp_inventory` and never exceed it in size.
+pub fn validate_hedge_request(lp_inventory: i128, current: i128, size: i128) -> Result<()> {
+    if size == 0 || size == i128::MIN {
+        return Err(RiskError::Overflow);
+    }
+    if saturating_abs_i128(size) as u128 > MAX_POSITION_ABS {
+        return Err(RiskError::Overflow);
+    }
+
+    let new = current.checked_add(size).ok_or(RiskError::Overflow)?;
+    if new != 0 && (lp_inventory == 0 || (new > 0) == (lp_inventory > 0)) {
+        return Err(RiskError::Unauthorized);
+    }
+    if saturating_abs_i128(new) > saturating_abs_i128(lp_inventory) {
+        return Err(RiskError::Unauthorized);
+    }
+
+    Ok(())
+}
+
+/// Validate a venue fill against the request that produced it
+pub fn validate_hedge_fill(requested: i128, fill: &HedgeFill) -> Result<()> {
+    if fill.price == 0 || fill.price > MAX_ORACLE_PRICE {
+        return Err(RiskError::InvalidMatchingEngine);
+    }
+    if fill.size == 0 {
+        return Ok(());
+    }
+    if fill.size == i128::MIN
+        || (fill.size > 0) != (requested > 0)
+        || saturating_abs_i128(fill.size) > saturating_abs_i128(requested)
+    {
+        return Err(RiskError::InvalidMatchingEngine);
+    }
+    Ok(())
+}
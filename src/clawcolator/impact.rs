@@ -0,0 +1,81 @@
+//! Price impact: how far a trade of a given size may move the price
+//!
+//! Impact grows with trade notional relative to available liquidity (the
+//! agent LP's equity). The engine uses it to cap how far in the LP's favour
+//! the agent may price a fill (oracle plus spread plus impact), and agents
+//! read the same model through `AgentContext::impact_price`.
+
+use super::math::{self, apply_bps, saturating_mul_div, Rounding, BPS};
+use crate::{RiskError, Result};
+
+/// Shape of the impact function
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImpactCurve {
+    /// Impact proportional to size / liquidity
+    Linear,
+
+    /// Impact proportional to sqrt(size / liquidity)
+    SquareRoot,
+}
+
+/// Configurable price-impact model
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImpactModel {
+    /// Impact function shape
+    pub curve: ImpactCurve,
+
+    /// Impact (bps) when trade notional equals available liquidity
+    pub coefficient_bps: u64,
+
+    /// Upper bound on impact regardless of size (bps)
+    pub max_impact_bps: u64,
+}
+
+/// Default model: square-root impact, 1% at full liquidity, capped at 5%
+pub const DEFAULT_IMPACT_MODEL: ImpactModel = ImpactModel {
+    curve: ImpactCurve::SquareRoot,
+    coefficient_bps: 100,
+    max_impact_bps: 500,
+};
+
+impl ImpactModel {
+    /// Check the model is usable
+    pub fn validate(&self) -> Result<()> {
+        if self.max_impact_bps >= 10_000 || self.coefficient_bps > self.max_impact_bps {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+
+    /// Impact (bps) of trading `notional` against `liquidity`
+    pub fn impact_bps(&self, notional: u128, liquidity: u128) -> u64 {
+        if notional == 0 {
+            return 0;
+        }
+        let Some(ratio_bps) = saturating_mul_div(notional, BPS, liquidity, Rounding::Down) else {
+            return self.max_impact_bps;
+        };
+        let scaled_bps = match self.curve {
+            ImpactCurve::Linear => ratio_bps,
+            ImpactCurve::SquareRoot => ratio_bps.saturating_mul(BPS).isqrt(),
+        };
+        let impact = apply_bps(scaled_bps, self.coefficient_bps, Rounding::Down);
+        impact.min(self.max_impact_bps as u128) as u64
+    }
+
+    /// Oracle price moved by the impact of `size` (up for buys, down for
+    /// sells), rounded away from the oracle
+    pub fn impact_price(&self, oracle_price: u64, size: i128, liquidity: u128) -> u64 {
+        let notional = math::notional(size.unsigned_abs(), oracle_price, Rounding::Down);
+        offset_price(oracle_price, size, self.impact_bps(notional, liquidity))
+    }
+}
+
+/// `oracle_price` moved `bps` against the taker of `size`
+pub(crate) fn offset_price(oracle_price: u64, size: i128, bps: u64) -> u64 {
+    if size >= 0 {
+        math::offset_bps(oracle_price, bps, true, Rounding::Up)
+    } else {
+        math::offset_bps(oracle_price, bps, false, Rounding::Down)
+    }
+}
@@ -0,0 +1,268 @@
+//! Off-chain indexer: derived tables from the market event stream
+//!
+//! Gated behind the `indexer` feature, which links `std` and serde. An
+//! `Indexer` consumes `MarketEvent`s, either straight from the engine as
+//! an `EventSink` or from program logs through `apply_log`, and keeps the
+//! tables dashboards chart: each account's fills, open interest after each
+//! slot that changed it, the funding rate the agent published over time and
+//! every liquidation. Positions are rebuilt from fills and liquidations, so
+//! the indexer must see the stream from the market's first event; accounts
+//! are keyed by index, and a reused index continues the previous history.
+//!
+//! `export` snapshots every table as one serde value; amounts that do not
+//! fit in 64 bits are written as decimal strings, as in `config`.
+
+use serde::Serialize;
+use std::{string::String, vec::Vec};
+
+use super::{EventSink, LiquidationEvent, MarketEvent};
+use crate::Result;
+
+/// One fill from an account's side
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct AccountTrade {
+    pub slot: u64,
+    pub price: u64,
+
+    /// Signed from this account's side (positive = bought)
+    #[serde(serialize_with = "signed_amount")]
+    pub size: i128,
+
+    /// The account was the maker (LP) side of the fill
+    pub maker: bool,
+
+    /// Index of the other side
+    pub counterparty: u16,
+}
+
+/// Open interest (sum of long positions) after `slot`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct OpenInterestPoint {
+    pub slot: u64,
+
+    #[serde(serialize_with = "amount")]
+    pub open_interest: u128,
+}
+
+/// Funding rate the agent published from `slot` on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct FundingPoint {
+    pub slot: u64,
+    pub rate_bps_per_slot: i64,
+}
+
+/// Liquidation as exported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct LiquidationRecord {
+    pub slot: u64,
+    pub idx: u16,
+    pub account_id: u64,
+    #[serde(serialize_with = "signed_amount")]
+    pub size: i128,
+    #[serde(serialize_with = "signed_amount")]
+    pub remaining: i128,
+    pub oracle_price: u64,
+}
+
+impl From<LiquidationEvent> for LiquidationRecord {
+    fn from(event: LiquidationEvent) -> Self {
+        Self {
+            slot: event.slot,
+            idx: event.idx,
+            account_id: event.account_id,
+            size: event.size,
+            remaining: event.remaining,
+            oracle_price: event.oracle_price,
+        }
+    }
+}
+
+/// One account's table
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct AccountHistory {
+    pub idx: u16,
+
+    /// Position implied by the events so far
+    #[serde(serialize_with = "signed_amount")]
+    pub position: i128,
+
+    #[serde(serialize_with = "amount")]
+    pub volume: u128,
+
+    pub trades: Vec<AccountTrade>,
+}
+
+/// Every table at one point of the stream
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct IndexExport {
+    /// Events applied
+    pub events: u64,
+
+    /// Slot of the last event
+    pub last_slot: u64,
+
+    pub accounts: Vec<AccountHistory>,
+    pub open_interest: Vec<OpenInterestPoint>,
+    pub funding: Vec<FundingPoint>,
+    pub liquidations: Vec<LiquidationRecord>,
+}
+
+/// Derived tables over a market's event stream
+#[derive(Clone, Debug, Default)]
+pub struct Indexer {
+    events: u64,
+    last_slot: u64,
+    accounts: Vec<Option<AccountHistory>>,
+    open_interest: u128,
+    oi_series: Vec<OpenInterestPoint>,
+    funding: Vec<FundingPoint>,
+    liquidations: Vec<LiquidationRecord>,
+}
+
+impl Indexer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold one event into the tables
+    pub fn apply(&mut self, event: &MarketEvent) {
+        self.events += 1;
+        match event {
+            MarketEvent::Trade(fill) => {
+                let volume = fill.size.unsigned_abs();
+                for (idx, size, maker, counterparty) in [
+                    (fill.user_idx, fill.size, false, fill.lp_idx),
+                    (fill.lp_idx, fill.size.saturating_neg(), true, fill.user_idx),
+                ] {
+                    let position = self.account(idx).position;
+                    self.set_position(idx, position.saturating_add(size));
+                    let account = self.account(idx);
+                    account.volume = account.volume.saturating_add(volume);
+                    account.trades.push(AccountTrade {
+                        slot: fill.slot,
+                        price: fill.price,
+                        size,
+                        maker,
+                        counterparty,
+                    });
+                }
+                self.record_open_interest(fill.slot);
+            }
+            MarketEvent::Liquidation(event) => {
+                // The closed part went to the LP or insurance; only this
+                // side's position is known, so open interest follows it
+                self.set_position(event.idx, event.remaining);
+                self.liquidations.push((*event).into());
+                self.record_open_interest(event.slot);
+            }
+            MarketEvent::ParamsChanged(params) => {
+                self.last_slot = self.last_slot.max(params.slot);
+                let rate = params.funding_rate_bps_per_slot;
+                if self.funding.last().is_none_or(|last| last.rate_bps_per_slot != rate) {
+                    self.funding.push(FundingPoint { slot: params.slot, rate_bps_per_slot: rate });
+                }
+            }
+        }
+    }
+
+    /// Fold one logged event (a decoded `Program data` payload)
+    pub fn apply_log(&mut self, data: &[u8]) -> Result<()> {
+        self.apply(&MarketEvent::decode(data)?);
+        Ok(())
+    }
+
+    /// Events applied so far
+    pub fn events(&self) -> u64 {
+        self.events
+    }
+
+    /// Fills of account `idx`, oldest first
+    pub fn account_trades(&self, idx: u16) -> &[AccountTrade] {
+        self.history(idx).map_or(&[], |account| &account.trades)
+    }
+
+    /// Position of account `idx` implied by the events so far
+    pub fn position(&self, idx: u16) -> i128 {
+        self.history(idx).map_or(0, |account| account.position)
+    }
+
+    /// Open interest now
+    pub fn open_interest(&self) -> u128 {
+        self.open_interest
+    }
+
+    /// Open interest after each slot that changed it
+    pub fn open_interest_series(&self) -> &[OpenInterestPoint] {
+        &self.oi_series
+    }
+
+    /// Funding rate changes, oldest first
+    pub fn funding_history(&self) -> &[FundingPoint] {
+        &self.funding
+    }
+
+    pub fn liquidations(&self) -> &[LiquidationRecord] {
+        &self.liquidations
+    }
+
+    /// Snapshot of every table
+    pub fn export(&self) -> IndexExport {
+        IndexExport {
+            events: self.events,
+            last_slot: self.last_slot,
+            accounts: self.accounts.iter().flatten().cloned().collect(),
+            open_interest: self.oi_series.clone(),
+            funding: self.funding.clone(),
+            liquidations: self.liquidations.clone(),
+        }
+    }
+
+    /// `export` as JSON
+    pub fn export_json(&self) -> String {
+        serde_json::to_string(&self.export()).unwrap_or_default()
+    }
+
+    fn history(&self, idx: u16) -> Option<&AccountHistory> {
+        self.accounts.get(idx as usize)?.as_ref()
+    }
+
+    fn account(&mut self, idx: u16) -> &mut AccountHistory {
+        let i = idx as usize;
+        if self.accounts.len() <= i {
+            self.accounts.resize(i + 1, None);
+        }
+        self.accounts[i].get_or_insert_with(|| AccountHistory { idx, ..AccountHistory::default() })
+    }
+
+    /// Move `idx` to `position`, keeping the long total in step
+    fn set_position(&mut self, idx: u16, position: i128) {
+        let account = self.account(idx);
+        let old = account.position;
+        account.position = position;
+        let long = |p: i128| p.max(0) as u128;
+        self.open_interest = self.open_interest.saturating_sub(long(old)).saturating_add(long(position));
+    }
+
+    fn record_open_interest(&mut self, slot: u64) {
+        self.last_slot = self.last_slot.max(slot);
+        let point = OpenInterestPoint { slot, open_interest: self.open_interest };
+        match self.oi_series.last_mut() {
+            Some(last) if last.slot == slot => *last = point,
+            _ => self.oi_series.push(point),
+        }
+    }
+}
+
+impl EventSink for Indexer {
+    fn emit(&mut self, event: &MarketEvent) {
+        self.apply(event);
+    }
+}
+
+fn amount<S: serde::Serializer>(value: &u128, s: S) -> core::result::Result<S::Ok, S::Error> {
+    s.collect_str(value)
+}
+
+fn signed_amount<S: serde::Serializer>(value: &i128, s: S) -> core::result::Result<S::Ok, S::Error> {
+    s.collect_str(value)
+}
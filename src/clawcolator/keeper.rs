@@ -0,0 +1,147 @@
+//! Keeper incentives: crank rewards and liquidation bounties
+//!
+//! Rewards accrue to the calling account's index as claims against the
+//! insurance fund. Nothing leaves insurance until the keeper claims, so
+//! accrued rewards never inflate the haircut residual. Accrual is capped
+//! per crank and by insurance above `insurance_floor` net of outstanding
+//! claims.
+
+use crate::{RiskEngine, RiskError, Result, MAX_ACCOUNTS, U128};
+
+/// Keeper reward schedule (all zero = incentives disabled)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeeperRewardParams {
+    /// Paid for a crank that advances the engine
+    pub crank_reward: u128,
+
+    /// Paid per account liquidated during a crank
+    pub liquidation_bounty: u128,
+
+    /// Maximum total reward accrued by a single crank
+    pub max_reward_per_crank: u128,
+
+    /// Insurance balance that rewards may never draw below
+    pub insurance_floor: u128,
+}
+
+/// Per-keeper claim ledger
+pub struct KeeperLedger {
+    /// Unclaimed rewards per keeper account index
+    pending: [u128; MAX_ACCOUNTS],
+
+    /// Sum of `pending`
+    pending_total: u128,
+
+    /// Rewards claimed to date
+    paid_total: u128,
+}
+
+impl KeeperLedger {
+    pub const fn new() -> Self {
+        Self {
+            pending: [0; MAX_ACCOUNTS],
+            pending_total: 0,
+            paid_total: 0,
+        }
+    }
+
+    /// Unclaimed rewards of `keeper_idx`
+    pub fn pending(&self, keeper_idx: u16) -> u128 {
+        self.pending.get(keeper_idx as usize).copied().unwrap_or(0)
+    }
+
+    /// Unclaimed rewards across all keepers
+    pub fn pending_total(&self) -> u128 {
+        self.pending_total
+    }
+
+    /// Rewards claimed to date
+    pub fn paid_total(&self) -> u128 {
+        self.paid_total
+    }
+
+    /// Accrue rewards for one crank; returns the amount accrued
+    pub(crate) fn accrue_crank(
+        &mut self,
+        engine: &RiskEngine,
+        params: &KeeperRewardParams,
+        keeper_idx: u16,
+        advanced: bool,
+        num_liquidations: u32,
+    ) -> u128 {
+        if keeper_idx as usize >= MAX_ACCOUNTS || !engine.is_used(keeper_idx as usize) {
+            return 0;
+        }
+        let base = if advanced { params.crank_reward } else { 0 };
+        let bounties = params.liquidation_bounty.saturating_mul(num_liquidations as u128);
+        let available = engine
+            .insurance_fund
+            .balance
+            .get()
+            .saturating_sub(params.insurance_floor)
+            .saturating_sub(self.pending_total);
+        let reward = base
+            .saturating_add(bounties)
+            .min(params.max_reward_per_crank)
+            .min(available);
+
+        self.pending[keeper_idx as usize] += reward;
+        self.pending_total += reward;
+        reward
+    }
+
+    /// Drop claims of accounts that no longer exist (slot may be reused)
+    pub(crate) fn forfeit_closed(&mut self, engine: &RiskEngine) {
+        if self.pending_total == 0 {
+            return;
+        }
+        for (idx, pending) in self.pending.iter_mut().enumerate() {
+            if *pending > 0 && !engine.is_used(idx) {
+                self.pending_total -= *pending;
+                *pending = 0;
+            }
+        }
+    }
+
+    /// Follow a keeper moved by compaction into the free slot `to`; a stale
+    /// claim left in `to` goes to `from` and is forfeited as usual
+    pub(crate) fn move_account(&mut self, from: u16, to: u16) {
+        self.pending.swap(from as usize, to as usize);
+    }
+
+    /// Pay `keeper_idx`'s claim from insurance into its capital
+    ///
+    /// Pays at most the insurance above `insurance_floor`; any remainder
+    /// stays pending. Returns the amount paid.
+    pub(crate) fn claim(
+        &mut self,
+        engine: &mut RiskEngine,
+        params: &KeeperRewardParams,
+        keeper_idx: u16,
+    ) -> Result<u128> {
+        if keeper_idx as usize >= MAX_ACCOUNTS || !engine.is_used(keeper_idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let idx = keeper_idx as usize;
+        let insurance = engine.insurance_fund.balance.get();
+        let pay = self.pending[idx].min(insurance.saturating_sub(params.insurance_floor));
+        if pay == 0 {
+            return Ok(0);
+        }
+
+        engine.insurance_fund.balance = U128::new(insurance - pay);
+        let capital = engine.accounts[idx].capital.get();
+        engine.set_capital(idx, capital.checked_add(pay).ok_or(RiskError::Overflow)?);
+
+        self.pending[idx] -= pay;
+        self.pending_total -= pay;
+        self.paid_total = self.paid_total.saturating_add(pay);
+        Ok(pay)
+    }
+}
+
+impl Default for KeeperLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,254 @@
+//! Margin lending from the LP reserve
+//!
+//! Capital the agent's LP account does not need to margin its inventory can
+//! be lent to traders as extra margin. A loan moves capital from the LP
+//! account to the borrower and is tracked here as debt: principal plus
+//! interest accrued every slot at the agent's rate (`LendingPolicy`, bounded
+//! by `LendingBounds`). Repayments move capital back, interest first.
+//!
+//! Debt is not netted out of the borrower's equity, so `max_borrow_bps`
+//! caps each loan against the borrower's own capital. A flat borrower whose
+//! capital no longer covers its debt, or an account closed with debt
+//! outstanding, is settled by the crank: its capital repays what it can,
+//! insurance above `insurance_floor` covers the rest, and anything beyond
+//! that is written off against the LP.
+
+use crate::{RiskError, Result, MAX_ACCOUNTS};
+
+/// Agent-tunable lending policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LendingPolicy {
+    /// Interest charged on outstanding debt per slot (bps)
+    pub interest_bps_per_slot: u64,
+
+    /// Largest share of the reserve (LP capital plus loans) lent out (bps,
+    /// 0 = lending off)
+    pub max_utilization_bps: u64,
+
+    /// Largest debt per borrower relative to its own capital (bps)
+    pub max_borrow_bps: u64,
+}
+
+/// Default policy: nothing is lent
+pub const DEFAULT_LENDING_POLICY: LendingPolicy = LendingPolicy {
+    interest_bps_per_slot: 0,
+    max_utilization_bps: 0,
+    max_borrow_bps: 0,
+};
+
+/// Protocol bounds on the agent's lending policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LendingBounds {
+    /// Highest interest rate the agent may set (bps per slot)
+    pub max_interest_bps_per_slot: u64,
+
+    /// Highest reserve utilization the agent may allow (bps)
+    pub max_utilization_bps: u64,
+
+    /// Highest debt-to-own-capital ratio the agent may allow (bps)
+    pub max_borrow_bps: u64,
+
+    /// Insurance balance the backstop may never draw below
+    pub insurance_floor: u128,
+}
+
+impl Default for LendingBounds {
+    fn default() -> Self {
+        Self {
+            max_interest_bps_per_slot: 10,
+            max_utilization_bps: 8_000,
+            max_borrow_bps: 10_000, // Borrow at most as much as you own
+            insurance_floor: 0,
+        }
+    }
+}
+
+impl LendingPolicy {
+    /// Check the policy against protocol bounds
+    pub fn validate(&self, bounds: &LendingBounds) -> Result<()> {
+        if self.interest_bps_per_slot > bounds.max_interest_bps_per_slot
+            || self.max_utilization_bps > bounds.max_utilization_bps
+            || self.max_borrow_bps > bounds.max_borrow_bps
+        {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+
+    /// Whether new loans may be taken out
+    pub fn enabled(&self) -> bool {
+        self.max_utilization_bps > 0 && self.max_borrow_bps > 0
+    }
+}
+
+/// Outstanding loan of one account
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Loan {
+    /// Id of the borrowing account (guards against slot reuse)
+    pub account_id: u64,
+
+    /// Capital lent and not yet repaid
+    pub principal: u128,
+
+    /// Interest accrued and not yet paid
+    pub interest: u128,
+}
+
+impl Loan {
+    /// Principal plus accrued interest
+    pub fn debt(&self) -> u128 {
+        self.principal.saturating_add(self.interest)
+    }
+}
+
+/// Lending totals since creation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LendingTotals {
+    /// Principal outstanding
+    pub principal: u128,
+
+    /// Interest accrued and not yet paid
+    pub interest: u128,
+
+    /// Interest paid to the LP
+    pub interest_paid: u128,
+
+    /// Debt of defaulted borrowers covered by insurance
+    pub insurance_covered: u128,
+
+    /// Debt of defaulted borrowers written off against the LP
+    pub written_off: u128,
+}
+
+/// Per-account loan ledger
+pub struct LendingBook {
+    loans: [Loan; MAX_ACCOUNTS],
+    totals: LendingTotals,
+    last_accrual_slot: u64,
+}
+
+impl LendingBook {
+    pub const fn new() -> Self {
+        Self {
+            loans: [Loan {
+                account_id: 0,
+                principal: 0,
+                interest: 0,
+            }; MAX_ACCOUNTS],
+            totals: LendingTotals {
+                principal: 0,
+                interest: 0,
+                interest_paid: 0,
+                insurance_covered: 0,
+                written_off: 0,
+            },
+            last_accrual_slot: 0,
+        }
+    }
+
+    /// Outstanding loan in slot `idx`
+    pub fn loan(&self, idx: u16) -> Option<&Loan> {
+        self.loans.get(idx as usize).filter(|l| l.debt() > 0)
+    }
+
+    /// Outstanding loans with their slots
+    pub fn loans(&self) -> impl Iterator<Item = (u16, &Loan)> {
+        self.loans
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| l.debt() > 0)
+            .map(|(idx, l)| (idx as u16, l))
+    }
+
+    /// Debt of the account `account_id` in slot `idx`
+    pub fn debt(&self, idx: u16, account_id: u64) -> u128 {
+        self.loan(idx)
+            .filter(|l| l.account_id == account_id)
+            .map_or(0, Loan::debt)
+    }
+
+    pub fn totals(&self) -> &LendingTotals {
+        &self.totals
+    }
+
+    /// Principal lent over the reserve (`lp_capital` plus principal), in bps
+    pub fn utilization_bps(&self, lp_capital: u128) -> u64 {
+        let reserve = lp_capital.saturating_add(self.totals.principal);
+        if reserve == 0 {
+            return 0;
+        }
+        (self.totals.principal.saturating_mul(10_000) / reserve) as u64
+    }
+
+    /// Charge interest on every loan for the slots since the last accrual
+    ///
+    /// Interest rounds up, so a loan never accrues less than the rate.
+    pub(crate) fn accrue(&mut self, now_slot: u64, interest_bps_per_slot: u64) {
+        let slots = now_slot.saturating_sub(self.last_accrual_slot);
+        self.last_accrual_slot = self.last_accrual_slot.max(now_slot);
+        if slots == 0 || interest_bps_per_slot == 0 {
+            return;
+        }
+        let rate = (interest_bps_per_slot as u128).saturating_mul(slots as u128);
+        for loan in self.loans.iter_mut().filter(|l| l.debt() > 0) {
+            let interest = loan.debt().saturating_mul(rate).div_ceil(10_000);
+            loan.interest = loan.interest.saturating_add(interest);
+            self.totals.interest = self.totals.interest.saturating_add(interest);
+        }
+    }
+
+    /// Add `amount` to the loan of `account_id` in slot `idx`
+    pub(crate) fn lend(&mut self, idx: u16, account_id: u64, amount: u128) -> Result<()> {
+        let loan = self.loans.get_mut(idx as usize).ok_or(RiskError::AccountNotFound)?;
+        if loan.debt() > 0 && loan.account_id != account_id {
+            return Err(RiskError::Unauthorized); // Previous holder's loan unsettled
+        }
+        loan.account_id = account_id;
+        loan.principal = loan.principal.checked_add(amount).ok_or(RiskError::Overflow)?;
+        self.totals.principal = self.totals.principal.saturating_add(amount);
+        Ok(())
+    }
+
+    /// Follow a borrower moved by compaction into the free slot `to`; an
+    /// unsettled loan of a closed account in `to` goes to `from` and
+    /// defaults there as it would have
+    pub(crate) fn move_account(&mut self, from: u16, to: u16) {
+        self.loans.swap(from as usize, to as usize);
+    }
+
+    /// Apply a repayment of `amount` (at most the debt), interest first;
+    /// returns the interest part
+    pub(crate) fn repay(&mut self, idx: u16, amount: u128) -> u128 {
+        let Some(loan) = self.loans.get_mut(idx as usize) else {
+            return 0;
+        };
+        let interest = amount.min(loan.interest);
+        let principal = amount.saturating_sub(interest).min(loan.principal);
+        loan.interest -= interest;
+        loan.principal -= principal;
+        self.totals.interest = self.totals.interest.saturating_sub(interest);
+        self.totals.principal = self.totals.principal.saturating_sub(principal);
+        self.totals.interest_paid = self.totals.interest_paid.saturating_add(interest);
+        interest
+    }
+
+    /// Clear a defaulted loan after `repaid` from the borrower's capital
+    /// and `covered` from insurance; the rest is written off
+    pub(crate) fn default_loan(&mut self, idx: u16, repaid: u128, covered: u128) {
+        self.repay(idx, repaid.saturating_add(covered));
+        let Some(loan) = self.loans.get_mut(idx as usize) else {
+            return;
+        };
+        self.totals.insurance_covered = self.totals.insurance_covered.saturating_add(covered);
+        self.totals.written_off = self.totals.written_off.saturating_add(loan.debt());
+        self.totals.principal = self.totals.principal.saturating_sub(loan.principal);
+        self.totals.interest = self.totals.interest.saturating_sub(loan.interest);
+        *loan = Loan::default();
+    }
+}
+
+impl Default for LendingBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,191 @@
+//! Dutch-auction liquidation of bankrupt positions
+//!
+//! Closing a bankrupt account at the oracle prices its position at a mark
+//! nobody traded at and books the whole deficit at once. With auctions on,
+//! the engine instead holds the account (`RiskEngine::is_liquidation_held`)
+//! and offers its position as a lot: the price starts at the oracle and
+//! improves for the taker by `max_discount_bps` over `duration_slots`. Any
+//! solvent account can take part or all of the lot at the current price;
+//! whatever is left when the auction ends goes to the LP at the final
+//! price. Only then is the account's remaining loss written off, and the
+//! insurance fund covers that shortfall before it reaches the haircut.
+
+use crate::{Result, RiskError};
+
+/// Lots that can be auctioned at once
+pub const MAX_LIQUIDATION_AUCTIONS: usize = 16;
+
+/// Auction schedule
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LiquidationAuctionParams {
+    /// Slots from the oracle price to the full discount
+    pub duration_slots: u64,
+
+    /// Discount to the oracle at the end of the auction (bps)
+    pub max_discount_bps: u64,
+}
+
+impl Default for LiquidationAuctionParams {
+    fn default() -> Self {
+        Self {
+            duration_slots: 10,
+            max_discount_bps: 500,
+        }
+    }
+}
+
+impl LiquidationAuctionParams {
+    pub fn validate(&self) -> Result<()> {
+        if self.duration_slots == 0 || self.max_discount_bps >= 10_000 {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+}
+
+/// Position of a bankrupt account on offer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LiquidationLot {
+    /// Bankrupt account
+    pub idx: u16,
+
+    /// Its account id (guards against slot reuse)
+    pub account_id: u64,
+
+    /// Size still on offer, with the sign of the bankrupt position
+    pub size: i128,
+
+    /// Slot the auction opened
+    pub start_slot: u64,
+
+    /// Schedule in force when the auction opened
+    pub params: LiquidationAuctionParams,
+}
+
+impl LiquidationLot {
+    /// Offer price at `now_slot` against `oracle_price`: a long lot is sold
+    /// below the oracle, a short lot bought back above it
+    pub fn price_at(&self, now_slot: u64, oracle_price: u64) -> u64 {
+        let params = &self.params;
+        let elapsed = now_slot.saturating_sub(self.start_slot).min(params.duration_slots);
+        let discount_bps = params.max_discount_bps as u128 * elapsed as u128 / params.duration_slots as u128;
+        let discount = (oracle_price as u128 * discount_bps / 10_000) as u64;
+        if self.size > 0 {
+            oracle_price.saturating_sub(discount).max(1)
+        } else {
+            oracle_price.saturating_add(discount)
+        }
+    }
+
+    /// Whether the auction has run its course
+    pub fn ended(&self, now_slot: u64) -> bool {
+        now_slot >= self.start_slot.saturating_add(self.params.duration_slots)
+    }
+}
+
+/// One taker fill of a lot
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AuctionFill {
+    /// Price the slice changed hands at
+    pub price: u64,
+
+    /// Size taken (sign of the bankrupt position)
+    pub size: i128,
+
+    /// PnL the taker gained against the oracle
+    pub discount: i128,
+}
+
+/// Lifetime auction totals
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AuctionTotals {
+    /// Lots opened
+    pub lots: u64,
+
+    /// Absolute size taken by bidders
+    pub taken_by_bidders: u128,
+
+    /// Absolute size left to the LP when auctions ended
+    pub taken_by_lp: u128,
+
+    /// Bankrupt losses the insurance fund covered
+    pub insurance_covered: u128,
+
+    /// Bankrupt losses insurance could not cover (left to the haircut)
+    pub uncovered: u128,
+}
+
+/// Open lots, keyed by account
+pub struct LiquidationAuctions {
+    lots: [Option<LiquidationLot>; MAX_LIQUIDATION_AUCTIONS],
+    totals: AuctionTotals,
+}
+
+impl Default for LiquidationAuctions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LiquidationAuctions {
+    pub const fn new() -> Self {
+        Self {
+            lots: [None; MAX_LIQUIDATION_AUCTIONS],
+            totals: AuctionTotals {
+                lots: 0,
+                taken_by_bidders: 0,
+                taken_by_lp: 0,
+                insurance_covered: 0,
+                uncovered: 0,
+            },
+        }
+    }
+
+    /// Lot of account `idx`, if one is open
+    pub fn lot(&self, idx: u16) -> Option<&LiquidationLot> {
+        self.lots.iter().flatten().find(|lot| lot.idx == idx)
+    }
+
+    /// Open lots
+    pub fn iter(&self) -> impl Iterator<Item = &LiquidationLot> {
+        self.lots.iter().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lots.iter().all(Option::is_none)
+    }
+
+    pub fn totals(&self) -> &AuctionTotals {
+        &self.totals
+    }
+
+    pub(crate) fn lot_mut(&mut self, idx: u16) -> Option<&mut LiquidationLot> {
+        self.lots.iter_mut().flatten().find(|lot| lot.idx == idx)
+    }
+
+    pub(crate) fn totals_mut(&mut self) -> &mut AuctionTotals {
+        &mut self.totals
+    }
+
+    /// Open a lot; false when every slot is taken
+    pub(crate) fn open(&mut self, lot: LiquidationLot) -> bool {
+        let Some(free) = self.lots.iter_mut().find(|l| l.is_none()) else {
+            return false;
+        };
+        *free = Some(lot);
+        self.totals.lots += 1;
+        true
+    }
+
+    pub(crate) fn close(&mut self, idx: u16) {
+        for slot in self.lots.iter_mut() {
+            if slot.is_some_and(|lot| lot.idx == idx) {
+                *slot = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Liquidation candidates bucketed by liquidation price
+//!
+//! The engine's crank sweeps the account slab `ACCOUNTS_PER_CRANK` at a
+//! time, so with many accounts an underwater one can wait several cranks
+//! to be visited. This index files every account with a position under the
+//! bucket of its liquidation price, longs and shorts apart, in O(1) per
+//! update. At a given oracle price only the buckets the price has crossed
+//! can hold liquidatable accounts: longs at or above the price's bucket,
+//! shorts at or below it. The crank checks just those.
+//!
+//! Buckets are geometric, four per power of two (~19% wide), so an entry
+//! stays valid across ordinary price moves. Trades and liquidations refile
+//! their accounts at once; any other change to an account marks it stale,
+//! and the next crank refiles stale accounts together with every account
+//! its sweep settled (fees, funding, warmup and haircut moves land there).
+//! Only raw engine access, which cannot be tracked, asks for a full
+//! rebuild. The engine sweep itself no longer liquidates: the crank's
+//! liquidation pass takes its accounts from here and re-checks each
+//! candidate exactly.
+
+use crate::MAX_ACCOUNTS;
+
+/// Buckets per side (four per power of two over the u64 price range)
+pub const LIQ_BUCKET_COUNT: usize = 256;
+
+/// Most candidates the crank liquidates from the index per call
+pub const LIQ_CANDIDATES_PER_CRANK: usize = 32;
+
+const NIL: u16 = u16::MAX;
+
+const STALE_WORDS: usize = MAX_ACCOUNTS.div_ceil(64);
+
+/// Bucket of a price
+pub fn bucket_of(price: u64) -> usize {
+    if price < 4 {
+        return price as usize;
+    }
+    let log2 = 63 - price.leading_zeros() as usize;
+    let mantissa = ((price >> (log2 - 2)) & 3) as usize;
+    log2 * 4 + mantissa
+}
+
+/// Accounts filed by side and liquidation-price bucket
+pub struct LiquidationIndex {
+    /// Side and bucket per account (`NIL` when not filed)
+    entries: [u16; MAX_ACCOUNTS],
+    next: [u16; MAX_ACCOUNTS],
+    prev: [u16; MAX_ACCOUNTS],
+    /// List heads, longs then shorts
+    heads: [[u16; LIQ_BUCKET_COUNT]; 2],
+    len: u32,
+    /// Accounts changed since they were filed (one bit per account)
+    stale: [u64; STALE_WORDS],
+    /// Every entry is suspect (raw engine access)
+    rebuild: bool,
+}
+
+impl Default for LiquidationIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LiquidationIndex {
+    pub const fn new() -> Self {
+        Self {
+            entries: [NIL; MAX_ACCOUNTS],
+            next: [NIL; MAX_ACCOUNTS],
+            prev: [NIL; MAX_ACCOUNTS],
+            heads: [[NIL; LIQ_BUCKET_COUNT]; 2],
+            len: 0,
+            stale: [0; STALE_WORDS],
+            rebuild: false,
+        }
+    }
+
+    /// Accounts filed
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Side (true = long) and bucket `idx` is filed under
+    pub fn entry(&self, idx: u16) -> Option<(bool, usize)> {
+        let entry = *self.entries.get(idx as usize)?;
+        (entry != NIL).then_some((entry >> 8 == 0, (entry & 0xff) as usize))
+    }
+
+    /// File `idx` under `liquidation_price` (None removes it)
+    pub fn update(&mut self, idx: u16, long: bool, liquidation_price: Option<u64>) {
+        let Some(price) = liquidation_price else {
+            self.remove(idx);
+            return;
+        };
+        let bucket = bucket_of(price);
+        self.clear_stale(idx);
+        if self.entry(idx) == Some((long, bucket)) {
+            return;
+        }
+        self.remove(idx);
+        let side = if long { 0 } else { 1 };
+        let head = self.heads[side][bucket];
+        let i = idx as usize;
+        self.next[i] = head;
+        self.prev[i] = NIL;
+        if head != NIL {
+            self.prev[head as usize] = idx;
+        }
+        self.heads[side][bucket] = idx;
+        self.entries[i] = ((side as u16) << 8) | bucket as u16;
+        self.len += 1;
+    }
+
+    /// Unfile `idx`
+    pub fn remove(&mut self, idx: u16) {
+        self.clear_stale(idx);
+        let Some((long, bucket)) = self.entry(idx) else {
+            return;
+        };
+        let side = if long { 0 } else { 1 };
+        let i = idx as usize;
+        let (prev, next) = (self.prev[i], self.next[i]);
+        if prev == NIL {
+            self.heads[side][bucket] = next;
+        } else {
+            self.next[prev as usize] = next;
+        }
+        if next != NIL {
+            self.prev[next as usize] = prev;
+        }
+        self.entries[i] = NIL;
+        self.next[i] = NIL;
+        self.prev[i] = NIL;
+        self.len -= 1;
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Note that `idx` changed and must be refiled before it is trusted
+    pub fn mark_stale(&mut self, idx: u16) {
+        if let Some(word) = self.stale.get_mut(idx as usize >> 6) {
+            *word |= 1 << (idx & 63);
+        }
+    }
+
+    /// Whether `idx` changed since it was filed
+    pub fn is_stale(&self, idx: u16) -> bool {
+        self.rebuild
+            || self
+                .stale
+                .get(idx as usize >> 6)
+                .is_some_and(|word| (word >> (idx & 63)) & 1 == 1)
+    }
+
+    /// Distrust every entry until the next rebuild
+    pub fn invalidate(&mut self) {
+        self.rebuild = true;
+    }
+
+    /// Whether the index must be rebuilt from the slab
+    pub fn needs_rebuild(&self) -> bool {
+        self.rebuild
+    }
+
+    fn clear_stale(&mut self, idx: u16) {
+        if let Some(word) = self.stale.get_mut(idx as usize >> 6) {
+            *word &= !(1 << (idx & 63));
+        }
+    }
+
+    /// Take and clear one word of stale marks (accounts `word * 64..`)
+    pub(crate) fn take_stale(&mut self, word: usize) -> u64 {
+        self.stale.get_mut(word).map_or(0, core::mem::take)
+    }
+
+    /// Accounts in buckets `price` has crossed: longs filed at or above its
+    /// bucket, shorts at or below
+    pub fn candidates(&self, price: u64) -> impl Iterator<Item = u16> + '_ {
+        let bucket = bucket_of(price);
+        let longs = (bucket..LIQ_BUCKET_COUNT).map(|b| self.heads[0][b]);
+        let shorts = (0..=bucket).map(|b| self.heads[1][b]);
+        longs.chain(shorts).flat_map(move |head| {
+            core::iter::successors((head != NIL).then_some(head), move |&idx| {
+                let next = self.next[idx as usize];
+                (next != NIL).then_some(next)
+            })
+        })
+    }
+}
@@ -0,0 +1,164 @@
+//! LP pool: passive liquidity providers pooled behind the agent's LP account
+//!
+//! Providers own shares of the LP account's mark-to-market equity. Deposits
+//! mint shares at the current share price, withdrawals burn them, and PnL
+//! is attributed pro-rata simply by share ownership.
+
+use crate::{RiskError, Result};
+
+/// Maximum providers in a pool
+pub const MAX_POOL_PROVIDERS: usize = 64;
+
+/// Pool provider ledger entry
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolProvider {
+    /// Provider identity (owner pubkey)
+    pub owner: [u8; 32],
+
+    /// Shares held
+    pub shares: u128,
+
+    /// Total capital deposited
+    pub deposited: u128,
+
+    /// Total capital withdrawn
+    pub withdrawn: u128,
+}
+
+/// Provider's stake valued at the current share price
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolPosition {
+    /// Shares held
+    pub shares: u128,
+
+    /// Current value of the shares
+    pub value: u128,
+
+    /// PnL attributed to the provider (value + withdrawn - deposited)
+    pub pnl: i128,
+}
+
+/// Share ledger of the pool
+#[derive(Clone, Copy, Debug)]
+pub struct LpPool {
+    providers: [PoolProvider; MAX_POOL_PROVIDERS],
+    len: usize,
+    total_shares: u128,
+}
+
+impl LpPool {
+    pub const fn new() -> Self {
+        Self {
+            providers: [PoolProvider {
+                owner: [0; 32],
+                shares: 0,
+                deposited: 0,
+                withdrawn: 0,
+            }; MAX_POOL_PROVIDERS],
+            len: 0,
+            total_shares: 0,
+        }
+    }
+
+    /// Providers currently holding shares
+    pub fn providers(&self) -> &[PoolProvider] {
+        &self.providers[..self.len]
+    }
+
+    pub fn total_shares(&self) -> u128 {
+        self.total_shares
+    }
+
+    pub fn provider(&self, owner: &[u8; 32]) -> Option<&PoolProvider> {
+        self.providers().iter().find(|p| &p.owner == owner)
+    }
+
+    /// Shares minted for depositing `amount` into a pool worth `equity`
+    pub fn shares_for_deposit(&self, amount: u128, equity: u128) -> Result<u128> {
+        if self.total_shares == 0 {
+            return Ok(amount);
+        }
+        if equity == 0 {
+            // Outstanding shares are worthless; minting would dilute nothing
+            return Err(RiskError::Undercollateralized);
+        }
+        amount
+            .checked_mul(self.total_shares)
+            .map(|v| v / equity)
+            .ok_or(RiskError::Overflow)
+    }
+
+    /// Value of `shares` in a pool worth `equity` (rounded down)
+    pub fn value_of_shares(&self, shares: u128, equity: u128) -> u128 {
+        if self.total_shares == 0 {
+            return 0;
+        }
+        shares.saturating_mul(equity) / self.total_shares
+    }
+
+    /// Provider's stake in a pool worth `equity`
+    pub fn position(&self, owner: &[u8; 32], equity: u128) -> Option<PoolPosition> {
+        let provider = self.provider(owner)?;
+        let value = self.value_of_shares(provider.shares, equity);
+        let pnl = (value.saturating_add(provider.withdrawn) as i128)
+            .saturating_sub(provider.deposited as i128);
+        Some(PoolPosition {
+            shares: provider.shares,
+            value,
+            pnl,
+        })
+    }
+
+    pub(crate) fn mint(&mut self, owner: [u8; 32], shares: u128, deposited: u128) -> Result<()> {
+        if shares == 0 {
+            return Err(RiskError::Overflow);
+        }
+        let pos = match self.providers().iter().position(|p| p.owner == owner) {
+            Some(pos) => pos,
+            None => {
+                if self.len >= MAX_POOL_PROVIDERS {
+                    return Err(RiskError::Overflow);
+                }
+                self.providers[self.len] = PoolProvider {
+                    owner,
+                    ..PoolProvider::default()
+                };
+                self.len += 1;
+                self.len - 1
+            }
+        };
+        let total = self.total_shares.checked_add(shares).ok_or(RiskError::Overflow)?;
+        let provider = &mut self.providers[pos];
+        provider.shares += shares;
+        provider.deposited = provider.deposited.saturating_add(deposited);
+        self.total_shares = total;
+        Ok(())
+    }
+
+    pub(crate) fn burn(&mut self, owner: &[u8; 32], shares: u128, withdrawn: u128) -> Result<()> {
+        let pos = self
+            .providers()
+            .iter()
+            .position(|p| &p.owner == owner)
+            .ok_or(RiskError::AccountNotFound)?;
+        let provider = &mut self.providers[pos];
+        if shares == 0 || provider.shares < shares {
+            return Err(RiskError::InsufficientBalance);
+        }
+        provider.shares -= shares;
+        provider.withdrawn = provider.withdrawn.saturating_add(withdrawn);
+        self.total_shares -= shares;
+
+        if provider.shares == 0 {
+            self.providers.copy_within(pos + 1..self.len, pos);
+            self.len -= 1;
+        }
+        Ok(())
+    }
+}
+
+impl Default for LpPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,105 @@
+//! Margin math shared by the engine, agents and the HTTP API
+//!
+//! Prices use the engine's 1e6 fixed-point scale and margin ratios are in
+//! basis points of position notional at the given price.
+
+use super::math::{self, apply_bps, ratio_bps, Rounding};
+use crate::{RiskParams, MAX_ORACLE_PRICE};
+
+/// Position notional at `price` (capital units)
+pub fn notional(position: i128, price: u64) -> u128 {
+    math::notional(position.unsigned_abs(), price, Rounding::Down)
+}
+
+/// Margin required to hold `position` at `bps` of notional
+pub fn margin_required(position: i128, price: u64, bps: u64) -> u128 {
+    apply_bps(notional(position, price), bps, Rounding::Down)
+}
+
+/// Initial margin for `position` at `price`
+pub fn initial_margin(position: i128, price: u64, params: &RiskParams) -> u128 {
+    margin_required(position, price, params.initial_margin_bps)
+}
+
+/// Maintenance margin for `position` at `price`
+pub fn maintenance_margin(position: i128, price: u64, params: &RiskParams) -> u128 {
+    margin_required(position, price, params.maintenance_margin_bps)
+}
+
+/// Equity over notional in bps (u64::MAX for a flat position)
+pub fn margin_ratio_bps(equity: u128, position: i128, price: u64) -> u64 {
+    ratio_bps(equity, notional(position, price), Rounding::Down)
+}
+
+/// Price at which `position`, holding `equity` at `price`, falls to
+/// `maintenance_bps` of notional
+///
+/// Rounded towards the current price (up for longs, down for shorts) so the
+/// estimate is never optimistic. None when the position is flat or can
+/// never be liquidated (a long whose equity covers the whole notional).
+pub fn liquidation_price(equity: u128, position: i128, price: u64, maintenance_bps: u64) -> Option<u64> {
+    if position == 0 || maintenance_bps >= 10_000 {
+        return None;
+    }
+    let size = position.unsigned_abs();
+    let equity_scaled = equity.saturating_mul(1_000_000);
+    let value = size.saturating_mul(price as u128);
+    let liq = if position > 0 {
+        // equity + size * (p - price) = size * p * mm
+        let numerator = value.checked_sub(equity_scaled).filter(|n| *n > 0)?;
+        numerator
+            .saturating_mul(10_000)
+            .div_ceil(size.saturating_mul(10_000 - maintenance_bps as u128))
+    } else {
+        // equity + size * (price - p) = size * p * mm
+        equity_scaled.saturating_add(value).saturating_mul(10_000)
+            / size.saturating_mul(10_000 + maintenance_bps as u128)
+    };
+    Some(liq.min(MAX_ORACLE_PRICE as u128) as u64)
+}
+
+/// Margin state of one account at a price
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MarginSummary {
+    /// Mark-to-market equity
+    pub equity: u128,
+
+    /// Position notional
+    pub notional: u128,
+
+    /// Initial margin requirement
+    pub initial_margin: u128,
+
+    /// Maintenance margin requirement
+    pub maintenance_margin: u128,
+
+    /// Equity over notional (bps, u64::MAX when flat)
+    pub margin_ratio_bps: u64,
+
+    /// Equity above initial margin (available to open or withdraw)
+    pub free_collateral: u128,
+
+    /// Estimated liquidation price (None when flat or unliquidatable)
+    pub liquidation_price: Option<u64>,
+}
+
+impl MarginSummary {
+    /// Summary for `position` holding `equity` at `price`
+    pub fn new(equity: u128, position: i128, price: u64, params: &RiskParams) -> Self {
+        let initial = initial_margin(position, price, params);
+        Self {
+            equity,
+            notional: notional(position, price),
+            initial_margin: initial,
+            maintenance_margin: maintenance_margin(position, price, params),
+            margin_ratio_bps: margin_ratio_bps(equity, position, price),
+            free_collateral: equity.saturating_sub(initial),
+            liquidation_price: liquidation_price(
+                equity,
+                position,
+                price,
+                params.maintenance_margin_bps,
+            ),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Fixed-point helpers for bps, price and ratio arithmetic
+//!
+//! Prices are 1e6 fixed point and rates are basis points. `mul_div` is the
+//! checked primitive; the `saturating_*` forms saturate the intermediate
+//! product the way the engine's own aggregates do, so they never panic on
+//! adversarial inputs. Every helper takes an explicit `Rounding` so callers
+//! state which side a remainder falls on.
+
+use crate::metering;
+
+/// Basis-point denominator (10000 = 100%)
+pub const BPS: u128 = 10_000;
+
+/// Price fixed-point scale (1e6 = 1.0)
+pub const PRICE_SCALE: u128 = 1_000_000;
+
+/// Direction a division remainder is rounded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// Towards zero
+    Down,
+
+    /// Away from zero
+    Up,
+}
+
+impl Rounding {
+    fn div(self, numerator: u128, denominator: u128) -> u128 {
+        match self {
+            Rounding::Down => numerator / denominator,
+            Rounding::Up => numerator.div_ceil(denominator),
+        }
+    }
+}
+
+/// `a * b / denominator`; None on overflow or a zero denominator
+pub fn mul_div(a: u128, b: u128, denominator: u128, rounding: Rounding) -> Option<u128> {
+    metering::math(1);
+    if denominator == 0 {
+        return None;
+    }
+    Some(rounding.div(a.checked_mul(b)?, denominator))
+}
+
+/// `a * b / denominator` with a saturating product; None only for a zero
+/// denominator
+pub fn saturating_mul_div(a: u128, b: u128, denominator: u128, rounding: Rounding) -> Option<u128> {
+    metering::math(1);
+    if denominator == 0 {
+        return None;
+    }
+    Some(rounding.div(a.saturating_mul(b), denominator))
+}
+
+/// `bps` of `value`
+pub fn apply_bps(value: u128, bps: u64, rounding: Rounding) -> u128 {
+    metering::math(1);
+    rounding.div(value.saturating_mul(bps as u128), BPS)
+}
+
+/// `part` as bps of `whole`, clamped to u64 (u64::MAX when `whole` is 0)
+pub fn ratio_bps(part: u128, whole: u128, rounding: Rounding) -> u64 {
+    saturating_mul_div(part, BPS, whole, rounding).map_or(u64::MAX, clamp_u64)
+}
+
+/// Quote value of `size` base units at `price`
+pub fn notional(size: u128, price: u64, rounding: Rounding) -> u128 {
+    metering::math(1);
+    rounding.div(size.saturating_mul(price as u128), PRICE_SCALE)
+}
+
+/// `price` moved up (`up = true`) or down by `bps`, clamped to u64
+pub fn offset_bps(price: u64, bps: u64, up: bool, rounding: Rounding) -> u64 {
+    metering::math(1);
+    let factor = if up {
+        BPS.saturating_add(bps as u128)
+    } else {
+        BPS.saturating_sub(bps as u128)
+    };
+    clamp_u64(rounding.div((price as u128).saturating_mul(factor), BPS))
+}
+
+/// Narrow to u64, saturating
+pub fn clamp_u64(value: u128) -> u64 {
+    value.min(u64::MAX as u128) as u64
+}
@@ -0,0 +1,120 @@
+//! Integer numerics for agents: roots, logarithms and volatility
+//!
+//! Everything here is integer-only and deterministic, so agents running on
+//! BPF can estimate volatility or size positions without floating point.
+//! Fixed-point results use the engine's 1e6 scale (`SCALE`); returns and
+//! volatilities are in basis points.
+
+use super::math::BPS;
+
+/// Fixed-point scale of logarithm results (1e6 = 1.0)
+pub const SCALE: i128 = 1_000_000;
+
+/// ln(2) at `SCALE`
+pub const LN_2: i128 = 693_147;
+
+/// Fractional bits computed by `log2_e6`
+const LOG2_FRAC_BITS: u32 = 24;
+
+/// Floor of the square root
+pub fn isqrt(x: u128) -> u128 {
+    x.isqrt()
+}
+
+/// log2(x) with `LOG2_FRAC_BITS` fractional bits (x > 0)
+fn log2_q(x: u128) -> i128 {
+    let int = 127 - x.leading_zeros();
+    // Normalise into [1, 2) with 62 fractional bits so squaring fits in u128
+    let mut y = if int > 62 { x >> (int - 62) } else { x << (62 - int) };
+    let mut result = (int as i128) << LOG2_FRAC_BITS;
+    for bit in (0..LOG2_FRAC_BITS).rev() {
+        y = (y * y) >> 62;
+        if y >= 1 << 63 {
+            y >>= 1;
+            result |= 1 << bit;
+        }
+    }
+    result
+}
+
+/// log2 of the fixed-point value `x / SCALE`, at `SCALE` (None for 0)
+pub fn log2_e6(x: u128) -> Option<i128> {
+    if x == 0 {
+        return None;
+    }
+    let q = log2_q(x) - log2_q(SCALE as u128);
+    Some((q * SCALE) >> LOG2_FRAC_BITS)
+}
+
+/// Natural log of the fixed-point value `x / SCALE`, at `SCALE`
+pub fn ln_e6(x: u128) -> Option<i128> {
+    Some(log2_e6(x)? * LN_2 / SCALE)
+}
+
+/// Log return from `from` to `to` in bps (None if either price is 0)
+pub fn log_return_bps(from: u64, to: u64) -> Option<i64> {
+    if from == 0 {
+        return None;
+    }
+    let ratio = (to as u128).checked_mul(SCALE as u128)? / from as u128;
+    Some((ln_e6(ratio)? / 100) as i64)
+}
+
+/// One EWMA step: `prev + alpha * (sample - prev)`
+pub fn ewma_update(prev: u128, sample: u128, alpha_bps: u64) -> u128 {
+    let alpha = alpha_bps.min(BPS as u64) as u128;
+    if sample >= prev {
+        prev + (sample - prev) * alpha / BPS
+    } else {
+        prev - (prev - sample) * alpha / BPS
+    }
+}
+
+/// Exponentially weighted volatility of log returns between updates
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EwmaVolatility {
+    /// Weight of each new squared return (bps)
+    pub alpha_bps: u64,
+
+    /// Current variance estimate (bps squared)
+    pub variance: u128,
+
+    /// Price of the previous update
+    pub last_price: Option<u64>,
+}
+
+impl EwmaVolatility {
+    pub const fn new(alpha_bps: u64) -> Self {
+        Self {
+            alpha_bps,
+            variance: 0,
+            last_price: None,
+        }
+    }
+
+    /// Fold in a new price; the first update only seeds `last_price`
+    pub fn update(&mut self, price: u64) {
+        if let Some(r) = self.last_price.and_then(|last| log_return_bps(last, price)) {
+            let squared = (r.unsigned_abs() as u128).saturating_mul(r.unsigned_abs() as u128);
+            self.variance = ewma_update(self.variance, squared, self.alpha_bps);
+        }
+        if price > 0 {
+            self.last_price = Some(price);
+        }
+    }
+
+    /// Volatility per update interval (bps)
+    pub fn volatility_bps(&self) -> u64 {
+        isqrt(self.variance).min(u64::MAX as u128) as u64
+    }
+}
+
+/// Kelly fraction for expected return `edge_bps` and return variance
+/// `variance` (bps squared), in bps of equity and capped at `max_bps`
+pub fn kelly_fraction_bps(edge_bps: i64, variance: u128, max_bps: u64) -> u64 {
+    if edge_bps <= 0 || variance == 0 {
+        return if edge_bps > 0 { max_bps } else { 0 };
+    }
+    let fraction = (edge_bps as u128).saturating_mul(BPS * BPS) / variance;
+    fraction.min(max_bps as u128) as u64
+}
@@ -0,0 +1,181 @@
+//! Monte Carlo price paths for stress runs and backtests
+//!
+//! Gated behind the `analysis` feature (std). Each path builds a fresh
+//! engine from a caller-supplied factory, walks the oracle price with a
+//! random walk seeded by the path's seed and cranks once per step. `run`
+//! goes through the seeds on the calling thread; with the `parallel`
+//! feature `run_parallel` spreads them across rayon's thread pool. A path
+//! shares nothing with the others and depends only on its seed, and
+//! reports are kept in seed order, so both return the same
+//! `MonteCarloReport` whatever the thread count.
+
+use core::ops::Range;
+use std::boxed::Box;
+use std::vec::Vec;
+
+use super::simulation::SplitMix64;
+use super::stress::shocked_price;
+use super::ClawcolatorEngine;
+
+/// Price path shape and crank schedule
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathParams {
+    /// Oracle price before the first step
+    pub start_price: u64,
+
+    /// Steps (cranks) per path
+    pub steps: u32,
+
+    /// Largest move per step (bps, drawn uniformly in ±step_bps)
+    pub step_bps: u32,
+
+    /// Slots between cranks
+    pub slots_per_step: u64,
+
+    /// Account that cranks
+    pub keeper_idx: u16,
+}
+
+impl Default for PathParams {
+    fn default() -> Self {
+        Self {
+            start_price: 1_000_000,
+            steps: 100,
+            step_bps: 200,
+            slots_per_step: 1,
+            keeper_idx: 0,
+        }
+    }
+}
+
+/// Outcome of one path
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathReport {
+    pub seed: u64,
+
+    /// Oracle price after the last step
+    pub final_price: u64,
+
+    /// Lowest and highest oracle price on the path
+    pub min_price: u64,
+    pub max_price: u64,
+
+    /// Accounts liquidated by the cranks
+    pub liquidations: u64,
+
+    /// Cranks that returned an error
+    pub failed_cranks: u32,
+
+    /// Insurance balance before the first step
+    pub insurance_start: u128,
+
+    /// Lowest insurance balance after any step
+    pub insurance_min: u128,
+
+    /// Insurance balance after the last step
+    pub insurance_end: u128,
+}
+
+impl PathReport {
+    /// Insurance lost at the worst point of the path
+    pub fn insurance_drawdown(&self) -> u128 {
+        self.insurance_start.saturating_sub(self.insurance_min)
+    }
+}
+
+/// Reports of every path, in seed order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MonteCarloReport {
+    pub paths: Vec<PathReport>,
+}
+
+impl MonteCarloReport {
+    /// Report over `paths`, in any order
+    pub fn from_paths(mut paths: Vec<PathReport>) -> Self {
+        paths.sort_by_key(|path| path.seed);
+        Self { paths }
+    }
+
+    /// Fold in the paths of another run (e.g. another seed range)
+    pub fn merge(&mut self, other: MonteCarloReport) {
+        self.paths.extend(other.paths);
+        self.paths.sort_by_key(|path| path.seed);
+    }
+
+    /// Liquidations across all paths
+    pub fn total_liquidations(&self) -> u64 {
+        self.paths.iter().map(|path| path.liquidations).sum()
+    }
+
+    /// Paths with at least one liquidation
+    pub fn paths_with_liquidations(&self) -> usize {
+        self.paths.iter().filter(|path| path.liquidations > 0).count()
+    }
+
+    /// Largest insurance drawdown of any path
+    pub fn worst_insurance_drawdown(&self) -> u128 {
+        self.paths.iter().map(PathReport::insurance_drawdown).max().unwrap_or(0)
+    }
+
+    /// Final prices as f64 (1.0 = one quote unit), for `analysis` statistics
+    pub fn final_prices(&self) -> Vec<f64> {
+        self.paths.iter().map(|path| path.final_price as f64 / 1_000_000.0).collect()
+    }
+}
+
+/// Run one path on `engine`
+pub fn run_path(engine: &mut ClawcolatorEngine, params: &PathParams, seed: u64) -> PathReport {
+    let mut rng = SplitMix64(seed);
+    let insurance = |engine: &ClawcolatorEngine| engine.risk_engine().insurance_fund.balance.get();
+    let mut price = params.start_price;
+    let mut report = PathReport {
+        seed,
+        final_price: price,
+        min_price: price,
+        max_price: price,
+        insurance_start: insurance(engine),
+        insurance_min: insurance(engine),
+        insurance_end: insurance(engine),
+        ..PathReport::default()
+    };
+    let span = 2 * params.step_bps as u64 + 1;
+    for step in 0..params.steps as u64 {
+        let shock = (rng.next() % span) as i64 - params.step_bps as i64;
+        price = shocked_price(price, shock);
+        let slot = (step + 1) * params.slots_per_step;
+        match engine.crank(params.keeper_idx, slot, price, None) {
+            Ok(outcome) => report.liquidations += outcome.num_liquidations as u64,
+            Err(_) => report.failed_cranks += 1,
+        }
+        report.min_price = report.min_price.min(price);
+        report.max_price = report.max_price.max(price);
+        report.insurance_min = report.insurance_min.min(insurance(engine));
+    }
+    report.final_price = price;
+    report.insurance_end = insurance(engine);
+    report
+}
+
+/// Run a path per seed, one after another
+pub fn run<F>(factory: F, params: &PathParams, seeds: Range<u64>) -> MonteCarloReport
+where
+    F: Fn() -> Box<ClawcolatorEngine>,
+{
+    MonteCarloReport::from_paths(seeds.map(|seed| run_path(&mut factory(), params, seed)).collect())
+}
+
+/// Run a path per seed across rayon's thread pool (same report as `run`)
+#[cfg(feature = "parallel")]
+pub fn run_parallel<F>(factory: F, params: &PathParams, seeds: Range<u64>) -> MonteCarloReport
+where
+    F: Fn() -> Box<ClawcolatorEngine> + Sync,
+{
+    use rayon::prelude::*;
+
+    MonteCarloReport::from_paths(
+        seeds
+            .into_par_iter()
+            .map(|seed| run_path(&mut factory(), params, seed))
+            .collect(),
+    )
+}
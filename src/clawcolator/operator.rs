@@ -0,0 +1,91 @@
+//! Operator kill-switch: human intervention independent of the agent
+//!
+//! An operator key, set administratively, signs kill-switch commands
+//! (freeze, force risk-reduction, veto agent params). Signatures are checked
+//! through a caller-supplied `SignatureVerifier` so the crate stays free of
+//! crypto dependencies: on-chain this wraps the ed25519 program, off-chain
+//! any ed25519 library. Each command carries a nonce that must increase,
+//! so a captured signature cannot be replayed.
+
+use crate::{RiskError, Result};
+
+/// Domain separator prefixed to every signed operator message
+pub const OPERATOR_DOMAIN: &[u8; 16] = b"clawcolator:kill";
+
+/// Length of an encoded operator message (domain, tag, flag, nonce)
+pub const OPERATOR_MESSAGE_LEN: usize = 16 + 1 + 1 + 8;
+
+/// Verifies ed25519 (or equivalent) signatures over operator messages
+pub trait SignatureVerifier {
+    /// Whether `signature` is a valid signature of `message` by `pubkey`
+    fn verify(&self, pubkey: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool;
+}
+
+/// Kill-switch commands available to the operator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OperatorCommand {
+    /// Freeze (true) or unfreeze (false) the market
+    Freeze(bool),
+
+    /// Force (true) or release (false) risk-reduction-only mode
+    ForceRiskReduction(bool),
+
+    /// Veto (true) the agent's latest params, restoring the previous set and
+    /// blocking agent updates, or lift the veto (false)
+    VetoParams(bool),
+}
+
+impl OperatorCommand {
+    /// Canonical message the operator signs for this command and nonce
+    pub fn encode(&self, nonce: u64) -> [u8; OPERATOR_MESSAGE_LEN] {
+        let (tag, flag) = match *self {
+            OperatorCommand::Freeze(on) => (0u8, on),
+            OperatorCommand::ForceRiskReduction(on) => (1, on),
+            OperatorCommand::VetoParams(on) => (2, on),
+        };
+        let mut message = [0u8; OPERATOR_MESSAGE_LEN];
+        message[..16].copy_from_slice(OPERATOR_DOMAIN);
+        message[16] = tag;
+        message[17] = flag as u8;
+        message[18..].copy_from_slice(&nonce.to_le_bytes());
+        message
+    }
+}
+
+/// Operator key and replay protection state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OperatorAuthority {
+    /// Operator public key
+    pub pubkey: [u8; 32],
+
+    /// Highest nonce accepted so far
+    pub last_nonce: u64,
+}
+
+impl OperatorAuthority {
+    /// Authority for `pubkey` with no commands accepted yet
+    pub fn new(pubkey: [u8; 32]) -> Self {
+        Self {
+            pubkey,
+            last_nonce: 0,
+        }
+    }
+
+    /// Check nonce and signature, consuming the nonce on success
+    pub fn authorize<V: SignatureVerifier>(
+        &mut self,
+        verifier: &V,
+        command: OperatorCommand,
+        nonce: u64,
+        signature: &[u8; 64],
+    ) -> Result<()> {
+        if nonce <= self.last_nonce {
+            return Err(RiskError::Unauthorized); // ReplayedNonce
+        }
+        if !verifier.verify(&self.pubkey, &command.encode(nonce), signature) {
+            return Err(RiskError::Unauthorized); // BadSignature
+        }
+        self.last_nonce = nonce;
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! Oracle adapters: decode on-chain price accounts into `OraclePrice`
+//!
+//! Adapters are feature-gated (`pyth`, `switchboard`) and parse raw account
+//! bytes without pulling in the provider SDKs. Every adapter normalizes to
+//! the engine's 1e6 price scale and rejects prices the engine cannot accept.
+
+use crate::{RiskError, Result, MAX_ORACLE_PRICE};
+
+#[cfg(feature = "pyth")]
+pub mod pyth;
+
+#[cfg(feature = "switchboard")]
+pub mod switchboard;
+
+/// Decimal exponent of engine prices (1e6 scale)
+pub const ENGINE_PRICE_EXPO: i32 = -6;
+
+/// Rescale `mantissa * 10^expo` to the engine's 1e6 scale
+///
+/// Rounds toward zero when precision is dropped (or up, if `round_up`).
+fn rescale(mantissa: u128, expo: i32, round_up: bool) -> Result<u128> {
+    let shift = expo - ENGINE_PRICE_EXPO;
+    if shift >= 0 {
+        let factor = 10u128.checked_pow(shift as u32).ok_or(RiskError::Overflow)?;
+        return mantissa.checked_mul(factor).ok_or(RiskError::Overflow);
+    }
+    let Some(divisor) = 10u128.checked_pow(shift.unsigned_abs()) else {
+        return Ok(if round_up && mantissa > 0 { 1 } else { 0 });
+    };
+    let scaled = mantissa / divisor;
+    if round_up && !mantissa.is_multiple_of(divisor) {
+        return Ok(scaled + 1);
+    }
+    Ok(scaled)
+}
+
+/// Normalize a provider price to the engine scale
+///
+/// Rejects non-positive prices and prices outside `(0, MAX_ORACLE_PRICE]`.
+pub fn normalize_price(mantissa: i128, expo: i32) -> Result<u64> {
+    if mantissa <= 0 {
+        return Err(RiskError::Overflow);
+    }
+    let price = rescale(mantissa as u128, expo, false)?;
+    if price == 0 || price > MAX_ORACLE_PRICE as u128 {
+        return Err(RiskError::Overflow);
+    }
+    Ok(price as u64)
+}
+
+/// Normalize a provider confidence interval to the engine scale
+///
+/// Rounds up so that precision loss never makes a price look tighter.
+pub fn normalize_confidence(mantissa: u128, expo: i32) -> Result<u64> {
+    let conf = rescale(mantissa, expo, true)?;
+    Ok(conf.min(u64::MAX as u128) as u64)
+}
+
+/// Little-endian field reader over raw account bytes
+pub(crate) struct AccountReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> AccountReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N]> {
+        let end = offset.checked_add(N).ok_or(RiskError::Overflow)?;
+        let slice = self.data.get(offset..end).ok_or(RiskError::Overflow)?;
+        let mut out = [0u8; N];
+        out.copy_from_slice(slice);
+        Ok(out)
+    }
+
+    pub(crate) fn u8(&self, offset: usize) -> Result<u8> {
+        self.data.get(offset).copied().ok_or(RiskError::Overflow)
+    }
+
+    pub(crate) fn u16(&self, offset: usize) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(offset)?))
+    }
+
+    pub(crate) fn i32(&self, offset: usize) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.bytes(offset)?))
+    }
+
+    pub(crate) fn u64(&self, offset: usize) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(offset)?))
+    }
+
+    pub(crate) fn i64(&self, offset: usize) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.bytes(offset)?))
+    }
+
+    pub(crate) fn i128(&self, offset: usize) -> Result<i128> {
+        Ok(i128::from_le_bytes(self.bytes(offset)?))
+    }
+
+    pub(crate) fn u128(&self, offset: usize) -> Result<u128> {
+        Ok(u128::from_le_bytes(self.bytes(offset)?))
+    }
+}
@@ -0,0 +1,65 @@
+//! Pyth adapter: `PriceUpdateV2` accounts from the Pyth Solana receiver
+//!
+//! Layout (Anchor/borsh):
+//! discriminator (8) | write_authority (32) | verification_level (1 or 2) |
+//! feed_id (32) | price i64 | conf u64 | exponent i32 | publish_time i64 |
+//! prev_publish_time i64 | ema_price i64 | ema_conf u64 | posted_slot u64
+
+use crate::{RiskError, Result};
+
+use super::super::OraclePrice;
+use super::{normalize_confidence, normalize_price, AccountReader};
+
+/// Anchor discriminator of `PriceUpdateV2`
+pub const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+/// Decoded Pyth price update
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PythPriceUpdate {
+    /// Pyth feed identifier
+    pub feed_id: [u8; 32],
+
+    /// Price normalized to the engine scale, published at `posted_slot`
+    pub price: OraclePrice,
+
+    /// Publisher timestamp (unix seconds)
+    pub publish_time: i64,
+}
+
+/// Parse a `PriceUpdateV2` account for `feed_id`
+///
+/// Only fully verified updates are accepted; partially verified updates and
+/// updates for another feed are rejected with `Unauthorized`.
+pub fn parse_price_update(data: &[u8], feed_id: &[u8; 32]) -> Result<PythPriceUpdate> {
+    let reader = AccountReader::new(data);
+    if reader.bytes::<8>(0)? != PRICE_UPDATE_V2_DISCRIMINATOR {
+        return Err(RiskError::Unauthorized);
+    }
+
+    // VerificationLevel: 0 = Partial { num_signatures: u8 }, 1 = Full
+    let msg = match reader.u8(40)? {
+        0 => return Err(RiskError::Unauthorized),
+        1 => 41,
+        _ => return Err(RiskError::Overflow),
+    };
+
+    let update_feed: [u8; 32] = reader.bytes(msg)?;
+    if &update_feed != feed_id {
+        return Err(RiskError::Unauthorized);
+    }
+    let price = reader.i64(msg + 32)?;
+    let conf = reader.u64(msg + 40)?;
+    let expo = reader.i32(msg + 48)?;
+    let publish_time = reader.i64(msg + 52)?;
+    let posted_slot = reader.u64(msg + 84)?;
+
+    Ok(PythPriceUpdate {
+        feed_id: update_feed,
+        price: OraclePrice::new(
+            normalize_price(price as i128, expo)?,
+            normalize_confidence(conf as u128, expo)?,
+            posted_slot,
+        ),
+        publish_time,
+    })
+}
@@ -0,0 +1,50 @@
+//! Switchboard adapter: on-demand `PullFeedAccountData` accounts
+//!
+//! The feed is a fixed-layout (zero-copy) account. Only the aggregated
+//! `result` is read: value and standard deviation are 1e18 fixed point,
+//! `slot` is the slot the result was produced at.
+
+use crate::{RiskError, Result};
+
+use super::super::OraclePrice;
+use super::{normalize_confidence, normalize_price, AccountReader};
+
+/// Anchor discriminator of `PullFeedAccountData`
+pub const PULL_FEED_DISCRIMINATOR: [u8; 8] = [196, 27, 108, 196, 10, 215, 219, 40];
+
+/// Decimal exponent of Switchboard fixed-point values
+pub const SWITCHBOARD_EXPO: i32 = -18;
+
+/// Offset of `result: CurrentResult` in the account data
+pub const RESULT_OFFSET: usize = 2264;
+
+const RESULT_VALUE: usize = RESULT_OFFSET;
+const RESULT_STD_DEV: usize = RESULT_OFFSET + 16;
+const RESULT_NUM_SAMPLES: usize = RESULT_OFFSET + 96;
+const RESULT_SLOT: usize = RESULT_OFFSET + 104;
+
+/// Parse a `PullFeedAccountData` account into an engine oracle price
+///
+/// The caller is responsible for checking the account address; feeds with
+/// no samples yet are rejected with `Unauthorized`.
+pub fn parse_pull_feed(data: &[u8]) -> Result<OraclePrice> {
+    let reader = AccountReader::new(data);
+    if reader.bytes::<8>(0)? != PULL_FEED_DISCRIMINATOR {
+        return Err(RiskError::Unauthorized);
+    }
+    if reader.u8(RESULT_NUM_SAMPLES)? == 0 {
+        return Err(RiskError::Unauthorized);
+    }
+
+    let value = reader.i128(RESULT_VALUE)?;
+    let std_dev = reader.i128(RESULT_STD_DEV)?;
+    if std_dev < 0 {
+        return Err(RiskError::Overflow);
+    }
+
+    Ok(OraclePrice::new(
+        normalize_price(value, SWITCHBOARD_EXPO)?,
+        normalize_confidence(std_dev as u128, SWITCHBOARD_EXPO)?,
+        reader.u64(RESULT_SLOT)?,
+    ))
+}
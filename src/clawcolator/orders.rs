@@ -0,0 +1,97 @@
+//! Client order IDs and duplicate suppression
+//!
+//! A trade submitted with a client order ID is remembered together with
+//! its outcome. Resubmitting the same ID for the same account inside the
+//! window returns the remembered outcome instead of trading again, so a
+//! client that retries after a dropped response (HTTP timeout, expired
+//! transaction) cannot fill twice. The log is a fixed ring: an ID falls out
+//! when it ages past the window or when `CLIENT_ORDER_LOG_LEN` newer orders
+//! have been recorded, whichever comes first.
+
+use super::TradeOutcome;
+
+/// Orders remembered for duplicate detection
+pub const CLIENT_ORDER_LOG_LEN: usize =
+    crate::layout::capacity(option_env!("PERCOLATOR_CLIENT_ORDER_LOG_LEN"), 128);
+
+/// Default dedup window (slots), roughly a Solana blockhash lifetime
+pub const DEFAULT_CLIENT_ORDER_WINDOW_SLOTS: u64 = 150;
+
+/// Remembered order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientOrder {
+    /// Submitting account
+    pub user_idx: u16,
+
+    /// Client-chosen ID, unique per account within the window
+    pub client_order_id: u64,
+
+    /// Slot of the first submission
+    pub slot: u64,
+
+    /// Outcome of the first submission
+    pub outcome: TradeOutcome,
+}
+
+/// Ring of recent client orders
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientOrderLog {
+    orders: [Option<ClientOrder>; CLIENT_ORDER_LOG_LEN],
+    head: usize,
+    window_slots: u64,
+}
+
+impl Default for ClientOrderLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_CLIENT_ORDER_WINDOW_SLOTS)
+    }
+}
+
+impl ClientOrderLog {
+    /// Empty log remembering orders for `window_slots` slots
+    pub const fn new(window_slots: u64) -> Self {
+        Self {
+            orders: [None; CLIENT_ORDER_LOG_LEN],
+            head: 0,
+            window_slots,
+        }
+    }
+
+    /// Slots an order is remembered for
+    pub fn window_slots(&self) -> u64 {
+        self.window_slots
+    }
+
+    /// Change the window; remembered orders are judged by the new one
+    pub fn set_window_slots(&mut self, window_slots: u64) {
+        self.window_slots = window_slots;
+    }
+
+    /// The earlier submission of `client_order_id` by `user_idx`, if it is
+    /// still inside the window at `now_slot`
+    pub fn find(&self, user_idx: u16, client_order_id: u64, now_slot: u64) -> Option<&ClientOrder> {
+        self.orders.iter().flatten().find(|order| {
+            order.user_idx == user_idx
+                && order.client_order_id == client_order_id
+                && now_slot.saturating_sub(order.slot) <= self.window_slots
+        })
+    }
+
+    /// Follow an account moved by compaction into the free slot `to`;
+    /// orders left under `to` by a closed account go to `from`
+    pub(crate) fn move_account(&mut self, from: u16, to: u16) {
+        for order in self.orders.iter_mut().flatten() {
+            if order.user_idx == from {
+                order.user_idx = to;
+            } else if order.user_idx == to {
+                order.user_idx = from;
+            }
+        }
+    }
+
+    /// Remember an order, evicting the oldest when full
+    pub fn record(&mut self, order: ClientOrder) {
+        self.orders[self.head] = Some(order);
+        self.head = (self.head + 1) % CLIENT_ORDER_LOG_LEN;
+    }
+}
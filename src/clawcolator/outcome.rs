@@ -0,0 +1,193 @@
+//! Machine-readable trade outcomes
+//!
+//! Every agent-driven trade leaves a `TradeOutcome` behind: the fill, or
+//! why the trade was rejected together with the limits a retry has to
+//! respect and the agent's counter-quote when it answered with one. The
+//! `RiskError` the call returned is kept alongside, so the outcome never
+//! disagrees with the result.
+
+use super::{DecisionMeta, TradeFill, TradeRejectionReason};
+use crate::RiskError;
+
+/// Direction of a trade from the user's side
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TradeSide {
+    /// Long (positive size)
+    Buy,
+    /// Short (negative size)
+    Sell,
+}
+
+impl TradeSide {
+    /// Side of a signed size (None for 0)
+    pub fn of(size: i128) -> Option<Self> {
+        match size {
+            0 => None,
+            s if s > 0 => Some(TradeSide::Buy),
+            _ => Some(TradeSide::Sell),
+        }
+    }
+
+    /// `size` signed for this side, saturating
+    pub fn signed(self, size: u128) -> i128 {
+        let size = size.min(i128::MAX as u128) as i128;
+        match self {
+            TradeSide::Buy => size,
+            TradeSide::Sell => -size,
+        }
+    }
+}
+
+/// Stage of the trade pipeline that turned a trade down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RejectionCause {
+    /// System shut down
+    Shutdown,
+    /// Market frozen
+    MarketFrozen,
+    /// Market is not agent-driven
+    MarketMode,
+    /// Sequence number not above the account's last one (replay or reorder)
+    Sequence,
+    /// Request arrived after its `valid_until_slot`
+    Expired,
+    /// Direct trade while commit-reveal is required
+    CommitRequired,
+    /// Reveal matches no live commitment, or falls outside its window
+    Commitment,
+    /// Fill worse than the slippage the user committed to
+    Slippage,
+    /// Direct trade while batch auction mode is on
+    BatchMode,
+    /// Oracle price invalid, stale or too uncertain
+    Oracle,
+    /// Agent failed to decide
+    AgentError,
+    /// Agent rejected the trade
+    Agent(TradeRejectionReason),
+    /// Agent answered with a counter-quote instead of a fill
+    CounterQuote,
+    /// Agent's (or the router's) fill failed protocol validation
+    InvalidFill,
+    /// Above the per-fill size limit or the tier's position or leverage limit
+    SizeLimit,
+    /// Only position-reducing trades are allowed (risk-reduction mode)
+    RiskReduction,
+    /// Cross-margin top-up from the parent account failed
+    CrossMargin,
+    /// LP pool utilization cap reached
+    PoolUtilization,
+    /// User's share of open interest would exceed the cap
+    Concentration,
+    /// Risk engine refused the fill (margin, account state)
+    Engine,
+    /// Account's position is under liquidation auction
+    LiquidationAuction,
+}
+
+/// Agent's counter-quote
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CounterQuote {
+    /// Price the agent would fill at
+    pub price: u64,
+
+    /// Largest size at that price
+    pub max_size: i128,
+}
+
+/// Limits binding a trade in the requested direction
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TradeLimits {
+    /// Largest size the protocol would accept right now (see
+    /// `ClawcolatorEngine::max_tradeable_size`)
+    pub max_size: u128,
+
+    /// Leverage cap of the user's tier (bps, 100 = 1x)
+    pub max_leverage_bps: u64,
+}
+
+/// Why a trade was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TradeRejection {
+    /// Stage that rejected the trade
+    pub cause: RejectionCause,
+
+    /// Error returned to the caller
+    pub error: RiskError,
+
+    /// Limits in effect when the trade was rejected
+    pub limits: TradeLimits,
+
+    /// Agent's counter-quote, if it answered with one
+    pub counter_quote: Option<CounterQuote>,
+}
+
+impl TradeRejection {
+    pub(crate) fn new(cause: RejectionCause, error: RiskError) -> Self {
+        Self {
+            cause,
+            error,
+            limits: TradeLimits::default(),
+            counter_quote: None,
+        }
+    }
+}
+
+/// Result of an agent-driven trade
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TradeOutcome {
+    /// Trade executed
+    Filled(TradeFill),
+
+    /// Trade rejected
+    Rejected(TradeRejection),
+}
+
+impl TradeOutcome {
+    /// The fill, if the trade executed
+    pub fn fill(&self) -> Option<&TradeFill> {
+        match self {
+            TradeOutcome::Filled(fill) => Some(fill),
+            TradeOutcome::Rejected(_) => None,
+        }
+    }
+
+    /// The rejection, if the trade did not execute
+    pub fn rejection(&self) -> Option<&TradeRejection> {
+        match self {
+            TradeOutcome::Filled(_) => None,
+            TradeOutcome::Rejected(rejection) => Some(rejection),
+        }
+    }
+}
+
+/// Projected result of a trade (see `ClawcolatorEngine::execute_trade_dry_run`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TradeSimulation {
+    /// Fill price
+    pub price: u64,
+
+    /// Filled size (may be a partial fill)
+    pub size: i128,
+
+    /// Filled by the vAMM fallback rather than the agent
+    pub vamm: bool,
+
+    /// Trading fee charged to the user
+    pub fee: u128,
+
+    /// User position after the fill
+    pub position_size: i128,
+
+    /// User equity after the fill, at the oracle price
+    pub equity: u128,
+
+    /// Equity over position notional after the fill (bps, u64::MAX when flat)
+    pub margin_ratio_bps: u64,
+
+    /// Oracle price at which the user would be liquidated after the fill
+    pub liquidation_price: Option<u64>,
+
+    /// Agent's rationale for the decision
+    pub meta: Option<DecisionMeta>,
+}
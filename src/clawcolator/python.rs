@@ -0,0 +1,315 @@
+//! pyo3 bindings for research workflows (`pyclawcolator`)
+//!
+//! Gated behind the `python` feature. The library is a no_std rlib, so
+//! build the extension as a one-off cdylib and rename it to
+//! `pyclawcolator.so`:
+//!
+//! ```text
+//! cargo rustc --release --features python --crate-type cdylib
+//! ```
+//!
+//! `Engine` wraps a `ClawcolatorEngine` and every call runs the same Rust
+//! code paths as production, so a Python agent prototyped here sees exact
+//! engine semantics. `PyAgent` adapts any Python object with a
+//! `decide_trade(ctx, req)` method to `OpenClawAgent`.
+//!
+//! ```python
+//! import pyclawcolator as pc
+//!
+//! class Passthrough:
+//!     def decide_trade(self, ctx, req):
+//!         return (ctx.oracle_price, req.size)
+//!
+//! engine = pc.Engine()
+//! lp = engine.add_lp(10_000_000)
+//! user = engine.add_user(1_000_000)
+//! engine.execute_trade(Passthrough(), user, 1_000_000, 1_000, 1)
+//! print(engine.account(user, 1_000_000).position_size)
+//! ```
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::{boxed::Box, format};
+
+use super::{
+    AccountView, AgentCapabilities, AgentContext, ClawcolatorEngine, MarketParams, OpenClawAgent,
+    OraclePrice, TradeDecision, TradeRejectionReason, TradeRequest,
+};
+use crate::{RiskError, RiskParams, Result, U128};
+
+fn py_error(e: RiskError) -> PyErr {
+    PyValueError::new_err(format!("{:?}", e))
+}
+
+/// Read-only agent context
+#[pyclass(name = "AgentContext", frozen, get_all, skip_from_py_object)]
+#[derive(Clone)]
+pub struct PyContext {
+    pub current_slot: u64,
+    pub oracle_price: u64,
+    pub oracle_confidence: u64,
+    pub oracle_stale: bool,
+    pub vault: u128,
+    pub insurance_balance: u128,
+    pub total_capital: u128,
+    pub total_positive_pnl: u128,
+    pub total_open_interest: u128,
+    pub risk_reduction_mode: bool,
+    pub oracle_divergence_bps: u64,
+    pub lp_liquidity: u128,
+    pub lp_liquidation_price: Option<u64>,
+    pub move_var_bps: u64,
+    pub lp_var: u128,
+}
+
+impl From<&AgentContext> for PyContext {
+    fn from(c: &AgentContext) -> Self {
+        Self {
+            current_slot: c.current_slot,
+            oracle_price: c.oracle_price,
+            oracle_confidence: c.oracle_confidence,
+            oracle_stale: c.oracle_stale,
+            vault: c.vault,
+            insurance_balance: c.insurance_balance,
+            total_capital: c.total_capital,
+            total_positive_pnl: c.total_positive_pnl,
+            total_open_interest: c.total_open_interest,
+            risk_reduction_mode: c.risk_reduction_mode,
+            oracle_divergence_bps: c.oracle_divergence_bps,
+            lp_liquidity: c.lp_liquidity,
+            lp_liquidation_price: c.lp_liquidation_price,
+            move_var_bps: c.value_at_risk.move_var_bps,
+            lp_var: c.value_at_risk.lp_var,
+        }
+    }
+}
+
+/// Trade request passed to `decide_trade`
+#[pyclass(name = "TradeRequest", frozen, get_all, skip_from_py_object)]
+#[derive(Clone)]
+pub struct PyTradeRequest {
+    pub user_idx: u16,
+    pub size: i128,
+}
+
+/// Read-only account state
+#[pyclass(name = "Account", frozen, get_all, skip_from_py_object)]
+#[derive(Clone)]
+pub struct PyAccount {
+    pub idx: u16,
+    pub is_lp: bool,
+    pub capital: u128,
+    pub pnl: i128,
+    pub unrealized_pnl: i128,
+    pub position_size: i128,
+    pub equity: u128,
+    pub margin_ratio_bps: u64,
+    pub undercollateralized: bool,
+    pub liquidation_price: Option<u64>,
+    pub funding_paid: i128,
+}
+
+impl From<AccountView> for PyAccount {
+    fn from(v: AccountView) -> Self {
+        Self {
+            idx: v.idx,
+            is_lp: v.kind == crate::AccountKind::LP,
+            capital: v.capital,
+            pnl: v.pnl,
+            unrealized_pnl: v.unrealized_pnl,
+            position_size: v.position_size,
+            equity: v.equity,
+            margin_ratio_bps: v.margin_ratio_bps,
+            undercollateralized: v.undercollateralized,
+            liquidation_price: v.liquidation_price,
+            funding_paid: v.funding_paid,
+        }
+    }
+}
+
+/// `OpenClawAgent` backed by a Python object
+///
+/// `decide_trade(ctx, req)` returns `(price, size)` to accept or None to
+/// reject. Optional methods: `market_params(ctx)` returning a dict of
+/// `spread_bps` / `max_leverage_bps` overrides, and `should_shutdown(ctx)`.
+/// Other decisions use passive defaults. A raising method counts as an
+/// agent error.
+pub struct PyAgent<'py> {
+    object: Bound<'py, PyAny>,
+}
+
+impl<'py> PyAgent<'py> {
+    pub fn new(object: Bound<'py, PyAny>) -> Self {
+        Self { object }
+    }
+
+    fn call(&self, method: &str, context: &AgentContext) -> Result<Option<Bound<'py, PyAny>>> {
+        if !self.object.hasattr(method).unwrap_or(false) {
+            return Ok(None);
+        }
+        self.object
+            .call_method1(method, (PyContext::from(context),))
+            .map(Some)
+            .map_err(|_| RiskError::Unauthorized)
+    }
+}
+
+impl OpenClawAgent for PyAgent<'_> {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        let req = PyTradeRequest {
+            user_idx: request.user_idx,
+            size: request.size,
+        };
+        let decision = self
+            .object
+            .call_method1("decide_trade", (PyContext::from(context), req))
+            .map_err(|_| RiskError::Unauthorized)?;
+        if decision.is_none() {
+            return Ok(TradeDecision::Reject {
+                reason: TradeRejectionReason::Other,
+            });
+        }
+        let (price, size): (u64, i128) =
+            decision.extract().map_err(|_| RiskError::InvalidMatchingEngine)?;
+        Ok(TradeDecision::Accept { price, size })
+    }
+
+    fn get_market_params(&self, context: &AgentContext) -> Result<MarketParams> {
+        let mut params = MarketParams::default();
+        let Some(overrides) = self.call("market_params", context)? else {
+            return Ok(params);
+        };
+        let get = |key: &str| -> Result<Option<u64>> {
+            match overrides.get_item(key) {
+                Ok(value) => value.extract().map(Some).map_err(|_| RiskError::Overflow),
+                Err(_) => Ok(None),
+            }
+        };
+        if let Some(spread) = get("spread_bps")? {
+            params.spread_bps = spread;
+        }
+        if let Some(leverage) = get("max_leverage_bps")? {
+            params.max_leverage_bps = leverage;
+        }
+        Ok(params)
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> Result<bool> {
+        match self.call("should_shutdown", context)? {
+            Some(answer) => answer.is_truthy().map_err(|_| RiskError::Unauthorized),
+            None => Ok(false),
+        }
+    }
+
+    fn agent_capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            shutdown: self.object.hasattr("should_shutdown").unwrap_or(false),
+            ..AgentCapabilities::CORE
+        }
+    }
+}
+
+/// Clawcolator engine
+#[pyclass(name = "Engine", unsendable)]
+pub struct PyEngine {
+    inner: Box<ClawcolatorEngine>,
+}
+
+#[pymethods]
+impl PyEngine {
+    /// Engine with the given margin and fee params (bps) and warmup (slots)
+    #[new]
+    #[pyo3(signature = (maintenance_margin_bps=500, initial_margin_bps=1000, trading_fee_bps=10, warmup_period_slots=100))]
+    fn new(
+        maintenance_margin_bps: u64,
+        initial_margin_bps: u64,
+        trading_fee_bps: u64,
+        warmup_period_slots: u64,
+    ) -> Self {
+        let params = RiskParams {
+            warmup_period_slots,
+            maintenance_margin_bps,
+            initial_margin_bps,
+            trading_fee_bps,
+            max_accounts: crate::MAX_ACCOUNTS as u64,
+            new_account_fee: U128::new(0),
+            risk_reduction_threshold: U128::new(0),
+            maintenance_fee_per_slot: U128::new(0),
+            max_crank_staleness_slots: u64::MAX,
+            liquidation_fee_bps: 50,
+            liquidation_fee_cap: U128::new(100_000),
+            liquidation_buffer_bps: 100,
+            min_liquidation_abs: U128::new(100_000),
+        };
+        Self {
+            inner: Box::new(ClawcolatorEngine::new(params)),
+        }
+    }
+
+    /// Open an LP account funded with `deposit`; returns its index
+    fn add_lp(&mut self, deposit: u128) -> PyResult<u16> {
+        let risk = self.inner.risk_engine_mut();
+        let idx = risk.add_lp([0; 32], [0; 32], 0).map_err(py_error)?;
+        risk.deposit(idx, deposit, 0).map_err(py_error)?;
+        Ok(idx)
+    }
+
+    /// Open a user account funded with `deposit`; returns its index
+    fn add_user(&mut self, deposit: u128) -> PyResult<u16> {
+        let risk = self.inner.risk_engine_mut();
+        let idx = risk.add_user(0).map_err(py_error)?;
+        risk.deposit(idx, deposit, 0).map_err(py_error)?;
+        Ok(idx)
+    }
+
+    /// Run a trade through a Python agent at a fresh oracle price
+    fn execute_trade(
+        &mut self,
+        agent: Bound<'_, PyAny>,
+        user_idx: u16,
+        oracle_price: u64,
+        size: i128,
+        now_slot: u64,
+    ) -> PyResult<()> {
+        let oracle = OraclePrice::new(oracle_price, 0, now_slot);
+        self.inner
+            .execute_trade(&PyAgent::new(agent), user_idx, oracle, size, now_slot)
+            .map_err(py_error)
+    }
+
+    /// Publish a Python agent's market params
+    fn update_market_params(&mut self, agent: Bound<'_, PyAny>) -> PyResult<()> {
+        self.inner
+            .update_market_params(&PyAgent::new(agent))
+            .map_err(py_error)
+    }
+
+    /// Crank the engine as `caller_idx`
+    fn crank(&mut self, caller_idx: u16, now_slot: u64, oracle_price: u64) -> PyResult<()> {
+        self.inner
+            .crank(caller_idx, now_slot, oracle_price, None)
+            .map(|_| ())
+            .map_err(py_error)
+    }
+
+    /// Context an agent would see at `oracle_price`
+    fn context(&self, oracle_price: u64) -> PyContext {
+        let slot = self.inner.risk_engine().current_slot;
+        PyContext::from(&self.inner.build_context(OraclePrice::new(oracle_price, 0, slot)))
+    }
+
+    /// Account state (None if the slot is free)
+    fn account(&self, idx: u16, oracle_price: u64) -> Option<PyAccount> {
+        self.inner.account_view(idx, oracle_price).map(PyAccount::from)
+    }
+}
+
+/// Python module entry point
+#[pymodule]
+fn pyclawcolator(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyEngine>()?;
+    m.add_class::<PyContext>()?;
+    m.add_class::<PyTradeRequest>()?;
+    m.add_class::<PyAccount>()?;
+    Ok(())
+}
@@ -0,0 +1,171 @@
+//! Referral program: a slice of a referred account's trading fees accrues
+//! to its referrer
+//!
+//! The slice comes out of the insurance share of each fee (`FeeTotals::
+//! referral`) and, like keeper rewards, stays in the insurance fund as a
+//! claim until the referrer claims it into capital. The share is capped by
+//! `ReferralBounds` and can never exceed the insurance share of the split.
+
+use crate::{RiskEngine, RiskError, Result, MAX_ACCOUNTS, U128};
+
+/// Share of a referred account's trading fees paid to its referrer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReferralPolicy {
+    /// Share of each fee (bps)
+    pub share_bps: u64,
+}
+
+/// Protocol caps on the referral program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReferralBounds {
+    /// Largest share of a fee a referrer can receive
+    pub max_share_bps: u64,
+}
+
+impl Default for ReferralBounds {
+    fn default() -> Self {
+        Self { max_share_bps: 2_000 }
+    }
+}
+
+impl ReferralPolicy {
+    pub fn validate(&self, bounds: &ReferralBounds) -> Result<()> {
+        if self.share_bps > bounds.max_share_bps || self.share_bps > 10_000 {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+}
+
+/// Referrer of one account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReferralLink {
+    /// Account id of the referred account (guards against slot reuse)
+    pub account_id: u64,
+
+    /// Referrer's index
+    pub referrer_idx: u16,
+
+    /// Referrer's account id
+    pub referrer_account_id: u64,
+}
+
+/// Referral links and per-referrer claims
+pub struct ReferralLedger {
+    links: [Option<ReferralLink>; MAX_ACCOUNTS],
+
+    /// Unclaimed rebates per referrer index
+    pending: [u128; MAX_ACCOUNTS],
+
+    /// Sum of `pending`
+    pending_total: u128,
+
+    /// Rebates accrued to date
+    accrued_total: u128,
+
+    /// Rebates claimed to date
+    paid_total: u128,
+}
+
+impl ReferralLedger {
+    pub const fn new() -> Self {
+        Self {
+            links: [None; MAX_ACCOUNTS],
+            pending: [0; MAX_ACCOUNTS],
+            pending_total: 0,
+            accrued_total: 0,
+            paid_total: 0,
+        }
+    }
+
+    /// Referrer link of `idx`, if it still belongs to `account_id`
+    pub fn link(&self, idx: u16, account_id: u64) -> Option<&ReferralLink> {
+        self.links
+            .get(idx as usize)?
+            .as_ref()
+            .filter(|link| link.account_id == account_id)
+    }
+
+    /// Unclaimed rebates of referrer `idx`
+    pub fn pending(&self, idx: u16) -> u128 {
+        self.pending.get(idx as usize).copied().unwrap_or(0)
+    }
+
+    /// Unclaimed rebates across all referrers (a claim on insurance)
+    pub fn pending_total(&self) -> u128 {
+        self.pending_total
+    }
+
+    /// Rebates accrued to date
+    pub fn accrued_total(&self) -> u128 {
+        self.accrued_total
+    }
+
+    /// Rebates claimed to date
+    pub fn paid_total(&self) -> u128 {
+        self.paid_total
+    }
+
+    pub(crate) fn set_link(&mut self, idx: u16, link: ReferralLink) {
+        self.links[idx as usize] = Some(link);
+    }
+
+    pub(crate) fn accrue(&mut self, referrer_idx: u16, amount: u128) {
+        self.pending[referrer_idx as usize] += amount;
+        self.pending_total += amount;
+        self.accrued_total = self.accrued_total.saturating_add(amount);
+    }
+
+    /// Follow account `account_id` moved by compaction into the free slot
+    /// `to`: its own link, its claims as a referrer and the links naming it
+    pub(crate) fn move_account(&mut self, from: u16, to: u16, account_id: u64) {
+        self.links.swap(from as usize, to as usize);
+        self.pending.swap(from as usize, to as usize);
+        for link in self.links.iter_mut().flatten() {
+            if link.referrer_idx == from && link.referrer_account_id == account_id {
+                link.referrer_idx = to;
+            }
+        }
+    }
+
+    /// Drop links and claims of accounts that no longer exist
+    pub(crate) fn forfeit_closed(&mut self, engine: &RiskEngine) {
+        for idx in 0..MAX_ACCOUNTS {
+            if engine.is_used(idx) {
+                continue;
+            }
+            self.links[idx] = None;
+            self.pending_total -= self.pending[idx];
+            self.pending[idx] = 0;
+        }
+    }
+
+    /// Pay referrer `idx`'s rebates from insurance into its capital (at
+    /// most the insurance balance; the rest stays pending)
+    pub(crate) fn claim(&mut self, engine: &mut RiskEngine, idx: u16) -> Result<u128> {
+        if idx as usize >= MAX_ACCOUNTS || !engine.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let i = idx as usize;
+        let insurance = engine.insurance_fund.balance.get();
+        let pay = self.pending[i].min(insurance);
+        if pay == 0 {
+            return Ok(0);
+        }
+
+        engine.insurance_fund.balance = U128::new(insurance - pay);
+        let capital = engine.accounts[i].capital.get();
+        engine.set_capital(i, capital.checked_add(pay).ok_or(RiskError::Overflow)?);
+
+        self.pending[i] -= pay;
+        self.pending_total -= pay;
+        self.paid_total = self.paid_total.saturating_add(pay);
+        Ok(pay)
+    }
+}
+
+impl Default for ReferralLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,381 @@
+//! Gym-style reinforcement-learning environment over the engine
+//!
+//! Gated behind the `rl` feature, which links `std` and serde. `RlEnv`
+//! plays the market of `simulate` one order at a time: each step a
+//! synthetic trader may send an order, the learning agent answers it with
+//! an `Action` (a `TradeDecision`, optionally after publishing new market
+//! params), the engine validates and fills the answer exactly as it would
+//! a live agent's, and a keeper cranks at the next oracle price. The
+//! observation is the `AgentContext` the engine would hand an agent plus
+//! the pending order, available as JSON (`to_json`) or as a fixed-length
+//! feature vector (`features`); the reward is the change in the LP's
+//! mark-to-market value. An episode ends after `SimulationParams::steps`,
+//! on shutdown, or once the LP is wiped out or the vault stops covering
+//! capital plus insurance.
+//!
+//! Everything random comes from one SplitMix64 stream seeded at `reset`,
+//! so a seed and a sequence of actions always replay the same episode.
+
+use serde::Serialize;
+use std::boxed::Box;
+use std::string::String;
+
+use super::simulation::SplitMix64;
+use super::{
+    AgentContext, AnomalyResponse, ClawcolatorEngine, MarketParams, OpenClawAgent, OraclePrice,
+    SimulationParams, TradeDecision, TradeRequest, AGENT_LP_IDX,
+};
+use crate::{RiskError, RiskParams, Result};
+
+/// Length of `Observation::features`
+pub const OBSERVATION_LEN: usize = 14;
+
+/// The learning agent's answer for one step
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Action {
+    /// Answer to the pending order (ignored when there is none)
+    pub decision: TradeDecision,
+
+    /// Params to publish before answering (None = keep the current ones)
+    pub params: Option<MarketParams>,
+}
+
+impl Action {
+    /// Reject the pending order, keep the params
+    pub fn reject() -> Self {
+        Self {
+            decision: TradeDecision::Reject { reason: super::TradeRejectionReason::MarketConditions },
+            params: None,
+        }
+    }
+}
+
+/// What the agent sees before acting
+#[derive(Clone, Debug)]
+pub struct Observation {
+    pub context: AgentContext,
+
+    /// Order waiting for a decision (None = no order this step)
+    pub request: Option<TradeRequest>,
+
+    /// LP position and mark-to-market value (capital + PnL + unrealized)
+    pub lp_position: i128,
+    pub lp_value: i128,
+}
+
+/// Serialized observation; amounts that do not fit in 64 bits are decimal
+/// strings, as in `indexer`
+#[derive(Serialize)]
+struct ObservationRecord {
+    current_slot: u64,
+    oracle_price: u64,
+    oracle_confidence: u64,
+    oracle_stale: bool,
+    #[serde(serialize_with = "amount")]
+    vault: u128,
+    #[serde(serialize_with = "amount")]
+    insurance_balance: u128,
+    #[serde(serialize_with = "amount")]
+    total_capital: u128,
+    #[serde(serialize_with = "amount")]
+    total_positive_pnl: u128,
+    #[serde(serialize_with = "amount")]
+    total_open_interest: u128,
+    risk_reduction_mode: bool,
+    oracle_divergence_bps: u64,
+    #[serde(serialize_with = "amount")]
+    lp_liquidity: u128,
+    lp_liquidation_price: Option<u64>,
+    move_var_bps: u64,
+    #[serde(serialize_with = "amount")]
+    lp_var: u128,
+    largest_oi_share_bps: u64,
+    #[serde(serialize_with = "signed_amount")]
+    lp_position: i128,
+    #[serde(serialize_with = "signed_amount")]
+    lp_value: i128,
+    request: Option<RequestRecord>,
+}
+
+#[derive(Serialize)]
+struct RequestRecord {
+    user_idx: u16,
+    #[serde(serialize_with = "signed_amount")]
+    size: i128,
+}
+
+impl Observation {
+    /// The observation as one JSON object
+    pub fn to_json(&self) -> String {
+        let c = &self.context;
+        let record = ObservationRecord {
+            current_slot: c.current_slot,
+            oracle_price: c.oracle_price,
+            oracle_confidence: c.oracle_confidence,
+            oracle_stale: c.oracle_stale,
+            vault: c.vault,
+            insurance_balance: c.insurance_balance,
+            total_capital: c.total_capital,
+            total_positive_pnl: c.total_positive_pnl,
+            total_open_interest: c.total_open_interest,
+            risk_reduction_mode: c.risk_reduction_mode,
+            oracle_divergence_bps: c.oracle_divergence_bps,
+            lp_liquidity: c.lp_liquidity,
+            lp_liquidation_price: c.lp_liquidation_price,
+            move_var_bps: c.value_at_risk.move_var_bps,
+            lp_var: c.value_at_risk.lp_var,
+            largest_oi_share_bps: c.concentration.largest_share_bps,
+            lp_position: self.lp_position,
+            lp_value: self.lp_value,
+            request: self.request.map(|r| RequestRecord { user_idx: r.user_idx, size: r.size }),
+        };
+        serde_json::to_string(&record).unwrap_or_default()
+    }
+
+    /// Numeric view for function approximators: prices in quote units
+    /// (1.0 = 1e6), amounts as multiples of the LP's starting value, bps
+    /// as fractions, flags as 0/1
+    ///
+    /// Order: oracle price, confidence, stale, vault, insurance, total
+    /// capital, open interest, LP liquidity, LP position, LP value, risk
+    /// reduction, oracle divergence, order size, order pending.
+    pub fn features(&self, lp_start: i128) -> [f64; OBSERVATION_LEN] {
+        let c = &self.context;
+        let scale = (lp_start.max(1)) as f64;
+        let price = c.oracle_price as f64 / 1e6;
+        let flag = |b: bool| if b { 1.0 } else { 0.0 };
+        let size = self.request.map_or(0, |r| r.size);
+        [
+            price,
+            c.oracle_confidence as f64 / c.oracle_price.max(1) as f64,
+            flag(c.oracle_stale),
+            c.vault as f64 / scale,
+            c.insurance_balance as f64 / scale,
+            c.total_capital as f64 / scale,
+            c.total_open_interest as f64 * price / scale,
+            c.lp_liquidity as f64 / scale,
+            self.lp_position as f64 * price / scale,
+            self.lp_value as f64 / scale,
+            flag(c.risk_reduction_mode),
+            c.oracle_divergence_bps as f64 / 10_000.0,
+            size as f64 * price / scale,
+            flag(self.request.is_some()),
+        ]
+    }
+}
+
+/// Side information about a step
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StepInfo {
+    /// The pending order was filled
+    pub filled: bool,
+
+    /// Why the engine refused the answer or the fill (None = filled or no order)
+    pub trade_error: Option<RiskError>,
+
+    /// Why the engine refused the published params
+    pub params_error: Option<RiskError>,
+
+    /// Accounts liquidated by the crank
+    pub liquidations: u32,
+}
+
+/// Outcome of `RlEnv::step`
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub observation: Observation,
+
+    /// Change in the LP's mark-to-market value over the step
+    pub reward: f64,
+
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+/// Replays the action's answer and params to the engine
+struct ActionAgent {
+    decision: TradeDecision,
+    params: MarketParams,
+}
+
+impl OpenClawAgent for ActionAgent {
+    fn decide_trade(&self, _context: &AgentContext, _request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(self.decision)
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(self.params)
+    }
+
+    fn detect_anomalies(&self, _context: &AgentContext) -> Result<AnomalyResponse> {
+        Ok(AnomalyResponse::none())
+    }
+
+    fn should_shutdown(&self, _context: &AgentContext) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// The engine as an RL environment
+pub struct RlEnv {
+    risk_params: RiskParams,
+    params: SimulationParams,
+    engine: Box<ClawcolatorEngine>,
+    rng: SplitMix64,
+    step: u32,
+    slot: u64,
+    price: u64,
+    lp_start: i128,
+    lp_value: i128,
+    pending: Option<TradeRequest>,
+}
+
+impl RlEnv {
+    /// Environment for `params`' market (the seed is taken at `reset`)
+    pub fn new(risk_params: RiskParams, params: SimulationParams) -> Result<Self> {
+        params.validate()?;
+        let mut env = Self {
+            risk_params,
+            params,
+            engine: Box::new(ClawcolatorEngine::new(risk_params)),
+            rng: SplitMix64(params.seed),
+            step: 0,
+            slot: 0,
+            price: params.start_price,
+            lp_start: 0,
+            lp_value: 0,
+            pending: None,
+        };
+        env.reset(params.seed)?;
+        Ok(env)
+    }
+
+    /// Start a new episode on a fresh engine
+    pub fn reset(&mut self, seed: u64) -> Result<Observation> {
+        let mut engine = Box::new(ClawcolatorEngine::new(self.risk_params));
+        let risk = engine.risk_engine_mut();
+        let lp = risk.add_lp([0; 32], [0; 32], 0)?;
+        risk.deposit(lp, self.params.lp_deposit, 0)?;
+        for _ in 0..self.params.traders {
+            let idx = risk.add_user(0)?;
+            risk.deposit(idx, self.params.trader_deposit, 0)?;
+        }
+        self.engine = engine;
+        self.rng = SplitMix64(seed);
+        self.step = 0;
+        self.slot = 0;
+        self.price = self.params.start_price;
+        self.lp_start = self.lp_value_at(self.price);
+        self.lp_value = self.lp_start;
+        self.pending = self.draw_order();
+        Ok(self.observe())
+    }
+
+    /// Answer the pending order, then advance the market one step
+    ///
+    /// Fails only if the episode is already over; refusals of the action
+    /// are reported in `StepInfo` and cost whatever they cost the LP.
+    pub fn step(&mut self, action: &Action) -> Result<Step> {
+        if self.is_done() {
+            return Err(RiskError::Unauthorized);
+        }
+        let agent = ActionAgent {
+            decision: action.decision,
+            params: action.params.unwrap_or(*self.engine.market_params()),
+        };
+        let mut info = StepInfo::default();
+        if action.params.is_some() {
+            info.params_error = self.engine.update_market_params(&agent).err();
+        }
+        if let Some(request) = self.pending.take() {
+            let oracle = OraclePrice::new(self.price, 0, self.slot);
+            match self.engine.submit_trade(&agent, request, oracle, self.slot) {
+                Ok(()) => info.filled = true,
+                Err(e) => info.trade_error = Some(e),
+            }
+        }
+
+        self.price = self.params.price_model.step(self.price, self.step, &mut self.rng);
+        self.step += 1;
+        self.slot += 1;
+        // A failed crank leaves the market as it was; the episode goes on
+        if let Ok(outcome) = self.engine.crank(AGENT_LP_IDX, self.slot, self.price, None) {
+            info.liquidations = outcome.num_liquidations;
+        }
+        self.pending = if self.is_done() { None } else { self.draw_order() };
+
+        let value = self.lp_value_at(self.price);
+        let reward = value.saturating_sub(self.lp_value) as f64;
+        self.lp_value = value;
+        Ok(Step {
+            observation: self.observe(),
+            reward,
+            done: self.is_done(),
+            info,
+        })
+    }
+
+    /// The episode is over
+    pub fn is_done(&self) -> bool {
+        let risk = self.engine.risk_engine();
+        let solvent = risk.vault.get() >= risk.c_tot.get().saturating_add(risk.insurance_fund.balance.get());
+        self.step >= self.params.steps || self.engine.shutdown || self.lp_value <= 0 || !solvent
+    }
+
+    /// LP value at the start of the episode (the `features` scale)
+    pub fn lp_start(&self) -> i128 {
+        self.lp_start
+    }
+
+    pub fn engine(&self) -> &ClawcolatorEngine {
+        &self.engine
+    }
+
+    fn observe(&self) -> Observation {
+        let oracle = OraclePrice::new(self.price, 0, self.slot);
+        Observation {
+            context: self.engine.build_context(oracle),
+            request: self.pending,
+            lp_position: self.engine.account_ref(AGENT_LP_IDX).map_or(0, |lp| lp.position_size()),
+            lp_value: self.lp_value,
+        }
+    }
+
+    fn lp_value_at(&self, price: u64) -> i128 {
+        self.engine.account_ref(AGENT_LP_IDX).map_or(0, |lp| {
+            (lp.capital() as i128)
+                .saturating_add(lp.pnl())
+                .saturating_add(lp.unrealized_pnl(price))
+        })
+    }
+
+    /// Order from a random trader, with `simulate`'s odds and sizes
+    fn draw_order(&mut self) -> Option<TradeRequest> {
+        if self.params.traders == 0 {
+            return None;
+        }
+        let trader = AGENT_LP_IDX + 1 + (self.rng.next() % self.params.traders as u64) as u16;
+        let roll = self.rng.next();
+        let size = self.rng.next() as u128 % self.params.max_order_size.saturating_add(1);
+        if roll % 10_000 >= self.params.order_probability_bps as u64 || size == 0 {
+            return None;
+        }
+        let size = if self.rng.next() & 1 == 0 { size as i128 } else { -(size as i128) };
+        Some(TradeRequest {
+            user_idx: trader,
+            size,
+            requested_price: None,
+            client_order_id: None,
+            sequence: None,
+            valid_until_slot: None,
+        })
+    }
+}
+
+fn amount<S: serde::Serializer>(value: &u128, s: S) -> core::result::Result<S::Ok, S::Error> {
+    s.collect_str(value)
+}
+
+fn signed_amount<S: serde::Serializer>(value: &i128, s: S) -> core::result::Result<S::Ok, S::Error> {
+    s.collect_str(value)
+}
@@ -0,0 +1,321 @@
+//! Seeded market simulation against an agent
+//!
+//! `simulate` funds the agent's LP and a crowd of synthetic traders on a
+//! fresh engine, then walks the oracle along a `PriceModel`. Each step,
+//! every trader flips a coin to send a random-sized order through the
+//! agent, and a keeper cranks. Everything random comes from one SplitMix64
+//! stream seeded by `SimulationParams::seed`, so a run is reproducible
+//! from its params alone. Unlike the Monte Carlo paths, which only crank,
+//! this exercises the agent's quoting, the fee split and liquidations of
+//! the traders it took the other side of. `replay` runs the same order
+//! flow along a recorded price series instead, for backtests on history.
+
+use super::stress::shocked_price;
+use super::{ClawcolatorEngine, OpenClawAgent, OraclePrice, TradeRequest, AGENT_LP_IDX};
+use crate::{RiskError, Result, MAX_ORACLE_PRICE};
+
+/// Most synthetic traders in one run
+pub const MAX_SIM_TRADERS: u16 = 64;
+
+/// Most steps in one run
+pub const MAX_SIM_STEPS: u32 = 10_000;
+
+/// SplitMix64: tiny, fast and good enough for price paths
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `-bound..=bound`
+    fn signed(&mut self, bound: u64) -> i64 {
+        (self.next() % (2 * bound + 1)) as i64 - bound as i64
+    }
+}
+
+/// How the oracle moves from step to step
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceModel {
+    /// Uniform moves in ±`step_bps`
+    RandomWalk { step_bps: u32 },
+
+    /// A random walk plus `drift_bps` every step (negative = bear market)
+    Trend { drift_bps: i32, step_bps: u32 },
+
+    /// A random walk with a one-off `shock_bps` jump at step `at_step`
+    Crash { at_step: u32, shock_bps: i32, step_bps: u32 },
+}
+
+impl Default for PriceModel {
+    fn default() -> Self {
+        PriceModel::RandomWalk { step_bps: 100 }
+    }
+}
+
+impl PriceModel {
+    /// Price after step `step` from `price`
+    pub(crate) fn step(&self, price: u64, step: u32, rng: &mut SplitMix64) -> u64 {
+        let (drift, step_bps) = match *self {
+            PriceModel::RandomWalk { step_bps } => (0, step_bps),
+            PriceModel::Trend { drift_bps, step_bps } => (drift_bps as i64, step_bps),
+            PriceModel::Crash { at_step, shock_bps, step_bps } if at_step == step => {
+                (shock_bps as i64, step_bps)
+            }
+            PriceModel::Crash { step_bps, .. } => (0, step_bps),
+        };
+        let bps = drift + rng.signed(step_bps as u64);
+        shocked_price(price, bps).clamp(1, MAX_ORACLE_PRICE)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let (drift, step_bps) = match *self {
+            PriceModel::RandomWalk { step_bps } => (0, step_bps),
+            PriceModel::Trend { drift_bps, step_bps } => (drift_bps, step_bps),
+            PriceModel::Crash { shock_bps, step_bps, .. } => (shock_bps, step_bps),
+        };
+        if step_bps >= 10_000 || drift <= -10_000 || drift > 100_000 {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+}
+
+/// What to simulate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimulationParams {
+    pub price_model: PriceModel,
+
+    /// Oracle price before the first step
+    pub start_price: u64,
+
+    /// Steps; each is one slot of trading followed by a crank
+    pub steps: u32,
+
+    /// Synthetic traders
+    pub traders: u16,
+
+    /// Capital of each trader
+    pub trader_deposit: u128,
+
+    /// Capital of the agent's LP
+    pub lp_deposit: u128,
+
+    /// Largest order a trader sends (drawn uniformly up to it, either side)
+    pub max_order_size: u128,
+
+    /// Chance a trader sends an order in a step (bps)
+    pub order_probability_bps: u16,
+
+    pub seed: u64,
+}
+
+impl Default for SimulationParams {
+    fn default() -> Self {
+        Self {
+            price_model: PriceModel::default(),
+            start_price: 1_000_000,
+            steps: 100,
+            traders: 10,
+            trader_deposit: 1_000_000,
+            lp_deposit: 100_000_000,
+            max_order_size: 1_000_000,
+            order_probability_bps: 5_000,
+            seed: 0,
+        }
+    }
+}
+
+impl SimulationParams {
+    pub fn validate(&self) -> Result<()> {
+        self.price_model.validate()?;
+        if self.start_price == 0
+            || self.start_price > MAX_ORACLE_PRICE
+            || self.steps > MAX_SIM_STEPS
+            || self.traders > MAX_SIM_TRADERS
+            || self.order_probability_bps > 10_000
+            || self.max_order_size > i128::MAX as u128
+        {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of a run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SimulationReport {
+    pub seed: u64,
+    pub steps: u32,
+
+    /// Oracle price after the last step, and its range over the run
+    pub final_price: u64,
+    pub min_price: u64,
+    pub max_price: u64,
+
+    /// Orders sent, filled and rejected by the agent or the engine
+    pub orders: u64,
+    pub fills: u64,
+    pub rejections: u64,
+
+    /// Sum of absolute filled sizes
+    pub volume: u128,
+
+    /// Accounts liquidated by the cranks
+    pub liquidations: u64,
+
+    /// Cranks that returned an error
+    pub failed_cranks: u32,
+
+    /// Fees collected over the run
+    pub fees: u128,
+
+    /// Change in the LP's capital plus PnL (mark-to-market at the end)
+    pub lp_pnl: i128,
+
+    /// Insurance balance before the first step, at its lowest and at the end
+    pub insurance_start: u128,
+    pub insurance_min: u128,
+    pub insurance_end: u128,
+
+    /// Bankruptcy holes left to the haircut over the run
+    pub socialized: u128,
+
+    /// Whether the vault still covered capital plus insurance at the end
+    /// (the primary conservation invariant)
+    pub solvent: bool,
+}
+
+/// Run `params` on `engine`, which must have no accounts yet, with `agent`
+/// quoting every order
+pub fn simulate<A: OpenClawAgent>(
+    engine: &mut ClawcolatorEngine,
+    agent: &A,
+    params: &SimulationParams,
+) -> Result<SimulationReport> {
+    params.validate()?;
+    let model = params.price_model;
+    run(engine, agent, params, params.start_price, params.steps, |price, step, rng| {
+        model.step(price, step, rng)
+    })
+}
+
+/// Replay recorded oracle `prices` on `engine` with `params`' synthetic
+/// order flow: the first price opens the run and each later one is a step
+/// (`price_model`, `start_price` and `steps` are ignored)
+pub fn replay<A: OpenClawAgent>(
+    engine: &mut ClawcolatorEngine,
+    agent: &A,
+    params: &SimulationParams,
+    prices: &[u64],
+) -> Result<SimulationReport> {
+    let (&start_price, path) = prices.split_first().ok_or(RiskError::Overflow)?;
+    if path.len() > MAX_SIM_STEPS as usize || prices.iter().any(|&p| p == 0 || p > MAX_ORACLE_PRICE) {
+        return Err(RiskError::Overflow);
+    }
+    SimulationParams { start_price, steps: path.len() as u32, ..*params }.validate()?;
+    run(engine, agent, params, start_price, path.len() as u32, |_, step, _| path[step as usize])
+}
+
+fn run<A: OpenClawAgent>(
+    engine: &mut ClawcolatorEngine,
+    agent: &A,
+    params: &SimulationParams,
+    start_price: u64,
+    steps: u32,
+    mut next_price: impl FnMut(u64, u32, &mut SplitMix64) -> u64,
+) -> Result<SimulationReport> {
+    let slot = engine.risk_engine().current_slot;
+    let risk = engine.risk_engine_mut();
+    let lp = risk.add_lp([0; 32], [0; 32], 0)?;
+    if lp != AGENT_LP_IDX {
+        return Err(RiskError::AccountKindMismatch);
+    }
+    risk.deposit(lp, params.lp_deposit, slot)?;
+    let first_trader = lp + 1;
+    for i in 0..params.traders {
+        let idx = risk.add_user(0)?;
+        if idx != first_trader + i {
+            return Err(RiskError::AccountKindMismatch);
+        }
+        risk.deposit(idx, params.trader_deposit, slot)?;
+    }
+    // Publishing can fail if the agent's params fall outside the bounds;
+    // the run then trades under the engine's defaults
+    let _ = engine.update_market_params(agent);
+
+    let lp_value = |engine: &ClawcolatorEngine, price: u64| {
+        engine.account_ref(AGENT_LP_IDX).map_or(0, |lp| {
+            (lp.capital() as i128)
+                .saturating_add(lp.pnl())
+                .saturating_add(lp.unrealized_pnl(price))
+        })
+    };
+    let insurance = |engine: &ClawcolatorEngine| engine.risk_engine().insurance_fund.balance.get();
+    let fees_start = engine.fee_totals().total();
+    let socialized_start = engine.bankruptcies().totals().socialized;
+    let lp_start = lp_value(engine, start_price);
+    let mut rng = SplitMix64(params.seed);
+    let mut price = start_price;
+    let mut report = SimulationReport {
+        seed: params.seed,
+        steps,
+        final_price: price,
+        min_price: price,
+        max_price: price,
+        insurance_start: insurance(engine),
+        insurance_min: insurance(engine),
+        ..SimulationReport::default()
+    };
+
+    for step in 0..steps {
+        let now = slot + step as u64 + 1;
+        price = next_price(price, step, &mut rng);
+        let oracle = OraclePrice::new(price, 0, now);
+        for trader in first_trader..first_trader + params.traders {
+            let roll = rng.next();
+            let size = rng.next() as u128 % params.max_order_size.saturating_add(1);
+            if roll % 10_000 >= params.order_probability_bps as u64 || size == 0 {
+                continue;
+            }
+            let size = if rng.next() & 1 == 0 { size as i128 } else { -(size as i128) };
+            let request = TradeRequest {
+                user_idx: trader,
+                size,
+                requested_price: None,
+                client_order_id: None,
+                sequence: None,
+                valid_until_slot: None,
+            };
+            report.orders += 1;
+            match engine.submit_trade(agent, request, oracle, now) {
+                Ok(()) => {
+                    report.fills += 1;
+                    let filled = engine.last_fill().map_or(0, |fill| fill.size.unsigned_abs());
+                    report.volume = report.volume.saturating_add(filled);
+                }
+                Err(_) => report.rejections += 1,
+            }
+        }
+        match engine.crank(AGENT_LP_IDX, now, price, None) {
+            Ok(outcome) => report.liquidations += outcome.num_liquidations as u64,
+            Err(_) => report.failed_cranks += 1,
+        }
+        report.min_price = report.min_price.min(price);
+        report.max_price = report.max_price.max(price);
+        report.insurance_min = report.insurance_min.min(insurance(engine));
+    }
+
+    report.final_price = price;
+    report.insurance_end = insurance(engine);
+    report.fees = engine.fee_totals().total().saturating_sub(fees_start);
+    report.lp_pnl = lp_value(engine, price).saturating_sub(lp_start);
+    report.socialized = engine.bankruptcies().totals().socialized.saturating_sub(socialized_start);
+    let risk = engine.risk_engine();
+    report.solvent = risk.vault.get() >= risk.c_tot.get().saturating_add(risk.insurance_fund.balance.get());
+    Ok(report)
+}
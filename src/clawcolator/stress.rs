@@ -0,0 +1,48 @@
+//! Insurance waterfall projection under a hypothetical price shock
+//!
+//! Read-only: every account is marked at the shocked price, deficits of
+//! bankrupt accounts are charged first to the insurance fund and whatever
+//! insurance cannot absorb is reported as socialized loss (the haircut on
+//! positive PnL the engine would apply).
+
+use super::math::{offset_bps, Rounding};
+
+/// Outcome of `ClawcolatorEngine::stress_project`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StressProjection {
+    /// Applied shock (bps of the oracle price, signed)
+    pub shock_bps: i64,
+
+    /// Oracle price after the shock
+    pub shocked_price: u64,
+
+    /// Accounts below maintenance margin at the shocked price
+    pub liquidatable_accounts: u32,
+
+    /// Accounts whose equity turns negative
+    pub bankrupt_accounts: u32,
+
+    /// Sum of negative equity across bankrupt accounts
+    pub total_deficit: u128,
+
+    /// Insurance used to cover deficits
+    pub insurance_drawdown: u128,
+
+    /// Insurance left after covering deficits
+    pub insurance_remaining: u128,
+
+    /// Deficit insurance cannot cover, borne by positive PnL
+    pub socialized_loss: u128,
+}
+
+impl StressProjection {
+    /// Whether the shock would force loss socialization
+    pub fn socialization_triggered(&self) -> bool {
+        self.socialized_loss > 0
+    }
+}
+
+/// Oracle price moved by `shock_bps` (floored at 1)
+pub fn shocked_price(oracle_price: u64, shock_bps: i64) -> u64 {
+    offset_bps(oracle_price, shock_bps.unsigned_abs(), shock_bps >= 0, Rounding::Down).max(1)
+}
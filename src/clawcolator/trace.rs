@@ -0,0 +1,122 @@
+//! Structured logging hooks
+//!
+//! With the `tracing` feature every agent-driven trade runs inside an
+//! `execute_trade` span (`user_idx`, `size`, then `decision` and
+//! `latency_us` once it completes) and every crank inside a `crank` span
+//! (`caller_idx`, `slot`, then `liquidations`, `advanced`, `latency_us`).
+//! Agent code that logs through `tracing` nests under these spans. Without
+//! the feature the hooks are empty and compile away, so no_std and on-chain
+//! builds pay nothing.
+
+use super::{RejectionCause, TradeOutcome};
+use crate::{CrankOutcome, Result};
+
+/// Short label for the decision behind a trade outcome
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+fn decision(outcome: Option<&TradeOutcome>) -> &'static str {
+    match outcome {
+        None => "none",
+        Some(TradeOutcome::Filled(fill)) if fill.vamm => "filled_vamm",
+        Some(TradeOutcome::Filled(_)) => "filled",
+        Some(TradeOutcome::Rejected(rejection)) => match rejection.cause {
+            RejectionCause::Agent(_) => "rejected_by_agent",
+            RejectionCause::CounterQuote => "counter_quote",
+            RejectionCause::AgentError => "agent_error",
+            _ => "rejected_by_protocol",
+        },
+    }
+}
+
+#[cfg(feature = "tracing")]
+mod imp {
+    use super::*;
+    use std::time::Instant;
+    use tracing::field::Empty;
+
+    /// Span around one engine operation
+    pub(crate) struct OpSpan {
+        span: tracing::span::EnteredSpan,
+        start: Instant,
+    }
+
+    impl OpSpan {
+        pub(crate) fn trade(user_idx: u16, size: i128) -> Self {
+            let span = tracing::info_span!("execute_trade", user_idx, size, decision = Empty, latency_us = Empty);
+            Self { span: span.entered(), start: Instant::now() }
+        }
+
+        pub(crate) fn crank(caller_idx: u16, slot: u64) -> Self {
+            let span = tracing::info_span!(
+                "crank",
+                caller_idx,
+                slot,
+                liquidations = Empty,
+                advanced = Empty,
+                latency_us = Empty
+            );
+            Self { span: span.entered(), start: Instant::now() }
+        }
+
+        fn latency_us(&self) -> u64 {
+            self.start.elapsed().as_micros().min(u64::MAX as u128) as u64
+        }
+
+        pub(crate) fn finish_trade(self, outcome: Option<&TradeOutcome>) {
+            let decision = decision(outcome);
+            let latency_us = self.latency_us();
+            self.span.record("decision", decision);
+            self.span.record("latency_us", latency_us);
+            match outcome.and_then(TradeOutcome::rejection) {
+                Some(rejection) => tracing::debug!(
+                    decision,
+                    latency_us,
+                    cause = ?rejection.cause,
+                    error = ?rejection.error,
+                    "trade rejected"
+                ),
+                None => tracing::debug!(decision, latency_us, "trade filled"),
+            }
+        }
+
+        pub(crate) fn finish_crank(self, result: &Result<CrankOutcome>) {
+            let latency_us = self.latency_us();
+            self.span.record("latency_us", latency_us);
+            match result {
+                Ok(outcome) => {
+                    self.span.record("liquidations", outcome.num_liquidations);
+                    self.span.record("advanced", outcome.advanced);
+                    tracing::debug!(latency_us, liquidations = outcome.num_liquidations, "crank");
+                }
+                Err(error) => tracing::warn!(latency_us, ?error, "crank failed"),
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod imp {
+    use super::*;
+
+    /// No-op stand-in when the `tracing` feature is off
+    pub(crate) struct OpSpan;
+
+    impl OpSpan {
+        #[inline(always)]
+        pub(crate) fn trade(_user_idx: u16, _size: i128) -> Self {
+            OpSpan
+        }
+
+        #[inline(always)]
+        pub(crate) fn crank(_caller_idx: u16, _slot: u64) -> Self {
+            OpSpan
+        }
+
+        #[inline(always)]
+        pub(crate) fn finish_trade(self, _outcome: Option<&TradeOutcome>) {}
+
+        #[inline(always)]
+        pub(crate) fn finish_crank(self, _result: &Result<CrankOutcome>) {}
+    }
+}
+
+pub(crate) use imp::OpSpan;
@@ -0,0 +1,365 @@
+//! Risk parameter recommendations from backtest sweeps
+//!
+//! Gated behind the `analysis` feature (std). `tune` runs every point of a
+//! `TuningGrid` (minimum margin × spread × funding cap) over the same set of
+//! backtests: seeded simulations along a `PriceModel`, or recorded price
+//! series replayed with synthetic order flow. At each point the caller's
+//! agent still decides which orders to take and how much of them, but
+//! fills are priced at the point's spread, leverage caps follow its margin
+//! and the agent's funding rate is clamped to its cap.
+//!
+//! A point is feasible when every run stays solvent, socializes nothing and
+//! stays within `TuningConstraints`. The recommendation is the feasible
+//! point that is cheapest for traders (tightest spread, then lowest margin,
+//! then lowest funding cap), applied to the params the agent would publish
+//! on a fresh engine. Tradeoff curves average each value of one dimension
+//! over the other two, for operators who want to pick their own point.
+
+use std::boxed::Box;
+use std::vec::Vec;
+
+use super::analysis::mean;
+use super::impact::offset_price;
+use super::simulation::{replay, simulate};
+use super::{
+    AgentCapabilities, AgentContext, AnomalyResponse, BatchSummary, ClawcolatorEngine,
+    LiquidityAllocation, MarketParams, OpenClawAgent, RiskAssessment, SimulationParams,
+    SimulationReport, TradeDecision, TradeRequest, TradeSide,
+};
+use crate::{RiskError, Result};
+
+/// Price data a candidate is backtested on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Backtest {
+    /// Seeded simulation along `price_model`
+    Synthetic(SimulationParams),
+
+    /// Recorded oracle prices with `flow`'s synthetic traders (see `replay`)
+    Historical { prices: Vec<u64>, flow: SimulationParams },
+}
+
+impl Backtest {
+    fn run<A: OpenClawAgent>(&self, engine: &mut ClawcolatorEngine, agent: &A) -> Result<SimulationReport> {
+        match self {
+            Backtest::Synthetic(params) => simulate(engine, agent, params),
+            Backtest::Historical { prices, flow } => replay(engine, agent, flow, prices),
+        }
+    }
+}
+
+/// Values swept in each dimension
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TuningGrid {
+    /// Minimum margin (bps); leverage caps are set to match
+    pub margin_bps: Vec<u64>,
+
+    /// Spread fills are priced at (bps from the oracle)
+    pub spread_bps: Vec<u64>,
+
+    /// Largest funding rate the agent may charge (bps per slot, either side)
+    pub funding_cap_bps_per_slot: Vec<u64>,
+}
+
+impl Default for TuningGrid {
+    fn default() -> Self {
+        Self {
+            margin_bps: std::vec![500, 1_000, 2_000],
+            spread_bps: std::vec![5, 10, 25, 50],
+            funding_cap_bps_per_slot: std::vec![0, 1, 5],
+        }
+    }
+}
+
+impl TuningGrid {
+    pub fn validate(&self) -> Result<()> {
+        // Margins under 1% would need leverage above the 100x ceiling
+        let margins_ok = self.margin_bps.iter().all(|&m| (100..=10_000).contains(&m));
+        let spreads_ok = self.spread_bps.iter().all(|&s| s < 10_000);
+        let funding_ok = self.funding_cap_bps_per_slot.iter().all(|&f| f <= 10_000);
+        if self.margin_bps.is_empty()
+            || self.spread_bps.is_empty()
+            || self.funding_cap_bps_per_slot.is_empty()
+            || !(margins_ok && spreads_ok && funding_ok)
+        {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+
+    /// Every combination, margin-major
+    pub fn points(&self) -> Vec<GridPoint> {
+        let mut points = Vec::new();
+        for &margin_bps in &self.margin_bps {
+            for &spread_bps in &self.spread_bps {
+                for &funding_cap_bps_per_slot in &self.funding_cap_bps_per_slot {
+                    points.push(GridPoint { margin_bps, spread_bps, funding_cap_bps_per_slot });
+                }
+            }
+        }
+        points
+    }
+}
+
+/// What every run of a feasible point must satisfy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TuningConstraints {
+    /// Lowest acceptable change in the LP's value over a run
+    pub min_lp_pnl: i128,
+
+    /// Largest acceptable insurance drawdown in a run
+    pub max_insurance_drawdown: u128,
+
+    /// Most liquidations acceptable in a run
+    pub max_liquidations: u64,
+}
+
+impl Default for TuningConstraints {
+    fn default() -> Self {
+        Self {
+            min_lp_pnl: 0,
+            max_insurance_drawdown: u128::MAX,
+            max_liquidations: u64::MAX,
+        }
+    }
+}
+
+impl TuningConstraints {
+    fn admits(&self, run: &SimulationReport) -> bool {
+        run.solvent
+            && run.socialized == 0
+            && run.lp_pnl >= self.min_lp_pnl
+            && run.insurance_start.saturating_sub(run.insurance_min) <= self.max_insurance_drawdown
+            && run.liquidations <= self.max_liquidations
+    }
+}
+
+/// One combination of swept values
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridPoint {
+    pub margin_bps: u64,
+    pub spread_bps: u64,
+    pub funding_cap_bps_per_slot: u64,
+}
+
+impl GridPoint {
+    /// `params` with this point's margin, leverage caps, spread and funding cap
+    pub fn apply(&self, mut params: MarketParams) -> MarketParams {
+        // 1000 bps of leverage is 10x: the cap at which a position holds
+        // exactly the minimum margin
+        let max_leverage_bps = 1_000_000 / self.margin_bps.max(1);
+        params.min_margin_bps = self.margin_bps;
+        params.max_leverage_bps = max_leverage_bps;
+        for limits in params.tier_limits.iter_mut() {
+            limits.max_leverage_bps = max_leverage_bps;
+        }
+        params.spread_bps = self.spread_bps;
+        let cap = self.funding_cap_bps_per_slot.min(i64::MAX as u64) as i64;
+        params.funding_rate_bps_per_slot = params.funding_rate_bps_per_slot.clamp(-cap, cap);
+        params
+    }
+}
+
+/// The caller's agent under one grid point
+struct Candidate<'a, A> {
+    agent: &'a A,
+    point: GridPoint,
+}
+
+impl<A: OpenClawAgent> OpenClawAgent for Candidate<'_, A> {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(match self.agent.decide_trade(context, request)? {
+            TradeDecision::Accept { size, .. } => TradeDecision::Accept {
+                price: offset_price(context.oracle_price, size, self.point.spread_bps),
+                size,
+            },
+            decision => decision,
+        })
+    }
+
+    fn get_market_params(&self, context: &AgentContext) -> Result<MarketParams> {
+        Ok(self.point.apply(self.agent.get_market_params(context)?))
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        self.agent.decide_liquidity_allocation(context)
+    }
+
+    fn assess_risk(&self, context: &AgentContext) -> Result<RiskAssessment> {
+        self.agent.assess_risk(context)
+    }
+
+    fn detect_anomalies(&self, context: &AgentContext) -> Result<AnomalyResponse> {
+        self.agent.detect_anomalies(context)
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> Result<bool> {
+        self.agent.should_shutdown(context)
+    }
+
+    fn max_pool_utilization_bps(&self, context: &AgentContext) -> Result<u64> {
+        self.agent.max_pool_utilization_bps(context)
+    }
+
+    fn quote_capacity(&self, context: &AgentContext, user_idx: u16, side: TradeSide) -> Result<u128> {
+        self.agent.quote_capacity(context, user_idx, side)
+    }
+
+    fn batch_clearing_price(&self, context: &AgentContext, batch: &BatchSummary) -> Result<u64> {
+        self.agent.batch_clearing_price(context, batch)
+    }
+
+    fn heartbeat(&self, context: &AgentContext) -> Result<()> {
+        self.agent.heartbeat(context)
+    }
+
+    fn agent_capabilities(&self) -> AgentCapabilities {
+        self.agent.agent_capabilities()
+    }
+}
+
+/// Backtest results of one grid point, one report per backtest
+#[derive(Clone, Debug, PartialEq)]
+pub struct CandidateReport {
+    pub point: GridPoint,
+    pub runs: Vec<SimulationReport>,
+
+    /// Every run met the constraints
+    pub feasible: bool,
+}
+
+impl CandidateReport {
+    pub fn mean_lp_pnl(&self) -> f64 {
+        mean(&self.runs.iter().map(|run| run.lp_pnl as f64).collect::<Vec<_>>()).unwrap_or(0.0)
+    }
+
+    pub fn worst_lp_pnl(&self) -> i128 {
+        self.runs.iter().map(|run| run.lp_pnl).min().unwrap_or(0)
+    }
+
+    pub fn liquidations(&self) -> u64 {
+        self.runs.iter().map(|run| run.liquidations).sum()
+    }
+
+    /// Share of orders filled, over all runs
+    pub fn fill_rate(&self) -> f64 {
+        let orders: u64 = self.runs.iter().map(|run| run.orders).sum();
+        let fills: u64 = self.runs.iter().map(|run| run.fills).sum();
+        if orders == 0 {
+            0.0
+        } else {
+            fills as f64 / orders as f64
+        }
+    }
+}
+
+/// Averages over every candidate sharing one swept value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TradeoffPoint {
+    pub value: u64,
+    pub mean_lp_pnl: f64,
+    pub mean_liquidations: f64,
+    pub fill_rate: f64,
+
+    /// Share of those candidates that were feasible
+    pub feasible_share: f64,
+}
+
+/// One curve per swept dimension, in grid order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TradeoffCurves {
+    pub margin: Vec<TradeoffPoint>,
+    pub spread: Vec<TradeoffPoint>,
+    pub funding_cap: Vec<TradeoffPoint>,
+}
+
+impl TradeoffCurves {
+    fn of(grid: &TuningGrid, candidates: &[CandidateReport]) -> Self {
+        let curve = |values: &[u64], key: fn(&GridPoint) -> u64| {
+            values
+                .iter()
+                .map(|&value| {
+                    let share: Vec<&CandidateReport> =
+                        candidates.iter().filter(|c| key(&c.point) == value).collect();
+                    let avg = |f: &dyn Fn(&CandidateReport) -> f64| {
+                        mean(&share.iter().map(|c| f(c)).collect::<Vec<_>>()).unwrap_or(0.0)
+                    };
+                    TradeoffPoint {
+                        value,
+                        mean_lp_pnl: avg(&CandidateReport::mean_lp_pnl),
+                        mean_liquidations: avg(&|c| c.liquidations() as f64 / c.runs.len().max(1) as f64),
+                        fill_rate: avg(&CandidateReport::fill_rate),
+                        feasible_share: avg(&|c| if c.feasible { 1.0 } else { 0.0 }),
+                    }
+                })
+                .collect()
+        };
+        Self {
+            margin: curve(&grid.margin_bps, |p| p.margin_bps),
+            spread: curve(&grid.spread_bps, |p| p.spread_bps),
+            funding_cap: curve(&grid.funding_cap_bps_per_slot, |p| p.funding_cap_bps_per_slot),
+        }
+    }
+}
+
+/// Outcome of `tune`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recommendation {
+    /// Params to publish (None = no feasible point)
+    pub params: Option<MarketParams>,
+
+    /// Grid point behind `params`
+    pub point: Option<GridPoint>,
+
+    /// Every grid point, in `TuningGrid::points` order
+    pub candidates: Vec<CandidateReport>,
+
+    pub curves: TradeoffCurves,
+}
+
+/// Backtest every point of `grid` and recommend one
+///
+/// `factory` builds the fresh, account-less engine each run starts from.
+/// Fails if the grid or a backtest is invalid, or if the engine refuses a
+/// point's params (e.g. a margin under its maintenance margin), rather than
+/// silently backtesting the defaults instead.
+pub fn tune<A, F>(
+    factory: F,
+    agent: &A,
+    backtests: &[Backtest],
+    grid: &TuningGrid,
+    constraints: &TuningConstraints,
+) -> Result<Recommendation>
+where
+    A: OpenClawAgent,
+    F: Fn() -> Box<ClawcolatorEngine>,
+{
+    grid.validate()?;
+    if backtests.is_empty() {
+        return Err(RiskError::Overflow);
+    }
+    let base = factory().dry_run_agent(agent).unwrap_or_default();
+
+    let mut candidates = Vec::new();
+    for point in grid.points() {
+        let candidate = Candidate { agent, point };
+        factory().dry_run_agent(&candidate)?;
+        let runs = backtests
+            .iter()
+            .map(|backtest| backtest.run(&mut factory(), &candidate))
+            .collect::<Result<Vec<_>>>()?;
+        let feasible = runs.iter().all(|run| constraints.admits(run));
+        candidates.push(CandidateReport { point, runs, feasible });
+    }
+
+    let point = candidates
+        .iter()
+        .filter(|c| c.feasible)
+        .map(|c| c.point)
+        .min_by_key(|p| (p.spread_bps, p.margin_bps, p.funding_cap_bps_per_slot));
+    Ok(Recommendation {
+        params: point.map(|p| p.apply(base)),
+        point,
+        curves: TradeoffCurves::of(grid, &candidates),
+        candidates,
+    })
+}
@@ -0,0 +1,64 @@
+//! Virtual AMM fallback: baseline quotes when the agent lacks liquidity
+//!
+//! When the agent rejects a trade with `InsufficientLiquidity`, the engine
+//! prices it on a constant-product curve centred on the oracle: virtual
+//! reserves of `depth` base against `depth * oracle` quote. The agent sets
+//! the depth (0 disables the fallback) within protocol bounds, and a single
+//! fill may take at most `max_fill_bps` of the depth, so the fallback is
+//! always worse-priced than the oracle and cannot drain the LP.
+
+use super::math::{apply_bps, Rounding};
+use crate::{RiskError, Result, MAX_ORACLE_PRICE};
+
+/// Protocol bounds on the agent's vAMM depth
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VammBounds {
+    /// Deepest virtual base reserve the agent may set
+    pub max_depth: u128,
+
+    /// Largest single fill as a share of the depth
+    pub max_fill_bps: u64,
+}
+
+impl Default for VammBounds {
+    fn default() -> Self {
+        Self {
+            max_depth: 100_000_000,
+            max_fill_bps: 1_000, // 10% of the curve per fill
+        }
+    }
+}
+
+impl VammBounds {
+    /// Check an agent-set depth against the bounds
+    pub fn validate_depth(&self, depth: u128) -> Result<()> {
+        if depth > self.max_depth {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+}
+
+/// Average execution price for `size` on a curve of `depth` around
+/// `oracle_price`, rounded against the taker
+///
+/// Returns None when the curve is disabled or `size` exceeds the per-fill cap.
+pub fn vamm_quote(depth: u128, oracle_price: u64, size: i128, max_fill_bps: u64) -> Option<u64> {
+    if depth == 0 || size == 0 || size == i128::MIN {
+        return None;
+    }
+    let dx = size.unsigned_abs();
+    if dx > apply_bps(depth, max_fill_bps, Rounding::Down) {
+        return None;
+    }
+    let numerator = (oracle_price as u128).checked_mul(depth)?;
+    let price = if size > 0 {
+        numerator.div_ceil(depth - dx)
+    } else {
+        numerator / (depth + dx)
+    };
+    if price == 0 || price > MAX_ORACLE_PRICE as u128 {
+        return None;
+    }
+    Some(price as u64)
+}
@@ -0,0 +1,192 @@
+//! Value-at-risk over recent crank prices
+//!
+//! Each advancing crank records the oracle price. Historical simulation
+//! replays the returns between consecutive samples against current
+//! exposure: the LP's net inventory (what the protocol side loses) and
+//! total open interest (how much notional one such move touches). VaR is
+//! the loss at the confidence quantile, ES the mean loss beyond it.
+
+use super::math::{self, apply_bps, Rounding};
+
+/// Price samples retained for the estimator
+pub const PRICE_HISTORY_LEN: usize = 64;
+
+/// Default VaR confidence (95%)
+pub const DEFAULT_VAR_CONFIDENCE_BPS: u64 = 9_500;
+
+/// Oracle price recorded at a crank
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PriceSample {
+    /// Crank slot
+    pub slot: u64,
+
+    /// Oracle price
+    pub price: u64,
+}
+
+/// Ring buffer of crank prices
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceHistory {
+    samples: [PriceSample; PRICE_HISTORY_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl Default for PriceHistory {
+    fn default() -> Self {
+        Self {
+            samples: [PriceSample::default(); PRICE_HISTORY_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+}
+
+impl PriceHistory {
+    /// Append a sample, evicting the oldest when full
+    pub fn push(&mut self, sample: PriceSample) {
+        self.samples[self.head] = sample;
+        self.head = (self.head + 1) % PRICE_HISTORY_LEN;
+        self.len = (self.len + 1).min(PRICE_HISTORY_LEN);
+    }
+
+    /// Number of retained samples
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no price has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Sample `i` in chronological order (0 = oldest retained)
+    pub fn get(&self, i: usize) -> Option<&PriceSample> {
+        if i >= self.len {
+            return None;
+        }
+        let start = (self.head + PRICE_HISTORY_LEN - self.len) % PRICE_HISTORY_LEN;
+        Some(&self.samples[(start + i) % PRICE_HISTORY_LEN])
+    }
+
+    /// Most recent sample
+    pub fn latest(&self) -> Option<&PriceSample> {
+        self.len.checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    /// Samples in chronological order
+    pub fn iter(&self) -> impl Iterator<Item = &PriceSample> + '_ {
+        (0..self.len).filter_map(move |i| self.get(i))
+    }
+
+    /// Time-weighted average price over `since_slot..now_slot`
+    ///
+    /// Each sample holds until the next one, the last until `now_slot`.
+    /// Falls back to the latest sample when the window has no duration;
+    /// None when nothing has been recorded.
+    pub fn twap(&self, since_slot: u64, now_slot: u64) -> Option<u64> {
+        let (mut weighted, mut slots) = (0u128, 0u128);
+        for i in 0..self.len {
+            let sample = self.get(i)?;
+            let next = self.get(i + 1).map_or(now_slot, |s| s.slot);
+            let start = sample.slot.max(since_slot);
+            let end = next.min(now_slot);
+            if end > start {
+                let span = (end - start) as u128;
+                weighted = weighted.saturating_add(sample.price as u128 * span);
+                slots += span;
+            }
+        }
+        match slots {
+            0 => self.latest().map(|s| s.price),
+            _ => Some((weighted / slots) as u64),
+        }
+    }
+
+    /// Returns between consecutive samples (bps), written into `out`;
+    /// returns the number written
+    pub fn returns_bps(&self, out: &mut [i64; PRICE_HISTORY_LEN]) -> usize {
+        let mut n = 0;
+        let mut prev: Option<u64> = None;
+        for sample in self.iter() {
+            if let Some(p) = prev.filter(|p| *p > 0) {
+                let diff = sample.price as i128 - p as i128;
+                out[n] = (diff * 10_000 / p as i128).clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+                n += 1;
+            }
+            prev = Some(sample.price);
+        }
+        n
+    }
+}
+
+/// Common risk measure shared by protocol thresholds and agents
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ValueAtRisk {
+    /// Returns the estimate is based on (0 = no estimate yet)
+    pub samples: u32,
+
+    /// Confidence level (bps)
+    pub confidence_bps: u64,
+
+    /// Absolute price move at the confidence quantile (bps)
+    pub move_var_bps: u64,
+
+    /// Mean absolute move beyond the quantile (bps)
+    pub move_es_bps: u64,
+
+    /// LP inventory loss at the confidence quantile
+    pub lp_var: u128,
+
+    /// Mean LP inventory loss beyond the quantile
+    pub lp_es: u128,
+
+    /// Open-interest notional times the quantile move
+    pub oi_var: u128,
+}
+
+/// Historical-simulation VaR/ES of `lp_position` and `open_interest`
+/// (base units) at `price`
+pub fn estimate(
+    history: &PriceHistory,
+    confidence_bps: u64,
+    price: u64,
+    lp_position: i128,
+    open_interest: u128,
+) -> ValueAtRisk {
+    let mut returns = [0i64; PRICE_HISTORY_LEN];
+    let n = history.returns_bps(&mut returns);
+    if n == 0 {
+        return ValueAtRisk {
+            confidence_bps,
+            ..ValueAtRisk::default()
+        };
+    }
+    // Index of the confidence quantile in ascending order
+    let q = ((n as u128 * confidence_bps as u128).div_ceil(10_000) as usize).clamp(1, n) - 1;
+
+    let mut moves = [0u64; PRICE_HISTORY_LEN];
+    let mut losses = [0i128; PRICE_HISTORY_LEN];
+    let lp_value = lp_position.saturating_mul(price as i128);
+    for i in 0..n {
+        moves[i] = returns[i].unsigned_abs();
+        // LP PnL of the move, negated into a loss
+        losses[i] = -(lp_value.saturating_mul(returns[i] as i128) / 10_000 / 1_000_000);
+    }
+    moves[..n].sort_unstable();
+    losses[..n].sort_unstable();
+
+    let tail = (n - q) as u128;
+    let move_tail: u128 = moves[q..n].iter().map(|m| *m as u128).sum();
+    let loss_tail: i128 = losses[q..n].iter().fold(0i128, |acc, l| acc.saturating_add(*l));
+    let notional = math::notional(open_interest, price, Rounding::Down);
+    ValueAtRisk {
+        samples: n as u32,
+        confidence_bps,
+        move_var_bps: moves[q],
+        move_es_bps: (move_tail / tail) as u64,
+        lp_var: losses[q].max(0) as u128,
+        lp_es: (loss_tail / tail as i128).max(0) as u128,
+        oi_var: apply_bps(notional, moves[q], Rounding::Down),
+    }
+}
@@ -0,0 +1,274 @@
+//! wasm-bindgen bindings for browser agent sandboxes
+//!
+//! Gated behind the `wasm` feature. `WasmEngine` wraps a `ClawcolatorEngine`
+//! with playground risk params; `JsAgent` adapts JS callbacks to
+//! `OpenClawAgent`. Amounts cross the boundary as JS numbers, so the
+//! playground is only exact up to 2^53. Build the module as a cdylib:
+//!
+//! ```text
+//! cargo rustc --release --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+//! wasm-bindgen --target web target/wasm32-unknown-unknown/release/percolator.wasm --out-dir pkg
+//! ```
+//!
+//! ```js
+//! const agent = new JsAgent((ctx, req) => ({ price: ctx.oracle_price, size: req.size }));
+//! const engine = new WasmEngine();
+//! const lp = engine.add_lp(10_000_000);
+//! const user = engine.add_user(1_000_000);
+//! engine.execute_trade(agent, user, 1_000_000, 1_000, 1);
+//! ```
+
+use js_sys::{Function, Object, Reflect};
+use std::{boxed::Box, format, string::String};
+use wasm_bindgen::prelude::*;
+
+use super::{
+    AgentCapabilities, AgentContext, ClawcolatorEngine, MarketParams, OpenClawAgent, OraclePrice,
+    TradeDecision, TradeRejectionReason, TradeRequest,
+};
+use crate::{RiskError, RiskParams, Result, U128};
+
+/// Risk params of the playground engine
+fn playground_params() -> RiskParams {
+    RiskParams {
+        warmup_period_slots: 100,
+        maintenance_margin_bps: 500,
+        initial_margin_bps: 1000,
+        trading_fee_bps: 10,
+        max_accounts: 1000,
+        new_account_fee: U128::new(0),
+        risk_reduction_threshold: U128::new(0),
+        maintenance_fee_per_slot: U128::new(0),
+        max_crank_staleness_slots: u64::MAX,
+        liquidation_fee_bps: 50,
+        liquidation_fee_cap: U128::new(100_000),
+        liquidation_buffer_bps: 100,
+        min_liquidation_abs: U128::new(100_000),
+    }
+}
+
+fn js_error(e: RiskError) -> JsError {
+    JsError::new(&format!("{:?}", e))
+}
+
+fn set(obj: &Object, key: &str, value: JsValue) {
+    // Setting a fresh key on a plain object cannot fail
+    let _ = Reflect::set(obj, &JsValue::from_str(key), &value);
+}
+
+fn get_f64(obj: &JsValue, key: &str) -> Option<f64> {
+    Reflect::get(obj, &JsValue::from_str(key)).ok()?.as_f64()
+}
+
+/// Agent context as a plain JS object
+fn context_object(context: &AgentContext) -> Object {
+    let obj = Object::new();
+    set(&obj, "current_slot", (context.current_slot as f64).into());
+    set(&obj, "oracle_price", (context.oracle_price as f64).into());
+    set(&obj, "oracle_stale", context.oracle_stale.into());
+    set(&obj, "vault", (context.vault as f64).into());
+    set(&obj, "insurance_balance", (context.insurance_balance as f64).into());
+    set(&obj, "total_capital", (context.total_capital as f64).into());
+    set(&obj, "total_open_interest", (context.total_open_interest as f64).into());
+    set(&obj, "risk_reduction_mode", context.risk_reduction_mode.into());
+    set(&obj, "lp_liquidity", (context.lp_liquidity as f64).into());
+    set(&obj, "oracle_divergence_bps", (context.oracle_divergence_bps as f64).into());
+    set(&obj, "move_var_bps", (context.value_at_risk.move_var_bps as f64).into());
+    obj
+}
+
+/// `OpenClawAgent` backed by JS callbacks
+///
+/// `decide_trade(ctx, req)` returns `{ price, size }` to accept or
+/// null/undefined to reject. Optional callbacks: `market_params(ctx)`
+/// returning `{ spread_bps, max_leverage_bps }` overrides, and
+/// `should_shutdown(ctx)` returning a boolean. Other decisions use
+/// passive defaults. A throwing callback counts as an agent error.
+#[wasm_bindgen]
+pub struct JsAgent {
+    decide_trade: Function,
+    market_params: Option<Function>,
+    should_shutdown: Option<Function>,
+}
+
+#[wasm_bindgen]
+impl JsAgent {
+    #[wasm_bindgen(constructor)]
+    pub fn new(decide_trade: Function) -> JsAgent {
+        JsAgent {
+            decide_trade,
+            market_params: None,
+            should_shutdown: None,
+        }
+    }
+
+    /// Install the market params callback
+    pub fn set_market_params(&mut self, callback: Function) {
+        self.market_params = Some(callback);
+    }
+
+    /// Install the shutdown callback
+    pub fn set_should_shutdown(&mut self, callback: Function) {
+        self.should_shutdown = Some(callback);
+    }
+}
+
+impl OpenClawAgent for JsAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        let req = Object::new();
+        set(&req, "user_idx", request.user_idx.into());
+        set(&req, "size", (request.size as f64).into());
+        let decision = self
+            .decide_trade
+            .call2(&JsValue::NULL, &context_object(context), &req)
+            .map_err(|_| RiskError::Unauthorized)?;
+        if decision.is_null() || decision.is_undefined() {
+            return Ok(TradeDecision::Reject {
+                reason: TradeRejectionReason::Other,
+            });
+        }
+        match (get_f64(&decision, "price"), get_f64(&decision, "size")) {
+            (Some(price), Some(size)) if price >= 0.0 => Ok(TradeDecision::Accept {
+                price: price as u64,
+                size: size as i128,
+            }),
+            _ => Err(RiskError::InvalidMatchingEngine),
+        }
+    }
+
+    fn get_market_params(&self, context: &AgentContext) -> Result<MarketParams> {
+        let mut params = MarketParams::default();
+        let Some(callback) = &self.market_params else {
+            return Ok(params);
+        };
+        let overrides = callback
+            .call1(&JsValue::NULL, &context_object(context))
+            .map_err(|_| RiskError::Unauthorized)?;
+        if let Some(spread) = get_f64(&overrides, "spread_bps") {
+            params.spread_bps = spread as u64;
+        }
+        if let Some(leverage) = get_f64(&overrides, "max_leverage_bps") {
+            params.max_leverage_bps = leverage as u64;
+        }
+        Ok(params)
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> Result<bool> {
+        let Some(callback) = &self.should_shutdown else {
+            return Ok(false);
+        };
+        let answer = callback
+            .call1(&JsValue::NULL, &context_object(context))
+            .map_err(|_| RiskError::Unauthorized)?;
+        Ok(answer.is_truthy())
+    }
+
+    fn agent_capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            shutdown: self.should_shutdown.is_some(),
+            ..AgentCapabilities::CORE
+        }
+    }
+}
+
+/// Clawcolator engine for the browser playground
+#[wasm_bindgen]
+pub struct WasmEngine {
+    inner: Box<ClawcolatorEngine>,
+}
+
+#[wasm_bindgen]
+impl WasmEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmEngine {
+        WasmEngine {
+            inner: Box::new(ClawcolatorEngine::new(playground_params())),
+        }
+    }
+
+    /// Open an LP account funded with `deposit`; returns its index
+    pub fn add_lp(&mut self, deposit: f64) -> core::result::Result<u16, JsError> {
+        let risk = self.inner.risk_engine_mut();
+        let idx = risk.add_lp([0; 32], [0; 32], 0).map_err(js_error)?;
+        risk.deposit(idx, deposit as u128, 0).map_err(js_error)?;
+        Ok(idx)
+    }
+
+    /// Open a user account funded with `deposit`; returns its index
+    pub fn add_user(&mut self, deposit: f64) -> core::result::Result<u16, JsError> {
+        let risk = self.inner.risk_engine_mut();
+        let idx = risk.add_user(0).map_err(js_error)?;
+        risk.deposit(idx, deposit as u128, 0).map_err(js_error)?;
+        Ok(idx)
+    }
+
+    /// Run a trade through `agent` at a fresh oracle price
+    pub fn execute_trade(
+        &mut self,
+        agent: &JsAgent,
+        user_idx: u16,
+        oracle_price: f64,
+        size: f64,
+        now_slot: f64,
+    ) -> core::result::Result<(), JsError> {
+        let slot = now_slot as u64;
+        let oracle = OraclePrice::new(oracle_price as u64, 0, slot);
+        self.inner
+            .execute_trade(agent, user_idx, oracle, size as i128, slot)
+            .map_err(js_error)
+    }
+
+    /// Publish the agent's market params
+    pub fn update_market_params(&mut self, agent: &JsAgent) -> core::result::Result<(), JsError> {
+        self.inner.update_market_params(agent).map_err(js_error)
+    }
+
+    /// Crank the engine as `caller_idx`
+    pub fn crank(
+        &mut self,
+        caller_idx: u16,
+        now_slot: f64,
+        oracle_price: f64,
+    ) -> core::result::Result<(), JsError> {
+        self.inner
+            .crank(caller_idx, now_slot as u64, oracle_price as u64, None)
+            .map(|_| ())
+            .map_err(js_error)
+    }
+
+    /// Account state as JSON (None if the slot is free)
+    pub fn account_json(&self, idx: u16, oracle_price: f64) -> Option<String> {
+        let v = self.inner.account_view(idx, oracle_price as u64)?;
+        Some(format!(
+            r#"{{"idx": {}, "kind": "{:?}", "capital": {}, "pnl": {}, "unrealized_pnl": {}, "position_size": {}, "equity": {}, "margin_ratio_bps": {}, "undercollateralized": {}}}"#,
+            v.idx,
+            v.kind,
+            v.capital,
+            v.pnl,
+            v.unrealized_pnl,
+            v.position_size,
+            v.equity,
+            v.margin_ratio_bps,
+            v.undercollateralized
+        ))
+    }
+
+    /// Engine totals as JSON
+    pub fn status_json(&self) -> String {
+        let risk = self.inner.risk_engine();
+        format!(
+            r#"{{"vault": {}, "insurance": {}, "total_capital": {}, "total_open_interest": {}, "current_slot": {}}}"#,
+            risk.vault.get(),
+            risk.insurance_fund.balance.get(),
+            risk.c_tot.get(),
+            risk.total_open_interest.get(),
+            risk.current_slot
+        )
+    }
+}
+
+impl Default for WasmEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
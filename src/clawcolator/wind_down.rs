@@ -0,0 +1,158 @@
+//! Market delisting and graceful wind-down
+//!
+//! A delisted market does not close at once. From the start of the
+//! wind-down only position-reducing trades execute, and each crank raises
+//! margin requirements linearly towards their targets over `ramp_slots`,
+//! so holders are pushed to close out (or are liquidated) while the market
+//! still trades. Once `settle_after_slots` have passed, the crank settles
+//! every residual position at the oracle TWAP over the last `twap_slots`
+//! and the market stays flat; capital is withdrawable as usual.
+
+use crate::{RiskError, Result};
+
+/// Wind-down schedule
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindDownParams {
+    /// Slots over which margins ramp to their targets
+    pub ramp_slots: u64,
+
+    /// Maintenance margin at the end of the ramp (bps)
+    pub target_maintenance_margin_bps: u64,
+
+    /// Initial margin at the end of the ramp (bps)
+    pub target_initial_margin_bps: u64,
+
+    /// Slots after the start at which residual positions are settled
+    pub settle_after_slots: u64,
+
+    /// Window of the settlement TWAP (slots)
+    pub twap_slots: u64,
+}
+
+impl Default for WindDownParams {
+    fn default() -> Self {
+        Self {
+            ramp_slots: 216_000, // ~1 day at 400ms slots
+            target_maintenance_margin_bps: 5_000,
+            target_initial_margin_bps: 10_000,
+            settle_after_slots: 432_000,
+            twap_slots: 9_000, // ~1 hour
+        }
+    }
+}
+
+impl WindDownParams {
+    /// Targets within 100%, initial above maintenance, settlement after the ramp
+    pub fn validate(&self, maintenance_margin_bps: u64, initial_margin_bps: u64) -> Result<()> {
+        if self.target_initial_margin_bps > 10_000
+            || self.target_maintenance_margin_bps > self.target_initial_margin_bps
+            || self.target_maintenance_margin_bps < maintenance_margin_bps
+            || self.target_initial_margin_bps < initial_margin_bps
+            || self.settle_after_slots < self.ramp_slots
+            || self.twap_slots == 0
+        {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+}
+
+/// Stage of a wind-down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindDownPhase {
+    /// Reduce-only, margins ramping
+    Ramping,
+    /// Reduce-only at target margins, awaiting settlement
+    AwaitingSettlement,
+    /// Residual positions settled
+    Settled,
+}
+
+/// Wind-down in progress
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindDown {
+    /// Schedule
+    pub params: WindDownParams,
+
+    /// Slot the wind-down started
+    pub started_slot: u64,
+
+    /// Maintenance margin when the wind-down started (bps)
+    pub start_maintenance_margin_bps: u64,
+
+    /// Initial margin when the wind-down started (bps)
+    pub start_initial_margin_bps: u64,
+
+    /// TWAP residual positions were settled at
+    pub settlement_price: Option<u64>,
+
+    /// Positions closed by the settlement
+    pub settled_positions: u32,
+}
+
+impl WindDown {
+    /// Slot from which residual positions are settled
+    pub fn settle_slot(&self) -> u64 {
+        self.started_slot.saturating_add(self.params.settle_after_slots)
+    }
+
+    /// Share of the ramp elapsed at `now_slot` (bps)
+    pub fn ramp_bps(&self, now_slot: u64) -> u64 {
+        let elapsed = now_slot.saturating_sub(self.started_slot);
+        if elapsed >= self.params.ramp_slots {
+            return 10_000;
+        }
+        (elapsed as u128 * 10_000 / self.params.ramp_slots as u128) as u64
+    }
+
+    /// Maintenance and initial margin in effect at `now_slot` (bps)
+    pub fn margins_at(&self, now_slot: u64) -> (u64, u64) {
+        let ramp = self.ramp_bps(now_slot) as u128;
+        let lerp = |from: u64, to: u64| {
+            from + ((to.saturating_sub(from)) as u128 * ramp / 10_000) as u64
+        };
+        (
+            lerp(self.start_maintenance_margin_bps, self.params.target_maintenance_margin_bps),
+            lerp(self.start_initial_margin_bps, self.params.target_initial_margin_bps),
+        )
+    }
+
+    /// Stage at `now_slot`
+    pub fn phase(&self, now_slot: u64) -> WindDownPhase {
+        if self.settlement_price.is_some() {
+            WindDownPhase::Settled
+        } else if self.ramp_bps(now_slot) < 10_000 {
+            WindDownPhase::Ramping
+        } else {
+            WindDownPhase::AwaitingSettlement
+        }
+    }
+}
+
+/// Wind-down progress (see `ClawcolatorEngine::wind_down_progress`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindDownProgress {
+    /// Stage
+    pub phase: WindDownPhase,
+
+    /// Share of the margin ramp elapsed (bps)
+    pub ramp_bps: u64,
+
+    /// Maintenance margin in effect (bps)
+    pub maintenance_margin_bps: u64,
+
+    /// Initial margin in effect (bps)
+    pub initial_margin_bps: u64,
+
+    /// Slot from which residual positions are settled
+    pub settle_slot: u64,
+
+    /// Accounts still holding a position
+    pub open_positions: u32,
+
+    /// Open interest still outstanding
+    pub open_interest: u128,
+
+    /// TWAP residual positions were settled at
+    pub settlement_price: Option<u64>,
+}
@@ -0,0 +1,218 @@
+//! Withdrawal queue: delayed release of large withdrawals
+//!
+//! Withdrawals above the agent's `queue_threshold` wait `delay_slots`
+//! before the crank releases them. Entries are released early while pool
+//! utilization is below `expedite_utilization_bps`, held while the market
+//! is frozen or settling, and paid pro-rata when the vault no longer covers
+//! capital plus insurance (the unpaid remainder stays queued).
+
+use crate::{RiskError, Result};
+
+/// Maximum queued withdrawals
+pub const MAX_QUEUED_WITHDRAWALS: usize =
+    crate::layout::capacity(option_env!("PERCOLATOR_MAX_QUEUED_WITHDRAWALS"), 32);
+
+/// Agent-tunable withdrawal queue policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WithdrawalPolicy {
+    /// Withdrawals above this amount are queued
+    pub queue_threshold: u128,
+
+    /// Slots a queued withdrawal waits before release
+    pub delay_slots: u64,
+
+    /// Release early while utilization (OI notional / capital) is at or below
+    /// this (0 = never expedite)
+    pub expedite_utilization_bps: u64,
+}
+
+/// Default policy: nothing is queued
+pub const DEFAULT_WITHDRAWAL_POLICY: WithdrawalPolicy = WithdrawalPolicy {
+    queue_threshold: u128::MAX,
+    delay_slots: 0,
+    expedite_utilization_bps: 0,
+};
+
+/// Protocol bounds on the agent's withdrawal policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WithdrawalBounds {
+    /// Smallest amount the agent may force into the queue
+    pub min_queue_threshold: u128,
+
+    /// Longest delay the agent may impose (slots)
+    pub max_delay_slots: u64,
+}
+
+impl Default for WithdrawalBounds {
+    fn default() -> Self {
+        Self {
+            min_queue_threshold: 100_000,
+            max_delay_slots: 216_000, // ~1 day at 400ms slots
+        }
+    }
+}
+
+impl WithdrawalPolicy {
+    /// Check the policy against protocol bounds
+    pub fn validate(&self, bounds: &WithdrawalBounds) -> Result<()> {
+        if self.queue_threshold < bounds.min_queue_threshold
+            || self.delay_slots > bounds.max_delay_slots
+            || self.expedite_utilization_bps > 10_000
+        {
+            return Err(RiskError::Overflow);
+        }
+        Ok(())
+    }
+}
+
+/// Withdrawal waiting in the queue
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueuedWithdrawal {
+    /// Account slot
+    pub account_idx: u16,
+
+    /// Id of the requesting account (guards against slot reuse)
+    pub account_id: u64,
+
+    /// Amount still to be paid
+    pub amount: u128,
+
+    /// Slot of the request
+    pub requested_slot: u64,
+
+    /// Slot from which the withdrawal may be released
+    pub release_slot: u64,
+}
+
+/// Outcome of a withdrawal request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WithdrawalStatus {
+    /// Paid immediately
+    Executed,
+    /// Queued until `release_slot`
+    Queued { release_slot: u64 },
+}
+
+/// Queue summary exposed to the agent
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WithdrawalQueueStatus {
+    /// Number of queued withdrawals
+    pub pending_count: u32,
+
+    /// Total amount queued
+    pub pending_amount: u128,
+
+    /// Earliest release slot (None = queue empty)
+    pub next_release_slot: Option<u64>,
+
+    /// Whether releases are paused (frozen market or settlement)
+    pub paused: bool,
+}
+
+/// FIFO of queued withdrawals
+#[derive(Clone, Copy, Debug)]
+pub struct WithdrawalQueue {
+    entries: [QueuedWithdrawal; MAX_QUEUED_WITHDRAWALS],
+    len: usize,
+}
+
+impl WithdrawalQueue {
+    pub const fn new() -> Self {
+        Self {
+            entries: [QueuedWithdrawal {
+                account_idx: 0,
+                account_id: 0,
+                amount: 0,
+                requested_slot: 0,
+                release_slot: 0,
+            }; MAX_QUEUED_WITHDRAWALS],
+            len: 0,
+        }
+    }
+
+    /// Queued withdrawals, oldest first
+    pub fn entries(&self) -> &[QueuedWithdrawal] {
+        &self.entries[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Total queued for `account_id`
+    pub fn pending_for(&self, account_id: u64) -> u128 {
+        self.entries()
+            .iter()
+            .filter(|e| e.account_id == account_id)
+            .fold(0u128, |acc, e| acc.saturating_add(e.amount))
+    }
+
+    /// Summary for the agent
+    pub fn status(&self, paused: bool) -> WithdrawalQueueStatus {
+        WithdrawalQueueStatus {
+            pending_count: self.len as u32,
+            pending_amount: self
+                .entries()
+                .iter()
+                .fold(0u128, |acc, e| acc.saturating_add(e.amount)),
+            next_release_slot: self.entries().iter().map(|e| e.release_slot).min(),
+            paused,
+        }
+    }
+
+    pub(crate) fn push(&mut self, entry: QueuedWithdrawal) -> Result<()> {
+        if self.len >= MAX_QUEUED_WITHDRAWALS {
+            return Err(RiskError::Overflow);
+        }
+        self.entries[self.len] = entry;
+        self.len += 1;
+        Ok(())
+    }
+
+    pub(crate) fn get_mut(&mut self, pos: usize) -> Option<&mut QueuedWithdrawal> {
+        self.entries[..self.len].get_mut(pos)
+    }
+
+    /// Remove the entry at `pos`, preserving order
+    pub(crate) fn remove(&mut self, pos: usize) {
+        if pos >= self.len {
+            return;
+        }
+        self.entries.copy_within(pos + 1..self.len, pos);
+        self.len -= 1;
+    }
+
+    /// Follow account `account_id` moved by compaction from `from` to `to`
+    pub(crate) fn move_account(&mut self, from: u16, to: u16, account_id: u64) {
+        for entry in self.entries[..self.len].iter_mut() {
+            if entry.account_idx == from && entry.account_id == account_id {
+                entry.account_idx = to;
+            }
+        }
+    }
+
+    /// Remove all entries of `account_id`; returns the amount cancelled
+    pub(crate) fn cancel(&mut self, account_id: u64) -> u128 {
+        let mut cancelled = 0u128;
+        let mut pos = 0;
+        while pos < self.len {
+            if self.entries[pos].account_id == account_id {
+                cancelled = cancelled.saturating_add(self.entries[pos].amount);
+                self.remove(pos);
+            } else {
+                pos += 1;
+            }
+        }
+        cancelled
+    }
+}
+
+impl Default for WithdrawalQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,117 @@
+//! Capacity layout
+//!
+//! Every fixed-size table in the crate takes its length from a constant
+//! declared next to it, and each of those constants can be overridden at
+//! build time through an environment variable read by `capacity`:
+//!
+//! | Variable                            | Constant                 | Default |
+//! |-------------------------------------|--------------------------|---------|
+//! | `PERCOLATOR_MAX_ACCOUNTS`           | `MAX_ACCOUNTS`           | 4096    |
+//! | `PERCOLATOR_BATCH_BOOK_LEN`         | `BATCH_BOOK_LEN`         | 64      |
+//! | `PERCOLATOR_COMMIT_BOOK_LEN`        | `COMMIT_BOOK_LEN`        | 64      |
+//! | `PERCOLATOR_CLIENT_ORDER_LOG_LEN`   | `CLIENT_ORDER_LOG_LEN`   | 128     |
+//! | `PERCOLATOR_MAX_QUEUED_WITHDRAWALS` | `MAX_QUEUED_WITHDRAWALS` | 32      |
+//! | `PERCOLATOR_ANOMALY_HISTORY_LEN`    | `ANOMALY_HISTORY_LEN`    | 16      |
+//!
+//! An embedded or BPF deployment trades memory for capacity with e.g.
+//! `PERCOLATOR_MAX_ACCOUNTS=1024 cargo build-sbf`, without forking the
+//! crate. Values are checked at compile time (the account slab must be a
+//! power of two no larger than 32768, every table non-empty). The `test`
+//! feature and Kani builds keep their small fixed slab. `LAYOUT` reports
+//! what a build was compiled with.
+
+use crate::{RiskEngine, MAX_ACCOUNTS};
+
+/// Capacities a build was compiled with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    /// Account slab size
+    pub max_accounts: usize,
+
+    /// Size of the risk engine state (bytes)
+    pub risk_engine_bytes: usize,
+
+    /// Orders one batch auction holds
+    #[cfg(feature = "clawcolator")]
+    pub batch_book_len: usize,
+
+    /// Pending trade commitments
+    #[cfg(feature = "clawcolator")]
+    pub commit_book_len: usize,
+
+    /// Client order ids remembered for deduplication
+    #[cfg(feature = "clawcolator")]
+    pub client_order_log_len: usize,
+
+    /// Queued withdrawals
+    #[cfg(feature = "clawcolator")]
+    pub max_queued_withdrawals: usize,
+
+    /// Anomaly checks retained
+    #[cfg(feature = "clawcolator")]
+    pub anomaly_history_len: usize,
+
+    /// Size of the Clawcolator engine state, risk engine included (bytes)
+    #[cfg(feature = "clawcolator")]
+    pub engine_bytes: usize,
+}
+
+/// Layout of this build
+pub const LAYOUT: Layout = Layout {
+    max_accounts: MAX_ACCOUNTS,
+    risk_engine_bytes: core::mem::size_of::<RiskEngine>(),
+    #[cfg(feature = "clawcolator")]
+    batch_book_len: crate::clawcolator::BATCH_BOOK_LEN,
+    #[cfg(feature = "clawcolator")]
+    commit_book_len: crate::clawcolator::COMMIT_BOOK_LEN,
+    #[cfg(feature = "clawcolator")]
+    client_order_log_len: crate::clawcolator::CLIENT_ORDER_LOG_LEN,
+    #[cfg(feature = "clawcolator")]
+    max_queued_withdrawals: crate::clawcolator::withdrawals::MAX_QUEUED_WITHDRAWALS,
+    #[cfg(feature = "clawcolator")]
+    anomaly_history_len: crate::clawcolator::ANOMALY_HISTORY_LEN,
+    #[cfg(feature = "clawcolator")]
+    engine_bytes: core::mem::size_of::<crate::clawcolator::ClawcolatorEngine>(),
+};
+
+/// Capacity from a build-time override (decimal, `_` separators allowed),
+/// or `default` when unset. Malformed values fail the build.
+pub const fn capacity(value: Option<&str>, default: usize) -> usize {
+    let bytes = match value {
+        Some(value) => value.as_bytes(),
+        None => return default,
+    };
+    let mut n: usize = 0;
+    let mut digits = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        i += 1;
+        if b == b'_' {
+            continue;
+        }
+        assert!(b.is_ascii_digit(), "capacity override must be a decimal integer");
+        n = match n.checked_mul(10) {
+            Some(n) => n + (b - b'0') as usize,
+            None => panic!("capacity override too large"),
+        };
+        digits += 1;
+    }
+    assert!(digits > 0, "capacity override is empty");
+    n
+}
+
+const _: () = assert!(
+    MAX_ACCOUNTS.is_power_of_two() && MAX_ACCOUNTS <= 1 << 15,
+    "PERCOLATOR_MAX_ACCOUNTS must be a power of two no larger than 32768"
+);
+
+#[cfg(feature = "clawcolator")]
+const _: () = assert!(
+    LAYOUT.batch_book_len > 0
+        && LAYOUT.commit_book_len > 0
+        && LAYOUT.client_order_log_len > 0
+        && LAYOUT.max_queued_withdrawals > 0
+        && LAYOUT.anomaly_history_len > 0,
+    "capacity overrides must be non-zero"
+);
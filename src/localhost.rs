@@ -1,20 +1,735 @@
 //! Localhost HTTP Server for Clawcolator
 //!
 //! Provides REST API for interacting with Clawcolator engine
+//!
+//! `serve` runs a fixed pool of worker threads over one shared
+//! `ServerState` behind an `RwLock`. Routes are registered on a `Router` as
+//! either read handlers, which run concurrently under the read lock, or
+//! write handlers, which take the write lock so trades and cranks are
+//! applied one at a time in arrival order.
+//!
+//! State survives restarts through a `SnapshotStore`. A snapshot is the
+//! journal of every state-changing command the server has applied; on boot
+//! the journal is replayed against a fresh engine built from the same
+//! config, which reproduces the state exactly as long as the agent is
+//! deterministic. The engine itself is never serialised, so snapshots stay
+//! small and readable and survive engine layout changes.
+//!
+//! Access is open by default, which is fine on loopback. Once the server is
+//! reachable from elsewhere, give the `Router` a set of `ApiKeys`: read-only
+//! keys see status and metrics, trade keys may trade for their own
+//! `user_idx`, and admin keys can do everything, including freezing the
+//! market and updating params.
+//!
+//! `Command::Inject` simulates oracle jumps and outages, agent outages and
+//! mass liquidations. Injections are journaled like any other command, so a
+//! chaos run replays exactly.
+//!
+//! Commands built from requests are stamped with the `SlotClock`'s slot,
+//! which can stay manual or run at wall-clock or accelerated speed.
+//!
+//! `serve_instances` hosts several independent markets at once: the
+//! default one on plain paths and named ones, created with `POST
+//! /instances`, under `/i/{name}/...`.
 
 #![cfg(all(feature = "localhost", feature = "clawcolator"))]
 
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::time::Duration;
+use std::{boxed::Box, format, io, string::String, string::ToString, thread, vec::Vec};
+
+use crate::clawcolator::stress::shocked_price;
 use crate::clawcolator::*;
-use crate::{RiskParams, U128};
+use crate::{RiskError, RiskParams, U128};
+
+mod clock;
+mod instances;
+mod leaderboard;
+mod migration;
+pub use clock::{ClockMode, SlotClock, MAX_SLOTS_PER_SEC, SLOT_MS};
+pub use instances::{
+    InstanceError, InstanceSpec, Instances, SharedState, INSTANCES_PATH, MAX_INSTANCES, MAX_INSTANCE_NAME,
+};
+pub use leaderboard::{Leaderboard, Standing};
+pub use migration::{MigrationStep, Migrations, JOURNAL_VERSION};
+
+/// Largest request (headers and body) the server reads
+const MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// How long a worker waits on a slow client
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Trades and events kept for the history endpoints; older ones are dropped
+pub const MAX_HISTORY: usize = 100_000;
+
+/// A state-changing request, journaled so it can be replayed on boot
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Open a user account funded with `deposit`
+    AddUser { deposit: u128 },
+
+    /// Open an LP account funded with `deposit`
+    AddLp { deposit: u128 },
+
+    /// Deposit into an existing account
+    Deposit { idx: u16, amount: u128 },
+
+    /// Run a trade through the agent at `slot`, at most once per
+    /// `client_order_id`, only above the account's last `sequence` and not
+    /// after `valid_until_slot` when those are given
+    Trade {
+        user_idx: u16,
+        oracle_price: u64,
+        size: i128,
+        slot: u64,
+        client_order_id: Option<u64>,
+        sequence: Option<u64>,
+        valid_until_slot: Option<u64>,
+    },
+
+    /// Commit to a trade (`TradeReveal::commitment`) at `slot`
+    CommitTrade {
+        user_idx: u16,
+        commitment: [u8; 32],
+        slot: u64,
+    },
+
+    /// Reveal a committed trade and run it through the agent at `slot`
+    RevealTrade {
+        reveal: TradeReveal,
+        oracle_price: u64,
+        slot: u64,
+    },
+
+    /// Add a trade to the current batch auction at `slot`
+    SubmitBatchOrder { user_idx: u16, size: i128, slot: u64 },
+
+    /// Clear the current batch auction at `slot`
+    ClearBatch { oracle_price: u64, slot: u64 },
+
+    /// Agent heartbeat at `slot`
+    Heartbeat { slot: u64 },
+
+    /// Crank at `slot`
+    Crank {
+        caller_idx: u16,
+        oracle_price: u64,
+        slot: u64,
+    },
+
+    /// Publish the agent's market params
+    UpdateMarketParams,
+
+    /// Administrative freeze (`true`) or resume (`false`)
+    SetFrozen { frozen: bool },
+
+    /// Inject a fault (chaos testing)
+    Inject(Fault),
+
+    /// Swap in the agent registered under `name`
+    SwapAgent { name: String },
+}
+
+/// Fault injected into the sandbox to exercise the engine's defensive paths
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Move every oracle price by `bps` until cleared
+    OracleJump { bps: i64 },
+
+    /// Stop oracle publishing for `slots` slots, so trades see a stale price
+    OracleOutage { slots: u64 },
+
+    /// Make every agent call fail for `slots` slots
+    AgentOutage { slots: u64 },
+
+    /// Crank once at the last oracle price moved by `shock_bps`
+    MassLiquidation { shock_bps: i64 },
+
+    /// Lift every active fault
+    Clear,
+}
+
+impl Fault {
+    fn encode(&self) -> String {
+        match *self {
+            Fault::OracleJump { bps } => format!("oracle_jump {}", bps),
+            Fault::OracleOutage { slots } => format!("oracle_outage {}", slots),
+            Fault::AgentOutage { slots } => format!("agent_outage {}", slots),
+            Fault::MassLiquidation { shock_bps } => format!("mass_liquidation {}", shock_bps),
+            Fault::Clear => "clear".into(),
+        }
+    }
+
+    fn decode(name: &str, arg: Option<&str>) -> Option<Self> {
+        let fault = match (name, arg) {
+            ("oracle_jump", Some(bps)) => Fault::OracleJump { bps: bps.parse().ok()? },
+            ("oracle_outage", Some(slots)) => Fault::OracleOutage { slots: slots.parse().ok()? },
+            ("agent_outage", Some(slots)) => Fault::AgentOutage { slots: slots.parse().ok()? },
+            ("mass_liquidation", Some(bps)) => Fault::MassLiquidation { shock_bps: bps.parse().ok()? },
+            ("clear", None) => Fault::Clear,
+            _ => return None,
+        };
+        Some(fault)
+    }
+}
+
+/// Faults in effect
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Faults {
+    /// Offset applied to every oracle price (bps)
+    pub oracle_offset_bps: i64,
+
+    /// Oracle stuck at publish slot `.0` until slot `.1`
+    pub oracle_outage: Option<(u64, u64)>,
+
+    /// Agent unavailable until this slot
+    pub agent_outage_until: Option<u64>,
+
+    /// Last oracle price a trade or crank was sent with (before any offset)
+    pub last_oracle_price: u64,
+}
+
+impl Faults {
+    /// Oracle as reported at `slot` for a submitted `price`
+    pub fn oracle(&self, price: u64, slot: u64) -> OraclePrice {
+        let publish_slot = match self.oracle_outage {
+            Some((since, until)) if slot < until => since.min(slot),
+            _ => slot,
+        };
+        OraclePrice::new(self.price(price), 0, publish_slot)
+    }
+
+    /// Whether the agent is unavailable at `slot`
+    pub fn agent_down(&self, slot: u64) -> bool {
+        self.agent_outage_until.is_some_and(|until| slot < until)
+    }
+
+    fn price(&self, price: u64) -> u64 {
+        match self.oracle_offset_bps {
+            0 => price,
+            bps => shocked_price(price, bps),
+        }
+    }
+}
+
+type AgentFactory<A> = Box<dyn Fn() -> A + Send + Sync>;
+
+/// Named agent constructors the server can swap between at runtime
+pub struct AgentRegistry<A> {
+    factories: Vec<(String, AgentFactory<A>)>,
+}
+
+impl<A> Default for AgentRegistry<A> {
+    fn default() -> Self {
+        Self { factories: Vec::new() }
+    }
+}
+
+impl<A> AgentRegistry<A> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `factory` under `name` (a single word, as it is journaled)
+    pub fn with<F>(mut self, name: &str, factory: F) -> Self
+    where
+        F: Fn() -> A + Send + Sync + 'static,
+    {
+        self.factories.push((name.to_string(), Box::new(factory)));
+        self
+    }
+
+    /// Registered names in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// A new agent from the factory registered under `name`
+    pub fn build(&self, name: &str) -> Option<A> {
+        self.factories
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, factory)| factory())
+    }
+}
+
+/// Type-erased agent, for registries of agents of different types
+pub type BoxedAgent = Box<dyn OpenClawAgent + Send + Sync>;
+
+impl OpenClawAgent for BoxedAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> crate::Result<TradeDecision> {
+        (**self).decide_trade(context, request)
+    }
+
+    fn decide_trade_with_meta(
+        &self,
+        context: &AgentContext,
+        request: &TradeRequest,
+    ) -> crate::Result<(TradeDecision, Option<DecisionMeta>)> {
+        (**self).decide_trade_with_meta(context, request)
+    }
+
+    fn get_market_params(&self, context: &AgentContext) -> crate::Result<MarketParams> {
+        (**self).get_market_params(context)
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> crate::Result<LiquidityAllocation> {
+        (**self).decide_liquidity_allocation(context)
+    }
+
+    fn assess_risk(&self, context: &AgentContext) -> crate::Result<RiskAssessment> {
+        (**self).assess_risk(context)
+    }
+
+    fn detect_anomalies(&self, context: &AgentContext) -> crate::Result<AnomalyResponse> {
+        (**self).detect_anomalies(context)
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> crate::Result<bool> {
+        (**self).should_shutdown(context)
+    }
+
+    fn max_pool_utilization_bps(&self, context: &AgentContext) -> crate::Result<u64> {
+        (**self).max_pool_utilization_bps(context)
+    }
+
+    fn quote_capacity(&self, context: &AgentContext, user_idx: u16, side: TradeSide) -> crate::Result<u128> {
+        (**self).quote_capacity(context, user_idx, side)
+    }
+
+    fn batch_clearing_price(&self, context: &AgentContext, batch: &BatchSummary) -> crate::Result<u64> {
+        (**self).batch_clearing_price(context, batch)
+    }
+
+    fn heartbeat(&self, context: &AgentContext) -> crate::Result<()> {
+        (**self).heartbeat(context)
+    }
+
+    fn agent_capabilities(&self) -> AgentCapabilities {
+        (**self).agent_capabilities()
+    }
+}
+
+/// Stands in for the agent during an agent outage
+struct UnavailableAgent;
+
+impl OpenClawAgent for UnavailableAgent {
+    fn decide_trade(&self, _context: &AgentContext, _request: &TradeRequest) -> crate::Result<TradeDecision> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> crate::Result<MarketParams> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn decide_liquidity_allocation(&self, _context: &AgentContext) -> crate::Result<LiquidityAllocation> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn assess_risk(&self, _context: &AgentContext) -> crate::Result<RiskAssessment> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn detect_anomalies(&self, _context: &AgentContext) -> crate::Result<AnomalyResponse> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn should_shutdown(&self, _context: &AgentContext) -> crate::Result<bool> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn batch_clearing_price(&self, _context: &AgentContext, _batch: &BatchSummary) -> crate::Result<u64> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn heartbeat(&self, _context: &AgentContext) -> crate::Result<()> {
+        Err(RiskError::Unauthorized)
+    }
+}
+
+impl Command {
+    /// One journal line
+    pub fn encode(&self) -> String {
+        match *self {
+            Command::AddUser { deposit } => format!("add_user {}", deposit),
+            Command::AddLp { deposit } => format!("add_lp {}", deposit),
+            Command::Deposit { idx, amount } => format!("deposit {} {}", idx, amount),
+            Command::Trade {
+                user_idx,
+                oracle_price,
+                size,
+                slot,
+                client_order_id,
+                sequence,
+                valid_until_slot,
+            } => {
+                let mut line = format!("trade {} {} {} {}", user_idx, oracle_price, size, slot);
+                if let Some(id) = client_order_id {
+                    line.push_str(&format!(" id={}", id));
+                }
+                if let Some(sequence) = sequence {
+                    line.push_str(&format!(" seq={}", sequence));
+                }
+                if let Some(until) = valid_until_slot {
+                    line.push_str(&format!(" until={}", until));
+                }
+                line
+            }
+            Command::CommitTrade { user_idx, ref commitment, slot } => {
+                format!("commit_trade {} {} {}", user_idx, encode_hex32(commitment), slot)
+            }
+            Command::RevealTrade { ref reveal, oracle_price, slot } => format!(
+                "reveal_trade {} {} {} {} {} {}",
+                reveal.user_idx,
+                reveal.size,
+                reveal.max_slippage_bps,
+                encode_hex32(&reveal.salt),
+                oracle_price,
+                slot
+            ),
+            Command::SubmitBatchOrder { user_idx, size, slot } => {
+                format!("batch_order {} {} {}", user_idx, size, slot)
+            }
+            Command::ClearBatch { oracle_price, slot } => format!("clear_batch {} {}", oracle_price, slot),
+            Command::Heartbeat { slot } => format!("heartbeat {}", slot),
+            Command::Crank { caller_idx, oracle_price, slot } => {
+                format!("crank {} {} {}", caller_idx, oracle_price, slot)
+            }
+            Command::UpdateMarketParams => "update_market_params".into(),
+            Command::SetFrozen { frozen } => format!("set_frozen {}", frozen),
+            Command::Inject(fault) => format!("inject {}", fault.encode()),
+            Command::SwapAgent { ref name } => format!("swap_agent {}", name),
+        }
+    }
+
+    /// Parse a journal line
+    pub fn decode(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let name = words.next()?;
+        let args: Vec<&str> = words.collect();
+        let arg = |i: usize| args.get(i).copied().unwrap_or("");
+        let command = match (name, args.len()) {
+            ("add_user", 1) => Command::AddUser { deposit: arg(0).parse().ok()? },
+            ("add_lp", 1) => Command::AddLp { deposit: arg(0).parse().ok()? },
+            ("deposit", 2) => Command::Deposit {
+                idx: arg(0).parse().ok()?,
+                amount: arg(1).parse().ok()?,
+            },
+            ("trade", 4..=7) => {
+                // Optional fields follow as key=value, in any order
+                let (mut client_order_id, mut sequence, mut valid_until_slot) = (None, None, None);
+                for tagged in &args[4..] {
+                    match tagged.split_once('=')? {
+                        ("id", id) => client_order_id = Some(id.parse().ok()?),
+                        ("seq", seq) => sequence = Some(seq.parse().ok()?),
+                        ("until", until) => valid_until_slot = Some(until.parse().ok()?),
+                        _ => return None,
+                    }
+                }
+                Command::Trade {
+                    user_idx: arg(0).parse().ok()?,
+                    oracle_price: arg(1).parse().ok()?,
+                    size: arg(2).parse().ok()?,
+                    slot: arg(3).parse().ok()?,
+                    client_order_id,
+                    sequence,
+                    valid_until_slot,
+                }
+            }
+            ("commit_trade", 3) => Command::CommitTrade {
+                user_idx: arg(0).parse().ok()?,
+                commitment: decode_hex32(arg(1))?,
+                slot: arg(2).parse().ok()?,
+            },
+            ("reveal_trade", 6) => Command::RevealTrade {
+                reveal: TradeReveal {
+                    user_idx: arg(0).parse().ok()?,
+                    size: arg(1).parse().ok()?,
+                    max_slippage_bps: arg(2).parse().ok()?,
+                    salt: decode_hex32(arg(3))?,
+                },
+                oracle_price: arg(4).parse().ok()?,
+                slot: arg(5).parse().ok()?,
+            },
+            ("batch_order", 3) => Command::SubmitBatchOrder {
+                user_idx: arg(0).parse().ok()?,
+                size: arg(1).parse().ok()?,
+                slot: arg(2).parse().ok()?,
+            },
+            ("clear_batch", 2) => Command::ClearBatch {
+                oracle_price: arg(0).parse().ok()?,
+                slot: arg(1).parse().ok()?,
+            },
+            ("heartbeat", 1) => Command::Heartbeat { slot: arg(0).parse().ok()? },
+            ("crank", 3) => Command::Crank {
+                caller_idx: arg(0).parse().ok()?,
+                oracle_price: arg(1).parse().ok()?,
+                slot: arg(2).parse().ok()?,
+            },
+            ("update_market_params", 0) => Command::UpdateMarketParams,
+            ("set_frozen", 1) => Command::SetFrozen { frozen: arg(0).parse().ok()? },
+            ("swap_agent", 1) => Command::SwapAgent { name: arg(0).to_string() },
+            ("inject", 1..=2) => Command::Inject(Fault::decode(arg(0), args.get(1).copied())?),
+            _ => return None,
+        };
+        Some(command)
+    }
+}
+
+/// Lowercase hex of a 32-byte value (commitments, salts)
+pub fn encode_hex32(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parse 64 hex digits into 32 bytes
+pub fn decode_hex32(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(out)
+}
+
+/// Journal of applied commands
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub commands: Vec<Command>,
+}
+
+impl Snapshot {
+    /// Text form: a header line naming the format version, then one
+    /// command per line
+    pub fn encode(&self) -> String {
+        let mut out = migration::header();
+        out.push('\n');
+        for command in &self.commands {
+            out.push_str(&command.encode());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Parse the text form of the current version, reporting the first
+    /// bad line (`Migrations::upgrade` also reads older versions)
+    pub fn decode(text: &str) -> io::Result<Self> {
+        Migrations::new().upgrade(text)
+    }
+}
+
+/// Where snapshots are kept between restarts
+pub trait SnapshotStore {
+    /// Persist the full journal
+    fn save(&mut self, snapshot: &Snapshot) -> io::Result<()>;
+
+    /// The last saved journal (None if nothing was ever saved)
+    fn load(&mut self) -> io::Result<Option<Snapshot>>;
+}
+
+/// Journal kept in a text file, replaced atomically on every save
+pub struct FileStore {
+    path: String,
+    migrations: Migrations,
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            migrations: Migrations::new(),
+        }
+    }
+
+    /// Upgrade journals written by older versions with `migrations`
+    pub fn with_migrations(mut self, migrations: Migrations) -> Self {
+        self.migrations = migrations;
+        self
+    }
+}
+
+impl SnapshotStore for FileStore {
+    fn save(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let tmp = format!("{}.tmp", self.path);
+        std::fs::write(&tmp, snapshot.encode())?;
+        std::fs::rename(&tmp, &self.path)
+    }
+
+    fn load(&mut self) -> io::Result<Option<Snapshot>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(text) => self.migrations.upgrade(&text).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Journal kept in memory (tests, or a server that should not persist)
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    pub snapshot: Option<Snapshot>,
+}
+
+impl SnapshotStore for MemoryStore {
+    fn save(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        self.snapshot = Some(snapshot.clone());
+        Ok(())
+    }
+
+    fn load(&mut self) -> io::Result<Option<Snapshot>> {
+        Ok(self.snapshot.clone())
+    }
+}
+
+/// Why a command could not be applied or persisted
+#[derive(Debug)]
+pub enum ServerError {
+    /// The engine rejected the command; nothing changed
+    Engine(RiskError),
+
+    /// The command was applied but the journal could not be saved
+    Store(io::Error),
+
+    /// A journaled command failed on replay (config or agent changed)
+    Replay { line: usize, error: RiskError },
+}
+
+/// Command the server applied or rejected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// Position in the event stream, starting at 0
+    pub seq: u64,
+    /// Engine slot after the command
+    pub slot: u64,
+    pub command: Command,
+    /// Why the command was rejected (None if applied)
+    pub error: Option<RiskError>,
+    /// Rationale the agent gave for a trade decision
+    pub meta: Option<DecisionMeta>,
+}
+
+/// OHLC candle over `interval` slots
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candle {
+    /// First slot of the interval
+    pub start_slot: u64,
+    pub open: u64,
+    pub high: u64,
+    pub low: u64,
+    pub close: u64,
+    /// Sum of absolute fill sizes
+    pub volume: u128,
+    pub trades: u32,
+}
+
+/// Executed trades and the event stream
+///
+/// Trades come from the engine's fills and are rebuilt when the journal is
+/// replayed. Events of rejected commands are not journaled, so only
+/// those since the last restart are kept.
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    trades: VecDeque<TradeFill>,
+    events: VecDeque<Event>,
+    next_seq: u64,
+}
+
+impl History {
+    /// Trades with `from <= slot <= to`, oldest first
+    pub fn trades(&self, from: u64, to: u64) -> impl Iterator<Item = &TradeFill> {
+        self.trades.iter().filter(move |t| (from..=to).contains(&t.slot))
+    }
+
+    /// Events with `seq >= since`, oldest first
+    pub fn events(&self, since: u64) -> impl Iterator<Item = &Event> {
+        self.events.iter().filter(move |e| e.seq >= since)
+    }
+
+    /// Sequence number the next event will get
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Candles of `interval` slots over trades with `from <= slot <= to`
+    ///
+    /// Intervals without trades are skipped.
+    pub fn candles(&self, interval: u64, from: u64, to: u64) -> Vec<Candle> {
+        let interval = interval.max(1);
+        let mut candles: Vec<Candle> = Vec::new();
+        for fill in self.trades(from, to) {
+            let start_slot = fill.slot - fill.slot % interval;
+            let volume = fill.size.unsigned_abs();
+            match candles.last_mut() {
+                Some(c) if c.start_slot == start_slot => {
+                    c.high = c.high.max(fill.price);
+                    c.low = c.low.min(fill.price);
+                    c.close = fill.price;
+                    c.volume = c.volume.saturating_add(volume);
+                    c.trades = c.trades.saturating_add(1);
+                }
+                _ => candles.push(Candle {
+                    start_slot,
+                    open: fill.price,
+                    high: fill.price,
+                    low: fill.price,
+                    close: fill.price,
+                    volume,
+                    trades: 1,
+                }),
+            }
+        }
+        candles
+    }
+
+    fn record_trade(&mut self, fill: TradeFill) {
+        if self.trades.len() == MAX_HISTORY {
+            self.trades.pop_front();
+        }
+        self.trades.push_back(fill);
+    }
+
+    fn record_event(
+        &mut self,
+        slot: u64,
+        command: Command,
+        error: Option<RiskError>,
+        meta: Option<DecisionMeta>,
+    ) {
+        if self.events.len() == MAX_HISTORY {
+            self.events.pop_front();
+        }
+        self.events.push_back(Event {
+            seq: self.next_seq,
+            slot,
+            command,
+            error,
+            meta,
+        });
+        self.next_seq += 1;
+    }
+}
 
 /// Simple in-memory server state
-pub struct ServerState {
-    pub engine: ClawcolatorEngine,
-    pub agent: Box<dyn OpenClawAgent + Send + Sync>,
+pub struct ServerState<A: OpenClawAgent> {
+    pub engine: Box<ClawcolatorEngine>,
+    pub agent: A,
+    journal: Snapshot,
+    store: Box<dyn SnapshotStore + Send + Sync>,
+    history: History,
+    faults: Faults,
+    agents: AgentRegistry<A>,
+    agent_name: Option<String>,
+    leaderboard: Option<Leaderboard>,
+    clock: SlotClock,
 }
 
-impl ServerState {
-    pub fn new(agent: Box<dyn OpenClawAgent + Send + Sync>) -> Self {
+impl<A: OpenClawAgent> ServerState<A> {
+    /// Demo risk params, nothing persisted
+    pub fn new(agent: A) -> Self {
         let base_params = RiskParams {
             warmup_period_slots: 100,
             maintenance_margin_bps: 500,
@@ -30,10 +745,990 @@ impl ServerState {
             liquidation_buffer_bps: 100,
             min_liquidation_abs: U128::new(100_000),
         };
-        
+
         Self {
-            engine: ClawcolatorEngine::new(base_params),
+            engine: Box::new(ClawcolatorEngine::new(base_params)),
             agent,
+            journal: Snapshot::default(),
+            store: Box::new(MemoryStore::default()),
+            history: History::default(),
+            faults: Faults::default(),
+            agents: AgentRegistry::new(),
+            agent_name: None,
+            leaderboard: None,
+            clock: SlotClock::default(),
+        }
+    }
+
+    /// Agents `Command::SwapAgent` can swap in
+    pub fn with_agents(mut self, agents: AgentRegistry<A>) -> Self {
+        self.agents = agents;
+        self
+    }
+
+    /// Rank accounts by PnL, volume and drawdown over the last
+    /// `window_slots` slots (a trading competition)
+    ///
+    /// Standings are not journaled: they cover commands applied from here
+    /// on, so a restored server starts a fresh board.
+    pub fn with_leaderboard(mut self, window_slots: u64) -> Self {
+        let mut board = Leaderboard::new(window_slots);
+        board.sample(&self.engine, self.faults.price(self.faults.last_oracle_price));
+        self.leaderboard = Some(board);
+        self
+    }
+
+    /// Restore from `store` by replaying its journal against `engine`,
+    /// which must be freshly built from the same config as before
+    pub fn restore(
+        engine: Box<ClawcolatorEngine>,
+        agent: A,
+        store: Box<dyn SnapshotStore + Send + Sync>,
+    ) -> Result<Self, ServerError> {
+        Self::restore_with_agents(engine, agent, AgentRegistry::new(), store)
+    }
+
+    /// `restore` with the registry journaled agent swaps are replayed from
+    pub fn restore_with_agents(
+        engine: Box<ClawcolatorEngine>,
+        agent: A,
+        agents: AgentRegistry<A>,
+        mut store: Box<dyn SnapshotStore + Send + Sync>,
+    ) -> Result<Self, ServerError> {
+        let saved = store.load().map_err(ServerError::Store)?.unwrap_or_default();
+        let mut state = Self {
+            engine,
+            agent,
+            journal: Snapshot::default(),
+            store,
+            history: History::default(),
+            faults: Faults::default(),
+            agents,
+            agent_name: None,
+            leaderboard: None,
+            clock: SlotClock::default(),
+        };
+        for (line, command) in saved.commands.into_iter().enumerate() {
+            state
+                .execute(command.clone())
+                .map_err(|error| ServerError::Replay { line: line + 2, error })?;
+            state.journal.commands.push(command);
+        }
+        Ok(state)
+    }
+
+    /// Apply a command, journal it and save the journal
+    ///
+    /// Returns the index of the account the command opened, if any.
+    /// Rejected commands are not journaled.
+    pub fn apply(&mut self, command: Command) -> Result<Option<u16>, ServerError> {
+        let opened = self.execute(command.clone()).map_err(ServerError::Engine)?;
+        self.journal.commands.push(command);
+        self.store.save(&self.journal).map_err(ServerError::Store)?;
+        Ok(opened)
+    }
+
+    /// Commands applied so far
+    pub fn journal(&self) -> &Snapshot {
+        &self.journal
+    }
+
+    /// Executed trades and events
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Clock that stamps slots on new commands
+    pub fn clock(&self) -> &SlotClock {
+        &self.clock
+    }
+
+    /// Slot for the next command built from a request
+    pub fn slot(&self) -> u64 {
+        self.clock.now(self.engine.risk_engine().current_slot)
+    }
+
+    /// Run the clock in `mode` from the current slot on
+    pub fn set_clock(&mut self, mode: ClockMode) {
+        self.clock.set_mode(mode, self.engine.risk_engine().current_slot);
+    }
+
+    /// Move the clock `slots` ahead of `slot()`
+    pub fn advance_clock(&mut self, slots: u64) {
+        self.clock.advance(slots, self.engine.risk_engine().current_slot);
+    }
+
+    /// Run a seeded market simulation against the active agent
+    ///
+    /// The run gets a fresh engine with this instance's risk params, so the
+    /// live market is untouched and the same params always give the same
+    /// report.
+    pub fn simulate(&self, params: &SimulationParams) -> crate::Result<SimulationReport> {
+        let mut engine = Box::new(ClawcolatorEngine::new(self.engine.risk_engine().params));
+        simulate(&mut engine, &self.agent, params)
+    }
+
+    /// Competition standings, if enabled with `with_leaderboard`
+    pub fn leaderboard(&self) -> Option<&Leaderboard> {
+        self.leaderboard.as_ref()
+    }
+
+    /// Injected faults in effect
+    pub fn faults(&self) -> &Faults {
+        &self.faults
+    }
+
+    /// Registered agents
+    pub fn agents(&self) -> &AgentRegistry<A> {
+        &self.agents
+    }
+
+    /// Name of the active agent (None for the one the server started with)
+    pub fn agent_name(&self) -> Option<&str> {
+        self.agent_name.as_deref()
+    }
+
+    /// Run `command` and record it in the history
+    fn execute(&mut self, command: Command) -> crate::Result<Option<u16>> {
+        // A resubmitted client order leaves no new fill or decision behind
+        let is_trade = match command {
+            Command::Trade { user_idx, slot, client_order_id: Some(id), .. } => {
+                self.engine.client_orders().find(user_idx, id, slot).is_none()
+            }
+            Command::Trade { .. } | Command::RevealTrade { .. } => true,
+            _ => false,
+        };
+        let (clock, last_oracle_price) =
+            (self.engine.risk_engine().current_slot, self.faults.last_oracle_price);
+        let result = self.run(command.clone());
+        if result.is_err() {
+            // Rejected commands are not journaled, so they must not move
+            // what later commands read (a replay would not see them)
+            self.engine.risk_engine_mut().current_slot = clock;
+            self.faults.last_oracle_price = last_oracle_price;
+        }
+        let slot = self.engine.risk_engine().current_slot;
+        let traded = is_trade && result.is_ok();
+        let meta = self.engine.last_decision_meta().copied().filter(|_| is_trade);
+        let cleared = matches!(command, Command::ClearBatch { .. }) && result.is_ok();
+        let deposit = match (&command, &result) {
+            (Command::AddUser { deposit } | Command::AddLp { deposit }, Ok(Some(idx))) => {
+                Some((*idx, *deposit))
+            }
+            (Command::Deposit { idx, amount }, Ok(_)) => Some((*idx, *amount)),
+            _ => None,
+        };
+        self.history.record_event(slot, command, result.err(), meta);
+        let mut fills = Vec::new();
+        if let (true, Some(fill)) = (traded, self.engine.last_fill()) {
+            fills.push(*fill);
+        }
+        if cleared {
+            fills.extend(self.engine.batch_fills().copied());
+        }
+        for fill in &fills {
+            self.history.record_trade(*fill);
+        }
+        if let Some(board) = self.leaderboard.as_mut() {
+            if let Some((idx, amount)) = deposit {
+                board.record_deposit(&self.engine, idx, amount);
+            }
+            for fill in &fills {
+                board.record_fill(&self.engine, fill);
+            }
+            board.sample(&self.engine, self.faults.price(self.faults.last_oracle_price));
+        }
+        result
+    }
+
+    fn run(&mut self, command: Command) -> crate::Result<Option<u16>> {
+        let slot = self.engine.risk_engine().current_slot;
+        match command {
+            Command::AddUser { deposit } => {
+                let risk = self.engine.risk_engine_mut();
+                let idx = risk.add_user(0)?;
+                risk.deposit(idx, deposit, slot)?;
+                Ok(Some(idx))
+            }
+            Command::AddLp { deposit } => {
+                let risk = self.engine.risk_engine_mut();
+                let idx = risk.add_lp([0; 32], [0; 32], 0)?;
+                risk.deposit(idx, deposit, slot)?;
+                Ok(Some(idx))
+            }
+            Command::Deposit { idx, amount } => {
+                self.engine.risk_engine_mut().deposit(idx, amount, slot)?;
+                Ok(None)
+            }
+            Command::Trade {
+                user_idx,
+                oracle_price,
+                size,
+                slot,
+                client_order_id,
+                sequence,
+                valid_until_slot,
+            } => {
+                self.faults.last_oracle_price = oracle_price;
+                let oracle = self.faults.oracle(oracle_price, slot);
+                let request = TradeRequest {
+                    user_idx,
+                    size,
+                    requested_price: None,
+                    client_order_id,
+                    sequence,
+                    valid_until_slot,
+                };
+                if self.faults.agent_down(slot) {
+                    self.engine.submit_trade(&UnavailableAgent, request, oracle, slot)?;
+                } else {
+                    self.engine.submit_trade(&self.agent, request, oracle, slot)?;
+                }
+                Ok(None)
+            }
+            Command::CommitTrade { user_idx, commitment, slot } => {
+                self.engine.commit_trade(user_idx, commitment, slot)?;
+                Ok(None)
+            }
+            Command::RevealTrade { reveal, oracle_price, slot } => {
+                self.faults.last_oracle_price = oracle_price;
+                let oracle = self.faults.oracle(oracle_price, slot);
+                if self.faults.agent_down(slot) {
+                    self.engine.reveal_trade(&UnavailableAgent, reveal, oracle, slot)?;
+                } else {
+                    self.engine.reveal_trade(&self.agent, reveal, oracle, slot)?;
+                }
+                Ok(None)
+            }
+            Command::SubmitBatchOrder { user_idx, size, slot } => {
+                self.engine.submit_batch_order(user_idx, size, slot)?;
+                Ok(None)
+            }
+            Command::ClearBatch { oracle_price, slot } => {
+                self.faults.last_oracle_price = oracle_price;
+                let oracle = self.faults.oracle(oracle_price, slot);
+                if self.faults.agent_down(slot) {
+                    self.engine.clear_batch(&UnavailableAgent, oracle, slot)?;
+                } else {
+                    self.engine.clear_batch(&self.agent, oracle, slot)?;
+                }
+                Ok(None)
+            }
+            Command::Heartbeat { slot } => {
+                if self.faults.agent_down(slot) {
+                    self.engine.record_heartbeat(&UnavailableAgent, slot)?;
+                } else {
+                    self.engine.record_heartbeat(&self.agent, slot)?;
+                }
+                Ok(None)
+            }
+            Command::Crank { caller_idx, oracle_price, slot } => {
+                self.faults.last_oracle_price = oracle_price;
+                let price = self.faults.price(oracle_price);
+                self.engine.crank(caller_idx, slot, price, None)?;
+                Ok(None)
+            }
+            Command::UpdateMarketParams => {
+                if self.faults.agent_down(slot) {
+                    self.engine.update_market_params(&UnavailableAgent)?;
+                } else {
+                    self.engine.update_market_params(&self.agent)?;
+                }
+                Ok(None)
+            }
+            Command::SetFrozen { frozen } => {
+                self.engine.set_market_frozen(frozen);
+                Ok(None)
+            }
+            Command::Inject(fault) => {
+                self.inject(fault, slot)?;
+                Ok(None)
+            }
+            Command::SwapAgent { name } => {
+                self.swap_agent(name)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Stage the named agent, dry-run it, then make it live and publish
+    /// its market params; the current agent stays on any failure
+    fn swap_agent(&mut self, name: String) -> crate::Result<()> {
+        let candidate = self.agents.build(&name).ok_or(RiskError::Unauthorized)?;
+        self.engine.dry_run_agent(&candidate)?;
+        self.engine.update_market_params(&candidate)?;
+        self.agent = candidate;
+        self.agent_name = Some(name);
+        Ok(())
+    }
+
+    fn inject(&mut self, fault: Fault, slot: u64) -> crate::Result<()> {
+        match fault {
+            Fault::OracleJump { bps } => {
+                // Never let the shifted price go non-positive
+                if bps <= -10_000 {
+                    return Err(RiskError::Overflow);
+                }
+                self.faults.oracle_offset_bps = bps;
+            }
+            Fault::OracleOutage { slots } => {
+                self.faults.oracle_outage = Some((slot, slot.saturating_add(slots)));
+            }
+            Fault::AgentOutage { slots } => {
+                self.faults.agent_outage_until = Some(slot.saturating_add(slots));
+            }
+            Fault::MassLiquidation { shock_bps } => {
+                if self.faults.last_oracle_price == 0 {
+                    return Err(RiskError::Overflow);
+                }
+                let price = shocked_price(self.faults.last_oracle_price, shock_bps);
+                self.engine.crank(AGENT_LP_IDX, slot + 1, price, None)?;
+            }
+            Fault::Clear => {
+                self.faults = Faults {
+                    last_oracle_price: self.faults.last_oracle_price,
+                    ..Faults::default()
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parsed HTTP request
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub body: String,
+    headers: Vec<(String, String)>,
+    params: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Parse the request line and body of a raw HTTP request
+    pub fn parse(raw: &str) -> Option<Self> {
+        let (head, body) = raw.split_once("\r\n\r\n").unwrap_or((raw, ""));
+        let mut lines = head.lines();
+        let mut parts = lines.next()?.split_whitespace();
+        let method = parts.next()?.to_string();
+        let target = parts.next()?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Some(Self {
+            method,
+            path: path.to_string(),
+            query: query.to_string(),
+            body: body.to_string(),
+            headers: lines
+                .filter_map(|l| l.split_once(':'))
+                .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+                .collect(),
+            params: Vec::new(),
+        })
+    }
+
+    /// Header value; names are case-insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Token from an `Authorization: Bearer <token>` header
+    pub fn bearer_token(&self) -> Option<&str> {
+        self.header("authorization")?.strip_prefix("Bearer ").map(str::trim)
+    }
+
+    /// Path segment captured by `{name}` in the route pattern
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Query string value (`?name=value`)
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .split('&')
+            .filter_map(|kv| kv.split_once('='))
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v)
+    }
+
+    /// Integer field of a flat JSON body
+    pub fn json_int(&self, key: &str) -> Option<i128> {
+        let pattern = format!("\"{}\":", key);
+        let start = self.body.find(&pattern)? + pattern.len();
+        self.body[start..]
+            .trim_start()
+            .split(|c: char| c == ',' || c == '}' || c.is_whitespace())
+            .next()?
+            .parse()
+            .ok()
+    }
+
+    /// String field of a flat JSON body
+    pub fn json_str(&self, key: &str) -> Option<&str> {
+        let pattern = format!("\"{}\":", key);
+        let start = self.body.find(&pattern)? + pattern.len();
+        self.body[start..].trim_start().strip_prefix('"')?.split('"').next()
+    }
+
+    /// Match `pattern` against the path, capturing `{name}` segments
+    fn matches(&mut self, pattern: &str) -> bool {
+        let mut params = Vec::new();
+        let mut segments = self.path.split('/');
+        for expected in pattern.split('/') {
+            let Some(actual) = segments.next() else {
+                return false;
+            };
+            match expected.strip_prefix('{').and_then(|e| e.strip_suffix('}')) {
+                Some(name) => params.push((name.to_string(), actual.to_string())),
+                None if expected == actual => {}
+                None => return false,
+            }
+        }
+        if segments.next().is_some() {
+            return false;
+        }
+        self.params = params;
+        true
+    }
+}
+
+/// JSON response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    pub fn ok(body: impl Into<String>) -> Self {
+        Self {
+            status: 200,
+            body: body.into(),
+        }
+    }
+
+    /// 400 with `error` as the message; handlers may pass client input
+    /// through, it is escaped
+    pub fn bad_request(error: &str) -> Self {
+        Self {
+            status: 400,
+            body: format!(r#"{{"error": {}}}"#, json_string(error)),
+        }
+    }
+
+    pub fn not_found(request: &Request) -> Self {
+        Self {
+            status: 404,
+            body: format!(
+                r#"{{"error": "Not found", "path": {}, "method": {}}}"#,
+                json_string(&request.path),
+                json_string(&request.method)
+            ),
+        }
+    }
+
+    pub fn unauthorized() -> Self {
+        Self {
+            status: 401,
+            body: r#"{"error": "Missing or unknown API key"}"#.into(),
+        }
+    }
+
+    pub fn forbidden(scope: Scope) -> Self {
+        Self {
+            status: 403,
+            body: format!(r#"{{"error": "API key lacks {:?} permission"}}"#, scope),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "Not Found",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+/// `text` as a quoted JSON string
+pub fn json_string(text: &str) -> String {
+    serde_json::Value::from(text).to_string()
+}
+
+/// What a route requires of the caller's API key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    /// Status and metrics
+    Read,
+    /// Trading on the `user_idx` named in the request body
+    Trade,
+    /// Freeze/resume, parameter updates and account administration
+    Admin,
+}
+
+/// What an API key may do
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+    /// `Scope::Read` only
+    ReadOnly,
+    /// `Scope::Read`, and `Scope::Trade` for one user account
+    Trade { user_idx: u16 },
+    /// Every scope
+    Admin,
+}
+
+impl Permission {
+    /// Whether this key may call a route requiring `scope` with `request`
+    pub fn allows(&self, scope: Scope, request: &Request) -> bool {
+        match (*self, scope) {
+            (Permission::Admin, _) | (_, Scope::Read) => true,
+            (Permission::Trade { user_idx }, Scope::Trade) => {
+                request.json_int("user_idx") == Some(user_idx as i128)
+            }
+            _ => false,
+        }
+    }
+
+    /// Parse `read`, `trade:<user_idx>` or `admin`
+    pub fn decode(text: &str) -> Option<Self> {
+        match text.split_once(':') {
+            Some(("trade", idx)) => Some(Permission::Trade { user_idx: idx.parse().ok()? }),
+            None if text == "read" => Some(Permission::ReadOnly),
+            None if text == "admin" => Some(Permission::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// API keys accepted by a `Router`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApiKeys {
+    keys: Vec<(String, Permission)>,
+}
+
+impl ApiKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept `token` with `permission`
+    pub fn with(mut self, token: impl Into<String>, permission: Permission) -> Self {
+        self.keys.push((token.into(), permission));
+        self
+    }
+
+    /// Parse a keys file: one `<token> <permission>` per line, `#` comments
+    pub fn decode(text: &str) -> io::Result<Self> {
+        let mut keys = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let parsed = line
+                .split_once(char::is_whitespace)
+                .and_then(|(token, p)| Some((token, Permission::decode(p.trim())?)));
+            let Some((token, permission)) = parsed else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("bad key on line {}", i + 1),
+                ));
+            };
+            keys = keys.with(token, permission);
+        }
+        Ok(keys)
+    }
+
+    /// Permission granted to `token`, if it is a known key
+    pub fn permission(&self, token: &str) -> Option<Permission> {
+        self.keys
+            .iter()
+            .find(|(key, _)| constant_time_eq(key.as_bytes(), token.as_bytes()))
+            .map(|(_, p)| *p)
+    }
+}
+
+/// Compare without short-circuiting on the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// `(name, JSON type)` pairs describing the fields of a flat JSON object
+pub type Fields = &'static [(&'static str, &'static str)];
+
+/// OpenAPI description of a route
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RouteDoc {
+    pub summary: &'static str,
+    /// `{name}` segments of the pattern
+    pub path: Fields,
+    /// `?name=value` parameters
+    pub query: Fields,
+    /// Request body fields
+    pub body: Fields,
+    /// Response body fields
+    pub response: Fields,
+}
+
+/// Path the spec is served on by `Router::with_openapi`
+pub const OPENAPI_PATH: &str = "/openapi.json";
+
+fn schema(fields: Fields) -> serde_json::Value {
+    let properties: serde_json::Map<String, serde_json::Value> = fields
+        .iter()
+        .map(|(name, kind)| (name.to_string(), serde_json::json!({ "type": kind })))
+        .collect();
+    serde_json::json!({ "type": "object", "properties": properties })
+}
+
+fn parameters(location: &str, fields: Fields) -> impl Iterator<Item = serde_json::Value> + '_ {
+    fields.iter().map(move |(name, kind)| {
+        serde_json::json!({
+            "name": name,
+            "in": location,
+            "required": location == "path",
+            "schema": { "type": kind },
+        })
+    })
+}
+
+type ReadHandler<A> = Box<dyn Fn(&ServerState<A>, &Request) -> Response + Send + Sync>;
+type WriteHandler<A> = Box<dyn Fn(&mut ServerState<A>, &Request) -> Response + Send + Sync>;
+
+enum Handler<A: OpenClawAgent> {
+    Read(ReadHandler<A>),
+    Write(WriteHandler<A>),
+}
+
+struct Route<A: OpenClawAgent> {
+    method: &'static str,
+    pattern: &'static str,
+    scope: Scope,
+    doc: RouteDoc,
+    handler: Handler<A>,
+}
+
+/// Routing table; the first matching route wins
+///
+/// Without `ApiKeys` every route is open. With them, each request must carry
+/// `Authorization: Bearer <token>` for a key whose permission covers the
+/// route's scope: `Scope::Read` for read routes and `Scope::Admin` for write
+/// routes unless overridden with `scope`.
+pub struct Router<A: OpenClawAgent> {
+    routes: Vec<Route<A>>,
+    keys: Option<ApiKeys>,
+}
+
+impl<A: OpenClawAgent> Default for Router<A> {
+    fn default() -> Self {
+        Self {
+            routes: Vec::new(),
+            keys: None,
+        }
+    }
+}
+
+impl<A: OpenClawAgent> Router<A> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Route served under the read lock, concurrently with other reads
+    pub fn read<F>(mut self, method: &'static str, pattern: &'static str, handler: F) -> Self
+    where
+        F: Fn(&ServerState<A>, &Request) -> Response + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            method,
+            pattern,
+            scope: Scope::Read,
+            doc: RouteDoc::default(),
+            handler: Handler::Read(Box::new(handler)),
+        });
+        self
+    }
+
+    /// Route served under the write lock, one at a time
+    pub fn write<F>(mut self, method: &'static str, pattern: &'static str, handler: F) -> Self
+    where
+        F: Fn(&mut ServerState<A>, &Request) -> Response + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            method,
+            pattern,
+            scope: Scope::Admin,
+            doc: RouteDoc::default(),
+            handler: Handler::Write(Box::new(handler)),
+        });
+        self
+    }
+
+    /// Require `scope` for the route registered last
+    pub fn scope(mut self, scope: Scope) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.scope = scope;
+        }
+        self
+    }
+
+    /// Describe the route registered last in the OpenAPI spec
+    pub fn doc(mut self, doc: RouteDoc) -> Self {
+        if let Some(route) = self.routes.last_mut() {
+            route.doc = doc;
+        }
+        self
+    }
+
+    /// Require an API key from `keys` on every route
+    pub fn with_keys(mut self, keys: ApiKeys) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Serve the OpenAPI spec of the routes registered so far at
+    /// `OPENAPI_PATH`
+    ///
+    /// Call after every other route and after `with_keys`.
+    pub fn with_openapi(self, title: &str, version: &str) -> Self {
+        let spec = self.openapi(title, version);
+        self.read("GET", OPENAPI_PATH, move |_, _| Response::ok(spec.clone()))
+            .doc(RouteDoc {
+                summary: "This OpenAPI description",
+                ..RouteDoc::default()
+            })
+    }
+
+    /// OpenAPI 3.0 description of the registered routes, as JSON
+    pub fn openapi(&self, title: &str, version: &str) -> String {
+        let mut paths = serde_json::Map::new();
+        for route in &self.routes {
+            let doc = &route.doc;
+            let mut responses = serde_json::json!({
+                "200": {
+                    "description": "OK",
+                    "content": { "application/json": { "schema": schema(doc.response) } },
+                },
+                "400": { "description": "Bad Request" },
+            });
+            if self.keys.is_some() {
+                responses["401"] = serde_json::json!({ "description": "Unauthorized" });
+                responses["403"] = serde_json::json!({ "description": "Forbidden" });
+            }
+            let mut operation = serde_json::json!({
+                "summary": doc.summary,
+                "parameters": parameters("path", doc.path)
+                    .chain(parameters("query", doc.query))
+                    .collect::<Vec<_>>(),
+                "responses": responses,
+                "x-scope": format!("{:?}", route.scope),
+            });
+            if !doc.body.is_empty() {
+                operation["requestBody"] = serde_json::json!({
+                    "required": true,
+                    "content": { "application/json": { "schema": schema(doc.body) } },
+                });
+            }
+            paths
+                .entry(route.pattern)
+                .or_insert_with(|| serde_json::json!({}))[route.method.to_ascii_lowercase()] =
+                operation;
+        }
+        let mut spec = serde_json::json!({
+            "openapi": "3.0.3",
+            "info": { "title": title, "version": version },
+            "paths": paths,
+        });
+        if self.keys.is_some() {
+            spec["components"] = serde_json::json!({
+                "securitySchemes": { "apiKey": { "type": "http", "scheme": "bearer" } },
+            });
+            spec["security"] = serde_json::json!([{ "apiKey": [] }]);
+        }
+        spec.to_string()
+    }
+
+    /// Registered (method, pattern) pairs in order
+    pub fn routes(&self) -> impl Iterator<Item = (&'static str, &'static str)> + '_ {
+        self.routes.iter().map(|r| (r.method, r.pattern))
+    }
+
+    /// Run the matching handler under the appropriate lock
+    pub fn dispatch(&self, state: &RwLock<ServerState<A>>, mut request: Request) -> Response {
+        let Some(route) = self
+            .routes
+            .iter()
+            .find(|r| r.method == request.method && request.matches(r.pattern))
+        else {
+            return Response::not_found(&request);
+        };
+        if let Some(denied) = self.authorize(route.scope, &request) {
+            return denied;
+        }
+        // A handler that panicked leaves the state as consistent as the
+        // engine does after an error, so keep serving
+        match &route.handler {
+            Handler::Read(f) => f(&state.read().unwrap_or_else(|e| e.into_inner()), &request),
+            Handler::Write(f) => f(&mut state.write().unwrap_or_else(|e| e.into_inner()), &request),
+        }
+    }
+
+    /// Route `request` to an instance: `/i/{name}/...` to the named one
+    /// with the prefix stripped, anything else to the default one
+    ///
+    /// `GET /instances` lists the named instances (`Scope::Read`) and `POST
+    /// /instances` creates one from an `InstanceSpec` body (`Scope::Admin`).
+    pub fn dispatch_instances(&self, instances: &Instances<A>, mut request: Request) -> Response {
+        if request.path == INSTANCES_PATH {
+            return match request.method.as_str() {
+                "GET" => self.authorize(Scope::Read, &request).unwrap_or_else(|| {
+                    let names: Vec<String> =
+                        instances.names().iter().map(|n| format!(r#""{}""#, n)).collect();
+                    Response::ok(format!(r#"{{"instances": [{}]}}"#, names.join(", ")))
+                }),
+                "POST" => self.authorize(Scope::Admin, &request).unwrap_or_else(|| {
+                    let Some(spec) = InstanceSpec::from_request(&request) else {
+                        return Response::bad_request("name required");
+                    };
+                    match instances.create(&spec) {
+                        Ok(_) => Response::ok(format!(r#"{{"ok": true, "name": "{}"}}"#, spec.name)),
+                        Err(e) => Response::bad_request(&e.message()),
+                    }
+                }),
+                _ => Response::not_found(&request),
+            };
+        }
+        let Some(rest) = request.path.strip_prefix("/i/") else {
+            return self.dispatch(instances.default_state(), request);
+        };
+        let (name, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let Some(state) = instances.get(name) else {
+            return Response::not_found(&request);
+        };
+        request.path = format!("/{}", path);
+        self.dispatch(&state, request)
+    }
+
+    /// Response refusing `request` if the API keys do not cover `scope`
+    fn authorize(&self, scope: Scope, request: &Request) -> Option<Response> {
+        let keys = self.keys.as_ref()?;
+        match request.bearer_token().and_then(|t| keys.permission(t)) {
+            None => Some(Response::unauthorized()),
+            Some(p) if !p.allows(scope, request) => Some(Response::forbidden(scope)),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Read one request: headers, then a body of `Content-Length` bytes
+fn read_request(stream: &mut TcpStream) -> io::Result<String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        let text = String::from_utf8_lossy(&buf);
+        if let Some(end) = text.find("\r\n\r\n") {
+            let length = text[..end]
+                .lines()
+                .filter_map(|l| l.split_once(':'))
+                .find(|(k, _)| k.trim().eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, v)| v.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            if buf.len() >= end + 4 + length {
+                break;
+            }
+        }
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "request too large"));
+        }
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    handler: &(dyn Fn(Request) -> Response + Send + Sync),
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let raw = read_request(&mut stream)?;
+    let response = match Request::parse(&raw) {
+        Some(request) => handler(request),
+        None => Response::bad_request("Invalid request"),
+    };
+    let http = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.reason(),
+        response.body.len(),
+        response.body
+    );
+    stream.write_all(http.as_bytes())
+}
+
+/// Serve `listener` with `workers` threads until it stops accepting
+pub fn serve<A>(
+    listener: TcpListener,
+    state: Arc<RwLock<ServerState<A>>>,
+    router: Arc<Router<A>>,
+    workers: usize,
+) -> io::Result<()>
+where
+    A: OpenClawAgent + Send + Sync + 'static,
+{
+    serve_with(listener, workers, Arc::new(move |request| router.dispatch(&state, request)))
+}
+
+/// `serve` over several instances, routed by `Router::dispatch_instances`
+pub fn serve_instances<A>(
+    listener: TcpListener,
+    instances: Arc<Instances<A>>,
+    router: Arc<Router<A>>,
+    workers: usize,
+) -> io::Result<()>
+where
+    A: OpenClawAgent + Send + Sync + 'static,
+{
+    serve_with(
+        listener,
+        workers,
+        Arc::new(move |request| router.dispatch_instances(&instances, request)),
+    )
+}
+
+type ConnectionHandler = Arc<dyn Fn(Request) -> Response + Send + Sync>;
+
+fn serve_with(listener: TcpListener, workers: usize, handler: ConnectionHandler) -> io::Result<()> {
+    let (tx, rx) = mpsc::channel::<TcpStream>();
+    let rx = Arc::new(Mutex::new(rx));
+    for _ in 0..workers.max(1) {
+        let (rx, handler) = (rx.clone(), handler.clone());
+        thread::spawn(move || loop {
+            let stream = match rx.lock().unwrap_or_else(|e| e.into_inner()).recv() {
+                Ok(stream) => stream,
+                Err(_) => return,
+            };
+            // Connection errors only affect that client
+            let _ = handle_connection(stream, &*handler);
+        });
+    }
+    for stream in listener.incoming() {
+        let stream = stream?;
+        if tx.send(stream).is_err() {
+            break;
         }
     }
+    Ok(())
 }
@@ -0,0 +1,134 @@
+//! Slot clock for new commands
+//!
+//! Every journaled command carries the slot it ran at, so the clock only
+//! decides which slot the server stamps on commands it builds from HTTP
+//! requests; replay never consults it. In manual mode the slot moves only
+//! when a crank or `advance` moves it. In wall-clock mode it follows real
+//! time at `SLOT_MS` per slot, and in accelerated mode at any rate, so
+//! funding and warmup over thousands of slots can be watched through the
+//! API in seconds. The clock never runs behind the engine.
+
+use std::time::{Duration, Instant};
+
+/// Length of a slot in wall-clock mode (as on Solana)
+pub const SLOT_MS: u64 = 400;
+
+/// Fastest accelerated rate
+pub const MAX_SLOTS_PER_SEC: u64 = 1_000_000;
+
+/// How the clock advances
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClockMode {
+    /// Only by cranks and explicit advances
+    #[default]
+    Manual,
+
+    /// One slot every `SLOT_MS`
+    WallClock,
+
+    /// `slots_per_sec` slots every second
+    Accelerated { slots_per_sec: u64 },
+}
+
+impl ClockMode {
+    /// Parse `manual`, `wall_clock` or `accelerated` (with a rate)
+    pub fn decode(mode: &str, slots_per_sec: Option<u64>) -> Option<Self> {
+        match mode {
+            "manual" => Some(ClockMode::Manual),
+            "wall_clock" => Some(ClockMode::WallClock),
+            "accelerated" => match slots_per_sec? {
+                rate @ 1..=MAX_SLOTS_PER_SEC => Some(ClockMode::Accelerated { slots_per_sec: rate }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClockMode::Manual => "manual",
+            ClockMode::WallClock => "wall_clock",
+            ClockMode::Accelerated { .. } => "accelerated",
+        }
+    }
+
+    /// Slots per second (0 in manual mode)
+    pub fn slots_per_sec(&self) -> f64 {
+        match self {
+            ClockMode::Manual => 0.0,
+            ClockMode::WallClock => 1_000.0 / SLOT_MS as f64,
+            ClockMode::Accelerated { slots_per_sec } => *slots_per_sec as f64,
+        }
+    }
+
+    fn slots_in(&self, elapsed: Duration) -> u64 {
+        let ms = elapsed.as_millis();
+        let slots = match self {
+            ClockMode::Manual => 0,
+            ClockMode::WallClock => ms / SLOT_MS as u128,
+            ClockMode::Accelerated { slots_per_sec } => ms * *slots_per_sec as u128 / 1_000,
+        };
+        u64::try_from(slots).unwrap_or(u64::MAX)
+    }
+}
+
+/// Slot the server stamps on new commands
+#[derive(Clone, Copy, Debug)]
+pub struct SlotClock {
+    mode: ClockMode,
+    /// Slot at `anchor`
+    anchor_slot: u64,
+    anchor: Instant,
+}
+
+impl Default for SlotClock {
+    fn default() -> Self {
+        Self {
+            mode: ClockMode::Manual,
+            anchor_slot: 0,
+            anchor: Instant::now(),
+        }
+    }
+}
+
+impl SlotClock {
+    pub fn mode(&self) -> ClockMode {
+        self.mode
+    }
+
+    /// Current slot, never behind `engine_slot`
+    pub fn now(&self, engine_slot: u64) -> u64 {
+        self.slot_at(engine_slot, Instant::now())
+    }
+
+    /// Slot at instant `at`, never behind `engine_slot`
+    pub fn slot_at(&self, engine_slot: u64, at: Instant) -> u64 {
+        let elapsed = at.saturating_duration_since(self.anchor);
+        self.anchor_slot
+            .saturating_add(self.mode.slots_in(elapsed))
+            .max(engine_slot)
+    }
+
+    /// Switch to `mode` from the current slot on
+    pub fn set_mode(&mut self, mode: ClockMode, engine_slot: u64) {
+        self.set_mode_at(mode, engine_slot, Instant::now());
+    }
+
+    /// `set_mode` as of instant `at`
+    pub fn set_mode_at(&mut self, mode: ClockMode, engine_slot: u64, at: Instant) {
+        self.anchor_slot = self.slot_at(engine_slot, at);
+        self.anchor = at;
+        self.mode = mode;
+    }
+
+    /// Jump `slots` ahead in any mode
+    pub fn advance(&mut self, slots: u64, engine_slot: u64) {
+        self.advance_at(slots, engine_slot, Instant::now());
+    }
+
+    /// `advance` as of instant `at`
+    pub fn advance_at(&mut self, slots: u64, engine_slot: u64, at: Instant) {
+        self.anchor_slot = self.slot_at(engine_slot, at).saturating_add(slots);
+        self.anchor = at;
+    }
+}
@@ -0,0 +1,170 @@
+//! Several independent markets behind one server
+//!
+//! `Instances` holds a default `ServerState` plus named ones created at
+//! runtime through `POST /instances`. Each instance has its own engine,
+//! agent, params, journal and history, behind its own lock, so a trade on
+//! one never waits on another. `Router::dispatch_instances` sends
+//! `/i/{name}/...` to the named instance with the prefix stripped and every
+//! other path to the default one, so the same routing table serves all of
+//! them.
+//!
+//! How an instance is built (engine config, which agent, where its journal
+//! lives) is up to the server: it registers a factory that turns an
+//! `InstanceSpec` into a `ServerState`.
+
+use std::sync::{Arc, RwLock};
+use std::{boxed::Box, format, string::String, string::ToString, vec::Vec};
+
+use crate::clawcolator::OpenClawAgent;
+
+use super::{Request, ServerState};
+
+/// Named instances that can exist at once (besides the default)
+pub const MAX_INSTANCES: usize = 16;
+
+/// Longest instance name
+pub const MAX_INSTANCE_NAME: usize = 32;
+
+/// Path instances are listed on and created at
+pub const INSTANCES_PATH: &str = "/instances";
+
+/// What `POST /instances` asks for
+///
+/// Unset fields keep the server's defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InstanceSpec {
+    pub name: String,
+    /// Registered agent to run (`AgentRegistry` name)
+    pub agent: Option<String>,
+    pub initial_margin_bps: Option<u64>,
+    pub maintenance_margin_bps: Option<u64>,
+    pub trading_fee_bps: Option<u64>,
+    pub warmup_period_slots: Option<u64>,
+}
+
+impl InstanceSpec {
+    /// Spec from a flat JSON body; `name` is required
+    pub fn from_request(request: &Request) -> Option<Self> {
+        let int = |key| request.json_int(key).and_then(|v| u64::try_from(v).ok());
+        Some(Self {
+            name: request.json_str("name")?.to_string(),
+            agent: request.json_str("agent").map(str::to_string),
+            initial_margin_bps: int("initial_margin_bps"),
+            maintenance_margin_bps: int("maintenance_margin_bps"),
+            trading_fee_bps: int("trading_fee_bps"),
+            warmup_period_slots: int("warmup_period_slots"),
+        })
+    }
+}
+
+/// Why an instance was not created
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InstanceError {
+    /// Names are 1-32 ASCII letters, digits, `-` or `_`
+    BadName,
+    /// An instance with this name exists
+    Exists,
+    /// `MAX_INSTANCES` are running
+    Full,
+    /// No factory registered
+    Unsupported,
+    /// The factory rejected the spec
+    Rejected(String),
+}
+
+impl InstanceError {
+    pub fn message(&self) -> String {
+        match self {
+            InstanceError::BadName => format!(
+                "name must be 1-{} letters, digits, '-' or '_'",
+                MAX_INSTANCE_NAME
+            ),
+            InstanceError::Exists => "instance exists".into(),
+            InstanceError::Full => format!("at most {} instances", MAX_INSTANCES),
+            InstanceError::Unsupported => "this server cannot create instances".into(),
+            InstanceError::Rejected(reason) => reason.clone(),
+        }
+    }
+}
+
+type InstanceFactory<A> = Box<dyn Fn(&InstanceSpec) -> Result<ServerState<A>, String> + Send + Sync>;
+
+/// Shared state of one instance
+pub type SharedState<A> = Arc<RwLock<ServerState<A>>>;
+
+/// The default instance and the named ones
+pub struct Instances<A: OpenClawAgent> {
+    default: SharedState<A>,
+    named: RwLock<Vec<(String, SharedState<A>)>>,
+    factory: Option<InstanceFactory<A>>,
+}
+
+impl<A: OpenClawAgent> Instances<A> {
+    pub fn new(default: ServerState<A>) -> Self {
+        Self {
+            default: Arc::new(RwLock::new(default)),
+            named: RwLock::new(Vec::new()),
+            factory: None,
+        }
+    }
+
+    /// Build named instances with `factory`; without one `POST /instances`
+    /// is refused
+    pub fn with_factory<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(&InstanceSpec) -> Result<ServerState<A>, String> + Send + Sync + 'static,
+    {
+        self.factory = Some(Box::new(factory));
+        self
+    }
+
+    /// Instance serving unprefixed paths
+    pub fn default_state(&self) -> &SharedState<A> {
+        &self.default
+    }
+
+    /// Named instance
+    pub fn get(&self, name: &str) -> Option<SharedState<A>> {
+        self.named
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, state)| state.clone())
+    }
+
+    /// Names of the named instances, in creation order
+    pub fn names(&self) -> Vec<String> {
+        let named = self.named.read().unwrap_or_else(|e| e.into_inner());
+        named.iter().map(|(n, _)| n.clone()).collect()
+    }
+
+    /// Build and register the instance `spec` describes
+    pub fn create(&self, spec: &InstanceSpec) -> Result<SharedState<A>, InstanceError> {
+        let name = &spec.name;
+        let valid = !name.is_empty()
+            && name.len() <= MAX_INSTANCE_NAME
+            && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        if !valid {
+            return Err(InstanceError::BadName);
+        }
+        let factory = self.factory.as_ref().ok_or(InstanceError::Unsupported)?;
+        check_room(&self.named.read().unwrap_or_else(|e| e.into_inner()), name)?;
+        // Build outside the lock; another request may take the name meanwhile
+        let state = Arc::new(RwLock::new(factory(spec).map_err(InstanceError::Rejected)?));
+        let mut named = self.named.write().unwrap_or_else(|e| e.into_inner());
+        check_room(&named, name)?;
+        named.push((name.clone(), state.clone()));
+        Ok(state)
+    }
+}
+
+fn check_room<S>(named: &[(String, S)], name: &str) -> Result<(), InstanceError> {
+    if named.iter().any(|(n, _)| n == name) {
+        return Err(InstanceError::Exists);
+    }
+    if named.len() >= MAX_INSTANCES {
+        return Err(InstanceError::Full);
+    }
+    Ok(())
+}
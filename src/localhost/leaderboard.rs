@@ -0,0 +1,216 @@
+//! Trading competition standings
+//!
+//! The leaderboard samples every account's PnL (capital, realized PnL and
+//! the position marked at the last oracle price, less what was deposited)
+//! after each command the server applies, and counts each account's fills
+//! as taker or maker. Over a window of the last `window_slots` slots it
+//! reports PnL, volume and the largest peak-to-trough drop in PnL, so
+//! agents and humans trading the same sandbox can be ranked against each
+//! other. Positive PnL is counted before the haircut and warmup, so a
+//! trader is scored on their trading rather than on how solvent the rest
+//! of the market is.
+
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+use crate::clawcolator::{ClawcolatorEngine, TradeFill};
+use crate::AccountKind;
+
+use super::MAX_HISTORY;
+
+/// One account's standing over the window
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Standing {
+    pub idx: u16,
+
+    /// Account id (the slot may be reused by a later account)
+    pub account_id: u64,
+
+    /// LP account (the agent's side) rather than a user
+    pub lp: bool,
+
+    /// PnL gained over the window
+    pub pnl: i128,
+
+    /// Sum of absolute fill sizes over the window
+    pub volume: u128,
+
+    /// Fills over the window
+    pub trades: u32,
+
+    /// Largest drop in PnL from a prior peak within the window
+    pub max_drawdown: u128,
+}
+
+#[derive(Clone, Debug)]
+struct Track {
+    account_id: u64,
+    lp: bool,
+    deposits: u128,
+    /// `(slot, pnl)` whenever the PnL changed, oldest first
+    samples: VecDeque<(u64, i128)>,
+    /// `(slot, absolute size)` of each fill, oldest first
+    fills: VecDeque<(u64, u128)>,
+}
+
+impl Track {
+    fn new(account_id: u64, lp: bool) -> Self {
+        Self {
+            account_id,
+            lp,
+            deposits: 0,
+            samples: VecDeque::new(),
+            fills: VecDeque::new(),
+        }
+    }
+
+    /// Drop what fell out of a window starting at `start`, keeping the last
+    /// sample before it as the baseline
+    fn prune(&mut self, start: u64) {
+        while self.samples.len() > 1 && self.samples[1].0 < start {
+            self.samples.pop_front();
+        }
+        while self.fills.front().is_some_and(|(slot, _)| *slot < start) {
+            self.fills.pop_front();
+        }
+    }
+
+    fn standing(&self, idx: u16, start: u64) -> Standing {
+        let baseline = self
+            .samples
+            .iter()
+            .rev()
+            .find(|(slot, _)| *slot < start)
+            .or(self.samples.front())
+            .map_or(0, |(_, pnl)| *pnl);
+        let mut peak = baseline;
+        let mut max_drawdown = 0u128;
+        let mut last = baseline;
+        for (_, pnl) in self.samples.iter().filter(|(slot, _)| *slot >= start) {
+            peak = peak.max(*pnl);
+            max_drawdown = max_drawdown.max(peak.saturating_sub(*pnl) as u128);
+            last = *pnl;
+        }
+        let fills = self.fills.iter().filter(|(slot, _)| *slot >= start);
+        Standing {
+            idx,
+            account_id: self.account_id,
+            lp: self.lp,
+            pnl: last.saturating_sub(baseline),
+            volume: fills.clone().fold(0u128, |v, (_, size)| v.saturating_add(*size)),
+            trades: fills.count() as u32,
+            max_drawdown,
+        }
+    }
+}
+
+/// Per-account PnL, volume and drawdown over a sliding window of slots
+#[derive(Clone, Debug)]
+pub struct Leaderboard {
+    window_slots: u64,
+    tracks: Vec<Option<Track>>,
+}
+
+impl Leaderboard {
+    /// Standings over the last `window_slots` slots (u64::MAX for all time)
+    pub fn new(window_slots: u64) -> Self {
+        Self {
+            window_slots: window_slots.max(1),
+            tracks: Vec::new(),
+        }
+    }
+
+    pub fn window_slots(&self) -> u64 {
+        self.window_slots
+    }
+
+    /// Accounts ranked by PnL over the window ending at `now_slot`, best
+    /// first (ties by lower drawdown, then index)
+    pub fn standings(&self, now_slot: u64) -> Vec<Standing> {
+        let start = self.window_start(now_slot);
+        let mut standings: Vec<Standing> = self
+            .tracks
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, track)| Some(track.as_ref()?.standing(idx as u16, start)))
+            .collect();
+        standings.sort_by(|a, b| {
+            b.pnl
+                .cmp(&a.pnl)
+                .then(a.max_drawdown.cmp(&b.max_drawdown))
+                .then(a.idx.cmp(&b.idx))
+        });
+        standings
+    }
+
+    /// Standing of account `idx` over the window ending at `now_slot`
+    pub fn standing(&self, idx: u16, now_slot: u64) -> Option<Standing> {
+        let track = self.tracks.get(idx as usize)?.as_ref()?;
+        Some(track.standing(idx, self.window_start(now_slot)))
+    }
+
+    /// Count `amount` deposited into `idx` as principal rather than PnL
+    pub(super) fn record_deposit(&mut self, engine: &ClawcolatorEngine, idx: u16, amount: u128) {
+        if let Some(track) = self.track(engine, idx) {
+            track.deposits = track.deposits.saturating_add(amount);
+        }
+    }
+
+    /// Count a fill towards both sides' volume
+    pub(super) fn record_fill(&mut self, engine: &ClawcolatorEngine, fill: &TradeFill) {
+        for idx in [fill.user_idx, fill.lp_idx] {
+            if let Some(track) = self.track(engine, idx) {
+                if track.fills.len() == MAX_HISTORY {
+                    track.fills.pop_front();
+                }
+                track.fills.push_back((fill.slot, fill.size.unsigned_abs()));
+            }
+        }
+    }
+
+    /// Sample every live account's PnL at `oracle_price`
+    pub(super) fn sample(&mut self, engine: &ClawcolatorEngine, oracle_price: u64) {
+        let slot = engine.risk_engine().current_slot;
+        let start = self.window_start(slot);
+        for account in engine.account_refs() {
+            let equity = (account.capital() as i128)
+                .saturating_add(account.pnl())
+                .saturating_add(account.unrealized_pnl(oracle_price));
+            let Some(track) = self.track(engine, account.idx()) else {
+                continue;
+            };
+            let pnl = equity.saturating_sub(track.deposits as i128);
+            if track.samples.back().is_none_or(|(_, last)| *last != pnl) {
+                if track.samples.len() == MAX_HISTORY {
+                    track.samples.pop_front();
+                }
+                track.samples.push_back((slot, pnl));
+            }
+            track.prune(start);
+        }
+        // Forget accounts that were closed
+        for (idx, track) in self.tracks.iter_mut().enumerate() {
+            if track.is_some() && !engine.risk_engine().is_used(idx) {
+                *track = None;
+            }
+        }
+    }
+
+    fn window_start(&self, now_slot: u64) -> u64 {
+        now_slot.saturating_sub(self.window_slots - 1)
+    }
+
+    /// Track of live account `idx`, restarted if the slot was reused
+    fn track(&mut self, engine: &ClawcolatorEngine, idx: u16) -> Option<&mut Track> {
+        let account = engine.account_ref(idx)?.account();
+        let i = idx as usize;
+        if self.tracks.len() <= i {
+            self.tracks.resize(i + 1, None);
+        }
+        let track = &mut self.tracks[i];
+        if track.as_ref().is_none_or(|t| t.account_id != account.account_id) {
+            *track = Some(Track::new(account.account_id, account.kind == AccountKind::LP));
+        }
+        track.as_mut()
+    }
+}
@@ -0,0 +1,100 @@
+//! Journal format versions and the steps that upgrade old journals
+//!
+//! A journal's header line names the format it was written in
+//! (`clawcolator-journal v1`). When a crate release changes how commands
+//! are written, it bumps `JOURNAL_VERSION` and registers a step that
+//! rewrites journals of the previous version line by line. `Migrations`
+//! chains the steps from whatever version a journal was written in up to
+//! the current one before parsing it, so a `FileStore` created with
+//! `with_migrations` restores an old deployment without hand-editing its
+//! journal; the next save writes it back in the current format.
+
+use std::{format, io, string::String, string::ToString, vec::Vec};
+
+use super::{Command, Snapshot};
+
+/// Format new journals are written in
+pub const JOURNAL_VERSION: u32 = 1;
+
+/// Header line prefix; the version follows as `v<N>`
+pub(super) const JOURNAL_MAGIC: &str = "clawcolator-journal";
+
+/// Rewrites the command lines of a journal one version up
+pub type MigrationStep = fn(Vec<String>) -> Result<Vec<String>, String>;
+
+/// Registered upgrade steps
+#[derive(Clone, Debug, Default)]
+pub struct Migrations {
+    /// `(from, step)`: upgrades journals of version `from` to `from + 1`
+    steps: Vec<(u32, MigrationStep)>,
+}
+
+impl Migrations {
+    /// No steps: only current journals load
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Upgrade journals of version `from` with `step`
+    pub fn step(mut self, from: u32, step: MigrationStep) -> Self {
+        self.steps.retain(|(v, _)| *v != from);
+        self.steps.push((from, step));
+        self
+    }
+
+    /// Versions a journal can be upgraded from, oldest first
+    pub fn supported(&self) -> Vec<u32> {
+        let mut versions: Vec<u32> = (0..JOURNAL_VERSION)
+            .filter(|v| (*v..JOURNAL_VERSION).all(|from| self.find(from).is_some()))
+            .collect();
+        versions.push(JOURNAL_VERSION);
+        versions
+    }
+
+    /// Parse a journal of any supported version into the current one
+    pub fn upgrade(&self, text: &str) -> io::Result<Snapshot> {
+        let mut lines = text.lines();
+        let version = lines.next().and_then(parse_header).ok_or_else(|| bad("missing journal header".into()))?;
+        if version > JOURNAL_VERSION {
+            return Err(bad(format!(
+                "journal v{} is newer than this build (v{})",
+                version, JOURNAL_VERSION
+            )));
+        }
+        let mut commands: Vec<String> = lines
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect();
+        for from in version..JOURNAL_VERSION {
+            let step = self
+                .find(from)
+                .ok_or_else(|| bad(format!("no migration from journal v{}", from)))?;
+            commands = step(commands).map_err(|e| bad(format!("migrating from v{}: {}", from, e)))?;
+        }
+        let mut parsed = Vec::with_capacity(commands.len());
+        for (n, line) in commands.iter().enumerate() {
+            let command = Command::decode(line)
+                .ok_or_else(|| bad(format!("line {}: cannot parse \"{}\"", n + 2, line)))?;
+            parsed.push(command);
+        }
+        Ok(Snapshot { commands: parsed })
+    }
+
+    fn find(&self, from: u32) -> Option<MigrationStep> {
+        self.steps.iter().find(|(v, _)| *v == from).map(|(_, step)| *step)
+    }
+}
+
+/// Header of the current format
+pub(super) fn header() -> String {
+    format!("{} v{}", JOURNAL_MAGIC, JOURNAL_VERSION)
+}
+
+fn parse_header(line: &str) -> Option<u32> {
+    let version = line.strip_prefix(JOURNAL_MAGIC)?.strip_prefix(" v")?;
+    version.parse().ok()
+}
+
+fn bad(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
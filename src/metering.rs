@@ -0,0 +1,238 @@
+//! Cost accounting per engine operation
+//!
+//! Compute units on BPF are spent mostly in three places: passes over the
+//! account slab, fixed-point arithmetic, and loops over queues and books.
+//! The engine reports each of them here (`scan`, `math`, `iterate`) and
+//! attributes the counts to the outermost public operation running
+//! (`enter`). Counts are only collected with the `metering` feature, into
+//! per-thread counters; without it every hook is an empty inline function
+//! and `report` returns zeros, so release and on-chain builds are
+//! unaffected.
+//!
+//! Typical use is a benchmark or test that runs a fixed scenario, takes a
+//! `report`, and compares it to the one recorded for the previous release
+//! with `CostReport::regressions`.
+
+/// Public operations costs are attributed to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeteredOp {
+    /// Agent-driven trade (direct, routed, revealed or batch fill)
+    Trade,
+    /// Keeper crank
+    Crank,
+    /// Batch auction clearing
+    ClearBatch,
+    /// Market params update from the agent
+    UpdateMarketParams,
+    /// Anomaly check
+    CheckAnomalies,
+    /// Withdrawal request
+    Withdrawal,
+    /// Anything outside the operations above
+    Other,
+}
+
+/// Number of `MeteredOp` variants
+pub const METERED_OP_COUNT: usize = 7;
+
+impl MeteredOp {
+    /// Every operation, in report order
+    pub const ALL: [MeteredOp; METERED_OP_COUNT] = [
+        MeteredOp::Trade,
+        MeteredOp::Crank,
+        MeteredOp::ClearBatch,
+        MeteredOp::UpdateMarketParams,
+        MeteredOp::CheckAnomalies,
+        MeteredOp::Withdrawal,
+        MeteredOp::Other,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Counters of one operation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpCost {
+    /// Times the operation ran
+    pub calls: u64,
+
+    /// Account slots examined
+    pub account_scans: u64,
+
+    /// Fixed-point helper calls (mul/div, bps, notional)
+    pub math_ops: u64,
+
+    /// Loop iterations over queues, books and orders
+    pub iterations: u64,
+}
+
+impl OpCost {
+    /// Scans, math ops and iterations per call (rounded up)
+    pub fn per_call(&self) -> OpCost {
+        let calls = self.calls.max(1);
+        OpCost {
+            calls: 1,
+            account_scans: self.account_scans.div_ceil(calls),
+            math_ops: self.math_ops.div_ceil(calls),
+            iterations: self.iterations.div_ceil(calls),
+        }
+    }
+}
+
+/// Counters of every operation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CostReport {
+    ops: [OpCost; METERED_OP_COUNT],
+}
+
+impl CostReport {
+    pub const fn new() -> Self {
+        Self {
+            ops: [OpCost {
+                calls: 0,
+                account_scans: 0,
+                math_ops: 0,
+                iterations: 0,
+            }; METERED_OP_COUNT],
+        }
+    }
+
+    /// Counters of `op`
+    pub fn get(&self, op: MeteredOp) -> &OpCost {
+        &self.ops[op.index()]
+    }
+
+    /// Operations with their counters, in report order
+    pub fn iter(&self) -> impl Iterator<Item = (MeteredOp, &OpCost)> {
+        MeteredOp::ALL.into_iter().zip(self.ops.iter())
+    }
+
+    /// Operations whose per-call scans, math ops or iterations grew by more
+    /// than `tolerance_bps` over `baseline`
+    pub fn regressions<'a>(
+        &'a self,
+        baseline: &'a CostReport,
+        tolerance_bps: u64,
+    ) -> impl Iterator<Item = MeteredOp> + 'a {
+        let exceeds = move |now: u64, before: u64| {
+            let allowed = (before as u128) * (10_000 + tolerance_bps as u128) / 10_000;
+            now as u128 > allowed
+        };
+        self.iter().filter_map(move |(op, cost)| {
+            let (now, before) = (cost.per_call(), baseline.get(op).per_call());
+            let regressed = cost.calls > 0
+                && (exceeds(now.account_scans, before.account_scans)
+                    || exceeds(now.math_ops, before.math_ops)
+                    || exceeds(now.iterations, before.iterations));
+            regressed.then_some(op)
+        })
+    }
+
+    #[cfg_attr(not(feature = "metering"), allow(dead_code))]
+    fn op_mut(&mut self, op: MeteredOp) -> &mut OpCost {
+        &mut self.ops[op.index()]
+    }
+}
+
+/// Whether counts are collected in this build
+pub const ENABLED: bool = cfg!(feature = "metering");
+
+#[cfg(feature = "metering")]
+mod imp {
+    use super::*;
+    use core::cell::{Cell, RefCell};
+
+    std::thread_local! {
+        static CURRENT: Cell<Option<MeteredOp>> = const { Cell::new(None) };
+        static REPORT: RefCell<CostReport> = const { RefCell::new(CostReport::new()) };
+    }
+
+    fn record(f: impl FnOnce(&mut OpCost)) {
+        let op = CURRENT.with(Cell::get).unwrap_or(MeteredOp::Other);
+        REPORT.with(|report| f(report.borrow_mut().op_mut(op)));
+    }
+
+    /// Attribution of one operation; ends when dropped
+    pub(crate) struct OpGuard {
+        outermost: bool,
+    }
+
+    impl Drop for OpGuard {
+        fn drop(&mut self) {
+            if self.outermost {
+                CURRENT.with(|current| current.set(None));
+            }
+        }
+    }
+
+    /// Start attributing costs to `op`, unless an outer operation runs
+    pub(crate) fn enter(op: MeteredOp) -> OpGuard {
+        let outermost = CURRENT.with(|current| {
+            if current.get().is_some() {
+                return false;
+            }
+            current.set(Some(op));
+            true
+        });
+        if outermost {
+            record(|cost| cost.calls += 1);
+        }
+        OpGuard { outermost }
+    }
+
+    pub(crate) fn scan(slots: u64) {
+        record(|cost| cost.account_scans = cost.account_scans.saturating_add(slots));
+    }
+
+    pub(crate) fn math(ops: u64) {
+        record(|cost| cost.math_ops = cost.math_ops.saturating_add(ops));
+    }
+
+    pub(crate) fn iterate(iterations: u64) {
+        record(|cost| cost.iterations = cost.iterations.saturating_add(iterations));
+    }
+
+    /// Counters collected on this thread since the last `reset`
+    pub fn report() -> CostReport {
+        REPORT.with(|report| *report.borrow())
+    }
+
+    /// Zero this thread's counters
+    pub fn reset() {
+        REPORT.with(|report| *report.borrow_mut() = CostReport::new());
+    }
+}
+
+#[cfg(not(feature = "metering"))]
+mod imp {
+    use super::*;
+
+    /// No-op stand-in when the `metering` feature is off
+    pub(crate) struct OpGuard;
+
+    #[inline(always)]
+    pub(crate) fn enter(_op: MeteredOp) -> OpGuard {
+        OpGuard
+    }
+
+    #[inline(always)]
+    pub(crate) fn scan(_slots: u64) {}
+
+    #[inline(always)]
+    pub(crate) fn math(_ops: u64) {}
+
+    #[inline(always)]
+    pub(crate) fn iterate(_iterations: u64) {}
+
+    /// Always zero without the `metering` feature
+    pub fn report() -> CostReport {
+        CostReport::new()
+    }
+
+    pub fn reset() {}
+}
+
+pub use imp::{report, reset};
+pub(crate) use imp::{enter, iterate, math, scan};
@@ -16,11 +16,28 @@
 //! suitable for a single Solana account.
 
 #![no_std]
-#![forbid(unsafe_code)]
+// The C ABI needs unsafe at its boundary; everything else stays unsafe-free
+#![cfg_attr(not(feature = "ffi"), forbid(unsafe_code))]
+#![cfg_attr(feature = "ffi", deny(unsafe_code))]
 
 #[cfg(kani)]
 extern crate kani;
 
+// Off-chain analytics and bindings link std; the engine itself never does
+#[cfg(any(
+    feature = "analysis",
+    feature = "wasm",
+    feature = "python",
+    feature = "ffi",
+    feature = "config",
+    feature = "localhost",
+    feature = "tracing",
+    feature = "metering",
+    feature = "indexer",
+    feature = "rl"
+))]
+extern crate std;
+
 // ============================================================================
 // Constants
 // ============================================================================
@@ -34,7 +51,7 @@ pub const MAX_ACCOUNTS: usize = 4; // Small for fast formal verification (1 bitm
 pub const MAX_ACCOUNTS: usize = 64; // Small for tests
 
 #[cfg(all(not(kani), not(feature = "test")))]
-pub const MAX_ACCOUNTS: usize = 4096; // Production
+pub const MAX_ACCOUNTS: usize = layout::capacity(option_env!("PERCOLATOR_MAX_ACCOUNTS"), 4096); // Production
 
 // Derived constants - all use size_of, no hardcoded values
 pub const BITMAP_WORDS: usize = (MAX_ACCOUNTS + 63) / 64;
@@ -58,6 +75,15 @@ pub const LIQ_BUDGET_PER_CRANK: u16 = 120;
 /// Hard CU bound in force-realize mode. Liquidations are skipped when active.
 pub const FORCE_REALIZE_BUDGET_PER_CRANK: u16 = 32;
 
+/// Whether mutating entrypoints cross-check the incremental aggregates
+/// against a full recomputation (debug builds; skipped under Kani)
+const CHECK_AGGREGATES: bool = cfg!(all(debug_assertions, not(kani)));
+
+/// Number of settlement stripes under dirty tracking. Each full sweep also
+/// settles the clean accounts of one stripe (`idx % SETTLE_STRIPES`), so
+/// every account is settled at least once per SETTLE_STRIPES sweeps.
+pub const SETTLE_STRIPES: u16 = 8;
+
 /// Maximum oracle price (prevents overflow in mark_pnl calculations)
 /// 10^15 allows prices up to $1B with 6 decimal places
 pub const MAX_ORACLE_PRICE: u64 = 1_000_000_000_000_000;
@@ -67,18 +93,35 @@ pub const MAX_ORACLE_PRICE: u64 = 1_000_000_000_000_000;
 /// Combined with MAX_ORACLE_PRICE, guarantees mark_pnl multiply won't overflow i128
 pub const MAX_POSITION_ABS: u128 = 100_000_000_000_000_000_000;
 
+/// Write-offs the engine keeps until the wrapper collects them; beyond
+/// that only their total is kept (`write_offs_overflow`)
+pub const WRITE_OFF_QUEUE_LEN: usize = 16;
+
 // ============================================================================
 // BPF-Safe 128-bit Types (see src/i128.rs)
 // ============================================================================
 pub mod i128;
 pub use i128::{I128, U128};
 
+// ============================================================================
+// Capacity layout (see src/layout.rs)
+// ============================================================================
+pub mod layout;
+
+// ============================================================================
+// Cost accounting (see src/metering.rs)
+// ============================================================================
+pub mod metering;
+
 // ============================================================================
 // Clawcolator: Agent-First Fork
 // ============================================================================
 #[cfg(feature = "clawcolator")]
 pub mod clawcolator;
 
+#[cfg(feature = "localhost")]
+pub mod localhost;
+
 // ============================================================================
 // Core Data Structures
 // ============================================================================
@@ -216,6 +259,23 @@ pub struct InsuranceFund {
     pub fee_revenue: U128,
 }
 
+/// Negative PnL written off an account whose capital ran out (spec §6.1)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WriteOff {
+    /// Account index
+    pub idx: u16,
+
+    /// Account id at the time (the slot may be freed right after)
+    pub account_id: u64,
+
+    /// Slot of the write-off
+    pub slot: u64,
+
+    /// Loss written off
+    pub amount: u128,
+}
+
 /// Outcome from oracle_close_position_core helper
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ClosedOutcome {
@@ -334,6 +394,12 @@ pub struct RiskEngine {
     /// This measures total risk exposure in the system.
     pub total_open_interest: U128,
 
+    /// Sum of long positions: Σ max(position_i, 0)
+    pub long_open_interest: U128,
+
+    /// Sum of short positions: Σ max(-position_i, 0)
+    pub short_open_interest: U128,
+
     // ========================================
     // O(1) Aggregates (spec §2.2, §4)
     // ========================================
@@ -345,6 +411,10 @@ pub struct RiskEngine {
     /// Maintained incrementally via set_pnl() helper.
     pub pnl_pos_tot: U128,
 
+    /// Capital of accounts with an open position: Σ C_i where position_i != 0
+    /// Maintained by set_capital() and update_position_aggregates().
+    pub active_capital: U128,
+
     // ========================================
     // Crank Cursors (bounded scan support)
     // ========================================
@@ -393,6 +463,53 @@ pub struct RiskEngine {
     /// In-progress max abs for current sweep (reset at sweep start, committed at completion)
     pub lp_max_abs_sweep: U128,
 
+    // ========================================
+    // Dirty Tracking (sparse crank settlement)
+    // ========================================
+    /// When set, the crank settles fees, funding and warmup only for dirty
+    /// accounts and the current stripe (see set_dirty_tracking)
+    pub dirty_tracking: bool,
+
+    /// Accounts touched since the crank last settled them (same layout as `used`)
+    pub dirty: [u64; BITMAP_WORDS],
+
+    /// Stripe whose clean accounts the current sweep settles (< SETTLE_STRIPES)
+    pub settle_stripe: u16,
+
+    // ========================================
+    // Funding Ledger
+    // ========================================
+    /// Funding settled into each account's PnL so far (+ paid, - received).
+    /// Charged in settle_account_funding; cleared when the slot is freed.
+    pub funding_settled: [I128; MAX_ACCOUNTS],
+
+    // ========================================
+    // Liquidation Holds (bankruptcy auctions)
+    // ========================================
+    /// When set, the crank holds bankrupt accounts for auction instead of
+    /// closing them at the oracle (see set_bankruptcy_auctions)
+    pub bankruptcy_auctions: bool,
+
+    /// Accounts whose position is under auction; liquidation, force-close
+    /// and compaction skip them (same layout as `used`)
+    pub liquidation_held: [u64; BITMAP_WORDS],
+
+    /// When set, the crank sweep settles accounts but leaves liquidating
+    /// those below maintenance to the caller (see set_deferred_liquidations)
+    pub deferred_liquidations: bool,
+
+    // ========================================
+    // Write-offs (bankruptcy accounting)
+    // ========================================
+    /// Write-offs not yet collected by the wrapper (see take_write_offs)
+    pub write_offs: [WriteOff; WRITE_OFF_QUEUE_LEN],
+
+    /// Entries used in `write_offs`
+    pub write_offs_len: u64,
+
+    /// Total of write-offs that did not fit in the queue
+    pub write_offs_overflow: U128,
+
     // ========================================
     // Slab Management
     // ========================================
@@ -478,12 +595,73 @@ pub struct CrankOutcome {
     pub force_realize_closed: u16,
     /// Number of force-realize errors during this crank
     pub force_realize_errors: u16,
+    /// Number of visited accounts whose fees, funding and warmup were settled
+    /// (all of them unless dirty tracking is on)
+    pub accounts_settled: u16,
     /// Index where this crank stopped (next crank continues from here)
     pub last_cursor: u16,
     /// Whether this crank completed a full sweep of all accounts
     pub sweep_complete: bool,
 }
 
+/// Incrementally maintained aggregates (see RiskEngine::check_aggregates)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Aggregates {
+    /// Σ capital
+    pub c_tot: u128,
+    /// Σ max(pnl, 0)
+    pub pnl_pos_tot: u128,
+    /// Σ |position|
+    pub total_open_interest: u128,
+    /// Σ max(position, 0)
+    pub long_open_interest: u128,
+    /// Σ max(-position, 0)
+    pub short_open_interest: u128,
+    /// Σ capital of accounts with a position
+    pub active_capital: u128,
+}
+
+impl Aggregates {
+    /// Values in declaration order
+    pub fn fields(&self) -> [u128; 6] {
+        [
+            self.c_tot,
+            self.pnl_pos_tot,
+            self.total_open_interest,
+            self.long_open_interest,
+            self.short_open_interest,
+            self.active_capital,
+        ]
+    }
+
+    /// Field-wise wrapping difference (drift of stored values from recomputed)
+    pub fn wrapping_sub(&self, other: &Aggregates) -> Aggregates {
+        Aggregates {
+            c_tot: self.c_tot.wrapping_sub(other.c_tot),
+            pnl_pos_tot: self.pnl_pos_tot.wrapping_sub(other.pnl_pos_tot),
+            total_open_interest: self.total_open_interest.wrapping_sub(other.total_open_interest),
+            long_open_interest: self.long_open_interest.wrapping_sub(other.long_open_interest),
+            short_open_interest: self.short_open_interest.wrapping_sub(other.short_open_interest),
+            active_capital: self.active_capital.wrapping_sub(other.active_capital),
+        }
+    }
+}
+
+/// Account slab occupancy (see RiskEngine::occupancy)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Occupancy {
+    /// Slots holding an account
+    pub used: u16,
+    /// Accounts that can still be opened (bounded by params.max_accounts and the slab)
+    pub available: u16,
+    /// Slab size (MAX_ACCOUNTS)
+    pub capacity: u16,
+    /// One past the highest used slot (0 when empty)
+    pub high_water: u16,
+    /// Free slots below high_water (what compact_accounts can reclaim)
+    pub holes: u16,
+}
+
 // ============================================================================
 // Math Helpers (Saturating Arithmetic for Safety)
 // ============================================================================
@@ -658,8 +836,11 @@ impl RiskEngine {
             last_crank_slot: 0,
             max_crank_staleness_slots: params.max_crank_staleness_slots,
             total_open_interest: U128::ZERO,
+            long_open_interest: U128::ZERO,
+            short_open_interest: U128::ZERO,
             c_tot: U128::ZERO,
             pnl_pos_tot: U128::ZERO,
+            active_capital: U128::ZERO,
             liq_cursor: 0,
             gc_cursor: 0,
             last_full_sweep_start_slot: 0,
@@ -672,6 +853,21 @@ impl RiskEngine {
             lp_sum_abs: U128::ZERO,
             lp_max_abs: U128::ZERO,
             lp_max_abs_sweep: U128::ZERO,
+            dirty_tracking: false,
+            dirty: [0; BITMAP_WORDS],
+            settle_stripe: 0,
+            funding_settled: [I128::ZERO; MAX_ACCOUNTS],
+            bankruptcy_auctions: false,
+            liquidation_held: [0; BITMAP_WORDS],
+            deferred_liquidations: false,
+            write_offs: [WriteOff {
+                idx: 0,
+                account_id: 0,
+                slot: 0,
+                amount: 0,
+            }; WRITE_OFF_QUEUE_LEN],
+            write_offs_len: 0,
+            write_offs_overflow: U128::ZERO,
             used: [0; BITMAP_WORDS],
             num_used_accounts: 0,
             next_account_id: 0,
@@ -726,6 +922,122 @@ impl RiskEngine {
         ((self.used[w] >> b) & 1) == 1
     }
 
+    /// Whether the account was touched since the crank last settled it
+    pub fn is_dirty(&self, idx: usize) -> bool {
+        if idx >= MAX_ACCOUNTS {
+            return false;
+        }
+        ((self.dirty[idx >> 6] >> (idx & 63)) & 1) == 1
+    }
+
+    fn mark_dirty(&mut self, idx: usize) {
+        self.dirty[idx >> 6] |= 1u64 << (idx & 63);
+    }
+
+    fn clear_dirty(&mut self, idx: usize) {
+        self.dirty[idx >> 6] &= !(1u64 << (idx & 63));
+    }
+
+    /// Enable or disable dirty tracking in the crank.
+    ///
+    /// With tracking on, a crank visiting an account settles its maintenance
+    /// fees, funding and warmup only if the account was touched since its
+    /// last settlement or falls in the sweep's stripe. Clean accounts still
+    /// get the liquidation and force-realize checks. All three accrue lazily
+    /// (fee slot, funding index, warmup slope), so a deferred settlement
+    /// catches up on the next visit. Enabling marks every used account dirty
+    /// so the first sweep settles everything.
+    pub fn set_dirty_tracking(&mut self, enabled: bool) {
+        self.dirty_tracking = enabled;
+        if enabled {
+            self.dirty = self.used;
+        }
+    }
+
+    /// Whether `idx` is held for a liquidation auction
+    pub fn is_liquidation_held(&self, idx: usize) -> bool {
+        if idx >= MAX_ACCOUNTS {
+            return false;
+        }
+        ((self.liquidation_held[idx >> 6] >> (idx & 63)) & 1) == 1
+    }
+
+    /// Hold or release `idx` for a liquidation auction. A held account is
+    /// left alone by `liquidate_at_oracle` and every crank close path until
+    /// released.
+    pub fn set_liquidation_held(&mut self, idx: usize, held: bool) {
+        if idx >= MAX_ACCOUNTS {
+            return;
+        }
+        if held {
+            self.liquidation_held[idx >> 6] |= 1u64 << (idx & 63);
+        } else {
+            self.liquidation_held[idx >> 6] &= !(1u64 << (idx & 63));
+        }
+    }
+
+    /// Enable or disable bankruptcy auctions in the crank.
+    ///
+    /// With auctions on, a crank that finds an account with a position and
+    /// zero equity at the oracle holds it (`is_liquidation_held`) instead of
+    /// closing it, for the caller to auction off. Solvent accounts below
+    /// maintenance are still liquidated at the oracle.
+    pub fn set_bankruptcy_auctions(&mut self, enabled: bool) {
+        self.bankruptcy_auctions = enabled;
+    }
+
+    /// Leave liquidations to the caller instead of the crank sweep.
+    ///
+    /// With this set, the sweep still settles fees, funding and mark as a
+    /// liquidation would, holds bankrupt accounts for auction and
+    /// force-closes dust positions, but closes nothing below maintenance
+    /// (zero equity included): a caller that tracks at-risk accounts
+    /// itself liquidates them instead.
+    pub fn set_deferred_liquidations(&mut self, deferred: bool) {
+        self.deferred_liquidations = deferred;
+    }
+
+    /// Zero `idx`'s negative PnL, queueing the amount as a write-off
+    fn write_off_negative_pnl(&mut self, idx: usize) {
+        let pnl = self.accounts[idx].pnl.get();
+        if pnl >= 0 {
+            return;
+        }
+        self.set_pnl(idx, 0);
+        let entry = WriteOff {
+            idx: idx as u16,
+            account_id: self.accounts[idx].account_id,
+            slot: self.current_slot,
+            amount: neg_i128_to_u128(pnl),
+        };
+        match self.write_offs.get_mut(self.write_offs_len as usize) {
+            Some(slot) => {
+                *slot = entry;
+                self.write_offs_len += 1;
+            }
+            None => self.write_offs_overflow = self.write_offs_overflow.saturating_add_u128(U128::new(entry.amount)),
+        }
+    }
+
+    /// Hand the queued write-offs to `f` and clear the queue; returns the
+    /// total of those that overflowed it
+    pub fn take_write_offs(&mut self, mut f: impl FnMut(WriteOff)) -> u128 {
+        for entry in &self.write_offs[..self.write_offs_len as usize] {
+            f(*entry);
+        }
+        self.write_offs_len = 0;
+        let overflow = self.write_offs_overflow.get();
+        self.write_offs_overflow = U128::ZERO;
+        overflow
+    }
+
+    /// Whether the crank settles fees, funding and warmup for `idx` this sweep
+    fn crank_settles(&self, idx: usize) -> bool {
+        !self.dirty_tracking
+            || self.is_dirty(idx)
+            || idx % SETTLE_STRIPES as usize == self.settle_stripe as usize
+    }
+
     fn set_used(&mut self, idx: usize) {
         let w = idx >> 6;
         let b = idx & 63;
@@ -748,6 +1060,7 @@ impl RiskEngine {
                 if idx >= MAX_ACCOUNTS {
                     continue; // Guard against stray high bits in bitmap
                 }
+                metering::scan(1);
                 f(idx, &mut self.accounts[idx]);
             }
         }
@@ -763,6 +1076,7 @@ impl RiskEngine {
                 if idx >= MAX_ACCOUNTS {
                     continue; // Guard against stray high bits in bitmap
                 }
+                metering::scan(1);
                 f(idx, &self.accounts[idx]);
             }
         }
@@ -786,6 +1100,7 @@ impl RiskEngine {
                 .saturating_sub(old_pos),
         );
         self.accounts[idx].pnl = I128::new(new_pnl);
+        self.mark_dirty(idx);
     }
 
     /// Helper: set account capital and maintain c_tot aggregate (spec §4.1).
@@ -797,22 +1112,139 @@ impl RiskEngine {
         } else {
             self.c_tot = U128::new(self.c_tot.get().saturating_sub(old - new_capital));
         }
+        if !self.accounts[idx].position_size.is_zero() {
+            self.active_capital = U128::new(
+                self.active_capital
+                    .get()
+                    .saturating_add(new_capital)
+                    .saturating_sub(old),
+            );
+        }
         self.accounts[idx].capital = U128::new(new_capital);
+        self.mark_dirty(idx);
     }
 
-    /// Recompute c_tot and pnl_pos_tot from account data. For test use after direct state mutation.
-    pub fn recompute_aggregates(&mut self) {
-        let mut c_tot = 0u128;
-        let mut pnl_pos_tot = 0u128;
+    /// Helper: move the position aggregates (total/long/short open interest,
+    /// active capital) from an account's old position and capital to its new
+    /// ones. Every path that changes position_size MUST call this; the caller
+    /// assigns the account fields itself.
+    #[inline]
+    fn update_position_aggregates(
+        &mut self,
+        old_pos: i128,
+        new_pos: i128,
+        old_capital: u128,
+        new_capital: u128,
+    ) {
+        let shift = |total: U128, old: u128, new: u128| {
+            U128::new(total.get().saturating_add(new).saturating_sub(old))
+        };
+        let long = |pos: i128| pos.max(0) as u128;
+        let short = |pos: i128| saturating_abs_i128(pos.min(0)) as u128;
+        let active = |pos: i128, capital: u128| if pos != 0 { capital } else { 0 };
+        self.total_open_interest = shift(
+            self.total_open_interest,
+            saturating_abs_i128(old_pos) as u128,
+            saturating_abs_i128(new_pos) as u128,
+        );
+        self.long_open_interest = shift(self.long_open_interest, long(old_pos), long(new_pos));
+        self.short_open_interest = shift(self.short_open_interest, short(old_pos), short(new_pos));
+        self.active_capital = shift(
+            self.active_capital,
+            active(old_pos, old_capital),
+            active(new_pos, new_capital),
+        );
+    }
+
+    /// Recompute every incrementally maintained aggregate from account data.
+    /// O(MAX_ACCOUNTS). Use `check_aggregates` to compare without writing.
+    pub fn compute_aggregates(&self) -> Aggregates {
+        let mut totals = Aggregates::default();
         self.for_each_used(|_idx, account| {
-            c_tot = c_tot.saturating_add(account.capital.get());
+            let capital = account.capital.get();
             let pnl = account.pnl.get();
+            let pos = account.position_size.get();
+            totals.c_tot = totals.c_tot.saturating_add(capital);
             if pnl > 0 {
-                pnl_pos_tot = pnl_pos_tot.saturating_add(pnl as u128);
+                totals.pnl_pos_tot = totals.pnl_pos_tot.saturating_add(pnl as u128);
+            }
+            let abs_pos = saturating_abs_i128(pos) as u128;
+            totals.total_open_interest = totals.total_open_interest.saturating_add(abs_pos);
+            if pos > 0 {
+                totals.long_open_interest = totals.long_open_interest.saturating_add(abs_pos);
+            } else if pos < 0 {
+                totals.short_open_interest = totals.short_open_interest.saturating_add(abs_pos);
+            }
+            if pos != 0 {
+                totals.active_capital = totals.active_capital.saturating_add(capital);
             }
         });
-        self.c_tot = U128::new(c_tot);
-        self.pnl_pos_tot = U128::new(pnl_pos_tot);
+        totals
+    }
+
+    /// Incrementally maintained aggregates as currently stored
+    pub fn aggregates(&self) -> Aggregates {
+        Aggregates {
+            c_tot: self.c_tot.get(),
+            pnl_pos_tot: self.pnl_pos_tot.get(),
+            total_open_interest: self.total_open_interest.get(),
+            long_open_interest: self.long_open_interest.get(),
+            short_open_interest: self.short_open_interest.get(),
+            active_capital: self.active_capital.get(),
+        }
+    }
+
+    /// Cross-check the stored aggregates against a full recomputation.
+    /// Returns the recomputed values on mismatch. O(MAX_ACCOUNTS).
+    pub fn check_aggregates(&self) -> core::result::Result<(), Aggregates> {
+        let expected = self.compute_aggregates();
+        if self.aggregates() == expected {
+            Ok(())
+        } else {
+            Err(expected)
+        }
+    }
+
+    /// Debug builds: aggregates as they stand at the start of an entrypoint
+    /// (see end_aggregate_check)
+    #[inline]
+    fn begin_aggregate_check(&self) -> Option<Aggregates> {
+        if !CHECK_AGGREGATES {
+            return None;
+        }
+        Some(self.aggregates().wrapping_sub(&self.compute_aggregates()))
+    }
+
+    /// Debug builds: panic if an aggregate that matched its recomputation at
+    /// the start of the entrypoint no longer does. Aggregates already off at
+    /// entry (state seeded by direct field writes in tests) are skipped.
+    #[inline]
+    fn end_aggregate_check(&self, drift: Option<Aggregates>) {
+        let Some(drift) = drift else {
+            return;
+        };
+        let stored = self.aggregates();
+        let expected = self.compute_aggregates();
+        let fields = stored.fields().into_iter().zip(expected.fields()).zip(drift.fields());
+        for ((stored_value, expected_value), before) in fields {
+            assert!(
+                before != 0 || stored_value == expected_value,
+                "aggregate drift: stored {:?}, recomputed {:?}",
+                stored,
+                expected
+            );
+        }
+    }
+
+    /// Recompute all aggregates from account data. For test use after direct state mutation.
+    pub fn recompute_aggregates(&mut self) {
+        let totals = self.compute_aggregates();
+        self.c_tot = U128::new(totals.c_tot);
+        self.pnl_pos_tot = U128::new(totals.pnl_pos_tot);
+        self.total_open_interest = U128::new(totals.total_open_interest);
+        self.long_open_interest = U128::new(totals.long_open_interest);
+        self.short_open_interest = U128::new(totals.short_open_interest);
+        self.active_capital = U128::new(totals.active_capital);
     }
 
     /// Compute haircut ratio (h_num, h_den) per spec §3.2.
@@ -877,11 +1309,92 @@ impl RiskEngine {
         let idx = self.free_head;
         self.free_head = self.next_free[idx as usize];
         self.set_used(idx as usize);
+        self.mark_dirty(idx as usize);
         // Increment O(1) counter atomically (fixes H2: TOCTOU fee bypass)
         self.num_used_accounts = self.num_used_accounts.saturating_add(1);
         Ok(idx)
     }
 
+    /// Slab occupancy: used and available accounts, high-water mark, holes.
+    /// O(BITMAP_WORDS).
+    pub fn occupancy(&self) -> Occupancy {
+        let high_water = (0..BITMAP_WORDS)
+            .rev()
+            .find(|&w| self.used[w] != 0)
+            .map_or(0, |w| w * 64 + 64 - self.used[w].leading_zeros() as usize);
+        let limit = (self.params.max_accounts as usize).min(MAX_ACCOUNTS);
+        let used = self.num_used_accounts;
+        Occupancy {
+            used,
+            available: limit.saturating_sub(used as usize) as u16,
+            capacity: MAX_ACCOUNTS as u16,
+            high_water: high_water as u16,
+            holes: (high_water as u16).saturating_sub(used),
+        }
+    }
+
+    /// Rebuild the freelist in ascending slot order, so new accounts fill
+    /// the lowest free slots first and the occupied region stays dense
+    /// under churn (closed slots are otherwise reused LIFO). O(MAX_ACCOUNTS).
+    pub fn rebuild_free_list(&mut self) {
+        let mut head = u16::MAX;
+        for idx in (0..MAX_ACCOUNTS).rev() {
+            if !self.is_used(idx) {
+                self.next_free[idx] = head;
+                head = idx as u16;
+            }
+        }
+        self.free_head = head;
+    }
+
+    /// Compaction pass: move up to `max_moves` accounts from the highest used
+    /// slots into the lowest free ones, then rebuild the freelist.
+    ///
+    /// Every move is reported to `on_move(from, to)`; callers keeping state
+    /// keyed by account index (owners off-chain, order books, wrappers) must
+    /// remap it there. LP accounts never move. The in-progress crank sweep
+    /// restarts so moved accounts are not skipped. Returns the number of moves.
+    pub fn compact_accounts(&mut self, max_moves: u16, mut on_move: impl FnMut(u16, u16)) -> u16 {
+        let mut moves = 0u16;
+        let mut lo = 0usize;
+        let mut hi = MAX_ACCOUNTS;
+        while moves < max_moves {
+            while lo < MAX_ACCOUNTS && self.is_used(lo) {
+                lo += 1;
+            }
+            while hi > lo
+                && (!self.is_used(hi - 1)
+                    || self.accounts[hi - 1].is_lp()
+                    || self.is_liquidation_held(hi - 1))
+            {
+                hi -= 1;
+            }
+            if hi <= lo + 1 {
+                break;
+            }
+            let from = hi - 1;
+            let dirty = self.is_dirty(from);
+            self.accounts[lo] = self.accounts[from];
+            self.accounts[from] = empty_account();
+            self.funding_settled[lo] = self.funding_settled[from];
+            self.funding_settled[from] = I128::ZERO;
+            self.set_used(lo);
+            self.clear_used(from);
+            self.clear_dirty(from);
+            if dirty {
+                self.mark_dirty(lo);
+            }
+            on_move(from as u16, lo as u16);
+            moves += 1;
+            hi = from;
+        }
+        if moves > 0 {
+            self.rebuild_free_list();
+            self.crank_cursor = self.sweep_start_idx;
+        }
+        moves
+    }
+
     /// Count used accounts
     fn count_used(&self) -> u64 {
         let mut count = 0u64;
@@ -1336,7 +1849,10 @@ impl RiskEngine {
     /// Caller must ensure the account is safe to free (no capital, no positive pnl, etc).
     fn free_slot(&mut self, idx: u16) {
         self.accounts[idx as usize] = empty_account();
+        self.funding_settled[idx as usize] = I128::ZERO;
+        self.set_liquidation_held(idx as usize, false);
         self.clear_used(idx as usize);
+        self.clear_dirty(idx as usize);
         self.next_free[idx as usize] = self.free_head;
         self.free_head = idx;
         self.num_used_accounts = self.num_used_accounts.saturating_sub(1);
@@ -1386,7 +1902,9 @@ impl RiskEngine {
             }
 
             // Best-effort fee settle so accounts with tiny capital get drained in THIS sweep.
-            let _ = self.settle_maintenance_fee_best_effort_for_crank(idx as u16, self.current_slot);
+            if self.crank_settles(idx) {
+                let _ = self.settle_maintenance_fee_best_effort_for_crank(idx as u16, self.current_slot);
+            }
 
             // Dust predicate: must have zero position, capital, reserved, and non-positive pnl
             {
@@ -1412,9 +1930,7 @@ impl RiskEngine {
             }
 
             // Write off negative pnl (spec §6.1: unpayable loss just reduces Residual)
-            if self.accounts[idx].pnl.is_negative() {
-                self.set_pnl(idx, 0);
-            }
+            self.write_off_negative_pnl(idx);
 
             // Queue for freeing
             to_free[num_to_free] = idx as u16;
@@ -1495,12 +2011,36 @@ impl RiskEngine {
         oracle_price: u64,
         funding_rate_bps_per_slot: i64,
         allow_panic: bool,
+    ) -> Result<CrankOutcome> {
+        self.keeper_crank_with(
+            caller_idx,
+            now_slot,
+            oracle_price,
+            funding_rate_bps_per_slot,
+            allow_panic,
+            |_| {},
+        )
+    }
+
+    /// `keeper_crank`, reporting every account the sweep settled, liquidated
+    /// or closed to `on_changed(idx)`. Accounts the sweep only visited are not
+    /// reported. GC'd accounts are not reported either (see `num_gc_closed`).
+    pub fn keeper_crank_with(
+        &mut self,
+        caller_idx: u16,
+        now_slot: u64,
+        oracle_price: u64,
+        funding_rate_bps_per_slot: i64,
+        allow_panic: bool,
+        mut on_changed: impl FnMut(u16),
     ) -> Result<CrankOutcome> {
         // Validate oracle price bounds (prevents overflow in mark_pnl calculations)
         if oracle_price == 0 || oracle_price > MAX_ORACLE_PRICE {
             return Err(RiskError::Overflow);
         }
 
+        let aggregate_check = self.begin_aggregate_check();
+
         // Update current_slot so warmup/bookkeeping progresses consistently
         self.current_slot = now_slot;
 
@@ -1555,6 +2095,7 @@ impl RiskEngine {
         let mut force_realize_errors: u16 = 0;
         let mut sweep_complete = false;
         let mut accounts_processed: u16 = 0;
+        let mut accounts_settled: u16 = 0;
         let mut liq_budget = LIQ_BUDGET_PER_CRANK;
         let mut force_realize_budget = FORCE_REALIZE_BUDGET_PER_CRANK;
 
@@ -1566,6 +2107,7 @@ impl RiskEngine {
 
         while accounts_processed < ACCOUNTS_PER_CRANK && slots_scanned < MAX_ACCOUNTS {
             slots_scanned += 1;
+            metering::scan(1);
 
             // Check if slot is used
             let block = idx >> 6;
@@ -1575,16 +2117,51 @@ impl RiskEngine {
             if is_occupied {
                 accounts_processed += 1;
 
-                // Always settle maintenance fees for every visited account.
+                // Settle maintenance fees for every visited account (with dirty
+                // tracking: touched accounts and the sweep's stripe only).
                 // This drains idle accounts over time so they eventually become dust.
-                let _ = self.settle_maintenance_fee_best_effort_for_crank(idx as u16, now_slot);
-                // Touch account and settle warmup to drain abandoned positive PnL
-                let _ = self.touch_account(idx as u16);
-                self.settle_warmup_to_capital_for_crank(idx as u16);
+                let settles = self.crank_settles(idx);
+                let mut changed = settles;
+                if settles {
+                    let _ = self.settle_maintenance_fee_best_effort_for_crank(idx as u16, now_slot);
+                    // Touch account and settle warmup to drain abandoned positive PnL
+                    let _ = self.touch_account(idx as u16);
+                    // Held accounts keep their loss until the auction settles it
+                    if self.is_liquidation_held(idx) {
+                        self.pay_loss_from_capital(idx as u16);
+                    } else {
+                        self.settle_warmup_to_capital_for_crank(idx as u16);
+                    }
+                    self.clear_dirty(idx);
+                    accounts_settled += 1;
+                }
+
+                // Bankrupt accounts are held for auction; held ones are left alone
+                if self.bankruptcy_auctions
+                    && !self.is_liquidation_held(idx)
+                    && !self.accounts[idx].position_size.is_zero()
+                    && self.account_equity_mtm_at_oracle(&self.accounts[idx], oracle_price) == 0
+                {
+                    self.set_liquidation_held(idx, true);
+                    changed = true;
+                }
+                let held = self.is_liquidation_held(idx);
 
                 // === Liquidation (if not in force-realize mode) ===
-                if !force_realize_active && liq_budget > 0 {
-                    if !self.accounts[idx].position_size.is_zero() {
+                if !force_realize_active && liq_budget > 0 && !held {
+                    if self.deferred_liquidations {
+                        // Settle as a liquidation would; the caller closes
+                        if settles
+                            && !self.accounts[idx].position_size.is_zero()
+                            && self
+                                .touch_account_for_liquidation(idx as u16, now_slot, oracle_price)
+                                .is_err()
+                        {
+                            num_liq_errors += 1;
+                        }
+                    } else if !self.accounts[idx].position_size.is_zero() {
+                        // Liquidation touches the account even when it stays open
+                        changed = true;
                         match self.liquidate_at_oracle(idx as u16, now_slot, oracle_price) {
                             Ok(true) => {
                                 num_liquidations += 1;
@@ -1597,26 +2174,29 @@ impl RiskEngine {
                         }
                     }
 
-                    // Force-close negative equity or dust positions
+                    // Force-close negative equity or dust positions (deferred
+                    // liquidation leaves negative equity to the caller's pass)
                     if !self.accounts[idx].position_size.is_zero() {
                         let equity =
                             self.account_equity_mtm_at_oracle(&self.accounts[idx], oracle_price);
                         let abs_pos = self.accounts[idx].position_size.unsigned_abs();
                         let is_dust = abs_pos < self.params.min_liquidation_abs.get();
 
-                        if equity == 0 || is_dust {
+                        if (equity == 0 && !self.deferred_liquidations) || is_dust {
                             // Force close: settle mark, close position, write off loss
                             let _ = self.touch_account_for_liquidation(idx as u16, now_slot, oracle_price);
                             let _ = self.oracle_close_position_core(idx as u16, oracle_price);
                             self.lifetime_force_realize_closes =
                                 self.lifetime_force_realize_closes.saturating_add(1);
+                            changed = true;
                         }
                     }
                 }
 
                 // === Force-realize (when insurance at/below threshold) ===
-                if force_realize_active && force_realize_budget > 0 {
+                if force_realize_active && force_realize_budget > 0 && !held {
                     if !self.accounts[idx].position_size.is_zero() {
+                        changed = true;
                         if self
                             .touch_account_for_force_realize(idx as u16, now_slot, oracle_price)
                             .is_ok()
@@ -1640,6 +2220,10 @@ impl RiskEngine {
                     let abs_pos = self.accounts[idx].position_size.unsigned_abs();
                     self.lp_max_abs_sweep = self.lp_max_abs_sweep.max(U128::new(abs_pos));
                 }
+
+                if changed {
+                    on_changed(idx as u16);
+                }
             }
 
             // Advance to next index (with wrap)
@@ -1665,10 +2249,14 @@ impl RiskEngine {
 
         // Garbage collect dust accounts
         let num_gc_closed = self.garbage_collect_dust();
+        if sweep_complete {
+            self.settle_stripe = (self.settle_stripe + 1) % SETTLE_STRIPES;
+        }
 
         // Detect conditions for informational flags
         let force_realize_needed = self.force_realize_active();
         let panic_needed = false; // No longer needed with haircut ratio
+        self.end_aggregate_check(aggregate_check);
 
         Ok(CrankOutcome {
             advanced,
@@ -1681,6 +2269,7 @@ impl RiskEngine {
             num_gc_closed,
             force_realize_closed,
             force_realize_errors,
+            accounts_settled,
             last_cursor: self.crank_cursor,
             sweep_complete,
         })
@@ -1843,14 +2432,16 @@ impl RiskEngine {
 
         // Update position
         let new_abs_pos = current_abs_pos.saturating_sub(close_abs);
-        self.accounts[idx as usize].position_size = if pos > 0 {
-            I128::new(new_abs_pos as i128)
+        let new_pos = if pos > 0 {
+            new_abs_pos as i128
         } else {
-            I128::new(-(new_abs_pos as i128))
+            -(new_abs_pos as i128)
         };
+        self.accounts[idx as usize].position_size = I128::new(new_pos);
 
-        // Update OI
-        self.total_open_interest = self.total_open_interest - close_abs;
+        // Update OI and active capital
+        let capital = self.accounts[idx as usize].capital.get();
+        self.update_position_aggregates(pos, new_pos, capital, capital);
 
         // Update LP aggregates if LP
         if self.accounts[idx as usize].is_lp() {
@@ -1863,9 +2454,7 @@ impl RiskEngine {
         self.settle_warmup_to_capital(idx)?;
 
         // Write off residual negative PnL (capital exhausted) per spec §6.1
-        if self.accounts[idx as usize].pnl.is_negative() {
-            self.set_pnl(idx as usize, 0);
-        }
+        self.write_off_negative_pnl(idx as usize);
 
         let cap_after = self.accounts[idx as usize].capital.get();
 
@@ -1913,8 +2502,9 @@ impl RiskEngine {
         self.accounts[idx as usize].position_size = I128::ZERO;
         self.accounts[idx as usize].entry_price = oracle_price;
 
-        // Update OI
-        self.total_open_interest = self.total_open_interest - abs_pos;
+        // Update OI and active capital
+        let capital = self.accounts[idx as usize].capital.get();
+        self.update_position_aggregates(pos, 0, capital, capital);
 
         // Update LP aggregates if LP
         if self.accounts[idx as usize].is_lp() {
@@ -1926,9 +2516,7 @@ impl RiskEngine {
         self.settle_warmup_to_capital(idx)?;
 
         // Write off residual negative PnL (capital exhausted) per spec §6.1
-        if self.accounts[idx as usize].pnl.is_negative() {
-            self.set_pnl(idx as usize, 0);
-        }
+        self.write_off_negative_pnl(idx as usize);
 
         let cap_after = self.accounts[idx as usize].capital.get();
 
@@ -1962,7 +2550,9 @@ impl RiskEngine {
             return Err(RiskError::Overflow);
         }
 
-        if self.accounts[idx as usize].position_size.is_zero() {
+        if self.accounts[idx as usize].position_size.is_zero()
+            || self.is_liquidation_held(idx as usize)
+        {
             return Ok(false);
         }
 
@@ -2014,8 +2604,111 @@ impl RiskEngine {
         }
 
         // Charge liquidation fee (from remaining capital → insurance)
-        // Use ceiling division for consistency with trade fees
-        let notional = mul_u128(outcome.abs_pos, oracle_price as u128) / 1_000_000;
+        self.charge_liquidation_fee(idx, outcome.abs_pos, oracle_price);
+
+        self.lifetime_liquidations = self.lifetime_liquidations.saturating_add(1);
+
+        Ok(true)
+    }
+
+    /// Move `size` of `from`'s position to `to` at `price` (auction fill).
+    ///
+    /// Both accounts are settled to `oracle_price` first, so the slice
+    /// changes hands at the oracle and the difference to `price` moves from
+    /// `from`'s PnL to `to`'s. `size` carries the sign of `from`'s position
+    /// and at most its size. With `check_margin`, a transfer that grows
+    /// `to`'s exposure must leave it above initial margin before counting
+    /// the gain. Returns the PnL `to` gained.
+    #[allow(clippy::too_many_arguments)]
+    pub fn transfer_position(
+        &mut self,
+        from: u16,
+        to: u16,
+        size: i128,
+        price: u64,
+        now_slot: u64,
+        oracle_price: u64,
+        check_margin: bool,
+    ) -> Result<i128> {
+        self.current_slot = now_slot;
+        if from == to || !self.is_used(from as usize) || !self.is_used(to as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        if price == 0 || price > MAX_ORACLE_PRICE || oracle_price == 0 || oracle_price > MAX_ORACLE_PRICE {
+            return Err(RiskError::Overflow);
+        }
+        let from_pos = self.accounts[from as usize].position_size.get();
+        if size == 0 || size == i128::MIN || (size > 0) != (from_pos > 0) || size.unsigned_abs() > from_pos.unsigned_abs() {
+            return Err(RiskError::PositionSizeMismatch);
+        }
+
+        self.touch_account(from)?;
+        self.touch_account(to)?;
+        self.settle_mark_to_oracle(from, oracle_price)?;
+        self.settle_mark_to_oracle(to, oracle_price)?;
+
+        let to_pos = self.accounts[to as usize].position_size.get();
+        let new_to = to_pos.checked_add(size).ok_or(RiskError::Overflow)?;
+        if check_margin && new_to.unsigned_abs() > to_pos.unsigned_abs() {
+            let mut probe = self.accounts[to as usize];
+            probe.position_size = I128::new(new_to);
+            if !self.is_above_margin_bps_mtm(&probe, oracle_price, self.params.initial_margin_bps) {
+                return Err(RiskError::Undercollateralized);
+            }
+        }
+
+        let gain = Self::mark_pnl_for_position(size, price, oracle_price)?;
+        let from_pnl = self.accounts[from as usize].pnl.get().saturating_sub(gain);
+        let to_pnl = self.accounts[to as usize].pnl.get().saturating_add(gain);
+        self.set_pnl(from as usize, from_pnl);
+        self.set_pnl(to as usize, to_pnl);
+        self.pay_loss_from_capital(from);
+
+        for (idx, old_pos, new_pos) in [(from, from_pos, from_pos - size), (to, to_pos, new_to)] {
+            self.accounts[idx as usize].position_size = I128::new(new_pos);
+            let capital = self.accounts[idx as usize].capital.get();
+            self.update_position_aggregates(old_pos, new_pos, capital, capital);
+            if self.accounts[idx as usize].is_lp() {
+                self.net_lp_pos = self.net_lp_pos - old_pos + new_pos;
+                self.lp_sum_abs = self.lp_sum_abs - old_pos.unsigned_abs() + new_pos.unsigned_abs();
+            }
+        }
+        self.update_warmup_slope(to)?;
+        Ok(gain)
+    }
+
+    /// Pay as much of `idx`'s negative PnL from capital as it can, leaving
+    /// the rest owed (accounts held for auction settle losses this way)
+    pub fn pay_loss_from_capital(&mut self, idx: u16) {
+        let pnl = self.accounts[idx as usize].pnl.get();
+        let capital = self.accounts[idx as usize].capital.get();
+        let pay = core::cmp::min(neg_i128_to_u128(pnl.min(0)), capital);
+        if pay > 0 {
+            self.set_capital(idx as usize, capital - pay);
+            self.set_pnl(idx as usize, pnl + pay as i128);
+        }
+    }
+
+    /// Pay `idx`'s negative PnL from capital and write off the rest (§6.1).
+    /// Returns the amount written off.
+    pub fn write_off_loss(&mut self, idx: u16) -> Result<u128> {
+        if !self.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        let pnl = self.accounts[idx as usize].pnl.get();
+        let capital = self.accounts[idx as usize].capital.get();
+        self.settle_loss_only(idx)?;
+        Ok(if pnl < 0 {
+            neg_i128_to_u128(pnl).saturating_sub(capital)
+        } else {
+            0
+        })
+    }
+
+    /// Charge the liquidation fee on `closed_abs` of position (capital → insurance).
+    /// Uses ceiling division for consistency with trade fees. Returns the amount paid.
+    fn charge_liquidation_fee(&mut self, idx: u16, closed_abs: u128, oracle_price: u64) -> u128 {
+        let notional = mul_u128(closed_abs, oracle_price as u128) / 1_000_000;
         let fee_raw = if notional > 0 && self.params.liquidation_fee_bps > 0 {
             (mul_u128(notional, self.params.liquidation_fee_bps as u128) + 9999) / 10_000
         } else {
@@ -2029,9 +2722,118 @@ impl RiskEngine {
         self.insurance_fund.balance = self.insurance_fund.balance.saturating_add_u128(U128::new(pay));
         self.insurance_fund.fee_revenue = self.insurance_fund.fee_revenue.saturating_add_u128(U128::new(pay));
 
-        self.lifetime_liquidations = self.lifetime_liquidations.saturating_add(1);
+        pay
+    }
 
-        Ok(true)
+    /// Close an account's whole position at `price` without a liquidation fee.
+    ///
+    /// Settlement path for a market being wound down: the same accounting as
+    /// a full `force_reduce_at_oracle`, minus the fee.
+    ///
+    /// Returns the absolute size closed.
+    pub fn settle_position_at_price(&mut self, idx: u16, now_slot: u64, price: u64) -> Result<u128> {
+        self.current_slot = now_slot;
+
+        if (idx as usize) >= MAX_ACCOUNTS || !self.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        if price == 0 || price > MAX_ORACLE_PRICE {
+            return Err(RiskError::Overflow);
+        }
+        if self.accounts[idx as usize].position_size.is_zero() {
+            return Ok(0);
+        }
+
+        self.touch_account_for_liquidation(idx, now_slot, price)?;
+        let outcome = self.oracle_close_position_core(idx, price)?;
+        Ok(outcome.abs_pos)
+    }
+
+    /// Forcibly reduce an account's position by `reduce_abs` at oracle price.
+    ///
+    /// Unlike liquidate_at_oracle this does not require the account to be below
+    /// maintenance margin; it is the execution path for protocol-directed
+    /// risk reduction. The liquidation fee applies to the closed notional.
+    /// If the remainder would fall below min_liquidation_abs, the whole
+    /// position is closed (dust kill-switch).
+    ///
+    /// Returns the absolute size actually closed.
+    pub fn force_reduce_at_oracle(
+        &mut self,
+        idx: u16,
+        reduce_abs: u128,
+        now_slot: u64,
+        oracle_price: u64,
+    ) -> Result<u128> {
+        self.current_slot = now_slot;
+
+        if (idx as usize) >= MAX_ACCOUNTS || !self.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        if oracle_price == 0 || oracle_price > MAX_ORACLE_PRICE {
+            return Err(RiskError::Overflow);
+        }
+
+        let abs_pos = saturating_abs_i128(self.accounts[idx as usize].position_size.get()) as u128;
+        if abs_pos == 0 || reduce_abs == 0 {
+            return Ok(0);
+        }
+
+        // Settle funding + mark-to-market + best-effort fees
+        self.touch_account_for_liquidation(idx, now_slot, oracle_price)?;
+
+        let remaining = abs_pos.saturating_sub(reduce_abs);
+        let outcome = if remaining == 0 || remaining < self.params.min_liquidation_abs.get() {
+            self.oracle_close_position_core(idx, oracle_price)?
+        } else {
+            match self.oracle_close_position_slice_core(idx, oracle_price, reduce_abs) {
+                Ok(r) => r,
+                Err(RiskError::Overflow) => self.oracle_close_position_core(idx, oracle_price)?,
+                Err(e) => return Err(e),
+            }
+        };
+
+        if !outcome.position_was_closed {
+            return Ok(0);
+        }
+
+        self.charge_liquidation_fee(idx, outcome.abs_pos, oracle_price);
+
+        Ok(outcome.abs_pos)
+    }
+
+    /// Check that `force_reduce_at_oracle` would succeed, without changing state.
+    ///
+    /// Covers everything the reduction can reject: the account, the oracle
+    /// bounds, and the funding and mark settlement it performs first (the
+    /// close itself cannot fail once those fit).
+    pub fn check_force_reduce(&self, idx: u16, reduce_abs: u128, oracle_price: u64) -> Result<()> {
+        if (idx as usize) >= MAX_ACCOUNTS || !self.is_used(idx as usize) {
+            return Err(RiskError::AccountNotFound);
+        }
+        if oracle_price == 0 || oracle_price > MAX_ORACLE_PRICE {
+            return Err(RiskError::Overflow);
+        }
+
+        let account = &self.accounts[idx as usize];
+        let pos = account.position_size.get();
+        if pos == 0 || reduce_abs == 0 {
+            return Ok(());
+        }
+
+        // Mirrors settle_account_funding
+        let delta_f = self
+            .funding_index_qpb_e6
+            .get()
+            .checked_sub(account.funding_index.get())
+            .ok_or(RiskError::Overflow)?;
+        if delta_f != 0 {
+            let payment = Self::funding_payment(pos, delta_f).ok_or(RiskError::Overflow)?;
+            account.pnl.get().checked_sub(payment).ok_or(RiskError::Overflow)?;
+        }
+
+        Self::mark_pnl_for_position(pos, account.entry_price, oracle_price)?;
+        Ok(())
     }
 
     // ========================================
@@ -2184,6 +2986,46 @@ impl RiskEngine {
         self.accrue_funding(now_slot, oracle_price)
     }
 
+    /// Funding a position owes for an index move: position × ΔF / 1e6.
+    /// Rounds UP for positive payments (account pays) and truncates negative
+    /// ones (account receives), so the vault always has at least what's owed
+    /// (one-sided conservation slack). None on overflow.
+    pub fn funding_payment(position: i128, delta_f: i128) -> Option<i128> {
+        let raw = position.checked_mul(delta_f)?;
+        if raw > 0 {
+            // Account is paying: round UP to ensure vault gets at least theoretical amount
+            raw.checked_add(999_999)?.checked_div(1_000_000)
+        } else {
+            // Account is receiving: truncate towards zero to give at most theoretical amount
+            raw.checked_div(1_000_000)
+        }
+    }
+
+    /// Funding accrued on `idx` since its last settlement, charged to its
+    /// PnL on the next touch (+ pays, - receives; saturating)
+    pub fn pending_funding(&self, idx: usize) -> i128 {
+        let account = &self.accounts[idx];
+        let delta_f = self
+            .funding_index_qpb_e6
+            .get()
+            .saturating_sub(account.funding_index.get());
+        Self::funding_payment(account.position_size.get(), delta_f).unwrap_or_else(|| {
+            if (account.position_size.get() > 0) == (delta_f > 0) {
+                i128::MAX
+            } else {
+                i128::MIN
+            }
+        })
+    }
+
+    /// Cumulative funding of `idx`, settled and pending (+ paid, - received).
+    /// O(1): nothing is iterated, the global index is read lazily.
+    pub fn funding_paid(&self, idx: usize) -> i128 {
+        self.funding_settled[idx]
+            .get()
+            .saturating_add(self.pending_funding(idx))
+    }
+
     /// Settle funding for an account (lazy update).
     /// Uses set_pnl helper to maintain pnl_pos_tot aggregate (spec §4.2).
     fn settle_account_funding(&mut self, idx: usize) -> Result<()> {
@@ -2195,26 +3037,9 @@ impl RiskEngine {
             .ok_or(RiskError::Overflow)?;
 
         if delta_f != 0 && !account.position_size.is_zero() {
-            // payment = position × ΔF / 1e6
-            // Round UP for positive payments (account pays), truncate for negative (account receives)
-            // This ensures vault always has at least what's owed (one-sided conservation slack).
-            let raw = account
-                .position_size
-                .get()
-                .checked_mul(delta_f)
+            let payment = Self::funding_payment(account.position_size.get(), delta_f)
                 .ok_or(RiskError::Overflow)?;
 
-            let payment = if raw > 0 {
-                // Account is paying: round UP to ensure vault gets at least theoretical amount
-                raw.checked_add(999_999)
-                    .ok_or(RiskError::Overflow)?
-                    .checked_div(1_000_000)
-                    .ok_or(RiskError::Overflow)?
-            } else {
-                // Account is receiving: truncate towards zero to give at most theoretical amount
-                raw.checked_div(1_000_000).ok_or(RiskError::Overflow)?
-            };
-
             // Longs pay when funding positive: pnl -= payment
             // Use set_pnl helper to maintain pnl_pos_tot aggregate (spec §4.2)
             let new_pnl = self.accounts[idx]
@@ -2223,6 +3048,8 @@ impl RiskEngine {
                 .checked_sub(payment)
                 .ok_or(RiskError::Overflow)?;
             self.set_pnl(idx, new_pnl);
+            self.funding_settled[idx] =
+                I128::new(self.funding_settled[idx].get().saturating_add(payment));
         }
 
         self.accounts[idx].funding_index = global_fi;
@@ -2235,6 +3062,7 @@ impl RiskEngine {
             return Err(RiskError::AccountNotFound);
         }
 
+        self.mark_dirty(idx as usize);
         self.settle_account_funding(idx as usize)
     }
 
@@ -2690,6 +3518,8 @@ impl RiskEngine {
         oracle_price: u64,
         size: i128,
     ) -> Result<()> {
+        let aggregate_check = self.begin_aggregate_check();
+
         // Update current_slot so warmup/bookkeeping progresses consistently
         self.current_slot = now_slot;
 
@@ -3041,17 +3871,15 @@ impl RiskEngine {
                 .saturating_sub(old_lp_pnl_pos),
         );
 
-        // Update total open interest tracking (O(1))
-        // OI = sum of abs(position_size) across all accounts
-        let old_oi =
-            saturating_abs_i128(old_user_pos) as u128 + saturating_abs_i128(old_lp_pos) as u128;
-        let new_oi = saturating_abs_i128(new_user_position) as u128
-            + saturating_abs_i128(new_lp_position) as u128;
-        if new_oi > old_oi {
-            self.total_open_interest = self.total_open_interest.saturating_add(new_oi - old_oi);
-        } else {
-            self.total_open_interest = self.total_open_interest.saturating_sub(old_oi - new_oi);
-        }
+        // Update open interest and active capital tracking (O(1))
+        let lp_capital = lp.capital.get();
+        self.update_position_aggregates(
+            old_user_pos,
+            new_user_position,
+            new_user_capital.saturating_add(fee),
+            new_user_capital,
+        );
+        self.update_position_aggregates(old_lp_pos, new_lp_position, lp_capital, lp_capital);
 
         // Update LP aggregates for funding/threshold (O(1))
         let old_lp_abs = saturating_abs_i128(old_lp_pos) as u128;
@@ -3085,6 +3913,7 @@ impl RiskEngine {
         self.update_warmup_slope(user_idx)?;
         self.update_warmup_slope(lp_idx)?;
 
+        self.end_aggregate_check(aggregate_check);
         Ok(())
     }
     /// Settle loss only (§6.1): negative PnL pays from capital immediately.
@@ -3108,9 +3937,7 @@ impl RiskEngine {
             }
 
             // Write off any remaining negative PnL (spec §6.1 step 4)
-            if self.accounts[idx as usize].pnl.is_negative() {
-                self.set_pnl(idx as usize, 0);
-            }
+            self.write_off_negative_pnl(idx as usize);
         }
 
         Ok(())
@@ -3141,9 +3968,7 @@ impl RiskEngine {
             }
 
             // Write off any remaining negative PnL (spec §6.1 step 4)
-            if self.accounts[idx as usize].pnl.is_negative() {
-                self.set_pnl(idx as usize, 0);
-            }
+            self.write_off_negative_pnl(idx as usize);
         }
 
         // §6.2 Profit conversion (warmup converts junior profit → protected principal)
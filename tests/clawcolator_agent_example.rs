@@ -103,6 +103,14 @@ impl OpenClawAgent for SimpleClawAgent {
             funding_rate_bps_per_slot: 0, // No funding for simplicity
             min_margin_bps: 500, // 5% minimum margin
             active_capital_ratio_bps: 8000, // 80% active, 20% reserve
+            tier_limits: DEFAULT_TIER_LIMITS,
+            dust_policy: DEFAULT_DUST_POLICY,
+            withdrawal_policy: DEFAULT_WITHDRAWAL_POLICY,
+            fee_split: DEFAULT_FEE_SPLIT,
+            vamm_depth: 0,
+            max_oi_share_bps: 10_000,
+            warmup_period_slots: None,
+            lending_policy: DEFAULT_LENDING_POLICY,
         })
     }
     
@@ -229,23 +237,24 @@ mod tests {
     fn test_simple_agent_trade_decision() {
         let agent = SimpleClawAgent::new(1_000_000, 1000, 10);
         
-        let context = AgentContext {
-            current_slot: 1000,
-            oracle_price: 1_000_000,
-            vault: 10_000_000,
-            insurance_balance: 1_000_000,
-            total_capital: 9_000_000,
-            total_positive_pnl: 0,
-            total_open_interest: 0,
-            risk_params: default_params(),
-            risk_reduction_mode: false,
-            last_crank_slot: 999,
-        };
+        let context = AgentContext::builder()
+            .current_slot(1000)
+            .oracle_price(1_000_000)
+            .oracle_publish_slot(1000)
+            .vault(10_000_000)
+            .insurance_balance(1_000_000)
+            .total_capital(9_000_000)
+            .risk_params(default_params())
+            .last_crank_slot(999)
+            .build();
         
         let request = TradeRequest {
             user_idx: 0,
             size: 1000,
             requested_price: None,
+            client_order_id: None,
+            sequence: None,
+            valid_until_slot: None,
         };
         
         let decision = agent.decide_trade(&context, &request).unwrap();
@@ -263,23 +272,24 @@ mod tests {
     fn test_simple_agent_rejects_oversized_trade() {
         let agent = SimpleClawAgent::new(1_000_000, 1000, 10);
         
-        let context = AgentContext {
-            current_slot: 1000,
-            oracle_price: 1_000_000,
-            vault: 10_000_000,
-            insurance_balance: 1_000_000,
-            total_capital: 9_000_000,
-            total_positive_pnl: 0,
-            total_open_interest: 0,
-            risk_params: default_params(),
-            risk_reduction_mode: false,
-            last_crank_slot: 999,
-        };
+        let context = AgentContext::builder()
+            .current_slot(1000)
+            .oracle_price(1_000_000)
+            .oracle_publish_slot(1000)
+            .vault(10_000_000)
+            .insurance_balance(1_000_000)
+            .total_capital(9_000_000)
+            .risk_params(default_params())
+            .last_crank_slot(999)
+            .build();
         
         let request = TradeRequest {
             user_idx: 0,
             size: 2_000_000, // Exceeds max_position_size
             requested_price: None,
+            client_order_id: None,
+            sequence: None,
+            valid_until_slot: None,
         };
         
         let decision = agent.decide_trade(&context, &request).unwrap();
@@ -291,4 +301,13 @@ mod tests {
             _ => panic!("Expected Reject decision"),
         }
     }
+    
+    #[test]
+    fn test_simple_agent_passes_conformance_kit() {
+        let agent = SimpleClawAgent::new(1_000_000, 1000, 10);
+        let engine = Box::new(ClawcolatorEngine::new(default_params()));
+        
+        let report = certify(&engine, &agent);
+        assert!(report.passed(), "{:?}", report.first_violation());
+    }
 }
@@ -0,0 +1,5119 @@
+//! Engine-level tests for the Clawcolator agent-first layer
+//! Run with: cargo test --features test,clawcolator
+//! and again with `metering` added, which the cost accounting test needs
+//! to check actual counts: cargo test --features test,clawcolator,metering
+
+#![cfg(feature = "clawcolator")]
+
+use percolator::clawcolator::*;
+use percolator::*;
+
+const ORACLE: u64 = 1_000_000;
+const ORACLE_PX: OraclePrice = OraclePrice {
+    price: ORACLE,
+    confidence: 0,
+    publish_slot: 0,
+};
+
+fn default_params() -> RiskParams {
+    RiskParams {
+        warmup_period_slots: 100,
+        maintenance_margin_bps: 500,
+        initial_margin_bps: 1000,
+        trading_fee_bps: 10,
+        max_accounts: 1000,
+        new_account_fee: U128::new(0),
+        risk_reduction_threshold: U128::new(0),
+        maintenance_fee_per_slot: U128::new(0),
+        max_crank_staleness_slots: u64::MAX,
+        liquidation_fee_bps: 50,
+        liquidation_fee_cap: U128::new(100_000),
+        liquidation_buffer_bps: 100,
+        min_liquidation_abs: U128::new(100_000),
+    }
+}
+
+/// Agent that fills every request in full at the oracle price
+struct PassthroughAgent;
+
+impl OpenClawAgent for PassthroughAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(TradeDecision::Accept {
+            price: context.oracle_price,
+            size: request.size,
+        })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        Ok(LiquidityAllocation {
+            target_active_capital: context.total_capital,
+            reserve_capital: 0,
+            defensive_mode: false,
+        })
+    }
+
+    fn assess_risk(&self, _context: &AgentContext) -> Result<RiskAssessment> {
+        Ok(RiskAssessment {
+            risk_level_bps: 0,
+            actions: RiskActions::default(),
+        })
+    }
+
+    fn detect_anomalies(&self, _context: &AgentContext) -> Result<AnomalyResponse> {
+        Ok(AnomalyResponse {
+            anomaly_type: AnomalyType::Other,
+            severity_bps: 0,
+            actions: AnomalyActions::default(),
+        })
+    }
+
+    fn should_shutdown(&self, _context: &AgentContext) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Engine with a funded LP at index 0 and a funded user at index 1
+fn funded_engine() -> Box<ClawcolatorEngine> {
+    let mut engine = Box::new(ClawcolatorEngine::new(default_params()));
+    let risk = engine.risk_engine_mut();
+    let lp = risk.add_lp([0; 32], [0; 32], 0).unwrap();
+    risk.deposit(lp, 10_000_000, 0).unwrap();
+    let user = risk.add_user(0).unwrap();
+    risk.deposit(user, 1_000_000, 0).unwrap();
+    assert_eq!((lp, user), (0, 1));
+    engine
+}
+
+// ==============================================================================
+// STATE DELTA
+// ==============================================================================
+
+#[test]
+fn test_state_delta_tracks_trade() {
+    let mut engine = funded_engine();
+
+    let setup = engine.take_state_delta();
+    assert!(setup.resync_required);
+    assert_eq!(setup.vault_delta, 11_000_000);
+
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000, 1)
+        .unwrap();
+
+    let delta = engine.take_state_delta();
+    assert!(!delta.resync_required);
+    assert_eq!(delta.accounts_len, 2);
+    let user = delta.accounts[..delta.accounts_len]
+        .iter()
+        .find(|a| a.idx == 1)
+        .unwrap();
+    assert_eq!(user.position_size, 1_000);
+    assert!(user.in_use);
+    assert_eq!(delta.market_params, None);
+
+    let empty = engine.take_state_delta();
+    assert_eq!(empty.accounts_len, 0);
+    assert_eq!(empty.vault_delta, 0);
+}
+
+#[test]
+fn test_state_delta_mirrors_funding_crank() {
+    use std::collections::BTreeMap;
+
+    type Mirror = BTreeMap<u16, (u128, i128, i128)>;
+    let read = |engine: &ClawcolatorEngine| -> Mirror {
+        let risk = engine.risk_engine();
+        (0..MAX_ACCOUNTS as u16)
+            .filter(|&idx| risk.is_used(idx as usize))
+            .map(|idx| {
+                let a = &risk.accounts[idx as usize];
+                (idx, (a.capital.get(), a.pnl.get(), a.position_size.get()))
+            })
+            .collect()
+    };
+    let apply = |mirror: &mut Mirror, engine: &mut ClawcolatorEngine| {
+        let delta = engine.take_state_delta();
+        assert!(!delta.resync_required);
+        for a in &delta.accounts[..delta.accounts_len] {
+            match a.in_use {
+                true => mirror.insert(a.idx, (a.capital, a.pnl, a.position_size)),
+                false => mirror.remove(&a.idx),
+            };
+        }
+    };
+
+    let mut engine = funded_engine();
+    let other = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.risk_engine_mut().deposit(other, 1_000_000, 0).unwrap();
+    assert!(engine.take_state_delta().resync_required);
+    let mut mirror = read(&engine);
+
+    let agent = ParamsAgent {
+        params: MarketParams { funding_rate_bps_per_slot: 10, ..MarketParams::default() },
+    };
+    engine.update_market_params(&agent).unwrap();
+    engine.execute_trade(&agent, 1, ORACLE_PX, 400_000, 1).unwrap();
+    engine.crank(other, 1, ORACLE, None).unwrap();
+    apply(&mut mirror, &mut engine);
+    assert_eq!(mirror, read(&engine));
+
+    // A crank called by a bystander settles funding on both sides of the trade
+    let before = read(&engine);
+    engine.crank(other, 100, ORACLE, None).unwrap();
+    let after = read(&engine);
+    assert_ne!(before[&0], after[&0]);
+    assert_ne!(before[&1], after[&1]);
+    apply(&mut mirror, &mut engine);
+    assert_eq!(mirror, after);
+}
+
+#[test]
+fn test_crank_delta_skips_clean_accounts() {
+    let mut engine = funded_engine();
+    for _ in 0..30 {
+        let idx = engine.risk_engine_mut().add_user(0).unwrap();
+        engine.risk_engine_mut().deposit(idx, 1_000, 0).unwrap();
+    }
+    engine.risk_engine_mut().set_dirty_tracking(true);
+    engine.crank(AGENT_LP_IDX, 1, ORACLE, None).unwrap();
+    engine.take_state_delta();
+
+    // Only the sweep's stripe and the caller settle; 32 clean accounts
+    // would overflow the delta if every visited account were recorded
+    engine.crank(AGENT_LP_IDX, 2, ORACLE, None).unwrap();
+    let delta = engine.take_state_delta();
+    assert!(!delta.resync_required);
+    assert!(delta.accounts_len > 0);
+    assert!(delta.accounts_len < MAX_DELTA_ACCOUNTS);
+}
+
+// ==============================================================================
+// ACCOUNT VIEWS
+// ==============================================================================
+
+#[test]
+fn test_account_iteration_filters() {
+    let mut engine = funded_engine();
+    engine.risk_engine_mut().add_user(0).unwrap();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000, 1)
+        .unwrap();
+
+    assert_eq!(engine.accounts(0, ORACLE, AccountFilter::All).count(), 3);
+
+    let open: Vec<u16> = engine
+        .accounts(0, ORACLE, AccountFilter::NonzeroPosition)
+        .map(|v| v.idx)
+        .collect();
+    assert_eq!(open, vec![0, 1]);
+    assert_eq!(
+        engine
+            .accounts(0, ORACLE, AccountFilter::Undercollateralized)
+            .count(),
+        0
+    );
+
+    let flat = engine.account_view(2, ORACLE).unwrap();
+    assert_eq!(flat.margin_ratio_bps, u64::MAX);
+    assert!(engine.account_view(3, ORACLE).is_none());
+}
+
+#[test]
+fn test_account_pagination() {
+    let engine = funded_engine();
+    let mut page = [engine.account_view(0, ORACLE).unwrap(); 1];
+
+    let (len, next) = engine.accounts_page(0, ORACLE, AccountFilter::All, &mut page);
+    assert_eq!((len, page[0].idx), (1, 0));
+
+    let (len, next) = engine.accounts_page(next.unwrap(), ORACLE, AccountFilter::All, &mut page);
+    assert_eq!((len, page[0].idx), (1, 1));
+
+    let (len, next) = engine.accounts_page(next.unwrap(), ORACLE, AccountFilter::All, &mut page);
+    assert_eq!((len, next), (0, None));
+}
+
+// ==============================================================================
+// ACCOUNT TIERS
+// ==============================================================================
+
+/// Agent that publishes a tight Retail position limit
+struct TieredAgent;
+
+impl OpenClawAgent for TieredAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        let mut params = MarketParams::default();
+        params.tier_limits[AccountTier::Retail.index()].max_position_size = 500;
+        Ok(params)
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        PassthroughAgent.decide_liquidity_allocation(context)
+    }
+
+    fn assess_risk(&self, context: &AgentContext) -> Result<RiskAssessment> {
+        PassthroughAgent.assess_risk(context)
+    }
+
+    fn detect_anomalies(&self, context: &AgentContext) -> Result<AnomalyResponse> {
+        PassthroughAgent.detect_anomalies(context)
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> Result<bool> {
+        PassthroughAgent.should_shutdown(context)
+    }
+}
+
+#[test]
+fn test_tier_limits_enforced_per_account() {
+    let mut engine = funded_engine();
+    engine.update_market_params(&TieredAgent).unwrap();
+    assert_eq!(engine.account_tier(0).unwrap(), AccountTier::LP);
+    assert_eq!(engine.account_tier(1).unwrap(), AccountTier::Retail);
+
+    assert_eq!(
+        engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000, 1),
+        Err(RiskError::Undercollateralized)
+    );
+
+    engine.set_account_tier(1, AccountTier::Pro, ORACLE).unwrap();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000, 1)
+        .unwrap();
+
+    // Downgrade would breach the Retail limit
+    assert_eq!(
+        engine.set_account_tier(1, AccountTier::Retail, ORACLE),
+        Err(RiskError::Undercollateralized)
+    );
+    // LP tier is reserved for LP accounts
+    assert_eq!(
+        engine.set_account_tier(1, AccountTier::LP, ORACLE),
+        Err(RiskError::AccountKindMismatch)
+    );
+    assert_eq!(
+        engine.account_view(1, ORACLE).unwrap().tier,
+        AccountTier::Pro
+    );
+}
+
+#[test]
+fn test_recycled_slot_starts_retail() {
+    let mut engine = funded_engine();
+    let pro = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.set_account_tier(pro, AccountTier::Pro, ORACLE).unwrap();
+    engine.risk_engine_mut().close_account(pro, 0, ORACLE).unwrap();
+
+    // A new account in the freed slot does not inherit the Pro tier
+    let fresh = engine.risk_engine_mut().add_user(0).unwrap();
+    assert_eq!(fresh, pro);
+    assert_eq!(engine.account_tier(fresh).unwrap(), AccountTier::Retail);
+    assert_eq!(engine.account_view(fresh, ORACLE).unwrap().tier, AccountTier::Retail);
+}
+
+// ==============================================================================
+// SUB-ACCOUNTS
+// ==============================================================================
+
+#[test]
+fn test_sub_account_isolation_and_aggregate() {
+    let mut engine = funded_engine();
+    let sub = engine.open_sub_account(1, 7).unwrap();
+    assert_eq!(engine.open_sub_account(1, 7), Err(RiskError::Unauthorized));
+    assert_eq!(
+        engine.open_sub_account(sub, 1),
+        Err(RiskError::AccountKindMismatch)
+    );
+
+    engine.transfer_to_sub_account(sub, 400_000, ORACLE).unwrap();
+    engine
+        .execute_trade(&PassthroughAgent, sub, ORACLE_PX, -2_000, 1)
+        .unwrap();
+
+    let view = engine.account_view(sub, ORACLE).unwrap();
+    assert_eq!((view.parent_idx, view.sub_id), (Some(1), 7));
+    assert_eq!(view.capital, 400_000 - 2); // trading fee paid from sub capital only
+    assert_eq!(engine.account_view(1, ORACLE).unwrap().capital, 600_000);
+
+    let agg = engine.user_aggregate(1, ORACLE).unwrap();
+    assert_eq!(agg.sub_accounts, 1);
+    assert_eq!(agg.net_position, -2_000);
+    assert_eq!(agg.total_capital, 1_000_000 - 2);
+
+    // Position must be flat before the sub-account can be closed
+    assert_eq!(
+        engine.close_sub_account(sub, 1, ORACLE),
+        Err(RiskError::Undercollateralized)
+    );
+    engine
+        .execute_trade(&PassthroughAgent, sub, ORACLE_PX, 2_000, 1)
+        .unwrap();
+    engine.close_sub_account(sub, 1, ORACLE).unwrap();
+    assert!(engine.account_view(sub, ORACLE).is_none());
+    assert_eq!(engine.user_aggregate(1, ORACLE).unwrap().sub_accounts, 0);
+}
+
+// ==============================================================================
+// MARGIN MODES
+// ==============================================================================
+
+#[test]
+fn test_cross_margin_draws_parent_capital() {
+    let mut engine = funded_engine();
+    let sub = engine.open_sub_account(1, 0).unwrap();
+    engine.transfer_to_sub_account(sub, 1_000, ORACLE).unwrap();
+
+    // Isolated: 1_000 capital cannot back a 50_000 notional position
+    assert!(engine
+        .execute_trade(&PassthroughAgent, sub, ORACLE_PX, 50_000, 1)
+        .is_err());
+
+    engine.set_margin_mode(1, MarginMode::Cross, ORACLE).unwrap();
+    assert_eq!(engine.margin_mode(sub), MarginMode::Cross);
+    engine
+        .execute_trade(&PassthroughAgent, sub, ORACLE_PX, 50_000, 1)
+        .unwrap();
+    let view = engine.account_view(sub, ORACLE).unwrap();
+    assert_eq!(view.margin_mode, MarginMode::Cross);
+    assert!(view.capital > 1_000);
+
+    // Mode can only change while the whole group is flat
+    assert_eq!(
+        engine.set_margin_mode(1, MarginMode::Isolated, ORACLE),
+        Err(RiskError::Undercollateralized)
+    );
+    assert_eq!(
+        engine.set_margin_mode(sub, MarginMode::Isolated, ORACLE),
+        Err(RiskError::AccountKindMismatch)
+    );
+}
+
+#[test]
+fn test_recycled_slot_starts_isolated() {
+    let mut engine = funded_engine();
+    let cross = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.set_margin_mode(cross, MarginMode::Cross, ORACLE).unwrap();
+    engine.risk_engine_mut().close_account(cross, 0, ORACLE).unwrap();
+
+    // Neither the new account nor its sub-accounts inherit cross margin
+    let fresh = engine.risk_engine_mut().add_user(0).unwrap();
+    assert_eq!(fresh, cross);
+    assert_eq!(engine.margin_mode(fresh), MarginMode::Isolated);
+    let sub = engine.open_sub_account(fresh, 0).unwrap();
+    assert_eq!(engine.margin_mode(sub), MarginMode::Isolated);
+    assert_eq!(engine.account_view(sub, ORACLE).unwrap().margin_mode, MarginMode::Isolated);
+}
+
+#[test]
+fn test_rejected_cross_margin_trade_leaves_parent_capital() {
+    let mut engine = funded_engine();
+    let other = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.risk_engine_mut().deposit(other, 1_000_000, 0).unwrap();
+    let agent = concentration_agent(3_000);
+    engine.update_market_params(&agent).unwrap();
+    engine.execute_trade(&agent, other, ORACLE_PX, -450_000, 1).unwrap();
+
+    let sub = engine.open_sub_account(1, 0).unwrap();
+    engine.set_margin_mode(1, MarginMode::Cross, ORACLE).unwrap();
+    let capital = |engine: &ClawcolatorEngine, idx| engine.account_view(idx, ORACLE).unwrap().capital;
+    let before = (capital(&engine, 1), capital(&engine, sub));
+
+    // 600k of 1.2M resulting OI breaches the 30% cap after the top-up is sized
+    assert_eq!(
+        engine.execute_trade(&agent, sub, ORACLE_PX, 600_000, 1),
+        Err(RiskError::Undercollateralized)
+    );
+    assert_eq!(
+        engine.last_trade_outcome().unwrap().rejection().unwrap().cause,
+        RejectionCause::Concentration
+    );
+    assert_eq!((capital(&engine, 1), capital(&engine, sub)), before);
+
+    // Within the cap the same path tops up and fills
+    engine.execute_trade(&agent, sub, ORACLE_PX, 100_000, 1).unwrap();
+    assert!(capital(&engine, sub) > before.1);
+    assert!(capital(&engine, 1) < before.0);
+}
+
+// ==============================================================================
+// HEDGING
+// ==============================================================================
+
+/// Venue that fills half of every order at a fixed price
+struct HalfFillVenue {
+    price: u64,
+}
+
+impl HedgeExecutor for HalfFillVenue {
+    fn execute_hedge(&mut self, size: i128, _oracle_price: u64) -> Result<HedgeFill> {
+        Ok(HedgeFill {
+            size: size / 2,
+            price: self.price,
+        })
+    }
+}
+
+#[test]
+fn test_hedge_validated_against_lp_inventory() {
+    let mut engine = funded_engine();
+    let mut venue = HalfFillVenue { price: ORACLE };
+
+    // No LP inventory yet: nothing to hedge
+    assert_eq!(
+        engine.execute_hedge(&mut venue, -100, ORACLE),
+        Err(RiskError::Unauthorized)
+    );
+
+    // User buys 1_000, so the LP is short 1_000 and may hedge by buying
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000, 1)
+        .unwrap();
+    assert_eq!(
+        engine.execute_hedge(&mut venue, -100, ORACLE),
+        Err(RiskError::Unauthorized)
+    );
+    assert_eq!(
+        engine.execute_hedge(&mut venue, 2_000, ORACLE),
+        Err(RiskError::Unauthorized)
+    );
+
+    let fill = engine.execute_hedge(&mut venue, 800, ORACLE).unwrap();
+    assert_eq!(fill.size, 400);
+    assert_eq!(engine.hedge_position().size, 400);
+
+    // Unwinding at a higher price realizes profit on the long hedge
+    let mut venue = HalfFillVenue { price: 2 * ORACLE };
+    engine.execute_hedge(&mut venue, -400, ORACLE).unwrap();
+    let hedge = engine.hedge_position();
+    assert_eq!((hedge.size, hedge.realized_pnl), (200, 200));
+    assert_eq!(hedge.unrealized_pnl(2 * ORACLE).unwrap(), 200);
+}
+
+// ==============================================================================
+// FORCED POSITION REDUCTION
+// ==============================================================================
+
+/// Agent whose risk assessment reduces account 1 by a fixed amount
+struct ReducingAgent {
+    reduce_by: u128,
+}
+
+impl OpenClawAgent for ReducingAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn get_market_params(&self, context: &AgentContext) -> Result<MarketParams> {
+        PassthroughAgent.get_market_params(context)
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        PassthroughAgent.decide_liquidity_allocation(context)
+    }
+
+    fn assess_risk(&self, _context: &AgentContext) -> Result<RiskAssessment> {
+        let mut actions = RiskActions::default();
+        actions.close_positions[0] = PositionReduction {
+            account_idx: 1,
+            reduce_by: self.reduce_by,
+        };
+        actions.close_positions_len = 1;
+        Ok(RiskAssessment {
+            risk_level_bps: 9000,
+            actions,
+        })
+    }
+
+    fn detect_anomalies(&self, context: &AgentContext) -> Result<AnomalyResponse> {
+        PassthroughAgent.detect_anomalies(context)
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> Result<bool> {
+        PassthroughAgent.should_shutdown(context)
+    }
+}
+
+#[test]
+fn test_close_positions_partial_and_dust() {
+    let mut engine = funded_engine();
+    let mut venue = HalfFillVenue { price: ORACLE };
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 500_000, 1)
+        .unwrap();
+    let insurance_before = engine.risk_engine().insurance_fund.balance.get();
+
+    // Partial: 500_000 -> 300_000 (remainder above min_liquidation_abs)
+    engine
+        .apply_risk_assessment(&ReducingAgent { reduce_by: 200_000 }, &mut venue, 2, ORACLE_PX)
+        .unwrap();
+    assert_eq!(engine.account_view(1, ORACLE).unwrap().position_size, 300_000);
+    assert!(engine.risk_engine().insurance_fund.balance.get() > insurance_before);
+
+    // Remainder 50_000 would be dust, so the whole position closes
+    engine
+        .apply_risk_assessment(&ReducingAgent { reduce_by: 250_000 }, &mut venue, 3, ORACLE_PX)
+        .unwrap();
+    assert_eq!(engine.account_view(1, ORACLE).unwrap().position_size, 0);
+}
+
+/// Agent whose risk assessment reduces every listed account
+struct BatchReducingAgent {
+    reductions: [(u16, u128); 2],
+}
+
+impl OpenClawAgent for BatchReducingAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn get_market_params(&self, context: &AgentContext) -> Result<MarketParams> {
+        PassthroughAgent.get_market_params(context)
+    }
+
+    fn assess_risk(&self, _context: &AgentContext) -> Result<RiskAssessment> {
+        let mut actions = RiskActions::default();
+        for (slot, &(account_idx, reduce_by)) in self.reductions.iter().enumerate() {
+            actions.close_positions[slot] = PositionReduction { account_idx, reduce_by };
+        }
+        actions.close_positions_len = self.reductions.len();
+        Ok(RiskAssessment { risk_level_bps: 9000, actions })
+    }
+}
+
+#[test]
+fn test_close_positions_apply_all_or_none() {
+    let mut engine = funded_engine();
+    let mut venue = HalfFillVenue { price: ORACLE };
+    let other = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.risk_engine_mut().deposit(other, 1_000_000, 0).unwrap();
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 500_000, 1).unwrap();
+    engine.execute_trade(&PassthroughAgent, other, ORACLE_PX, -400_000, 1).unwrap();
+    let agent = BatchReducingAgent { reductions: [(1, 200_000), (other, 200_000)] };
+
+    // The second reduction's funding settlement cannot fit: nothing executes
+    let account = &mut engine.risk_engine_mut().accounts[other as usize];
+    account.funding_index = I128::new(i128::MIN);
+    let before = engine.risk_engine().accounts[1];
+    assert_eq!(
+        engine.apply_risk_assessment(&agent, &mut venue, 2, ORACLE_PX).unwrap_err(),
+        RiskError::Overflow
+    );
+    assert_eq!(engine.risk_engine().accounts[1], before);
+    assert_eq!(engine.current_epoch().liquidations.forced_reductions, 0);
+
+    engine.risk_engine_mut().accounts[other as usize].funding_index = I128::ZERO;
+    engine.apply_risk_assessment(&agent, &mut venue, 2, ORACLE_PX).unwrap();
+    assert_eq!(engine.account_view(1, ORACLE).unwrap().position_size, 300_000);
+    assert_eq!(engine.account_view(other, ORACLE).unwrap().position_size, -200_000);
+    assert_eq!(engine.current_epoch().liquidations.forced_reductions, 2);
+}
+
+// ==============================================================================
+// ANOMALY ESCALATION
+// ==============================================================================
+
+/// Agent that reports a fixed anomaly with every action requested
+struct AlarmedAgent {
+    severity_bps: u64,
+}
+
+impl OpenClawAgent for AlarmedAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn get_market_params(&self, context: &AgentContext) -> Result<MarketParams> {
+        PassthroughAgent.get_market_params(context)
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        PassthroughAgent.decide_liquidity_allocation(context)
+    }
+
+    fn assess_risk(&self, context: &AgentContext) -> Result<RiskAssessment> {
+        PassthroughAgent.assess_risk(context)
+    }
+
+    fn detect_anomalies(&self, _context: &AgentContext) -> Result<AnomalyResponse> {
+        Ok(AnomalyResponse {
+            anomaly_type: AnomalyType::OracleManipulation,
+            severity_bps: self.severity_bps,
+            actions: AnomalyActions {
+                freeze_market: true,
+                reduce_limits: Some(1_000),
+                stop_trading: true,
+                initiate_shutdown: true,
+            },
+        })
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> Result<bool> {
+        PassthroughAgent.should_shutdown(context)
+    }
+}
+
+#[test]
+fn test_low_severity_anomaly_only_logged() {
+    let mut engine = funded_engine();
+    let outcome = engine
+        .check_anomalies(&AlarmedAgent { severity_bps: 1000 }, ORACLE_PX)
+        .unwrap();
+    assert_eq!(outcome.level, EscalationLevel::Logged);
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000, 1)
+        .unwrap();
+
+    let outcome = engine
+        .check_anomalies(&AlarmedAgent { severity_bps: 3000 }, ORACLE_PX)
+        .unwrap();
+    assert_eq!(outcome.level, EscalationLevel::Restricted);
+    assert!(engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000, 1)
+        .is_err());
+}
+
+#[test]
+fn test_shutdown_requires_sustained_severity() {
+    let mut engine = funded_engine();
+    let agent = AlarmedAgent { severity_bps: 9000 };
+
+    for streak in 1..3 {
+        let outcome = engine.check_anomalies(&agent, ORACLE_PX).unwrap();
+        assert_eq!(outcome.level, EscalationLevel::ShutdownPending);
+        assert_eq!(outcome.shutdown_streak, streak);
+    }
+
+    // A calmer check resets the streak
+    let outcome = engine
+        .check_anomalies(&AlarmedAgent { severity_bps: 6000 }, ORACLE_PX)
+        .unwrap();
+    assert_eq!((outcome.level, outcome.shutdown_streak), (EscalationLevel::Frozen, 0));
+
+    for _ in 0..2 {
+        engine.check_anomalies(&agent, ORACLE_PX).unwrap();
+    }
+    let outcome = engine.check_anomalies(&agent, ORACLE_PX).unwrap();
+    assert_eq!(outcome.level, EscalationLevel::Shutdown);
+
+    let bad = EscalationPolicy {
+        freeze_threshold_bps: 9000,
+        ..EscalationPolicy::default()
+    };
+    assert_eq!(engine.set_escalation_policy(bad), Err(RiskError::Overflow));
+}
+
+#[test]
+fn test_anomaly_deescalation_after_calm_period() {
+    let mut engine = funded_engine();
+    engine
+        .check_anomalies(&AlarmedAgent { severity_bps: 6000 }, ORACLE_PX)
+        .unwrap();
+    assert_eq!(engine.freeze_origin(), Some(FreezeOrigin::Anomaly));
+
+    for i in 1..5 {
+        let outcome = engine.check_anomalies(&PassthroughAgent, ORACLE_PX).unwrap();
+        assert!(!outcome.deescalated, "lifted after only {} calm checks", i);
+    }
+    let outcome = engine.check_anomalies(&PassthroughAgent, ORACLE_PX).unwrap();
+    assert!(outcome.deescalated);
+    assert_eq!(engine.freeze_origin(), None);
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000, 1)
+        .unwrap();
+
+    let history = engine.build_context(ORACLE_PX).anomaly_history;
+    assert_eq!(history.len(), 6);
+    assert_eq!(history.get(0).unwrap().level, EscalationLevel::Frozen);
+    assert_eq!(history.latest().unwrap().severity_bps, 0);
+
+    // Admin freezes survive calm periods
+    engine.set_market_frozen(true);
+    for _ in 0..10 {
+        engine.check_anomalies(&PassthroughAgent, ORACLE_PX).unwrap();
+    }
+    assert_eq!(engine.freeze_origin(), Some(FreezeOrigin::Admin));
+    assert_eq!(engine.anomaly_history().len(), ANOMALY_HISTORY_LEN);
+}
+
+// ==============================================================================
+// ORACLE DIVERGENCE
+// ==============================================================================
+
+#[test]
+fn test_oracle_divergence_enters_risk_reduction() {
+    let mut engine = funded_engine();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 300_000, 1)
+        .unwrap();
+
+    // 1% apart: within the default 2% bound
+    engine.crank(1, 2, ORACLE, Some(ORACLE + ORACLE / 100)).unwrap();
+    assert!(!engine.risk_reduction_mode());
+
+    // 5% apart: protocol enters risk-reduction mode on its own
+    engine.crank(1, 3, ORACLE, Some(ORACLE + ORACLE / 20)).unwrap();
+    assert!(engine.risk_reduction_mode());
+    let context = engine.build_context(ORACLE_PX);
+    assert_eq!(context.oracle_divergence_bps, 500);
+    assert_eq!(context.protocol_anomaly, Some(AnomalyType::OracleManipulation));
+    assert!(context.risk_reduction_mode);
+
+    // Only position-reducing trades are allowed
+    assert_eq!(
+        engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000, 3),
+        Err(RiskError::Unauthorized)
+    );
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, -100_000, 3)
+        .unwrap();
+
+    // Oracles agree again
+    engine.crank(1, 4, ORACLE, Some(ORACLE)).unwrap();
+    assert!(!engine.risk_reduction_mode());
+    assert_eq!(engine.build_context(ORACLE_PX).protocol_anomaly, None);
+}
+
+// ==============================================================================
+// ORACLE STALENESS AND CONFIDENCE
+// ==============================================================================
+
+#[test]
+fn test_stale_or_uncertain_oracle_rejects_trades() {
+    let mut engine = funded_engine();
+    engine.crank(1, 40, ORACLE, None).unwrap();
+
+    // Published 40 slots ago: stale under the default 25-slot bound
+    let context = engine.build_context(ORACLE_PX);
+    assert!(context.oracle_stale);
+    assert_eq!(context.oracle_publish_slot, 0);
+    assert_eq!(
+        engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000, 40),
+        Err(RiskError::Unauthorized)
+    );
+
+    // Fresh price, but a 5% confidence interval exceeds the 2% bound
+    let wide = OraclePrice::new(ORACLE, ORACLE / 20, 40);
+    assert_eq!(wide.confidence_bps(), 500);
+    assert!(!engine.build_context(wide).oracle_stale);
+    assert_eq!(
+        engine.execute_trade(&PassthroughAgent, 1, wide, 1_000, 40),
+        Err(RiskError::Unauthorized)
+    );
+
+    // Fresh and tight
+    let fresh = OraclePrice::new(ORACLE, ORACLE / 1000, 38);
+    engine
+        .execute_trade(&PassthroughAgent, 1, fresh, 1_000, 40)
+        .unwrap();
+
+    // Loosened bounds accept the wide price; invalid bounds are refused
+    let bounds = OracleBounds {
+        max_confidence_bps: 1_000,
+        ..*engine.oracle_bounds()
+    };
+    engine.set_oracle_bounds(bounds).unwrap();
+    engine
+        .execute_trade(&PassthroughAgent, 1, wide, 1_000, 40)
+        .unwrap();
+    assert_eq!(
+        engine.set_oracle_bounds(OracleBounds {
+            max_confidence_bps: 0,
+            ..bounds
+        }),
+        Err(RiskError::Overflow)
+    );
+}
+
+// ==============================================================================
+// ORACLE ADAPTERS
+// ==============================================================================
+
+#[test]
+fn test_oracle_normalization() {
+    use percolator::clawcolator::oracle::{normalize_confidence, normalize_price};
+
+    assert_eq!(normalize_price(6_500_000_000, -8).unwrap(), 65_000_000);
+    assert_eq!(normalize_price(65, 0).unwrap(), 65_000_000);
+    assert_eq!(normalize_confidence(1_234_567, -8).unwrap(), 12_346);
+    assert_eq!(normalize_confidence(1, -40).unwrap(), 1);
+    assert_eq!(normalize_price(0, -8), Err(RiskError::Overflow));
+    assert_eq!(normalize_price(-1, -8), Err(RiskError::Overflow));
+    assert_eq!(normalize_price(1, -9), Err(RiskError::Overflow)); // rounds to zero
+    assert_eq!(normalize_price(1, 30), Err(RiskError::Overflow));
+}
+
+#[cfg(feature = "pyth")]
+#[test]
+fn test_pyth_price_update_parsing() {
+    use percolator::clawcolator::oracle::pyth::*;
+
+    let feed = [7u8; 32];
+    let mut data = [0u8; 134];
+    data[..8].copy_from_slice(&PRICE_UPDATE_V2_DISCRIMINATOR);
+    data[40] = 1; // Full verification
+    data[41..73].copy_from_slice(&feed);
+    data[73..81].copy_from_slice(&6_500_000_000i64.to_le_bytes());
+    data[81..89].copy_from_slice(&1_234_567u64.to_le_bytes());
+    data[89..93].copy_from_slice(&(-8i32).to_le_bytes());
+    data[93..101].copy_from_slice(&1_700_000_000i64.to_le_bytes());
+    data[125..133].copy_from_slice(&42u64.to_le_bytes());
+
+    let update = parse_price_update(&data[..133], &feed).unwrap();
+    assert_eq!(update.price, OraclePrice::new(65_000_000, 12_346, 42));
+    assert_eq!(update.publish_time, 1_700_000_000);
+
+    // Wrong feed, partial verification, foreign account and truncation
+    assert_eq!(parse_price_update(&data, &[0u8; 32]), Err(RiskError::Unauthorized));
+    let mut partial = data;
+    partial[40] = 0;
+    assert_eq!(parse_price_update(&partial, &feed), Err(RiskError::Unauthorized));
+    let mut foreign = data;
+    foreign[0] ^= 1;
+    assert_eq!(parse_price_update(&foreign, &feed), Err(RiskError::Unauthorized));
+    assert_eq!(parse_price_update(&data[..100], &feed), Err(RiskError::Overflow));
+}
+
+#[cfg(feature = "switchboard")]
+#[test]
+fn test_switchboard_pull_feed_parsing() {
+    use percolator::clawcolator::oracle::switchboard::*;
+
+    let mut data = [0u8; RESULT_OFFSET + 128];
+    data[..8].copy_from_slice(&PULL_FEED_DISCRIMINATOR);
+    let value: i128 = 65 * 10i128.pow(18);
+    let std_dev: i128 = 5 * 10i128.pow(15); // 0.005
+    data[RESULT_OFFSET..RESULT_OFFSET + 16].copy_from_slice(&value.to_le_bytes());
+    data[RESULT_OFFSET + 16..RESULT_OFFSET + 32].copy_from_slice(&std_dev.to_le_bytes());
+    data[RESULT_OFFSET + 104..RESULT_OFFSET + 112].copy_from_slice(&99u64.to_le_bytes());
+
+    // No samples yet
+    assert_eq!(parse_pull_feed(&data), Err(RiskError::Unauthorized));
+
+    data[RESULT_OFFSET + 96] = 3;
+    assert_eq!(
+        parse_pull_feed(&data).unwrap(),
+        OraclePrice::new(65_000_000, 5_000, 99)
+    );
+    assert_eq!(parse_pull_feed(&data[..RESULT_OFFSET]), Err(RiskError::Overflow));
+}
+
+// ==============================================================================
+// KEEPER INCENTIVES
+// ==============================================================================
+
+#[test]
+fn test_keeper_rewards_accrue_and_claim() {
+    let mut engine = funded_engine();
+    engine.risk_engine_mut().top_up_insurance_fund(1_000).unwrap();
+
+    // Disabled by default
+    engine.crank(1, 1, ORACLE, None).unwrap();
+    assert_eq!(engine.pending_keeper_rewards(1), 0);
+
+    engine.set_keeper_reward_params(KeeperRewardParams {
+        crank_reward: 300,
+        liquidation_bounty: 1_000,
+        max_reward_per_crank: 500,
+        insurance_floor: 400,
+    });
+    engine.crank(1, 2, ORACLE, None).unwrap();
+    assert_eq!(engine.pending_keeper_rewards(1), 300);
+
+    // Same slot: crank does not advance, nothing accrues
+    engine.crank(1, 2, ORACLE, None).unwrap();
+    assert_eq!(engine.pending_keeper_rewards(1), 300);
+
+    // Capped by insurance above the floor net of outstanding claims
+    engine.crank(1, 3, ORACLE, None).unwrap();
+    assert_eq!(engine.pending_keeper_rewards(1), 600);
+    assert_eq!(engine.keeper_ledger().pending_total(), 600);
+    assert_eq!(engine.risk_engine().insurance_fund.balance.get(), 1_000);
+
+    let capital = engine.risk_engine().accounts[1].capital.get();
+    assert_eq!(engine.claim_keeper_rewards(1).unwrap(), 600);
+    assert_eq!(engine.pending_keeper_rewards(1), 0);
+    assert_eq!(engine.keeper_ledger().paid_total(), 600);
+    assert_eq!(engine.risk_engine().insurance_fund.balance.get(), 400);
+    assert_eq!(engine.risk_engine().accounts[1].capital.get(), capital + 600);
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+
+    assert_eq!(
+        engine.claim_keeper_rewards(9),
+        Err(RiskError::AccountNotFound)
+    );
+}
+
+// ==============================================================================
+// DUST CLEANUP
+// ==============================================================================
+
+/// Agent that publishes fixed market parameters
+struct ParamsAgent {
+    params: MarketParams,
+}
+
+impl ParamsAgent {
+    fn dust(policy: DustPolicy) -> Self {
+        Self {
+            params: MarketParams {
+                dust_policy: policy,
+                ..MarketParams::default()
+            },
+        }
+    }
+}
+
+impl OpenClawAgent for ParamsAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(self.params)
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        PassthroughAgent.decide_liquidity_allocation(context)
+    }
+
+    fn assess_risk(&self, context: &AgentContext) -> Result<RiskAssessment> {
+        PassthroughAgent.assess_risk(context)
+    }
+
+    fn detect_anomalies(&self, context: &AgentContext) -> Result<AnomalyResponse> {
+        PassthroughAgent.detect_anomalies(context)
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> Result<bool> {
+        PassthroughAgent.should_shutdown(context)
+    }
+}
+
+#[test]
+fn test_dust_sweep_closes_idle_accounts() {
+    let mut engine = funded_engine();
+    let dust = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.risk_engine_mut().deposit(dust, 500, 0).unwrap();
+
+    // Policies outside protocol bounds are rejected
+    let too_wide = DustPolicy {
+        maintenance_fee_per_slot: None,
+        dust_capital_threshold: 20_000,
+        idle_slots: 1_000,
+    };
+    assert_eq!(
+        engine.update_market_params(&ParamsAgent::dust(too_wide)),
+        Err(RiskError::Overflow)
+    );
+    let too_eager = DustPolicy {
+        dust_capital_threshold: 1_000,
+        idle_slots: 10,
+        ..too_wide
+    };
+    assert_eq!(
+        engine.update_market_params(&ParamsAgent::dust(too_eager)),
+        Err(RiskError::Overflow)
+    );
+
+    let policy = DustPolicy {
+        maintenance_fee_per_slot: Some(0),
+        dust_capital_threshold: 1_000,
+        idle_slots: 1_000,
+    };
+    engine.update_market_params(&ParamsAgent::dust(policy)).unwrap();
+
+    // First sweep only starts the idle clock
+    engine.crank(0, 10, ORACLE, None).unwrap();
+    assert_eq!(engine.last_dust_sweep().closed_len, 0);
+    engine.crank(0, 500, ORACLE, None).unwrap();
+    assert_eq!(engine.last_dust_sweep().closed_len, 0);
+
+    let insurance = engine.risk_engine().insurance_fund.balance.get();
+    engine.crank(0, 1_010, ORACLE, None).unwrap();
+    let sweep = *engine.last_dust_sweep();
+    assert_eq!(sweep.slot, 1_010);
+    assert_eq!(sweep.swept_total, 500);
+    assert_eq!(sweep.events().len(), 1);
+    assert_eq!(sweep.events()[0].account_idx, dust);
+    assert_eq!(sweep.events()[0].swept_capital, 500);
+    assert!(!engine.risk_engine().is_used(dust as usize));
+    assert!(engine.risk_engine().is_used(1));
+    assert_eq!(engine.risk_engine().insurance_fund.balance.get(), insurance + 500);
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+}
+
+// ==============================================================================
+// WITHDRAWAL QUEUE
+// ==============================================================================
+
+fn withdrawal_agent(delay_slots: u64, expedite_utilization_bps: u64) -> ParamsAgent {
+    ParamsAgent {
+        params: MarketParams {
+            withdrawal_policy: WithdrawalPolicy {
+                queue_threshold: 100_000,
+                delay_slots,
+                expedite_utilization_bps,
+            },
+            ..MarketParams::default()
+        },
+    }
+}
+
+#[test]
+fn test_withdrawal_queue_delays_large_withdrawals() {
+    let mut engine = funded_engine();
+    engine.update_market_params(&withdrawal_agent(100, 0)).unwrap();
+    engine.crank(0, 1, ORACLE, None).unwrap();
+
+    // Small withdrawals are paid at once
+    assert_eq!(
+        engine.request_withdrawal(1, 50_000, 1, ORACLE),
+        Ok(WithdrawalStatus::Executed)
+    );
+    assert_eq!(
+        engine.request_withdrawal(1, 200_000, 1, ORACLE),
+        Ok(WithdrawalStatus::Queued { release_slot: 101 })
+    );
+    assert_eq!(
+        engine.request_withdrawal(1, 900_000, 1, ORACLE),
+        Err(RiskError::InsufficientBalance)
+    );
+    let status = engine.build_context(ORACLE_PX).withdrawal_queue;
+    assert_eq!(status.pending_count, 1);
+    assert_eq!(status.pending_amount, 200_000);
+    assert_eq!(status.next_release_slot, Some(101));
+
+    engine.crank(0, 50, ORACLE, None).unwrap();
+    assert_eq!(engine.withdrawal_queue().len(), 1);
+
+    // Held while the market is frozen, released once it thaws
+    engine.set_market_frozen(true);
+    engine.crank(0, 150, ORACLE, None).unwrap();
+    assert!(engine.build_context(ORACLE_PX).withdrawal_queue.paused);
+    assert_eq!(engine.withdrawal_queue().len(), 1);
+    engine.set_market_frozen(false);
+    engine.crank(0, 160, ORACLE, None).unwrap();
+    assert!(engine.withdrawal_queue().is_empty());
+    assert_eq!(engine.risk_engine().accounts[1].capital.get(), 750_000);
+
+    // Idle pool: queued withdrawals are expedited
+    engine.update_market_params(&withdrawal_agent(100, 5_000)).unwrap();
+    engine.request_withdrawal(1, 150_000, 160, ORACLE).unwrap();
+    engine.crank(0, 161, ORACLE, None).unwrap();
+    assert!(engine.withdrawal_queue().is_empty());
+    assert_eq!(engine.risk_engine().accounts[1].capital.get(), 600_000);
+
+    // Policies outside protocol bounds are rejected
+    assert_eq!(
+        engine.update_market_params(&withdrawal_agent(1_000_000, 0)),
+        Err(RiskError::Overflow)
+    );
+}
+
+#[test]
+fn test_immediate_withdrawal_leaves_queued_amounts() {
+    let mut engine = funded_engine();
+    engine.update_market_params(&withdrawal_agent(100, 0)).unwrap();
+    engine.crank(0, 1, ORACLE, None).unwrap();
+    assert_eq!(
+        engine.request_withdrawal(1, 940_000, 1, ORACLE),
+        Ok(WithdrawalStatus::Queued { release_slot: 101 })
+    );
+
+    // Small withdrawals skip the queue but not the capital it reserves
+    assert_eq!(
+        engine.request_withdrawal(1, 90_000, 1, ORACLE),
+        Err(RiskError::InsufficientBalance)
+    );
+    assert_eq!(
+        engine.request_withdrawal(1, 50_000, 1, ORACLE),
+        Ok(WithdrawalStatus::Executed)
+    );
+
+    engine.crank(0, 101, ORACLE, None).unwrap();
+    assert!(engine.withdrawal_queue().is_empty());
+    assert_eq!(engine.risk_engine().accounts[1].capital.get(), 10_000);
+}
+
+// ==============================================================================
+// LP POOL
+// ==============================================================================
+
+/// Agent that caps LP pool utilization at 1%
+struct CappedPoolAgent;
+
+impl OpenClawAgent for CappedPoolAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn get_market_params(&self, context: &AgentContext) -> Result<MarketParams> {
+        PassthroughAgent.get_market_params(context)
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        PassthroughAgent.decide_liquidity_allocation(context)
+    }
+
+    fn assess_risk(&self, context: &AgentContext) -> Result<RiskAssessment> {
+        PassthroughAgent.assess_risk(context)
+    }
+
+    fn detect_anomalies(&self, context: &AgentContext) -> Result<AnomalyResponse> {
+        PassthroughAgent.detect_anomalies(context)
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> Result<bool> {
+        PassthroughAgent.should_shutdown(context)
+    }
+
+    fn max_pool_utilization_bps(&self, _context: &AgentContext) -> Result<u64> {
+        Ok(100)
+    }
+}
+
+#[test]
+fn test_lp_pool_shares_track_lp_equity() {
+    let mut engine = funded_engine();
+    let operator = [0u8; 32];
+    let alice = [1u8; 32];
+
+    // Operator's existing equity is minted first, so Alice buys in at 1:1
+    assert_eq!(engine.pool_deposit(alice, 1_000_000, 1, ORACLE).unwrap(), 1_000_000);
+    assert_eq!(engine.lp_pool().total_shares(), 11_000_000);
+    assert_eq!(engine.lp_pool().provider(&operator).unwrap().shares, 10_000_000);
+
+    // Utilization cap: 2M notional at 10% initial margin is ~1.8% of LP equity
+    assert_eq!(
+        engine.execute_trade(&CappedPoolAgent, 1, ORACLE_PX, 2_000_000, 1),
+        Err(RiskError::Undercollateralized)
+    );
+    engine
+        .execute_trade(&CappedPoolAgent, 1, ORACLE_PX, 500_000, 1)
+        .unwrap();
+
+    // LP is short; a 10% rally is a loss shared pro-rata
+    let up = ORACLE + ORACLE / 10;
+    let alice_pos = engine.pool_position(&alice, up).unwrap();
+    let operator_pos = engine.pool_position(&operator, up).unwrap();
+    assert!(alice_pos.pnl < 0);
+    assert!(operator_pos.value > 9 * alice_pos.value);
+    assert!(operator_pos.value <= 10 * alice_pos.value + 10);
+
+    // Burning shares pays their value out of the LP account
+    engine.crank(1, 2, ORACLE, None).unwrap();
+    let value = engine.pool_position(&alice, ORACLE).unwrap().value;
+    assert_eq!(engine.pool_withdraw(alice, 1_000_000, 2, ORACLE), Ok(value));
+    assert!(engine.lp_pool().provider(&alice).is_none());
+    assert_eq!(engine.lp_pool().total_shares(), 10_000_000);
+    assert_eq!(
+        engine.pool_withdraw(alice, 1, 2, ORACLE),
+        Err(RiskError::InsufficientBalance)
+    );
+}
+
+// ==============================================================================
+// FEE DISTRIBUTION
+// ==============================================================================
+
+fn fee_agent(lp_bps: u64, insurance_bps: u64, treasury_bps: u64) -> ParamsAgent {
+    ParamsAgent {
+        params: MarketParams {
+            fee_split: FeeSplit {
+                lp_bps,
+                insurance_bps,
+                treasury_bps,
+            },
+            ..MarketParams::default()
+        },
+    }
+}
+
+#[test]
+fn test_trading_fees_split_between_lp_insurance_and_treasury() {
+    let mut engine = funded_engine();
+    let treasury = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.set_treasury_account(treasury).unwrap();
+
+    // Split must sum to 100% and respect protocol ranges
+    assert_eq!(
+        engine.update_market_params(&fee_agent(5_000, 3_000, 1_000)),
+        Err(RiskError::Overflow)
+    );
+    assert_eq!(
+        engine.update_market_params(&fee_agent(5_000, 1_000, 4_000)),
+        Err(RiskError::Overflow)
+    );
+    engine.update_market_params(&fee_agent(5_000, 3_000, 2_000)).unwrap();
+
+    let risk = engine.risk_engine();
+    let (lp_capital, insurance) = (risk.accounts[0].capital.get(), risk.insurance_fund.balance.get());
+
+    // 1M notional at 10 bps = 1_000 fee
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000_000, 1)
+        .unwrap();
+    let risk = engine.risk_engine();
+    assert_eq!(risk.accounts[0].capital.get(), lp_capital + 500);
+    assert_eq!(risk.accounts[treasury as usize].capital.get(), 200);
+    assert_eq!(risk.insurance_fund.balance.get(), insurance + 300);
+    assert!(risk.check_conservation(ORACLE));
+
+    let expected = FeeTotals {
+        lp: 500,
+        insurance: 300,
+        treasury: 200,
+        ..FeeTotals::default()
+    };
+    assert_eq!(*engine.fee_totals(), expected);
+    assert_eq!(engine.roll_fee_epoch(), expected);
+    assert_eq!(engine.epoch_fee_totals().total(), 0);
+    assert_eq!(engine.fee_totals().total(), 1_000);
+}
+
+// ==============================================================================
+// EPOCH ACCOUNTING
+// ==============================================================================
+
+#[test]
+fn test_epoch_rollover_finalizes_report() {
+    let mut engine = funded_engine();
+    engine.set_epoch_slots(100);
+
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000_000, 1)
+        .unwrap();
+    engine
+        .check_anomalies(&AlarmedAgent { severity_bps: 1000 }, ORACLE_PX)
+        .unwrap();
+
+    engine.crank(1, 50, ORACLE, None).unwrap();
+    assert!(engine.epoch_history().is_empty());
+    assert_eq!(engine.current_epoch().scorecard.trades_accepted, 1);
+
+    engine.crank(1, 100, ORACLE, None).unwrap();
+    let report = *engine.epoch_history().latest().unwrap();
+    assert_eq!((report.epoch, report.start_slot, report.end_slot), (0, 0, 100));
+    assert_eq!(report.fees.total(), 1_000);
+    assert_eq!(report.scorecard.volume, 1_000_000);
+    assert_eq!(report.scorecard.anomalies_flagged, 1);
+    assert_eq!(report.scorecard.max_escalation, EscalationLevel::Logged);
+    assert!(report.scorecard.lp_equity_end > 0);
+
+    // Next epoch starts fresh from the previous end
+    let current = engine.current_epoch();
+    assert_eq!((current.epoch, current.start_slot), (1, 100));
+    assert_eq!(current.scorecard.lp_equity_start, report.scorecard.lp_equity_end);
+    assert_eq!(current.scorecard.trades_accepted, 0);
+    assert_eq!(engine.epoch_fee_totals().total(), 0);
+
+    for slot in 1..=EPOCH_HISTORY_LEN as u64 + 2 {
+        engine.crank(1, 100 + slot * 100, ORACLE, None).unwrap();
+    }
+    assert_eq!(engine.epoch_history().len(), EPOCH_HISTORY_LEN);
+    assert_eq!(engine.epoch_history().latest().unwrap().epoch, EPOCH_HISTORY_LEN as u64 + 2);
+}
+
+// ==============================================================================
+// RISK REDUCTION MODE
+// ==============================================================================
+
+#[test]
+fn test_risk_reduction_mode_semantics() {
+    let mut engine = funded_engine();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000_000, 1)
+        .unwrap();
+
+    engine.set_risk_reduction_admin(true);
+    assert!(engine.risk_reduction_mode());
+    let context = engine.build_context(ORACLE_PX);
+    assert!(context.risk_reduction_mode);
+    assert!(context.risk_reduction_triggers.admin);
+
+    // Only position-reducing trades go through
+    assert_eq!(
+        engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 100_000, 2),
+        Err(RiskError::Unauthorized)
+    );
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, -400_000, 2)
+        .unwrap();
+
+    // Withdrawals limited to 1% of the vault per window
+    engine
+        .set_risk_reduction_limits(RiskReductionLimits {
+            max_withdrawal_bps_per_window: 100,
+            window_slots: 150,
+        })
+        .unwrap();
+    let budget = engine.risk_engine().vault.get() / 100;
+    assert_eq!(engine.withdrawal_allowance(10), budget);
+    engine.request_withdrawal(1, budget / 2, 10, ORACLE).unwrap();
+    assert_eq!(
+        engine.request_withdrawal(1, budget / 2 + 1, 20, ORACLE),
+        Err(RiskError::Unauthorized)
+    );
+    engine.request_withdrawal(1, budget / 2 + 1, 160, ORACLE).unwrap();
+
+    // Params may tighten but not loosen
+    let tighter = MarketParams {
+        max_leverage_bps: 500,
+        spread_bps: 20,
+        ..MarketParams::default()
+    };
+    engine.update_market_params(&ParamsAgent { params: tighter }).unwrap();
+    assert_eq!(
+        engine.update_market_params(&ParamsAgent {
+            params: MarketParams::default()
+        }),
+        Err(RiskError::Unauthorized)
+    );
+
+    engine.set_risk_reduction_admin(false);
+    assert!(!engine.risk_reduction_mode());
+    engine
+        .update_market_params(&ParamsAgent {
+            params: MarketParams::default(),
+        })
+        .unwrap();
+}
+
+// ==============================================================================
+// OPERATOR KILL-SWITCH
+// ==============================================================================
+
+const OPERATOR_KEY: [u8; 32] = [7; 32];
+
+/// Test verifier: a "signature" is the pubkey followed by the message
+struct EchoVerifier;
+
+impl SignatureVerifier for EchoVerifier {
+    fn verify(&self, pubkey: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+        signature[..32] == pubkey[..] && signature[32..32 + message.len()] == *message
+    }
+}
+
+fn operator_sign(key: [u8; 32], command: OperatorCommand, nonce: u64) -> [u8; 64] {
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(&key);
+    signature[32..32 + OPERATOR_MESSAGE_LEN].copy_from_slice(&command.encode(nonce));
+    signature
+}
+
+#[test]
+fn test_operator_kill_switch() {
+    let mut engine = funded_engine();
+    let freeze = OperatorCommand::Freeze(true);
+
+    // No operator configured
+    assert_eq!(
+        engine.execute_operator_command(&EchoVerifier, freeze, 1, &operator_sign(OPERATOR_KEY, freeze, 1)),
+        Err(RiskError::Unauthorized)
+    );
+    engine.set_operator(Some(OPERATOR_KEY));
+
+    // Wrong key, then a signature for a different nonce
+    assert_eq!(
+        engine.execute_operator_command(&EchoVerifier, freeze, 1, &operator_sign([8; 32], freeze, 1)),
+        Err(RiskError::Unauthorized)
+    );
+    assert_eq!(
+        engine.execute_operator_command(&EchoVerifier, freeze, 1, &operator_sign(OPERATOR_KEY, freeze, 2)),
+        Err(RiskError::Unauthorized)
+    );
+
+    engine
+        .execute_operator_command(&EchoVerifier, freeze, 1, &operator_sign(OPERATOR_KEY, freeze, 1))
+        .unwrap();
+    assert_eq!(engine.freeze_origin(), Some(FreezeOrigin::Operator));
+    assert!(engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000, 1).is_err());
+
+    // Replayed nonce is refused
+    assert_eq!(
+        engine.execute_operator_command(&EchoVerifier, freeze, 1, &operator_sign(OPERATOR_KEY, freeze, 1)),
+        Err(RiskError::Unauthorized)
+    );
+
+    let unfreeze = OperatorCommand::Freeze(false);
+    engine
+        .execute_operator_command(&EchoVerifier, unfreeze, 2, &operator_sign(OPERATOR_KEY, unfreeze, 2))
+        .unwrap();
+    assert_eq!(engine.freeze_origin(), None);
+
+    let force = OperatorCommand::ForceRiskReduction(true);
+    engine
+        .execute_operator_command(&EchoVerifier, force, 3, &operator_sign(OPERATOR_KEY, force, 3))
+        .unwrap();
+    assert!(engine.risk_reduction_mode());
+    assert!(engine.build_context(ORACLE_PX).risk_reduction_triggers.operator);
+    let release = OperatorCommand::ForceRiskReduction(false);
+    engine
+        .execute_operator_command(&EchoVerifier, release, 4, &operator_sign(OPERATOR_KEY, release, 4))
+        .unwrap();
+    assert!(!engine.risk_reduction_mode());
+
+    // Veto restores the previous params and blocks further agent updates
+    let reckless = MarketParams {
+        max_leverage_bps: 10_000,
+        ..MarketParams::default()
+    };
+    engine.update_market_params(&ParamsAgent { params: reckless }).unwrap();
+    let veto = OperatorCommand::VetoParams(true);
+    engine
+        .execute_operator_command(&EchoVerifier, veto, 10, &operator_sign(OPERATOR_KEY, veto, 10))
+        .unwrap();
+    assert_eq!(engine.market_params().max_leverage_bps, MarketParams::default().max_leverage_bps);
+    assert!(engine.build_context(ORACLE_PX).params_vetoed);
+    assert_eq!(
+        engine.update_market_params(&ParamsAgent { params: reckless }),
+        Err(RiskError::Unauthorized)
+    );
+    let lift = OperatorCommand::VetoParams(false);
+    engine
+        .execute_operator_command(&EchoVerifier, lift, 11, &operator_sign(OPERATOR_KEY, lift, 11))
+        .unwrap();
+    engine.update_market_params(&ParamsAgent { params: reckless }).unwrap();
+}
+
+// ==============================================================================
+// GOVERNANCE
+// ==============================================================================
+
+const GOVERNORS: [[u8; 32]; 3] = [[1; 32], [2; 32], [3; 32]];
+
+fn governance_sign(signer: usize, vote: GovernanceVote, id: u64) -> [u8; 64] {
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(&GOVERNORS[signer]);
+    signature[32..32 + GOVERNANCE_MESSAGE_LEN].copy_from_slice(&vote.encode(id));
+    signature
+}
+
+#[test]
+fn test_governance_multisig_timelock() {
+    let mut engine = funded_engine();
+    engine
+        .enable_governance(GovernanceConfig::new(&GOVERNORS, 2, 100).unwrap())
+        .unwrap();
+
+    // Admin setters are closed once governed
+    assert_eq!(
+        engine.set_oracle_bounds(OracleBounds::default()),
+        Err(RiskError::Unauthorized)
+    );
+    assert_eq!(
+        engine.set_dust_bounds(DustBounds::default()),
+        Err(RiskError::Unauthorized)
+    );
+
+    let approve = GovernanceVote::Approve;
+    let action = ProposalAction::LeverageCeiling(500);
+    assert_eq!(
+        engine.propose_governance_change(&EchoVerifier, action, 0, &governance_sign(1, approve, 0), 10),
+        Err(RiskError::Unauthorized)
+    );
+    let id = engine
+        .propose_governance_change(&EchoVerifier, action, 0, &governance_sign(0, approve, 0), 10)
+        .unwrap();
+    let pending = *engine.pending_proposals().next().unwrap();
+    assert_eq!((pending.id, pending.approval_count(), pending.eta_slot), (id, 1, None));
+
+    // Below threshold: not executable
+    assert_eq!(engine.execute_governance_proposal(id, 500), Err(RiskError::Unauthorized));
+
+    engine
+        .approve_governance_proposal(&EchoVerifier, id, 2, &governance_sign(2, approve, id), 20)
+        .unwrap();
+    assert_eq!(engine.governance().unwrap().proposal(id).unwrap().eta_slot, Some(120));
+    assert_eq!(engine.execute_governance_proposal(id, 119), Err(RiskError::Unauthorized));
+    engine.execute_governance_proposal(id, 120).unwrap();
+    assert_eq!(engine.max_leverage_ceiling_bps(), 500);
+    assert_eq!(engine.pending_proposals().count(), 0);
+    assert_eq!(
+        engine.update_market_params(&ParamsAgent {
+            params: MarketParams::default()
+        }),
+        Err(RiskError::Overflow)
+    );
+
+    // Any signer can cancel a pending proposal
+    let id = engine
+        .propose_governance_change(
+            &EchoVerifier,
+            ProposalAction::DustBounds(DustBounds::default()),
+            1,
+            &governance_sign(1, approve, 1),
+            200,
+        )
+        .unwrap();
+    engine
+        .cancel_governance_proposal(&EchoVerifier, id, 2, &governance_sign(2, GovernanceVote::Cancel, id))
+        .unwrap();
+    assert!(engine.governance().unwrap().proposal(id).is_none());
+}
+
+// ==============================================================================
+// MARKET MODE
+// ==============================================================================
+
+#[test]
+fn test_market_mode_switch_lands_on_crank() {
+    let mut engine = funded_engine();
+    engine.set_market_mode(MarketMode::Classic).unwrap();
+
+    // Staged until the next advancing crank
+    assert_eq!(engine.market_mode(), MarketMode::AgentDriven);
+    assert_eq!(engine.pending_market_mode(), Some(MarketMode::Classic));
+    assert_eq!(
+        engine.execute_classic_trade(&NoOpMatcher, 0, 1, ORACLE_PX, 100_000, 0),
+        Err(RiskError::InvalidMatchingEngine)
+    );
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 100_000, 0)
+        .unwrap();
+
+    engine.crank(1, 1, ORACLE, None).unwrap();
+    assert_eq!(engine.market_mode(), MarketMode::Classic);
+    assert_eq!(engine.pending_market_mode(), None);
+    assert_eq!(engine.build_context(ORACLE_PX).market_mode, MarketMode::Classic);
+    assert_eq!(
+        engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 100_000, 1),
+        Err(RiskError::InvalidMatchingEngine)
+    );
+    engine
+        .execute_classic_trade(&NoOpMatcher, 0, 1, ORACLE_PX, 100_000, 1)
+        .unwrap();
+    assert_eq!(engine.account_view(1, ORACLE).unwrap().position_size, 200_000);
+    assert_eq!(engine.current_epoch().scorecard.trades_accepted, 2);
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+
+    // Reduce-only still applies to the matcher's fill
+    engine.set_risk_reduction_admin(true);
+    assert_eq!(
+        engine.execute_classic_trade(&NoOpMatcher, 0, 1, ORACLE_PX, 100_000, 1),
+        Err(RiskError::Unauthorized)
+    );
+    assert_eq!(engine.account_view(1, ORACLE).unwrap().position_size, 200_000);
+}
+
+#[test]
+fn test_classic_trade_records_bankruptcies() {
+    let mut engine = funded_engine();
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 9_000_000, 0).unwrap();
+    engine.set_market_mode(MarketMode::Classic).unwrap();
+    engine.crank(0, 1, ORACLE, None).unwrap();
+
+    // A close filled 12% below the oracle loses more than the user's capital
+    let venue = FixedVenue {
+        price: 880_000,
+        fill_divisor: 1,
+    };
+    engine
+        .execute_classic_trade(&venue, 0, 1, ORACLE_PX, -9_000_000, 1)
+        .unwrap();
+    let entry = *engine.bankruptcies().get(0).unwrap();
+    assert_eq!((entry.slot, entry.idx, entry.cause), (1, 1, BankruptcyCause::WriteOff));
+    assert!(entry.hole > 0);
+    assert_eq!(engine.risk_engine().write_offs_len, 0);
+}
+
+// ==============================================================================
+// ROUTED EXECUTION
+// ==============================================================================
+
+/// Venue filling a fraction of the size at a fixed price
+struct FixedVenue {
+    price: u64,
+    fill_divisor: i128,
+}
+
+impl MatchingEngine for FixedVenue {
+    fn execute_match(
+        &self,
+        _lp_program: &[u8; 32],
+        _lp_context: &[u8; 32],
+        _lp_account_id: u64,
+        _oracle_price: u64,
+        size: i128,
+    ) -> Result<TradeExecution> {
+        Ok(TradeExecution {
+            price: self.price,
+            size: size / self.fill_divisor,
+        })
+    }
+}
+
+#[test]
+fn test_routed_trade_respects_agent_limit() {
+    let mut engine = funded_engine();
+
+    // Buying: the venue may not charge more than the agent accepted
+    let expensive = FixedVenue {
+        price: ORACLE + 1,
+        fill_divisor: 1,
+    };
+    assert_eq!(
+        engine.execute_trade_routed(&PassthroughAgent, &expensive, 1, ORACLE_PX, 200_000, 1),
+        Err(RiskError::InvalidMatchingEngine)
+    );
+    assert_eq!(engine.account_view(1, ORACLE).unwrap().position_size, 0);
+
+    // Price improvement and a partial fill are passed through
+    let better = FixedVenue {
+        price: ORACLE - 1_000,
+        fill_divisor: 2,
+    };
+    engine
+        .execute_trade_routed(&PassthroughAgent, &better, 1, ORACLE_PX, 200_000, 1)
+        .unwrap();
+    let view = engine.account_view(1, ORACLE).unwrap();
+    assert_eq!(view.position_size, 100_000);
+    assert_eq!(view.pnl, 100); // bought 1_000 below the oracle
+
+    // Selling: the venue may not pay less than the agent accepted
+    let cheap = FixedVenue {
+        price: ORACLE - 1,
+        fill_divisor: 1,
+    };
+    assert_eq!(
+        engine.execute_trade_routed(&PassthroughAgent, &cheap, 1, ORACLE_PX, -50_000, 1),
+        Err(RiskError::InvalidMatchingEngine)
+    );
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+}
+
+// ==============================================================================
+// VAMM FALLBACK
+// ==============================================================================
+
+/// Agent with no liquidity to offer
+struct DryAgent {
+    vamm_depth: u128,
+}
+
+impl OpenClawAgent for DryAgent {
+    fn decide_trade(&self, _context: &AgentContext, _request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(TradeDecision::Reject {
+            reason: TradeRejectionReason::InsufficientLiquidity,
+        })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams {
+            vamm_depth: self.vamm_depth,
+            ..MarketParams::default()
+        })
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        PassthroughAgent.decide_liquidity_allocation(context)
+    }
+
+    fn assess_risk(&self, context: &AgentContext) -> Result<RiskAssessment> {
+        PassthroughAgent.assess_risk(context)
+    }
+
+    fn detect_anomalies(&self, context: &AgentContext) -> Result<AnomalyResponse> {
+        PassthroughAgent.detect_anomalies(context)
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> Result<bool> {
+        PassthroughAgent.should_shutdown(context)
+    }
+}
+
+#[test]
+fn test_vamm_quote_is_worse_than_oracle() {
+    assert_eq!(vamm_quote(0, ORACLE, 1_000, 1_000), None);
+    assert_eq!(vamm_quote(1_000_000, ORACLE, 100_001, 1_000), None);
+    // Buying 10% of the curve costs depth / (depth - dx) = 1/0.9 of the oracle
+    assert_eq!(vamm_quote(1_000_000, ORACLE, 100_000, 1_000), Some(1_111_112));
+    assert_eq!(vamm_quote(1_000_000, ORACLE, -100_000, 1_000), Some(909_090));
+}
+
+#[test]
+fn test_vamm_fallback_fills_when_agent_is_dry() {
+    let mut engine = funded_engine();
+    let agent = DryAgent { vamm_depth: 0 };
+    engine.update_market_params(&agent).unwrap();
+    assert_eq!(
+        engine.execute_trade(&agent, 1, ORACLE_PX, 10_000, 1),
+        Err(RiskError::Unauthorized)
+    );
+
+    let agent = DryAgent { vamm_depth: 1_000_000 };
+    engine.update_market_params(&agent).unwrap();
+    engine.execute_trade(&agent, 1, ORACLE_PX, 10_000, 1).unwrap();
+    let view = engine.account_view(1, ORACLE).unwrap();
+    assert_eq!(view.position_size, 10_000);
+    // 101 paid above the oracle (1_010_102 on a 1M curve) plus an 11 fee
+    assert_eq!(view.capital, 1_000_000 - 101 - 11);
+    assert_eq!(engine.current_epoch().scorecard.vamm_fills, 1);
+
+    // Beyond the per-fill cap the rejection stands
+    assert_eq!(
+        engine.execute_trade(&agent, 1, ORACLE_PX, 200_000, 1),
+        Err(RiskError::Unauthorized)
+    );
+
+    // Depth is bounded by protocol limits
+    assert_eq!(
+        engine.update_market_params(&DryAgent {
+            vamm_depth: VammBounds::default().max_depth + 1
+        }),
+        Err(RiskError::Overflow)
+    );
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+}
+
+// ==============================================================================
+// PRICE IMPACT
+// ==============================================================================
+
+/// Agent quoting a fixed price for every trade
+struct PricedAgent {
+    price: u64,
+}
+
+impl OpenClawAgent for PricedAgent {
+    fn decide_trade(&self, _context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(TradeDecision::Accept {
+            price: self.price,
+            size: request.size,
+        })
+    }
+
+    fn get_market_params(&self, context: &AgentContext) -> Result<MarketParams> {
+        PassthroughAgent.get_market_params(context)
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        PassthroughAgent.decide_liquidity_allocation(context)
+    }
+
+    fn assess_risk(&self, context: &AgentContext) -> Result<RiskAssessment> {
+        PassthroughAgent.assess_risk(context)
+    }
+
+    fn detect_anomalies(&self, context: &AgentContext) -> Result<AnomalyResponse> {
+        PassthroughAgent.detect_anomalies(context)
+    }
+
+    fn should_shutdown(&self, context: &AgentContext) -> Result<bool> {
+        PassthroughAgent.should_shutdown(context)
+    }
+}
+
+#[test]
+fn test_impact_caps_agent_fill_price() {
+    let linear = ImpactModel {
+        curve: ImpactCurve::Linear,
+        coefficient_bps: 100,
+        max_impact_bps: 500,
+    };
+    assert_eq!(linear.impact_bps(1_000, 10_000), 10);
+    assert_eq!(linear.impact_bps(1_000, 0), 500);
+    assert_eq!(DEFAULT_IMPACT_MODEL.impact_bps(1_000, 10_000), 31);
+
+    let mut engine = funded_engine();
+
+    // 1M notional against 10M LP equity: sqrt(10%) * 1% = 31 bps
+    let context = engine.build_context(ORACLE_PX);
+    assert_eq!(context.lp_liquidity, 10_000_000);
+    assert_eq!(context.impact_bps(1_000_000), 31);
+    assert_eq!(context.impact_price(1_000_000), 1_003_100);
+    assert_eq!(context.impact_price(-1_000_000), 996_900);
+
+    // Agent may charge at most spread (10 bps) plus impact
+    assert_eq!(
+        engine.execute_trade(&PricedAgent { price: 1_004_101 }, 1, ORACLE_PX, 1_000_000, 1),
+        Err(RiskError::InvalidMatchingEngine)
+    );
+    engine
+        .execute_trade(&PricedAgent { price: 1_004_100 }, 1, ORACLE_PX, 1_000_000, 1)
+        .unwrap();
+
+    assert_eq!(
+        engine.set_impact_model(ImpactModel {
+            max_impact_bps: 10_000,
+            ..linear
+        }),
+        Err(RiskError::Overflow)
+    );
+    engine.set_impact_model(linear).unwrap();
+    assert_eq!(engine.build_context(ORACLE_PX).impact_model, linear);
+}
+
+// ==============================================================================
+// CONCENTRATION LIMITS
+// ==============================================================================
+
+fn concentration_agent(max_oi_share_bps: u64) -> ParamsAgent {
+    ParamsAgent {
+        params: MarketParams {
+            max_oi_share_bps,
+            ..MarketParams::default()
+        },
+    }
+}
+
+#[test]
+fn test_concentration_limit_caps_user_oi_share() {
+    let mut engine = funded_engine();
+    let other = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.risk_engine_mut().deposit(other, 1_000_000, 0).unwrap();
+
+    assert_eq!(
+        engine.update_market_params(&concentration_agent(0)),
+        Err(RiskError::Overflow)
+    );
+    let agent = concentration_agent(3_000);
+    engine.update_market_params(&agent).unwrap();
+
+    // Below the minimum open interest the limit is not enforced
+    engine.execute_trade(&agent, 1, ORACLE_PX, 400_000, 1).unwrap();
+    engine.execute_trade(&agent, other, ORACLE_PX, -450_000, 1).unwrap();
+    let status = engine.concentration();
+    assert_eq!(status.largest_idx, Some(other));
+    // OI = 400k + 450k users + 50k LP
+    assert_eq!(status.largest_share_bps, 5_000);
+
+    // Growing past 30% of resulting OI (1M+) is refused, shrinking is not
+    assert_eq!(
+        engine.execute_trade(&agent, 1, ORACLE_PX, 100_000, 1),
+        Err(RiskError::Undercollateralized)
+    );
+    engine.execute_trade(&agent, other, ORACLE_PX, 150_000, 1).unwrap();
+    assert_eq!(engine.account_view(other, ORACLE).unwrap().position_size, -300_000);
+}
+
+#[test]
+fn test_concentration_reports_user_aggregate() {
+    let mut engine = funded_engine();
+    let other = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.risk_engine_mut().deposit(other, 1_000_000, 0).unwrap();
+    let sub = engine.open_sub_account(1, 0).unwrap();
+    engine.transfer_to_sub_account(sub, 500_000, ORACLE).unwrap();
+
+    // The parent and its sub-account are each smaller than `other`, together larger
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 200_000, 1).unwrap();
+    engine.execute_trade(&PassthroughAgent, sub, ORACLE_PX, -200_000, 1).unwrap();
+    engine.execute_trade(&PassthroughAgent, other, ORACLE_PX, 300_000, 1).unwrap();
+    let status = engine.concentration();
+    assert_eq!(status.largest_idx, Some(1));
+    // OI = 400k group + 300k other + 300k LP
+    assert_eq!(status.largest_share_bps, 4_000);
+
+    // Contexts report the share as of the last crank
+    assert_eq!(engine.build_context(ORACLE_PX).concentration, ConcentrationStatus::default());
+    engine.crank(AGENT_LP_IDX, 1, ORACLE, None).unwrap();
+    assert_eq!(engine.build_context(ORACLE_PX).concentration, status);
+}
+
+// ==============================================================================
+// MARGIN HELPERS
+// ==============================================================================
+
+#[test]
+fn test_margin_helpers() {
+    use percolator::clawcolator::margin;
+
+    assert_eq!(margin::notional(-2_000_000, 1_500_000), 3_000_000);
+    assert_eq!(margin::margin_required(1_000_000, ORACLE, 500), 50_000);
+    assert_eq!(margin::margin_ratio_bps(100_000, 1_000_000, ORACLE), 1_000);
+    assert_eq!(margin::margin_ratio_bps(100_000, 0, ORACLE), u64::MAX);
+
+    // 10x long liquidates ~5.3% lower at 5% maintenance, short ~4.8% higher
+    assert_eq!(margin::liquidation_price(100_000, 1_000_000, ORACLE, 500), Some(947_369));
+    assert_eq!(margin::liquidation_price(100_000, -1_000_000, ORACLE, 500), Some(1_047_619));
+    // Fully funded long and flat positions never liquidate
+    assert_eq!(margin::liquidation_price(1_000_000, 1_000_000, ORACLE, 500), None);
+    assert_eq!(margin::liquidation_price(100_000, 0, ORACLE, 500), None);
+
+    let mut engine = funded_engine();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, -5_000_000, 1)
+        .unwrap();
+    let summary = engine.margin_summary(1, ORACLE).unwrap();
+    let view = engine.account_view(1, ORACLE).unwrap();
+    assert_eq!(summary.equity, view.equity);
+    assert_eq!(summary.margin_ratio_bps, view.margin_ratio_bps);
+    assert_eq!(summary.notional, 5_000_000);
+    assert_eq!(summary.free_collateral, summary.equity - summary.initial_margin);
+    let liq = summary.liquidation_price.unwrap();
+    assert!(liq > ORACLE);
+    assert!(engine.margin_summary(40, ORACLE).is_none());
+}
+
+#[test]
+fn test_liquidation_price_per_account() {
+    let mut engine = funded_engine();
+    assert_eq!(engine.liquidation_price(1, ORACLE), None);
+
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000_000, 1)
+        .unwrap();
+    let view = engine.account_view(1, ORACLE).unwrap();
+    let liq = engine.liquidation_price(1, ORACLE).unwrap();
+    assert_eq!(view.liquidation_price, Some(liq));
+    assert!(liq < ORACLE);
+
+    // Just above the estimate the account holds, at it the account is liquidatable
+    assert!(!engine.account_view(1, liq + 1).unwrap().undercollateralized);
+    assert!(engine.account_view(1, liq - 1).unwrap().undercollateralized);
+
+    // The agent sees its own LP account's liquidation price (short 5M)
+    let context = engine.build_context(ORACLE_PX);
+    assert_eq!(context.lp_liquidation_price, engine.liquidation_price(AGENT_LP_IDX, ORACLE));
+    assert!(context.lp_liquidation_price.unwrap() > ORACLE);
+}
+
+#[test]
+fn test_liquidation_index_files_positions_and_liquidates_candidates() {
+    let mut engine = funded_engine();
+    engine.risk_engine_mut().top_up_insurance_fund(10_000).unwrap(); // No force-realize
+    assert!(engine.liquidation_index().is_empty());
+
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000_000, 1)
+        .unwrap();
+    let liq = engine.liquidation_price(1, ORACLE).unwrap();
+    let index = engine.liquidation_index();
+    assert_eq!(index.len(), 2);
+    assert_eq!(index.entry(1), Some((true, liquidation_index::bucket_of(liq))));
+    assert_eq!(index.entry(AGENT_LP_IDX).map(|(long, _)| long), Some(false));
+
+    // Untouched at the oracle, a candidate once the price falls through its bucket
+    assert!(!engine.liquidation_candidates(ORACLE).any(|idx| idx == 1));
+    assert!(engine.liquidation_candidates(liq / 2).any(|idx| idx == 1));
+
+    let outcome = engine.crank(1, 2, liq / 2, None).unwrap();
+    assert!(outcome.num_liquidations >= 1);
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 0);
+    assert_eq!(engine.liquidation_index().entry(1), None);
+}
+
+#[test]
+fn test_liquidation_index_refiles_stale_accounts_without_rebuild() {
+    let mut engine = funded_engine();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 2_000_000, 1)
+        .unwrap();
+    engine.crank(AGENT_LP_IDX, 1, ORACLE, None).unwrap();
+    assert!(!engine.liquidation_index().needs_rebuild());
+    let filed = engine.liquidation_index().entry(1);
+
+    // Moving capital out shifts the liquidation price; the crank refiles it
+    let sub = engine.open_sub_account(1, 0).unwrap();
+    engine.transfer_to_sub_account(sub, 600_000, ORACLE).unwrap();
+    assert!(engine.liquidation_index().is_stale(1));
+    assert_eq!(engine.liquidation_index().entry(1), filed);
+
+    engine.crank(AGENT_LP_IDX, 2, ORACLE, None).unwrap();
+    let liq = engine.liquidation_price(1, ORACLE).unwrap();
+    assert!(!engine.liquidation_index().is_stale(1));
+    assert_eq!(engine.liquidation_index().entry(1), Some((true, liquidation_index::bucket_of(liq))));
+    assert_ne!(engine.liquidation_index().entry(1), filed);
+
+    // Only raw engine access forces a full rebuild
+    engine.risk_engine_mut();
+    assert!(engine.liquidation_index().needs_rebuild());
+    engine.crank(AGENT_LP_IDX, 3, ORACLE, None).unwrap();
+    assert!(!engine.liquidation_index().needs_rebuild());
+}
+
+#[test]
+fn test_account_ref_reads_in_place_and_matches_view() {
+    let mut engine = funded_engine();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000_000, 1)
+        .unwrap();
+    let price = ORACLE - 10_000;
+    let view = engine.account_view(1, price).unwrap();
+    let account = engine.account_ref(1).unwrap();
+    assert_eq!(account.to_view(price), view);
+    assert_eq!((account.capital(), account.pnl(), account.position_size()), (view.capital, view.pnl, view.position_size));
+    assert_eq!(account.equity(price), view.equity);
+    assert_eq!(account.unrealized_pnl(price), view.unrealized_pnl);
+    assert_eq!(account.margin_ratio_bps(price), view.margin_ratio_bps);
+    assert_eq!(account.liquidation_price(price), view.liquidation_price);
+    assert_eq!(account.undercollateralized(price), view.undercollateralized);
+    assert_eq!((account.tier(), account.margin_mode(), account.parent()), (view.tier, view.margin_mode, None));
+    assert!(core::mem::size_of::<AccountRef>() < core::mem::size_of::<AccountView>() / 4);
+
+    assert!(engine.account_ref(40).is_none());
+    let live: Vec<u16> = engine.account_refs().map(|a| a.idx()).collect();
+    assert_eq!(live, vec![AGENT_LP_IDX, 1]);
+}
+
+// ==============================================================================
+// STRESS PROJECTION
+// ==============================================================================
+
+#[test]
+fn test_stress_projection_waterfall() {
+    let mut engine = funded_engine();
+    engine.risk_engine_mut().top_up_insurance_fund(50_000).unwrap();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000_000, 1)
+        .unwrap();
+    let capital = engine.account_view(1, ORACLE).unwrap().capital;
+
+    // Calm: nothing breaks
+    let calm = engine.stress_project(0, ORACLE);
+    assert_eq!((calm.liquidatable_accounts, calm.bankrupt_accounts), (0, 0));
+    assert!(!calm.socialization_triggered());
+
+    // -17%: the 5x long is liquidatable but solvent
+    let mild = engine.stress_project(-1_700, ORACLE);
+    assert_eq!(mild.shocked_price, 830_000);
+    assert_eq!((mild.liquidatable_accounts, mild.bankrupt_accounts), (1, 0));
+
+    // -25%: 1.25M loss on ~1M capital, insurance absorbs up to its balance
+    let severe = engine.stress_project(-2_500, ORACLE);
+    let insurance = engine.risk_engine().insurance_fund.balance.get();
+    assert_eq!(severe.bankrupt_accounts, 1);
+    assert_eq!(severe.total_deficit, 1_250_000 - capital);
+    assert_eq!(severe.insurance_drawdown, insurance.min(severe.total_deficit));
+    assert_eq!(severe.insurance_remaining, insurance - severe.insurance_drawdown);
+    assert_eq!(severe.socialized_loss, severe.total_deficit - severe.insurance_drawdown);
+    assert!(severe.socialization_triggered());
+
+    // Projection is read-only
+    assert_eq!(engine.account_view(1, ORACLE).unwrap().capital, capital);
+}
+
+// ==============================================================================
+// VALUE AT RISK
+// ==============================================================================
+
+#[test]
+fn test_value_at_risk_from_crank_prices() {
+    let mut engine = funded_engine();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000_000, 1)
+        .unwrap();
+    assert_eq!(engine.value_at_risk().samples, 0);
+
+    // Returns: +200, -196, -100, +101 bps
+    for (slot, price) in [1_000_000, 1_020_000, 1_000_000, 990_000, 1_000_000]
+        .into_iter()
+        .enumerate()
+    {
+        engine.crank(1, slot as u64 + 2, price, None).unwrap();
+    }
+    assert_eq!(engine.price_history().len(), 5);
+    assert_eq!(engine.price_history().latest().unwrap().price, 1_000_000);
+
+    let var = engine.value_at_risk();
+    assert_eq!(var.samples, 4);
+    assert_eq!(var.confidence_bps, DEFAULT_VAR_CONFIDENCE_BPS);
+    // 95% of four samples lands on the worst one
+    assert_eq!((var.move_var_bps, var.move_es_bps), (200, 200));
+    // The LP is short 1M: a +2% move costs it 20k
+    assert_eq!(var.lp_var, 20_000);
+    assert_eq!(var.lp_es, 20_000);
+    let oi = engine.risk_engine().total_open_interest.get();
+    assert_eq!(var.oi_var, oi * 200 / 10_000);
+
+    let context = engine.build_context(ORACLE_PX);
+    assert_eq!(context.value_at_risk, var);
+}
+
+// ==============================================================================
+// FUNDING HISTORY
+// ==============================================================================
+
+#[test]
+fn test_funding_history_per_account() {
+    let mut engine = funded_engine();
+    engine.set_epoch_slots(100);
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000_000, 1)
+        .unwrap();
+    let params = MarketParams {
+        funding_rate_bps_per_slot: 1,
+        ..MarketParams::default()
+    };
+    engine.update_market_params(&ParamsAgent { params }).unwrap();
+
+    // The first crank installs the rate, the next accrues 10 slots of it
+    engine.crank(1, 2, ORACLE, None).unwrap();
+    engine.crank(1, 12, ORACLE, None).unwrap();
+
+    // 1 bps/slot * 10 slots on 1M notional: the long pays the short LP
+    assert_eq!(engine.funding_ledger().paid(1), 1_000);
+    assert_eq!(engine.funding_ledger().paid(AGENT_LP_IDX), -1_000);
+    assert_eq!(engine.account_view(1, ORACLE).unwrap().funding_paid, 1_000);
+    let lp = engine
+        .accounts(0, ORACLE, AccountFilter::All)
+        .find(|v| v.idx == AGENT_LP_IDX)
+        .unwrap();
+    assert_eq!(lp.funding_paid, -1_000);
+
+    // Flat accounts stop accruing
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, -1_000_000, 13)
+        .unwrap();
+    engine.crank(1, 22, ORACLE, None).unwrap();
+    assert_eq!(engine.funding_ledger().paid(1), 1_000);
+
+    engine.crank(1, 101, ORACLE, None).unwrap();
+    let report = engine.epoch_history().latest().unwrap();
+    assert_eq!((report.funding_paid, report.funding_received), (1_000, 1_000));
+}
+
+// ==============================================================================
+// REALIZED / UNREALIZED PNL
+// ==============================================================================
+
+#[test]
+fn test_settle_pnl_moves_backed_gains_to_capital() {
+    let mut engine = funded_engine();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000_000, 1)
+        .unwrap();
+    let up = OraclePrice::new(1_010_000, 0, 2);
+
+    // Open position: the move is unrealized
+    let view = engine.account_view(1, up.price).unwrap();
+    assert_eq!((view.pnl, view.unrealized_pnl), (0, 10_000));
+
+    // Closing realizes it, still subject to warmup
+    engine.execute_trade(&PassthroughAgent, 1, up, -1_000_000, 2).unwrap();
+    let view = engine.account_view(1, up.price).unwrap();
+    assert_eq!((view.pnl, view.unrealized_pnl), (10_000, 0));
+    assert_eq!(view.withdrawable_pnl, 0);
+
+    // Half the warmup period later half the gain is releasable
+    engine.risk_engine_mut().current_slot = 52;
+    assert_eq!(engine.account_view(1, up.price).unwrap().withdrawable_pnl, 5_000);
+
+    // The LP's loss was paid from capital on the close, backing the gain
+    assert_eq!(engine.account_view(AGENT_LP_IDX, up.price).unwrap().pnl, 0);
+    assert_eq!(engine.settle_pnl(AGENT_LP_IDX).unwrap(), PnlSettlement::default());
+
+    let capital = engine.account_view(1, up.price).unwrap().capital;
+    let user = engine.settle_pnl(1).unwrap();
+    assert_eq!(user.profit_released, 5_000);
+    assert_eq!(user.haircut(), 0);
+    let view = engine.account_view(1, up.price).unwrap();
+    assert_eq!(view.capital, capital + 5_000);
+    assert_eq!(view.pnl, 5_000);
+
+    assert_eq!(engine.settle_pnl(99), Err(RiskError::AccountNotFound));
+}
+
+// ==============================================================================
+// PNL WARMUP
+// ==============================================================================
+
+fn warmup_agent(slots: u64) -> ParamsAgent {
+    ParamsAgent {
+        params: MarketParams {
+            warmup_period_slots: Some(slots),
+            ..MarketParams::default()
+        },
+    }
+}
+
+#[test]
+fn test_warmup_visibility_and_agent_period() {
+    let mut engine = funded_engine();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000_000, 1)
+        .unwrap();
+    let up = OraclePrice::new(1_010_000, 0, 2);
+    engine.execute_trade(&PassthroughAgent, 1, up, -1_000_000, 2).unwrap();
+
+    // 10k gain over the 100-slot warmup
+    let view = engine.account_view(1, up.price).unwrap();
+    assert_eq!((view.withdrawable_pnl, view.unvested_pnl), (0, 10_000));
+    assert_eq!(view.slots_to_vest, 100);
+
+    engine.risk_engine_mut().current_slot = 52;
+    let view = engine.account_view(1, up.price).unwrap();
+    assert_eq!((view.withdrawable_pnl, view.unvested_pnl), (5_000, 5_000));
+    assert_eq!(view.slots_to_vest, 50);
+
+    // Agent period must sit inside protocol bounds
+    engine
+        .set_warmup_bounds(WarmupBounds { min_slots: 50, max_slots: 500 })
+        .unwrap();
+    assert_eq!(
+        engine.update_market_params(&warmup_agent(1_000)),
+        Err(RiskError::Overflow)
+    );
+    assert_eq!(
+        engine.update_market_params(&warmup_agent(10)),
+        Err(RiskError::Overflow)
+    );
+    engine.update_market_params(&warmup_agent(200)).unwrap();
+    assert_eq!(engine.risk_engine().params.warmup_period_slots, 200);
+
+    // The next warmup runs at the new period
+    engine.settle_pnl(1).unwrap();
+    let view = engine.account_view(1, up.price).unwrap();
+    assert_eq!((view.withdrawable_pnl, view.unvested_pnl), (0, 5_000));
+    assert_eq!(view.slots_to_vest, 200);
+}
+
+// ==============================================================================
+// FIXED-POINT MATH
+// ==============================================================================
+
+#[test]
+fn test_fixed_point_helpers() {
+    use percolator::clawcolator::math::*;
+
+    assert_eq!(mul_div(10, 3, 4, Rounding::Down), Some(7));
+    assert_eq!(mul_div(10, 3, 4, Rounding::Up), Some(8));
+    assert_eq!(mul_div(1, 1, 0, Rounding::Down), None);
+    assert_eq!(mul_div(u128::MAX, 2, 1, Rounding::Down), None);
+    assert_eq!(saturating_mul_div(u128::MAX, 2, 2, Rounding::Down), Some(u128::MAX / 2));
+
+    assert_eq!(apply_bps(1_001, 10, Rounding::Down), 1);
+    assert_eq!(apply_bps(1_001, 10, Rounding::Up), 2);
+    assert_eq!(ratio_bps(1, 3, Rounding::Down), 3_333);
+    assert_eq!(ratio_bps(1, 3, Rounding::Up), 3_334);
+    assert_eq!(ratio_bps(1, 0, Rounding::Down), u64::MAX);
+
+    assert_eq!(notional(1_500_000, 2_000_000, Rounding::Down), 3_000_000);
+    assert_eq!(notional(1, 1_500_000, Rounding::Up), 2);
+    assert_eq!(offset_bps(1_000_001, 100, true, Rounding::Up), 1_010_002);
+    assert_eq!(offset_bps(1_000_001, 100, false, Rounding::Down), 990_000);
+    assert_eq!(offset_bps(1_000_000, 20_000, false, Rounding::Down), 0);
+}
+
+// ==============================================================================
+// AGENT NUMERICS
+// ==============================================================================
+
+#[test]
+fn test_mathx_helpers() {
+    use percolator::clawcolator::mathx::*;
+
+    assert_eq!(isqrt(0), 0);
+    assert_eq!(isqrt(99), 9);
+    assert_eq!(isqrt(1 << 100), 1 << 50);
+
+    // log2(1) = 0, log2(2) = 1, log2(0.5) = -1, log2(3) ~ 1.584963
+    assert_eq!(log2_e6(1_000_000), Some(0));
+    assert_eq!(log2_e6(2_000_000), Some(1_000_000));
+    assert_eq!(log2_e6(500_000), Some(-1_000_000));
+    assert!((log2_e6(3_000_000).unwrap() - 1_584_963).abs() <= 2);
+    assert_eq!(log2_e6(0), None);
+
+    // ln(e) ~ 1
+    assert!((ln_e6(2_718_282).unwrap() - 1_000_000).abs() <= 2);
+    // +1% price move ~ 99.5 bps log return; symmetric for the way back
+    assert_eq!(log_return_bps(1_000_000, 1_010_000), Some(99));
+    assert_eq!(log_return_bps(1_010_000, 1_000_000), Some(-99));
+    assert_eq!(log_return_bps(0, 1), None);
+
+    assert_eq!(ewma_update(100, 200, 2_500), 125);
+    assert_eq!(ewma_update(200, 100, 2_500), 175);
+
+    // Alternating +-1% moves converge towards 1% volatility
+    let mut vol = EwmaVolatility::new(5_000);
+    vol.update(1_000_000);
+    assert_eq!(vol.volatility_bps(), 0);
+    for i in 0..20 {
+        vol.update(if i % 2 == 0 { 1_010_000 } else { 1_000_000 });
+    }
+    assert!((98..=100).contains(&vol.volatility_bps()));
+
+    // 1% edge at 20% volatility: f = 0.01 / 0.04 = 25%
+    assert_eq!(kelly_fraction_bps(100, 2_000 * 2_000, 10_000), 2_500);
+    assert_eq!(kelly_fraction_bps(100, 2_000 * 2_000, 1_000), 1_000);
+    assert_eq!(kelly_fraction_bps(-100, 2_000 * 2_000, 10_000), 0);
+}
+
+// ==============================================================================
+// F64 ANALYSIS
+// ==============================================================================
+
+#[cfg(feature = "analysis")]
+#[test]
+fn test_analysis_series_stats() {
+    use percolator::clawcolator::analysis::*;
+
+    let series = [100.0, 120.0, 90.0, 110.0, 80.0, 130.0];
+    let dd = max_drawdown(&series);
+    assert!((dd.depth - 1.0 / 3.0).abs() < 1e-12);
+    assert_eq!((dd.peak_idx, dd.trough_idx), (1, 4));
+    assert_eq!(max_drawdown(&[1.0, 2.0, 3.0]).depth, 0.0);
+
+    let r = returns(&[100.0, 110.0, 99.0]);
+    assert!((r[0] - 0.1).abs() < 1e-12 && (r[1] + 0.1).abs() < 1e-12);
+    assert!((log_returns(&[1.0, core::f64::consts::E])[0] - 1.0).abs() < 1e-12);
+    assert_eq!(mean(&[]), None);
+    assert!((std_dev(&[1.0, 3.0]).unwrap() - 2f64.sqrt()).abs() < 1e-12);
+
+    let a = [1.0, 2.0, 3.0, 4.0];
+    let b = [2.0, 4.0, 6.0, 8.0];
+    let c = [4.0, 3.0, 2.0, 1.0];
+    assert!((correlation(&a, &b).unwrap() - 1.0).abs() < 1e-12);
+    assert!((correlation(&a, &c).unwrap() + 1.0).abs() < 1e-12);
+    assert_eq!(correlation(&a, &[1.0; 4]), None);
+
+    // Engine history feeds the same statistics
+    let mut engine = funded_engine();
+    for (slot, price) in [1_000_000, 1_100_000, 990_000].into_iter().enumerate() {
+        engine.crank(1, slot as u64 + 2, price, None).unwrap();
+    }
+    let analytics = EngineAnalytics::capture(&engine);
+    assert_eq!(prices(engine.price_history()), [1.0, 1.1, 0.99]);
+    assert_eq!(analytics.price.samples, 2);
+    assert!((analytics.price.drawdown.depth - 0.1).abs() < 1e-12);
+    assert_eq!(analytics.lp_price_correlation, None);
+}
+
+#[cfg(feature = "analysis")]
+#[test]
+fn test_monte_carlo_paths_are_seeded_and_merge_in_order() {
+    use percolator::clawcolator::analysis::SeriesStats;
+    use percolator::clawcolator::monte_carlo::*;
+
+    let factory = || {
+        let mut engine = funded_engine();
+        engine.risk_engine_mut().top_up_insurance_fund(10_000).unwrap();
+        engine
+            .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000_000, 1)
+            .unwrap();
+        engine
+    };
+    let params = PathParams { steps: 40, step_bps: 300, ..PathParams::default() };
+    let report = run(factory, &params, 0..16);
+    assert_eq!(report.paths.iter().map(|p| p.seed).collect::<Vec<_>>(), (0..16).collect::<Vec<_>>());
+    for path in &report.paths {
+        assert!(path.min_price <= path.final_price && path.final_price <= path.max_price);
+        assert!(path.insurance_min <= path.insurance_start.min(path.insurance_end));
+        assert_eq!(path.failed_cranks, 0);
+    }
+    // Same seed, same path; different seeds diverge
+    assert_eq!(run(factory, &params, 3..4).paths[0], report.paths[3]);
+    assert_ne!(report.paths[0].final_price, report.paths[1].final_price);
+    assert!(report.paths_with_liquidations() > 0);
+    assert!(report.total_liquidations() >= report.paths_with_liquidations() as u64);
+    assert_eq!(SeriesStats::of(&report.final_prices()).samples, 15);
+
+    // Merging split runs restores seed order
+    let mut merged = run(factory, &params, 8..16);
+    merged.merge(run(factory, &params, 0..8));
+    assert_eq!(merged, report);
+
+    #[cfg(feature = "parallel")]
+    assert_eq!(run_parallel(factory, &params, 0..16), report);
+}
+
+#[cfg(feature = "analysis")]
+#[test]
+fn test_tuner_recommends_cheapest_feasible_params() {
+    use percolator::clawcolator::tuner::*;
+
+    let factory = || Box::new(ClawcolatorEngine::new(default_params()));
+    let flow = SimulationParams {
+        steps: 60,
+        traders: 8,
+        max_order_size: 4_000_000,
+        ..SimulationParams::default()
+    };
+    let history: Vec<u64> = (0..60).map(|i| 1_000_000 - i * 4_000 + (i % 3) * 9_000).collect();
+    let backtests = [
+        Backtest::Synthetic(SimulationParams { seed: 1, ..flow }),
+        Backtest::Synthetic(SimulationParams {
+            price_model: PriceModel::Crash { at_step: 30, shock_bps: -1_000, step_bps: 50 },
+            seed: 2,
+            ..flow
+        }),
+        Backtest::Historical { prices: history, flow: SimulationParams { seed: 3, ..flow } },
+    ];
+    let grid = TuningGrid {
+        margin_bps: vec![500, 2_000],
+        spread_bps: vec![0, 25, 100],
+        funding_cap_bps_per_slot: vec![0, 2],
+    };
+    // Charges 3 bps per slot, above one of the caps
+    let agent = ParamsAgent {
+        params: MarketParams { funding_rate_bps_per_slot: 3, ..MarketParams::default() },
+    };
+    let rec = tune(factory, &agent, &backtests, &grid, &TuningConstraints::default()).unwrap();
+    assert_eq!(rec, tune(factory, &agent, &backtests, &grid, &TuningConstraints::default()).unwrap());
+    assert_eq!(rec.candidates.len(), 12);
+    assert!(rec.candidates.iter().all(|c| c.runs.len() == 3));
+
+    // Tight spreads lose the LP money on the replayed decline; a 1% spread
+    // keeps it whole at either margin
+    let point = rec.point.unwrap();
+    assert_eq!((point.margin_bps, point.spread_bps, point.funding_cap_bps_per_slot), (500, 100, 0));
+    let params = rec.params.unwrap();
+    assert_eq!((params.min_margin_bps, params.max_leverage_bps, params.spread_bps), (500, 2_000, 100));
+    assert_eq!(params.funding_rate_bps_per_slot, 0);
+    assert!(params.tier_limits.iter().all(|limits| limits.max_leverage_bps == 2_000));
+    assert_eq!(rec.curves.spread.iter().map(|p| p.feasible_share).collect::<Vec<_>>(), [0.0, 0.0, 1.0]);
+    assert!(rec.curves.spread.windows(2).all(|w| w[0].mean_lp_pnl < w[1].mean_lp_pnl));
+    assert!(rec.curves.spread[0].fill_rate > rec.curves.spread[2].fill_rate);
+    assert!(rec.curves.margin[0].mean_liquidations > rec.curves.margin[1].mean_liquidations);
+
+    // Capping liquidations moves the recommendation to the higher margin
+    let strict = TuningConstraints { max_liquidations: 3, ..TuningConstraints::default() };
+    let rec = tune(factory, &agent, &backtests, &grid, &strict).unwrap();
+    assert_eq!(rec.point.map(|p| p.margin_bps), Some(2_000));
+    let impossible = TuningConstraints { min_lp_pnl: i128::MAX, ..strict };
+    let rec = tune(factory, &agent, &backtests, &grid, &impossible).unwrap();
+    assert_eq!((rec.point, rec.params), (None, None));
+
+    // A margin under maintenance is refused, not backtested as the defaults
+    let low = TuningGrid { margin_bps: vec![200], ..grid.clone() };
+    let err = tune(factory, &agent, &backtests, &low, &strict).unwrap_err();
+    assert_eq!(err, RiskError::Undercollateralized);
+    let empty = TuningGrid { spread_bps: vec![], ..grid };
+    assert_eq!(tune(factory, &agent, &backtests, &empty, &strict).unwrap_err(), RiskError::Overflow);
+}
+
+#[cfg(feature = "simd")]
+#[test]
+fn test_batch_margin_check_matches_engine() {
+    use percolator::clawcolator::batch::*;
+
+    let mut engine = funded_engine();
+    engine.risk_engine_mut().top_up_insurance_fund(10_000).unwrap();
+    for i in 0..10u128 {
+        let risk = engine.risk_engine_mut();
+        let user = risk.add_user(0).unwrap();
+        risk.deposit(user, 250_000 + i * 25_000, 0).unwrap();
+        let size = if i % 2 == 0 { 2_000_000 } else { -2_000_000 };
+        engine.execute_trade(&PassthroughAgent, user, ORACLE_PX, size, 1).unwrap();
+    }
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000_000, 1).unwrap();
+
+    let batch = MarginBatch::capture(&engine);
+    assert_eq!(batch.len(), 12); // LP, user 1 and the ten new accounts
+    let bps = engine.risk_engine().params.maintenance_margin_bps;
+    let prices = [700_000, 850_000, 910_000, 1_000_000, 1_090_000, 1_150_000, 1_300_000];
+    for price in prices {
+        let expected: Vec<u16> = (0..12)
+            .filter(|&idx| engine.account_view(idx, price).unwrap().undercollateralized)
+            .collect();
+        let mut below = Vec::new();
+        batch.undercollateralized(price, bps, &mut below);
+        assert_eq!(below, expected, "price {price}");
+    }
+    let counts = batch.count_per_price(&prices, bps);
+    assert_eq!(counts[3], 0);
+    assert!(counts[0] > 0 && counts[6] > 0);
+
+    // Synthetic rows fill whole and partial chunks alike
+    let mut synthetic = MarginBatch::default();
+    for i in 0..(3 * LANES as u16 + 5) {
+        synthetic.push(i, 100_000.0 * (i + 1) as f64, 0.0, 1_000_000.0, 1_000_000.0);
+    }
+    // Equity 100k(i+1) - 500k at 0.5; maintenance 25k: only i = 0..=4 fail
+    assert_eq!(synthetic.count_undercollateralized(500_000, 500), 5);
+}
+
+// ==============================================================================
+// C FFI
+// ==============================================================================
+
+#[cfg(feature = "ffi")]
+#[test]
+fn test_ffi_engine_roundtrip() {
+    use core::ffi::c_void;
+    use percolator::clawcolator::ffi::*;
+
+    extern "C" fn passthrough(
+        user_data: *mut c_void,
+        context: *const ClawContext,
+        request: *const ClawTradeRequest,
+        out: *mut ClawDecision,
+    ) -> i32 {
+        unsafe {
+            *(user_data as *mut u32) += 1;
+            *out = ClawDecision {
+                kind: CLAW_DECISION_ACCEPT,
+                price: (*context).oracle_price,
+                size: (*request).size,
+            };
+        }
+        0
+    }
+
+    unsafe {
+        let engine = claw_engine_new(500, 1000, 10, 100);
+        let (mut lp, mut user) = (u16::MAX, u16::MAX);
+        assert_eq!(claw_add_account(engine, true, 10_000_000, &mut lp), CLAW_OK);
+        assert_eq!(claw_add_account(engine, false, 1_000_000, &mut user), CLAW_OK);
+
+        // No callback registered: the trade is refused
+        assert_eq!(
+            claw_execute_trade(engine, user, ORACLE, 1_000, 1),
+            CLAW_ERR_UNAUTHORIZED
+        );
+
+        let mut calls = 0u32;
+        let user_data = &mut calls as *mut u32 as *mut c_void;
+        assert_eq!(claw_set_decision_callback(engine, Some(passthrough), user_data), CLAW_OK);
+        assert_eq!(claw_execute_trade(engine, user, ORACLE, 1_000, 1), CLAW_OK);
+        assert_eq!(calls, 1);
+
+        let mut account = ClawAccount::default();
+        assert_eq!(claw_account(engine, user, ORACLE, &mut account), CLAW_OK);
+        assert_eq!((account.idx, account.is_lp, account.position_size), (user, false, 1_000));
+        assert_eq!(
+            claw_account(engine, 200, ORACLE, &mut account),
+            CLAW_ERR_ACCOUNT_NOT_FOUND
+        );
+
+        assert_eq!(claw_crank(engine, user, 2, ORACLE), CLAW_OK);
+        let mut status = ClawStatus::default();
+        assert_eq!(claw_status(engine, &mut status), CLAW_OK);
+        assert_eq!(status.current_slot, 2);
+        assert!(status.vault >= 11_000_000);
+
+        assert_eq!(claw_status(core::ptr::null(), &mut status), CLAW_ERR_NULL);
+        claw_engine_free(engine);
+    }
+}
+
+// ==============================================================================
+// SCENARIO CLI
+// ==============================================================================
+
+#[cfg(feature = "cli")]
+#[test]
+fn test_cli_runs_basic_scenario() {
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_clawcolator-cli"))
+            .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/basic.toml"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let out = run(&["--json"]);
+    assert!(out.status.success());
+    let json = String::from_utf8(out.stdout).unwrap();
+    assert!(json.contains(r#""agent": "Spread""#));
+    assert!(json.contains(r#""total_open_interest": 400000"#));
+    // The oversized trade is rejected, the others fill
+    assert_eq!(json.matches(r#""ok": true"#).count(), 2);
+    assert_eq!(json.matches(r#""ok": false"#).count(), 1);
+
+    // Passthrough fills the oversized trade too, up to the margin check
+    let out = run(&["--agent", "passthrough", "--json"]);
+    let json = String::from_utf8(out.stdout).unwrap();
+    assert!(json.contains(r#""error": "Undercollateralized""#));
+
+    assert!(!run(&["--agent", "nope"]).status.success());
+}
+
+// ==============================================================================
+// CONFIG LOADER
+// ==============================================================================
+
+#[cfg(feature = "config")]
+#[test]
+fn test_config_loader() {
+    use percolator::clawcolator::config::*;
+
+    let toml = r#"
+        [risk]
+        maintenance_margin_bps = 400
+        initial_margin_bps = 800
+
+        [market]
+        spread_bps = 25
+        min_margin_bps = 400
+
+        [bounds.oracle]
+        max_staleness_slots = 50
+    "#;
+    let config = EngineConfig::from_toml(toml).unwrap();
+    assert_eq!(config.risk.risk_params().maintenance_margin_bps, 400);
+    assert_eq!(config.risk.trading_fee_bps, 10);
+    assert_eq!(config.market.market_params().spread_bps, 25);
+    assert_eq!(config.bounds.oracle.max_confidence_bps, 200);
+
+    let json = r#"{"risk": {"maintenance_margin_bps": 400, "initial_margin_bps": 800},
+        "market": {"spread_bps": 25, "min_margin_bps": 400},
+        "bounds": {"oracle": {"max_staleness_slots": 50}}}"#;
+    assert_eq!(EngineConfig::from_json(json).unwrap(), config);
+
+    let engine = config.build_engine().unwrap();
+    assert_eq!(engine.oracle_bounds().max_staleness_slots, 50);
+    assert_eq!(engine.risk_engine().params.initial_margin_bps, 800);
+
+    // Validation names the offending field
+    let err = EngineConfig::from_toml("[risk]\ninitial_margin_bps = 100").unwrap_err();
+    assert!(matches!(err, ConfigError::Invalid { field: "risk.initial_margin_bps", .. }));
+    let err = EngineConfig::from_toml("[market]\nmax_leverage_bps = 50_000").unwrap_err();
+    assert!(matches!(err, ConfigError::Invalid { field: "market.max_leverage_bps", .. }));
+    let err = EngineConfig::from_json(r#"{"bounds": {"warmup": {"min_slots": 10, "max_slots": 5}}}"#)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "bounds.warmup.max_slots: is below min_slots");
+
+    // Amounts past u64 are written as strings
+    let big = EngineConfig::from_toml("[market]\nmax_position_size = \"100_000_000_000_000_000_000\"");
+    assert_eq!(big.unwrap().market.max_position_size, MAX_POSITION_ABS);
+
+    // Typos are rejected rather than silently ignored
+    assert!(matches!(
+        EngineConfig::from_toml("[risk]\nmaintenance_margin = 400"),
+        Err(ConfigError::Parse(_))
+    ));
+    assert!(matches!(EngineConfig::load("/nonexistent.toml"), Err(ConfigError::Io(_))));
+}
+
+// ==============================================================================
+// LOCALHOST PERSISTENCE
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_server_state_survives_restart() {
+    use percolator::localhost::*;
+
+    let mut state = ServerState::new(PassthroughAgent);
+    assert_eq!(state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap(), Some(0));
+    assert_eq!(state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap(), Some(1));
+    let trade = Command::Trade {
+        user_idx: 1,
+        oracle_price: ORACLE,
+        size: 200_000,
+        slot: 0,
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: None,
+    };
+    state.apply(trade).unwrap();
+    state.apply(Command::Crank { caller_idx: 0, oracle_price: 1_010_000, slot: 1 }).unwrap();
+    // Rejected commands leave no trace in the journal
+    assert!(matches!(
+        state.apply(Command::Deposit { idx: 42, amount: 1 }),
+        Err(ServerError::Engine(_))
+    ));
+    assert_eq!(state.journal().commands.len(), 4);
+
+    // Text round trip, as written by FileStore
+    let text = state.journal().encode();
+    assert!(text.contains("trade 1 1000000 200000 0\n"));
+    assert_eq!(&Snapshot::decode(&text).unwrap(), state.journal());
+    assert!(Snapshot::decode("clawcolator-journal v1\ntrade 1 x\n").is_err());
+    assert!(Snapshot::decode("add_user 5\n").is_err());
+
+    // A file-backed restart replays into an identical engine
+    let path = std::env::temp_dir().join(format!("claw-journal-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut store = FileStore::new(path);
+    assert!(store.load().unwrap().is_none());
+    store.save(state.journal()).unwrap();
+    let fresh = Box::new(ClawcolatorEngine::new(default_params()));
+    let restored = ServerState::restore(fresh, PassthroughAgent, Box::new(store)).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(restored.journal(), state.journal());
+    for idx in [0, 1] {
+        assert_eq!(
+            restored.engine.account_view(idx, ORACLE),
+            state.engine.account_view(idx, ORACLE)
+        );
+    }
+    assert_eq!(restored.engine.risk_engine().vault, state.engine.risk_engine().vault);
+}
+
+// ==============================================================================
+// THREADED LOCALHOST SERVER
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_threaded_server_routes_reads_and_writes() {
+    use percolator::localhost::*;
+    use std::io::{Read, Write};
+    use std::sync::{Arc, RwLock};
+
+    let router = Router::new()
+        .read("GET", "/accounts/{idx}", |state: &ServerState<PassthroughAgent>, req| {
+            let idx = req.param("idx").and_then(|v| v.parse().ok()).unwrap_or(u16::MAX);
+            match state.engine.account_view(idx, ORACLE) {
+                Some(v) => Response::ok(format!(r#"{{"position_size": {}}}"#, v.position_size)),
+                None => Response::bad_request("Account not found"),
+            }
+        })
+        .write("POST", "/execute", |state, req| {
+            let command = Command::Trade {
+                user_idx: req.json_int("user_idx").unwrap_or(0) as u16,
+                oracle_price: ORACLE,
+                size: req.json_int("size").unwrap_or(0),
+                slot: 0,
+                client_order_id: None,
+                sequence: None,
+                valid_until_slot: None,
+            };
+            match state.apply(command) {
+                Ok(_) => Response::ok(r#"{"ok": true}"#),
+                Err(e) => Response::bad_request(&format!("{:?}", e)),
+            }
+        });
+    assert_eq!(
+        router.routes().collect::<Vec<_>>(),
+        [("GET", "/accounts/{idx}"), ("POST", "/execute")]
+    );
+
+    let mut state = ServerState::new(PassthroughAgent);
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap();
+    let state = Arc::new(RwLock::new(state));
+
+    // Routing without the network
+    let req = Request::parse("GET /accounts/1?x=1 HTTP/1.1\r\n\r\n").unwrap();
+    assert_eq!(req.query_param("x"), Some("1"));
+    assert_eq!(router.dispatch(&state, req).body, r#"{"position_size": 0}"#);
+    let missing = Request::parse("GET /accounts/1/extra HTTP/1.1\r\n\r\n").unwrap();
+    assert_eq!(router.dispatch(&state, missing).status, 404);
+
+    // Concurrent writes over TCP are serialized under the write lock
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (shared, router) = (state.clone(), Arc::new(router));
+    std::thread::spawn(move || serve(listener, shared, router, 4));
+    let send = move |raw: String| {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut out = String::new();
+        stream.read_to_string(&mut out).unwrap();
+        out
+    };
+    let clients: Vec<_> = (0..8)
+        .map(|_| {
+            std::thread::spawn(move || {
+                let body = r#"{"user_idx": 1, "size": 1000}"#;
+                send(format!(
+                    "POST /execute HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                ))
+            })
+        })
+        .collect();
+    for client in clients {
+        assert!(client.join().unwrap().starts_with("HTTP/1.1 200 OK"));
+    }
+    let response = send("GET /accounts/1 HTTP/1.1\r\n\r\n".into());
+    assert!(response.ends_with(r#"{"position_size": 8000}"#));
+    assert_eq!(state.read().unwrap().journal().commands.len(), 10);
+}
+
+// ==============================================================================
+// LOCALHOST API KEYS
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_server_api_key_permissions() {
+    use percolator::localhost::*;
+    use std::sync::RwLock;
+
+    let keys = ApiKeys::decode("# operators\nr0 read\nt1 trade:1\nroot admin\n").unwrap();
+    assert_eq!(keys.permission("t1"), Some(Permission::Trade { user_idx: 1 }));
+    assert_eq!(keys.permission("t"), None);
+    assert!(ApiKeys::decode("k trade:x").is_err());
+
+    let router = Router::new()
+        .read("GET", "/status", |_: &ServerState<PassthroughAgent>, _| Response::ok("{}"))
+        .write("POST", "/execute", |state, req| {
+            let command = Command::Trade {
+                user_idx: req.json_int("user_idx").unwrap_or(0) as u16,
+                oracle_price: ORACLE,
+                size: req.json_int("size").unwrap_or(0),
+                slot: 0,
+                client_order_id: None,
+                sequence: None,
+                valid_until_slot: None,
+            };
+            match state.apply(command) {
+                Ok(_) => Response::ok("{}"),
+                Err(e) => Response::bad_request(&format!("{:?}", e)),
+            }
+        })
+        .scope(Scope::Trade)
+        .write("POST", "/admin/freeze", |state, _| {
+            state.apply(Command::SetFrozen { frozen: true }).unwrap();
+            Response::ok("{}")
+        })
+        .with_keys(keys);
+
+    let mut state = ServerState::new(PassthroughAgent);
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap();
+    state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap();
+    let state = RwLock::new(state);
+
+    let call = |method: &str, path: &str, token: Option<&str>, body: &str| {
+        let auth = token.map_or(String::new(), |t| format!("Authorization: Bearer {}\r\n", t));
+        let raw = format!("{} {} HTTP/1.1\r\n{}\r\n{}", method, path, auth, body);
+        router.dispatch(&state, Request::parse(&raw).unwrap()).status
+    };
+    let trade = |idx: u16| format!(r#"{{"user_idx": {}, "size": 1000}}"#, idx);
+
+    assert_eq!(call("GET", "/status", None, ""), 401);
+    assert_eq!(call("GET", "/status", Some("bogus"), ""), 401);
+    assert_eq!(call("GET", "/status", Some("r0"), ""), 200);
+    assert_eq!(call("POST", "/execute", Some("r0"), &trade(1)), 403);
+    // Trade keys only trade for their own account
+    assert_eq!(call("POST", "/execute", Some("t1"), &trade(2)), 403);
+    assert_eq!(call("POST", "/execute", Some("t1"), &trade(1)), 200);
+    assert_eq!(call("POST", "/admin/freeze", Some("t1"), ""), 403);
+    assert_eq!(call("POST", "/admin/freeze", Some("root"), ""), 200);
+    assert_eq!(call("POST", "/execute", Some("root"), &trade(2)), 400);
+
+    // Freezes are journaled so they survive a restart
+    let state = state.into_inner().unwrap();
+    assert_eq!(state.engine.freeze_origin(), Some(FreezeOrigin::Admin));
+    assert_eq!(
+        state.journal().commands.last(),
+        Some(&Command::SetFrozen { frozen: true })
+    );
+    assert_eq!(Command::decode("set_frozen true"), Some(Command::SetFrozen { frozen: true }));
+}
+
+// ==============================================================================
+// OPENAPI SPEC
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_router_serves_openapi_spec() {
+    use percolator::localhost::*;
+    use std::sync::RwLock;
+
+    let router = Router::new()
+        .read("GET", "/accounts/{idx}", |_: &ServerState<PassthroughAgent>, _| Response::ok("{}"))
+        .doc(RouteDoc {
+            summary: "Account state",
+            path: &[("idx", "integer")],
+            response: &[("idx", "integer"), ("position_size", "integer")],
+            ..RouteDoc::default()
+        })
+        .write("POST", "/execute", |_, _| Response::ok("{}"))
+        .scope(Scope::Trade)
+        .doc(RouteDoc {
+            summary: "Execute a trade",
+            body: &[("user_idx", "integer"), ("size", "integer")],
+            ..RouteDoc::default()
+        })
+        .with_keys(ApiKeys::new().with("r", Permission::ReadOnly))
+        .with_openapi("Test API", "1.0.0");
+
+    let state = RwLock::new(ServerState::new(PassthroughAgent));
+    let raw = format!("GET {} HTTP/1.1\r\nAuthorization: Bearer r\r\n\r\n", OPENAPI_PATH);
+    let response = router.dispatch(&state, Request::parse(&raw).unwrap());
+    assert_eq!(response.status, 200);
+
+    let spec: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(spec["openapi"], "3.0.3");
+    assert_eq!(spec["info"]["title"], "Test API");
+    assert_eq!(spec["components"]["securitySchemes"]["apiKey"]["scheme"], "bearer");
+
+    let account = &spec["paths"]["/accounts/{idx}"]["get"];
+    assert_eq!(account["summary"], "Account state");
+    assert_eq!(account["parameters"][0]["in"], "path");
+    assert_eq!(account["parameters"][0]["required"], true);
+    let properties = &account["responses"]["200"]["content"]["application/json"]["schema"]["properties"];
+    assert_eq!(properties["position_size"]["type"], "integer");
+    assert_eq!(account["x-scope"], "Read");
+
+    let execute = &spec["paths"]["/execute"]["post"];
+    assert_eq!(execute["x-scope"], "Trade");
+    let body = &execute["requestBody"]["content"]["application/json"]["schema"]["properties"];
+    assert_eq!(body["user_idx"]["type"], "integer");
+    assert!(execute["responses"]["403"].is_object());
+    // The spec route is not part of its own spec
+    assert!(spec["paths"].get(OPENAPI_PATH).is_none());
+}
+
+// ==============================================================================
+// SERVER TRADE HISTORY
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_server_history_trades_events_candles() {
+    use percolator::localhost::*;
+
+    let mut state = ServerState::new(PassthroughAgent);
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    let user = state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap().unwrap();
+    let trade = |size: i128, price: u64, slot: u64| Command::Trade {
+        user_idx: user,
+        oracle_price: price,
+        size,
+        slot,
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: None,
+    };
+
+    state.apply(trade(1000, 1_000_000, 0)).unwrap();
+    state.apply(trade(2000, 1_010_000, 5)).unwrap();
+    state.apply(trade(-500, 990_000, 12)).unwrap();
+    state.apply(Command::SetFrozen { frozen: true }).unwrap();
+    assert!(state.apply(trade(1000, 1_000_000, 13)).is_err());
+
+    let fills: Vec<_> = state.history().trades(0, u64::MAX).collect();
+    assert_eq!(fills.len(), 3);
+    assert_eq!((fills[1].slot, fills[1].price, fills[1].size), (5, 1_010_000, 2000));
+    assert_eq!(state.engine.last_fill(), Some(fills[2]));
+    assert_eq!(state.history().trades(1, 10).count(), 1);
+
+    let candles = state.history().candles(10, 0, u64::MAX);
+    assert_eq!(candles.len(), 2);
+    assert_eq!(
+        (candles[0].start_slot, candles[0].open, candles[0].high, candles[0].close),
+        (0, 1_000_000, 1_010_000, 1_010_000)
+    );
+    assert_eq!((candles[0].volume, candles[0].trades), (3000, 2));
+    assert_eq!((candles[1].start_slot, candles[1].low, candles[1].volume), (10, 990_000, 500));
+
+    // Rejections are events too, but not trades
+    let events: Vec<_> = state.history().events(0).collect();
+    assert_eq!(events.len(), 7);
+    assert_eq!(events.last().unwrap().error, Some(RiskError::Unauthorized));
+    assert_eq!(state.history().events(6).count(), 1);
+    assert_eq!(state.history().next_seq(), 7);
+
+    // Replaying the journal rebuilds the trade history
+    let journal = state.journal().clone();
+    let mut store = MemoryStore::default();
+    store.save(&journal).unwrap();
+    let restored = ServerState::restore(
+        Box::new(ClawcolatorEngine::new(state.engine.risk_engine().params)),
+        PassthroughAgent,
+        Box::new(store),
+    )
+    .unwrap();
+    assert_eq!(restored.history().candles(10, 0, u64::MAX), candles);
+}
+
+// ==============================================================================
+// SERVER FAULT INJECTION
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_server_fault_injection() {
+    use percolator::localhost::*;
+
+    let mut state = ServerState::new(PassthroughAgent);
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    let user = state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap().unwrap();
+    let trade = |size: i128, slot: u64| Command::Trade {
+        user_idx: user,
+        oracle_price: ORACLE,
+        size,
+        slot,
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: None,
+    };
+
+    // Oracle jump: fills happen at the shifted price
+    state.apply(Command::Inject(Fault::OracleJump { bps: -1000 })).unwrap();
+    state.apply(trade(1000, 0)).unwrap();
+    assert_eq!(state.engine.last_fill().unwrap().price, 900_000);
+    assert!(state.apply(Command::Inject(Fault::OracleJump { bps: -10_000 })).is_err());
+    state.apply(Command::Inject(Fault::Clear)).unwrap();
+    assert_eq!(state.faults().oracle_offset_bps, 0);
+
+    // Agent outage: trades fail until the outage ends
+    state.apply(Command::Inject(Fault::AgentOutage { slots: 10 })).unwrap();
+    assert!(matches!(state.apply(trade(1000, 0)), Err(ServerError::Engine(_))));
+    state.apply(Command::Crank { caller_idx: 0, oracle_price: ORACLE, slot: 10 }).unwrap();
+    state.apply(trade(1000, 10)).unwrap();
+
+    // Oracle outage: the price goes stale once the outage outlasts staleness
+    state.apply(Command::Inject(Fault::OracleOutage { slots: 100 })).unwrap();
+    state.apply(trade(1000, 10)).unwrap();
+    state.apply(Command::Crank { caller_idx: 0, oracle_price: ORACLE, slot: 50 }).unwrap();
+    assert!(state.apply(trade(1000, 50)).is_err());
+    state.apply(Command::Inject(Fault::Clear)).unwrap();
+    state.apply(trade(1000, 50)).unwrap();
+
+    // Mass liquidation: a leveraged account is closed out by the shocked crank
+    state.apply(trade(5_000_000, 50)).unwrap();
+    assert_ne!(state.engine.account_view(user, ORACLE).unwrap().position_size, 0);
+    state.apply(Command::Inject(Fault::MassLiquidation { shock_bps: -3000 })).unwrap();
+    let position = state.engine.account_view(user, 700_000).map_or(0, |v| v.position_size);
+    assert_eq!(position, 0);
+
+    // Injections are journaled and replay identically
+    let text = state.journal().encode();
+    assert!(text.contains("inject oracle_jump -1000\n"));
+    assert!(text.contains("inject mass_liquidation -3000\n"));
+    let mut store = MemoryStore::default();
+    store.save(&Snapshot::decode(&text).unwrap()).unwrap();
+    let restored = ServerState::restore(
+        Box::new(ClawcolatorEngine::new(state.engine.risk_engine().params)),
+        PassthroughAgent,
+        Box::new(store),
+    )
+    .unwrap();
+    assert_eq!(restored.engine.account_view(user, ORACLE), state.engine.account_view(user, ORACLE));
+    assert_eq!(restored.faults(), state.faults());
+}
+
+// ==============================================================================
+// AGENT HOT-SWAP
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_server_agent_hot_swap() {
+    use percolator::localhost::*;
+
+    let agents = || {
+        AgentRegistry::<BoxedAgent>::new()
+            .with("priced", || Box::new(PricedAgent { price: 1_001_000 }))
+            .with("passthrough", || Box::new(PassthroughAgent))
+            .with("reckless", || {
+                Box::new(ParamsAgent {
+                    params: MarketParams {
+                        max_leverage_bps: 20_000,
+                        ..MarketParams::default()
+                    },
+                })
+            })
+    };
+    assert_eq!(agents().names().collect::<Vec<_>>(), ["priced", "passthrough", "reckless"]);
+
+    let initial: BoxedAgent = Box::new(PassthroughAgent);
+    let mut state = ServerState::new(initial).with_agents(agents());
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    let user = state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap().unwrap();
+    let trade = Command::Trade {
+        user_idx: user,
+        oracle_price: ORACLE,
+        size: 1000,
+        slot: 0,
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: None,
+    };
+
+    // The dry run catches params the engine would reject; nothing changes
+    let reckless = state.engine.dry_run_agent(&agents().build("reckless").unwrap());
+    assert_eq!(reckless, Err(RiskError::Overflow));
+    let swap = |name: &str| Command::SwapAgent { name: name.to_string() };
+    assert!(state.apply(swap("reckless")).is_err());
+    assert!(state.apply(swap("missing")).is_err());
+    assert_eq!(state.agent_name(), None);
+
+    state.apply(swap("priced")).unwrap();
+    assert_eq!(state.agent_name(), Some("priced"));
+    state.apply(trade.clone()).unwrap();
+    assert_eq!(state.engine.last_fill().unwrap().price, 1_001_000);
+
+    // Swaps are events and replay from the journal
+    let events: Vec<_> = state.history().events(0).map(|e| (e.command.clone(), e.error)).collect();
+    assert!(events.contains(&(swap("reckless"), Some(RiskError::Overflow))));
+    assert!(events.contains(&(swap("priced"), None)));
+    let text = state.journal().encode();
+    assert!(text.contains("swap_agent priced\n"));
+    let mut store = MemoryStore::default();
+    store.save(&Snapshot::decode(&text).unwrap()).unwrap();
+    let restored = ServerState::restore_with_agents(
+        Box::new(ClawcolatorEngine::new(state.engine.risk_engine().params)),
+        Box::new(PassthroughAgent) as BoxedAgent,
+        agents(),
+        Box::new(store),
+    )
+    .unwrap();
+    assert_eq!(restored.agent_name(), Some("priced"));
+    assert_eq!(restored.engine.last_fill(), state.engine.last_fill());
+}
+
+// ==============================================================================
+// AGENT CAPABILITIES
+// ==============================================================================
+
+/// Agent implementing only the required hooks
+struct MinimalAgent;
+
+impl OpenClawAgent for MinimalAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+}
+
+/// Agent whose optional hooks all fail, declaring `capabilities`
+struct FailingHooksAgent {
+    capabilities: AgentCapabilities,
+}
+
+impl OpenClawAgent for FailingHooksAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+
+    fn assess_risk(&self, _context: &AgentContext) -> Result<RiskAssessment> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn detect_anomalies(&self, _context: &AgentContext) -> Result<AnomalyResponse> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn should_shutdown(&self, _context: &AgentContext) -> Result<bool> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn max_pool_utilization_bps(&self, _context: &AgentContext) -> Result<u64> {
+        Err(RiskError::Unauthorized)
+    }
+
+    fn agent_capabilities(&self) -> AgentCapabilities {
+        self.capabilities
+    }
+}
+
+#[test]
+fn test_agent_default_hooks_and_capabilities() {
+    // Defaults are neutral: nothing detected, no shutdown, all capital active
+    let mut engine = funded_engine();
+    let mut venue = HalfFillVenue { price: ORACLE };
+    assert_eq!(MinimalAgent.agent_capabilities(), AgentCapabilities::ALL);
+    assert_eq!(AgentCapabilities::ALL.version, AGENT_TRAIT_VERSION);
+    let context = engine.build_context(ORACLE_PX);
+    let allocation = MinimalAgent.decide_liquidity_allocation(&context).unwrap();
+    assert_eq!(allocation.target_active_capital, context.total_capital);
+    let outcome = engine.check_anomalies(&MinimalAgent, ORACLE_PX).unwrap();
+    assert_eq!(outcome.level, EscalationLevel::Logged);
+    engine.check_shutdown(&MinimalAgent, ORACLE_PX).unwrap();
+    engine.apply_risk_assessment(&MinimalAgent, &mut venue, 1, ORACLE_PX).unwrap();
+    engine.execute_trade(&MinimalAgent, 1, ORACLE_PX, 5_000, 1).unwrap();
+
+    // Hooks left out of the capabilities are never called
+    let core = FailingHooksAgent {
+        capabilities: AgentCapabilities::CORE,
+    };
+    engine.check_anomalies(&core, ORACLE_PX).unwrap();
+    engine.check_shutdown(&core, ORACLE_PX).unwrap();
+    engine.apply_risk_assessment(&core, &mut venue, 1, ORACLE_PX).unwrap();
+    engine.update_market_params(&core).unwrap();
+    engine.execute_trade(&core, 1, ORACLE_PX, 5_000, 1).unwrap();
+
+    let all = FailingHooksAgent {
+        capabilities: AgentCapabilities::ALL,
+    };
+    assert!(engine.check_anomalies(&all, ORACLE_PX).is_err());
+    assert!(engine.check_shutdown(&all, ORACLE_PX).is_err());
+    assert!(engine.update_market_params(&all).is_err());
+    assert!(engine.execute_trade(&all, 1, ORACLE_PX, 5_000, 1).is_err());
+}
+
+// ==============================================================================
+// CONTEXT EXTENSIONS
+// ==============================================================================
+
+/// Published by a hypothetical venue feed: funding on the reference venue
+const VENUE_FUNDING_BPS: ExtensionKey<i64> = ExtensionKey::new(1);
+const VENUE_HALTED: ExtensionKey<bool> = ExtensionKey::new(2);
+
+/// Rejects trades while the reference venue is halted
+struct VenueAwareAgent;
+
+impl OpenClawAgent for VenueAwareAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        if context.extensions.get(VENUE_HALTED) == Some(true) {
+            return Ok(TradeDecision::Reject {
+                reason: TradeRejectionReason::MarketConditions,
+            });
+        }
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+}
+
+#[test]
+fn test_context_builder_and_extensions() {
+    let context = AgentContext::builder()
+        .oracle_price(ORACLE)
+        .total_capital(9_000_000)
+        .risk_params(default_params())
+        .extension(VENUE_FUNDING_BPS, -12)
+        .unwrap()
+        .build();
+    assert_eq!(context.oracle_price, ORACLE);
+    assert_eq!(context.market_mode, MarketMode::AgentDriven);
+    assert_eq!(context.extensions.get(VENUE_FUNDING_BPS), Some(-12));
+    assert_eq!(context.extensions.get(VENUE_HALTED), None);
+
+    // Slots are bounded; re-setting a key reuses its slot
+    let mut extensions = ContextExtensions::default();
+    for id in 0..MAX_CONTEXT_EXTENSIONS as u32 {
+        extensions.insert(ExtensionKey::<u128>::new(id), u128::MAX).unwrap();
+    }
+    assert_eq!(extensions.get(ExtensionKey::<u128>::new(3)), Some(u128::MAX));
+    assert_eq!(extensions.insert(ExtensionKey::new(99), 1u64), Err(RiskError::Overflow));
+    extensions.insert(ExtensionKey::new(3), 7u64).unwrap();
+    assert!(extensions.remove(ExtensionKey::<u64>::new(3)));
+    assert!(!extensions.remove(ExtensionKey::<u64>::new(3)));
+    assert_eq!(extensions.len(), MAX_CONTEXT_EXTENSIONS - 1);
+
+    // Engine-published extensions reach the agent
+    let mut engine = funded_engine();
+    engine.set_context_extension(VENUE_HALTED, true).unwrap();
+    assert_eq!(engine.build_context(ORACLE_PX).extensions.get(VENUE_HALTED), Some(true));
+    assert!(engine.execute_trade(&VenueAwareAgent, 1, ORACLE_PX, 5_000, 1).is_err());
+    assert!(engine.remove_context_extension(VENUE_HALTED));
+    engine.execute_trade(&VenueAwareAgent, 1, ORACLE_PX, 5_000, 1).unwrap();
+}
+
+// ==============================================================================
+// DECISION META
+// ==============================================================================
+
+/// Passthrough that tags each decision with strategy 7
+struct ExplainingAgent;
+
+impl OpenClawAgent for ExplainingAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn decide_trade_with_meta(
+        &self,
+        context: &AgentContext,
+        request: &TradeRequest,
+    ) -> Result<(TradeDecision, Option<DecisionMeta>)> {
+        let decision = self.decide_trade(context, request)?;
+        let meta = DecisionMeta {
+            confidence_bps: 9_000,
+            strategy: 7,
+            model_version: 3,
+            reason_code: request.size.unsigned_abs() as u16,
+        };
+        Ok((decision, Some(meta)))
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+}
+
+#[test]
+fn test_decision_meta_recorded_with_fills_and_events() {
+    // Agents without meta behave as before
+    let mut engine = funded_engine();
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000, 1).unwrap();
+    assert_eq!(engine.last_decision_meta(), None);
+    assert_eq!(engine.last_fill().unwrap().meta, None);
+
+    engine.execute_trade(&ExplainingAgent, 1, ORACLE_PX, 1_000, 2).unwrap();
+    let meta = *engine.last_decision_meta().unwrap();
+    assert_eq!((meta.strategy, meta.model_version, meta.reason_code), (7, 3, 1_000));
+    assert_eq!(engine.last_fill().unwrap().meta, Some(meta));
+
+    // A trade that never reaches the agent clears the previous meta
+    engine.set_market_frozen(true);
+    assert!(engine.execute_trade(&ExplainingAgent, 1, ORACLE_PX, 1_000, 3).is_err());
+    assert_eq!(engine.last_decision_meta(), None);
+}
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_server_events_carry_decision_meta() {
+    use percolator::localhost::*;
+
+    let mut state = ServerState::new(ExplainingAgent);
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    let user = state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap().unwrap();
+    state
+        .apply(Command::Trade {
+            user_idx: user,
+            oracle_price: 1_000_000,
+            size: 500,
+            slot: 1,
+            client_order_id: None,
+            sequence: None,
+            valid_until_slot: None,
+        })
+        .unwrap();
+    state.apply(Command::SetFrozen { frozen: true }).unwrap();
+
+    let events: Vec<_> = state.history().events(0).collect();
+    assert_eq!(events[2].meta.map(|m| m.reason_code), Some(500));
+    // Non-trade commands never inherit the last trade's meta
+    assert_eq!(events[3].meta, None);
+    let fill = state.history().trades(0, u64::MAX).next().unwrap();
+    assert_eq!(fill.meta.map(|m| m.strategy), Some(7));
+}
+
+// ==============================================================================
+// TRADE OUTCOMES
+// ==============================================================================
+
+/// Agent that answers every request with a counter-quote for half the size
+struct QuotingAgent;
+
+impl OpenClawAgent for QuotingAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(TradeDecision::RequestQuote {
+            quote_price: context.oracle_price + 100,
+            max_size: request.size / 2,
+        })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+}
+
+#[test]
+fn test_rejected_trade_reports_cause_limits_and_quote() {
+    let mut engine = funded_engine();
+    engine.update_market_params(&TieredAgent).unwrap();
+    assert_eq!(engine.last_trade_outcome(), None);
+
+    // Retail position limit of 500 binds before leverage
+    assert_eq!(
+        engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000, 1),
+        Err(RiskError::Undercollateralized)
+    );
+    let rejection = *engine.last_trade_outcome().unwrap().rejection().unwrap();
+    assert_eq!(rejection.cause, RejectionCause::SizeLimit);
+    assert_eq!(rejection.error, RiskError::Undercollateralized);
+    assert_eq!(rejection.limits, TradeLimits { max_size: 500, max_leverage_bps: 1000 });
+    assert_eq!(rejection.counter_quote, None);
+
+    // Retrying at the reported size fills; selling may flip to -500
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 500, 1).unwrap();
+    let fill = *engine.last_trade_outcome().unwrap().fill().unwrap();
+    assert_eq!((fill.user_idx, fill.size), (1, 500));
+    engine.execute_trade(&QuotingAgent, 1, ORACLE_PX, -2_000, 1).unwrap_err();
+    let rejection = *engine.last_trade_outcome().unwrap().rejection().unwrap();
+    assert_eq!(rejection.cause, RejectionCause::CounterQuote);
+    assert_eq!(rejection.limits.max_size, 1_000);
+    assert_eq!(
+        rejection.counter_quote,
+        Some(CounterQuote { price: ORACLE + 100, max_size: -1_000 })
+    );
+
+    // A closed market has no room at all
+    engine.set_market_frozen(true);
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, -100, 2).unwrap_err();
+    let rejection = *engine.last_trade_outcome().unwrap().rejection().unwrap();
+    assert_eq!(rejection.cause, RejectionCause::MarketFrozen);
+    assert_eq!(rejection.limits.max_size, 0);
+}
+
+// ==============================================================================
+// MAX TRADEABLE SIZE
+// ==============================================================================
+
+/// Agent that only quotes up to 300 on either side
+struct ShallowAgent;
+
+impl OpenClawAgent for ShallowAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        PassthroughAgent.decide_trade(context, request)
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+
+    fn quote_capacity(&self, _context: &AgentContext, _user_idx: u16, _side: TradeSide) -> Result<u128> {
+        Ok(300)
+    }
+}
+
+#[test]
+fn test_max_tradeable_size_matches_acceptance() {
+    let mut engine = funded_engine();
+    engine.update_market_params(&TieredAgent).unwrap();
+    assert_eq!(engine.max_tradeable_size(1, TradeSide::Buy, ORACLE), 500);
+    assert_eq!(engine.max_tradeable_size(7, TradeSide::Buy, ORACLE), 0);
+
+    // Long 200: 300 more on the same side, through zero to -500 on the other
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 200, 1).unwrap();
+    assert_eq!(engine.max_tradeable_size(1, TradeSide::Buy, ORACLE), 300);
+    assert_eq!(engine.max_tradeable_size(1, TradeSide::Sell, ORACLE), 700);
+
+    // The agent's capacity only ever tightens the protocol's answer
+    assert_eq!(
+        engine.max_tradeable_size_with_agent(&ShallowAgent, 1, TradeSide::Sell, ORACLE_PX),
+        Ok(300)
+    );
+    assert_eq!(
+        engine.max_tradeable_size_with_agent(&PassthroughAgent, 1, TradeSide::Sell, ORACLE_PX),
+        Ok(700)
+    );
+
+    engine.set_market_frozen(true);
+    assert_eq!(engine.max_tradeable_size(1, TradeSide::Sell, ORACLE), 0);
+}
+
+#[test]
+fn test_dry_run_projects_fill_without_mutating() {
+    let mut engine = funded_engine();
+    engine.update_market_params(&TieredAgent).unwrap();
+    let capital = engine.risk_engine().accounts[1].capital.get();
+
+    let sim = engine.execute_trade_dry_run(&PassthroughAgent, 1, ORACLE_PX, -200, 1).unwrap();
+    assert_eq!((sim.price, sim.size, sim.position_size), (ORACLE, -200, -200));
+    assert!(sim.liquidation_price.unwrap() > ORACLE);
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 0);
+    assert!(engine.last_trade_outcome().is_none());
+
+    // The real trade lands where the simulation said
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, -200, 1).unwrap();
+    let account = engine.risk_engine().accounts[1];
+    assert_eq!(account.position_size.get(), sim.position_size);
+    assert_eq!(capital - account.capital.get(), sim.fee);
+    let view = engine.account_view(1, ORACLE).unwrap();
+    assert_eq!((view.equity, view.margin_ratio_bps), (sim.equity, sim.margin_ratio_bps));
+
+    // Rejections come back as execute_trade would record them
+    let rejection = engine.execute_trade_dry_run(&PassthroughAgent, 1, ORACLE_PX, -400, 1).unwrap_err();
+    assert_eq!(rejection.cause, RejectionCause::SizeLimit);
+    assert_eq!(rejection.limits.max_size, 300);
+}
+
+#[test]
+fn test_dry_run_and_max_size_count_cross_margin_top_up() {
+    let mut engine = funded_engine();
+    let sub = engine.open_sub_account(1, 0).unwrap();
+    engine.transfer_to_sub_account(sub, 1_000, ORACLE).unwrap();
+    let isolated = engine.max_tradeable_size(sub, TradeSide::Buy, ORACLE);
+    engine.set_margin_mode(1, MarginMode::Cross, ORACLE).unwrap();
+
+    // The parent's free capital backs the sub-account in both previews
+    let max_size = engine.max_tradeable_size(sub, TradeSide::Buy, ORACLE);
+    assert!(max_size > 50 * isolated);
+    let sim = engine.execute_trade_dry_run(&PassthroughAgent, sub, ORACLE_PX, 50_000, 1).unwrap();
+    assert_eq!(engine.account_view(sub, ORACLE).unwrap().capital, 1_000);
+
+    // The real fill lands where the simulation said
+    engine.execute_trade(&PassthroughAgent, sub, ORACLE_PX, 50_000, 1).unwrap();
+    let view = engine.account_view(sub, ORACLE).unwrap();
+    assert_eq!(view.position_size, sim.position_size);
+    assert_eq!((view.equity, view.margin_ratio_bps), (sim.equity, sim.margin_ratio_bps));
+
+    // And the largest size the protocol reports is one it fills
+    let room = engine.max_tradeable_size(sub, TradeSide::Buy, ORACLE);
+    let over = room as i128 + 10_000;
+    assert!(engine.execute_trade_dry_run(&PassthroughAgent, sub, ORACLE_PX, over, 1).is_err());
+    assert!(engine.execute_trade(&PassthroughAgent, sub, ORACLE_PX, over, 1).is_err());
+    engine.execute_trade_dry_run(&PassthroughAgent, sub, ORACLE_PX, room as i128, 1).unwrap();
+    engine.execute_trade(&PassthroughAgent, sub, ORACLE_PX, room as i128, 1).unwrap();
+}
+
+#[test]
+fn test_client_order_id_executes_once_per_window() {
+    let mut engine = funded_engine();
+    engine.set_client_order_window(5);
+    engine.execute_trade_with_id(&PassthroughAgent, 1, ORACLE_PX, 100, 42, 1).unwrap();
+    let fill = *engine.last_fill().unwrap();
+    assert_eq!(fill.client_order_id, Some(42));
+
+    // A retry returns the original fill without trading again
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 50, 2).unwrap();
+    engine.execute_trade_with_id(&PassthroughAgent, 1, ORACLE_PX, 100, 42, 3).unwrap();
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 150);
+    assert_eq!(engine.last_trade_outcome(), Some(&TradeOutcome::Filled(fill)));
+
+    // IDs are per account, and forgotten once the window has passed
+    let other = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.risk_engine_mut().deposit(other, 1_000_000, 0).unwrap();
+    engine.execute_trade_with_id(&PassthroughAgent, other, ORACLE_PX, 100, 42, 3).unwrap();
+    assert_eq!(engine.risk_engine().accounts[other as usize].position_size.get(), 100);
+    engine.execute_trade_with_id(&PassthroughAgent, 1, ORACLE_PX, 100, 42, 7).unwrap();
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 250);
+
+    // Rejections are replayed as well
+    engine.set_market_frozen(true);
+    assert!(engine.execute_trade_with_id(&PassthroughAgent, 1, ORACLE_PX, 100, 43, 7).is_err());
+    engine.set_market_frozen(false);
+    let retry = engine.execute_trade_with_id(&PassthroughAgent, 1, ORACLE_PX, 100, 43, 8);
+    assert_eq!(retry, Err(RiskError::Unauthorized));
+    assert_eq!(
+        engine.last_trade_outcome().and_then(|o| o.rejection()).map(|r| r.cause),
+        Some(RejectionCause::MarketFrozen)
+    );
+}
+
+#[test]
+fn test_sequence_numbers_reject_replay_and_reordering() {
+    let mut engine = funded_engine();
+    let request = |size: i128, sequence: u64| TradeRequest {
+        user_idx: 1,
+        size,
+        requested_price: None,
+        client_order_id: None,
+        sequence: Some(sequence),
+        valid_until_slot: None,
+    };
+    assert_eq!(engine.account_sequence(1), 0);
+    engine.submit_trade(&PassthroughAgent, request(100, 5), ORACLE_PX, 1).unwrap();
+    assert_eq!(engine.account_sequence(1), 5);
+
+    // Replayed and late requests are turned away without trading
+    for sequence in [5, 4] {
+        let result = engine.submit_trade(&PassthroughAgent, request(100, sequence), ORACLE_PX, 1);
+        assert_eq!(result, Err(RiskError::Unauthorized));
+        assert_eq!(
+            engine.last_trade_outcome().and_then(|o| o.rejection()).map(|r| r.cause),
+            Some(RejectionCause::Sequence)
+        );
+    }
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 100);
+
+    // Gaps are fine; withdrawals draw from the same sequence
+    engine.request_withdrawal_sequenced(1, 10, 9, 1, ORACLE).unwrap();
+    let capital = engine.risk_engine().accounts[1].capital.get();
+    assert_eq!(
+        engine.request_withdrawal_sequenced(1, 10, 9, 1, ORACLE),
+        Err(RiskError::Unauthorized)
+    );
+    assert_eq!(engine.risk_engine().accounts[1].capital.get(), capital);
+    assert!(engine.submit_trade(&PassthroughAgent, request(100, 8), ORACLE_PX, 1).is_err());
+    engine.submit_trade(&PassthroughAgent, request(100, 10), ORACLE_PX, 1).unwrap();
+}
+
+#[test]
+fn test_expired_trade_request_is_rejected() {
+    let mut engine = funded_engine();
+    let request = TradeRequest {
+        user_idx: 1,
+        size: 100,
+        requested_price: None,
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: Some(5),
+    };
+    // Relayed late: nothing executes at a price the user never saw
+    let late = engine.submit_trade(&PassthroughAgent, request, ORACLE_PX, 6);
+    assert_eq!(late, Err(RiskError::Unauthorized));
+    assert_eq!(
+        engine.last_trade_outcome().and_then(|o| o.rejection()).map(|r| r.cause),
+        Some(RejectionCause::Expired)
+    );
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 0);
+
+    // The deadline slot itself is still valid
+    engine.submit_trade(&PassthroughAgent, request, ORACLE_PX, 5).unwrap();
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 100);
+}
+
+/// Agent that fills every buy `bps` above the oracle (spread 1%)
+struct MarkupAgent(u64);
+
+impl OpenClawAgent for MarkupAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(TradeDecision::Accept {
+            price: context.oracle_price + context.oracle_price * self.0 / 10_000,
+            size: request.size,
+        })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams { spread_bps: 100, ..MarketParams::default() })
+    }
+}
+
+#[test]
+fn test_commitment_hash_is_sha256() {
+    use percolator::clawcolator::commit::sha256;
+    assert_eq!(
+        sha256(b"abc")[..4],
+        [0xba, 0x78, 0x16, 0xbf],
+        "FIPS 180-2 test vector"
+    );
+    let long = sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+    assert_eq!(long[28..], [0x19, 0xdb, 0x06, 0xc1]);
+}
+
+#[test]
+fn test_commit_reveal_prices_only_committed_trades() {
+    let mut engine = funded_engine();
+    engine.update_market_params(&MarkupAgent(0)).unwrap();
+    engine
+        .set_commit_reveal(CommitRevealParams { required: true, ..Default::default() })
+        .unwrap();
+    let reveal = TradeReveal { user_idx: 1, size: 100, max_slippage_bps: 50, salt: [7; 32] };
+
+    // Direct flow is refused outright, before the agent sees it
+    assert!(engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 100, 1).is_err());
+    assert_eq!(
+        engine.last_trade_outcome().and_then(|o| o.rejection()).map(|r| r.cause),
+        Some(RejectionCause::CommitRequired)
+    );
+
+    // Same-slot reveals and reveals of anything else are refused
+    engine.commit_trade(1, reveal.commitment(), 1).unwrap();
+    let cause = |engine: &ClawcolatorEngine| {
+        engine.last_trade_outcome().and_then(|o| o.rejection()).map(|r| r.cause)
+    };
+    assert!(engine.reveal_trade(&PassthroughAgent, reveal, ORACLE_PX, 1).is_err());
+    assert_eq!(cause(&engine), Some(RejectionCause::Commitment));
+    engine.commit_trade(1, reveal.commitment(), 1).unwrap();
+    let bigger = TradeReveal { size: 1_000, ..reveal };
+    assert!(engine.reveal_trade(&PassthroughAgent, bigger, ORACLE_PX, 2).is_err());
+    assert_eq!(cause(&engine), Some(RejectionCause::Commitment));
+
+    // The agent must fill within the committed slippage
+    assert!(engine.reveal_trade(&MarkupAgent(100), reveal, ORACLE_PX, 2).is_err());
+    assert_eq!(cause(&engine), Some(RejectionCause::Slippage));
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 0);
+    engine.commit_trade(1, reveal.commitment(), 2).unwrap();
+    engine.reveal_trade(&MarkupAgent(20), reveal, ORACLE_PX, 3).unwrap();
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 100);
+
+    // Each commitment opens once
+    assert!(engine.reveal_trade(&PassthroughAgent, reveal, ORACLE_PX, 3).is_err());
+    assert_eq!(engine.commit_book().iter().count(), 0);
+}
+
+/// Agent that clears every batch `bps` above the oracle and counts its calls
+struct BatchAgent {
+    bps: u64,
+    calls: core::cell::Cell<u32>,
+}
+
+impl OpenClawAgent for BatchAgent {
+    fn decide_trade(&self, _context: &AgentContext, _request: &TradeRequest) -> Result<TradeDecision> {
+        self.calls.set(self.calls.get() + 1);
+        Ok(TradeDecision::Reject { reason: TradeRejectionReason::MarketConditions })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+
+    fn batch_clearing_price(&self, context: &AgentContext, batch: &BatchSummary) -> Result<u64> {
+        self.calls.set(self.calls.get() + 1);
+        assert_eq!((batch.orders, batch.buy_size, batch.sell_size), (2, 150, 100));
+        Ok(context.oracle_price + context.oracle_price * self.bps / 10_000)
+    }
+}
+
+#[test]
+fn test_batch_auction_clears_at_one_uniform_price() {
+    let mut engine = funded_engine();
+    let other = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.risk_engine_mut().deposit(other, 1_000_000, 0).unwrap();
+    engine
+        .set_batch_auction(Some(BatchAuctionParams { batch_slots: 5, max_deviation_bps: 100 }))
+        .unwrap();
+
+    // Direct trades are refused while batching
+    assert!(engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 100, 1).is_err());
+    assert_eq!(
+        engine.last_trade_outcome().and_then(|o| o.rejection()).map(|r| r.cause),
+        Some(RejectionCause::BatchMode)
+    );
+
+    // Orders of one account are netted; the batch is due 5 slots after it opened
+    engine.submit_batch_order(1, 100, 1).unwrap();
+    engine.submit_batch_order(1, 50, 2).unwrap();
+    engine.submit_batch_order(other, -100, 3).unwrap();
+    assert_eq!(engine.batch_book().iter().count(), 2);
+    let agent = BatchAgent { bps: 50, calls: core::cell::Cell::new(0) };
+    assert!(engine.clear_batch(&agent, ORACLE_PX, 5).is_err());
+
+    // A price outside the band leaves the batch open
+    let greedy = BatchAgent { bps: 200, calls: core::cell::Cell::new(0) };
+    assert_eq!(engine.clear_batch(&greedy, ORACLE_PX, 6), Err(RiskError::InvalidMatchingEngine));
+    assert_eq!(engine.batch_book().iter().count(), 2);
+
+    // One pricing call fills every order at the same price
+    let report = engine.clear_batch(&agent, ORACLE_PX, 6).unwrap();
+    assert_eq!(agent.calls.get(), 1);
+    assert_eq!((report.price, report.filled, report.rejected), (1_005_000, 2, 0));
+    assert!(engine.batch_fills().all(|fill| fill.price == 1_005_000));
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 150);
+    assert_eq!(engine.risk_engine().accounts[other as usize].position_size.get(), -100);
+    assert_eq!(engine.batch_book().opened_slot(), None);
+
+    // Batch mode can be left once the book is empty
+    engine.submit_batch_order(1, 10, 7).unwrap();
+    assert_eq!(engine.set_batch_auction(None), Err(RiskError::Unauthorized));
+    assert_eq!(engine.cancel_batch_order(1), Some(10));
+    engine.set_batch_auction(None).unwrap();
+}
+
+/// Agent publishing default market params with `policy` for lending
+struct LenderAgent(LendingPolicy);
+
+impl OpenClawAgent for LenderAgent {
+    fn decide_trade(&self, _context: &AgentContext, _request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(TradeDecision::Reject { reason: TradeRejectionReason::MarketConditions })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams { lending_policy: self.0, ..MarketParams::default() })
+    }
+}
+
+#[test]
+fn test_margin_loans_accrue_interest_and_default_to_insurance() {
+    let mut engine = funded_engine();
+    assert_eq!(engine.borrow(1, 100_000, 1, ORACLE), Err(RiskError::Unauthorized));
+    let policy = LendingPolicy { interest_bps_per_slot: 1, max_utilization_bps: 5_000, max_borrow_bps: 10_000 };
+    let greedy = LendingPolicy { interest_bps_per_slot: 50, ..policy };
+    assert!(engine.update_market_params(&LenderAgent(greedy)).is_err());
+    engine.update_market_params(&LenderAgent(policy)).unwrap();
+
+    // At most the borrower's own capital; the LP pays out the loan
+    assert_eq!(engine.borrow(1, 1_000_001, 10, ORACLE), Err(RiskError::Undercollateralized));
+    engine.borrow(1, 500_000, 10, ORACLE).unwrap();
+    assert_eq!(engine.risk_engine().accounts[0].capital.get(), 9_500_000);
+    assert_eq!(engine.risk_engine().accounts[1].capital.get(), 1_500_000);
+    assert_eq!(engine.lending_utilization_bps(), 500);
+    assert_eq!(
+        engine.request_withdrawal(1, 1_200_000, 10, ORACLE),
+        Err(RiskError::InsufficientBalance)
+    );
+
+    // 10 slots at 1 bps, repaid interest first
+    engine.crank(1, 20, ORACLE, None).unwrap();
+    assert_eq!(engine.loan_debt(1), 500_500);
+    assert_eq!(engine.repay_loan(1, u128::MAX, 20, ORACLE), Ok(500_500));
+    assert_eq!(engine.lending().totals().interest_paid, 500);
+    assert_eq!(engine.risk_engine().accounts[0].capital.get(), 10_000_500);
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+
+    // A flat borrower short of its debt: capital, then insurance, then write-off
+    engine.borrow(1, 100_000, 20, ORACLE).unwrap();
+    engine.risk_engine_mut().withdraw(1, 1_050_000, 20, ORACLE).unwrap();
+    engine.risk_engine_mut().top_up_insurance_fund(30_000).unwrap();
+    engine.crank(1, 20, ORACLE, None).unwrap();
+    let totals = *engine.lending().totals();
+    assert_eq!((totals.insurance_covered, totals.written_off), (30_000, 20_500));
+    assert_eq!(engine.loan_debt(1), 0);
+    assert_eq!(engine.risk_engine().accounts[1].capital.get(), 0);
+    assert_eq!(engine.risk_engine().insurance_fund.balance.get(), 0);
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+}
+
+#[test]
+fn test_compaction_carries_loans_withdrawals_and_sub_accounts() {
+    let mut engine = funded_engine();
+    let risk = engine.risk_engine_mut();
+    let fillers = [risk.add_user(0).unwrap(), risk.add_user(0).unwrap()];
+    for filler in fillers {
+        risk.deposit(filler, 1_000, 0).unwrap();
+    }
+    let parent = risk.add_user(0).unwrap();
+    risk.deposit(parent, 1_000_000, 0).unwrap();
+    let params = MarketParams {
+        lending_policy: LendingPolicy { interest_bps_per_slot: 0, max_utilization_bps: 5_000, max_borrow_bps: 10_000 },
+        withdrawal_policy: WithdrawalPolicy { queue_threshold: 100_000, delay_slots: 100, expedite_utilization_bps: 0 },
+        ..MarketParams::default()
+    };
+    engine.update_market_params(&ParamsAgent { params }).unwrap();
+    engine.crank(0, 1, ORACLE, None).unwrap();
+
+    engine.set_account_tier(parent, AccountTier::Pro, ORACLE).unwrap();
+    let sub = engine.open_sub_account(parent, 0).unwrap();
+    engine.set_margin_mode(parent, MarginMode::Cross, ORACLE).unwrap();
+    engine.borrow(parent, 200_000, 1, ORACLE).unwrap();
+    assert_eq!(
+        engine.request_withdrawal(parent, 300_000, 1, ORACLE),
+        Ok(WithdrawalStatus::Queued { release_slot: 101 })
+    );
+    for filler in fillers {
+        engine.risk_engine_mut().close_account(filler, 1, ORACLE).unwrap();
+    }
+
+    // Highest first: the sub-account, then its parent
+    assert_eq!(engine.compact_accounts(u16::MAX), 2);
+    let (new_sub, new_parent) = (fillers[0], fillers[1]);
+    assert_eq!((sub, parent), (new_parent + 2, new_parent + 1));
+    assert_eq!(engine.loan_debt(new_parent), 200_000);
+    assert_eq!(engine.loan_debt(parent), 0);
+    assert_eq!(engine.withdrawal_queue().entries()[0].account_idx, new_parent);
+    assert_eq!(engine.account_tier(new_parent), Ok(AccountTier::Pro));
+    assert_eq!(engine.margin_mode(new_sub), MarginMode::Cross);
+    assert_eq!(engine.user_aggregate(new_parent, ORACLE).unwrap().sub_accounts, 1);
+    engine.transfer_to_sub_account(new_sub, 1_000, ORACLE).unwrap();
+
+    // The queued withdrawal is paid to the moved account
+    engine.crank(0, 101, ORACLE, None).unwrap();
+    assert!(engine.withdrawal_queue().is_empty());
+    assert_eq!(engine.risk_engine().accounts[new_parent as usize].capital.get(), 899_000);
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+}
+
+#[test]
+fn test_wind_down_ramps_margins_and_settles_at_twap() {
+    let mut engine = funded_engine();
+    engine.risk_engine_mut().top_up_insurance_fund(10_000).unwrap(); // No force-realize
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 200_000, 1).unwrap();
+    let params = WindDownParams {
+        ramp_slots: 100,
+        target_maintenance_margin_bps: 1_000,
+        target_initial_margin_bps: 2_000,
+        settle_after_slots: 200,
+        twap_slots: 100,
+    };
+    let lower = WindDownParams { target_maintenance_margin_bps: 100, ..params };
+    assert_eq!(engine.start_wind_down(lower, 0), Err(RiskError::Overflow));
+    engine.start_wind_down(params, 0).unwrap();
+
+    // Reduce-only from the start
+    let oracle = |price, slot| OraclePrice { price, confidence: 0, publish_slot: slot };
+    assert!(engine.execute_trade(&PassthroughAgent, 1, oracle(ORACLE, 2), 10, 2).is_err());
+    assert_eq!(
+        engine.last_trade_outcome().and_then(|o| o.rejection()).map(|r| r.cause),
+        Some(RejectionCause::RiskReduction)
+    );
+    engine.execute_trade(&PassthroughAgent, 1, oracle(ORACLE, 2), -50_000, 2).unwrap();
+
+    // Margins follow the ramp on each crank
+    engine.crank(1, 50, ORACLE, None).unwrap();
+    let progress = engine.wind_down_progress(50).unwrap();
+    assert_eq!((progress.phase, progress.ramp_bps), (WindDownPhase::Ramping, 5_000));
+    assert_eq!(engine.risk_engine().params.maintenance_margin_bps, 750);
+    assert_eq!(engine.risk_engine().params.initial_margin_bps, 1_500);
+    engine.crank(1, 150, 1_100_000, None).unwrap();
+    let progress = engine.wind_down_progress(150).unwrap();
+    assert_eq!((progress.phase, progress.open_positions), (WindDownPhase::AwaitingSettlement, 2));
+
+    // Residual positions settle at the TWAP of slots 100..200
+    engine.crank(1, 200, 1_200_000, None).unwrap();
+    let progress = engine.wind_down_progress(200).unwrap();
+    assert_eq!(progress.phase, WindDownPhase::Settled);
+    assert_eq!(progress.settlement_price, Some(1_050_000));
+    assert_eq!((progress.open_positions, progress.open_interest), (0, 0));
+    assert_eq!(engine.wind_down().map(|w| w.settled_positions), Some(2));
+    assert!(engine.risk_engine().check_conservation(1_200_000));
+    engine.request_withdrawal(1, 500_000, 200, 1_200_000).unwrap();
+}
+
+#[test]
+fn test_dead_man_switch_reduces_then_freezes_a_silent_agent() {
+    let mut engine = funded_engine();
+    engine.risk_engine_mut().top_up_insurance_fund(10_000).unwrap(); // No force-realize
+    let params = DeadManParams { reduce_after_slots: 100, freeze_after_slots: 100, margin_widen_bps: 500 };
+    let invalid = DeadManParams { reduce_after_slots: 0, ..params };
+    assert_eq!(engine.set_dead_man_switch(Some(invalid)), Err(RiskError::Overflow));
+    engine.set_dead_man_switch(Some(params)).unwrap();
+    let base = engine.risk_engine().params.maintenance_margin_bps;
+
+    let oracle = |slot| OraclePrice { price: ORACLE, confidence: 0, publish_slot: slot };
+    engine.execute_trade(&PassthroughAgent, 1, oracle(1), 200_000, 1).unwrap();
+    assert_eq!(engine.last_agent_activity_slot(), 1);
+    engine.crank(1, 50, ORACLE, None).unwrap();
+    assert_eq!(engine.dead_man_status(50).map(|s| s.stage), Some(DeadManStage::Live));
+
+    // Silent past the first threshold: reduce-only with wider margins
+    engine.crank(1, 150, ORACLE, None).unwrap();
+    let status = engine.dead_man_status(150).unwrap();
+    assert_eq!((status.stage, status.idle_slots), (DeadManStage::RiskReduction, 149));
+    assert!(engine.risk_reduction_triggers().agent_timeout);
+    assert_eq!(engine.risk_engine().params.maintenance_margin_bps, base + 500);
+    engine.crank(1, 160, ORACLE, None).unwrap();
+    assert_eq!(engine.risk_engine().params.maintenance_margin_bps, base + 500); // Not compounded
+
+    // Silent past the second: frozen, trades never reach the agent
+    engine.crank(1, 300, ORACLE, None).unwrap();
+    assert_eq!(engine.dead_man_status(300).map(|s| s.stage), Some(DeadManStage::Frozen));
+    assert!(engine.execute_trade(&PassthroughAgent, 1, oracle(300), -10, 300).is_err());
+    assert_eq!(
+        engine.last_trade_outcome().and_then(|o| o.rejection()).map(|r| r.cause),
+        Some(RejectionCause::MarketFrozen)
+    );
+    assert_eq!(engine.last_agent_activity_slot(), 1);
+
+    // A params update from the agent lifts everything at the next crank
+    engine.update_market_params(&PassthroughAgent).unwrap();
+    assert_eq!(engine.last_agent_activity_slot(), 300);
+    engine.crank(1, 301, ORACLE, None).unwrap();
+    assert_eq!(engine.dead_man_status(301).map(|s| s.stage), Some(DeadManStage::Live));
+    assert!(!engine.risk_reduction_mode());
+    assert_eq!(engine.risk_engine().params.maintenance_margin_bps, base);
+    engine.execute_trade(&PassthroughAgent, 1, oracle(301), 10_000, 301).unwrap();
+}
+
+/// Agent whose heartbeat reports it cannot serve
+struct StalledAgent;
+
+impl OpenClawAgent for StalledAgent {
+    fn decide_trade(&self, _context: &AgentContext, _request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(TradeDecision::Reject { reason: TradeRejectionReason::MarketConditions })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+
+    fn heartbeat(&self, _context: &AgentContext) -> Result<()> {
+        Err(RiskError::Unauthorized)
+    }
+}
+
+#[test]
+fn test_heartbeats_keep_the_dead_man_switch_off() {
+    let mut engine = funded_engine();
+    let params = DeadManParams { reduce_after_slots: 100, freeze_after_slots: 100, margin_widen_bps: 500 };
+    engine.set_dead_man_switch(Some(params)).unwrap();
+    assert_eq!(engine.build_context(ORACLE_PX).heartbeat_age_slots, None);
+
+    engine.record_heartbeat(&PassthroughAgent, 90).unwrap();
+    assert_eq!(engine.record_heartbeat(&StalledAgent, 180), Err(RiskError::Unauthorized));
+    assert_eq!(engine.last_heartbeat_slot(), Some(90));
+    engine.crank(1, 150, ORACLE, None).unwrap();
+    assert_eq!(engine.dead_man_status(150).map(|s| s.stage), Some(DeadManStage::Live));
+    assert_eq!(engine.build_context(ORACLE_PX).heartbeat_age_slots, Some(60));
+
+    // Only the failed heartbeat since slot 90
+    engine.crank(1, 190, ORACLE, None).unwrap();
+    assert!(engine.risk_reduction_mode());
+    engine.record_heartbeat(&PassthroughAgent, 191).unwrap();
+    engine.crank(1, 192, ORACLE, None).unwrap();
+    assert!(!engine.risk_reduction_mode());
+    assert_eq!(engine.heartbeat_age(200), Some(9));
+}
+
+#[test]
+fn test_flapping_params_lock_at_the_tightest_recent_values() {
+    let mut engine = funded_engine();
+    let guard = FlappingGuard { max_flips: 2, window_slots: 100 };
+    assert!(engine.set_flapping_guard(Some(FlappingGuard { max_flips: 0, ..guard })).is_err());
+    engine.set_flapping_guard(Some(guard)).unwrap();
+    let params = |spread_bps, max_leverage_bps| ParamsAgent {
+        params: MarketParams { spread_bps, max_leverage_bps, ..MarketParams::default() },
+    };
+    let (wide, tight) = (params(10, 800), params(50, 1_000));
+
+    // Changes spread out over more than the window are not flapping
+    for (slot, agent) in [(10, &wide), (150, &tight), (300, &wide)] {
+        engine.crank(1, slot, ORACLE, None).unwrap();
+        engine.update_market_params(agent).unwrap();
+    }
+    engine.update_market_params(&wide).unwrap(); // Unchanged: not a flip
+    assert!(!engine.params_locked());
+
+    // Three flips in one window: locked at the tightest of each limit
+    engine.crank(1, 320, ORACLE, None).unwrap();
+    engine.update_market_params(&tight).unwrap();
+    engine.update_market_params(&wide).unwrap();
+    assert!(engine.params_locked());
+    assert!(engine.build_context(ORACLE_PX).params_locked);
+    let locked = engine.market_params();
+    assert_eq!((locked.spread_bps, locked.max_leverage_bps), (50, 800));
+    assert_eq!(
+        engine.operator_alerts().latest(),
+        Some(&OperatorAlert { slot: 320, kind: AlertKind::DecisionFlapping { flips: 3 } })
+    );
+    assert_eq!(engine.update_market_params(&tight), Err(RiskError::Unauthorized));
+
+    engine.unlock_params();
+    engine.update_market_params(&tight).unwrap();
+    assert_eq!(engine.market_params().max_leverage_bps, 1_000);
+    assert_eq!(engine.operator_alerts().len(), 1);
+}
+
+#[test]
+fn test_metering_attributes_costs_to_the_outermost_operation() {
+    use percolator::metering::{self, MeteredOp, OpCost};
+
+    let mut engine = funded_engine();
+    metering::reset();
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 200_000, 1).unwrap();
+    engine.crank(1, 2, ORACLE, None).unwrap();
+    engine.crank(1, 3, ORACLE, None).unwrap();
+    let baseline = metering::report();
+
+    // Without the feature every hook is a no-op and reports stay zero
+    if !metering::ENABLED {
+        assert!(baseline.iter().all(|(_, cost)| *cost == OpCost::default()));
+        return;
+    }
+    let (trade, crank) = (baseline.get(MeteredOp::Trade), baseline.get(MeteredOp::Crank));
+    assert_eq!((trade.calls, crank.calls), (1, 2));
+    assert!(trade.math_ops > 0);
+    assert!(crank.account_scans >= 2 * 2); // Both accounts, each crank
+    assert_eq!(baseline.get(MeteredOp::Other).calls, 0); // keeper_crank nests under crank
+    assert_eq!(baseline.regressions(&baseline, 0).count(), 0);
+
+    // More accounts to scan: the crank regresses against the baseline
+    metering::reset();
+    for _ in 0..8 {
+        engine.risk_engine_mut().add_user(0).unwrap();
+    }
+    engine.crank(1, 4, ORACLE, None).unwrap();
+    let report = metering::report();
+    assert_eq!(report.regressions(&baseline, 1_000).collect::<Vec<_>>(), vec![MeteredOp::Crank]);
+}
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_server_dedups_client_orders() {
+    use percolator::localhost::*;
+
+    let mut state = ServerState::new(PassthroughAgent);
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    let user = state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap().unwrap();
+    let trade = Command::Trade {
+        user_idx: user,
+        oracle_price: ORACLE,
+        size: 500,
+        slot: 0,
+        client_order_id: Some(9),
+        sequence: None,
+        valid_until_slot: None,
+    };
+    assert_eq!(trade.encode(), format!("trade {} 1000000 500 0 id=9", user));
+    assert_eq!(Command::decode(&trade.encode()), Some(trade.clone()));
+    let tagged = format!("trade {} 1000000 500 0 until=3 seq=2 id=9", user);
+    assert!(matches!(
+        Command::decode(&tagged),
+        Some(Command::Trade { client_order_id: Some(9), sequence: Some(2), valid_until_slot: Some(3), .. })
+    ));
+    assert_eq!(Command::decode("trade 1 1000000 500 0 ttl=3"), None);
+    let reveal = Command::RevealTrade {
+        reveal: TradeReveal { user_idx: user, size: 5, max_slippage_bps: 10, salt: [0xab; 32] },
+        oracle_price: ORACLE,
+        slot: 3,
+    };
+    let commit = Command::CommitTrade { user_idx: user, commitment: [1; 32], slot: 2 };
+    let order = Command::SubmitBatchOrder { user_idx: user, size: -5, slot: 2 };
+    let clear = Command::ClearBatch { oracle_price: ORACLE, slot: 4 };
+    for command in [reveal, commit, order, clear, Command::Heartbeat { slot: 5 }] {
+        assert_eq!(Command::decode(&command.encode()), Some(command));
+    }
+    state.apply(trade.clone()).unwrap();
+    state.apply(trade).unwrap();
+
+    // The duplicate is journaled (replay dedups it again) but leaves no second fill
+    assert_eq!(state.history().trades(0, u64::MAX).count(), 1);
+    assert_eq!(state.engine.risk_engine().accounts[user as usize].position_size.get(), 500);
+    assert_eq!(state.journal().commands.len(), 4);
+}
+
+#[test]
+fn test_max_tradeable_size_respects_concentration_cap() {
+    let mut engine = funded_engine();
+    let other = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.risk_engine_mut().deposit(other, 1_000_000, 0).unwrap();
+    let agent = concentration_agent(3_000);
+    engine.update_market_params(&agent).unwrap();
+    engine.execute_trade(&agent, 1, ORACLE_PX, 400_000, 1).unwrap();
+    engine.execute_trade(&agent, other, ORACLE_PX, -450_000, 1).unwrap();
+
+    // The reported size is the exact edge of the cap
+    let max = engine.max_tradeable_size(1, TradeSide::Buy, ORACLE);
+    assert!(max > 0 && max < 100_000);
+    assert_eq!(
+        engine.execute_trade(&agent, 1, ORACLE_PX, max as i128 + 1, 1),
+        Err(RiskError::Undercollateralized)
+    );
+    engine.execute_trade(&agent, 1, ORACLE_PX, max as i128, 1).unwrap();
+}
+
+#[test]
+fn test_dutch_auction_liquidates_bankrupt_positions() {
+    let mut engine = funded_engine();
+    let bad = LiquidationAuctionParams { duration_slots: 0, max_discount_bps: 500 };
+    assert_eq!(engine.set_liquidation_auctions(Some(bad)), Err(RiskError::Overflow));
+    engine.set_liquidation_auctions(Some(LiquidationAuctionParams::default())).unwrap();
+    let bidder = engine.risk_engine_mut().add_user(0).unwrap();
+    engine.risk_engine_mut().deposit(bidder, 5_000_000, 0).unwrap();
+    engine.risk_engine_mut().top_up_insurance_fund(1_000_000).unwrap();
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 9_000_000, 1).unwrap();
+
+    // A 12% drop wipes out the user: the crank holds it and opens a lot
+    let crashed = 880_000;
+    engine.crank(0, 2, crashed, None).unwrap();
+    let lot = *engine.liquidation_auctions().lot(1).unwrap();
+    assert_eq!((lot.size, lot.start_slot), (9_000_000, 2));
+    assert!(engine.risk_engine().is_liquidation_held(1));
+    assert_eq!(engine.liquidate(1, 2, crashed), Ok(false));
+    let oracle = OraclePrice { price: crashed, confidence: 0, publish_slot: 2 };
+    assert_eq!(
+        engine.execute_trade(&PassthroughAgent, 1, oracle, -1_000, 2),
+        Err(RiskError::Unauthorized)
+    );
+
+    // The price improves for takers as the auction runs
+    assert_eq!(lot.price_at(2, crashed), crashed);
+    assert_eq!(lot.price_at(7, crashed), 858_000);
+    assert_eq!(lot.price_at(50, crashed), 836_000);
+    assert_eq!(
+        engine.bid_liquidation_auction(1, 1, 1_000, 7, crashed),
+        Err(RiskError::Unauthorized)
+    );
+    let fill = engine.bid_liquidation_auction(bidder, 1, 4_000_000, 7, crashed).unwrap();
+    assert_eq!(fill, AuctionFill { price: 858_000, size: 4_000_000, discount: 88_000 });
+    assert_eq!(engine.risk_engine().accounts[bidder as usize].position_size.get(), 4_000_000);
+    assert_eq!(engine.liquidation_auctions().lot(1).unwrap().size, 5_000_000);
+
+    // Unbid size goes to the LP at the final price; insurance covers what
+    // the user's capital cannot: 1_080_000 mark + 88_000 + 220_000 - 991_000
+    let insurance = engine.risk_engine().insurance_fund.balance.get();
+    engine.crank(0, 12, crashed, None).unwrap();
+    assert!(engine.liquidation_auctions().is_empty());
+    assert!(!engine.risk_engine().is_liquidation_held(1));
+    assert_eq!(engine.risk_engine().accounts[1].position_size.get(), 0);
+    assert_eq!(engine.risk_engine().accounts[0].position_size.get(), -9_000_000 + 5_000_000);
+    let totals = *engine.liquidation_auctions().totals();
+    assert_eq!((totals.lots, totals.taken_by_bidders, totals.taken_by_lp), (1, 4_000_000, 5_000_000));
+    assert_eq!((totals.insurance_covered, totals.uncovered), (397_000, 0));
+    let entry = *engine.bankruptcies().get(0).unwrap();
+    assert_eq!((entry.idx, entry.cause, entry.hole), (1, BankruptcyCause::Auction, 397_000));
+    assert_eq!((entry.insurance, entry.socialized), (397_000, 0));
+    let risk = engine.risk_engine();
+    assert_eq!(risk.insurance_fund.balance.get(), insurance - 397_000);
+    assert!(risk.vault.get() >= risk.c_tot.get() + risk.insurance_fund.balance.get());
+}
+
+#[test]
+fn test_backstops_absorb_liquidations_pro_rata_for_fee_rebates() {
+    let mut engine = funded_engine();
+    assert_eq!(engine.register_backstop(1), Err(RiskError::Unauthorized));
+    engine.set_backstop_params(Some(BackstopParams::default())).unwrap();
+    let mut backstops = [0u16; 2];
+    for (slot, deposit) in backstops.iter_mut().zip([3_000_000, 1_000_000]) {
+        *slot = engine.risk_engine_mut().add_user(0).unwrap();
+        engine.risk_engine_mut().deposit(*slot, deposit, 0).unwrap();
+        engine.register_backstop(*slot).unwrap();
+    }
+    let [big, small] = backstops;
+    assert_eq!(engine.backstops().len(), 2);
+
+    // An 8% drop puts the user below maintenance; backstops split the
+    // position 3:1 (by capital) at 2% under the oracle
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 9_000_000, 1).unwrap();
+    let dropped = 920_000;
+    assert_eq!(engine.liquidate(1, 2, dropped), Ok(true));
+    let risk = engine.risk_engine();
+    assert_eq!(risk.accounts[1].position_size.get(), 0);
+    assert_eq!(risk.accounts[big as usize].position_size.get(), 6_750_000);
+    assert_eq!(risk.accounts[small as usize].position_size.get(), 2_250_000);
+    let earned = engine.backstops().get(big).unwrap();
+    assert_eq!((earned.absorbed, earned.discount_earned), (6_750_000, 124_200));
+    assert_eq!(engine.backstops().get(small).unwrap().discount_earned, 41_400);
+
+    // The user keeps what is left: 991_000 - 720_000 mark - 165_600 discount
+    assert_eq!(engine.risk_engine().accounts[1].capital.get(), 105_400);
+
+    // Half of a backstop's 1_000 trading fee comes back from insurance
+    let before = engine.risk_engine().accounts[small as usize].capital.get();
+    engine.execute_trade(&PassthroughAgent, small, ORACLE_PX, -1_000_000, 3).unwrap();
+    let after = engine.risk_engine().accounts[small as usize].capital.get();
+    assert_eq!(engine.backstops().get(small).unwrap().rebates, 500);
+    assert_eq!(after, before - 1_000 + 500);
+    assert_eq!(engine.backstops().get(big).unwrap().rebates, 0);
+    engine.deregister_backstop(small).unwrap();
+    assert_eq!(engine.deregister_backstop(small), Err(RiskError::AccountNotFound));
+}
+
+#[test]
+fn test_bankruptcy_ledger_records_holes_and_their_cover() {
+    let mut engine = funded_engine();
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 9_000_000, 1).unwrap();
+    let account_id = engine.risk_engine().accounts[1].account_id;
+
+    // Closed at the oracle after a 12% drop: the loss beyond capital is
+    // written off and left to the haircut
+    assert_eq!(engine.liquidate(1, 2, 880_000), Ok(true));
+    assert_eq!(engine.risk_engine().accounts[1].capital.get(), 0);
+    let entry = *engine.bankruptcies().get(0).unwrap();
+    assert_eq!((entry.slot, entry.idx, entry.account_id), (2, 1, account_id));
+    assert_eq!((entry.cause, entry.hole, entry.insurance), (BankruptcyCause::WriteOff, 89_000, 0));
+    assert_eq!(entry.socialized, entry.hole);
+    assert_eq!(engine.bankruptcies().for_account(account_id).count(), 1);
+
+    // Totals reach the epoch report and the agent's context
+    let totals = *engine.bankruptcies().totals();
+    assert_eq!((totals.count, totals.hole, totals.socialized), (1, 89_000, 89_000));
+    assert_eq!(engine.current_epoch().bankruptcies, totals);
+    assert_eq!(engine.build_context(ORACLE_PX).bankruptcies, totals);
+    assert_eq!(engine.risk_engine().write_offs_len, 0);
+}
+
+#[test]
+fn test_referrers_accrue_a_capped_fee_share_and_claim_it() {
+    let mut engine = funded_engine();
+    let referrer = engine.risk_engine_mut().add_user(0).unwrap();
+    assert_eq!(engine.register_referrer(1, referrer), Err(RiskError::Unauthorized));
+    let greedy = ReferralPolicy { share_bps: 2_500 };
+    assert_eq!(engine.set_referral_policy(Some(greedy)), Err(RiskError::Overflow));
+    engine.set_referral_policy(Some(ReferralPolicy { share_bps: 1_000 })).unwrap();
+    assert_eq!(engine.set_referral_bounds(ReferralBounds { max_share_bps: 500 }), Err(RiskError::Overflow));
+    assert_eq!(engine.register_referrer(1, 1), Err(RiskError::Unauthorized));
+    engine.register_referrer(1, referrer).unwrap();
+    assert_eq!(engine.register_referrer(1, 0), Err(RiskError::Unauthorized));
+
+    // 10% of the 1_000 fee comes out of the insurance share
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000_000, 1).unwrap();
+    let fees = *engine.fee_totals();
+    assert_eq!((fees.insurance, fees.referral, fees.total()), (900, 100, 1_000));
+    assert_eq!(engine.pending_referral_rebates(referrer), 100);
+    assert_eq!(engine.referrals().pending_total(), 100);
+
+    let insurance = engine.risk_engine().insurance_fund.balance.get();
+    assert_eq!(engine.claim_referral_rebates(referrer), Ok(100));
+    assert_eq!(engine.risk_engine().accounts[referrer as usize].capital.get(), 100);
+    assert_eq!(engine.risk_engine().insurance_fund.balance.get(), insurance - 100);
+    assert_eq!(engine.claim_referral_rebates(referrer), Ok(0));
+    assert_eq!(engine.referrals().paid_total(), 100);
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+}
+
+#[test]
+fn test_fills_inside_the_oracle_rebate_the_lp_from_the_taker_fee() {
+    let mut engine = funded_engine();
+    let bad = MakerRebateParams { rebate_bps: 10_001, min_improvement_bps: 1 };
+    assert_eq!(engine.set_maker_rebate(Some(bad)), Err(RiskError::Overflow));
+    engine.set_maker_rebate(Some(MakerRebateParams::default())).unwrap();
+    assert_eq!(price_improvement_bps(1, 999_000, ORACLE), 10);
+    assert_eq!(price_improvement_bps(-1, 999_000, ORACLE), 0);
+
+    // At the oracle: plain taker flow, no rebate
+    engine.execute_trade(&PassthroughAgent, 1, ORACLE_PX, 1_000_000, 1).unwrap();
+    assert_eq!(engine.fee_totals().maker_rebate, 0);
+
+    // 10 bps inside the oracle: a quarter of the 999 fee goes to the LP
+    let lp_capital = engine.risk_engine().accounts[0].capital.get();
+    let agent = PricedAgent { price: 999_000 };
+    engine.execute_trade(&agent, 1, ORACLE_PX, 1_000_000, 1).unwrap();
+    let fees = *engine.fee_totals();
+    assert_eq!((fees.insurance, fees.maker_rebate), (1_000 + 750, 249));
+    assert_eq!(fees.total(), 1_000 + 999);
+    // The LP pays the 1_000 of improvement and receives the rebate
+    assert_eq!(engine.risk_engine().accounts[0].capital.get(), lp_capital - 1_000 + 249);
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+}
+
+// ==============================================================================
+// SANDBOX LEADERBOARD
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_leaderboard_ranks_pnl_volume_and_drawdown_over_a_window() {
+    use percolator::localhost::*;
+
+    let trade = |user_idx, size, slot| Command::Trade {
+        user_idx,
+        oracle_price: ORACLE,
+        size,
+        slot,
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: None,
+    };
+    let crank = |oracle_price, slot| Command::Crank { caller_idx: 0, oracle_price, slot };
+
+    let state = ServerState::new(PassthroughAgent);
+    assert!(state.leaderboard().is_none());
+    let mut state = state.with_leaderboard(10);
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap();
+    state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap();
+    // Deposits are principal, not PnL
+    let board = state.leaderboard().unwrap();
+    assert!(board.standings(0).iter().all(|s| s.pnl == 0 && s.volume == 0));
+
+    state.apply(trade(1, 200_000, 0)).unwrap();
+    state.apply(trade(2, -100_000, 0)).unwrap();
+    state.apply(crank(1_010_000, 1)).unwrap();
+    state.apply(crank(990_000, 2)).unwrap();
+
+    let board = state.leaderboard().unwrap();
+    let standings = board.standings(2);
+    let [lp, short, long] = [standings[0], standings[1], standings[2]];
+    assert_eq!([lp.idx, short.idx, long.idx], [0, 2, 1]);
+    assert!(lp.lp && !long.lp);
+    // The LP made both fills; each user one
+    assert_eq!((lp.volume, lp.trades), (300_000, 2));
+    assert_eq!((long.volume, long.trades), (200_000, 1));
+    // The long paid a 200 fee, gained 2_000 at 1.01 and gave back 4_000 by 0.99
+    assert_eq!((long.pnl, long.max_drawdown), (-2_200, 4_000));
+    assert_eq!(short.pnl, 1_000 - 100);
+    assert_eq!(board.standing(1, 2), Some(long));
+
+    // Once the window has slid past the activity, every account starts over
+    state.apply(crank(990_000, 20)).unwrap();
+    let board = state.leaderboard().unwrap();
+    for s in board.standings(20) {
+        assert_eq!((s.pnl, s.volume, s.trades, s.max_drawdown), (0, 0, 0, 0));
+    }
+}
+
+// ==============================================================================
+// MULTI-INSTANCE SANDBOX
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_instances_host_independent_engines_under_path_prefixes() {
+    use percolator::localhost::*;
+
+    let router = Router::new()
+        .read("GET", "/accounts/{idx}", |state: &ServerState<PassthroughAgent>, req| {
+            let idx = req.param("idx").and_then(|v| v.parse().ok()).unwrap_or(u16::MAX);
+            match state.engine.account_view(idx, ORACLE) {
+                Some(v) => Response::ok(format!(r#"{{"capital": {}}}"#, v.capital)),
+                None => Response::bad_request("Account not found"),
+            }
+        })
+        .write("POST", "/accounts/user", |state, req| {
+            let deposit = req.json_int("deposit").unwrap_or(0) as u128;
+            match state.apply(Command::AddUser { deposit }) {
+                Ok(idx) => Response::ok(format!(r#"{{"idx": {}}}"#, idx.unwrap())),
+                Err(e) => Response::bad_request(&format!("{:?}", e)),
+            }
+        })
+        .with_keys(
+            ApiKeys::new()
+                .with("reader", Permission::ReadOnly)
+                .with("root", Permission::Admin),
+        );
+    let instances = Instances::new(ServerState::new(PassthroughAgent)).with_factory(|spec| {
+        let mut params = default_params();
+        params.trading_fee_bps = spec.trading_fee_bps.unwrap_or(params.trading_fee_bps);
+        if spec.agent.as_deref().is_some_and(|a| a != "passthrough") {
+            return Err("unknown agent".into());
+        }
+        let engine = Box::new(ClawcolatorEngine::new(params));
+        ServerState::restore(engine, PassthroughAgent, Box::new(MemoryStore::default()))
+            .map_err(|e| format!("{:?}", e))
+    });
+    let send = |method: &str, path: &str, key: &str, body: &str| {
+        let raw = format!(
+            "{} {} HTTP/1.1\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            key,
+            body.len(),
+            body
+        );
+        router.dispatch_instances(&instances, Request::parse(&raw).unwrap())
+    };
+
+    // Creating instances takes an admin key and a fresh, well-formed name
+    let spec = r#"{"name": "team-a", "trading_fee_bps": 30}"#;
+    assert_eq!(send("POST", "/instances", "reader", spec).status, 403);
+    assert_eq!(send("POST", "/instances", "root", spec).status, 200);
+    assert_eq!(send("POST", "/instances", "root", spec).status, 400);
+    assert_eq!(send("POST", "/instances", "root", r#"{"name": "a/b"}"#).status, 400);
+    let rejected = send("POST", "/instances", "root", r#"{"name": "b", "agent": "x"}"#);
+    assert_eq!(rejected.body, r#"{"error": "unknown agent"}"#);
+    assert_eq!(send("GET", "/instances", "reader", "").body, r#"{"instances": ["team-a"]}"#);
+
+    // The same routes serve each instance; state does not leak across
+    let deposit = r#"{"deposit": 5000}"#;
+    assert_eq!(send("POST", "/i/team-a/accounts/user", "root", deposit).body, r#"{"idx": 0}"#);
+    assert_eq!(send("GET", "/i/team-a/accounts/0", "reader", "").body, r#"{"capital": 5000}"#);
+    assert_eq!(send("GET", "/accounts/0", "reader", "").status, 400);
+    assert_eq!(send("GET", "/i/team-b/accounts/0", "reader", "").status, 404);
+    assert_eq!(send("POST", "/i/team-a/accounts/user", "reader", deposit).status, 403);
+
+    let team = instances.get("team-a").unwrap();
+    assert_eq!(team.read().unwrap().engine.risk_engine().params.trading_fee_bps, 30);
+    let default = instances.default_state().read().unwrap();
+    assert_eq!(default.engine.risk_engine().params.trading_fee_bps, 10);
+}
+
+// ==============================================================================
+// SANDBOX SLOT CLOCK
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_slot_clock_runs_manual_wall_clock_and_accelerated() {
+    use percolator::localhost::*;
+    use std::time::{Duration, Instant};
+
+    let t0 = Instant::now();
+    let secs = |s: u64| t0 + Duration::from_secs(s);
+    let mut clock = SlotClock::default();
+    assert_eq!(clock.mode(), ClockMode::Manual);
+    // Manual: only advances move it, and it never runs behind the engine
+    assert_eq!(clock.slot_at(7, secs(60)), 7);
+    clock.advance_at(5, 7, t0);
+    assert_eq!(clock.slot_at(7, secs(60)), 12);
+    assert_eq!(clock.slot_at(20, secs(60)), 20);
+
+    // Wall clock: 400ms a slot, from the slot the mode was set at
+    clock.set_mode_at(ClockMode::WallClock, 7, t0);
+    assert_eq!(clock.slot_at(7, secs(2)), 12 + 5);
+
+    // Accelerated: 100 slots a second
+    let fast = ClockMode::decode("accelerated", Some(100)).unwrap();
+    assert_eq!(fast, ClockMode::Accelerated { slots_per_sec: 100 });
+    clock.set_mode_at(fast, 7, secs(2));
+    assert_eq!(clock.slot_at(7, secs(12)), 17 + 1_000);
+    assert_eq!(ClockMode::decode("accelerated", None), None);
+    assert_eq!(ClockMode::decode("accelerated", Some(0)), None);
+    assert_eq!(ClockMode::decode("sundial", None), None);
+
+    // Back to manual: the clock stops where it was
+    clock.set_mode_at(ClockMode::Manual, 7, secs(12));
+    assert_eq!(clock.slot_at(7, secs(100)), 1_017);
+
+    // The server stamps new commands with the clock's slot
+    let mut state = ServerState::new(PassthroughAgent);
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap();
+    assert_eq!(state.slot(), 0);
+    state.advance_clock(500);
+    let slot = state.slot();
+    assert_eq!(slot, 500);
+    state.apply(Command::Crank { caller_idx: 0, oracle_price: ORACLE, slot }).unwrap();
+    state.set_clock(ClockMode::Accelerated { slots_per_sec: MAX_SLOTS_PER_SEC });
+    std::thread::sleep(Duration::from_millis(5));
+    assert!(state.slot() > 500);
+    let trade = Command::Trade {
+        user_idx: 1,
+        oracle_price: ORACLE,
+        size: 1_000,
+        slot: state.slot(),
+        client_order_id: None,
+        sequence: None,
+        valid_until_slot: None,
+    };
+    state.apply(trade).unwrap();
+    assert!(state.engine.risk_engine().current_slot > 500);
+}
+
+// ==============================================================================
+// SEEDED MARKET SIMULATION
+// ==============================================================================
+
+#[test]
+fn test_seeded_simulation_is_reproducible_and_conserves() {
+    let params = SimulationParams {
+        price_model: PriceModel::Crash { at_step: 20, shock_bps: -3_000, step_bps: 50 },
+        steps: 40,
+        traders: 8,
+        max_order_size: 5_000_000,
+        seed: 7,
+        ..SimulationParams::default()
+    };
+    let run = |params: &SimulationParams| {
+        let mut engine = Box::new(ClawcolatorEngine::new(default_params()));
+        simulate(&mut engine, &PassthroughAgent, params).unwrap()
+    };
+    let report = run(&params);
+
+    assert_eq!(report, run(&params));
+    assert_ne!(report, run(&SimulationParams { seed: 8, ..params }));
+    assert_eq!(report.orders, report.fills + report.rejections);
+    assert!(report.fills > 0 && report.volume > 0 && report.fees > 0);
+    assert!(report.min_price < 750_000);
+    // The crash bankrupts traders; their holes are socialized, never unbacked
+    assert!(report.liquidations > 0 && report.socialized > 0);
+    assert!(report.solvent);
+
+    // Bad params and a used engine are refused
+    let mut engine = Box::new(ClawcolatorEngine::new(default_params()));
+    let too_many = SimulationParams { traders: MAX_SIM_TRADERS + 1, ..params };
+    assert_eq!(simulate(&mut engine, &PassthroughAgent, &too_many), Err(RiskError::Overflow));
+    let mut used = funded_engine();
+    assert_eq!(
+        simulate(&mut used, &PassthroughAgent, &params),
+        Err(RiskError::AccountKindMismatch)
+    );
+}
+
+#[test]
+fn test_replay_walks_recorded_prices() {
+    let prices = [1_000_000, 990_000, 1_020_000, 950_000, 1_000_000];
+    let params = SimulationParams { traders: 4, seed: 3, ..SimulationParams::default() };
+    let run = |prices: &[u64]| {
+        let mut engine = Box::new(ClawcolatorEngine::new(default_params()));
+        replay(&mut engine, &PassthroughAgent, &params, prices)
+    };
+    let report = run(&prices).unwrap();
+    assert_eq!(report, run(&prices).unwrap());
+    assert_eq!(report.steps, 4);
+    assert_eq!((report.final_price, report.min_price, report.max_price), (1_000_000, 950_000, 1_020_000));
+    assert!(report.fills > 0 && report.solvent);
+
+    assert_eq!(run(&[]), Err(RiskError::Overflow));
+    assert_eq!(run(&[1_000_000, 0]), Err(RiskError::Overflow));
+    assert_eq!(run(&[1_000_000, MAX_ORACLE_PRICE + 1]), Err(RiskError::Overflow));
+}
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_server_simulation_leaves_the_live_market_untouched() {
+    use percolator::localhost::*;
+
+    let mut state = ServerState::new(PassthroughAgent);
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    let params = SimulationParams { steps: 10, traders: 3, seed: 1, ..SimulationParams::default() };
+    let report = state.simulate(&params).unwrap();
+    assert_eq!(report, state.simulate(&params).unwrap());
+    assert!(report.fills > 0);
+    assert_eq!(state.engine.risk_engine().num_used_accounts, 1);
+    assert_eq!(state.journal().commands.len(), 1);
+}
+
+// ==============================================================================
+// CPI MATCHING ENGINES
+// ==============================================================================
+
+/// Stand-in for a third-party LP program reached through CPI
+struct MockLpProgram {
+    program: [u8; 32],
+    /// Program that sets the return data (the LP program unless spoofed)
+    returned_by: [u8; 32],
+    /// Answer as a function of the decoded request
+    answer: fn(&MatchRequest) -> TradeExecution,
+    seen: std::cell::Cell<Option<([u8; 32], MatchRequest)>>,
+}
+
+impl MockLpProgram {
+    fn new(answer: fn(&MatchRequest) -> TradeExecution) -> Self {
+        Self {
+            program: [7; 32],
+            returned_by: [7; 32],
+            answer,
+            seen: std::cell::Cell::new(None),
+        }
+    }
+}
+
+impl CpiInvoker for &MockLpProgram {
+    fn invoke(
+        &self,
+        program: &[u8; 32],
+        context: &[u8; 32],
+        data: &[u8],
+        return_data: &mut [u8],
+    ) -> Result<Option<([u8; 32], usize)>> {
+        assert_eq!(*program, self.program);
+        let request = MatchRequest::decode(data)?;
+        self.seen.set(Some((*context, request)));
+        let out = cpi::encode_execution(&(self.answer)(&request));
+        return_data[..out.len()].copy_from_slice(&out);
+        Ok(Some((self.returned_by, out.len())))
+    }
+}
+
+#[test]
+fn test_cpi_matcher_fills_through_the_lp_program_within_bounds() {
+    let mut engine = Box::new(ClawcolatorEngine::new(default_params()));
+    let risk = engine.risk_engine_mut();
+    let lp = risk.add_lp([7; 32], [8; 32], 0).unwrap();
+    risk.deposit(lp, 10_000_000, 0).unwrap();
+    let user = risk.add_user(0).unwrap();
+    risk.deposit(user, 1_000_000, 0).unwrap();
+    engine.set_market_mode(MarketMode::Classic).unwrap();
+    engine.crank(user, 1, ORACLE, None).unwrap();
+
+    // Half fill one tick inside the oracle
+    let program = MockLpProgram::new(|req| TradeExecution {
+        price: req.oracle_price - 1,
+        size: req.size / 2,
+    });
+    engine
+        .execute_classic_trade(&CpiMatcher::new(&program), lp, user, ORACLE_PX, 100_000, 1)
+        .unwrap();
+    let lp_id = engine.risk_engine().accounts[lp as usize].account_id;
+    assert_eq!(
+        program.seen.get(),
+        Some(([8; 32], MatchRequest { lp_account_id: lp_id, oracle_price: ORACLE, size: 100_000 }))
+    );
+    let fill = engine.last_fill().unwrap();
+    assert_eq!((fill.price, fill.size), (ORACLE - 1, 50_000));
+    assert_eq!(engine.account_view(user, ORACLE).unwrap().position_size, 50_000);
+
+    // Overfills, flips and zero prices are refused before the engine sees them
+    let answers: [fn(&MatchRequest) -> TradeExecution; 3] = [
+        |req| TradeExecution { price: req.oracle_price, size: req.size + 1 },
+        |req| TradeExecution { price: req.oracle_price, size: -req.size },
+        |req| TradeExecution { price: 0, size: req.size },
+    ];
+    for answer in answers {
+        let program = MockLpProgram::new(answer);
+        assert_eq!(
+            engine.execute_classic_trade(&CpiMatcher::new(&program), lp, user, ORACLE_PX, 100_000, 1),
+            Err(RiskError::InvalidMatchingEngine)
+        );
+    }
+
+    // Return data set by another program is not the LP's answer
+    let mut spoofed = MockLpProgram::new(|req| TradeExecution { price: req.oracle_price, size: req.size });
+    spoofed.returned_by = [9; 32];
+    assert_eq!(
+        engine.execute_classic_trade(&CpiMatcher::new(&spoofed), lp, user, ORACLE_PX, 100_000, 1),
+        Err(RiskError::InvalidMatchingEngine)
+    );
+    assert_eq!(engine.account_view(user, ORACLE).unwrap().position_size, 50_000);
+    assert!(engine.risk_engine().check_conservation(ORACLE));
+
+    // An LP without a program is never invoked
+    let matcher = CpiMatcher::new(&program);
+    assert_eq!(
+        matcher.execute_match(&[0; 32], &[0; 32], 0, ORACLE, 1),
+        Err(RiskError::InvalidMatchingEngine)
+    );
+}
+
+// ==============================================================================
+// ANCHOR LAYOUT
+// ==============================================================================
+
+#[cfg(feature = "anchor")]
+#[test]
+fn test_anchor_instructions_round_trip_and_contexts_check_accounts() {
+    use percolator::clawcolator::anchor::*;
+    use percolator::layout::LAYOUT;
+
+    let instructions = [
+        MarketInstruction::InitializeMarket,
+        MarketInstruction::AddUser { fee_payment: 5 },
+        MarketInstruction::Deposit { account_idx: 3, amount: 1_000_000 },
+        MarketInstruction::Withdraw { account_idx: 3, amount: u128::MAX },
+        MarketInstruction::ExecuteTrade { user_idx: 1, size: -250_000 },
+        MarketInstruction::Crank { caller_idx: 7 },
+    ];
+    for instruction in instructions {
+        let data = instruction.encode();
+        assert_eq!(data.as_bytes()[..8], instruction.discriminator());
+        assert_eq!(MarketInstruction::decode(data.as_bytes()), Ok(instruction));
+    }
+    // Borsh layout: u16 index then u128 amount, little-endian
+    let deposit = MarketInstruction::Deposit { account_idx: 0x0102, amount: 9 }.encode();
+    assert_eq!(deposit.as_bytes()[8..11], [0x02, 0x01, 9]);
+    let mut trailing = [0u8; 11];
+    trailing[..10].copy_from_slice(MarketInstruction::Crank { caller_idx: 1 }.encode().as_bytes());
+    assert_eq!(MarketInstruction::decode(&trailing), Err(RiskError::Overflow));
+    assert_eq!(MarketInstruction::decode(&[0; 26]), Err(RiskError::Unauthorized));
+    assert_eq!(MarketInstruction::decode(&DEPOSIT_DISCRIMINATOR), Err(RiskError::Overflow));
+
+    // Market account: discriminator, then the engine
+    assert_eq!(MarketAccount::SPACE, 8 + LAYOUT.engine_bytes);
+    let mut data = vec![0u8; MarketAccount::SPACE];
+    assert_eq!(MarketAccount::body(&data), Err(RiskError::AccountKindMismatch));
+    data[..8].copy_from_slice(&MarketAccount::DISCRIMINATOR);
+    assert_eq!(MarketAccount::body(&data).unwrap().len(), LAYOUT.engine_bytes);
+    assert_eq!(MarketAccount::body(&data[1..]), Err(RiskError::AccountKindMismatch));
+
+    // Contexts check signers and writability, and allow remaining accounts
+    let account = |key: u8, is_signer, is_writable| AccountKey { key: [key; 32], is_signer, is_writable };
+    let trade = [account(1, false, true), account(2, true, false), account(3, false, false), account(4, false, false)];
+    let ctx = TradeAccounts::from_accounts(&trade).unwrap();
+    assert_eq!((ctx.market, ctx.owner, ctx.oracle), ([1; 32], [2; 32], [3; 32]));
+    let mut unsigned = trade;
+    unsigned[1].is_signer = false;
+    assert_eq!(TradeAccounts::from_accounts(&unsigned), Err(RiskError::Unauthorized));
+    let mut read_only = trade;
+    read_only[0].is_writable = false;
+    assert_eq!(CrankAccounts::from_accounts(&read_only), Err(RiskError::Unauthorized));
+    assert_eq!(TransferAccounts::from_accounts(&trade), Err(RiskError::AccountNotFound));
+
+    let init = [account(1, false, true), account(2, true, true), account(0, false, false)];
+    assert_eq!(InitializeMarketAccounts::from_accounts(&init).unwrap().authority, [2; 32]);
+    let mut foreign = init;
+    foreign[2].key = [9; 32];
+    assert_eq!(InitializeMarketAccounts::from_accounts(&foreign), Err(RiskError::Unauthorized));
+}
+
+// ==============================================================================
+// MARKET EVENTS
+// ==============================================================================
+
+#[test]
+fn test_events_drain_trades_liquidations_and_params_as_anchor_logs() {
+    let mut engine = funded_engine();
+    engine.risk_engine_mut().top_up_insurance_fund(10_000).unwrap(); // No force-realize
+    let spread = ParamsAgent {
+        params: MarketParams { spread_bps: 25, ..MarketParams::default() },
+    };
+    engine.update_market_params(&spread).unwrap();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000_000, 1)
+        .unwrap();
+    let liq = engine.liquidation_price(1, ORACLE).unwrap();
+    let user_id = engine.risk_engine().accounts[1].account_id;
+    engine.crank(1, 2, liq / 2, None).unwrap();
+
+    let events: Vec<MarketEvent> = engine.pending_events().iter().copied().collect();
+    assert!(matches!(events[0], MarketEvent::ParamsChanged(p) if p.spread_bps == 25));
+    let MarketEvent::Trade(fill) = events[1] else { panic!("expected a trade") };
+    assert_eq!((fill.user_idx, fill.size), (1, 5_000_000));
+    assert!(events.contains(&MarketEvent::Liquidation(LiquidationEvent {
+        slot: 2,
+        idx: 1,
+        account_id: user_id,
+        size: 5_000_000,
+        remaining: 0,
+        oracle_price: liq / 2,
+    })));
+
+    // Drained as `sol_log_data` payloads: discriminator then borsh fields
+    let mut logged = Vec::new();
+    let mut sink = ProgramLogSink::new(|data: &[&[u8]]| logged.push(data.concat()));
+    assert_eq!(engine.drain_events(&mut sink), events.len());
+    assert!(engine.pending_events().is_empty());
+    assert_eq!(logged.len(), events.len());
+    let trade = &logged[1];
+    assert_eq!(trade[..8], events::TRADE_EVENT_DISCRIMINATOR);
+    assert_eq!(trade[8..16], 1u64.to_le_bytes()); // slot
+    assert_eq!(trade[16..18], 1u16.to_le_bytes()); // user_idx
+    assert_eq!(trade[28..44], 5_000_000i128.to_le_bytes()); // size
+    assert_eq!(trade.len(), 46); // vamm and a `None` client order id
+    assert_eq!(logged[0].len(), 72);
+
+    // Unchanged params emit nothing; a full ring drops the oldest
+    engine.update_market_params(&spread).unwrap();
+    assert!(engine.pending_events().is_empty());
+    let risk = engine.risk_engine_mut();
+    let user = risk.add_user(0).unwrap();
+    risk.deposit(user, 1_000_000, 3).unwrap();
+    for i in 0..EVENT_LOG_LEN + 2 {
+        let size = if i % 2 == 0 { -1_000 } else { 1_000 };
+        engine
+            .execute_trade(&PassthroughAgent, user, ORACLE_PX, size, 3)
+            .unwrap();
+    }
+    assert_eq!(engine.pending_events().len(), EVENT_LOG_LEN);
+    assert_eq!(engine.pending_events().dropped(), 2);
+}
+
+// ==============================================================================
+// INDEXER
+// ==============================================================================
+
+#[cfg(feature = "indexer")]
+#[test]
+fn test_indexer_builds_trade_oi_and_funding_tables_from_events_and_logs() {
+    use percolator::clawcolator::indexer::*;
+
+    let mut engine = funded_engine();
+    engine.risk_engine_mut().top_up_insurance_fund(10_000).unwrap(); // No force-realize
+    let funding = ParamsAgent {
+        params: MarketParams { funding_rate_bps_per_slot: 2, ..MarketParams::default() },
+    };
+    engine.update_market_params(&funding).unwrap();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, 5_000_000, 1)
+        .unwrap();
+    engine
+        .execute_trade(&PassthroughAgent, 1, ORACLE_PX, -1_000_000, 2)
+        .unwrap();
+    let liq = engine.liquidation_price(1, ORACLE).unwrap();
+    engine.crank(1, 3, liq / 2, None).unwrap();
+
+    // The same stream through the sink and through the program logs
+    let mut direct = Indexer::new();
+    let mut logs = Vec::new();
+    let mut sink = ProgramLogSink::new(|data: &[&[u8]]| logs.push(data.concat()));
+    for event in engine.pending_events().iter() {
+        direct.emit(event);
+    }
+    engine.drain_events(&mut sink);
+    let mut from_logs = Indexer::new();
+    for log in &logs {
+        from_logs.apply_log(log).unwrap();
+    }
+    assert_eq!(direct.export(), from_logs.export());
+    assert_eq!(from_logs.apply_log(&logs[0][..10]), Err(RiskError::Overflow));
+
+    let trades = direct.account_trades(1);
+    assert_eq!(trades.len(), 2);
+    assert_eq!((trades[0].size, trades[0].maker, trades[0].counterparty), (5_000_000, false, 0));
+    let lp_trades = direct.account_trades(AGENT_LP_IDX);
+    assert_eq!((lp_trades[1].size, lp_trades[1].maker), (1_000_000, true));
+    assert_eq!(direct.position(AGENT_LP_IDX), -4_000_000);
+
+    // Long OI rises with the open, falls with the reduce and the liquidation
+    let oi: Vec<(u64, u128)> = direct.open_interest_series().iter().map(|p| (p.slot, p.open_interest)).collect();
+    assert_eq!(oi, vec![(1, 5_000_000), (2, 4_000_000), (3, 0)]);
+    assert_eq!(direct.position(1), 0);
+    assert_eq!(direct.liquidations().len(), 1);
+    assert_eq!(direct.funding_history(), &[FundingPoint { slot: 0, rate_bps_per_slot: 2 }]);
+
+    // Wide amounts are exported as strings
+    let json = direct.export_json();
+    assert!(json.contains(r#""open_interest":"4000000""#), "{json}");
+    assert!(json.contains(r#""size":"-1000000","maker":false"#), "{json}");
+}
+
+// ==============================================================================
+// JOURNAL MIGRATIONS
+// ==============================================================================
+
+#[cfg(feature = "localhost")]
+#[test]
+fn test_file_store_upgrades_old_journals_through_registered_steps() {
+    use percolator::localhost::*;
+
+    // A pretend v0 format that called deposits "fund" and had no trade slot
+    fn from_v0(lines: Vec<String>) -> std::result::Result<Vec<String>, String> {
+        lines
+            .into_iter()
+            .map(|line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["fund", idx, amount] => Ok(format!("deposit {} {}", idx, amount)),
+                ["trade", user, price, size] => Ok(format!("trade {} {} {} 0", user, price, size)),
+                ["trade", ..] => Err(format!("bad trade \"{}\"", line)),
+                _ => Ok(line),
+            })
+            .collect()
+    }
+    let old = "clawcolator-journal v0\nadd_lp 10000000\nadd_user 0\nfund 1 1000000\n\ntrade 1 1000000 200000\n";
+    let migrations = Migrations::new().step(0, from_v0);
+    assert_eq!(migrations.supported(), vec![0, JOURNAL_VERSION]);
+    assert_eq!(Migrations::new().supported(), vec![JOURNAL_VERSION]);
+
+    let snapshot = migrations.upgrade(old).unwrap();
+    assert_eq!(snapshot.commands[2], Command::Deposit { idx: 1, amount: 1_000_000 });
+    assert!(snapshot.encode().starts_with(&format!("clawcolator-journal v{}\n", JOURNAL_VERSION)));
+    assert_eq!(migrations.upgrade(&snapshot.encode()).unwrap(), snapshot);
+
+    // Without the step, from a newer build, or with a failing step
+    let err = |text: &str, m: &Migrations| m.upgrade(text).unwrap_err().to_string();
+    assert_eq!(err(old, &Migrations::new()), "no migration from journal v0");
+    assert!(err("clawcolator-journal v99\n", &migrations).contains("newer than this build"));
+    assert!(err("clawcolator-journal v0\ntrade 1\n", &migrations).starts_with("migrating from v0"));
+
+    // A file-backed restore upgrades, and the next save writes the current format
+    let path = std::env::temp_dir().join(format!("claw-journal-v0-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::write(path, old).unwrap();
+    assert!(FileStore::new(path).load().is_err());
+    let store = FileStore::new(path).with_migrations(migrations);
+    let fresh = Box::new(ClawcolatorEngine::new(default_params()));
+    let mut state = ServerState::restore(fresh, PassthroughAgent, Box::new(store)).unwrap();
+    assert_eq!(state.engine.account_view(1, ORACLE).unwrap().position_size, 200_000);
+    state.apply(Command::Crank { caller_idx: 0, oracle_price: ORACLE, slot: 1 }).unwrap();
+    let saved = std::fs::read_to_string(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(Snapshot::decode(&saved).unwrap().commands.len(), 5);
+}
+
+// ==============================================================================
+// CONFORMANCE KIT
+// ==============================================================================
+
+/// Agent with knobs for each conformance property
+struct KitAgent {
+    params: MarketParams,
+    /// How far from the oracle, against the taker, fills are priced (bps)
+    markup_bps: u64,
+    /// Severity as a function of oracle divergence
+    severity: fn(u64) -> u64,
+}
+
+impl KitAgent {
+    fn sound() -> Self {
+        Self {
+            params: MarketParams {
+                max_position_size: 10_000_000,
+                ..MarketParams::default()
+            },
+            markup_bps: 10,
+            severity: |divergence_bps| divergence_bps,
+        }
+    }
+}
+
+impl OpenClawAgent for KitAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        let cap = self.params.max_position_size as i128;
+        let size = request.size.clamp(-cap, cap);
+        let markup = context.oracle_price as u128 * self.markup_bps as u128 / 10_000;
+        let price = if size > 0 {
+            context.oracle_price.saturating_add(markup as u64).min(MAX_ORACLE_PRICE)
+        } else {
+            context.oracle_price - markup as u64
+        };
+        Ok(TradeDecision::Accept { price: price.max(1), size })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(self.params)
+    }
+
+    fn detect_anomalies(&self, context: &AgentContext) -> Result<AnomalyResponse> {
+        let severity_bps = (self.severity)(context.oracle_divergence_bps);
+        Ok(AnomalyResponse {
+            anomaly_type: AnomalyType::OracleManipulation,
+            severity_bps,
+            actions: AnomalyActions {
+                freeze_market: severity_bps >= 5_000,
+                ..AnomalyActions::default()
+            },
+        })
+    }
+
+    fn should_shutdown(&self, _context: &AgentContext) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+#[test]
+fn test_conformance_kit_passes_sound_agent() {
+    let engine = funded_engine();
+    let report = certify(&engine, &KitAgent::sound());
+    assert!(report.passed(), "{:?}", report.first_violation());
+    assert_eq!(report.first_violation(), None);
+    for check in ConformanceCheck::ALL {
+        assert!(report.result(check).cases > 0, "{:?} ran no probes", check);
+    }
+    // Probing leaves the engine as it was
+    assert_eq!(engine.market_params(), &MarketParams::default());
+}
+
+#[test]
+fn test_conformance_kit_catches_oversized_fills() {
+    // Fills every request in full, whatever its size
+    let report = certify(&funded_engine(), &PassthroughAgent);
+    assert!(!report.passed());
+    let oversized = report.result(ConformanceCheck::OversizedTrades).first_violation.unwrap();
+    assert_eq!(oversized.scenario, "calm");
+    assert_eq!(oversized.input, MAX_POSITION_ABS as i128 + 1);
+    assert_eq!(oversized.kind, ViolationKind::Oversized);
+    let bounds = report.result(ConformanceCheck::DecisionBounds).first_violation.unwrap();
+    assert_eq!(bounds.kind, ViolationKind::InvalidFill);
+    assert!(report.result(ConformanceCheck::ParamsConsistency).passed());
+    assert!(report.result(ConformanceCheck::AnomalyMonotonicity).passed());
+    assert_eq!(report.first_violation().unwrap().0, ConformanceCheck::DecisionBounds);
+}
+
+#[test]
+fn test_conformance_kit_catches_each_property() {
+    let engine = funded_engine();
+    let first = |agent: &KitAgent, check| certify(&engine, agent).result(check).first_violation.map(|v| v.kind);
+
+    // Priced beyond the published spread
+    let greedy = KitAgent { markup_bps: 50, ..KitAgent::sound() };
+    assert_eq!(first(&greedy, ConformanceCheck::DecisionBounds), Some(ViolationKind::PriceBeyondSpread));
+
+    // 20x leverage cap with a 10% minimum margin
+    let mut loose = KitAgent::sound();
+    loose.params.max_leverage_bps = 2_000;
+    loose.params.min_margin_bps = 1_000;
+    assert_eq!(first(&loose, ConformanceCheck::ParamsConsistency), Some(ViolationKind::LeverageExceedsMargin));
+    loose.params.max_leverage_bps = 1_000;
+    loose.params.tier_limits[AccountTier::Pro.index()].max_leverage_bps = 2_000;
+    assert_eq!(first(&loose, ConformanceCheck::ParamsConsistency), Some(ViolationKind::LeverageExceedsMargin));
+
+    // Beyond the engine's bounds
+    let mut reckless = KitAgent::sound();
+    reckless.params.min_margin_bps = 100;
+    assert_eq!(
+        first(&reckless, ConformanceCheck::ParamsConsistency),
+        Some(ViolationKind::ParamsRejected(RiskError::Undercollateralized))
+    );
+
+    // Calmer the worse the divergence, and unfreezes again
+    let inverted = KitAgent { severity: |bps| 10_000 - bps, ..KitAgent::sound() };
+    let violation = certify(&engine, &inverted)
+        .result(ConformanceCheck::AnomalyMonotonicity)
+        .first_violation
+        .unwrap();
+    assert_eq!((violation.scenario, violation.input), ("oracle_divergence", 100));
+    assert_eq!(violation.kind, ViolationKind::SeverityDecreased);
+    let unclamped = KitAgent { severity: |bps| bps * 2, ..KitAgent::sound() };
+    assert_eq!(
+        first(&unclamped, ConformanceCheck::AnomalyMonotonicity),
+        Some(ViolationKind::SeverityOutOfRange)
+    );
+}
+
+// ==============================================================================
+// RL ENVIRONMENT
+// ==============================================================================
+
+#[cfg(feature = "rl")]
+#[test]
+fn test_rl_env_episodes_replay_and_reward_lp_value() {
+    use percolator::clawcolator::rl::*;
+
+    let params = SimulationParams { steps: 50, traders: 4, seed: 9, ..SimulationParams::default() };
+    // Fill every order in full, 5 bps against the taker
+    let quote = |obs: &Observation| {
+        let size = obs.request.map_or(0, |r| r.size);
+        let spread = obs.context.oracle_price / 2_000;
+        let price = if size > 0 { obs.context.oracle_price + spread } else { obs.context.oracle_price - spread };
+        Action { decision: TradeDecision::Accept { price, size }, params: None }
+    };
+    let episode = |env: &mut RlEnv, seed: u64, policy: &dyn Fn(&Observation) -> Action| {
+        let mut obs = env.reset(seed).unwrap();
+        let (mut rewards, mut infos) = (Vec::new(), Vec::new());
+        loop {
+            let step = env.step(&policy(&obs)).unwrap();
+            rewards.push(step.reward);
+            infos.push(step.info);
+            obs = step.observation;
+            if step.done {
+                return (rewards, infos, obs);
+            }
+        }
+    };
+
+    let mut env = RlEnv::new(default_params(), params).unwrap();
+    let (rewards, infos, last) = episode(&mut env, 9, &quote);
+    assert_eq!(rewards.len(), 50);
+    assert!(infos.iter().filter(|i| i.filled).count() > 10);
+    assert!(infos.iter().all(|i| i.trade_error.is_none() && i.params_error.is_none()));
+    // Rewards add up to the LP's change in value
+    let total: f64 = rewards.iter().sum();
+    assert_eq!(total, (last.lp_value - env.lp_start()) as f64);
+    assert!(last.lp_position != 0);
+    assert!(env.step(&quote(&last)).is_err());
+
+    // Same seed, same actions, same episode; another seed differs
+    let again = episode(&mut env, 9, &quote);
+    assert_eq!((rewards.clone(), infos), (again.0, again.1));
+    assert_ne!(episode(&mut env, 10, &quote).0, rewards);
+
+    // Refusing everything leaves the LP flat
+    let (rewards, infos, last) = episode(&mut env, 9, &|_| Action::reject());
+    assert!(rewards.iter().all(|&r| r == 0.0) && last.lp_position == 0);
+    assert!(infos.iter().all(|i| !i.filled));
+
+    // Invalid answers and params are refused by the engine, not the env
+    let mut obs = env.reset(9).unwrap();
+    while obs.request.is_none() {
+        obs = env.step(&Action::reject()).unwrap().observation;
+    }
+    let size = obs.request.unwrap().size;
+    let bad = Action {
+        decision: TradeDecision::Accept { price: 0, size },
+        params: Some(MarketParams { min_margin_bps: 100, ..MarketParams::default() }),
+    };
+    let step = env.step(&bad).unwrap();
+    assert!(!step.info.filled && step.info.trade_error.is_some());
+    assert_eq!(step.info.params_error, Some(RiskError::Undercollateralized));
+
+    // Observations serialize the context and the pending order
+    let features = obs.features(env.lp_start());
+    assert_eq!(features.len(), OBSERVATION_LEN);
+    assert_eq!((features[0], features[OBSERVATION_LEN - 1]), (obs.context.oracle_price as f64 / 1e6, 1.0));
+    let json: serde_json::Value = serde_json::from_str(&obs.to_json()).unwrap();
+    assert_eq!(json["oracle_price"], obs.context.oracle_price);
+    assert_eq!(json["vault"], obs.context.vault.to_string());
+    assert_eq!(json["request"]["size"], size.to_string());
+    assert!(RlEnv::new(default_params(), SimulationParams { traders: MAX_SIM_TRADERS + 1, ..params }).is_err());
+}
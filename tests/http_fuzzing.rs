@@ -0,0 +1,504 @@
+//! Deterministic fuzzing of the localhost HTTP layer
+//!
+//! ## Running Tests
+//! - Quick: `cargo test --features localhost,fuzz --test http_fuzzing`
+//!   (200 deterministic seeds, 256 proptest cases)
+//! - Deep: `PROPTEST_CASES=5000 cargo test --features localhost,fuzz --test http_fuzzing`
+//!
+//! Requests are generated from a vocabulary of real routes, field names
+//! and hostile fragments (quotes, backslashes, control characters,
+//! multi-byte UTF-8, out-of-range numbers) and pushed through
+//! `Request::parse` and `Router::dispatch_instances` the way a worker
+//! thread would. Whatever comes in, the server must not panic and must
+//! answer with a known status and a body that parses as a JSON object.
+//! Journal lines get the same treatment: `Command::decode` must not panic
+//! and whatever it accepts must survive an encode/decode round trip, and
+//! every journal the fuzzed requests produce must replay to the same
+//! engine state.
+
+#![cfg(all(feature = "fuzz", feature = "localhost"))]
+
+use percolator::clawcolator::*;
+use percolator::localhost::*;
+use percolator::*;
+use proptest::prelude::*;
+
+const ORACLE: u64 = 1_000_000;
+
+/// Agent that fills every request in full at the oracle price
+struct PassthroughAgent;
+
+impl OpenClawAgent for PassthroughAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        Ok(TradeDecision::Accept {
+            price: context.oracle_price,
+            size: request.size,
+        })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams::default())
+    }
+
+    fn decide_liquidity_allocation(&self, context: &AgentContext) -> Result<LiquidityAllocation> {
+        Ok(LiquidityAllocation {
+            target_active_capital: context.total_capital,
+            reserve_capital: 0,
+            defensive_mode: false,
+        })
+    }
+
+    fn assess_risk(&self, _context: &AgentContext) -> Result<RiskAssessment> {
+        Ok(RiskAssessment {
+            risk_level_bps: 0,
+            actions: RiskActions::default(),
+        })
+    }
+
+    fn detect_anomalies(&self, _context: &AgentContext) -> Result<AnomalyResponse> {
+        Ok(AnomalyResponse {
+            anomaly_type: AnomalyType::Other,
+            severity_bps: 0,
+            actions: AnomalyActions::default(),
+        })
+    }
+
+    fn should_shutdown(&self, _context: &AgentContext) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+// ============================================================================
+// SERVER UNDER TEST
+// ============================================================================
+
+fn applied(result: std::result::Result<Option<u16>, ServerError>) -> Response {
+    match result {
+        Ok(Some(idx)) => Response::ok(format!(r#"{{"ok": true, "idx": {}}}"#, idx)),
+        Ok(None) => Response::ok(r#"{"ok": true}"#),
+        Err(e) => Response::bad_request(&format!("{:?}", e)),
+    }
+}
+
+/// Integer body field that must fit `T`
+fn field<T: TryFrom<i128>>(req: &Request, key: &str) -> Option<T> {
+    req.json_int(key).and_then(|v| T::try_from(v).ok())
+}
+
+/// Routes in the style of the example server; errors echo what the client
+/// sent, as real handlers do
+fn router(keys: Option<ApiKeys>) -> Router<PassthroughAgent> {
+    let router = Router::new()
+        .read("GET", "/health", |_: &ServerState<PassthroughAgent>, _| {
+            Response::ok(r#"{"status": "ok"}"#)
+        })
+        .read("GET", "/accounts/{idx}", |state, req| {
+            let raw = req.param("idx").unwrap_or("");
+            let Some(view) = raw.parse().ok().and_then(|idx| state.engine.account_view(idx, ORACLE))
+            else {
+                return Response::bad_request(&format!("no account {}", raw));
+            };
+            Response::ok(format!(
+                r#"{{"capital": {}, "position_size": {}}}"#,
+                view.capital, view.position_size
+            ))
+        })
+        .read("GET", "/trades", |_, req| {
+            for key in ["from", "to"] {
+                if let Some(v) = req.query_param(key) {
+                    if v.parse::<u64>().is_err() {
+                        return Response::bad_request(&format!("{}: not a slot: {}", key, v));
+                    }
+                }
+            }
+            Response::ok(r#"{"trades": []}"#)
+        })
+        .write("POST", "/accounts/user", |state, req| {
+            let deposit = field(req, "deposit").unwrap_or(0);
+            applied(state.apply(Command::AddUser { deposit }))
+        })
+        .write("POST", "/deposit", |state, req| {
+            let (Some(idx), Some(amount)) = (field(req, "user_idx"), field(req, "amount")) else {
+                return Response::bad_request("user_idx and amount required");
+            };
+            applied(state.apply(Command::Deposit { idx, amount }))
+        })
+        .scope(Scope::Trade)
+        .write("POST", "/execute", |state, req| {
+            let (Some(user_idx), Some(size)) = (field(req, "user_idx"), req.json_int("size")) else {
+                return Response::bad_request("user_idx and size required");
+            };
+            applied(state.apply(Command::Trade {
+                user_idx,
+                oracle_price: field(req, "oracle_price").unwrap_or(ORACLE),
+                size,
+                slot: field(req, "slot").unwrap_or(0),
+                client_order_id: field(req, "client_order_id"),
+                sequence: None,
+                valid_until_slot: None,
+            }))
+        })
+        .scope(Scope::Trade)
+        .write("POST", "/crank", |state, req| {
+            applied(state.apply(Command::Crank {
+                caller_idx: field(req, "caller_idx").unwrap_or(0),
+                oracle_price: field(req, "oracle_price").unwrap_or(ORACLE),
+                slot: field(req, "slot").unwrap_or(0),
+            }))
+        })
+        .write("POST", "/agent/swap", |state, req| match req.json_str("name") {
+            Some(name) => applied(state.apply(Command::SwapAgent { name: name.into() })),
+            None => Response::bad_request("name required"),
+        });
+    match keys {
+        Some(keys) => router.with_keys(keys),
+        None => router,
+    }
+    .with_openapi("Fuzz API", "1.0.0")
+}
+
+fn keys() -> ApiKeys {
+    ApiKeys::new()
+        .with("reader", Permission::ReadOnly)
+        .with("t1", Permission::Trade { user_idx: 1 })
+        .with("root", Permission::Admin)
+}
+
+fn seeded_state() -> ServerState<PassthroughAgent> {
+    let mut state = ServerState::new(PassthroughAgent);
+    state.apply(Command::AddLp { deposit: 10_000_000 }).unwrap();
+    state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap();
+    state.apply(Command::AddUser { deposit: 1_000_000 }).unwrap();
+    state
+}
+
+fn instances() -> Instances<PassthroughAgent> {
+    Instances::new(seeded_state()).with_factory(|spec| match spec.agent.as_deref() {
+        None | Some("passthrough") => Ok(seeded_state()),
+        // Rejections quote the client's input back
+        Some(other) => Err(format!("unknown agent \"{}\"", other)),
+    })
+}
+
+/// What a worker thread does with the bytes it read
+fn serve_raw(router: &Router<PassthroughAgent>, instances: &Instances<PassthroughAgent>, raw: &str) -> Response {
+    match Request::parse(raw) {
+        Some(request) => router.dispatch_instances(instances, request),
+        None => Response::bad_request("Invalid request"),
+    }
+}
+
+/// A known status and a JSON object body
+fn assert_well_formed(response: &Response, raw: &str) {
+    assert!(
+        [200, 400, 401, 403, 404].contains(&response.status),
+        "status {} for {:?}",
+        response.status,
+        raw
+    );
+    match serde_json::from_str::<serde_json::Value>(&response.body) {
+        Ok(body) => assert!(body.is_object(), "non-object body {:?} for {:?}", response.body, raw),
+        Err(e) => panic!("malformed JSON {:?} ({}) for {:?}", response.body, e, raw),
+    }
+}
+
+/// The journal replays to the state it was recorded from
+fn assert_replays(state: &ServerState<PassthroughAgent>) {
+    let text = state.journal().encode();
+    let store = MemoryStore {
+        snapshot: Some(Snapshot::decode(&text).expect("journal parses")),
+    };
+    let engine = Box::new(ClawcolatorEngine::new(state.engine.risk_engine().params));
+    let replayed = ServerState::restore(engine, PassthroughAgent, Box::new(store)).expect("journal replays");
+    let (a, b) = (state.engine.risk_engine(), replayed.engine.risk_engine());
+    assert_eq!(a.vault, b.vault);
+    assert_eq!(a.insurance_fund.balance, b.insurance_fund.balance);
+    assert_eq!(&a.accounts[..], &b.accounts[..]);
+    assert!(a.check_conservation(ORACLE));
+}
+
+// ============================================================================
+// REQUEST GENERATOR
+// ============================================================================
+
+/// xorshift64 PRNG for deterministic randomness
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng {
+            state: if seed == 0 { 1 } else { seed },
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+const HOSTILE: &[&str] = &[
+    "\"", "\\", "\\\"", "{", "}", "[", "]", ":", ",", "/", "?", "&", "=", "%22", "%", " ", "\t",
+    "\r", "\n", "\r\n", "\r\n\r\n", "\u{0}", "\u{7f}", "é", "€", "💥", "\u{feff}", "{idx}", "..",
+];
+
+const SEGMENTS: &[&str] = &[
+    "", "health", "accounts", "user", "1", "2", "0", "65535", "65536", "-1", "trades", "deposit",
+    "execute", "crank", "agent", "swap", "i", "team-a", "team_b", "instances", "openapi.json",
+];
+
+const FIELDS: &[&str] = &[
+    "user_idx", "size", "amount", "deposit", "oracle_price", "slot", "caller_idx", "client_order_id",
+    "name", "agent", "trading_fee_bps", "initial_margin_bps",
+];
+
+const NUMBERS: &[&str] = &[
+    "0", "1", "2", "-1", "1000", "-5000", "1000000", "99999999999", "18446744073709551616",
+    "-170141183460469231731687303715884105728", "340282366920938463463374607431768211456", "1e6",
+    "0x10", "+7", "1.5", "",
+];
+
+fn junk(rng: &mut Rng) -> String {
+    let mut out = String::new();
+    for _ in 0..rng.below(6) {
+        if rng.chance(50) {
+            out.push_str(rng.pick(HOSTILE));
+        } else {
+            out.push_str(rng.pick(SEGMENTS));
+        }
+    }
+    out
+}
+
+const ROUTES: &[&str] = &[
+    "/health", "/accounts/1", "/accounts/user", "/deposit", "/execute", "/crank", "/agent/swap",
+    "/trades", "/instances", "/openapi.json", "/i/team-a/execute", "/i/team-a/accounts/2",
+];
+
+fn path(rng: &mut Rng) -> String {
+    if rng.chance(50) {
+        return rng.pick(ROUTES).to_string();
+    }
+    let mut path = String::new();
+    if rng.chance(20) {
+        path.push_str("/i/");
+        path.push_str(rng.pick(&["team-a", "team_b", "nope", "\"q\""]));
+    }
+    for _ in 0..1 + rng.below(3) {
+        path.push('/');
+        if rng.chance(85) {
+            path.push_str(rng.pick(SEGMENTS));
+        } else {
+            path.push_str(&junk(rng));
+        }
+    }
+    if rng.chance(30) {
+        path.push('?');
+        for _ in 0..1 + rng.below(3) {
+            let key = rng.pick(&["from", "to", "x", ""]);
+            let value = if rng.chance(70) { rng.pick(NUMBERS).to_string() } else { junk(rng) };
+            path.push_str(&format!("{}={}&", key, value));
+        }
+    }
+    path
+}
+
+fn body(rng: &mut Rng) -> String {
+    if rng.chance(15) {
+        return junk(rng);
+    }
+    if rng.chance(40) {
+        // Well-typed: what a correct client would send
+        return format!(
+            r#"{{"user_idx": {}, "size": {}, "amount": {}, "name": "{}", "slot": {}}}"#,
+            rng.pick(&["0", "1", "2", "3"]),
+            rng.pick(&["1000", "-1000", "250000", "-4000000", "0"]),
+            rng.pick(&["1", "50000"]),
+            rng.pick(&["team-a", "team_b", "passthrough"]),
+            rng.below(50),
+        );
+    }
+    let mut fields = Vec::new();
+    for _ in 0..rng.below(5) {
+        let key = if rng.chance(90) { rng.pick(FIELDS).to_string() } else { junk(rng) };
+        let value = match rng.below(4) {
+            0 => format!("\"{}\"", junk(rng)),
+            1 => format!("\"{}\"", rng.pick(&["passthrough", "team-a", "x", ""])),
+            _ => rng.pick(NUMBERS).to_string(),
+        };
+        let gap = rng.pick(&["", " ", "\n"]);
+        fields.push(format!("\"{}\":{}{}", key, gap, value));
+    }
+    let mut body = format!("{{{}}}", fields.join(", "));
+    if rng.chance(10) {
+        let mut end = rng.below(body.len() + 1);
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+    }
+    body
+}
+
+/// One raw request as a client might send it
+fn request(rng: &mut Rng) -> String {
+    let method = if rng.chance(90) {
+        rng.pick(&["GET", "POST"]).to_string()
+    } else {
+        rng.pick(&["PUT", "get", "", " ", "POST POST", "\u{0}"]).to_string()
+    };
+    let target = if rng.chance(95) { path(rng) } else { junk(rng) };
+    let mut raw = format!("{} {} HTTP/1.1\r\n", method, target);
+    if rng.chance(80) {
+        let token = if rng.chance(85) { rng.pick(&["reader", "t1", "root"]).to_string() } else { junk(rng) };
+        raw.push_str(&format!("Authorization: Bearer {}\r\n", token));
+    }
+    for _ in 0..rng.below(3) {
+        raw.push_str(&format!("{}: {}\r\n", junk(rng), junk(rng)));
+    }
+    let body = body(rng);
+    raw.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    raw.push_str(rng.pick(&["\r\n", "\r\n", "\n", ""]));
+    raw.push_str(&body);
+    raw
+}
+
+// ============================================================================
+// DETERMINISTIC SEEDED FUZZER
+// ============================================================================
+
+fn fuzz_requests(seeds: std::ops::Range<u64>, requests_per_seed: usize) {
+    let open = router(None);
+    let keyed = router(Some(keys()));
+    let (mut statuses, mut trades, mut named) = (Vec::new(), 0, 0);
+    for seed in seeds {
+        let mut rng = Rng::new(seed);
+        let instances = instances();
+        for _ in 0..requests_per_seed {
+            let raw = request(&mut rng);
+            let router = if rng.chance(50) { &open } else { &keyed };
+            let response = serve_raw(router, &instances, &raw);
+            assert_well_formed(&response, &raw);
+            if !statuses.contains(&response.status) {
+                statuses.push(response.status);
+            }
+        }
+        let state = instances.default_state().read().unwrap();
+        assert_replays(&state);
+        trades += state.journal().commands.iter().filter(|c| matches!(c, Command::Trade { .. })).count();
+        for name in instances.names() {
+            assert_replays(&instances.get(&name).unwrap().read().unwrap());
+            named += 1;
+        }
+    }
+
+    // The generator reaches every outcome, not just the error paths
+    statuses.sort_unstable();
+    assert_eq!(statuses, [200, 400, 401, 403, 404]);
+    assert!(trades > 0 && named > 0, "{} trades, {} instances", trades, named);
+}
+
+#[test]
+fn fuzz_http_requests_deterministic() {
+    fuzz_requests(1..201, 50);
+}
+
+#[test]
+fn test_hostile_inputs_echoed_into_errors_stay_json() {
+    let router = router(None);
+    let instances = instances();
+    for raw in [
+        "GET /accounts/\"}{ HTTP/1.1\r\n\r\n",
+        "GET /trades?from=\\\" HTTP/1.1\r\n\r\n",
+        "GET /nope\"\\\u{1} HTTP/1.1\r\n\r\n",
+        "DELETE /instances HTTP/1.1\r\n\r\n",
+        "POST /instances HTTP/1.1\r\n\r\n{\"name\": \"ok\", \"agent\": \"evil\\\"}",
+        "POST /instances HTTP/1.1\r\n\r\n{\"name\": \"bad name\"}",
+        "GET /i/\"x\"/health HTTP/1.1\r\n\r\n",
+        "",
+        "\r\n\r\n",
+    ] {
+        let response = serve_raw(&router, &instances, raw);
+        assert_well_formed(&response, raw);
+        assert_ne!(response.status, 200, "{:?}", raw);
+    }
+
+    // The echoed text survives as the error string
+    let response = serve_raw(&router, &instances, "GET /accounts/a\"b HTTP/1.1\r\n\r\n");
+    let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+    assert_eq!(body["error"], "no account a\"b");
+}
+
+// ============================================================================
+// PROPERTY TESTS
+// ============================================================================
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    /// Arbitrary text, as read off the socket
+    #[test]
+    fn proptest_arbitrary_text_gets_json(raw in "\\PC{0,300}") {
+        let router = router(Some(keys()));
+        assert_well_formed(&serve_raw(&router, &instances(), &raw), &raw);
+    }
+
+    /// Arbitrary bytes, decoded lossily like `read_request`
+    #[test]
+    fn proptest_arbitrary_bytes_get_json(bytes in prop::collection::vec(any::<u8>(), 0..400)) {
+        let raw = String::from_utf8_lossy(&bytes);
+        assert_well_formed(&serve_raw(&router(None), &instances(), &raw), &raw);
+    }
+
+    /// Structured sequences from a proptest-chosen seed, shrinkable by seed
+    #[test]
+    fn proptest_request_sequences(seed in any::<u64>(), len in 1usize..40) {
+        let router = router(Some(keys()));
+        let instances = instances();
+        let mut rng = Rng::new(seed);
+        for _ in 0..len {
+            let raw = request(&mut rng);
+            assert_well_formed(&serve_raw(&router, &instances, &raw), &raw);
+        }
+        assert_replays(&instances.default_state().read().unwrap());
+    }
+
+    /// Journal lines: never panic, and what parses round-trips
+    #[test]
+    fn proptest_journal_lines_round_trip(
+        line in prop_oneof![
+            "\\PC{0,80}",
+            (
+                prop::sample::select(vec![
+                    "add_user", "add_lp", "deposit", "trade", "commit_trade", "reveal_trade",
+                    "batch_order", "clear_batch", "heartbeat", "crank", "update_market_params",
+                    "set_frozen", "swap_agent", "inject",
+                ]),
+                prop::collection::vec("-?[0-9]{1,40}|true|false|[a-f0-9]{64}|[+-]?[0-9a-fA-F]{64}|(id|seq|until)=[0-9]{1,20}|oracle_jump|clear|\\PC{1,6}", 0..8),
+            )
+                .prop_map(|(name, args)| format!("{} {}", name, args.join(" "))),
+        ]
+    ) {
+        if let Some(command) = Command::decode(&line) {
+            prop_assert_eq!(Command::decode(&command.encode()), Some(command), "{:?}", line);
+        }
+    }
+}
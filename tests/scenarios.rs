@@ -0,0 +1,332 @@
+//! Golden-file scenario regression tests
+//!
+//! Runs the canonical market scenarios (bull run, crash, oracle glitch,
+//! mass liquidation) against a fixed agent and compares a SHA-256 of the
+//! final engine state with the value committed in `tests/scenarios.golden`.
+//! Any change to fills, fees, funding, liquidations or escalation shows up
+//! as a hash mismatch naming the scenario.
+//!
+//! After an intentional behaviour change, regenerate the golden file and
+//! commit it with the change:
+//!
+//!   REGENERATE_GOLDEN=1 cargo test --features test,clawcolator --test scenarios
+//!
+//! Run with: cargo test --features test,clawcolator --test scenarios
+
+#![cfg(all(feature = "clawcolator", feature = "test"))]
+
+use percolator::clawcolator::commit::sha256;
+use percolator::clawcolator::*;
+use percolator::*;
+use std::fmt::Write as _;
+
+const GOLDEN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/scenarios.golden");
+const REGENERATE_VAR: &str = "REGENERATE_GOLDEN";
+const START_PRICE: u64 = 1_000_000;
+
+fn params() -> RiskParams {
+    RiskParams {
+        warmup_period_slots: 100,
+        maintenance_margin_bps: 500,
+        initial_margin_bps: 1000,
+        trading_fee_bps: 10,
+        max_accounts: 1000,
+        new_account_fee: U128::new(0),
+        risk_reduction_threshold: U128::new(0),
+        maintenance_fee_per_slot: U128::new(0),
+        max_crank_staleness_slots: u64::MAX,
+        liquidation_fee_bps: 50,
+        liquidation_fee_cap: U128::new(100_000),
+        liquidation_buffer_bps: 100,
+        min_liquidation_abs: U128::new(100_000),
+    }
+}
+
+/// Quotes the oracle plus its spread, sized in full, and escalates with
+/// the oracle divergence the protocol reports
+struct GoldenAgent;
+
+const GOLDEN_SPREAD_BPS: u64 = 20;
+
+impl OpenClawAgent for GoldenAgent {
+    fn decide_trade(&self, context: &AgentContext, request: &TradeRequest) -> Result<TradeDecision> {
+        let spread = context.oracle_price * GOLDEN_SPREAD_BPS / 10_000;
+        let price = if request.size > 0 {
+            context.oracle_price + spread
+        } else {
+            context.oracle_price - spread
+        };
+        Ok(TradeDecision::Accept { price, size: request.size })
+    }
+
+    fn get_market_params(&self, _context: &AgentContext) -> Result<MarketParams> {
+        Ok(MarketParams {
+            spread_bps: GOLDEN_SPREAD_BPS,
+            funding_rate_bps_per_slot: 1,
+            ..MarketParams::default()
+        })
+    }
+
+    fn detect_anomalies(&self, context: &AgentContext) -> Result<AnomalyResponse> {
+        let severity_bps = context.oracle_divergence_bps.min(10_000);
+        Ok(AnomalyResponse {
+            anomaly_type: AnomalyType::OracleManipulation,
+            severity_bps,
+            actions: AnomalyActions {
+                freeze_market: severity_bps >= 5_000,
+                ..AnomalyActions::default()
+            },
+        })
+    }
+
+    fn should_shutdown(&self, _context: &AgentContext) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+// ==============================================================================
+// STATE HASH
+// ==============================================================================
+
+/// SHA-256 over the engine's balances, every used account and the agent
+/// layer's published params and escalation state
+fn state_hash(engine: &ClawcolatorEngine) -> [u8; 32] {
+    let risk = engine.risk_engine();
+    let mut bytes = Vec::new();
+    for value in [
+        risk.vault.get(),
+        risk.insurance_fund.balance.get(),
+        risk.insurance_fund.fee_revenue.get(),
+        risk.c_tot.get(),
+        risk.pnl_pos_tot.get(),
+        risk.total_open_interest.get(),
+        risk.long_open_interest.get(),
+        risk.short_open_interest.get(),
+    ] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&risk.funding_index_qpb_e6.get().to_le_bytes());
+    for value in [risk.current_slot, risk.lifetime_liquidations, risk.lifetime_force_realize_closes] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    for idx in 0..MAX_ACCOUNTS {
+        if !risk.is_used(idx) {
+            continue;
+        }
+        let account = &risk.accounts[idx];
+        bytes.extend_from_slice(&(idx as u16).to_le_bytes());
+        bytes.extend_from_slice(&account.capital.get().to_le_bytes());
+        for value in [account.pnl.get(), account.position_size.get(), account.funding_index.get()] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [account.reserved_pnl, account.entry_price, account.warmup_started_at_slot] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    let agent_state = format!(
+        "{:?}|{}|{}|{:?}|{:?}",
+        engine.market_params(),
+        engine.withdrawals_paused(),
+        engine.risk_reduction_mode(),
+        engine.freeze_origin(),
+        engine.anomaly_history().latest(),
+    );
+    bytes.extend_from_slice(agent_state.as_bytes());
+    sha256(&bytes)
+}
+
+fn hex(hash: &[u8; 32]) -> String {
+    hash.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{:02x}", byte);
+        out
+    })
+}
+
+// ==============================================================================
+// SCENARIOS
+// ==============================================================================
+
+/// Fresh engine with the agent's params published
+fn engine() -> Box<ClawcolatorEngine> {
+    let mut engine = Box::new(ClawcolatorEngine::new(params()));
+    engine.update_market_params(&GoldenAgent).unwrap();
+    engine
+}
+
+/// Seeded simulation along `price_model`
+fn simulated(price_model: PriceModel, seed: u64) -> (Box<ClawcolatorEngine>, SimulationReport) {
+    let mut engine = engine();
+    let sim = SimulationParams {
+        price_model,
+        steps: 300,
+        traders: 16,
+        max_order_size: 3_000_000,
+        seed,
+        ..SimulationParams::default()
+    };
+    let report = simulate(&mut engine, &GoldenAgent, &sim).unwrap();
+    (engine, report)
+}
+
+/// LP with 100M and `users` traders with 1M each
+fn funded(users: u16) -> Box<ClawcolatorEngine> {
+    let mut engine = engine();
+    let risk = engine.risk_engine_mut();
+    let lp = risk.add_lp([0; 32], [0; 32], 0).unwrap();
+    risk.deposit(lp, 100_000_000, 0).unwrap();
+    for _ in 0..users {
+        let user = risk.add_user(0).unwrap();
+        risk.deposit(user, 1_000_000, 0).unwrap();
+    }
+    engine
+}
+
+fn oracle(price: u64, slot: u64) -> OraclePrice {
+    OraclePrice::new(price, 0, slot)
+}
+
+fn bull_run() -> Box<ClawcolatorEngine> {
+    let (engine, report) = simulated(PriceModel::Trend { drift_bps: 40, step_bps: 80 }, 1);
+    assert!(report.final_price > START_PRICE * 2, "{:?}", report);
+    assert!(report.fills > 0);
+    engine
+}
+
+fn crash() -> Box<ClawcolatorEngine> {
+    let model = PriceModel::Crash { at_step: 150, shock_bps: -4_000, step_bps: 80 };
+    let (engine, report) = simulated(model, 2);
+    assert!(report.min_price < START_PRICE * 2 / 3, "{:?}", report);
+    assert!(report.liquidations > 0, "{:?}", report);
+    engine
+}
+
+/// The primary feed prints double the price for one crank while the
+/// secondary holds; trading reduces to closing, the agent freezes the
+/// market, and both unwind once the feeds agree again
+fn oracle_glitch() -> Box<ClawcolatorEngine> {
+    let mut engine = funded(4);
+    for (user, size) in [(1, 3_000_000), (2, -2_000_000), (3, 1_000_000), (4, -500_000)] {
+        engine.execute_trade(&GoldenAgent, user, oracle(START_PRICE, 1), size, 1).unwrap();
+    }
+    engine.crank(0, 2, START_PRICE, Some(START_PRICE)).unwrap();
+
+    let _ = engine.crank(0, 3, START_PRICE * 2, Some(START_PRICE));
+    assert!(engine.risk_reduction_mode());
+    let outcome = engine.check_anomalies(&GoldenAgent, oracle(START_PRICE * 2, 3)).unwrap();
+    assert_eq!(outcome.level, EscalationLevel::Frozen);
+    assert!(engine.execute_trade(&GoldenAgent, 1, oracle(START_PRICE, 3), 500_000, 3).is_err());
+
+    for slot in 4..14 {
+        engine.crank(0, slot, START_PRICE, Some(START_PRICE)).unwrap();
+        engine.check_anomalies(&GoldenAgent, oracle(START_PRICE, slot)).unwrap();
+    }
+    assert!(!engine.risk_reduction_mode() && !engine.withdrawals_paused());
+    engine.execute_trade(&GoldenAgent, 1, oracle(START_PRICE, 14), -1_000_000, 14).unwrap();
+    engine.crank(0, 15, START_PRICE, Some(START_PRICE)).unwrap();
+    engine
+}
+
+/// Thirty traders near the leverage limit, all long, then a 12% drop
+fn mass_liquidation() -> Box<ClawcolatorEngine> {
+    let mut engine = funded(30);
+    for user in 1..=30u16 {
+        let size = 8_000_000 + user as i128 * 50_000;
+        engine.execute_trade(&GoldenAgent, user, oracle(START_PRICE, 1), size, 1).unwrap();
+    }
+    let mut price = START_PRICE;
+    for slot in 2..12 {
+        price = price * 988 / 1_000;
+        engine.crank(0, slot, price, None).unwrap();
+    }
+    assert!(engine.risk_engine().lifetime_liquidations >= 30);
+    engine
+}
+
+/// Runs a scenario to its final state
+type Scenario = fn() -> Box<ClawcolatorEngine>;
+
+const SCENARIOS: [(&str, Scenario); 4] = [
+    ("bull_run", bull_run),
+    ("crash", crash),
+    ("oracle_glitch", oracle_glitch),
+    ("mass_liquidation", mass_liquidation),
+];
+
+// ==============================================================================
+// GOLDEN FILE
+// ==============================================================================
+
+/// `name hash` lines; blank lines and `#` comments are skipped
+fn read_golden() -> Vec<(String, String)> {
+    let text = std::fs::read_to_string(GOLDEN_PATH).unwrap_or_default();
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, hash) = line.split_once(' ').expect("golden line is `name hash`");
+            (name.to_string(), hash.trim().to_string())
+        })
+        .collect()
+}
+
+fn write_golden(hashes: &[(&str, String)]) {
+    let mut text = format!(
+        "# Final engine state hashes of tests/scenarios.rs\n\
+         # Regenerate with: {}=1 cargo test --features test,clawcolator --test scenarios\n",
+        REGENERATE_VAR
+    );
+    for (name, hash) in hashes {
+        let _ = writeln!(text, "{} {}", name, hash);
+    }
+    std::fs::write(GOLDEN_PATH, text).unwrap();
+}
+
+#[test]
+fn golden_scenarios() {
+    let hashes: Vec<(&str, String)> = SCENARIOS
+        .iter()
+        .map(|(name, run)| (*name, hex(&state_hash(&run()))))
+        .collect();
+    if std::env::var_os(REGENERATE_VAR).is_some() {
+        write_golden(&hashes);
+        return;
+    }
+
+    let golden = read_golden();
+    let mismatches: Vec<String> = hashes
+        .iter()
+        .filter_map(|(name, hash)| {
+            let expected = golden.iter().find(|(n, _)| n == name).map(|(_, h)| h.as_str());
+            (expected != Some(hash.as_str()))
+                .then(|| format!("{}: expected {}, got {}", name, expected.unwrap_or("nothing"), hash))
+        })
+        .collect();
+    let stale: Vec<&str> = golden
+        .iter()
+        .filter(|(name, _)| !SCENARIOS.iter().any(|(n, _)| n == name))
+        .map(|(name, _)| name.as_str())
+        .collect();
+    assert!(
+        mismatches.is_empty() && stale.is_empty(),
+        "scenario state changed:\n{}\nunknown golden entries: {:?}\n\
+         if intended, rerun with {}=1 and commit tests/scenarios.golden",
+        mismatches.join("\n"),
+        stale,
+        REGENERATE_VAR
+    );
+}
+
+#[test]
+fn golden_scenarios_are_deterministic() {
+    for (name, run) in SCENARIOS {
+        assert_eq!(state_hash(&run()), state_hash(&run()), "{} differs between runs", name);
+    }
+}
+
+#[test]
+fn state_hash_sees_every_account() {
+    let mut engine = funded(2);
+    let before = state_hash(&engine);
+    engine.risk_engine_mut().deposit(2, 1, 0).unwrap();
+    assert_ne!(state_hash(&engine), before);
+}
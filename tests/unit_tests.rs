@@ -2120,6 +2120,38 @@ fn test_partial_liquidation_fee_charged() {
     assert!(fee_received > 0, "Some fee should be charged");
 }
 
+/// Forced reduction works on healthy accounts and honors the dust threshold
+#[test]
+fn test_force_reduce_at_oracle_partial_and_dust() {
+    let mut engine = Box::new(RiskEngine::new(default_params()));
+    let user = engine.add_user(0).unwrap();
+
+    // Healthy account: 1_000_000 capital backing a 500_000 position
+    engine.accounts[user as usize].capital = U128::new(1_000_000);
+    engine.accounts[user as usize].position_size = I128::new(500_000);
+    engine.accounts[user as usize].entry_price = 1_000_000;
+    engine.total_open_interest = U128::new(500_000);
+    engine.vault = U128::new(1_000_000);
+    engine.recompute_aggregates();
+
+    // Partial: 500_000 -> 300_000, fee = 200_000 * 0.5% = 1_000
+    let closed = engine
+        .force_reduce_at_oracle(user, 200_000, 0, 1_000_000)
+        .unwrap();
+    assert_eq!(closed, 200_000);
+    assert_eq!(engine.accounts[user as usize].position_size.get(), 300_000);
+    assert_eq!(engine.insurance_fund.balance.get(), 1_000);
+    assert_eq!(engine.total_open_interest.get(), 300_000);
+
+    // Remainder 50_000 < min_liquidation_abs (100_000): full close
+    let closed = engine
+        .force_reduce_at_oracle(user, 250_000, 0, 1_000_000)
+        .unwrap();
+    assert_eq!(closed, 300_000);
+    assert!(engine.accounts[user as usize].position_size.is_zero());
+    assert_conserved(&engine);
+}
+
 /// Test 4: Compute liquidation close amount basic test
 #[test]
 fn test_compute_liquidation_close_amount_basic() {
@@ -4572,3 +4604,159 @@ fn test_rounding_bound_with_many_positive_pnl_accounts() {
         MAX_ROUNDING_SLACK
     );
 }
+
+#[test]
+fn test_dirty_tracking_settles_only_touched_and_stripe_accounts() {
+    let mut params = default_params();
+    params.maintenance_fee_per_slot = U128::new(1);
+    let mut engine = Box::new(RiskEngine::new(params));
+    let users: Vec<u16> = (0..SETTLE_STRIPES)
+        .map(|_| {
+            let user = engine.add_user(0).unwrap();
+            engine.deposit(user, 1_000_000, 0).unwrap();
+            user
+        })
+        .collect();
+    engine.set_dirty_tracking(true);
+
+    // First sweep settles everyone (enabling marks all dirty)
+    let outcome = engine.keeper_crank(u16::MAX, 10, 1_000_000, 0, false).unwrap();
+    assert!(outcome.sweep_complete);
+    assert_eq!(outcome.accounts_settled, SETTLE_STRIPES);
+    assert!(users.iter().all(|&u| !engine.is_dirty(u as usize)));
+
+    // Next sweep: one touched account plus the one in the rotating stripe
+    engine.deposit(users[5], 10, 20).unwrap();
+    assert!(engine.is_dirty(users[5] as usize));
+    let stripe = engine.settle_stripe;
+    let outcome = engine.keeper_crank(u16::MAX, 30, 1_000_000, 0, false).unwrap();
+    assert_eq!(outcome.accounts_settled, 2);
+    let settled = |engine: &RiskEngine, u: u16| engine.accounts[u as usize].last_fee_slot == 30;
+    assert!(settled(&engine, users[5]));
+    assert!(settled(&engine, users[stripe as usize]));
+    let clean = users.iter().find(|&&u| u != users[5] && u != stripe).copied().unwrap();
+    assert_eq!(engine.accounts[clean as usize].last_fee_slot, 10);
+
+    // The clean account is charged the full interval once its stripe comes up
+    let capital = engine.accounts[clean as usize].capital.get();
+    let mut slot = 30;
+    while engine.accounts[clean as usize].last_fee_slot == 10 {
+        slot += 10;
+        engine.keeper_crank(u16::MAX, slot, 1_000_000, 0, false).unwrap();
+    }
+    assert_eq!(capital - engine.accounts[clean as usize].capital.get(), (slot - 10) as u128);
+    assert_conserved(&engine);
+}
+
+#[test]
+fn test_free_list_reuse_and_compaction() {
+    let mut engine = Box::new(RiskEngine::new(default_params()));
+    let lp = engine.add_lp([0; 32], [0; 32], 0).unwrap();
+    let users: Vec<u16> = (0..6).map(|_| engine.add_user(0).unwrap()).collect();
+    for &user in &users {
+        engine.deposit(user, 1_000 + user as u128, 0).unwrap();
+    }
+    let occupancy = engine.occupancy();
+    assert_eq!((occupancy.used, occupancy.high_water, occupancy.holes), (7, 7, 0));
+    assert_eq!(occupancy.available, MAX_ACCOUNTS as u16 - 7); // Slab smaller than max_accounts
+
+    // Closing accounts leaves holes; closed slots are reused
+    for &user in &users[..3] {
+        engine.close_account(user, 0, 1_000_000).unwrap();
+    }
+    assert_eq!(engine.occupancy().holes, 3);
+    let reused = engine.add_user(0).unwrap();
+    assert!(users[..3].contains(&reused));
+    engine.close_account(reused, 0, 1_000_000).unwrap();
+
+    // Compaction moves the highest accounts down and reports each move
+    let capital_before = engine.c_tot.get();
+    let mut moves = Vec::new();
+    let moved = engine.compact_accounts(u16::MAX, |from, to| moves.push((from, to)));
+    assert_eq!(moved, 3);
+    assert_eq!(moves, vec![(users[5], users[0]), (users[4], users[1]), (users[3], users[2])]);
+    let occupancy = engine.occupancy();
+    assert_eq!((occupancy.used, occupancy.high_water, occupancy.holes), (4, 4, 0));
+    assert_eq!(engine.accounts[users[0] as usize].capital.get(), 1_000 + users[5] as u128);
+    assert_eq!(engine.c_tot.get(), capital_before);
+    assert!(engine.accounts[lp as usize].is_lp());
+
+    // New accounts fill the lowest free slot
+    assert_eq!(engine.add_user(0).unwrap(), 4);
+    assert_conserved(&engine);
+}
+
+#[test]
+fn test_layout_reports_build_capacities() {
+    use percolator::layout::{capacity, LAYOUT};
+    assert_eq!(capacity(None, 4096), 4096);
+    assert_eq!(capacity(Some("1_024"), 4096), 1024);
+    assert_eq!(LAYOUT.max_accounts, MAX_ACCOUNTS);
+    assert_eq!(LAYOUT.risk_engine_bytes, core::mem::size_of::<RiskEngine>());
+    assert!(LAYOUT.risk_engine_bytes > MAX_ACCOUNTS * core::mem::size_of::<Account>());
+}
+
+#[test]
+fn test_funding_accrues_globally_and_settles_on_touch() {
+    let mut engine = Box::new(RiskEngine::new(default_params()));
+    let lp = engine.add_lp([1u8; 32], [2u8; 32], 0).unwrap();
+    let alice = engine.add_user(0).unwrap();
+    engine.deposit(lp, 10_000_000, 0).unwrap();
+    engine.deposit(alice, 200_000, 0).unwrap();
+    engine.execute_trade(&MATCHER, lp, alice, 0, 1_000_000, 1_000_000).unwrap();
+
+    // 1 bps/slot for 10 slots moves only the global index
+    engine.accrue_funding_with_rate(10, 1_000_000, 1).unwrap();
+    assert_eq!(engine.funding_index_qpb_e6.get(), 1_000);
+    assert_eq!(engine.funding_settled[alice as usize].get(), 0);
+    assert_eq!(engine.pending_funding(alice as usize), 1_000);
+    assert_eq!(engine.funding_paid(lp as usize), -1_000);
+
+    // The next touch charges it to PnL and moves it from pending to settled
+    let pnl = engine.accounts[alice as usize].pnl.get();
+    engine.touch_account(alice).unwrap();
+    assert_eq!(engine.accounts[alice as usize].pnl.get(), pnl - 1_000);
+    assert_eq!(engine.funding_settled[alice as usize].get(), 1_000);
+    assert_eq!(engine.pending_funding(alice as usize), 0);
+
+    engine.accrue_funding(20, 1_000_000).unwrap();
+    assert_eq!(engine.funding_paid(alice as usize), 2_000);
+    assert_eq!(RiskEngine::funding_payment(-3, 500_000), Some(-1));
+    assert_eq!(RiskEngine::funding_payment(3, 500_000), Some(2));
+}
+
+#[test]
+fn test_aggregates_maintained_incrementally() {
+    let mut engine = Box::new(RiskEngine::new(default_params()));
+    engine.insurance_fund.balance = U128::new(1_000_000);
+    let lp = engine.add_lp([1u8; 32], [2u8; 32], 0).unwrap();
+    let alice = engine.add_user(0).unwrap();
+    let bob = engine.add_user(0).unwrap();
+    engine.deposit(lp, 1_000_000, 0).unwrap();
+    engine.deposit(alice, 100_000, 0).unwrap();
+    engine.deposit(bob, 100_000, 0).unwrap();
+    assert_eq!(engine.active_capital.get(), 0);
+
+    engine.execute_trade(&MATCHER, lp, alice, 0, 1_000_000, 300_000).unwrap();
+    engine.execute_trade(&MATCHER, lp, bob, 0, 1_000_000, -100_000).unwrap();
+    assert_eq!(engine.check_aggregates(), Ok(()));
+    let totals = engine.aggregates();
+    assert_eq!((totals.long_open_interest, totals.short_open_interest), (300_000, 300_000));
+    assert_eq!(totals.total_open_interest, 600_000);
+    let capital = |engine: &RiskEngine, idx: u16| engine.accounts[idx as usize].capital.get();
+    assert_eq!(totals.active_capital, capital(&engine, lp) + capital(&engine, alice) + capital(&engine, bob));
+
+    // Bob goes flat: his capital leaves active capital
+    engine.execute_trade(&MATCHER, lp, bob, 0, 1_000_000, 100_000).unwrap();
+    engine.keeper_crank(u16::MAX, 1, 1_000_000, 0, false).unwrap();
+    assert_eq!(engine.check_aggregates(), Ok(()));
+    assert_eq!(engine.short_open_interest.get(), 300_000); // LP now carries Bob's side
+    let active = capital(&engine, lp) + capital(&engine, alice);
+    assert_eq!(engine.active_capital.get(), active);
+
+    // A direct write shows up in the checker until aggregates are recomputed
+    engine.accounts[bob as usize].capital = U128::new(1);
+    assert_eq!(engine.check_aggregates().map_err(|e| e.c_tot), Err(active + 1));
+    engine.recompute_aggregates();
+    assert_eq!(engine.check_aggregates(), Ok(()));
+}